@@ -0,0 +1,140 @@
+//! Split matching over a 1500-message window — the sample size
+//! `/api/split-counts` fetches — comparing one-off matching (a regex
+//! compiled per message per filter) with matchers precompiled once per
+//! config load.
+//!
+//! Run with `cargo bench --bench split_matching`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use supervillain::splits::{self, CompiledSplits};
+use supervillain::types::{
+    Email, EmailAddress, FilterType, MatchMode, SplitFilter, SplitInbox, SplitsConfig,
+};
+
+const WINDOW: usize = 1500;
+const ROUNDS: u32 = 20;
+
+fn address(email: String) -> EmailAddress {
+    EmailAddress { name: None, email }
+}
+
+fn email(i: usize) -> Email {
+    let domains = ["corp.com", "shop.example", "github.com", "news.example"];
+    let subjects = [
+        "Invoice #1042",
+        "Weekly digest",
+        "Re: lunch?",
+        "[repo] PR merged",
+    ];
+    Email {
+        id: format!("m{i}"),
+        blob_id: format!("b{i}"),
+        thread_id: format!("t{i}"),
+        mailbox_ids: HashMap::new(),
+        keywords: HashMap::new(),
+        received_at: chrono::Utc::now(),
+        subject: subjects[i % subjects.len()].into(),
+        from: vec![address(format!("Sender{i}@{}", domains[i % domains.len()]))],
+        to: vec![address("me@corp.com".into())],
+        cc: vec![],
+        preview: String::new(),
+        has_attachment: false,
+        size: 1000,
+        text_body: None,
+        html_body: None,
+        has_calendar: false,
+        attachments: vec![],
+        in_reply_to: None,
+        body_parts: Vec::new(),
+        list_unsubscribe: None,
+        message_id: None,
+        references: Vec::new(),
+        reply_to: Vec::new(),
+        spam: None,
+    }
+}
+
+fn filter(filter_type: FilterType, pattern: &str) -> SplitFilter {
+    SplitFilter {
+        filter_type,
+        pattern: pattern.into(),
+        name: None,
+        pattern_kind: None,
+        glob_match: None,
+        extra: Default::default(),
+    }
+}
+
+fn split(id: &str, filters: Vec<SplitFilter>) -> SplitInbox {
+    SplitInbox {
+        id: id.into(),
+        name: id.into(),
+        icon: None,
+        filters,
+        match_mode: MatchMode::Any,
+        account: None,
+        action: None,
+        extra: Default::default(),
+    }
+}
+
+fn config() -> SplitsConfig {
+    SplitsConfig {
+        splits: vec![
+            split("github", vec![filter(FilterType::From, "*@github.com")]),
+            split(
+                "bills",
+                vec![
+                    filter(FilterType::Subject, r"^invoice #\d+"),
+                    filter(FilterType::From, "*@shop.example"),
+                ],
+            ),
+            split(
+                "news",
+                vec![filter(FilterType::Subject, "digest|newsletter")],
+            ),
+            split("corp", vec![filter(FilterType::To, "*@corp.com")]),
+        ],
+        version: splits::CONFIG_VERSION,
+        extra: Default::default(),
+    }
+}
+
+fn time(label: &str, mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let per_round = start.elapsed() / ROUNDS;
+    println!("{label:<12} {per_round:>12.2?} per {WINDOW}-message window");
+    per_round
+}
+
+fn main() {
+    let config = config();
+    let emails: Vec<Email> = (0..WINDOW).map(email).collect();
+
+    let naive = time("one-off", || {
+        for s in &config.splits {
+            black_box(
+                emails
+                    .iter()
+                    .filter(|e| splits::matches_split(e, s))
+                    .count(),
+            );
+        }
+    });
+    let compiled = CompiledSplits::new(&config);
+    let precompiled = time("precompiled", || {
+        black_box(compiled.counts(&emails));
+    });
+
+    println!(
+        "speedup      {:>11.1}x",
+        naive.as_secs_f64() / precompiled.as_secs_f64()
+    );
+}
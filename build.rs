@@ -34,6 +34,24 @@ fn main() {
 
     println!("cargo:rustc-env=SUPERVILLAIN_BUILD_ID={build_id}");
 
+    // Build date for /api/version. SOURCE_DATE_EPOCH wins so reproducible
+    // builds embed the same date; the script only re-runs when git moves
+    // (below), so this is the date the current commit was first built.
+    let epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    println!(
+        "cargo:rustc-env=SUPERVILLAIN_BUILD_DATE={}",
+        civil_date(epoch / 86_400)
+    );
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
     // Re-run when HEAD moves to a new commit or a branch/ref is updated, so a
     // fresh commit always gets a fresh build id without a `cargo clean`. In a
     // worktree checkout `.git` is a file (not a directory) pointing at the
@@ -67,3 +85,18 @@ fn main() {
         git_dir.join("logs").join("HEAD").display()
     );
 }
+
+/// `YYYY-MM-DD` for a count of days since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`); build scripts can't pull in chrono.
+fn civil_date(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -35,6 +35,10 @@ pub enum AccountConfig {
         /// see `AccountConfig::signature()`.
         #[serde(default)]
         signature: Option<String>,
+        /// Per-account `proxy`, replacing the global one for this
+        /// account's session; see `http_client`. `none` goes direct.
+        #[serde(default)]
+        proxy: Option<String>,
     },
     Outlook {
         #[serde(rename = "client-id")]
@@ -43,6 +47,8 @@ pub enum AccountConfig {
         email: Option<String>,
         #[serde(default)]
         signature: Option<String>,
+        #[serde(default)]
+        proxy: Option<String>,
     },
     Gmail {
         #[serde(rename = "client-id")]
@@ -53,6 +59,8 @@ pub enum AccountConfig {
         email: Option<String>,
         #[serde(default)]
         signature: Option<String>,
+        #[serde(default)]
+        proxy: Option<String>,
     },
 }
 
@@ -93,6 +101,44 @@ impl AccountConfig {
             | Self::Gmail { signature, .. } => signature.as_deref().filter(|s| !s.is_empty()),
         }
     }
+
+    /// The account's own `proxy`, or `None` to use the global setting.
+    /// Empty reads as unset, like `signature()`.
+    pub fn proxy(&self) -> Option<&str> {
+        match self {
+            Self::Fastmail { proxy, .. }
+            | Self::Outlook { proxy, .. }
+            | Self::Gmail { proxy, .. } => proxy.as_deref().filter(|p| !p.is_empty()),
+        }
+    }
+
+    fn proxy_mut(&mut self) -> &mut Option<String> {
+        match self {
+            Self::Fastmail { proxy, .. }
+            | Self::Outlook { proxy, .. }
+            | Self::Gmail { proxy, .. } => proxy,
+        }
+    }
+
+    /// The HTTP client this account's session should use.
+    pub fn http_client(&self) -> reqwest::Client {
+        crate::http_client::client_via(self.proxy())
+    }
+}
+
+/// Per-account `startup-mailbox` / `startup-split`: what the UI opens when
+/// it selects the account, served by `GET /api/preferences`. The mailbox
+/// is an id, role or name; the split is a split id and implies the inbox.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StartupView {
+    pub mailbox: Option<String>,
+    pub split: Option<String>,
+}
+
+impl StartupView {
+    pub fn is_empty(&self) -> bool {
+        self.mailbox.is_none() && self.split.is_none()
+    }
 }
 
 /// The complete on-disk configuration: a default account selector plus a map
@@ -101,7 +147,23 @@ impl AccountConfig {
 #[derive(Clone, Debug, Default)]
 pub struct ConfigFile {
     pub default_account: Option<String>,
+    /// Global `default-reminder = 10m`: a VALARM injected into invites
+    /// auto-added to the calendar when the organizer sent none. Kept as
+    /// the raw string; `calendar::reminder_trigger` interprets it.
+    pub default_reminder: Option<String>,
+    /// Global `body-cache-mb = 128`: memory budget for cached email bodies
+    /// across all accounts (`prefetch::PrefetchCache`). Unset means
+    /// `prefetch::DEFAULT_BODY_CACHE_BYTES`.
+    pub body_cache_mb: Option<u64>,
+    /// Global `read-only = true`: every route that would change mail,
+    /// settings or accounts answers 403; see `read_only`.
+    pub read_only: bool,
+    /// Global `user-agent`, repeatable `http-header = Name: Value` and
+    /// `proxy` for every outbound request; see `http_client`.
+    pub http: crate::http_client::HttpConfig,
     pub accounts: BTreeMap<String, AccountConfig>,
+    /// Startup views by account name; accounts without one are absent.
+    pub startup: BTreeMap<String, StartupView>,
 }
 
 // =============================================================================
@@ -213,6 +275,10 @@ pub fn startup_config_errors(
 /// Pure parser; tested without filesystem.
 pub fn parse_config_str(content: &str) -> (ConfigFile, Vec<ConfigParseError>) {
     let mut default_account: Option<String> = None;
+    let mut default_reminder: Option<String> = None;
+    let mut body_cache_mb: Option<u64> = None;
+    let mut read_only = false;
+    let mut http = crate::http_client::HttpConfig::default();
     let mut current_section: Option<String> = None;
     let mut sections: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
     let mut errors: Vec<ConfigParseError> = Vec::new();
@@ -251,11 +317,29 @@ pub fn parse_config_str(content: &str) -> (ConfigFile, Vec<ConfigParseError>) {
         let key = key.trim().to_string();
         let value = value.trim().to_string();
         match &current_section {
-            None => {
-                if key == "default-account" {
-                    default_account = Some(value);
-                }
-            }
+            None => match key.as_str() {
+                "default-account" => default_account = Some(value),
+                "default-reminder" => default_reminder = Some(value),
+                "body-cache-mb" => match value.parse() {
+                    Ok(mb) => body_cache_mb = Some(mb),
+                    Err(_) => tracing::warn!("ignoring invalid body-cache-mb = {value}"),
+                },
+                "read-only" => match value.as_str() {
+                    "true" | "yes" | "on" | "1" => read_only = true,
+                    "false" | "no" | "off" | "0" => read_only = false,
+                    _ => tracing::warn!("ignoring invalid read-only = {value}"),
+                },
+                "user-agent" => http.user_agent = Some(value),
+                "http-header" => match crate::http_client::parse_header(&value) {
+                    Ok(header) => http.headers.push(header),
+                    Err(e) => tracing::warn!("ignoring http-header: {e}"),
+                },
+                "proxy" => match crate::http_client::parse_proxy(&value) {
+                    Ok(proxy) => http.proxy = Some(proxy),
+                    Err(e) => tracing::warn!("ignoring proxy: {e}"),
+                },
+                _ => {}
+            },
             Some(section) => {
                 sections
                     .entry(section.clone())
@@ -266,6 +350,7 @@ pub fn parse_config_str(content: &str) -> (ConfigFile, Vec<ConfigParseError>) {
     }
 
     let mut accounts = BTreeMap::new();
+    let mut startup = BTreeMap::new();
     for (name, props) in sections {
         let Some(provider) = props.get("provider").cloned() else {
             // Previously defaulted to "fastmail", which produced a misleading
@@ -282,6 +367,19 @@ pub fn parse_config_str(content: &str) -> (ConfigFile, Vec<ConfigParseError>) {
         };
         match account_from_props(&provider, &props) {
             Ok(acct) => {
+                let view = StartupView {
+                    mailbox: props
+                        .get("startup-mailbox")
+                        .cloned()
+                        .filter(|v| !v.is_empty()),
+                    split: props
+                        .get("startup-split")
+                        .cloned()
+                        .filter(|v| !v.is_empty()),
+                };
+                if !view.is_empty() {
+                    startup.insert(name.clone(), view);
+                }
                 accounts.insert(name, acct);
             }
             Err(reason) => {
@@ -309,6 +407,11 @@ pub fn parse_config_str(content: &str) -> (ConfigFile, Vec<ConfigParseError>) {
         ConfigFile {
             default_account,
             accounts,
+            default_reminder,
+            body_cache_mb,
+            read_only,
+            http,
+            startup,
         },
         errors,
     )
@@ -328,11 +431,18 @@ fn account_from_props(
     // normalizes an empty string to `None`, so a hand-edited `signature = `
     // (empty value) round-trips as "no signature" same as an omitted key.
     let signature = props.get("signature").map(|s| unescape_ini_multiline(s));
+    // A bad proxy skips the account rather than letting it connect around
+    // a proxy the user asked for.
+    let proxy = props
+        .get("proxy")
+        .map(|p| crate::http_client::parse_proxy(p).map_err(|e| format!("`proxy`: {e}")))
+        .transpose()?;
     match provider {
         "fastmail" => Ok(AccountConfig::Fastmail {
             username: require("username")?,
             api_token: require("api-token")?,
             signature,
+            proxy,
         }),
         "outlook" => Ok(AccountConfig::Outlook {
             client_id: require("client-id")?,
@@ -344,12 +454,14 @@ fn account_from_props(
                 .or_else(|| props.get("username"))
                 .cloned(),
             signature,
+            proxy,
         }),
         "gmail" => Ok(AccountConfig::Gmail {
             client_id: require("client-id")?,
             client_secret: require("client-secret")?,
             email: props.get("email").cloned(),
             signature,
+            proxy,
         }),
         other => Err(format!("unknown provider `{other}`")),
     }
@@ -363,7 +475,33 @@ fn account_from_props(
 pub fn serialize_config(cfg: &ConfigFile) -> String {
     let mut out = String::new();
     if let Some(ref d) = cfg.default_account {
-        out.push_str(&format!("default-account = {d}\n\n"));
+        out.push_str(&format!("default-account = {d}\n"));
+    }
+    if let Some(ref r) = cfg.default_reminder {
+        out.push_str(&format!("default-reminder = {r}\n"));
+    }
+    if let Some(mb) = cfg.body_cache_mb {
+        out.push_str(&format!("body-cache-mb = {mb}\n"));
+    }
+    if cfg.read_only {
+        out.push_str("read-only = true\n");
+    }
+    if let Some(ref ua) = cfg.http.user_agent {
+        out.push_str(&format!("user-agent = {ua}\n"));
+    }
+    for (name, value) in &cfg.http.headers {
+        out.push_str(&format!("http-header = {name}: {value}\n"));
+    }
+    if let Some(ref proxy) = cfg.http.proxy {
+        out.push_str(&format!("proxy = {proxy}\n"));
+    }
+    if cfg.default_account.is_some()
+        || cfg.default_reminder.is_some()
+        || cfg.body_cache_mb.is_some()
+        || cfg.read_only
+        || cfg.http != Default::default()
+    {
+        out.push('\n');
     }
     let mut first = true;
     for (name, acct) in &cfg.accounts {
@@ -375,6 +513,14 @@ pub fn serialize_config(cfg: &ConfigFile) -> String {
             out.push_str(&line);
             out.push('\n');
         }
+        if let Some(view) = cfg.startup.get(name) {
+            if let Some(mailbox) = &view.mailbox {
+                out.push_str(&format!("startup-mailbox = {mailbox}\n"));
+            }
+            if let Some(split) = &view.split {
+                out.push_str(&format!("startup-split = {split}\n"));
+            }
+        }
     }
     out
 }
@@ -417,6 +563,9 @@ fn account_to_ini_lines(name: &str, acct: &AccountConfig) -> Vec<String> {
     if let Some(sig) = acct.signature() {
         lines.push(format!("signature = {}", escape_ini_multiline(sig)));
     }
+    if let Some(proxy) = acct.proxy() {
+        lines.push(format!("proxy = {proxy}"));
+    }
     lines
 }
 
@@ -567,6 +716,7 @@ pub enum FieldId {
     ClientId,
     ClientSecret,
     Email,
+    Proxy,
 }
 
 #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
@@ -634,6 +784,25 @@ pub fn validate_section_name(s: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Separates a configured account from a shared JMAP account it can open,
+/// as in `?account=work#u2a4f`. [`validate_section_name`] refuses `#`, so
+/// such a key never collides with a configured account.
+pub const DELEGATED_SEPARATOR: char = '#';
+
+/// `work#u2a4f` → `("work", Some("u2a4f"))`; a plain name has no shared
+/// account.
+pub fn split_delegated(key: &str) -> (&str, Option<&str>) {
+    match key.split_once(DELEGATED_SEPARATOR) {
+        Some((account, shared)) if !shared.is_empty() => (account, Some(shared)),
+        _ => (key, None),
+    }
+}
+
+/// The key routes take for `shared_id` opened through `account`.
+pub fn delegated_key(account: &str, shared_id: &str) -> String {
+    format!("{account}{DELEGATED_SEPARATOR}{shared_id}")
+}
+
 /// Minimal email validator: a single `@`, non-empty local and domain parts,
 /// no whitespace. Strict enough to catch obvious typos at the boundary; not
 /// a full RFC validator (the provider does that authoritatively).
@@ -782,6 +951,11 @@ pub fn validate_account(cfg: &AccountConfig, name: &str) -> Result<(), Vec<Field
             }
         }
     }
+    if let Some(proxy) = cfg.proxy()
+        && let Err(e) = crate::http_client::parse_proxy(proxy)
+    {
+        errs.push(FieldError::new(FieldId::Proxy, e));
+    }
     if errs.is_empty() { Ok(()) } else { Err(errs) }
 }
 
@@ -873,7 +1047,12 @@ pub fn check_provider_change(
 /// When updating an existing account, an empty secret field means "keep
 /// existing value." UI doesn't echo secrets back; this lets the user save
 /// non-secret edits without retyping the api-token / client-secret.
-pub fn merge_secrets(existing: &AccountConfig, new: AccountConfig) -> AccountConfig {
+/// An omitted `proxy` is kept the same way; an empty one clears it.
+pub fn merge_secrets(existing: &AccountConfig, mut new: AccountConfig) -> AccountConfig {
+    let proxy = new.proxy_mut();
+    if proxy.is_none() {
+        *proxy = existing.proxy().map(String::from);
+    }
     match (existing, new) {
         (
             AccountConfig::Fastmail { api_token: old, .. },
@@ -881,6 +1060,7 @@ pub fn merge_secrets(existing: &AccountConfig, new: AccountConfig) -> AccountCon
                 username,
                 api_token: incoming,
                 signature,
+                proxy,
             },
         ) => AccountConfig::Fastmail {
             username,
@@ -890,6 +1070,7 @@ pub fn merge_secrets(existing: &AccountConfig, new: AccountConfig) -> AccountCon
                 incoming
             },
             signature,
+            proxy,
         },
         (
             AccountConfig::Gmail {
@@ -900,6 +1081,7 @@ pub fn merge_secrets(existing: &AccountConfig, new: AccountConfig) -> AccountCon
                 client_secret: incoming,
                 email,
                 signature,
+                proxy,
             },
         ) => AccountConfig::Gmail {
             client_id,
@@ -910,6 +1092,7 @@ pub fn merge_secrets(existing: &AccountConfig, new: AccountConfig) -> AccountCon
             },
             email,
             signature,
+            proxy,
         },
         (_, new) => new,
     }
@@ -962,6 +1145,7 @@ pub fn wire_account_list(
                 "authStatus": if session.is_some() { "ok" } else { "pending" },
                 "clientId": acct.oauth_client_id(),
                 "signature": acct.signature(),
+                "proxy": acct.proxy(),
             })
         })
         .collect()
@@ -1173,15 +1357,13 @@ async fn upsert_account(
             } => {
                 let mut sess =
                     crate::jmap::JmapSession::new(username, &format!("Bearer {api_token}"));
+                sess.client = cfg.http_client();
                 crate::jmap::connect(&mut sess)
                     .await
                     .map_err(|e| Error::BadRequest(format!("connection failed: {e}")))?;
+                sess.role_aliases = crate::mailbox_roles::load_aliases(&state.mailbox_roles_path);
                 if let Ok(mailboxes) = crate::jmap::get_mailboxes(&sess).await {
-                    for mb in &mailboxes {
-                        if let Some(ref role) = mb.role {
-                            sess.mailbox_cache.insert(role.clone(), mb.clone());
-                        }
-                    }
+                    crate::jmap::cache_mailboxes(&mut sess, &mailboxes);
                 }
                 new_session = Some(ProviderSession::Fastmail(Box::new(sess)));
             }
@@ -1191,6 +1373,10 @@ async fn upsert_account(
         }
     }
 
+    // An existing session whose proxy changed; its client is swapped once
+    // the registry lock is released, since a send may hold the session.
+    let mut retarget: Option<SessionLock> = None;
+
     // Take the lock, mutate, write config — all held under the write lock
     // (Carmack et al.: a millisecond of held-lock during disk write beats
     // the lost-update race where T2's snapshot overwrites T1's commit).
@@ -1204,6 +1390,8 @@ async fn upsert_account(
                 id.clone(),
                 SessionLock::new(tokio::sync::RwLock::new(session)),
             );
+        } else if reg.account_configs.get(&id).map(AccountConfig::proxy) != Some(cfg.proxy()) {
+            retarget = reg.sessions.get(&id).cloned();
         }
         reg.account_configs.insert(id.clone(), cfg.clone());
         promote_default_if_empty(&mut reg, &id);
@@ -1214,6 +1402,9 @@ async fn upsert_account(
         state.reset_config_error_baseline();
     }
 
+    if let Some(lock) = retarget {
+        lock.write().await.set_http_client(cfg.http_client());
+    }
     clear_setup_sentinel(&state).await;
     if needs_auth {
         push_error(
@@ -1396,6 +1587,7 @@ pub fn update_email_from_session(
             AccountConfig::Outlook {
                 client_id,
                 signature,
+                proxy,
                 ..
             },
             Some(email),
@@ -1403,12 +1595,14 @@ pub fn update_email_from_session(
             client_id,
             email: Some(email),
             signature,
+            proxy,
         },
         (
             AccountConfig::Gmail {
                 client_id,
                 client_secret,
                 signature,
+                proxy,
                 ..
             },
             Some(email),
@@ -1417,6 +1611,7 @@ pub fn update_email_from_session(
             client_secret,
             email: Some(email),
             signature,
+            proxy,
         },
         (other, _) => other,
     }
@@ -1436,9 +1631,10 @@ async fn run_authorize(
         }
         AccountConfig::Outlook { client_id, .. } => {
             let token_path = token_file_path(tokens_dir, id);
-            let session = crate::outlook::oauth_flow(client_id, &token_path)
+            let mut session = crate::outlook::oauth_flow(client_id, &token_path)
                 .await
                 .map_err(|e| e.to_string())?;
+            session.client = account.http_client();
             Ok(ProviderSession::Outlook(Box::new(session)))
         }
         AccountConfig::Gmail {
@@ -1446,10 +1642,11 @@ async fn run_authorize(
             client_secret,
             ..
         } => {
-            let session =
+            let mut session =
                 crate::gmail::oauth_flow(state.token_store.clone(), id, client_id, client_secret)
                     .await
                     .map_err(|e| e.to_string())?;
+            session.client = account.http_client();
             Ok(ProviderSession::Gmail(Box::new(session)))
         }
     }
@@ -1468,6 +1665,7 @@ mod tests {
             username: username.into(),
             api_token: token.into(),
             signature: None,
+            proxy: None,
         }
     }
     fn outlook(client_id: &str, email: Option<&str>) -> AccountConfig {
@@ -1475,6 +1673,7 @@ mod tests {
             client_id: client_id.into(),
             email: email.map(String::from),
             signature: None,
+            proxy: None,
         }
     }
     fn gmail(client_id: &str, secret: &str, email: Option<&str>) -> AccountConfig {
@@ -1483,6 +1682,7 @@ mod tests {
             client_secret: secret.into(),
             email: email.map(String::from),
             signature: None,
+            proxy: None,
         }
     }
 
@@ -1503,6 +1703,11 @@ mod tests {
         let cfg = ConfigFile {
             default_account: Some("fm".into()),
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let s = serialize_config(&cfg);
         let (parsed, errors) = parse_config_str(&s);
@@ -1544,6 +1749,160 @@ mod tests {
         }
     }
 
+    #[test]
+    fn default_reminder_round_trips() {
+        let (cfg, errors) = parse_config_str(
+            "default-account = fm\ndefault-reminder = 10m\n\n[fm]\nprovider = fastmail\nusername = a@b.c\napi-token = t\n",
+        );
+        assert!(errors.is_empty());
+        assert_eq!(cfg.default_reminder.as_deref(), Some("10m"));
+        let s = serialize_config(&cfg);
+        assert!(
+            s.starts_with("default-account = fm\ndefault-reminder = 10m\n\n[fm]"),
+            "{s}"
+        );
+        let (reparsed, _) = parse_config_str(&s);
+        assert_eq!(reparsed.default_reminder.as_deref(), Some("10m"));
+    }
+
+    #[test]
+    fn read_only_round_trips_and_defaults_off() {
+        let (cfg, errors) = parse_config_str("read-only = true\n");
+        assert!(errors.is_empty());
+        assert!(cfg.read_only);
+        let reparsed = parse_config_str(&serialize_config(&cfg)).0;
+        assert!(reparsed.read_only);
+        assert!(!parse_config_str("read-only = maybe\n").0.read_only);
+        assert!(!serialize_config(&ConfigFile::default()).contains("read-only"));
+    }
+
+    #[test]
+    fn body_cache_mb_round_trips_and_ignores_garbage() {
+        let (cfg, errors) = parse_config_str("body-cache-mb = 64\n");
+        assert!(errors.is_empty());
+        assert_eq!(cfg.body_cache_mb, Some(64));
+        let (reparsed, _) = parse_config_str(&serialize_config(&cfg));
+        assert_eq!(reparsed.body_cache_mb, Some(64));
+        let (cfg, _) = parse_config_str("body-cache-mb = lots\n");
+        assert_eq!(cfg.body_cache_mb, None);
+    }
+
+    #[test]
+    fn http_settings_round_trip_and_skip_bad_headers() {
+        let (cfg, errors) = parse_config_str(
+            "user-agent = supervillain (ops@example.com)\n\
+             http-header = X-Proxy: one\n\
+             http-header = not a header\n\
+             http-header = X-Team: mail\n",
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            cfg.http.user_agent.as_deref(),
+            Some("supervillain (ops@example.com)")
+        );
+        assert_eq!(
+            cfg.http.headers,
+            [
+                ("X-Proxy".to_string(), "one".to_string()),
+                ("X-Team".to_string(), "mail".to_string())
+            ]
+        );
+        let (reparsed, _) = parse_config_str(&serialize_config(&cfg));
+        assert_eq!(reparsed.http, cfg.http);
+    }
+
+    #[test]
+    fn proxy_settings_round_trip_globally_and_per_account() {
+        let (cfg, errors) = parse_config_str(
+            "proxy = socks5h://127.0.0.1:1080\n\
+             \n\
+             [fm]\n\
+             provider = fastmail\n\
+             username = u@fm.com\n\
+             api-token = tok\n\
+             proxy = none\n\
+             \n\
+             [gm]\n\
+             provider = gmail\n\
+             client-id = x.apps.googleusercontent.com\n\
+             client-secret = s\n\
+             proxy = gopher://old:70\n",
+        );
+        assert_eq!(cfg.http.proxy.as_deref(), Some("socks5h://127.0.0.1:1080"));
+        assert_eq!(
+            cfg.accounts["fm"].proxy(),
+            Some(crate::http_client::NO_PROXY)
+        );
+        // A proxy that can't be honored drops the account instead of
+        // letting it connect directly.
+        assert!(!cfg.accounts.contains_key("gm"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].reason.contains("proxy"), "{}", errors[0].reason);
+
+        let (reparsed, _) = parse_config_str(&serialize_config(&cfg));
+        assert_eq!(reparsed.http, cfg.http);
+        assert_eq!(reparsed.accounts, cfg.accounts);
+    }
+
+    #[test]
+    fn startup_view_round_trips_per_account() {
+        let (cfg, errors) = parse_config_str(
+            "default-account = work\n\
+             \n\
+             [work]\n\
+             provider = fastmail\n\
+             username = u@fm.com\n\
+             api-token = tok\n\
+             startup-split = clients\n\
+             \n\
+             [home]\n\
+             provider = fastmail\n\
+             username = h@fm.com\n\
+             api-token = tok\n\
+             startup-mailbox = \n",
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            cfg.startup["work"],
+            StartupView {
+                mailbox: None,
+                split: Some("clients".into()),
+            }
+        );
+        assert!(!cfg.startup.contains_key("home"));
+
+        let serialized = serialize_config(&cfg);
+        assert!(serialized.contains("startup-split = clients\n"));
+        let (reparsed, _) = parse_config_str(&serialized);
+        assert_eq!(reparsed.startup, cfg.startup);
+    }
+
+    #[test]
+    fn validate_account_rejects_bad_proxy() {
+        let mut acct = fastmail("u@fm.com", "tok");
+        if let AccountConfig::Fastmail { proxy, .. } = &mut acct {
+            *proxy = Some("proxy.corp:3128".into());
+        }
+        let errs = validate_account(&acct, "fm").unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].field, FieldId::Proxy);
+    }
+
+    #[test]
+    fn registry_snapshot_preserves_default_reminder() {
+        let reg = crate::types::AccountRegistry {
+            sessions: std::collections::HashMap::new(),
+            account_configs: BTreeMap::new(),
+            default_account: String::new(),
+            default_reminder: Some("1h".into()),
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
+        };
+        assert_eq!(reg.snapshot().default_reminder.as_deref(), Some("1h"));
+    }
+
     #[test]
     fn signature_round_trips_present_absent_and_multiline() {
         // Present (single line), absent, and multiline signatures must all
@@ -1557,6 +1916,7 @@ mod tests {
                 username: "alice@fm.com".into(),
                 api_token: "tok".into(),
                 signature: Some("Best,\nAlice\nAcme Inc.".into()),
+                proxy: None,
             },
         );
         accounts.insert("ms".to_string(), outlook("client-abc", None)); // absent
@@ -1567,11 +1927,17 @@ mod tests {
                 client_secret: "cs".into(),
                 email: Some("bob@gmail.com".into()),
                 signature: Some("Sent from my phone".into()),
+                proxy: None,
             },
         );
         let cfg = ConfigFile {
             default_account: Some("fm".into()),
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let s = serialize_config(&cfg);
         // The serialized INI must not contain a raw embedded newline inside
@@ -1646,11 +2012,17 @@ mod tests {
                     username: "u@fm.com".into(),
                     api_token: "tok".into(),
                     signature: Some(sig.into()),
+                    proxy: None,
                 },
             );
             let cfg = ConfigFile {
                 default_account: None,
                 accounts,
+                default_reminder: None,
+                body_cache_mb: None,
+                read_only: false,
+                http: Default::default(),
+                startup: Default::default(),
             };
             let (parsed, errors) = parse_config_str(&serialize_config(&cfg));
             assert!(errors.is_empty(), "no parse errors for {sig:?}: {errors:?}");
@@ -1699,11 +2071,17 @@ mod tests {
                     username: "u@fm.com".into(),
                     api_token: "tok".into(),
                     signature: Some(sig.into()),
+                    proxy: None,
                 },
             );
             let cfg = ConfigFile {
                 default_account: None,
                 accounts,
+                default_reminder: None,
+                body_cache_mb: None,
+                read_only: false,
+                http: Default::default(),
+                startup: Default::default(),
             };
             let (parsed, errors) = parse_config_str(&serialize_config(&cfg));
             assert!(errors.is_empty(), "no parse errors for {sig:?}: {errors:?}");
@@ -1723,6 +2101,7 @@ mod tests {
             username: "u@fm.com".into(),
             api_token: "tok".into(),
             signature: Some(String::new()),
+            proxy: None,
         };
         assert_eq!(acct.signature(), None);
         // ...and the INI writer must not emit an empty `signature =` line.
@@ -1740,6 +2119,11 @@ mod tests {
         let cfg = ConfigFile {
             default_account: Some("fm".into()),
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let s = serialize_config(&cfg);
         let default_pos = s.find("default-account").unwrap();
@@ -1756,6 +2140,11 @@ mod tests {
         let cfg = ConfigFile {
             default_account: None,
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let s = serialize_config(&cfg);
         let alpha_pos = s.find("[alpha]").unwrap();
@@ -1799,6 +2188,11 @@ api-token = tok
         let cfg = ConfigFile {
             default_account: Some("fm".into()),
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         atomic_write_config(&path, &cfg).unwrap();
         let meta = std::fs::metadata(&path).unwrap();
@@ -1815,6 +2209,11 @@ api-token = tok
         let cfg1 = ConfigFile {
             default_account: Some("fm".into()),
             accounts: accounts.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         atomic_write_config(&path, &cfg1).unwrap();
         let first = std::fs::read_to_string(&path).unwrap();
@@ -1825,6 +2224,11 @@ api-token = tok
         let cfg2 = ConfigFile {
             default_account: Some("fm".into()),
             accounts: accounts2,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         atomic_write_config(&path, &cfg2).unwrap();
         let second = std::fs::read_to_string(&path).unwrap();
@@ -1841,6 +2245,11 @@ api-token = tok
         let cfg = ConfigFile {
             default_account: None,
             accounts,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         atomic_write_config(&path, &cfg).unwrap();
         let entries: Vec<_> = std::fs::read_dir(dir.path())
@@ -1959,6 +2368,15 @@ api-token = tok
 
     // ---- Validators ----
 
+    #[test]
+    fn delegated_keys_split_back_into_account_and_shared_id() {
+        let key = delegated_key("work", "u2a4f");
+        assert_eq!(split_delegated(&key), ("work", Some("u2a4f")));
+        assert_eq!(split_delegated("work"), ("work", None));
+        assert_eq!(split_delegated("work#"), ("work#", None));
+        assert!(validate_section_name(&key).is_err());
+    }
+
     #[test]
     fn validate_section_name_rejects_brackets_newlines_and_empty() {
         assert!(validate_section_name("").is_err());
@@ -2169,6 +2587,7 @@ api-token = tok
                 username: "u@fm.com".into(),
                 api_token: "tok".into(),
                 signature: Some("Best,\nAlice".into()),
+                proxy: None,
             },
         );
         configs.insert(
@@ -2189,6 +2608,7 @@ api-token = tok
                 username: "u@fm.com".into(),
                 api_token: "tok".into(),
                 signature: Some(String::new()),
+                proxy: None,
             },
         );
         let list = wire_account_list(&configs, &live(&[]), "fm");
@@ -2219,6 +2639,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: accounts.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         assert!(stale_config_banner(Path::new("/x/config"), &disk, &[], &[], &accounts).is_none());
     }
@@ -2233,6 +2658,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: edited,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let banner = stale_config_banner(Path::new("/x/config"), &disk, &[], &[], &running)
             .expect("must fire");
@@ -2256,6 +2686,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: edited,
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         assert!(stale_config_banner(Path::new("/x/config"), &disk, &[], &[], &running).is_some());
     }
@@ -2270,6 +2705,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("something-else".into()),
             accounts: accounts.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         assert!(stale_config_banner(Path::new("/x/config"), &disk, &[], &[], &accounts).is_none());
     }
@@ -2284,6 +2724,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: running.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let new_err = vec![ConfigParseError {
             section: "typo".into(),
@@ -2305,6 +2750,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: running.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let startup_err = vec![ConfigParseError {
             section: "broken".into(),
@@ -2331,9 +2781,45 @@ api-token = tok
         let state = crate::types::AppState {
             accounts: tokio::sync::RwLock::new(empty_registry()),
             account_errors: tokio::sync::RwLock::new(Vec::new()),
-            splits_config_path: PathBuf::from("/x/splits.json"),
+            splits: crate::splits::SplitsStore::new(PathBuf::from("/x/splits.json"), None),
             timezone_config_path: PathBuf::from("/x/timezone.json"),
+            groups_config_path: PathBuf::from("/x/groups.json"),
+            identities_config_path: PathBuf::from("/x/identities.json"),
+            protected_senders_path: PathBuf::from("/x/protected-senders.json"),
+            image_senders_path: PathBuf::from("/x/image-senders.json"),
+            digest_config_path: PathBuf::from("/x/digest.json"),
+            receipts_path: PathBuf::from("/x/receipts.json"),
+            packages_config_path: PathBuf::from("/x/packages.json"),
+            packages_path: PathBuf::from("/x/packages-tracked.json"),
+            mailbox_roles_path: PathBuf::from("/x/mailbox-roles.json"),
+            recently_deleted_path: PathBuf::from("/x/recently-deleted.json"),
+            snoozed_path: PathBuf::from("/x/snoozed.json"),
+            snoozed_lock: Default::default(),
+            priority_lock: Default::default(),
+            newsletters_lock: Default::default(),
+            muted_lock: Default::default(),
+            empty_confirmations: Default::default(),
+            store: Arc::new(crate::storage::FileStore::new(PathBuf::from(
+                "/tmp/nonexistent-store",
+            ))),
+            perf: Default::default(),
+            update_check: Default::default(),
+            signature_blobs: Default::default(),
+            undo_send: Default::default(),
+            at_rest: Default::default(),
+            send_guard_path: PathBuf::from("/x/send-guard.json"),
+            keybindings_path: PathBuf::from("/x/keybindings.json"),
+            send_hours_path: PathBuf::from("/x/send-hours.json"),
+            compose_sessions_path: PathBuf::from("/x/compose-sessions.json"),
+            contacts_path: PathBuf::from("/x/contacts.json"),
+            contacts_lock: Default::default(),
+            compose_sessions: Default::default(),
+            blob_cache: Default::default(),
+            email_cache: Default::default(),
+            recently_deleted_lock: Default::default(),
             timezone_write_lock: tokio::sync::Mutex::new(()),
+            calendar_reply_log: Default::default(),
+            otp: Default::default(),
             config_path: PathBuf::from("/x/config"),
             tokens_dir: PathBuf::from("/x/tokens"),
             token_store: std::sync::Arc::new(crate::platform::FsTokenStore::new(PathBuf::from(
@@ -2399,6 +2885,11 @@ api-token = tok
         let disk = ConfigFile {
             default_account: Some("fm".into()),
             accounts: running.clone(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         let startup_err = vec![ConfigParseError {
             section: "broken".into(),
@@ -2487,6 +2978,19 @@ api-token = tok
         }
     }
 
+    #[test]
+    fn merge_keeps_proxy_unless_the_payload_sets_one() {
+        let mut existing = outlook("0e86662a-14b9-4e95-97d6-e91972f91d48", None);
+        *existing.proxy_mut() = Some("socks5h://127.0.0.1:1080".into());
+        let patch = outlook("0e86662a-14b9-4e95-97d6-e91972f91d48", None);
+        let merged = merge_secrets(&existing, patch.clone());
+        assert_eq!(merged.proxy(), Some("socks5h://127.0.0.1:1080"));
+
+        let mut cleared = patch;
+        *cleared.proxy_mut() = Some(String::new());
+        assert_eq!(merge_secrets(&existing, cleared).proxy(), None);
+    }
+
     /// `POST /api/accounts/{id}` on an already-configured account must
     /// persist a new signature both in the in-memory registry and on disk.
     /// Uses an existing account (update path) so the handler never tries a
@@ -2505,9 +3009,48 @@ api-token = tok
         let state = Arc::new(crate::types::AppState {
             accounts: tokio::sync::RwLock::new(reg),
             account_errors: tokio::sync::RwLock::new(Vec::new()),
-            splits_config_path: PathBuf::from("/tmp/nonexistent-splits.json"),
+            splits: crate::splits::SplitsStore::new(
+                PathBuf::from("/tmp/nonexistent-splits.json"),
+                None,
+            ),
             timezone_config_path: PathBuf::from("/tmp/nonexistent-timezone.json"),
+            groups_config_path: PathBuf::from("/tmp/nonexistent-groups.json"),
+            identities_config_path: PathBuf::from("/tmp/nonexistent-identities.json"),
+            protected_senders_path: PathBuf::from("/tmp/nonexistent-protected-senders.json"),
+            image_senders_path: PathBuf::from("/tmp/nonexistent-image-senders.json"),
+            digest_config_path: PathBuf::from("/tmp/nonexistent-digest.json"),
+            receipts_path: PathBuf::from("/tmp/nonexistent-receipts.json"),
+            packages_config_path: PathBuf::from("/tmp/nonexistent-packages.json"),
+            packages_path: PathBuf::from("/tmp/nonexistent-packages-tracked.json"),
+            mailbox_roles_path: PathBuf::from("/tmp/nonexistent-mailbox-roles.json"),
+            recently_deleted_path: PathBuf::from("/tmp/nonexistent-recently-deleted.json"),
+            snoozed_path: PathBuf::from("/tmp/nonexistent-snoozed.json"),
+            snoozed_lock: Default::default(),
+            priority_lock: Default::default(),
+            newsletters_lock: Default::default(),
+            muted_lock: Default::default(),
+            empty_confirmations: Default::default(),
+            store: Arc::new(crate::storage::FileStore::new(PathBuf::from(
+                "/tmp/nonexistent-store",
+            ))),
+            perf: Default::default(),
+            update_check: Default::default(),
+            signature_blobs: Default::default(),
+            undo_send: Default::default(),
+            at_rest: Default::default(),
+            send_guard_path: PathBuf::from("/tmp/nonexistent-send-guard.json"),
+            keybindings_path: PathBuf::from("/tmp/nonexistent-keybindings.json"),
+            send_hours_path: PathBuf::from("/tmp/nonexistent-send-hours.json"),
+            compose_sessions_path: PathBuf::from("/tmp/nonexistent-compose-sessions.json"),
+            contacts_path: PathBuf::from("/tmp/nonexistent-contacts.json"),
+            contacts_lock: Default::default(),
+            compose_sessions: Default::default(),
+            blob_cache: Default::default(),
+            email_cache: Default::default(),
+            recently_deleted_lock: Default::default(),
             timezone_write_lock: tokio::sync::Mutex::new(()),
+            calendar_reply_log: Default::default(),
+            otp: Default::default(),
             config_path: config_path.clone(),
             tokens_dir: tokens_dir.clone(),
             token_store: std::sync::Arc::new(crate::platform::FsTokenStore::new(tokens_dir)),
@@ -2522,6 +3065,7 @@ api-token = tok
             // Empty api-token: merge_secrets must preserve the existing one.
             api_token: String::new(),
             signature: Some("Cheers,\nBob".into()),
+            proxy: None,
         };
 
         let _ = upsert_account(State(state.clone()), AxumPath("fm".into()), Json(incoming))
@@ -2556,6 +3100,11 @@ api-token = tok
         let mut cfg = ConfigFile {
             default_account: Some("alpha".into()),
             accounts: BTreeMap::new(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         cfg.accounts
             .insert("alpha".into(), fastmail("a@a.com", "t"));
@@ -2572,6 +3121,11 @@ api-token = tok
         let mut cfg = ConfigFile {
             default_account: Some("only".into()),
             accounts: BTreeMap::new(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         cfg.accounts.insert("only".into(), fastmail("u@u.com", "t"));
         assert!(delete_and_pick_new_default(&mut cfg, "only"));
@@ -2583,6 +3137,11 @@ api-token = tok
         let mut cfg = ConfigFile {
             default_account: Some("alpha".into()),
             accounts: BTreeMap::new(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         cfg.accounts
             .insert("alpha".into(), fastmail("a@a.com", "t"));
@@ -2596,6 +3155,11 @@ api-token = tok
         let mut cfg = ConfigFile {
             default_account: None,
             accounts: BTreeMap::new(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         };
         cfg.accounts.insert("fm".into(), fastmail("u@fm.com", "t"));
         assert!(set_default_in_config(&mut cfg, "fm").is_ok());
@@ -2626,6 +3190,11 @@ api-token = tok
             sessions: std::collections::HashMap::new(),
             account_configs: BTreeMap::new(),
             default_account: String::new(),
+            default_reminder: None,
+            body_cache_mb: None,
+            read_only: false,
+            http: Default::default(),
+            startup: Default::default(),
         }
     }
 
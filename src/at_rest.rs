@@ -0,0 +1,326 @@
+//! Optional encryption of cached mail at rest.
+//!
+//! `SUPERVILLAIN_ENCRYPT_CACHE` names the accounts (comma-separated, or
+//! `all`) whose cached mail is sealed before it touches disk: rows in
+//! `email-cache.sqlite`, attachment blobs in `blob-cache/`, and that
+//! account's part of `prefetch-cache.json`. Each is sealed with
+//! XChaCha20-Poly1305 under a per-account key (HMAC-SHA256 of the master
+//! key and the account name), with the account name as associated data
+//! so a sealed row can't be replayed under another account.
+//!
+//! The master key is Argon2id over the cache passphrase:
+//! `SUPERVILLAIN_CACHE_PASSPHRASE` if set, else the OS keyring entry
+//! [`KEYRING_SERVICE`]. Its salt lives in `cache-key.json` next to a
+//! check value that tells a changed passphrase apart from corruption.
+//! With encryption asked for but no passphrase, those accounts' caches
+//! are not written at all rather than written in the clear.
+//!
+//! Data sealed under a key that no longer opens (passphrase changed,
+//! account renamed) and plaintext left over from before an account was
+//! selected both read as misses, so the caches refill sealed.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as B64;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// OS keyring service holding the cache passphrase.
+pub const KEYRING_SERVICE: &str = "supervillain-cache";
+
+/// Prefix of every sealed value; the format version is the last byte.
+const MAGIC: &[u8; 4] = b"SVE1";
+const NONCE_LEN: usize = 24;
+
+/// What the check value in `cache-key.json` seals.
+const CHECK_PLAINTEXT: &[u8] = b"supervillain cache key check";
+const CHECK_ACCOUNT: &str = "";
+
+/// Which accounts have their caches encrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    All,
+    Only(HashSet<String>),
+}
+
+impl Selection {
+    /// `SUPERVILLAIN_ENCRYPT_CACHE`: `all`, or comma-separated account
+    /// names. Blank means nothing is encrypted.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("all") {
+            return Some(Self::All);
+        }
+        let names: HashSet<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        (!names.is_empty()).then_some(Self::Only(names))
+    }
+
+    fn includes(&self, account: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(names) => names.contains(account),
+        }
+    }
+}
+
+/// On-disk shape of `cache-key.json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    salt: String,
+    check: String,
+}
+
+/// Sealing policy and key. `Default` encrypts nothing.
+#[derive(Default)]
+pub struct AtRest {
+    selection: Option<Selection>,
+    master: Option<[u8; 32]>,
+}
+
+impl std::fmt::Debug for AtRest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AtRest")
+            .field("selection", &self.selection)
+            .field("has_key", &self.master.is_some())
+            .finish()
+    }
+}
+
+impl AtRest {
+    /// Encrypt `selection`'s caches with a key derived from the
+    /// passphrase `passphrase` returns (only asked for when something is
+    /// selected) and the salt in `key_path`, created on first use.
+    pub fn configure(
+        selection: Option<Selection>,
+        passphrase: impl FnOnce() -> Option<String>,
+        key_path: &Path,
+    ) -> Self {
+        let Some(selection) = selection else {
+            return Self::default();
+        };
+        let master = match passphrase() {
+            Some(p) => match master_key(&p, key_path) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    tracing::warn!("cache encryption: {e}; encrypted caches are off");
+                    None
+                }
+            },
+            None => {
+                tracing::warn!(
+                    "cache encryption: no passphrase (SUPERVILLAIN_CACHE_PASSPHRASE or keyring \
+                     entry {KEYRING_SERVICE}); encrypted accounts won't be cached on disk"
+                );
+                None
+            }
+        };
+        Self {
+            selection: Some(selection),
+            master,
+        }
+    }
+
+    /// Whether `account`'s cached mail is sealed.
+    pub fn encrypts(&self, account: &str) -> bool {
+        self.selection.as_ref().is_some_and(|s| s.includes(account))
+    }
+
+    /// `plain` as it should be stored for `account`: sealed when the
+    /// account is selected, unchanged when not. None means don't store it
+    /// (selected, but there's no key).
+    pub fn seal<'a>(&self, account: &str, plain: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        if !self.encrypts(account) {
+            return Some(Cow::Borrowed(plain));
+        }
+        seal_with(self.master.as_ref()?, account, plain).map(Cow::Owned)
+    }
+
+    /// Undo [`seal`](Self::seal). None for anything that doesn't open
+    /// under `account`'s key, and for plaintext of a selected account.
+    pub fn open(&self, account: &str, stored: Vec<u8>) -> Option<Vec<u8>> {
+        if !stored.starts_with(MAGIC) {
+            return (!self.encrypts(account)).then_some(stored);
+        }
+        open_with(self.master.as_ref()?, account, &stored)
+    }
+}
+
+fn account_key(master: &[u8; 32], account: &str) -> XChaCha20Poly1305 {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master).expect("HMAC takes any key size");
+    mac.update(b"supervillain cache v1\0");
+    mac.update(account.as_bytes());
+    XChaCha20Poly1305::new(&mac.finalize().into_bytes())
+}
+
+fn seal_with(master: &[u8; 32], account: &str, plain: &[u8]) -> Option<Vec<u8>> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let sealed = account_key(master, account)
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plain,
+                aad: account.as_bytes(),
+            },
+        )
+        .ok()?;
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Some(out)
+}
+
+fn open_with(master: &[u8; 32], account: &str, stored: &[u8]) -> Option<Vec<u8>> {
+    let rest = stored.strip_prefix(MAGIC)?;
+    if rest.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    account_key(master, account)
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: sealed,
+                aad: account.as_bytes(),
+            },
+        )
+        .ok()
+}
+
+/// Argon2id of `passphrase` with the salt in `key_path`. A new salt is
+/// written on first use, and when the check value shows the passphrase
+/// changed (what the old one sealed is unreadable either way).
+fn master_key(passphrase: &str, key_path: &Path) -> Result<[u8; 32], String> {
+    let existing: Option<KeyFile> = std::fs::read(key_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+    if let Some(file) = &existing
+        && let Ok(salt) = B64.decode(&file.salt)
+    {
+        let key = derive(passphrase, &salt)?;
+        let check = B64.decode(&file.check).unwrap_or_default();
+        if open_with(&key, CHECK_ACCOUNT, &check).as_deref() == Some(CHECK_PLAINTEXT) {
+            return Ok(key);
+        }
+        tracing::warn!(
+            "cache encryption: passphrase changed; previously cached mail will be refetched"
+        );
+    }
+    let salt: [u8; 16] = rand::random();
+    let key = derive(passphrase, &salt)?;
+    let check =
+        seal_with(&key, CHECK_ACCOUNT, CHECK_PLAINTEXT).ok_or("sealing the key check failed")?;
+    let file = KeyFile {
+        salt: B64.encode(salt),
+        check: B64.encode(check),
+    };
+    let json = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+    crate::accounts::atomic_write_bytes(key_path, &json, /* secret */ true)
+        .map_err(|e| format!("writing {}: {e}", key_path.display()))?;
+    Ok(key)
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("key derivation failed: {e}"))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured(dir: &Path, selection: &str, passphrase: &str) -> AtRest {
+        let passphrase = passphrase.to_string();
+        AtRest::configure(
+            Selection::parse(selection),
+            || Some(passphrase),
+            &dir.join("cache-key.json"),
+        )
+    }
+
+    #[test]
+    fn selection_parses_all_and_names() {
+        assert_eq!(Selection::parse(" ALL "), Some(Selection::All));
+        assert_eq!(
+            Selection::parse("work, home,"),
+            Some(Selection::Only(HashSet::from([
+                "work".to_string(),
+                "home".to_string()
+            ])))
+        );
+        assert_eq!(Selection::parse(" , "), None);
+    }
+
+    #[test]
+    fn seals_only_selected_accounts_and_binds_to_the_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let at_rest = configured(dir.path(), "work", "hunter2");
+
+        assert_eq!(at_rest.seal("home", b"plain").unwrap().as_ref(), b"plain");
+        let sealed = at_rest.seal("work", b"secret body").unwrap().into_owned();
+        assert!(sealed.starts_with(MAGIC));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(
+            at_rest.open("work", sealed.clone()).unwrap(),
+            b"secret body"
+        );
+        // Another account's key and associated data don't open it.
+        assert_eq!(at_rest.open("home", sealed), None);
+        // Plaintext from before the account was selected is a miss.
+        assert_eq!(at_rest.open("work", b"{}".to_vec()), None);
+        assert_eq!(at_rest.open("home", b"{}".to_vec()).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn key_survives_restart_and_a_new_passphrase_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let sealed = configured(dir.path(), "all", "one")
+            .seal("work", b"body")
+            .unwrap()
+            .into_owned();
+        let same = configured(dir.path(), "all", "one");
+        assert_eq!(same.open("work", sealed.clone()).unwrap(), b"body");
+        let changed = configured(dir.path(), "all", "two");
+        assert_eq!(changed.open("work", sealed), None);
+        let resealed = changed.seal("work", b"body").unwrap().into_owned();
+        assert_eq!(
+            configured(dir.path(), "all", "two")
+                .open("work", resealed)
+                .unwrap(),
+            b"body"
+        );
+    }
+
+    #[test]
+    fn selected_without_a_key_stores_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let at_rest = AtRest::configure(
+            Selection::parse("work"),
+            || None,
+            &dir.path().join("cache-key.json"),
+        );
+        assert!(at_rest.seal("work", b"body").is_none());
+        assert!(at_rest.seal("home", b"body").is_some());
+        assert!(!dir.path().join("cache-key.json").exists());
+    }
+
+    #[test]
+    fn default_passes_everything_through() {
+        let at_rest = AtRest::default();
+        assert!(!at_rest.encrypts("work"));
+        assert_eq!(at_rest.open("work", b"x".to_vec()).unwrap(), b"x");
+    }
+}
@@ -0,0 +1,262 @@
+//! Sender authentication and list identity from a message's headers.
+//!
+//! The receiving server records its SPF, DKIM, DMARC and ARC verdicts in
+//! an `Authentication-Results` header (RFC 8601). A sender can put any
+//! number of such headers in the message themselves, so only the topmost
+//! one — added last, by the server that delivered it to the mailbox — is
+//! read. [`analyze`] and [`list_id`] back the `authentication` and
+//! `listId` fields of `/api/emails/{id}/headers`.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::types::EmailHeader;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authentication {
+    /// The server that checked the message, e.g. `mx.google.com`. Outlook
+    /// leaves it out.
+    pub authserv_id: Option<String>,
+    /// One per check, in header order (a message with two DKIM signatures
+    /// gets two `dkim` entries).
+    pub results: Vec<MethodResult>,
+    /// The header value as received.
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodResult {
+    /// `spf`, `dkim`, `dmarc`, `arc`, … lowercased.
+    pub method: String,
+    /// `pass`, `fail`, `softfail`, `neutral`, `none`, … lowercased.
+    pub result: String,
+    pub reason: Option<String>,
+    /// What was checked, e.g. `header.d` → `example.com`,
+    /// `smtp.mailfrom` → `bounce@example.com`.
+    pub properties: BTreeMap<String, String>,
+}
+
+/// `List-Id: Project updates <updates.example.com>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListId {
+    pub id: String,
+    /// The phrase before the id, when there is one.
+    pub name: Option<String>,
+}
+
+/// The topmost `Authentication-Results` header, parsed. `None` when the
+/// message has none.
+pub fn analyze(headers: &[EmailHeader]) -> Option<Authentication> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case("Authentication-Results"))
+        .map(|h| parse_results(&h.value))
+}
+
+/// `authserv-id [version]; method=result reason="…" ptype.prop=value; …`.
+/// Comments are dropped; `none` (nothing was checked) gives no results.
+fn parse_results(value: &str) -> Authentication {
+    let cleaned = strip_comments(value);
+    let mut clauses = split_outside_quotes(&cleaned, ';').into_iter();
+    let mut auth = Authentication {
+        raw: value.trim().to_string(),
+        ..Default::default()
+    };
+    let mut pending = Vec::new();
+    if let Some(first) = clauses.next() {
+        // Outlook starts straight with `spf=…`, without an authserv-id.
+        match first.split_whitespace().next() {
+            Some(word) if !word.contains('=') => auth.authserv_id = Some(word.to_string()),
+            Some(_) => pending.push(first),
+            None => {}
+        }
+    }
+    pending.extend(clauses);
+    auth.results = pending.iter().filter_map(|c| parse_method(c)).collect();
+    auth
+}
+
+fn parse_method(clause: &str) -> Option<MethodResult> {
+    let mut words = split_outside_quotes(clause, ' ').into_iter();
+    let first = words.next()?;
+    let (method, result) = first.split_once('=')?;
+    // `dkim/1=pass`: the method version isn't interesting.
+    let method = method.split('/').next().unwrap_or(method).trim();
+    let mut out = MethodResult {
+        method: method.to_ascii_lowercase(),
+        result: result.trim().to_ascii_lowercase(),
+        ..Default::default()
+    };
+    for word in words {
+        let Some((key, value)) = word.split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches('"').to_string();
+        if key.eq_ignore_ascii_case("reason") {
+            out.reason = Some(value);
+        } else {
+            out.properties.insert(key.to_ascii_lowercase(), value);
+        }
+    }
+    Some(out)
+}
+
+/// The `List-Id` header: the id in angle brackets and the phrase before it.
+pub fn list_id(headers: &[EmailHeader]) -> Option<ListId> {
+    let value = headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case("List-Id"))?
+        .value
+        .trim();
+    let (name, id) = match (value.rfind('<'), value.rfind('>')) {
+        (Some(open), Some(close)) if open < close => {
+            (value[..open].trim(), value[open + 1..close].trim())
+        }
+        _ => ("", value),
+    };
+    if id.is_empty() {
+        return None;
+    }
+    let name = name.trim_matches('"').trim();
+    Some(ListId {
+        id: id.to_string(),
+        name: (!name.is_empty()).then(|| name.to_string()),
+    })
+}
+
+/// Drop parenthesized comments, leaving quoted strings alone.
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let (mut depth, mut quoted) = (0usize, false);
+    for c in text.chars() {
+        match c {
+            '"' if depth == 0 => {
+                quoted = !quoted;
+                out.push(c);
+            }
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Split on `sep` (or any whitespace when `sep` is a space) outside
+/// double quotes, dropping empty pieces.
+fn split_outside_quotes(text: &str, sep: char) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        let splits = if sep == ' ' {
+            c.is_whitespace()
+        } else {
+            c == sep
+        };
+        if c == '"' {
+            quoted = !quoted;
+            current.push(c);
+        } else if splits && !quoted {
+            pieces.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    pieces.push(current);
+    pieces
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> EmailHeader {
+        EmailHeader {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn topmost_results_header_is_parsed() {
+        let headers = vec![
+            header(
+                "Authentication-Results",
+                "mx.google.com; dkim=pass header.i=@example.com header.s=s1 \
+                 header.b=abc; spf=pass (google.com: domain of bounce@example.com \
+                 designates 203.0.113.5 as permitted sender) smtp.mailfrom=bounce@example.com; \
+                 dmarc=fail reason=\"policy; p=reject\" header.from=example.com; arc=none",
+            ),
+            // A sender-supplied header further down must not count.
+            header("Authentication-Results", "evil.example; spf=pass"),
+        ];
+        let auth = analyze(&headers).unwrap();
+        assert_eq!(auth.authserv_id.as_deref(), Some("mx.google.com"));
+        let methods: Vec<_> = auth
+            .results
+            .iter()
+            .map(|r| (r.method.as_str(), r.result.as_str()))
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                ("dkim", "pass"),
+                ("spf", "pass"),
+                ("dmarc", "fail"),
+                ("arc", "none")
+            ]
+        );
+        assert_eq!(auth.results[0].properties["header.s"], "s1");
+        assert_eq!(
+            auth.results[1].properties["smtp.mailfrom"],
+            "bounce@example.com"
+        );
+        assert_eq!(auth.results[2].reason.as_deref(), Some("policy; p=reject"));
+    }
+
+    #[test]
+    fn outlook_results_have_no_authserv_id() {
+        let headers = vec![header(
+            "Authentication-Results",
+            "spf=pass (sender IP is 203.0.113.5) smtp.mailfrom=example.com; \
+             dkim=pass (signature was verified) header.d=example.com;",
+        )];
+        let auth = analyze(&headers).unwrap();
+        assert_eq!(auth.authserv_id, None);
+        assert_eq!(auth.results.len(), 2);
+        assert_eq!(auth.results[1].properties["header.d"], "example.com");
+        assert!(analyze(&[header("Subject", "Hi")]).is_none());
+        assert!(
+            analyze(&[header("Authentication-Results", "mx.example; none")])
+                .unwrap()
+                .results
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn list_id_splits_name_and_id() {
+        let id = list_id(&[header(
+            "List-Id",
+            "\"Project updates\" <updates.example.com>",
+        )]);
+        assert_eq!(
+            id,
+            Some(ListId {
+                id: "updates.example.com".into(),
+                name: Some("Project updates".into()),
+            })
+        );
+        let bare = list_id(&[header("list-id", "<dev.lists.example>")]).unwrap();
+        assert_eq!((bare.id.as_str(), bare.name), ("dev.lists.example", None));
+        assert_eq!(list_id(&[header("List-Id", "<>")]), None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Disk cache for downloaded attachment blobs.
+//!
+//! Previewing the same attachment twice, or zipping a message whose
+//! attachments were just opened, used to fetch every blob from the
+//! provider again. Blobs are immutable per id, so the first download is
+//! kept under `blob-cache/` in the config dir, named by the SHA-256 of
+//! account and blob id, with a small JSON sidecar holding the content
+//! type and byte count. A read whose file size disagrees with the sidecar
+//! (a torn write, a truncated disk) is treated as a miss and removed.
+//!
+//! The cache is bounded by total blob bytes. Each hit bumps the blob's
+//! mtime, and a write evicts the least recently used blobs until the
+//! total fits again. Caching is best-effort throughout: any I/O failure
+//! is logged and the caller falls back to the provider.
+//!
+//! Blobs of accounts selected for encryption (`at_rest`) are stored
+//! sealed; the sidecar's byte count is then the sealed size.
+
+use crate::at_rest::AtRest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Default `SUPERVILLAIN_BLOB_CACHE_MB`.
+pub const DEFAULT_MAX_MB: u64 = 512;
+
+const BLOB_EXT: &str = "blob";
+const META_EXT: &str = "json";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlobMeta {
+    content_type: String,
+    size: u64,
+}
+
+/// A cache of at most `max_bytes` under `dir`. `max_bytes == 0` disables
+/// it, which is also the `Default`.
+#[derive(Debug, Default)]
+pub struct BlobCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Serializes write→evict so two inserts don't both count the
+    /// directory before either has trimmed it.
+    write_lock: std::sync::Mutex<()>,
+    at_rest: Arc<AtRest>,
+}
+
+impl BlobCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            write_lock: Default::default(),
+            at_rest: Arc::default(),
+        }
+    }
+
+    /// Seal blobs of the accounts `at_rest` selects.
+    pub fn with_at_rest(mut self, at_rest: Arc<AtRest>) -> Self {
+        self.at_rest = at_rest;
+        self
+    }
+
+    fn enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    fn paths(&self, account: &str, blob_id: &str) -> (PathBuf, PathBuf) {
+        let mut hasher = Sha256::new();
+        hasher.update(account.as_bytes());
+        hasher.update([0]);
+        hasher.update(blob_id.as_bytes());
+        let key: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        (
+            self.dir.join(format!("{key}.{BLOB_EXT}")),
+            self.dir.join(format!("{key}.{META_EXT}")),
+        )
+    }
+
+    /// `(content_type, bytes)` as [`put`](Self::put) stored them, or None
+    /// on a miss or a failed size check.
+    pub fn get(&self, account: &str, blob_id: &str) -> Option<(String, Vec<u8>)> {
+        if !self.enabled() {
+            return None;
+        }
+        let (blob_path, meta_path) = self.paths(account, blob_id);
+        let meta: BlobMeta = serde_json::from_slice(&std::fs::read(&meta_path).ok()?).ok()?;
+        let bytes = std::fs::read(&blob_path).ok()?;
+        if bytes.len() as u64 != meta.size {
+            tracing::warn!(
+                "blob cache: {} is {} bytes, expected {}; dropping it",
+                blob_path.display(),
+                bytes.len(),
+                meta.size
+            );
+            let _ = std::fs::remove_file(&blob_path);
+            let _ = std::fs::remove_file(&meta_path);
+            return None;
+        }
+        let bytes = self.at_rest.open(account, bytes)?;
+        // Recency for eviction. A failure only makes it an earlier victim.
+        if let Ok(f) = std::fs::File::options().write(true).open(&blob_path) {
+            let _ = f.set_modified(SystemTime::now());
+        }
+        Some((meta.content_type, bytes))
+    }
+
+    /// Store a downloaded blob, then evict down to the size limit. Blobs
+    /// larger than the whole cache are not stored.
+    pub fn put(&self, account: &str, blob_id: &str, content_type: &str, bytes: &[u8]) {
+        if !self.enabled() {
+            return;
+        }
+        let Some(bytes) = self.at_rest.seal(account, bytes) else {
+            return;
+        };
+        if bytes.len() as u64 > self.max_bytes {
+            return;
+        }
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (blob_path, meta_path) = self.paths(account, blob_id);
+        let meta = BlobMeta {
+            content_type: content_type.into(),
+            size: bytes.len() as u64,
+        };
+        // Blob first: a sidecar without its blob is a miss, never a lie.
+        let write = || -> std::io::Result<()> {
+            crate::accounts::atomic_write_bytes(&blob_path, &bytes, /* secret */ true)?;
+            let json = serde_json::to_vec(&meta)?;
+            crate::accounts::atomic_write_bytes(&meta_path, &json, /* secret */ true)
+        };
+        if let Err(e) = write() {
+            tracing::warn!("blob cache: failed to store {blob_id}: {e}");
+            return;
+        }
+        if let Err(e) = evict(&self.dir, self.max_bytes) {
+            tracing::warn!("blob cache: eviction failed: {e}");
+        }
+    }
+}
+
+/// Remove least-recently-used blobs (and their sidecars) from `dir`
+/// until their total size is at most `max_bytes`.
+fn evict(dir: &Path, max_bytes: u64) -> std::io::Result<()> {
+    let mut blobs: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(BLOB_EXT) {
+            continue;
+        }
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        blobs.push((used, meta.len(), path));
+    }
+    let mut total: u64 = blobs.iter().map(|(_, len, _)| len).sum();
+    blobs.sort_by_key(|(used, _, _)| *used);
+    for (_, len, path) in blobs {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_file(&path)?;
+        let _ = std::fs::remove_file(path.with_extension(META_EXT));
+        total -= len;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn cache(dir: &Path, max_bytes: u64) -> BlobCache {
+        BlobCache::new(dir.join("blob-cache"), max_bytes)
+    }
+
+    fn set_used(cache: &BlobCache, account: &str, blob_id: &str, secs_ago: u64) {
+        let (blob, _) = cache.paths(account, blob_id);
+        std::fs::File::options()
+            .write(true)
+            .open(blob)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+            .unwrap();
+    }
+
+    #[test]
+    fn put_then_get_roundtrips_per_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 1024);
+        cache.put("work", "B1", "application/pdf", b"%PDF-1.7");
+        assert_eq!(
+            cache.get("work", "B1"),
+            Some(("application/pdf".to_string(), b"%PDF-1.7".to_vec()))
+        );
+        assert_eq!(cache.get("home", "B1"), None);
+        assert_eq!(cache.get("work", "B2"), None);
+    }
+
+    #[test]
+    fn size_mismatch_is_a_miss_and_removes_the_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 1024);
+        cache.put("work", "B1", "image/png", b"0123456789");
+        let (blob, meta) = cache.paths("work", "B1");
+        std::fs::write(&blob, b"01234").unwrap();
+        assert_eq!(cache.get("work", "B1"), None);
+        assert!(!blob.exists() && !meta.exists());
+    }
+
+    #[test]
+    fn put_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 25);
+        cache.put("a", "old", "text/plain", &[b'o'; 10]);
+        cache.put("a", "hit", "text/plain", &[b'h'; 10]);
+        set_used(&cache, "a", "old", 60);
+        set_used(&cache, "a", "hit", 120);
+        // Reading "hit" makes it the most recent, leaving "old" to go.
+        assert!(cache.get("a", "hit").is_some());
+
+        cache.put("a", "new", "text/plain", &[b'n'; 10]);
+        assert!(cache.get("a", "old").is_none());
+        assert!(cache.get("a", "hit").is_some());
+        assert!(cache.get("a", "new").is_some());
+    }
+
+    #[test]
+    fn disabled_or_oversized_stores_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let off = cache(dir.path(), 0);
+        off.put("a", "B1", "text/plain", b"x");
+        assert_eq!(off.get("a", "B1"), None);
+
+        let small = cache(dir.path(), 4);
+        small.put("a", "B2", "text/plain", b"too big");
+        assert_eq!(small.get("a", "B2"), None);
+    }
+
+    #[test]
+    fn selected_accounts_are_sealed_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let at_rest = Arc::new(AtRest::configure(
+            crate::at_rest::Selection::parse("secret"),
+            || Some("pass".into()),
+            &dir.path().join("cache-key.json"),
+        ));
+        let sealed = cache(dir.path(), 1 << 20).with_at_rest(at_rest);
+        sealed.put("secret", "B1", "text/plain", b"payroll.csv contents");
+        let (blob_path, _) = sealed.paths("secret", "B1");
+        assert!(!std::fs::read(blob_path).unwrap().starts_with(b"payroll"));
+        assert_eq!(
+            sealed.get("secret", "B1").unwrap().1,
+            b"payroll.csv contents"
+        );
+        // A cache without the key can't read it back.
+        assert_eq!(cache(dir.path(), 1 << 20).get("secret", "B1"), None);
+    }
+}
@@ -0,0 +1,508 @@
+//! On-disk email cache (`email-cache.sqlite`).
+//!
+//! The in-memory body cache (`prefetch`) is bounded and only partly
+//! survives a restart, and the split-count sample refetched the same
+//! ~1500 messages' headers on every recompute. This cache keeps fetched
+//! emails in SQLite, keyed by account and email id, in two kinds:
+//!
+//! - [`Kind::Full`]: a full fetch with bodies, as served by
+//!   `/api/emails/{id}`. Keywords and mailboxes in it can go stale.
+//! - [`Kind::SplitFields`]: the `id/from/to/cc/subject` projection that
+//!   split counting reads. Those properties never change for an email
+//!   id, so these rows stay valid until the email is destroyed.
+//!
+//! [`EmailCache::sync`] replays `Email/changes` since the stored state:
+//! updated ids lose their full row, destroyed ids lose both. When the
+//! provider can't compute changes from that old a state (or has no
+//! changes API, like Outlook), full rows are dropped wholesale so nothing
+//! older than the last sync is served as current. Like `blob_cache`,
+//! every failure degrades to a miss and the caller fetches live.
+//!
+//! For accounts selected for encryption (`at_rest`), the `json` column
+//! holds a sealed blob instead of text.
+
+use crate::at_rest::AtRest;
+use crate::error::Error;
+use crate::provider::{self, ProviderSession};
+use crate::types::{Email, EmailChanges};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Full rows kept per account; the least recently stored go first.
+pub const MAX_FULL_PER_ACCOUNT: usize = 2000;
+
+/// `Email/changes` page size for [`EmailCache::sync`].
+const SYNC_PAGE: usize = 500;
+
+/// Pages replayed per sync before giving up and starting over: past this
+/// much churn, dropping full rows is cheaper than catching up.
+const SYNC_MAX_PAGES: usize = 10;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS emails (
+        account TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        id TEXT NOT NULL,
+        stored_at INTEGER NOT NULL,
+        json TEXT NOT NULL,
+        PRIMARY KEY (account, kind, id)
+    );
+    CREATE TABLE IF NOT EXISTS sync_state (
+        account TEXT PRIMARY KEY,
+        state TEXT NOT NULL
+    );
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Full,
+    SplitFields,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Full => "full",
+            Kind::SplitFields => "split",
+        }
+    }
+}
+
+/// Connection to the cache database; `Default` is a disabled cache that
+/// misses on every read and drops every write.
+#[derive(Default)]
+pub struct EmailCache {
+    conn: Option<Mutex<Connection>>,
+    at_rest: Arc<AtRest>,
+}
+
+impl EmailCache {
+    /// Open (creating if needed) the database at `path`. A file that
+    /// can't be opened disables the cache with a warning rather than
+    /// failing startup.
+    pub fn open(path: &Path) -> Self {
+        let opened = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|()| Connection::open(path).map_err(|e| e.to_string()))
+            .and_then(|conn| Self::init(conn).map_err(|e| e.to_string()));
+        match opened {
+            Ok(cache) => {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+                }
+                cache
+            }
+            Err(e) => {
+                tracing::warn!("email cache disabled: {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// An in-memory cache, for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::init(Connection::open_in_memory().expect("in-memory sqlite"))
+            .expect("email cache schema")
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Some(Mutex::new(conn)),
+            at_rest: Arc::default(),
+        })
+    }
+
+    /// Seal rows of the accounts `at_rest` selects.
+    pub fn with_at_rest(mut self, at_rest: Arc<AtRest>) -> Self {
+        self.at_rest = at_rest;
+        self
+    }
+
+    /// Run `f` against the connection, logging and swallowing errors.
+    fn with<T>(
+        &self,
+        what: &str,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> Option<T> {
+        let conn = self.conn.as_ref()?;
+        let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut conn)
+            .inspect_err(|e| tracing::warn!("email cache: {what} failed: {e}"))
+            .ok()
+    }
+
+    pub fn get(&self, account: &str, kind: Kind, id: &str) -> Option<Email> {
+        self.get_many(account, kind, std::slice::from_ref(&id.to_string()))
+            .remove(id)
+    }
+
+    /// The cached rows among `ids`; absent ids simply aren't in the map.
+    pub fn get_many(&self, account: &str, kind: Kind, ids: &[String]) -> HashMap<String, Email> {
+        self.with("read", |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT json FROM emails WHERE account = ?1 AND kind = ?2 AND id = ?3",
+            )?;
+            let mut out = HashMap::new();
+            for id in ids {
+                let stored: Option<Value> = stmt
+                    .query_row(params![account, kind.as_str(), id], |row| row.get(0))
+                    .optional()?;
+                let json = stored.and_then(|v| match v {
+                    Value::Text(t) => Some(t.into_bytes()),
+                    Value::Blob(b) => Some(b),
+                    _ => None,
+                });
+                if let Some(email) = json
+                    .and_then(|j| self.at_rest.open(account, j))
+                    .and_then(|j| serde_json::from_slice::<Email>(&j).ok())
+                {
+                    out.insert(id.clone(), email);
+                }
+            }
+            Ok(out)
+        })
+        .unwrap_or_default()
+    }
+
+    /// Store `emails` under their ids, then trim full rows to
+    /// [`MAX_FULL_PER_ACCOUNT`].
+    pub fn put_many(&self, account: &str, kind: Kind, emails: &[Email]) {
+        if emails.is_empty() {
+            return;
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+        self.with("write", |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT OR REPLACE INTO emails (account, kind, id, stored_at, json)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?;
+                for email in emails {
+                    let Ok(json) = serde_json::to_string(email) else {
+                        continue;
+                    };
+                    let stored = if self.at_rest.encrypts(account) {
+                        match self.at_rest.seal(account, json.as_bytes()) {
+                            Some(sealed) => Value::Blob(sealed.into_owned()),
+                            None => continue,
+                        }
+                    } else {
+                        Value::Text(json)
+                    };
+                    stmt.execute(params![account, kind.as_str(), email.id, now, stored])?;
+                }
+            }
+            if kind == Kind::Full {
+                tx.execute(
+                    "DELETE FROM emails WHERE account = ?1 AND kind = 'full' AND id NOT IN (
+                         SELECT id FROM emails WHERE account = ?1 AND kind = 'full'
+                         ORDER BY stored_at DESC LIMIT ?2)",
+                    params![account, MAX_FULL_PER_ACCOUNT as i64],
+                )?;
+            }
+            tx.commit()
+        });
+    }
+
+    pub fn put(&self, account: &str, kind: Kind, email: &Email) {
+        self.put_many(account, kind, std::slice::from_ref(email));
+    }
+
+    /// The `Email/changes` state the cache was last synced to.
+    pub fn state(&self, account: &str) -> Option<String> {
+        self.with("read state", |conn| {
+            conn.query_row(
+                "SELECT state FROM sync_state WHERE account = ?1",
+                params![account],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .flatten()
+    }
+
+    /// Apply one page of changes and record its `new_state`.
+    pub fn apply_changes(&self, account: &str, changes: &EmailChanges) {
+        self.with("apply changes", |conn| {
+            let tx = conn.transaction()?;
+            for id in &changes.updated {
+                tx.execute(
+                    "DELETE FROM emails WHERE account = ?1 AND kind = 'full' AND id = ?2",
+                    params![account, id],
+                )?;
+            }
+            for id in &changes.destroyed {
+                tx.execute(
+                    "DELETE FROM emails WHERE account = ?1 AND id = ?2",
+                    params![account, id],
+                )?;
+            }
+            set_state(&tx, account, &changes.new_state)?;
+            tx.commit()
+        });
+    }
+
+    /// Drop every full row for `account` and record `state` (if any) as
+    /// the new starting point. Split-field rows are immutable and stay.
+    pub fn reset_full(&self, account: &str, state: Option<&str>) {
+        self.with("reset", |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM emails WHERE account = ?1 AND kind = 'full'",
+                params![account],
+            )?;
+            match state {
+                Some(state) => set_state(&tx, account, state)?,
+                None => {
+                    tx.execute(
+                        "DELETE FROM sync_state WHERE account = ?1",
+                        params![account],
+                    )?;
+                }
+            }
+            tx.commit()
+        });
+    }
+
+    /// Remove every row for `account`, or for all accounts. Returns how
+    /// many emails were dropped.
+    pub fn clear(&self, account: Option<&str>) -> usize {
+        self.with("clear", |conn| {
+            let tx = conn.transaction()?;
+            let n = match account {
+                Some(a) => {
+                    tx.execute("DELETE FROM sync_state WHERE account = ?1", params![a])?;
+                    tx.execute("DELETE FROM emails WHERE account = ?1", params![a])?
+                }
+                None => {
+                    tx.execute("DELETE FROM sync_state", [])?;
+                    tx.execute("DELETE FROM emails", [])?
+                }
+            };
+            tx.commit()?;
+            Ok(n)
+        })
+        .unwrap_or(0)
+    }
+
+    /// Bring `account` up to date with the provider's `Email/changes`.
+    /// The first sync only records the current state: rows stored before
+    /// it were fetched after it, so there is nothing to replay.
+    pub async fn sync(&self, session: &ProviderSession, account: &str) -> Result<(), Error> {
+        if self.conn.is_none() {
+            return Ok(());
+        }
+        let Some(mut since) = self.state(account) else {
+            let current = provider::email_changes(session, None, SYNC_PAGE).await;
+            self.reset_full(account, current.ok().map(|c| c.new_state).as_deref());
+            return Ok(());
+        };
+        for _ in 0..SYNC_MAX_PAGES {
+            match provider::email_changes(session, Some(&since), SYNC_PAGE).await {
+                Ok(changes) => {
+                    self.apply_changes(account, &changes);
+                    if !changes.has_more_changes {
+                        return Ok(());
+                    }
+                    since = changes.new_state;
+                }
+                // State too old, or no changes API: start over.
+                Err(Error::Conflict(_) | Error::BadRequest(_)) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let current = provider::email_changes(session, None, SYNC_PAGE).await;
+        self.reset_full(account, current.ok().map(|c| c.new_state).as_deref());
+        Ok(())
+    }
+}
+
+fn set_state(tx: &rusqlite::Transaction, account: &str, state: &str) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO sync_state (account, state) VALUES (?1, ?2)",
+        params![account, state],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str, subject: &str) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "blob_id": format!("B{id}"),
+            "thread_id": "T1",
+            "mailbox_ids": {},
+            "keywords": {},
+            "received_at": "2026-01-14T10:00:00Z",
+            "subject": subject,
+            "from": [],
+            "to": [],
+            "cc": [],
+            "preview": "",
+            "has_attachment": false,
+            "size": 0,
+            "text_body": "hello",
+            "html_body": null,
+            "has_calendar": false,
+            "attachments": [],
+        }))
+        .unwrap()
+    }
+
+    fn changes(updated: &[&str], destroyed: &[&str], new_state: &str) -> EmailChanges {
+        EmailChanges {
+            new_state: new_state.into(),
+            updated: updated.iter().map(|s| s.to_string()).collect(),
+            destroyed: destroyed.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn put_then_get_is_scoped_by_account_and_kind() {
+        let cache = EmailCache::in_memory();
+        cache.put("work", Kind::Full, &email("m1", "Hi"));
+        assert_eq!(cache.get("work", Kind::Full, "m1").unwrap().subject, "Hi");
+        assert!(cache.get("work", Kind::SplitFields, "m1").is_none());
+        assert!(cache.get("home", Kind::Full, "m1").is_none());
+
+        cache.put_many(
+            "work",
+            Kind::SplitFields,
+            &[email("m1", "Hi"), email("m2", "Yo")],
+        );
+        let ids = ["m1", "m2", "m3"].map(String::from);
+        let found = cache.get_many("work", Kind::SplitFields, &ids);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found["m2"].subject, "Yo");
+    }
+
+    #[test]
+    fn changes_drop_updated_full_rows_and_destroyed_rows() {
+        let cache = EmailCache::in_memory();
+        for id in ["m1", "m2"] {
+            cache.put("a", Kind::Full, &email(id, "s"));
+            cache.put("a", Kind::SplitFields, &email(id, "s"));
+        }
+        cache.apply_changes("a", &changes(&["m1"], &["m2"], "S2"));
+        assert!(cache.get("a", Kind::Full, "m1").is_none());
+        // From/subject can't change, so the split row outlives an update.
+        assert!(cache.get("a", Kind::SplitFields, "m1").is_some());
+        assert!(cache.get("a", Kind::Full, "m2").is_none());
+        assert!(cache.get("a", Kind::SplitFields, "m2").is_none());
+        assert_eq!(cache.state("a").as_deref(), Some("S2"));
+    }
+
+    #[test]
+    fn reset_full_keeps_split_rows() {
+        let cache = EmailCache::in_memory();
+        cache.put("a", Kind::Full, &email("m1", "s"));
+        cache.put("a", Kind::SplitFields, &email("m1", "s"));
+        cache.reset_full("a", Some("S9"));
+        assert!(cache.get("a", Kind::Full, "m1").is_none());
+        assert!(cache.get("a", Kind::SplitFields, "m1").is_some());
+        assert_eq!(cache.state("a").as_deref(), Some("S9"));
+        cache.reset_full("a", None);
+        assert_eq!(cache.state("a"), None);
+    }
+
+    #[test]
+    fn full_rows_are_capped_per_account() {
+        let cache = EmailCache::in_memory();
+        let batch: Vec<Email> = (0..MAX_FULL_PER_ACCOUNT + 5)
+            .map(|i| email(&format!("m{i}"), "s"))
+            .collect();
+        cache.put_many("a", Kind::Full, &batch);
+        cache.put("b", Kind::Full, &email("m0", "s"));
+        let count = |account: &str| {
+            cache
+                .with("count", |conn| {
+                    conn.query_row(
+                        "SELECT COUNT(*) FROM emails WHERE account = ?1",
+                        params![account],
+                        |row| row.get::<_, i64>(0),
+                    )
+                })
+                .unwrap()
+        };
+        assert_eq!(count("a"), MAX_FULL_PER_ACCOUNT as i64);
+        assert_eq!(count("b"), 1);
+    }
+
+    #[test]
+    fn clear_one_account_or_all() {
+        let cache = EmailCache::in_memory();
+        cache.put("a", Kind::Full, &email("m1", "s"));
+        cache.put("b", Kind::Full, &email("m1", "s"));
+        cache.apply_changes("a", &changes(&[], &[], "S1"));
+        assert_eq!(cache.clear(Some("a")), 1);
+        assert_eq!(cache.state("a"), None);
+        assert!(cache.get("b", Kind::Full, "m1").is_some());
+        assert_eq!(cache.clear(None), 1);
+    }
+
+    #[test]
+    fn open_persists_across_connections_and_default_is_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("email-cache.sqlite");
+        EmailCache::open(&path).put("a", Kind::Full, &email("m1", "Kept"));
+        assert_eq!(
+            EmailCache::open(&path)
+                .get("a", Kind::Full, "m1")
+                .unwrap()
+                .subject,
+            "Kept"
+        );
+
+        let off = EmailCache::default();
+        off.put("a", Kind::Full, &email("m1", "s"));
+        assert!(off.get("a", Kind::Full, "m1").is_none());
+        assert_eq!(off.clear(None), 0);
+    }
+
+    #[test]
+    fn selected_accounts_are_sealed_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("email-cache.sqlite");
+        let at_rest = || {
+            Arc::new(AtRest::configure(
+                crate::at_rest::Selection::parse("secret"),
+                || Some("pass".into()),
+                &dir.path().join("cache-key.json"),
+            ))
+        };
+        let cache = EmailCache::open(&path).with_at_rest(at_rest());
+        cache.put("secret", Kind::Full, &email("m1", "Quarterly numbers"));
+        cache.put("plain", Kind::Full, &email("m1", "Lunch"));
+        drop(cache);
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(9).any(|w| w == b"Quarterly"));
+        assert!(raw.windows(5).any(|w| w == b"Lunch"));
+
+        let reopened = EmailCache::open(&path).with_at_rest(at_rest());
+        assert_eq!(
+            reopened.get("secret", Kind::Full, "m1").unwrap().subject,
+            "Quarterly numbers"
+        );
+        // Without the key the sealed row is a miss, not garbage.
+        assert!(
+            EmailCache::open(&path)
+                .get("secret", Kind::Full, "m1")
+                .is_none()
+        );
+    }
+}
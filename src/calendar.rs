@@ -1,9 +1,10 @@
-use crate::types::{Attendee, CalendarEvent, RsvpStatus};
+use crate::types::{Alarm, AppliedReply, Attendee, CalendarEvent, RsvpStatus};
 use chrono::{
     DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
 };
 use chrono_tz::Tz;
 use regex::Regex;
+use serde::Serialize;
 use std::collections::{BTreeSet, HashMap};
 use std::str::FromStr;
 use std::sync::LazyLock;
@@ -89,6 +90,20 @@ static TAG_NAME_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^</?([a-zA-Z
 // ICS Parsing (hand-rolled)
 // =============================================================================
 
+/// Choose the invite among a message's calendar parts (a forward can carry
+/// the original invite next to an exported copy). The first part with both
+/// a METHOD and a VEVENT is the actionable iTIP message; failing that, the
+/// first with a VEVENT, then simply the first.
+pub fn pick_invite_ics(candidates: Vec<String>) -> Option<String> {
+    let has_event = |ics: &str| ics.contains("BEGIN:VEVENT");
+    let position = candidates
+        .iter()
+        .position(|ics| has_event(ics) && extract_property(ics, "METHOD").is_some())
+        .or_else(|| candidates.iter().position(|ics| has_event(ics)))
+        .unwrap_or(0);
+    candidates.into_iter().nth(position)
+}
+
 pub fn parse_ics(data: &str) -> Option<CalendarEvent> {
     let data = data.trim();
     if !data.contains("BEGIN:VCALENDAR") {
@@ -107,6 +122,13 @@ pub fn parse_ics(data: &str) -> Option<CalendarEvent> {
     // Unfold lines (RFC 5545: continuation lines start with space or tab)
     let unfolded = unfold_lines(vevent);
 
+    // VALARM sub-components carry their own DESCRIPTION/SUMMARY/ATTENDEE
+    // lines. Collect them, then drop them before the event-level property
+    // scan so an alarm's "DESCRIPTION:Reminder" can't stand in for a
+    // missing event DESCRIPTION.
+    let alarms = parse_alarms(&unfolded);
+    let unfolded = strip_alarms(&unfolded);
+
     // Extract VTIMEZONE UTC offsets from the full calendar data so we can
     // resolve TZID references on DTSTART/DTEND inside the VEVENT.
     let tz_offsets = parse_vtimezone_offsets(data);
@@ -120,6 +142,7 @@ pub fn parse_ics(data: &str) -> Option<CalendarEvent> {
         .unwrap_or(0);
 
     let dtstart = parse_ics_datetime_property(&unfolded, "DTSTART", &tz_offsets)?;
+    let all_day = property_is_date_only(&unfolded, "DTSTART");
     let dtend = parse_ics_datetime_property(&unfolded, "DTEND", &tz_offsets);
 
     let status = extract_property(&unfolded, "STATUS");
@@ -151,9 +174,99 @@ pub fn parse_ics(data: &str) -> Option<CalendarEvent> {
         raw_ics: data.to_string(),
         user_rsvp_status: None,
         is_update: false,
+        changes: Vec::new(),
+        organizer_mismatch: None,
+        my_response: None,
+        alarms,
+        all_day,
     })
 }
 
+// =============================================================================
+// VALARM reminders
+// =============================================================================
+
+/// Every `BEGIN:VALARM`…`END:VALARM` block in an (unfolded) VEVENT.
+/// Alarms without a TRIGGER are dropped — RFC 5545 requires one, and an
+/// alarm that never fires isn't worth surfacing.
+fn parse_alarms(vevent: &str) -> Vec<Alarm> {
+    let mut alarms = Vec::new();
+    let mut rest = vevent;
+    while let Some(start) = rest.find("BEGIN:VALARM") {
+        let Some(len) = rest[start..].find("END:VALARM") else {
+            break;
+        };
+        let block = &rest[start..start + len];
+        if let Some(trigger) = extract_property(block, "TRIGGER") {
+            alarms.push(Alarm {
+                action: extract_property(block, "ACTION").unwrap_or_else(|| "DISPLAY".into()),
+                trigger,
+                description: extract_property(block, "DESCRIPTION"),
+            });
+        }
+        rest = &rest[start + len + "END:VALARM".len()..];
+    }
+    alarms
+}
+
+/// `vevent` with every VALARM block (including its END line) removed.
+fn strip_alarms(vevent: &str) -> String {
+    let mut out = String::with_capacity(vevent.len());
+    let mut rest = vevent;
+    while let Some(start) = rest.find("BEGIN:VALARM") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find("END:VALARM") else {
+            rest = "";
+            break;
+        };
+        rest = rest[start + len + "END:VALARM".len()..].trim_start_matches(['\r', '\n']);
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Convert a `default-reminder` setting (`10m`, `1h`, `2d`, `1w`) into an
+/// RFC 5545 TRIGGER duration before DTSTART (`-PT10M`, `-PT1H`, `-P2D`,
+/// `-P1W`). `None` for anything else, including a bare number — the unit
+/// is required so `default-reminder = 1` can't silently mean a minute.
+pub fn reminder_trigger(setting: &str) -> Option<String> {
+    let setting = setting.trim();
+    let unit = setting.chars().last()?;
+    let num: u32 = setting[..setting.len() - unit.len_utf8()].parse().ok()?;
+    match unit.to_ascii_lowercase() {
+        'm' => Some(format!("-PT{num}M")),
+        'h' => Some(format!("-PT{num}H")),
+        'd' => Some(format!("-P{num}D")),
+        'w' => Some(format!("-P{num}W")),
+        _ => None,
+    }
+}
+
+/// Add a DISPLAY alarm with `trigger` to the first VEVENT, unless the event
+/// already carries a VALARM (the organizer's reminder wins). Line endings
+/// follow the input: CRLF if the data uses CRLF, bare LF otherwise.
+pub fn inject_default_alarm(ics: &str, trigger: &str) -> String {
+    let Some(vevent_start) = ics.find("BEGIN:VEVENT") else {
+        return ics.to_string();
+    };
+    let Some(end_rel) = ics[vevent_start..].find("END:VEVENT") else {
+        return ics.to_string();
+    };
+    let end = vevent_start + end_rel;
+    if ics[vevent_start..end].contains("BEGIN:VALARM") {
+        return ics.to_string();
+    }
+    let nl = if ics.contains("\r\n") { "\r\n" } else { "\n" };
+    let alarm = format!(
+        "BEGIN:VALARM{nl}ACTION:DISPLAY{nl}DESCRIPTION:Reminder{nl}TRIGGER:{trigger}{nl}END:VALARM{nl}"
+    );
+    let mut out = String::with_capacity(ics.len() + alarm.len());
+    out.push_str(&ics[..end]);
+    out.push_str(&alarm);
+    out.push_str(&ics[end..]);
+    out
+}
+
 // =============================================================================
 // Invite update decision (RFC 5546 SEQUENCE semantics + anti-spoof)
 // =============================================================================
@@ -249,6 +362,104 @@ pub fn events_content_match(stored: &CalendarEvent, incoming: &CalendarEvent) ->
         && attendee_email_set(&stored.attendees) == attendee_email_set(&incoming.attendees)
 }
 
+/// Human-readable list of what `incoming` changes relative to `stored`:
+/// start/end time, location, and the attendee set. Times are rendered in
+/// `tz` (the user's primary display zone). Empty when nothing tracked
+/// differs. Unlike `events_content_match` this ignores SUMMARY and
+/// DESCRIPTION — it's the glanceable "what moved" line for a rescheduling
+/// email, not an equality check.
+pub fn describe_changes(stored: &CalendarEvent, incoming: &CalendarEvent, tz: Tz) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    // All-day dates are floating: render them as the UTC date they encode,
+    // never shifted into the display zone.
+    let all_day = stored.all_day || incoming.all_day;
+    let tz = if all_day { Tz::UTC } else { tz };
+    let old_start = stored.dtstart.with_timezone(&tz);
+    let new_start = incoming.dtstart.with_timezone(&tz);
+    if old_start != new_start && all_day {
+        changes.push(format!(
+            "Moved from {} to {}",
+            old_start.format("%a %b %-d"),
+            new_start.format("%a %b %-d")
+        ));
+    } else if old_start != new_start {
+        if old_start.date_naive() == new_start.date_naive() {
+            changes.push(format!(
+                "Time moved from {} to {}",
+                short_time(&old_start),
+                short_time(&new_start)
+            ));
+        } else {
+            changes.push(format!(
+                "Moved from {} to {}",
+                short_date_time(&old_start),
+                short_date_time(&new_start)
+            ));
+        }
+    } else if !dtend_matches_normalized(stored, incoming)
+        && let (Some(old_end), Some(new_end)) = (stored.dtend, incoming.dtend)
+    {
+        changes.push(format!(
+            "Now ends at {} instead of {}",
+            short_time(&new_end.with_timezone(&tz)),
+            short_time(&old_end.with_timezone(&tz))
+        ));
+    }
+
+    match (&stored.location, &incoming.location) {
+        (Some(old), Some(new)) if old != new => {
+            changes.push(format!("Location changed from {old} to {new}"))
+        }
+        (None, Some(new)) if !new.is_empty() => changes.push(format!("Location added: {new}")),
+        (Some(old), None) if !old.is_empty() => {
+            changes.push(format!("Location removed (was {old})"))
+        }
+        _ => {}
+    }
+
+    let old_set = attendee_email_set(&stored.attendees);
+    let new_set = attendee_email_set(&incoming.attendees);
+    let added: Vec<&str> = new_set
+        .iter()
+        .filter(|e| !old_set.contains(e))
+        .map(String::as_str)
+        .collect();
+    let removed: Vec<&str> = old_set
+        .iter()
+        .filter(|e| !new_set.contains(e))
+        .map(String::as_str)
+        .collect();
+    if !added.is_empty() {
+        changes.push(format!("Added: {}", added.join(", ")));
+    }
+    if !removed.is_empty() {
+        changes.push(format!("Removed: {}", removed.join(", ")));
+    }
+
+    changes
+}
+
+/// "3pm" / "3:30pm" — minutes only when non-zero.
+fn short_time<T: TimeZone>(dt: &DateTime<T>) -> String
+where
+    T::Offset: std::fmt::Display,
+{
+    if dt.format("%M").to_string() == "00" {
+        dt.format("%-I%P").to_string()
+    } else {
+        dt.format("%-I:%M%P").to_string()
+    }
+}
+
+/// "Mon Feb 16, 3pm" — for moves that cross a date boundary.
+fn short_date_time<T: TimeZone>(dt: &DateTime<T>) -> String
+where
+    T::Offset: std::fmt::Display,
+{
+    format!("{}, {}", dt.format("%a %b %-d"), short_time(dt))
+}
+
 /// DTEND equality for `events_content_match`, normalized for Outlook's
 /// store-time default (roborev 295 #2): `build_graph_event` fills a missing
 /// incoming DTEND with `dtstart + 1h` before persisting to Graph, so a
@@ -460,6 +671,206 @@ pub fn cancel_decision(
     }
 }
 
+// =============================================================================
+// Reply decision (organizer-side METHOD:REPLY processing)
+// =============================================================================
+
+/// Outcome of validating an incoming `METHOD:REPLY` against the event stored
+/// in the organizer's (the user's) calendar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplyAction {
+    /// No stored event for this UID — nothing to update.
+    NoStored,
+    /// The stored event is organized by someone else; attendee replies to
+    /// it are none of our business.
+    NotOrganizer,
+    /// The replying attendee already has this PARTSTAT on the stored event
+    /// (typically: the same reply re-opened).
+    Unchanged,
+    /// Apply `status` for `attendee_email` to the stored event.
+    Apply {
+        attendee_email: String,
+        status: RsvpStatus,
+    },
+    /// The sender isn't an attendee of the stored event, or the reply
+    /// carries no usable ATTENDEE/PARTSTAT — a possible spoof. Touch nothing.
+    RejectSpoof,
+}
+
+/// Decide whether an incoming `METHOD:REPLY` may update an attendee's
+/// PARTSTAT on the stored event.
+///
+/// Only events the user organizes are touched: `my_addresses` is the
+/// account's own addresses (login plus identities), and the stored
+/// organizer must be one of them. Anti-spoof mirrors `cancel_decision`,
+/// scoped to the attendee instead of the organizer: a REPLY carries the
+/// replying attendee's own ATTENDEE line, so the message sender must be
+/// that attendee and must already be on the stored event — otherwise
+/// anyone who learns a UID could flip another attendee's response.
+pub fn reply_decision(
+    stored: Option<&CalendarEvent>,
+    reply: &CalendarEvent,
+    sender_email: Option<&str>,
+    my_addresses: &[&str],
+) -> ReplyAction {
+    let Some(stored) = stored else {
+        return ReplyAction::NoStored;
+    };
+    if stored.organizer_email.is_empty()
+        || !my_addresses
+            .iter()
+            .any(|a| a.eq_ignore_ascii_case(&stored.organizer_email))
+    {
+        return ReplyAction::NotOrganizer;
+    }
+    let Some(sender) = sender_email.filter(|s| !s.is_empty()) else {
+        return ReplyAction::RejectSpoof;
+    };
+    let Some(replied) = reply
+        .attendees
+        .iter()
+        .find(|a| a.email.eq_ignore_ascii_case(sender))
+    else {
+        return ReplyAction::RejectSpoof;
+    };
+    let Some(status) = RsvpStatus::from_ics_str(&replied.status) else {
+        return ReplyAction::RejectSpoof;
+    };
+    let Some(current) = stored
+        .attendees
+        .iter()
+        .find(|a| a.email.eq_ignore_ascii_case(sender))
+    else {
+        return ReplyAction::RejectSpoof;
+    };
+    if current.status.eq_ignore_ascii_case(status.as_ics_str()) {
+        return ReplyAction::Unchanged;
+    }
+    ReplyAction::Apply {
+        attendee_email: current.email.clone(),
+        status,
+    }
+}
+
+// =============================================================================
+// Organizer view (who's coming)
+// =============================================================================
+
+/// One attendee's answer to an event the user organizes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttendeeResponse {
+    pub email: String,
+    pub name: Option<String>,
+    /// PARTSTAT: `ACCEPTED`, `TENTATIVE`, `DECLINED` or `NEEDS-ACTION`.
+    pub status: String,
+    /// When the REPLY that set `status` came in; `None` when the status
+    /// is just what the calendar copy holds.
+    pub replied_at: Option<DateTime<Utc>>,
+}
+
+/// Attendees per PARTSTAT; anything unrecognized counts as no answer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCounts {
+    pub accepted: usize,
+    pub tentative: usize,
+    pub declined: usize,
+    pub needs_action: usize,
+}
+
+/// Every attendee of `event` but the organizer, with their answer: the
+/// stored PARTSTAT, overridden by the newest applied REPLY for this UID
+/// in `replies`. A reply lands in the calendar copy too, but the write
+/// can fail or lag behind (Outlook), and the log knows when it came in.
+pub fn attendee_responses(
+    event: &CalendarEvent,
+    replies: &[AppliedReply],
+) -> Vec<AttendeeResponse> {
+    event
+        .attendees
+        .iter()
+        .filter(|a| !a.email.eq_ignore_ascii_case(&event.organizer_email))
+        .map(|a| {
+            let reply = replies
+                .iter()
+                .filter(|r| r.uid == event.uid && r.attendee.eq_ignore_ascii_case(&a.email))
+                .max_by_key(|r| r.applied_at);
+            AttendeeResponse {
+                email: a.email.clone(),
+                name: a.name.clone(),
+                status: reply.map_or(&a.status, |r| &r.status).to_ascii_uppercase(),
+                replied_at: reply.map(|r| r.applied_at),
+            }
+        })
+        .collect()
+}
+
+pub fn response_counts(responses: &[AttendeeResponse]) -> ResponseCounts {
+    let mut counts = ResponseCounts::default();
+    for r in responses {
+        match r.status.as_str() {
+            "ACCEPTED" => counts.accepted += 1,
+            "TENTATIVE" => counts.tentative += 1,
+            "DECLINED" => counts.declined += 1,
+            _ => counts.needs_action += 1,
+        }
+    }
+    counts
+}
+
+// =============================================================================
+// Organizer validation (spoofed-invite warning)
+// =============================================================================
+
+/// A warning for the reader when an invite's claimed author and its sender
+/// are on different domains: the organizer for a REQUEST or CANCEL, the
+/// replying attendee(s) for a REPLY. Subdomains count as the same domain
+/// (`calendar.example.com` sending for `example.com`). Unlike the update
+/// and cancel decisions this changes nothing in the calendar; it flags mail
+/// that impersonates a colleague or a bank through an ICS. `None` when the
+/// domains agree or there's nothing to compare.
+pub fn organizer_mismatch(event: &CalendarEvent, sender_email: Option<&str>) -> Option<String> {
+    let sender = sender_email?;
+    let sender_domain = domain_of(sender)?;
+    if event.method.eq_ignore_ascii_case("REPLY") {
+        let attendees: Vec<&str> = event
+            .attendees
+            .iter()
+            .map(|a| a.email.as_str())
+            .filter(|e| domain_of(e).is_some())
+            .collect();
+        let first = attendees.first()?;
+        if attendees
+            .iter()
+            .any(|a| domain_of(a).is_some_and(|d| same_domain(d, sender_domain)))
+        {
+            return None;
+        }
+        return Some(format!(
+            "This reply is for {first} but was sent from {sender}"
+        ));
+    }
+    let organizer_domain = domain_of(&event.organizer_email)?;
+    (!same_domain(organizer_domain, sender_domain)).then(|| {
+        format!(
+            "The organizer is {} but this was sent from {sender}",
+            event.organizer_email
+        )
+    })
+}
+
+fn domain_of(addr: &str) -> Option<&str> {
+    addr.rsplit_once('@')
+        .map(|(_, d)| d.trim_end_matches('.'))
+        .filter(|d| !d.is_empty())
+}
+
+fn same_domain(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+    a == b || a.ends_with(&format!(".{b}")) || b.ends_with(&format!(".{a}"))
+}
+
 fn unfold_lines(s: &str) -> String {
     // ICS line folding: CRLF followed by single whitespace = continuation
     let s = s.replace("\r\n ", "").replace("\r\n\t", "");
@@ -549,6 +960,30 @@ fn parse_utc_offset(s: &str) -> Option<FixedOffset> {
     FixedOffset::east_opt(total_seconds)
 }
 
+/// True when the first `name` property is a DATE (all-day) rather than a
+/// DATE-TIME — same detection as `parse_ics_datetime_property`.
+fn property_is_date_only(text: &str, name: &str) -> bool {
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        let Some(rest) = line.strip_prefix(name) else {
+            continue;
+        };
+        let (params, value) = if let Some(stripped) = rest.strip_prefix(':') {
+            ("", stripped)
+        } else if rest.starts_with(';') {
+            match rest.find(':') {
+                Some(colon) => (&rest[1..colon], &rest[colon + 1..]),
+                None => return false,
+            }
+        } else {
+            continue;
+        };
+        return (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME"))
+            || value.trim().len() == 8;
+    }
+    false
+}
+
 fn parse_ics_datetime_property(
     text: &str,
     name: &str,
@@ -754,11 +1189,17 @@ pub fn generate_rsvp(event: &CalendarEvent, attendee_email: &str, status: &RsvpS
         None => String::new(),
     };
 
-    let dtstart = format_ics_datetime(event.dtstart);
-    let dtend_line = event
-        .dtend
-        .map(|dt| format!("DTEND:{}\r\n", format_ics_datetime(dt)))
-        .unwrap_or_default();
+    let (dtstart_line, dtend_line) = if event.all_day {
+        all_day_lines(event)
+    } else {
+        (
+            format!("DTSTART:{}\r\n", format_ics_datetime(event.dtstart)),
+            event
+                .dtend
+                .map(|dt| format!("DTEND:{}\r\n", format_ics_datetime(dt)))
+                .unwrap_or_default(),
+        )
+    };
 
     let organizer_cn = event
         .organizer_name
@@ -773,7 +1214,7 @@ pub fn generate_rsvp(event: &CalendarEvent, attendee_email: &str, status: &RsvpS
          METHOD:REPLY\r\n\
          BEGIN:VEVENT\r\n\
          UID:{uid}\r\n\
-         DTSTART:{dtstart}\r\n\
+         {dtstart_line}\
          {dtend_line}\
          SUMMARY:{summary}\r\n\
          ORGANIZER{organizer_cn}:mailto:{organizer_email}\r\n\
@@ -782,7 +1223,7 @@ pub fn generate_rsvp(event: &CalendarEvent, attendee_email: &str, status: &RsvpS
          END:VEVENT\r\n\
          END:VCALENDAR",
         uid = event.uid,
-        dtstart = dtstart,
+        dtstart_line = dtstart_line,
         dtend_line = dtend_line,
         summary = escape_text(&event.summary),
         organizer_cn = organizer_cn,
@@ -794,6 +1235,18 @@ pub fn generate_rsvp(event: &CalendarEvent, attendee_email: &str, status: &RsvpS
     )
 }
 
+/// `DTSTART;VALUE=DATE` / `DTEND;VALUE=DATE` lines for an all-day event.
+/// The stored instants are UTC midnight of the floating date (see
+/// `CalendarEvent::all_day`), so the UTC calendar date is the DATE value.
+fn all_day_lines(event: &CalendarEvent) -> (String, String) {
+    let dtstart = format!("DTSTART;VALUE=DATE:{}\r\n", event.dtstart.format("%Y%m%d"));
+    let dtend = event
+        .dtend
+        .map(|dt| format!("DTEND;VALUE=DATE:{}\r\n", dt.format("%Y%m%d")))
+        .unwrap_or_default();
+    (dtstart, dtend)
+}
+
 fn format_ics_datetime(dt: DateTime<Utc>) -> String {
     dt.format("%Y%m%dT%H%M%SZ").to_string()
 }
@@ -996,18 +1449,26 @@ pub fn generate_rsvp_with_tz(
         None => String::new(),
     };
 
-    let dtstart_local = event.dtstart.with_timezone(&reply_tz);
-    let dtend_local = event.dtend.map(|dt| dt.with_timezone(&reply_tz));
-    let tzid = reply_tz.name();
-    let vtimezone = synth_vtimezone(reply_tz, dtstart_local);
-
-    let dtstart_line = format!(
-        "DTSTART;TZID={tzid}:{}\r\n",
-        format_ics_datetime_local(dtstart_local)
-    );
-    let dtend_line = dtend_local
-        .map(|dt| format!("DTEND;TZID={tzid}:{}\r\n", format_ics_datetime_local(dt)))
-        .unwrap_or_default();
+    // All-day events are floating dates: no zone to quote them in, so no
+    // VTIMEZONE either.
+    let (vtimezone, dtstart_line, dtend_line) = if event.all_day {
+        let (dtstart_line, dtend_line) = all_day_lines(event);
+        (String::new(), dtstart_line, dtend_line)
+    } else {
+        let dtstart_local = event.dtstart.with_timezone(&reply_tz);
+        let dtend_local = event.dtend.map(|dt| dt.with_timezone(&reply_tz));
+        let tzid = reply_tz.name();
+        (
+            synth_vtimezone(reply_tz, dtstart_local),
+            format!(
+                "DTSTART;TZID={tzid}:{}\r\n",
+                format_ics_datetime_local(dtstart_local)
+            ),
+            dtend_local
+                .map(|dt| format!("DTEND;TZID={tzid}:{}\r\n", format_ics_datetime_local(dt)))
+                .unwrap_or_default(),
+        )
+    };
 
     let organizer_cn = event
         .organizer_name
@@ -1040,6 +1501,65 @@ pub fn generate_rsvp_with_tz(
     )
 }
 
+/// Build the organizer's iTIP CANCEL (RFC 5546 §3.2.5) for `event`. Every
+/// attendee is listed so each recipient's client matches and removes its
+/// copy; SEQUENCE is bumped past the stored one because a CANCEL is a
+/// revision of the event and clients ignore stale sequences.
+pub fn generate_cancel(event: &CalendarEvent) -> String {
+    let (dtstart_line, dtend_line) = if event.all_day {
+        all_day_lines(event)
+    } else {
+        (
+            format!("DTSTART:{}\r\n", format_ics_datetime(event.dtstart)),
+            event
+                .dtend
+                .map(|dt| format!("DTEND:{}\r\n", format_ics_datetime(dt)))
+                .unwrap_or_default(),
+        )
+    };
+    let organizer_cn = event
+        .organizer_name
+        .as_ref()
+        .map(|n| format!(";CN={}", escape_param_value(n)))
+        .unwrap_or_default();
+    let attendee_lines: String = event
+        .attendees
+        .iter()
+        .map(|att| {
+            let cn = att
+                .name
+                .as_ref()
+                .map(|n| format!(";CN={}", escape_param_value(n)))
+                .unwrap_or_default();
+            format!("ATTENDEE{cn}:mailto:{}\r\n", sanitize_address(&att.email))
+        })
+        .collect();
+
+    format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//Supervillain//EN\r\n\
+         METHOD:CANCEL\r\n\
+         BEGIN:VEVENT\r\n\
+         UID:{uid}\r\n\
+         DTSTAMP:{dtstamp}\r\n\
+         {dtstart_line}\
+         {dtend_line}\
+         SUMMARY:{summary}\r\n\
+         ORGANIZER{organizer_cn}:mailto:{organizer_email}\r\n\
+         {attendee_lines}\
+         SEQUENCE:{sequence}\r\n\
+         STATUS:CANCELLED\r\n\
+         END:VEVENT\r\n\
+         END:VCALENDAR",
+        uid = sanitize_token(&event.uid),
+        dtstamp = format_ics_datetime(Utc::now()),
+        summary = escape_text(&event.summary),
+        organizer_email = sanitize_address(&event.organizer_email),
+        sequence = event.sequence + 1,
+    )
+}
+
 fn escape_text(s: &str) -> String {
     // RFC 5545: backslash, newline, comma, semicolon need escaping in TEXT values.
     // CR has no escape — strict parsers reject a bare CR mid-line. Normalize
@@ -1075,6 +1595,48 @@ pub fn strip_method(ics: &str) -> String {
         .join("\n")
 }
 
+/// An invite's ICS as a standalone file for "add to external calendar":
+/// METHOD stripped, so importers treat it as a plain event instead of an
+/// iTIP message to answer, and CRLF line endings throughout (RFC 5545
+/// §3.1), which some importers insist on.
+pub fn export_ics(ics: &str) -> String {
+    let mut out: String = strip_method(ics)
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    out.push_str("\r\n");
+    out
+}
+
+/// Download name for [`export_ics`]: the summary reduced to characters safe
+/// in a Content-Disposition filename, or `event.ics` when nothing is left.
+pub fn export_filename(summary: &str) -> String {
+    let cleaned: String = summary
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let stem = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(60)
+        .collect::<String>();
+    let stem = stem.trim();
+    if stem.is_empty() {
+        "event.ics".into()
+    } else {
+        format!("{stem}.ics")
+    }
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -1607,6 +2169,47 @@ END:VCALENDAR";
         assert_eq!(parsed.attendees[0].status, "DECLINED");
     }
 
+    // --- generate_cancel tests ---
+
+    #[test]
+    fn cancel_round_trips_with_bumped_sequence() {
+        let event = sample_event();
+        let ics = generate_cancel(&event);
+        assert!(ics.contains("METHOD:CANCEL"));
+        assert!(ics.contains("STATUS:CANCELLED"));
+        let parsed = parse_ics(&ics).unwrap();
+        assert_eq!(parsed.method, "CANCEL");
+        assert_eq!(parsed.uid, event.uid);
+        assert_eq!(parsed.summary, event.summary);
+        assert_eq!(parsed.organizer_email, event.organizer_email);
+        assert_eq!(parsed.sequence, event.sequence + 1);
+        assert_eq!(parsed.dtstart, event.dtstart);
+    }
+
+    #[test]
+    fn cancel_lists_every_attendee_without_rsvp() {
+        let mut event = sample_event();
+        event.attendees.push(Attendee {
+            email: "carol@example.com".into(),
+            name: None,
+            status: "ACCEPTED".into(),
+        });
+        let ics = generate_cancel(&event);
+        let parsed = parse_ics(&ics).unwrap();
+        let emails: Vec<_> = parsed.attendees.iter().map(|a| a.email.as_str()).collect();
+        assert!(emails.contains(&"bob@example.com"));
+        assert!(emails.contains(&"carol@example.com"));
+        assert!(!ics.contains("RSVP=TRUE"));
+    }
+
+    #[test]
+    fn cancel_all_day_keeps_dates() {
+        let mut event = sample_event();
+        event.all_day = true;
+        let ics = generate_cancel(&event);
+        assert!(ics.contains("DTSTART;VALUE=DATE:"));
+    }
+
     // --- update_partstat tests ---
 
     #[test]
@@ -1776,6 +2379,31 @@ END:VCALENDAR";
         assert!(result.contains("BEGIN:VCALENDAR"));
     }
 
+    // --- export_ics / export_filename tests ---
+
+    #[test]
+    fn export_ics_drops_method_and_uses_crlf() {
+        let out = export_ics(
+            "BEGIN:VCALENDAR\nMETHOD:REQUEST\r\nBEGIN:VEVENT\nEND:VEVENT\nEND:VCALENDAR",
+        );
+        assert_eq!(
+            out,
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+        );
+        assert!(parse_ics(&export_ics(SAMPLE_ICS)).is_some());
+    }
+
+    #[test]
+    fn export_filename_is_header_safe() {
+        assert_eq!(export_filename("Team Standup"), "Team Standup.ics");
+        assert_eq!(
+            export_filename("Q3 \"review\"; 2/3\r\n"),
+            "Q3 review 2 3.ics"
+        );
+        assert_eq!(export_filename("  ?? "), "event.ics");
+        assert_eq!(export_filename(&"x".repeat(100)).len(), 64);
+    }
+
     // --- invite_update_decision tests (RFC 5546 SEQUENCE + anti-spoof) ---
 
     #[test]
@@ -2429,6 +3057,380 @@ END:VCALENDAR";
         );
     }
 
+    // --- all-day (VALUE=DATE) round-trip tests ---
+
+    #[test]
+    fn parse_all_day_sets_flag() {
+        assert!(parse_ics(SAMPLE_ICS_ALL_DAY).unwrap().all_day);
+        assert!(!parse_ics(SAMPLE_ICS).unwrap().all_day);
+    }
+
+    #[test]
+    fn rsvp_all_day_keeps_value_date() {
+        let event = parse_ics(SAMPLE_ICS_ALL_DAY).unwrap();
+        let rsvp = generate_rsvp(&event, "bob@example.com", &RsvpStatus::Accepted);
+        assert!(rsvp.contains("DTSTART;VALUE=DATE:20260215\r\n"), "{rsvp}");
+        assert!(!rsvp.contains("T000000"), "{rsvp}");
+    }
+
+    #[test]
+    fn rsvp_with_tz_all_day_keeps_value_date_without_vtimezone() {
+        let mut event = parse_ics(SAMPLE_ICS_ALL_DAY).unwrap();
+        event.dtend = Some(event.dtstart + chrono::Duration::days(1));
+        let rsvp = generate_rsvp_with_tz(
+            &event,
+            "bob@example.com",
+            &RsvpStatus::Accepted,
+            chrono_tz::America::Los_Angeles,
+        );
+        assert!(rsvp.contains("DTSTART;VALUE=DATE:20260215\r\n"), "{rsvp}");
+        assert!(rsvp.contains("DTEND;VALUE=DATE:20260216\r\n"), "{rsvp}");
+        assert!(!rsvp.contains("VTIMEZONE"), "{rsvp}");
+        let reparsed = parse_ics(&rsvp).unwrap();
+        assert!(reparsed.all_day);
+        assert_eq!(reparsed.dtstart, event.dtstart);
+    }
+
+    #[test]
+    fn update_partstat_preserves_value_date() {
+        let ics = SAMPLE_ICS_ALL_DAY.replace(
+            "SEQUENCE:0",
+            "ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:bob@example.com\r\nSEQUENCE:0",
+        );
+        let updated = update_partstat(&ics, "bob@example.com", &RsvpStatus::Accepted);
+        assert!(updated.contains("DTSTART;VALUE=DATE:20260215"));
+        assert!(updated.contains("PARTSTAT=ACCEPTED"));
+    }
+
+    #[test]
+    fn describe_changes_all_day_renders_dates_not_times() {
+        let stored = parse_ics(SAMPLE_ICS_ALL_DAY).unwrap();
+        let mut incoming = stored.clone();
+        incoming.dtstart += chrono::Duration::days(2);
+        assert_eq!(
+            describe_changes(&stored, &incoming, chrono_tz::America::Los_Angeles),
+            vec!["Moved from Sun Feb 15 to Tue Feb 17"]
+        );
+    }
+
+    // --- VALARM tests ---
+
+    const SAMPLE_ICS_WITH_ALARM: &str = "\
+BEGIN:VCALENDAR\r\n\
+METHOD:REQUEST\r\n\
+BEGIN:VEVENT\r\n\
+UID:alarm-uid@example.com\r\n\
+DTSTART:20260215T100000Z\r\n\
+SUMMARY:With Alarm\r\n\
+ORGANIZER:mailto:alice@example.com\r\n\
+BEGIN:VALARM\r\n\
+ACTION:DISPLAY\r\n\
+DESCRIPTION:Reminder\r\n\
+TRIGGER:-PT15M\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR";
+
+    #[test]
+    fn parse_alarms_from_vevent() {
+        let event = parse_ics(SAMPLE_ICS_WITH_ALARM).unwrap();
+        assert_eq!(
+            event.alarms,
+            vec![Alarm {
+                action: "DISPLAY".into(),
+                trigger: "-PT15M".into(),
+                description: Some("Reminder".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn alarm_description_does_not_leak_into_event_description() {
+        let event = parse_ics(SAMPLE_ICS_WITH_ALARM).unwrap();
+        assert_eq!(event.description, None);
+    }
+
+    #[test]
+    fn parse_without_alarm_is_empty() {
+        assert!(parse_ics(SAMPLE_ICS).unwrap().alarms.is_empty());
+    }
+
+    #[test]
+    fn reminder_trigger_units() {
+        assert_eq!(reminder_trigger("10m").as_deref(), Some("-PT10M"));
+        assert_eq!(reminder_trigger(" 1h ").as_deref(), Some("-PT1H"));
+        assert_eq!(reminder_trigger("2d").as_deref(), Some("-P2D"));
+        assert_eq!(reminder_trigger("1W").as_deref(), Some("-P1W"));
+        assert_eq!(reminder_trigger("10"), None);
+        assert_eq!(reminder_trigger("m"), None);
+        assert_eq!(reminder_trigger("-5m"), None);
+    }
+
+    #[test]
+    fn inject_default_alarm_adds_when_missing() {
+        let out = inject_default_alarm(SAMPLE_ICS, "-PT10M");
+        assert!(out.contains("BEGIN:VALARM\r\nACTION:DISPLAY\r\n"));
+        assert!(out.contains("TRIGGER:-PT10M\r\nEND:VALARM\r\nEND:VEVENT"));
+        let event = parse_ics(&out).unwrap();
+        assert_eq!(event.alarms.len(), 1);
+        assert_eq!(event.description.as_deref(), Some("Daily standup meeting"));
+    }
+
+    #[test]
+    fn inject_default_alarm_keeps_organizer_alarm() {
+        let out = inject_default_alarm(SAMPLE_ICS_WITH_ALARM, "-PT10M");
+        assert_eq!(out, SAMPLE_ICS_WITH_ALARM);
+    }
+
+    #[test]
+    fn inject_default_alarm_uses_bare_lf_input_endings() {
+        let lf = SAMPLE_ICS.replace("\r\n", "\n");
+        let out = inject_default_alarm(&lf, "-PT5M");
+        assert!(!out.contains('\r'));
+        assert!(out.contains("TRIGGER:-PT5M\nEND:VALARM\nEND:VEVENT"));
+    }
+
+    // --- describe_changes tests ---
+
+    #[test]
+    fn describe_changes_none_for_identical_event() {
+        let e = sample_event();
+        assert!(describe_changes(&e, &e, Tz::UTC).is_empty());
+    }
+
+    #[test]
+    fn describe_changes_same_day_time_move() {
+        let stored = sample_event();
+        let mut incoming = sample_event();
+        incoming.dtstart += chrono::Duration::minutes(90);
+        incoming.dtend = incoming.dtend.map(|d| d + chrono::Duration::minutes(90));
+        assert_eq!(
+            describe_changes(&stored, &incoming, Tz::UTC),
+            vec!["Time moved from 10am to 11:30am"]
+        );
+    }
+
+    #[test]
+    fn describe_changes_renders_in_display_timezone() {
+        let stored = sample_event();
+        let mut incoming = sample_event();
+        incoming.dtstart += chrono::Duration::hours(1);
+        // 10:00Z / 11:00Z are 5am / 6am in New York (EST).
+        assert_eq!(
+            describe_changes(&stored, &incoming, chrono_tz::America::New_York),
+            vec!["Time moved from 5am to 6am"]
+        );
+    }
+
+    #[test]
+    fn describe_changes_cross_day_move_includes_date() {
+        let stored = sample_event();
+        let mut incoming = sample_event();
+        incoming.dtstart += chrono::Duration::days(1);
+        assert_eq!(
+            describe_changes(&stored, &incoming, Tz::UTC),
+            vec!["Moved from Sun Feb 15, 10am to Mon Feb 16, 10am"]
+        );
+    }
+
+    #[test]
+    fn describe_changes_end_only() {
+        let stored = sample_event();
+        let mut incoming = sample_event();
+        incoming.dtend = incoming.dtend.map(|d| d + chrono::Duration::minutes(30));
+        assert_eq!(
+            describe_changes(&stored, &incoming, Tz::UTC),
+            vec!["Now ends at 11:30am instead of 11am"]
+        );
+    }
+
+    #[test]
+    fn describe_changes_location_and_attendees() {
+        let stored = sample_event();
+        let mut incoming = sample_event();
+        incoming.location = Some("Room C".into());
+        incoming
+            .attendees
+            .retain(|a| a.email != "carol@example.com");
+        incoming.attendees.push(Attendee {
+            email: "dave@example.com".into(),
+            name: None,
+            status: "NEEDS-ACTION".into(),
+        });
+        assert_eq!(
+            describe_changes(&stored, &incoming, Tz::UTC),
+            vec![
+                "Location changed from Conference Room B to Room C",
+                "Added: dave@example.com",
+                "Removed: carol@example.com",
+            ]
+        );
+    }
+
+    // --- reply_decision tests (organizer-side METHOD:REPLY) ---
+
+    fn reply_from(attendee: &str, partstat: &str) -> CalendarEvent {
+        parse_ics(&format!(
+            "BEGIN:VCALENDAR\r\nMETHOD:REPLY\r\nBEGIN:VEVENT\r\n\
+             UID:test-uid-123@example.com\r\nDTSTART:20260215T100000Z\r\n\
+             ORGANIZER:mailto:alice@example.com\r\n\
+             ATTENDEE;PARTSTAT={partstat}:mailto:{attendee}\r\n\
+             END:VEVENT\r\nEND:VCALENDAR"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn reply_decision_no_stored_event() {
+        let reply = reply_from("bob@example.com", "ACCEPTED");
+        assert_eq!(
+            reply_decision(
+                None,
+                &reply,
+                Some("bob@example.com"),
+                &["alice@example.com"]
+            ),
+            ReplyAction::NoStored
+        );
+    }
+
+    #[test]
+    fn reply_decision_applies_attendee_partstat() {
+        let stored = sample_event();
+        let reply = reply_from("bob@example.com", "ACCEPTED");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("Bob@Example.com"),
+                &["Alice@Example.com"]
+            ),
+            ReplyAction::Apply {
+                attendee_email: "bob@example.com".into(),
+                status: RsvpStatus::Accepted,
+            }
+        );
+    }
+
+    #[test]
+    fn reply_decision_ignores_events_i_do_not_organize() {
+        let stored = sample_event();
+        let reply = reply_from("bob@example.com", "ACCEPTED");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("bob@example.com"),
+                &["carol@example.com"]
+            ),
+            ReplyAction::NotOrganizer
+        );
+    }
+
+    #[test]
+    fn reply_decision_unchanged_when_status_already_applied() {
+        let stored = sample_event();
+        let reply = reply_from("carol@example.com", "ACCEPTED");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("carol@example.com"),
+                &["alice@example.com"]
+            ),
+            ReplyAction::Unchanged
+        );
+    }
+
+    #[test]
+    fn reply_decision_rejects_sender_other_than_replying_attendee() {
+        // Mallory mails a REPLY claiming to be Bob's response.
+        let stored = sample_event();
+        let reply = reply_from("bob@example.com", "DECLINED");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("mallory@evil.example"),
+                &["alice@example.com"]
+            ),
+            ReplyAction::RejectSpoof
+        );
+    }
+
+    #[test]
+    fn reply_decision_rejects_attendee_not_on_stored_event() {
+        let stored = sample_event();
+        let reply = reply_from("mallory@evil.example", "ACCEPTED");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("mallory@evil.example"),
+                &["alice@example.com"]
+            ),
+            ReplyAction::RejectSpoof
+        );
+    }
+
+    #[test]
+    fn reply_decision_rejects_non_response_partstat() {
+        let stored = sample_event();
+        let reply = reply_from("bob@example.com", "NEEDS-ACTION");
+        assert_eq!(
+            reply_decision(
+                Some(&stored),
+                &reply,
+                Some("bob@example.com"),
+                &["alice@example.com"]
+            ),
+            ReplyAction::RejectSpoof
+        );
+    }
+
+    #[test]
+    fn attendee_responses_prefer_the_newest_reply() {
+        let event = sample_event();
+        let reply = |attendee: &str, status: &str, minutes: i64| AppliedReply {
+            account: "work".into(),
+            email_id: "M1".into(),
+            uid: event.uid.clone(),
+            summary: event.summary.clone(),
+            attendee: attendee.into(),
+            status: status.into(),
+            applied_at: Utc.with_ymd_and_hms(2026, 2, 1, 9, 0, 0).unwrap()
+                + chrono::Duration::minutes(minutes),
+        };
+        let other_event = AppliedReply {
+            uid: "other".into(),
+            ..reply("carol@example.com", "DECLINED", 30)
+        };
+        let replies = [
+            reply("Bob@Example.com", "TENTATIVE", 0),
+            reply("bob@example.com", "ACCEPTED", 10),
+            other_event,
+        ];
+        let responses = attendee_responses(&event, &replies);
+        let statuses: Vec<_> = responses
+            .iter()
+            .map(|r| (r.email.as_str(), r.status.as_str(), r.replied_at.is_some()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("bob@example.com", "ACCEPTED", true),
+                ("carol@example.com", "ACCEPTED", false)
+            ]
+        );
+        assert_eq!(
+            response_counts(&responses),
+            ResponseCounts {
+                accepted: 2,
+                ..Default::default()
+            }
+        );
+    }
+
     // --- timezone handling tests ---
 
     #[test]
@@ -3026,4 +4028,58 @@ END:VCALENDAR";
             );
         }
     }
+
+    #[test]
+    fn pick_invite_prefers_vevent_with_method() {
+        let bare =
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:x\r\nEND:VEVENT\r\nEND:VCALENDAR".to_string();
+        let todo = "BEGIN:VCALENDAR\r\nMETHOD:PUBLISH\r\nBEGIN:VTODO\r\nEND:VTODO\r\nEND:VCALENDAR"
+            .to_string();
+        let picked = pick_invite_ics(vec![todo.clone(), bare.clone(), SAMPLE_ICS.to_string()]);
+        assert_eq!(picked.as_deref(), Some(SAMPLE_ICS));
+        assert_eq!(
+            pick_invite_ics(vec![todo.clone(), bare.clone()]),
+            Some(bare)
+        );
+        assert_eq!(pick_invite_ics(vec![todo.clone()]), Some(todo));
+        assert_eq!(pick_invite_ics(Vec::new()), None);
+    }
+
+    // --- organizer_mismatch tests ---
+
+    #[test]
+    fn organizer_mismatch_flags_foreign_sender_domains() {
+        let invite = sample_event();
+        assert_eq!(invite.organizer_email, "alice@example.com");
+        assert_eq!(organizer_mismatch(&invite, Some("alice@example.com")), None);
+        assert_eq!(
+            organizer_mismatch(&invite, Some("noreply@calendar.Example.com")),
+            None
+        );
+        assert_eq!(
+            organizer_mismatch(&invite, Some("alice@example.com.evil.test")),
+            Some(
+                "The organizer is alice@example.com but this was sent from \
+                 alice@example.com.evil.test"
+                    .into()
+            )
+        );
+        // Nothing to compare against.
+        assert_eq!(organizer_mismatch(&invite, None), None);
+    }
+
+    #[test]
+    fn organizer_mismatch_checks_the_replying_attendee() {
+        let reply = reply_from("bob@partner.example", "ACCEPTED");
+        // Replies come from the attendee, not the organizer's domain.
+        assert_eq!(
+            organizer_mismatch(&reply, Some("bob@partner.example")),
+            None
+        );
+        assert!(
+            organizer_mismatch(&reply, Some("bob@example.com"))
+                .unwrap()
+                .contains("bob@partner.example")
+        );
+    }
 }
@@ -0,0 +1,104 @@
+//! Attendee replies to events you organize.
+//!
+//! A `METHOD:REPLY` email updates the attendee's PARTSTAT in your stored
+//! copy of the event (`calendar::reply_decision` decides whether it may).
+//! That happens when the reply is opened, and also without opening it:
+//! [`spawn_watcher`] reads the newest [`WATCH_SCAN`] Inbox messages of
+//! each connected account once a minute and applies the replies among
+//! them that it hasn't looked at yet. Each applied reply is logged in
+//! `AppState::calendar_reply_log` for `GET /api/calendar/replies`. The log
+//! is saved to `storage` on every change and read back at startup, so
+//! it survives restarts.
+
+use crate::error::Error;
+use crate::storage::{self, DocumentStore, Namespace};
+use crate::types::AppliedReply;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SUBSYSTEM: &str = "calendar-replies";
+const LOG_KEY: &str = "log";
+
+/// Newest Inbox messages the watcher reads per account and tick.
+pub const WATCH_SCAN: usize = 50;
+
+pub fn load(store: &dyn DocumentStore) -> VecDeque<AppliedReply> {
+    storage::get_json(store, &Namespace::global(SUBSYSTEM), LOG_KEY)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read the calendar reply log: {e}");
+            None
+        })
+        .unwrap_or_default()
+}
+
+pub fn save(store: &dyn DocumentStore, log: &VecDeque<AppliedReply>) -> Result<(), Error> {
+    storage::put_json(store, &Namespace::global(SUBSYSTEM), LOG_KEY, log)
+}
+
+/// Apply attendee replies in new Inbox mail every `interval`; see the
+/// module docs.
+pub fn spawn_watcher(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("calendar-replies", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Messages already looked at, per account. Only the current window
+        // is kept; after a restart the window is read once more, and
+        // replies applied before come back unchanged.
+        let mut seen: HashMap<String, HashSet<String>> = HashMap::new();
+        loop {
+            ticker.tick().await;
+            health.ticked("calendar-replies");
+            if crate::read_only::enabled(&state).await {
+                continue;
+            }
+            let accounts: Vec<String> = {
+                let reg = state.accounts.read().await;
+                reg.sessions.keys().cloned().collect()
+            };
+            for account in accounts {
+                let seen = seen.entry(account.clone()).or_default();
+                match crate::routes::apply_inbox_replies(&state, &account, seen).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Applied {n} calendar reply(ies) on {account}"),
+                    Err(e) => {
+                        tracing::warn!("Calendar reply pass failed for {account}: {e}");
+                        health.failed("calendar-replies", format!("{account}: {e}"));
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_survives_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::storage::FileStore::new(dir.path().to_path_buf());
+        assert!(load(&store).is_empty());
+        let log: VecDeque<AppliedReply> = [AppliedReply {
+            account: "work".into(),
+            email_id: "m1".into(),
+            uid: "evt-1".into(),
+            summary: "Standup".into(),
+            attendee: "bob@x.example".into(),
+            status: "ACCEPTED".into(),
+            applied_at: chrono::Utc::now(),
+        }]
+        .into();
+        save(&store, &log).unwrap();
+        let loaded = load(&store);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].email_id, "m1");
+        assert_eq!(loaded[0].status, "ACCEPTED");
+    }
+}
@@ -0,0 +1,628 @@
+//! Server-side command registry.
+//!
+//! One table of every action the UI exposes — its palette label, default key
+//! binding, and the context it needs to make sense. `/api/commands` serves it
+//! with per-message applicability (e.g. "unsubscribe" only when the focused
+//! message carries List-Unsubscribe), so the command palette and the keyboard
+//! shortcut table come from the same source instead of drifting apart.
+//! `/api/help` builds the help overlay's cheatsheet from it too, adding
+//! the keys that aren't commands (movement, compose, settings).
+//!
+//! Keys can be remapped in `keybindings.json`, read on every request:
+//!
+//! ```json
+//! { "bindings": { "archive": "x", "snooze": "Z", "trash": null } }
+//! ```
+//!
+//! A command id maps to a single key (`KeyboardEvent.key`), or to `null`
+//! to leave it unbound. [`Keymap`] resolves the file against the defaults
+//! in [`COMMANDS`]. Unknown ids, keys the list view already uses for
+//! movement, and keys two commands would share are ignored with a
+//! warning, so a broken file never leaves a command on someone else's
+//! key. The desktop client runs remapped keys through the same command ids
+//! the palette uses.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::types::Email;
+
+/// What a command needs before it can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandScope {
+    /// Always available.
+    Global,
+    /// Needs a focused message.
+    Message,
+    /// Needs a focused message with a calendar invite.
+    Calendar,
+    /// Needs a focused message with a List-Unsubscribe header.
+    Unsubscribe,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    /// Stable id; matches the client's action name.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Default key binding in the list/detail views, if any.
+    pub key: Option<&'static str>,
+    pub scope: CommandScope,
+}
+
+const fn cmd(
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    key: Option<&'static str>,
+    scope: CommandScope,
+) -> Command {
+    Command {
+        id,
+        name,
+        description,
+        key,
+        scope,
+    }
+}
+
+use CommandScope::{Calendar, Global, Message, Unsubscribe};
+
+/// Every command, in palette order.
+pub const COMMANDS: &[Command] = &[
+    cmd("archive", "Archive", "Archive email", Some("e"), Message),
+    cmd("trash", "Trash", "Move to trash", Some("#"), Message),
+    cmd("reply", "Reply", "Reply to sender", Some("r"), Message),
+    cmd("reply-all", "Reply All", "Reply to all", Some("a"), Message),
+    cmd("compose", "Compose", "New email", Some("c"), Global),
+    cmd("forward", "Forward", "Forward email", Some("f"), Message),
+    cmd(
+        "toggle-unread",
+        "Mark Unread",
+        "Toggle unread",
+        Some("u"),
+        Message,
+    ),
+    cmd("toggle-flag", "Star", "Toggle star", Some("s"), Message),
+    cmd(
+        "snooze",
+        "Snooze",
+        "Hide until later, then return unread",
+        None,
+        Message,
+    ),
+    cmd(
+        "flag-color",
+        "Star Color",
+        "Star in a color (Fastmail)",
+        None,
+        Message,
+    ),
+    cmd(
+        "mark-thread-read",
+        "Mark Thread Read",
+        "Mark the whole conversation read",
+        None,
+        Message,
+    ),
+    cmd(
+        "mute-thread",
+        "Mute Thread",
+        "Archive the conversation and any later replies",
+        None,
+        Message,
+    ),
+    cmd(
+        "export-thread-pdf",
+        "Export Thread PDF",
+        "Download the whole conversation as a PDF",
+        None,
+        Message,
+    ),
+    cmd(
+        "mark-all-read",
+        "Mark All Read",
+        "Mark every message in this mailbox read",
+        Some("M"),
+        Global,
+    ),
+    cmd(
+        "unsubscribe",
+        "Unsubscribe",
+        "Unsubscribe and archive all from sender",
+        Some("U"),
+        Unsubscribe,
+    ),
+    cmd(
+        "rsvp-accept",
+        "Accept Invite",
+        "RSVP yes",
+        Some("y"),
+        Calendar,
+    ),
+    cmd(
+        "rsvp-decline",
+        "Decline Invite",
+        "RSVP no",
+        Some("n"),
+        Calendar,
+    ),
+    cmd("rsvp-tentative", "Maybe", "RSVP maybe", Some("m"), Calendar),
+    cmd(
+        "empty-mailbox",
+        "Empty Mailbox",
+        "Permanently delete everything in Trash or Spam",
+        None,
+        Global,
+    ),
+    cmd(
+        "unsubscribe-suggestions",
+        "Unsubscribe Suggestions",
+        "Review newsletters you rarely open",
+        None,
+        Global,
+    ),
+    cmd("undo", "Undo", "Undo last action", Some("z"), Global),
+    cmd("search", "Search", "Search emails", Some("/"), Global),
+    cmd("refresh", "Refresh", "Reload emails", Some("R"), Global),
+    cmd("inbox", "Go to Inbox", "Switch to inbox", None, Global),
+    cmd(
+        "go-archive",
+        "Go to Archive",
+        "Switch to archive",
+        None,
+        Global,
+    ),
+    cmd("go-trash", "Go to Trash", "Switch to trash", None, Global),
+    cmd("new-split", "New Split", "Create split inbox", None, Global),
+    cmd(
+        "add-account",
+        "Add Account",
+        "Connect a new mailbox",
+        None,
+        Global,
+    ),
+    cmd(
+        "download-diagnostics",
+        "Download Diagnostics",
+        "Save a redacted bundle to attach to a bug report",
+        None,
+        Global,
+    ),
+    cmd("help", "Help", "Show shortcuts", Some("?"), Global),
+];
+
+/// What the client currently has focused, reduced to the facts command
+/// scopes depend on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandContext {
+    pub has_message: bool,
+    pub has_calendar: bool,
+    pub has_list_unsubscribe: bool,
+}
+
+impl CommandContext {
+    pub fn from_email(email: Option<&Email>) -> Self {
+        match email {
+            None => Self::default(),
+            Some(e) => Self {
+                has_message: true,
+                has_calendar: e.has_calendar,
+                has_list_unsubscribe: e.list_unsubscribe.as_deref().is_some_and(|h| !h.is_empty()),
+            },
+        }
+    }
+}
+
+impl Command {
+    pub fn is_applicable(&self, ctx: &CommandContext) -> bool {
+        match self.scope {
+            CommandScope::Global => true,
+            CommandScope::Message => ctx.has_message,
+            CommandScope::Calendar => ctx.has_message && ctx.has_calendar,
+            CommandScope::Unsubscribe => ctx.has_message && ctx.has_list_unsubscribe,
+        }
+    }
+}
+
+/// Keys the list and detail views handle outside the registry (movement,
+/// opening, chords, account switching); no command may take them.
+const RESERVED_KEYS: &[&str] = &[
+    "j", "k", "g", "G", "o", "q", " ", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+];
+
+/// On-disk shape of `keybindings.json`: command id → key, or `null` for
+/// no key.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KeymapConfig {
+    #[serde(default)]
+    pub bindings: BTreeMap<String, Option<String>>,
+}
+
+pub fn load_keymap_config(path: &Path) -> KeymapConfig {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return KeymapConfig::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring {}: {e}", path.display());
+        KeymapConfig::default()
+    })
+}
+
+/// The key each command is bound to: its default from [`COMMANDS`] unless
+/// `keybindings.json` remaps it.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    keys: HashMap<&'static str, Option<String>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::resolve(&KeymapConfig::default())
+    }
+}
+
+impl Keymap {
+    pub fn load(path: &Path) -> Self {
+        Self::resolve(&load_keymap_config(path))
+    }
+
+    /// Apply `config` over the defaults; see the module docs for what is
+    /// ignored.
+    pub fn resolve(config: &KeymapConfig) -> Self {
+        let mut keys: HashMap<&'static str, Option<String>> = COMMANDS
+            .iter()
+            .map(|c| (c.id, c.key.map(str::to_string)))
+            .collect();
+        let mut overridden = Vec::new();
+        for (id, key) in &config.bindings {
+            let Some(command) = COMMANDS.iter().find(|c| c.id == id) else {
+                tracing::warn!("keybindings.json: no command '{id}'");
+                continue;
+            };
+            let usable = key
+                .as_deref()
+                .is_none_or(|k| k.chars().count() == 1 && !RESERVED_KEYS.contains(&k));
+            if !usable {
+                tracing::warn!(
+                    "keybindings.json: {key:?} can't be bound to '{id}'; use one key the list view doesn't use"
+                );
+                continue;
+            }
+            keys.insert(command.id, key.clone());
+            overridden.push(command.id);
+        }
+        // Overrides that land on a key another command holds go back to
+        // their defaults, which can take another override's key; repeat
+        // until no key is shared.
+        loop {
+            let clashes: Vec<&'static str> = overridden
+                .iter()
+                .copied()
+                .filter(|id| {
+                    keys[id].as_deref().is_some_and(|k| {
+                        keys.iter()
+                            .any(|(other, key)| other != id && key.as_deref() == Some(k))
+                    })
+                })
+                .collect();
+            if clashes.is_empty() {
+                break;
+            }
+            for id in clashes {
+                tracing::warn!(
+                    "keybindings.json: {:?} for '{id}' is already bound; keeping the default",
+                    keys[id]
+                );
+                let default = COMMANDS.iter().find(|c| c.id == id).and_then(|c| c.key);
+                keys.insert(id, default.map(str::to_string));
+                overridden.retain(|o| *o != id);
+            }
+        }
+        Self { keys }
+    }
+
+    /// The key `command` is bound to, if any.
+    pub fn key(&self, command: &Command) -> Option<&str> {
+        self.keys.get(command.id).and_then(|k| k.as_deref())
+    }
+}
+
+/// A registry entry as served by `/api/commands`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandView {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The key after `keybindings.json`.
+    pub key: Option<String>,
+    /// The built-in key, which the client's own key handler knows.
+    pub default_key: Option<&'static str>,
+    pub scope: CommandScope,
+    pub applicable: bool,
+}
+
+/// The full registry with keys from `keymap` and applicability resolved
+/// against `ctx`.
+pub fn describe(ctx: &CommandContext, keymap: &Keymap) -> Vec<CommandView> {
+    COMMANDS
+        .iter()
+        .map(|c| CommandView {
+            id: c.id,
+            name: c.name,
+            description: c.description,
+            key: keymap.key(c).map(str::to_string),
+            default_key: c.key,
+            scope: c.scope,
+            applicable: c.is_applicable(ctx),
+        })
+        .collect()
+}
+
+/// One line of the help overlay: keys that do the same thing (`j` / `k`
+/// share a line) and what they do.
+#[derive(Debug, Clone, Serialize)]
+pub struct HelpEntry {
+    pub keys: Vec<String>,
+    pub description: &'static str,
+}
+
+/// A titled group of help lines.
+#[derive(Debug, Clone, Serialize)]
+pub struct HelpSection {
+    pub title: &'static str,
+    pub entries: Vec<HelpEntry>,
+}
+
+/// Keys handled outside the command registry: movement, modal views,
+/// chords. Listed in the help overlay around the registry's own keys.
+const NAVIGATION_KEYS: &[(&[&str], &str)] = &[
+    (&["j", "k"], "Move down/up"),
+    (&["g g"], "Go to top"),
+    (&["G"], "Go to bottom"),
+    (&["Enter", "o"], "Open email"),
+    (&["q", "Esc"], "Back to list"),
+    (&["Tab", "Shift+Tab"], "Cycle split tabs"),
+];
+const OTHER_KEYS: &[(&[&str], &str)] = &[
+    (&["Ctrl+k"], "Command palette"),
+    (&["Ctrl+1-9"], "Jump to split tab"),
+    (&["1-9"], "Switch account"),
+    (&["g s"], "Open account settings"),
+];
+const COMPOSE_KEYS: &[(&[&str], &str)] = &[
+    (&["Esc"], "Normal mode (again: cancel, draft kept)"),
+    (&["i"], "Insert mode (edit body)"),
+    (&["Ctrl+Enter"], "Send"),
+    (&["a"], "Attach file (normal mode)"),
+];
+const SETTINGS_KEYS: &[(&[&str], &str)] = &[
+    (&["a"], "Add account"),
+    (&["d"], "Delete (then confirm)"),
+    (&["Shift+D"], "Set default"),
+    (&["Ctrl+Enter"], "Save"),
+];
+
+fn entries(keys: &[(&[&'static str], &'static str)]) -> Vec<HelpEntry> {
+    keys.iter()
+        .map(|(keys, description)| HelpEntry {
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            description,
+        })
+        .collect()
+}
+
+/// The keyboard cheatsheet served by `/api/help`. Bound commands come
+/// from [`COMMANDS`] with their keys from `keymap`: those that act on a
+/// message under "Actions", the rest under "Other" ahead of the fixed
+/// keys. A command left without a key isn't listed.
+pub fn help(keymap: &Keymap) -> Vec<HelpSection> {
+    let bound = |global: bool| {
+        COMMANDS
+            .iter()
+            .filter(move |c| (c.scope == CommandScope::Global) == global)
+            .filter_map(|c| {
+                keymap.key(c).map(|key| HelpEntry {
+                    keys: vec![key.to_string()],
+                    description: c.description,
+                })
+            })
+    };
+    vec![
+        HelpSection {
+            title: "Navigation",
+            entries: entries(NAVIGATION_KEYS),
+        },
+        HelpSection {
+            title: "Actions",
+            entries: bound(false).collect(),
+        },
+        HelpSection {
+            title: "Other",
+            entries: bound(true).chain(entries(OTHER_KEYS)).collect(),
+        },
+        HelpSection {
+            title: "Compose (vim modes)",
+            entries: entries(COMPOSE_KEYS),
+        },
+        HelpSection {
+            title: "Settings (account management)",
+            entries: entries(SETTINGS_KEYS),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applicable_ids(ctx: &CommandContext) -> Vec<&'static str> {
+        describe(ctx, &Keymap::default())
+            .into_iter()
+            .filter(|c| c.applicable)
+            .map(|c| c.id)
+            .collect()
+    }
+
+    #[test]
+    fn ids_and_keys_are_unique() {
+        let mut ids: Vec<_> = COMMANDS.iter().map(|c| c.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), COMMANDS.len());
+        let mut keys: Vec<_> = COMMANDS.iter().filter_map(|c| c.key).collect();
+        let bound = keys.len();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), bound, "two commands share a key binding");
+    }
+
+    #[test]
+    fn help_lists_every_bound_command_once() {
+        let sections = help(&Keymap::default());
+        let listed: Vec<_> = sections
+            .iter()
+            .filter(|s| s.title == "Actions" || s.title == "Other")
+            .flat_map(|s| &s.entries)
+            .map(|e| e.description)
+            .collect();
+        for c in COMMANDS.iter().filter(|c| c.key.is_some()) {
+            let times = listed.iter().filter(|d| **d == c.description).count();
+            assert_eq!(times, 1, "{} listed {times} times", c.id);
+        }
+        let actions = &sections
+            .iter()
+            .find(|s| s.title == "Actions")
+            .unwrap()
+            .entries;
+        assert!(
+            actions
+                .iter()
+                .any(|e| e.keys == ["m"] && e.description == "RSVP maybe")
+        );
+    }
+
+    fn keymap(bindings: &[(&str, Option<&str>)]) -> Keymap {
+        Keymap::resolve(&KeymapConfig {
+            bindings: bindings
+                .iter()
+                .map(|(id, key)| (id.to_string(), key.map(str::to_string)))
+                .collect(),
+        })
+    }
+
+    fn key_of<'a>(keymap: &'a Keymap, id: &str) -> Option<&'a str> {
+        keymap.key(COMMANDS.iter().find(|c| c.id == id).unwrap())
+    }
+
+    #[test]
+    fn keymap_remaps_unbinds_and_swaps() {
+        let km = keymap(&[
+            ("snooze", Some("Z")),
+            ("trash", None),
+            ("archive", Some("r")),
+            ("reply", Some("e")),
+        ]);
+        assert_eq!(key_of(&km, "snooze"), Some("Z"));
+        assert_eq!(key_of(&km, "trash"), None);
+        assert_eq!(key_of(&km, "archive"), Some("r"));
+        assert_eq!(key_of(&km, "reply"), Some("e"));
+        assert_eq!(key_of(&km, "compose"), Some("c"));
+        let views = describe(&CommandContext::default(), &km);
+        let archive = views.iter().find(|v| v.id == "archive").unwrap();
+        assert_eq!(
+            (archive.key.as_deref(), archive.default_key),
+            (Some("r"), Some("e"))
+        );
+    }
+
+    #[test]
+    fn keymap_ignores_clashes_reserved_keys_and_unknown_ids() {
+        let km = keymap(&[
+            ("archive", Some("r")),
+            ("snooze", Some("j")),
+            ("forward", Some("ctrl+f")),
+            ("nope", Some("x")),
+        ]);
+        // `r` still belongs to reply, so archive keeps `e`.
+        assert_eq!(key_of(&km, "archive"), Some("e"));
+        assert_eq!(key_of(&km, "reply"), Some("r"));
+        assert_eq!(key_of(&km, "snooze"), None);
+        assert_eq!(key_of(&km, "forward"), Some("f"));
+        // Two overrides onto one free key: both fall back.
+        let km = keymap(&[("snooze", Some("x")), ("flag-color", Some("x"))]);
+        assert_eq!(key_of(&km, "snooze"), None);
+        assert_eq!(key_of(&km, "flag-color"), None);
+    }
+
+    #[test]
+    fn help_shows_remapped_keys() {
+        let km = keymap(&[
+            ("snooze", Some("Z")),
+            ("trash", None),
+            ("archive", Some("x")),
+        ]);
+        let entries: Vec<HelpEntry> = help(&km).into_iter().flat_map(|s| s.entries).collect();
+        let keys_for = |description: &str| {
+            entries
+                .iter()
+                .filter(|e| e.description == description)
+                .map(|e| e.keys.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys_for("Archive email"), [["x"]]);
+        assert_eq!(keys_for("Hide until later, then return unread"), [["Z"]]);
+        assert!(keys_for("Move to trash").is_empty());
+    }
+
+    #[test]
+    fn keymap_reads_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keybindings.json");
+        assert_eq!(key_of(&Keymap::load(&path), "archive"), Some("e"));
+        std::fs::write(&path, r#"{"bindings": {"archive": "x"}}"#).unwrap();
+        assert_eq!(key_of(&Keymap::load(&path), "archive"), Some("x"));
+    }
+
+    #[test]
+    fn no_focus_offers_only_global_commands() {
+        let ids = applicable_ids(&CommandContext::default());
+        assert!(ids.contains(&"compose"));
+        assert!(!ids.contains(&"archive"));
+        assert!(!ids.contains(&"unsubscribe"));
+    }
+
+    #[test]
+    fn unsubscribe_requires_list_unsubscribe() {
+        let plain = CommandContext {
+            has_message: true,
+            ..Default::default()
+        };
+        assert!(applicable_ids(&plain).contains(&"archive"));
+        assert!(!applicable_ids(&plain).contains(&"unsubscribe"));
+        let list = CommandContext {
+            has_list_unsubscribe: true,
+            ..plain
+        };
+        assert!(applicable_ids(&list).contains(&"unsubscribe"));
+    }
+
+    #[test]
+    fn rsvp_requires_calendar_invite() {
+        let invite = CommandContext {
+            has_message: true,
+            has_calendar: true,
+            ..Default::default()
+        };
+        assert!(applicable_ids(&invite).contains(&"rsvp-accept"));
+        let plain = CommandContext {
+            has_message: true,
+            ..Default::default()
+        };
+        assert!(!applicable_ids(&plain).contains(&"rsvp-accept"));
+    }
+}
@@ -0,0 +1,412 @@
+//! Reply, reply-all and forward drafts built server-side.
+//!
+//! `GET /api/emails/{id}/reply?mode=reply|reply_all|forward` returns what
+//! a compose window should open with: recipients, subject, the quoted
+//! body in text and (when the original had one) HTML, and the threading
+//! headers. Clients send the result through `POST /api/emails/send`
+//! as-is, so the rules below live in one place:
+//!
+//! - Replies go to Reply-To when the original has one, else From. A reply
+//!   to your own message goes to its original recipients instead.
+//! - Reply-all adds the original To and Cc, minus your own addresses and
+//!   anyone already listed; To stays To and Cc stays Cc.
+//! - `Re: ` / `Fwd: ` is added unless the subject already starts with a
+//!   reply (`Re:`, `Aw:`, `Sv:`) or forward (`Fwd:`, `Fw:`) prefix.
+//! - `In-Reply-To` is the original's Message-ID, and `References` is the
+//!   original's References plus that id. Without a Message-ID (Graph list
+//!   fetches, some old mail) `In-Reply-To` is the email id, which every
+//!   provider's send path resolves itself. Forwards carry neither.
+//! - Reply-all to a message that reached you without your address in To
+//!   or Cc (you were Bcc'd) sets `bccWarning`: replying to everyone would
+//!   show them you received it. Mailing list mail is exempt, since it
+//!   reaches you through the list's address.
+//! - Text quotes prefix every line with `> ` under an attribution line;
+//!   HTML quotes wrap the original in `<blockquote type="cite">`. Forwards
+//!   use a header block instead and don't prefix lines.
+
+use crate::error::Error;
+use crate::types::{Attachment, Email, EmailAddress};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Reply,
+    ReplyAll,
+    Forward,
+}
+
+impl Mode {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "reply" => Ok(Self::Reply),
+            "reply_all" => Ok(Self::ReplyAll),
+            "forward" => Ok(Self::Forward),
+            other => Err(Error::BadRequest(format!(
+                "Unknown compose mode: {other} (expected reply, reply_all or forward)"
+            ))),
+        }
+    }
+}
+
+/// What a reply/forward compose window opens with.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComposeDraft {
+    pub to: Vec<EmailAddress>,
+    pub cc: Vec<EmailAddress>,
+    pub subject: String,
+    /// Plain-text body: an empty line to type on, then the quote.
+    pub body: String,
+    /// HTML body, when the original had one.
+    pub html_body: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// The original's attachments, for a forward to offer re-attaching.
+    /// Sending with `forward_of` set to the original's id attaches them all.
+    pub attachments: Vec<Attachment>,
+    /// Reply-all only: why sending to everyone may reveal you were Bcc'd.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bcc_warning: Option<String>,
+}
+
+const BCC_WARNING: &str = "You weren't in To or Cc, so you were probably Bcc'd. \
+    Replying to all shows everyone that you received this message.";
+
+/// Build the `mode` draft for `original`. `own` is every address the
+/// account sends as (username and identities); `tz` dates the attribution.
+pub fn build(original: &Email, mode: Mode, own: &[&str], tz: Tz) -> ComposeDraft {
+    let own: HashSet<String> = own.iter().map(|a| a.to_lowercase()).collect();
+    let is_own = |a: &EmailAddress| own.contains(&a.email.to_lowercase());
+    let date = original
+        .received_at
+        .with_timezone(&tz)
+        .format("%a, %-d %b %Y at %H:%M")
+        .to_string();
+    let sender = original.from.first();
+    let original_text = original_text(original);
+
+    if mode == Mode::Forward {
+        let mut header = vec!["---------- Forwarded message ---------".to_string()];
+        if let Some(from) = sender {
+            header.push(format!("From: {}", display(from)));
+        }
+        header.push(format!("Date: {date}"));
+        header.push(format!("Subject: {}", original.subject));
+        if !original.to.is_empty() {
+            header.push(format!("To: {}", display_list(&original.to)));
+        }
+        if !original.cc.is_empty() {
+            header.push(format!("Cc: {}", display_list(&original.cc)));
+        }
+        let html_body = original.html_body.as_ref().map(|html| {
+            let header: Vec<String> = header.iter().map(|l| escape_html(l)).collect();
+            format!("<p><br></p><p>{}</p>{html}", header.join("<br>"))
+        });
+        return ComposeDraft {
+            to: Vec::new(),
+            cc: Vec::new(),
+            subject: prefixed(&original.subject, "Fwd: ", &["fwd:", "fw:"]),
+            body: format!("\n\n{}\n\n{original_text}", header.join("\n")),
+            html_body,
+            in_reply_to: None,
+            references: Vec::new(),
+            attachments: original.attachments.clone(),
+            bcc_warning: None,
+        };
+    }
+
+    let from_me = sender.is_some_and(is_own);
+    let mut to: Vec<EmailAddress> = if from_me {
+        original.to.clone()
+    } else if !original.reply_to.is_empty() {
+        original.reply_to.clone()
+    } else {
+        original.from.clone()
+    };
+    let mut cc = Vec::new();
+    if mode == Mode::ReplyAll {
+        if !from_me {
+            to.extend(original.to.iter().cloned());
+        }
+        cc = original.cc.clone();
+    }
+    let bcc_warning = (mode == Mode::ReplyAll)
+        .then(|| bcc_warning(original, &own))
+        .flatten();
+    let mut seen = HashSet::new();
+    let mut keep = |a: &EmailAddress| !is_own(a) && seen.insert(a.email.to_lowercase());
+    to.retain(&mut keep);
+    cc.retain(&mut keep);
+    // Replying to your own message that had no other recipients.
+    if to.is_empty() && cc.is_empty() && from_me {
+        to = original.from.clone();
+    }
+
+    let attribution = match sender {
+        Some(from) => format!("On {date}, {} wrote:", display(from)),
+        None => format!("On {date}, you wrote:"),
+    };
+    let quoted: Vec<String> = original_text
+        .lines()
+        .map(|l| {
+            if l.is_empty() {
+                ">".into()
+            } else {
+                format!("> {l}")
+            }
+        })
+        .collect();
+    let html_body = original.html_body.as_ref().map(|html| {
+        format!(
+            "<p><br></p><p>{}</p><blockquote type=\"cite\">{html}</blockquote>",
+            escape_html(&attribution)
+        )
+    });
+    let mut references = original.references.clone();
+    if let Some(id) = &original.message_id
+        && !references.contains(id)
+    {
+        references.push(id.clone());
+    }
+
+    ComposeDraft {
+        to,
+        cc,
+        subject: prefixed(&original.subject, "Re: ", &["re:", "aw:", "sv:"]),
+        body: format!("\n\n{attribution}\n{}", quoted.join("\n")),
+        html_body,
+        in_reply_to: Some(
+            original
+                .message_id
+                .clone()
+                .unwrap_or_else(|| original.id.clone()),
+        ),
+        references,
+        attachments: Vec::new(),
+        bcc_warning,
+    }
+}
+
+/// Why replying to all of `original` would reveal you received it: none
+/// of `own` (lowercased) is in its To or Cc. Your own messages and list
+/// mail (`List-Unsubscribe`), which names the list instead, never warn.
+pub fn bcc_warning(original: &Email, own: &HashSet<String>) -> Option<String> {
+    let is_own = |a: &EmailAddress| own.contains(&a.email.to_lowercase());
+    let bcc = original.list_unsubscribe.is_none()
+        && !original.from.iter().any(is_own)
+        && (!original.to.is_empty() || !original.cc.is_empty())
+        && !original.to.iter().chain(&original.cc).any(is_own);
+    bcc.then(|| BCC_WARNING.to_string())
+}
+
+fn prefixed(subject: &str, prefix: &str, existing: &[&str]) -> String {
+    let lower = subject.trim_start().to_lowercase();
+    if existing.iter().any(|p| lower.starts_with(p)) {
+        subject.trim_start().to_string()
+    } else {
+        format!("{prefix}{subject}")
+    }
+}
+
+fn display(a: &EmailAddress) -> String {
+    match a.name.as_deref().filter(|n| !n.is_empty()) {
+        Some(name) => format!("{name} <{}>", a.email),
+        None => a.email.clone(),
+    }
+}
+
+pub(crate) fn display_list(list: &[EmailAddress]) -> String {
+    list.iter().map(display).collect::<Vec<_>>().join(", ")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+static BLOCK_BREAK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>|</(p|div|li|tr|h[1-6]|blockquote)\s*>").unwrap());
+static INVISIBLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<(style|script|head)\b.*?</(style|script|head)\s*>").unwrap()
+});
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+static BLANK_RUN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").unwrap());
+
+/// The original's text body, or its HTML flattened to text.
+fn original_text(original: &Email) -> String {
+    if let Some(text) = original
+        .text_body
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+    {
+        return text.replace("\r\n", "\n").trim_end().to_string();
+    }
+    match original.html_body.as_deref() {
+        Some(html) => html_to_text(html),
+        None => original.preview.clone(),
+    }
+}
+
+/// `html` flattened to text: block ends become line breaks, tags and
+/// `<style>`/`<script>` go, common entities are decoded.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let text = INVISIBLE.replace_all(html, "");
+    let text = BLOCK_BREAK.replace_all(&text, "\n");
+    let text = TAG.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    BLANK_RUN
+        .replace_all(lines.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(email: &str) -> EmailAddress {
+        EmailAddress {
+            name: None,
+            email: email.into(),
+        }
+    }
+
+    fn emails(list: &[EmailAddress]) -> Vec<&str> {
+        list.iter().map(|a| a.email.as_str()).collect()
+    }
+
+    fn original() -> Email {
+        let mut email: Email = serde_json::from_value(serde_json::json!({
+            "id": "e1",
+            "blob_id": "b1",
+            "thread_id": "t1",
+            "mailbox_ids": {},
+            "keywords": {},
+            "received_at": "2026-03-03T14:05:00Z",
+            "subject": "Quarterly plan",
+            "from": [{ "name": "Alice", "email": "alice@example.com" }],
+            "to": [{ "name": null, "email": "me@example.com" }, { "name": null, "email": "bob@example.com" }],
+            "cc": [{ "name": null, "email": "carol@example.com" }, { "name": null, "email": "Bob@example.com" }],
+            "preview": "",
+            "has_attachment": false,
+            "size": 0,
+            "text_body": "Numbers attached.\n\nThanks",
+            "html_body": null,
+            "has_calendar": false,
+            "attachments": []
+        }))
+        .unwrap();
+        email.message_id = Some("<m2@example.com>".into());
+        email.references = vec!["<m1@example.com>".into()];
+        email
+    }
+
+    #[test]
+    fn reply_quotes_and_threads() {
+        let draft = build(&original(), Mode::Reply, &["me@example.com"], Tz::UTC);
+        assert_eq!(emails(&draft.to), ["alice@example.com"]);
+        assert!(draft.cc.is_empty());
+        assert_eq!(draft.subject, "Re: Quarterly plan");
+        assert_eq!(
+            draft.body,
+            "\n\nOn Tue, 3 Mar 2026 at 14:05, Alice <alice@example.com> wrote:\n\
+             > Numbers attached.\n>\n> Thanks"
+        );
+        assert_eq!(draft.in_reply_to.as_deref(), Some("<m2@example.com>"));
+        assert_eq!(draft.references, ["<m1@example.com>", "<m2@example.com>"]);
+        assert_eq!(draft.html_body, None);
+    }
+
+    #[test]
+    fn reply_all_drops_own_addresses_and_duplicates() {
+        let draft = build(&original(), Mode::ReplyAll, &["ME@example.com"], Tz::UTC);
+        assert_eq!(emails(&draft.to), ["alice@example.com", "bob@example.com"]);
+        assert_eq!(emails(&draft.cc), ["carol@example.com"]);
+    }
+
+    #[test]
+    fn reply_all_warns_when_you_were_bcc_d() {
+        let draft = build(&original(), Mode::ReplyAll, &["me@example.com"], Tz::UTC);
+        assert_eq!(draft.bcc_warning, None);
+
+        let draft = build(
+            &original(),
+            Mode::ReplyAll,
+            &["hidden@example.com"],
+            Tz::UTC,
+        );
+        assert!(draft.bcc_warning.is_some());
+        let json = serde_json::to_value(&draft).unwrap();
+        assert!(json["bccWarning"].is_string());
+
+        let reply = build(&original(), Mode::Reply, &["hidden@example.com"], Tz::UTC);
+        assert_eq!(reply.bcc_warning, None);
+
+        let mut list_mail = original();
+        list_mail.list_unsubscribe = Some("<mailto:leave@example.com>".into());
+        let draft = build(&list_mail, Mode::ReplyAll, &["hidden@example.com"], Tz::UTC);
+        assert_eq!(draft.bcc_warning, None);
+    }
+
+    #[test]
+    fn reply_prefers_reply_to_and_handles_own_messages() {
+        let mut email = original();
+        email.reply_to = vec![addr("list@example.com")];
+        let draft = build(&email, Mode::Reply, &["me@example.com"], Tz::UTC);
+        assert_eq!(emails(&draft.to), ["list@example.com"]);
+
+        let mut sent = original();
+        sent.from = vec![addr("me@example.com")];
+        sent.subject = "RE: Quarterly plan".into();
+        let draft = build(&sent, Mode::Reply, &["me@example.com"], Tz::UTC);
+        assert_eq!(emails(&draft.to), ["bob@example.com"]);
+        assert_eq!(draft.subject, "RE: Quarterly plan");
+    }
+
+    #[test]
+    fn forward_has_a_header_block_and_no_threading() {
+        let mut email = original();
+        email.text_body = None;
+        email.html_body = Some("<style>p{}</style><p>Numbers &amp; notes</p><p>Thanks</p>".into());
+        let draft = build(&email, Mode::Forward, &["me@example.com"], Tz::UTC);
+        assert!(draft.to.is_empty());
+        assert_eq!(draft.subject, "Fwd: Quarterly plan");
+        assert!(draft.in_reply_to.is_none() && draft.references.is_empty());
+        assert!(
+            draft
+                .body
+                .contains("From: Alice <alice@example.com>\nDate: Tue, 3 Mar 2026 at 14:05")
+        );
+        assert!(draft.body.ends_with("\n\nNumbers & notes\nThanks"));
+        let html = draft.html_body.unwrap();
+        assert!(html.contains("From: Alice &lt;alice@example.com&gt;"));
+        assert!(html.ends_with("<p>Thanks</p>"));
+    }
+
+    #[test]
+    fn missing_message_id_falls_back_to_the_email_id() {
+        let mut email = original();
+        email.message_id = None;
+        let draft = build(&email, Mode::Reply, &[], Tz::UTC);
+        assert_eq!(draft.in_reply_to.as_deref(), Some("e1"));
+        assert_eq!(draft.references, ["<m1@example.com>"]);
+    }
+
+    #[test]
+    fn mode_parses_known_values() {
+        assert_eq!(Mode::parse("reply_all").unwrap(), Mode::ReplyAll);
+        assert!(matches!(Mode::parse("replyall"), Err(Error::BadRequest(_))));
+    }
+}
@@ -0,0 +1,303 @@
+//! In-progress compose windows, kept server-side so they follow you
+//! between devices.
+//!
+//! A compose window saves its state (recipients, subject, body, attached
+//! blobs) as a [`ComposeSession`] in `compose-sessions.json` through
+//! `/api/compose-sessions`. Every change is pushed as a `compose` event on
+//! `/api/events`, so a message started on the desktop can be picked up
+//! and finished on the mobile PWA, and the desktop window closes when the
+//! phone sends it and deletes the session.
+//!
+//! Each write bumps `version`. An update that names the version it was
+//! based on fails with 409 when another device got there first, instead
+//! of silently overwriting that device's edits. Sessions idle for
+//! [`IDLE_DAYS`] are pruned on write, and only the [`MAX_SESSIONS`] most
+//! recently touched are kept.
+
+use crate::error::Error;
+use crate::types::Attachment;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Sessions not written for this long are dropped.
+pub const IDLE_DAYS: i64 = 14;
+
+/// Most sessions kept; the least recently updated go first.
+pub const MAX_SESSIONS: usize = 50;
+
+/// The part of a compose window a client writes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComposeFields {
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub from_address: Option<String>,
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub html_body: Option<String>,
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// Already-uploaded blobs (`/api/upload`), not their bytes.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComposeSession {
+    pub id: String,
+    /// Starts at 1 and goes up by one on every update.
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub fields: ComposeFields,
+}
+
+/// On-disk shape of `compose-sessions.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComposeSessions {
+    #[serde(default)]
+    pub sessions: Vec<ComposeSession>,
+}
+
+impl ComposeSessions {
+    pub fn create(&mut self, fields: ComposeFields, now: DateTime<Utc>) -> ComposeSession {
+        let session = ComposeSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            fields,
+        };
+        self.sessions.push(session.clone());
+        self.prune(now);
+        session
+    }
+
+    /// Replace `id`'s fields. With `base_version`, fail with
+    /// `Error::Conflict` unless that is still the stored version.
+    pub fn update(
+        &mut self,
+        id: &str,
+        fields: ComposeFields,
+        base_version: Option<u64>,
+        now: DateTime<Utc>,
+    ) -> Result<ComposeSession, Error> {
+        let session = self
+            .sessions
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| Error::NotFound(format!("Compose session '{id}' not found")))?;
+        if let Some(base) = base_version
+            && base != session.version
+        {
+            return Err(Error::Conflict(format!(
+                "compose session '{id}' is at version {}, not {base}",
+                session.version
+            )));
+        }
+        session.fields = fields;
+        session.version += 1;
+        session.updated_at = now;
+        let session = session.clone();
+        self.prune(now);
+        Ok(session)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<ComposeSession> {
+        let pos = self.sessions.iter().position(|s| s.id == id)?;
+        Some(self.sessions.remove(pos))
+    }
+
+    pub fn find(&self, id: &str) -> Option<&ComposeSession> {
+        self.sessions.iter().find(|s| s.id == id)
+    }
+
+    /// Most recently updated first.
+    pub fn recent(&self) -> Vec<ComposeSession> {
+        let mut out = self.sessions.clone();
+        out.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        out
+    }
+
+    /// Drop sessions idle past [`IDLE_DAYS`], then all but the
+    /// [`MAX_SESSIONS`] most recent.
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - chrono::Duration::days(IDLE_DAYS);
+        self.sessions.retain(|s| s.updated_at >= cutoff);
+        if self.sessions.len() > MAX_SESSIONS {
+            self.sessions = self.recent();
+            self.sessions.truncate(MAX_SESSIONS);
+        }
+    }
+}
+
+/// `compose` event payload. Clients refetch the session when `version` is
+/// newer than what they hold, and close the window when `deleted`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComposeEvent {
+    pub id: String,
+    pub version: u64,
+    pub deleted: bool,
+}
+
+/// Write lock for `compose-sessions.json` plus the broadcast that feeds
+/// `/api/events`.
+pub struct ComposeHub {
+    /// Serializes load→mutate→save across concurrent requests.
+    pub write_lock: tokio::sync::Mutex<()>,
+    events: tokio::sync::broadcast::Sender<ComposeEvent>,
+}
+
+impl Default for ComposeHub {
+    fn default() -> Self {
+        Self {
+            write_lock: Default::default(),
+            events: tokio::sync::broadcast::channel(64).0,
+        }
+    }
+}
+
+impl ComposeHub {
+    pub fn publish(&self, session: &ComposeSession, deleted: bool) {
+        // No receivers is the normal idle case, not an error.
+        let _ = self.events.send(ComposeEvent {
+            id: session.id.clone(),
+            version: session.version,
+            deleted,
+        });
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ComposeEvent> {
+        self.events.subscribe()
+    }
+}
+
+// =============================================================================
+// Load/save
+// =============================================================================
+
+pub fn load_sessions(path: &Path) -> ComposeSessions {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read compose sessions: {e}");
+                return ComposeSessions::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    ComposeSessions::default()
+}
+
+pub fn save_sessions(sessions: &ComposeSessions, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(sessions)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(subject: &str) -> ComposeFields {
+        ComposeFields {
+            to: vec!["ann@example.com".into()],
+            subject: subject.into(),
+            body: "Draft text".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_bumps_version_and_rejects_stale_base() {
+        let mut store = ComposeSessions::default();
+        let now = Utc::now();
+        let created = store.create(fields("One"), now);
+        assert_eq!(created.version, 1);
+
+        let updated = store
+            .update(&created.id, fields("Two"), Some(1), now)
+            .unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(store.find(&created.id).unwrap().fields.subject, "Two");
+
+        let err = store
+            .update(&created.id, fields("Stale"), Some(1), now)
+            .unwrap_err();
+        assert!(matches!(err, Error::Conflict(_)), "got {err:?}");
+        assert_eq!(store.find(&created.id).unwrap().fields.subject, "Two");
+
+        // No base version: last write wins.
+        assert_eq!(
+            store
+                .update(&created.id, fields("Three"), None, now)
+                .unwrap()
+                .version,
+            3
+        );
+        assert!(matches!(
+            store.update("missing", fields("x"), None, now),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn prune_drops_idle_and_caps_count() {
+        let mut store = ComposeSessions::default();
+        let now = Utc::now();
+        store.create(fields("Idle"), now - chrono::Duration::days(IDLE_DAYS + 1));
+        for i in 0..MAX_SESSIONS {
+            store.create(
+                fields(&format!("s{i}")),
+                now - chrono::Duration::minutes(i as i64),
+            );
+        }
+        let newest = store.create(fields("Newest"), now + chrono::Duration::seconds(1));
+        assert_eq!(store.sessions.len(), MAX_SESSIONS);
+        assert!(store.sessions.iter().all(|s| s.fields.subject != "Idle"));
+        let oldest = format!("s{}", MAX_SESSIONS - 1);
+        assert!(store.sessions.iter().all(|s| s.fields.subject != oldest));
+        assert_eq!(store.recent()[0].id, newest.id);
+    }
+
+    #[test]
+    fn session_serializes_fields_flat() {
+        let mut store = ComposeSessions::default();
+        let session = store.create(fields("Hi"), Utc::now());
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(json["subject"], "Hi");
+        assert_eq!(json["version"], 1);
+        assert!(json.get("fields").is_none());
+    }
+
+    #[test]
+    fn save_then_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("compose-sessions.json");
+        let mut store = ComposeSessions::default();
+        let session = store.create(fields("Hi"), Utc::now());
+        save_sessions(&store, &path).unwrap();
+        let loaded = load_sessions(&path);
+        assert_eq!(loaded.find(&session.id).unwrap().fields.subject, "Hi");
+        assert!(
+            load_sessions(&dir.path().join("missing.json"))
+                .sessions
+                .is_empty()
+        );
+    }
+}
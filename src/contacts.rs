@@ -0,0 +1,816 @@
+//! Read-only address book sync.
+//!
+//! Every fifteen minutes the syncer ([`spawn_sync`]) pulls each Fastmail
+//! account's default CardDAV address book and folds its vCards into
+//! `contacts.json`: contact names and addresses plus contact groups, kept
+//! per account. The index backs compose autocomplete (`GET /api/contacts`),
+//! and its groups expand as `group:<id>` recipients on send next to the
+//! hand-made ones in `groups.json` (see [`with_address_book_groups`]).
+//! Nothing is written back to the server. Outlook and Gmail accounts have
+//! no CardDAV here and aren't synced.
+//!
+//! The same pass harvests every account's recent sent mail
+//! ([`harvest_account`]): the people written to most often become
+//! [`Correspondent`]s, suggested after address book matches so someone
+//! never added as a contact still autocompletes.
+
+use crate::error::Error;
+use crate::groups::{GroupsConfig, RecipientGroup};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub uid: String,
+    pub name: String,
+    pub emails: Vec<String>,
+}
+
+/// One account's synced address book.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressBook {
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+    /// Contact groups, with members resolved to addresses. Ids are slugs
+    /// of the group name, so `group:book-club` can be typed by hand.
+    #[serde(default)]
+    pub groups: Vec<RecipientGroup>,
+    pub synced_at: Option<DateTime<Utc>>,
+    /// Recipients of recent sent mail, most written to first. Kept across
+    /// address book syncs; replaced by each harvest.
+    #[serde(default)]
+    pub correspondents: Vec<Correspondent>,
+}
+
+/// Someone the account has sent mail to, from [`harvest_correspondents`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Correspondent {
+    pub email: String,
+    /// Name from the most recent To/Cc header that had one.
+    #[serde(default)]
+    pub name: String,
+    /// Messages in the harvested sample addressed to them.
+    pub count: u32,
+    pub last_sent: DateTime<Utc>,
+}
+
+/// Sent messages sampled per harvest.
+pub const HARVEST_SAMPLE: usize = 300;
+
+/// Correspondents kept per account.
+pub const MAX_CORRESPONDENTS: usize = 500;
+
+/// One autocomplete result: a single address of a contact.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ContactMatch {
+    pub name: String,
+    pub email: String,
+}
+
+impl AddressBook {
+    /// Contacts' addresses matching `query`, by address prefix or anywhere
+    /// in the name, sorted by name, then matching correspondents not in
+    /// the address book, most written to first. An empty query matches
+    /// everything.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<ContactMatch> {
+        let q = query.trim().to_lowercase();
+        let matches = |m: &ContactMatch| {
+            q.is_empty()
+                || m.email.to_lowercase().starts_with(&q)
+                || m.name.to_lowercase().contains(&q)
+        };
+        let mut out: Vec<ContactMatch> = self
+            .contacts
+            .iter()
+            .flat_map(|c| {
+                c.emails.iter().map(|e| ContactMatch {
+                    name: c.name.clone(),
+                    email: e.clone(),
+                })
+            })
+            .filter(matches)
+            .collect();
+        out.sort_by(|a, b| {
+            a.name
+                .to_lowercase()
+                .cmp(&b.name.to_lowercase())
+                .then_with(|| a.email.cmp(&b.email))
+        });
+        let known: HashSet<String> = out.iter().map(|m| m.email.to_lowercase()).collect();
+        let in_book = self.display_names();
+        out.extend(
+            self.correspondents
+                .iter()
+                .filter(|c| !in_book.contains_key(&c.email.to_lowercase()))
+                .map(|c| ContactMatch {
+                    name: c.name.clone(),
+                    email: c.email.clone(),
+                })
+                .filter(|m| matches(m) && !known.contains(&m.email.to_lowercase())),
+        );
+        out.truncate(limit);
+        out
+    }
+
+    /// Lowercased address → contact name, for contacts that have a name.
+    pub fn display_names(&self) -> HashMap<String, String> {
+        self.contacts
+            .iter()
+            .filter(|c| !c.name.is_empty())
+            .flat_map(|c| c.emails.iter().map(|e| (e.to_lowercase(), c.name.clone())))
+            .collect()
+    }
+}
+
+/// On-disk shape of `contacts.json`: account id → address book.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ContactsIndex {
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AddressBook>,
+}
+
+/// `config` plus `book`'s groups. A hand-made group keeps its id when an
+/// address book group would collide with it.
+pub fn with_address_book_groups(
+    mut config: GroupsConfig,
+    book: Option<&AddressBook>,
+) -> GroupsConfig {
+    for group in book.map(|b| b.groups.as_slice()).unwrap_or_default() {
+        if !config.groups.iter().any(|g| g.id == group.id) {
+            config.groups.push(group.clone());
+        }
+    }
+    config
+}
+
+// =============================================================================
+// vCard parsing
+// =============================================================================
+
+#[derive(Debug, Default)]
+struct Card {
+    uid: String,
+    name: String,
+    emails: Vec<String>,
+    is_group: bool,
+    /// `MEMBER` values: `urn:uuid:<uid>` references or `mailto:` addresses.
+    members: Vec<String>,
+}
+
+/// `<address-data>` contents from a CardDAV multistatus response, with
+/// XML escapes undone.
+pub fn extract_address_data(xml: &str) -> Vec<String> {
+    static ADDRESS_DATA: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)<(?:[\w-]+:)?address-data\b[^>]*>(.*?)</(?:[\w-]+:)?address-data>")
+            .expect("valid regex")
+    });
+    ADDRESS_DATA
+        .captures_iter(xml)
+        .map(|c| {
+            let raw = c[1].trim();
+            match raw
+                .strip_prefix("<![CDATA[")
+                .and_then(|r| r.strip_suffix("]]>"))
+            {
+                Some(cdata) => cdata.to_string(),
+                None => xml_unescape(raw),
+            }
+        })
+        .collect()
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&#10;", "\n")
+        .replace("&#xA;", "\n")
+        .replace("&amp;", "&")
+}
+
+/// Undo vCard text escaping (`\,` `\;` `\n` `\\`).
+fn unescape_value(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push(' '),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Split a content line into upper-cased property name (group prefix like
+/// `item1.` dropped) and value. Colons inside quoted parameters don't end
+/// the name.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let name = line[..colon].split(';').next()?;
+    let name = name.rsplit('.').next()?.to_ascii_uppercase();
+    Some((name, &line[colon + 1..]))
+}
+
+fn parse_vcards(text: &str) -> Vec<Card> {
+    let unfolded = text
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut cards = Vec::new();
+    let mut current: Option<Card> = None;
+    let mut structured_name: Option<String> = None;
+    for line in unfolded.lines() {
+        let line = line.trim_end_matches('\r');
+        let Some((name, value)) = split_line(line) else {
+            continue;
+        };
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(Card::default());
+                structured_name = None;
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                let mut card = current.take().expect("checked above");
+                if card.name.is_empty() {
+                    card.name = structured_name.take().unwrap_or_default();
+                }
+                cards.push(card);
+            }
+            ("UID", Some(card)) => card.uid = value.trim().to_string(),
+            ("FN", Some(card)) => card.name = unescape_value(value),
+            ("N", Some(_)) => {
+                // Family;Given;Additional;Prefix;Suffix → "Given Family".
+                let parts: Vec<String> = value.split(';').map(unescape_value).collect();
+                let given = parts.get(1).map(String::as_str).unwrap_or("");
+                let family = parts.first().map(String::as_str).unwrap_or("");
+                let joined = format!("{given} {family}").trim().to_string();
+                if !joined.is_empty() {
+                    structured_name = Some(joined);
+                }
+            }
+            ("EMAIL", Some(card)) => {
+                let email = strip_mailto(&unescape_value(value));
+                if email.contains('@') && !card.emails.contains(&email) {
+                    card.emails.push(email);
+                }
+            }
+            ("KIND" | "X-ADDRESSBOOKSERVER-KIND", Some(card)) => {
+                card.is_group = value.trim().eq_ignore_ascii_case("group");
+            }
+            ("MEMBER" | "X-ADDRESSBOOKSERVER-MEMBER", Some(card)) => {
+                card.members.push(value.trim().to_string());
+            }
+            _ => {}
+        }
+    }
+    cards
+}
+
+fn strip_mailto(s: &str) -> String {
+    let s = s.trim();
+    match s.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("mailto:") => s[7..].trim().to_string(),
+        _ => s.to_string(),
+    }
+}
+
+/// Group id for `name`: lowercase alphanumerics joined by dashes.
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Index the vCards in `texts` (each may hold several cards). Group
+/// members are resolved to each member contact's first address; members
+/// that aren't in the book are dropped, and so are groups left empty.
+pub fn build_address_book(texts: &[String], now: DateTime<Utc>) -> AddressBook {
+    let cards: Vec<Card> = texts.iter().flat_map(|t| parse_vcards(t)).collect();
+    let (group_cards, person_cards): (Vec<Card>, Vec<Card>) =
+        cards.into_iter().partition(|c| c.is_group);
+
+    let contacts: Vec<Contact> = person_cards
+        .into_iter()
+        .filter(|c| !c.emails.is_empty())
+        .map(|c| Contact {
+            uid: c.uid,
+            name: c.name,
+            emails: c.emails,
+        })
+        .collect();
+    let first_email: HashMap<&str, &str> = contacts
+        .iter()
+        .filter(|c| !c.uid.is_empty())
+        .map(|c| (c.uid.as_str(), c.emails[0].as_str()))
+        .collect();
+
+    let mut used_ids = HashSet::new();
+    let mut groups = Vec::new();
+    for card in group_cards {
+        let mut members: Vec<String> = Vec::new();
+        for member in &card.members {
+            let email = match member.get(..9) {
+                Some(p) if p.eq_ignore_ascii_case("urn:uuid:") => {
+                    first_email.get(&member[9..]).map(|e| e.to_string())
+                }
+                _ => Some(strip_mailto(member)).filter(|e| e.contains('@')),
+            };
+            if let Some(email) = email
+                && !members.iter().any(|m| m.eq_ignore_ascii_case(&email))
+            {
+                members.push(email);
+            }
+        }
+        if members.is_empty() {
+            continue;
+        }
+        let base = Some(slug(&card.name))
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| slug(&card.uid));
+        let mut id = base.clone();
+        let mut n = 2;
+        while !used_ids.insert(id.clone()) {
+            id = format!("{base}-{n}");
+            n += 1;
+        }
+        groups.push(RecipientGroup {
+            id,
+            name: card.name,
+            members,
+        });
+    }
+
+    AddressBook {
+        contacts,
+        groups,
+        synced_at: Some(now),
+        correspondents: Vec::new(),
+    }
+}
+
+/// Tally the To and Cc recipients of `sent`, skipping `own` addresses
+/// (a reply-all copies yourself). Most written to first, ties broken by
+/// recency, capped at [`MAX_CORRESPONDENTS`].
+pub fn harvest_correspondents(sent: &[crate::types::Email], own: &[&str]) -> Vec<Correspondent> {
+    let own: HashSet<String> = own.iter().map(|a| a.to_lowercase()).collect();
+    let mut by_address: HashMap<String, Correspondent> = HashMap::new();
+    for email in sent {
+        let mut seen = HashSet::new();
+        for addr in email.to.iter().chain(&email.cc) {
+            let key = addr.email.trim().to_lowercase();
+            if key.is_empty() || own.contains(&key) || !seen.insert(key.clone()) {
+                continue;
+            }
+            let name = addr.name.as_deref().map(str::trim).unwrap_or_default();
+            let entry = by_address.entry(key).or_insert_with(|| Correspondent {
+                email: addr.email.trim().to_string(),
+                name: String::new(),
+                count: 0,
+                last_sent: email.received_at,
+            });
+            entry.count += 1;
+            if !name.is_empty() && (entry.name.is_empty() || email.received_at >= entry.last_sent) {
+                entry.name = name.to_string();
+            }
+            entry.last_sent = entry.last_sent.max(email.received_at);
+        }
+    }
+    let mut out: Vec<Correspondent> = by_address.into_values().collect();
+    out.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| b.last_sent.cmp(&a.last_sent))
+            .then_with(|| a.email.cmp(&b.email))
+    });
+    out.truncate(MAX_CORRESPONDENTS);
+    out
+}
+
+// =============================================================================
+// Index load/save
+// =============================================================================
+
+pub fn load_index(path: &Path) -> ContactsIndex {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read contacts index: {e}");
+                return ContactsIndex::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    ContactsIndex::default()
+}
+
+pub fn save_index(index: &ContactsIndex, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(index)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Sync
+// =============================================================================
+
+/// Fetch `account`'s address book and replace its entry in the index.
+/// `Ok(None)` when the provider has no address book to sync.
+pub async fn sync_account(
+    state: &crate::types::AppState,
+    account: &str,
+) -> Result<Option<AddressBook>, Error> {
+    let session_lock = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .get(account)
+            .cloned()
+            .ok_or(Error::NotConnected)?
+    };
+    let cards = {
+        let session = session_lock.read().await;
+        crate::provider::address_book_cards(&session).await?
+    };
+    let Some(cards) = cards else {
+        return Ok(None);
+    };
+    let mut book = build_address_book(&cards, Utc::now());
+    let _guard = state.contacts_lock.lock().await;
+    let mut index = load_index(&state.contacts_path);
+    if let Some(old) = index.accounts.remove(account) {
+        book.correspondents = old.correspondents;
+    }
+    index.accounts.insert(account.to_string(), book.clone());
+    save_index(&index, &state.contacts_path)?;
+    Ok(Some(book))
+}
+
+/// Re-harvest `account`'s correspondents from its latest
+/// [`HARVEST_SAMPLE`] sent messages. Returns how many were found; zero
+/// when the account has no Sent mailbox.
+pub async fn harvest_account(
+    state: &crate::types::AppState,
+    account: &str,
+) -> Result<usize, Error> {
+    let session_lock = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .get(account)
+            .cloned()
+            .ok_or(Error::NotConnected)?
+    };
+    let identities = state
+        .prefetch
+        .get_identities(account)
+        .await
+        .unwrap_or_default();
+    let (sent, username) = {
+        let session = session_lock.read().await;
+        let mailboxes = crate::provider::get_mailboxes(&session).await?;
+        let Some(sent_box) = mailboxes.iter().find(|m| m.role.as_deref() == Some("sent")) else {
+            return Ok(0);
+        };
+        let ids = crate::provider::query_emails(
+            &session,
+            Some(&sent_box.id),
+            HARVEST_SAMPLE,
+            0,
+            None,
+            crate::types::EmailSort::DateDesc,
+        )
+        .await?;
+        let sent = crate::provider::get_emails(&session, &ids, false, None, false).await?;
+        (sent, session.username().to_string())
+    };
+    let own: Vec<&str> = std::iter::once(username.as_str())
+        .chain(identities.iter().map(|i| i.email.as_str()))
+        .collect();
+    let correspondents = harvest_correspondents(&sent, &own);
+    let found = correspondents.len();
+    let _guard = state.contacts_lock.lock().await;
+    let mut index = load_index(&state.contacts_path);
+    index
+        .accounts
+        .entry(account.to_string())
+        .or_default()
+        .correspondents = correspondents;
+    save_index(&index, &state.contacts_path)?;
+    Ok(found)
+}
+
+/// Sync every connected account's address book and correspondents now
+/// and then every `interval`.
+pub fn spawn_sync(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("contacts", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("contacts");
+            let accounts: Vec<String> = {
+                let reg = state.accounts.read().await;
+                reg.sessions.keys().cloned().collect()
+            };
+            for account in accounts {
+                match sync_account(&state, &account).await {
+                    Ok(Some(book)) => tracing::debug!(
+                        "Synced {} contacts and {} groups for {account}",
+                        book.contacts.len(),
+                        book.groups.len()
+                    ),
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Address book sync failed for {account}: {e}");
+                        health.failed("contacts", format!("{account}: {e}"));
+                    }
+                }
+                match harvest_account(&state, &account).await {
+                    Ok(n) => tracing::debug!("Harvested {n} correspondents for {account}"),
+                    Err(e) => {
+                        tracing::warn!("Correspondent harvest failed for {account}: {e}");
+                        health.failed("contacts", format!("{account}: {e}"));
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARDS: &str = "BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+UID:ann-1\r\n\
+FN:Ann Lee\r\n\
+item1.EMAIL;TYPE=\"work:main\":ann@work.example\r\n\
+EMAIL;TYPE=home:mailto:ann@home.example\r\n\
+END:VCARD\r\n\
+BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+UID:bob-2\r\n\
+N:Ng;Bob;;;\r\n\
+EMAIL:bob@example.com\r\n\
+END:VCARD\r\n\
+BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+UID:nomail\r\n\
+FN:No Address\r\n\
+END:VCARD\r\n\
+BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+UID:grp-1\r\n\
+FN:Book Club\\, Tuesdays\r\n\
+X-ADDRESSBOOKSERVER-KIND:group\r\n\
+X-ADDRESSBOOKSERVER-MEMBER:urn:uuid:ann-1\r\n\
+X-ADDRESSBOOKSERVER-MEMBER:urn:uuid:bob-2\r\n\
+X-ADDRESSBOOKSERVER-MEMBER:urn:uuid:missing\r\n\
+END:VCARD\r\n";
+
+    #[test]
+    fn builds_contacts_and_resolves_group_members() {
+        let book = build_address_book(&[CARDS.to_string()], Utc::now());
+        assert_eq!(
+            book.contacts,
+            vec![
+                Contact {
+                    uid: "ann-1".into(),
+                    name: "Ann Lee".into(),
+                    emails: vec!["ann@work.example".into(), "ann@home.example".into()],
+                },
+                Contact {
+                    uid: "bob-2".into(),
+                    name: "Bob Ng".into(),
+                    emails: vec!["bob@example.com".into()],
+                },
+            ]
+        );
+        assert_eq!(
+            book.groups,
+            vec![RecipientGroup {
+                id: "book-club-tuesdays".into(),
+                name: "Book Club, Tuesdays".into(),
+                members: vec!["ann@work.example".into(), "bob@example.com".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn vcard4_groups_and_folded_lines() {
+        let text = "BEGIN:VCARD\nVERSION:4.0\nUID:a\nFN:Very Long\n  Name\nEMAIL:a@x.example\nEND:VCARD\n\
+BEGIN:VCARD\nVERSION:4.0\nKIND:group\nFN:Team\nMEMBER:urn:uuid:a\nMEMBER:mailto:ext@y.example\nEND:VCARD\n\
+BEGIN:VCARD\nVERSION:4.0\nKIND:group\nFN:Team\nMEMBER:mailto:z@y.example\nEND:VCARD\n";
+        let book = build_address_book(&[text.to_string()], Utc::now());
+        assert_eq!(book.contacts[0].name, "Very Long Name");
+        let ids: Vec<&str> = book.groups.iter().map(|g| g.id.as_str()).collect();
+        assert_eq!(ids, vec!["team", "team-2"]);
+        assert_eq!(book.groups[0].members, vec!["a@x.example", "ext@y.example"]);
+    }
+
+    #[test]
+    fn extracts_escaped_address_data_from_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+  <d:response><d:propstat><d:prop>
+    <card:address-data>BEGIN:VCARD&#13;
+FN:Tom &amp; Jerry&#13;
+EMAIL:tj@example.com&#13;
+END:VCARD&#13;
+</card:address-data>
+  </d:prop></d:propstat></d:response>
+  <d:response><d:propstat><d:prop>
+    <C:address-data><![CDATA[BEGIN:VCARD
+FN:<Raw>
+EMAIL:raw@example.com
+END:VCARD]]></C:address-data>
+  </d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+        let cards = extract_address_data(xml);
+        assert_eq!(cards.len(), 2);
+        let book = build_address_book(&cards, Utc::now());
+        assert_eq!(book.contacts[0].name, "Tom & Jerry");
+        assert_eq!(book.contacts[1].name, "<Raw>");
+    }
+
+    #[test]
+    fn search_matches_address_prefix_or_name() {
+        let book = build_address_book(&[CARDS.to_string()], Utc::now());
+        let emails =
+            |q: &str| -> Vec<String> { book.search(q, 10).into_iter().map(|m| m.email).collect() };
+        assert_eq!(emails("bob"), vec!["bob@example.com"]);
+        assert_eq!(emails("lee"), vec!["ann@home.example", "ann@work.example"]);
+        assert!(emails("example.com").is_empty());
+        assert_eq!(emails("").len(), 3);
+        assert_eq!(book.search("", 1).len(), 1);
+    }
+
+    #[test]
+    fn display_names_key_by_lowercased_address() {
+        let book = build_address_book(&[CARDS.to_string()], Utc::now());
+        let names = book.display_names();
+        assert_eq!(names["ann@home.example"], "Ann Lee");
+        assert_eq!(names["bob@example.com"], "Bob Ng");
+        assert_eq!(names.len(), 3);
+    }
+
+    #[test]
+    fn hand_made_groups_win_on_id_collision() {
+        let book = AddressBook {
+            groups: vec![
+                RecipientGroup {
+                    id: "team".into(),
+                    name: "Team".into(),
+                    members: vec!["book@x.example".into()],
+                },
+                RecipientGroup {
+                    id: "family".into(),
+                    name: "Family".into(),
+                    members: vec!["mum@x.example".into()],
+                },
+            ],
+            ..Default::default()
+        };
+        let local = GroupsConfig {
+            groups: vec![RecipientGroup {
+                id: "team".into(),
+                name: "Team".into(),
+                members: vec!["local@x.example".into()],
+            }],
+        };
+        let merged = with_address_book_groups(local, Some(&book));
+        assert_eq!(merged.groups.len(), 2);
+        assert_eq!(merged.groups[0].members, vec!["local@x.example"]);
+        assert_eq!(merged.groups[1].id, "family");
+    }
+
+    #[test]
+    fn save_then_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contacts.json");
+        let mut index = ContactsIndex::default();
+        index.accounts.insert(
+            "a".into(),
+            build_address_book(&[CARDS.to_string()], Utc::now()),
+        );
+        save_index(&index, &path).unwrap();
+        assert_eq!(load_index(&path), index);
+        assert_eq!(
+            load_index(&dir.path().join("missing.json")),
+            ContactsIndex::default()
+        );
+    }
+
+    fn sent(days_ago: i64, to: &[(&str, Option<&str>)], cc: &[&str]) -> crate::types::Email {
+        let addr = |email: &str, name: Option<&str>| crate::types::EmailAddress {
+            name: name.map(String::from),
+            email: email.into(),
+        };
+        crate::types::Email {
+            id: format!("s{days_ago}"),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: HashMap::new(),
+            keywords: HashMap::new(),
+            received_at: Utc::now() - chrono::Duration::days(days_ago),
+            subject: String::new(),
+            from: vec![],
+            to: to.iter().map(|(e, n)| addr(e, *n)).collect(),
+            cc: cc.iter().map(|e| addr(e, None)).collect(),
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    #[test]
+    fn harvest_ranks_by_frequency_and_skips_own_addresses() {
+        let mail = [
+            sent(1, &[("Dee@x.example", Some("Dee New"))], &["me@x.example"]),
+            sent(2, &[("cat@x.example", None)], &["dee@x.example"]),
+            sent(3, &[("dee@x.example", Some("Dee Old"))], &[]),
+            sent(4, &[("cat@x.example", Some("Cat"))], &["cat@x.example"]),
+        ];
+        let found = harvest_correspondents(&mail, &["ME@x.example"]);
+        let summary: Vec<(&str, &str, u32)> = found
+            .iter()
+            .map(|c| (c.email.as_str(), c.name.as_str(), c.count))
+            .collect();
+        // Dee: three messages, newest name wins. Cat: two, counted once
+        // per message, and the only name is kept.
+        assert_eq!(
+            summary,
+            [("Dee@x.example", "Dee New", 3), ("cat@x.example", "Cat", 2)]
+        );
+    }
+
+    #[test]
+    fn search_suggests_correspondents_after_the_address_book() {
+        let mut book = build_address_book(&[CARDS.to_string()], Utc::now());
+        book.correspondents = harvest_correspondents(
+            &[
+                sent(
+                    1,
+                    &[("annie@x.example", Some("Annie"))],
+                    &["ann@work.example"],
+                ),
+                sent(2, &[("annie@x.example", None)], &[]),
+            ],
+            &[],
+        );
+        let emails: Vec<String> = book
+            .search("ann", 10)
+            .into_iter()
+            .map(|m| m.email)
+            .collect();
+        // The address book's Ann comes first and isn't repeated.
+        assert_eq!(
+            emails,
+            ["ann@home.example", "ann@work.example", "annie@x.example"]
+        );
+        assert_eq!(book.search("ann", 1).len(), 1);
+    }
+}
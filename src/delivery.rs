@@ -0,0 +1,231 @@
+//! Received-chain analysis.
+//!
+//! Every MTA that relays a message prepends a `Received` header ending in
+//! `; <date>`, so the chain read bottom-up is the route the message took.
+//! [`analyze`] turns it into hops with per-hop delays and the total time
+//! from the `Date` header to final delivery, served as `deliveryPath` by
+//! `/api/emails/{id}/headers` for "why did this arrive four hours late".
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::types::EmailHeader;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryPath {
+    /// Oldest hop first.
+    pub hops: Vec<Hop>,
+    /// The `Date` header: when the sender's client says it sent the mail.
+    pub sent_at: Option<DateTime<FixedOffset>>,
+    /// The newest hop's timestamp: final delivery.
+    pub delivered_at: Option<DateTime<FixedOffset>>,
+    /// `delivered_at - sent_at`, or first-to-last hop when `Date` is
+    /// missing or unparseable.
+    pub total_delay_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hop {
+    /// Host the relay received the message from, as it named itself.
+    pub from: Option<String>,
+    /// Host that added this header.
+    pub by: Option<String>,
+    /// Protocol, e.g. `ESMTPS`.
+    pub with: Option<String>,
+    pub timestamp: Option<DateTime<FixedOffset>>,
+    /// Time since the previous hop (or since `Date` for the first one).
+    /// Negative when the two hosts' clocks disagree.
+    pub delay_secs: Option<i64>,
+    /// The header value as received, for anything the parse leaves out.
+    pub raw: String,
+}
+
+/// Build the delivery path from a message's headers (any order of names;
+/// `Received` fields in message order, newest first).
+pub fn analyze(headers: &[EmailHeader]) -> DeliveryPath {
+    let sent_at = headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case("Date"))
+        .and_then(|h| parse_date(&h.value));
+
+    let mut hops: Vec<Hop> = headers
+        .iter()
+        .filter(|h| h.name.eq_ignore_ascii_case("Received"))
+        .map(|h| parse_received(&h.value))
+        .collect();
+    hops.reverse();
+
+    let mut previous = sent_at;
+    for hop in &mut hops {
+        if let (Some(prev), Some(ts)) = (previous, hop.timestamp) {
+            hop.delay_secs = Some((ts - prev).num_seconds());
+        }
+        if hop.timestamp.is_some() {
+            previous = hop.timestamp;
+        }
+    }
+
+    let first_hop = hops.iter().find_map(|h| h.timestamp);
+    let delivered_at = hops.iter().rev().find_map(|h| h.timestamp);
+    let total_delay_secs = match (sent_at.or(first_hop), delivered_at) {
+        (Some(start), Some(end)) => Some((end - start).num_seconds()),
+        _ => None,
+    };
+
+    DeliveryPath {
+        hops,
+        sent_at,
+        delivered_at,
+        total_delay_secs,
+    }
+}
+
+/// Split `from … by … with … ; date` into its clauses. Comments (the
+/// parenthesized HELO names, IPs and TLS details) are skipped, so only the
+/// host each clause names is kept.
+fn parse_received(value: &str) -> Hop {
+    let (clauses, date) = match value.rfind(';') {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+    let clauses = strip_comments(clauses);
+    let words: Vec<&str> = clauses.split_whitespace().collect();
+    let clause = |keyword: &str| {
+        words
+            .iter()
+            .position(|w| w.eq_ignore_ascii_case(keyword))
+            .and_then(|i| words.get(i + 1))
+            .map(|w| w.to_string())
+    };
+    Hop {
+        from: clause("from"),
+        by: clause("by"),
+        with: clause("with"),
+        timestamp: date.and_then(parse_date),
+        delay_secs: None,
+        raw: value.trim().to_string(),
+    }
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// RFC 2822 date, tolerating a trailing comment such as `(UTC)` and the
+/// extra whitespace some relays emit.
+pub(crate) fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let cleaned = strip_comments(value);
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    DateTime::parse_from_rfc2822(&cleaned).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> EmailHeader {
+        EmailHeader {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    fn sample() -> Vec<EmailHeader> {
+        vec![
+            header(
+                "Received",
+                "from mx.relay.example (mx.relay.example [203.0.113.5]) by mail.fastmail.example \
+                 (Postfix) with ESMTPS id 4A1; Tue, 1 Oct 2024 14:00:30 +0000 (UTC)",
+            ),
+            header(
+                "Received",
+                "from sender.example ([198.51.100.7]) by mx.relay.example with ESMTP id x1; \
+                 Tue, 1 Oct 2024 10:00:10 -0000",
+            ),
+            header("Subject", "Late"),
+            header("Date", "Tue, 01 Oct 2024 10:00:00 +0000"),
+        ]
+    }
+
+    #[test]
+    fn hops_are_oldest_first_with_clauses() {
+        let path = analyze(&sample());
+        assert_eq!(path.hops.len(), 2);
+        assert_eq!(path.hops[0].from.as_deref(), Some("sender.example"));
+        assert_eq!(path.hops[0].by.as_deref(), Some("mx.relay.example"));
+        assert_eq!(path.hops[0].with.as_deref(), Some("ESMTP"));
+        assert_eq!(path.hops[1].by.as_deref(), Some("mail.fastmail.example"));
+        assert_eq!(path.hops[1].with.as_deref(), Some("ESMTPS"));
+    }
+
+    #[test]
+    fn delays_are_per_hop_and_total_from_date() {
+        let path = analyze(&sample());
+        assert_eq!(path.hops[0].delay_secs, Some(10));
+        assert_eq!(path.hops[1].delay_secs, Some(4 * 3600 + 20));
+        assert_eq!(path.total_delay_secs, Some(4 * 3600 + 30));
+        assert_eq!(
+            path.delivered_at.unwrap().to_rfc3339(),
+            "2024-10-01T14:00:30+00:00"
+        );
+    }
+
+    #[test]
+    fn total_falls_back_to_first_hop_without_date() {
+        let headers: Vec<_> = sample().into_iter().filter(|h| h.name != "Date").collect();
+        let path = analyze(&headers);
+        assert!(path.sent_at.is_none());
+        assert_eq!(path.hops[0].delay_secs, None);
+        assert_eq!(path.total_delay_secs, Some(4 * 3600 + 20));
+    }
+
+    #[test]
+    fn unparseable_hop_date_is_skipped_not_fatal() {
+        let headers = vec![
+            header("Received", "by c.example; Tue, 1 Oct 2024 10:02:00 +0000"),
+            header("Received", "by b.example; sometime"),
+            header("Received", "by a.example; Tue, 1 Oct 2024 10:00:00 +0000"),
+        ];
+        let path = analyze(&headers);
+        assert_eq!(path.hops[1].timestamp, None);
+        assert_eq!(path.hops[1].delay_secs, None);
+        // c's delay is measured from a, the last hop with a timestamp.
+        assert_eq!(path.hops[2].delay_secs, Some(120));
+    }
+
+    #[test]
+    fn clock_skew_shows_as_negative_delay() {
+        let headers = vec![
+            header("Received", "by b.example; Tue, 1 Oct 2024 09:59:00 +0000"),
+            header("Received", "by a.example; Tue, 1 Oct 2024 10:00:00 +0000"),
+        ];
+        assert_eq!(analyze(&headers).hops[1].delay_secs, Some(-60));
+    }
+
+    #[test]
+    fn no_received_headers_is_an_empty_path() {
+        let path = analyze(&[header("Date", "Tue, 1 Oct 2024 10:00:00 +0000")]);
+        assert!(path.hops.is_empty());
+        assert!(path.sent_at.is_some());
+        assert_eq!(path.total_delay_secs, None);
+    }
+
+    #[test]
+    fn serializes_camel_case() {
+        let json = serde_json::to_value(analyze(&sample())).unwrap();
+        assert!(json["totalDelaySecs"].is_i64());
+        assert!(json["hops"][0]["delaySecs"].is_i64());
+    }
+}
@@ -0,0 +1,352 @@
+//! Daily digest email.
+//!
+//! `digest.json` lives next to `groups.json`. When enabled, the scheduler
+//! ([`spawn_scheduler`]) sends one HTML summary per day, after the
+//! configured local hour. It covers the day's inbox mail in each included
+//! split: the newsletters and low-priority buckets the user would rather
+//! skim once than triage one by one. The digest goes to the account's own
+//! address, or is saved as a draft instead. `routes::run_digest` gathers
+//! the mail; this module owns the config, the schedule, and the rendering.
+
+use crate::error::Error;
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Rows listed per split before the section collapses to "and N more".
+pub const MAX_ITEMS_PER_SECTION: usize = 25;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestDelivery {
+    /// Send to the account's own address.
+    #[default]
+    Send,
+    /// Save in Drafts (Fastmail only, like every draft path).
+    Draft,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Account to digest; the default account when absent.
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Local hour (0–23, configured timezone) after which the day's digest
+    /// goes out.
+    #[serde(default = "default_hour")]
+    pub hour: u32,
+    /// Split ids included in the digest, in section order. A split not
+    /// listed is left out.
+    #[serde(default)]
+    pub splits: Vec<String>,
+    #[serde(default)]
+    pub delivery: DigestDelivery,
+    /// Local date of the last scheduled digest, so a restart after the
+    /// send hour doesn't send it twice.
+    #[serde(default)]
+    pub last_sent: Option<NaiveDate>,
+}
+
+fn default_hour() -> u32 {
+    18
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            account: None,
+            hour: default_hour(),
+            splits: Vec::new(),
+            delivery: DigestDelivery::default(),
+            last_sent: None,
+        }
+    }
+}
+
+// =============================================================================
+// Config load/save
+// =============================================================================
+
+pub fn load_digest(config_path: &Path) -> DigestConfig {
+    if config_path.exists() {
+        let content = match std::fs::read_to_string(config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read digest config: {e}");
+                return DigestConfig::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    DigestConfig::default()
+}
+
+pub fn save_digest(config: &DigestConfig, config_path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(config)?;
+    crate::accounts::atomic_write_bytes(config_path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+pub fn validate_digest(config: &DigestConfig) -> Result<(), Error> {
+    if config.hour > 23 {
+        return Err(Error::BadRequest(format!(
+            "Digest hour must be 0-23, got {}",
+            config.hour
+        )));
+    }
+    if config.enabled && config.splits.is_empty() {
+        return Err(Error::BadRequest(
+            "An enabled digest needs at least one split".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the scheduled digest should go out at local time `now`.
+pub fn is_due(config: &DigestConfig, now: NaiveDateTime) -> bool {
+    config.enabled && now.hour() >= config.hour && config.last_sent != Some(now.date())
+}
+
+// =============================================================================
+// Rendering
+// =============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestItem {
+    pub from: String,
+    pub subject: String,
+    pub preview: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestSection {
+    pub title: String,
+    pub items: Vec<DigestItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedDigest {
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+    pub message_count: usize,
+}
+
+/// Subject, plain-text and HTML bodies for `sections`. Empty sections are
+/// dropped, and every value from a message is escaped before it reaches
+/// the HTML.
+pub fn render(date: NaiveDate, sections: &[DigestSection]) -> RenderedDigest {
+    let sections: Vec<&DigestSection> = sections.iter().filter(|s| !s.items.is_empty()).collect();
+    let message_count = sections.iter().map(|s| s.items.len()).sum();
+    let subject = format!(
+        "Digest for {}: {message_count} message{}",
+        date.format("%a %b %-d"),
+        if message_count == 1 { "" } else { "s" }
+    );
+
+    let mut text = String::new();
+    let mut html = String::from("<div style=\"font-family:sans-serif\">");
+    if sections.is_empty() {
+        text.push_str("Nothing new in your digest splits today.\n");
+        html.push_str("<p>Nothing new in your digest splits today.</p>");
+    }
+    for section in sections {
+        let shown = &section.items[..section.items.len().min(MAX_ITEMS_PER_SECTION)];
+        let hidden = section.items.len() - shown.len();
+        text.push_str(&format!("{} ({})\n", section.title, section.items.len()));
+        html.push_str(&format!(
+            "<h3>{} ({})</h3><ul>",
+            ammonia::clean_text(&section.title),
+            section.items.len()
+        ));
+        for item in shown {
+            text.push_str(&format!("- {}: {}\n", item.from, item.subject));
+            html.push_str(&format!(
+                "<li><b>{}</b>: {}<br><small>{}</small></li>",
+                ammonia::clean_text(&item.from),
+                ammonia::clean_text(&item.subject),
+                ammonia::clean_text(&item.preview),
+            ));
+        }
+        if hidden > 0 {
+            text.push_str(&format!("  …and {hidden} more\n"));
+            html.push_str(&format!("<li>…and {hidden} more</li>"));
+        }
+        text.push('\n');
+        html.push_str("</ul>");
+    }
+    html.push_str("</div>");
+
+    RenderedDigest {
+        subject,
+        text_body: text,
+        html_body: html,
+        message_count,
+    }
+}
+
+// =============================================================================
+// Scheduler
+// =============================================================================
+
+/// Check every `interval` whether today's digest is due and, if so, run
+/// it and record the date. A failed run is logged and retried on the next
+/// tick; `last_sent` only moves on success.
+pub fn spawn_scheduler(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("digest", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("digest");
+            let config = load_digest(&state.digest_config_path);
+            let now = crate::routes::local_now(&state);
+            if !is_due(&config, now) || crate::read_only::enabled(&state).await {
+                continue;
+            }
+            match crate::routes::run_digest(&state, &config, false).await {
+                Ok(rendered) => {
+                    tracing::info!(
+                        "Digest for {} sent ({} messages)",
+                        now.date(),
+                        rendered.message_count
+                    );
+                    // Reload so a settings edit made while the digest ran
+                    // isn't clobbered; only the date is ours to write.
+                    let mut latest = load_digest(&state.digest_config_path);
+                    latest.last_sent = Some(now.date());
+                    if let Err(e) = save_digest(&latest, &state.digest_config_path) {
+                        tracing::warn!("Failed to record digest date: {e}");
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Digest run failed: {e}");
+                    health.failed("digest", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(from: &str, subject: &str) -> DigestItem {
+        DigestItem {
+            from: from.into(),
+            subject: subject.into(),
+            preview: "preview".into(),
+        }
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn is_due_after_hour_once_per_day() {
+        let mut config = DigestConfig {
+            enabled: true,
+            hour: 18,
+            splits: vec!["news".into()],
+            ..Default::default()
+        };
+        assert!(!is_due(&config, at("2026-10-16 17:59")));
+        assert!(is_due(&config, at("2026-10-16 18:00")));
+        config.last_sent = Some("2026-10-16".parse().unwrap());
+        assert!(!is_due(&config, at("2026-10-16 23:00")));
+        assert!(is_due(&config, at("2026-10-17 18:30")));
+        config.enabled = false;
+        assert!(!is_due(&config, at("2026-10-17 18:30")));
+    }
+
+    #[test]
+    fn validate_rejects_bad_hour_and_empty_enabled_digest() {
+        let config = DigestConfig {
+            hour: 24,
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_digest(&config),
+            Err(Error::BadRequest(_))
+        ));
+        let config = DigestConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_digest(&config),
+            Err(Error::BadRequest(_))
+        ));
+        assert!(validate_digest(&DigestConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn render_escapes_and_drops_empty_sections() {
+        let date = "2026-10-16".parse().unwrap();
+        let sections = [
+            DigestSection {
+                title: "News".into(),
+                items: vec![item("Shop <deals@shop.com>", "<script>x</script>")],
+            },
+            DigestSection {
+                title: "Empty".into(),
+                items: vec![],
+            },
+        ];
+        let rendered = render(date, &sections);
+        assert_eq!(rendered.subject, "Digest for Fri Oct 16: 1 message");
+        assert_eq!(rendered.message_count, 1);
+        assert!(!rendered.html_body.contains("<script>"));
+        assert!(rendered.html_body.contains("&lt;script&gt;"));
+        assert!(!rendered.text_body.contains("Empty"));
+    }
+
+    #[test]
+    fn render_collapses_long_sections() {
+        let date = "2026-10-16".parse().unwrap();
+        let items = (0..MAX_ITEMS_PER_SECTION + 3)
+            .map(|i| item("a@x.com", &format!("s{i}")))
+            .collect();
+        let rendered = render(
+            date,
+            &[DigestSection {
+                title: "Promos".into(),
+                items,
+            }],
+        );
+        assert!(rendered.text_body.contains("…and 3 more"));
+        assert!(
+            !rendered
+                .html_body
+                .contains(&format!("s{MAX_ITEMS_PER_SECTION}<"))
+        );
+    }
+
+    #[test]
+    fn save_then_load_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("digest.json");
+        let config = DigestConfig {
+            enabled: true,
+            splits: vec!["news".into()],
+            delivery: DigestDelivery::Draft,
+            ..Default::default()
+        };
+        save_digest(&config, &path).unwrap();
+        assert_eq!(load_digest(&path), config);
+    }
+}
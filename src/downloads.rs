@@ -0,0 +1,197 @@
+//! File names for downloads, and "download all" as one archive.
+//!
+//! Attachment names are sender-controlled: they repeat (three
+//! `image.png` from a phone), carry path separators, and arrive in either
+//! Unicode normal form. [`entry_name`] makes one safe to unpack,
+//! [`dedupe_names`] suffixes repeats the way file managers do
+//! (`report (2).pdf`), and [`content_disposition`] sends a non-ASCII name
+//! as RFC 6266 `filename*` so browsers save it as written rather than as
+//! Latin-1 mojibake.
+
+use crate::error::Error;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use std::collections::HashSet;
+use std::io::Write;
+use unicode_normalization::UnicodeNormalization;
+
+/// RFC 5987 `attr-char`: everything else in `filename*` is %-encoded.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Name used when nothing printable is left.
+const FALLBACK_NAME: &str = "attachment";
+
+/// `name` as a single safe path component: NFC, the part after any `/` or
+/// `\`, no control characters, and no leading or trailing spaces or
+/// trailing dots (which Windows drops on extract).
+pub fn entry_name(name: &str) -> String {
+    let nfc: String = name.nfc().collect();
+    let base = nfc.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).collect();
+    let trimmed = cleaned.trim().trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() || trimmed == "." || trimmed == ".." {
+        FALLBACK_NAME.into()
+    } else {
+        trimmed.into()
+    }
+}
+
+/// [`entry_name`] of each of `names`, with later repeats suffixed
+/// ` (2)`, ` (3)`… before the extension. Comparison ignores case, since
+/// the archive may be unpacked on a case-insensitive file system.
+pub fn dedupe_names<S: AsRef<str>>(names: &[S]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let name = entry_name(name.as_ref());
+            if taken.insert(name.to_lowercase()) {
+                return name;
+            }
+            let (stem, ext) = match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+                _ => (name.as_str(), String::new()),
+            };
+            (2..)
+                .map(|n| format!("{stem} ({n}){ext}"))
+                .find(|candidate| taken.insert(candidate.to_lowercase()))
+                .expect("unbounded counter")
+        })
+        .collect()
+}
+
+/// `Content-Disposition: attachment` for `filename`. The quoted
+/// `filename` is an ASCII fallback for old clients; a name with anything
+/// else also gets `filename*=UTF-8''…`, which current browsers prefer.
+pub fn content_disposition(filename: &str) -> String {
+    let name = entry_name(filename);
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == name {
+        format!("attachment; filename=\"{fallback}\"")
+    } else {
+        format!(
+            "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+            utf8_percent_encode(&name, ATTR_CHAR)
+        )
+    }
+}
+
+/// A zip of `files` (name, bytes) with names passed through
+/// [`dedupe_names`]. Entries are stored uncompressed.
+pub fn zip_files(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, Error> {
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(files.iter().any(|(_, b)| b.len() as u64 >= u32::MAX as u64));
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, (_, bytes)) in dedupe_names(&names).into_iter().zip(files) {
+        zip.start_file(name, options).map_err(zip_error)?;
+        zip.write_all(bytes)?;
+    }
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+fn zip_error(e: zip::result::ZipError) -> Error {
+    Error::Internal(format!("zip: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_name_strips_paths_and_controls() {
+        assert_eq!(entry_name("report.pdf"), "report.pdf");
+        assert_eq!(entry_name("../../etc/passwd"), "passwd");
+        assert_eq!(entry_name("C:\\Users\\ann\\scan.jpg"), "scan.jpg");
+        assert_eq!(entry_name("a\r\nb\0.txt"), "ab.txt");
+        assert_eq!(entry_name(" notes.txt. "), "notes.txt");
+        assert_eq!(entry_name(".."), FALLBACK_NAME);
+        assert_eq!(entry_name(""), FALLBACK_NAME);
+    }
+
+    #[test]
+    fn entry_name_normalizes_to_nfc() {
+        // "résumé.pdf" with combining acute accents, as macOS sends it.
+        let decomposed = "re\u{301}sume\u{301}.pdf";
+        assert_eq!(entry_name(decomposed), "r\u{e9}sum\u{e9}.pdf");
+    }
+
+    #[test]
+    fn dedupe_suffixes_repeats_before_the_extension() {
+        let names = [
+            "image.png",
+            "IMAGE.png",
+            "image (2).png",
+            "notes",
+            "notes",
+            "re\u{301}sume\u{301}.pdf",
+            "r\u{e9}sum\u{e9}.pdf",
+        ];
+        assert_eq!(
+            dedupe_names(&names),
+            [
+                "image.png",
+                "IMAGE (2).png",
+                "image (2) (2).png",
+                "notes",
+                "notes (2)",
+                "r\u{e9}sum\u{e9}.pdf",
+                "r\u{e9}sum\u{e9} (2).pdf",
+            ]
+        );
+    }
+
+    #[test]
+    fn content_disposition_adds_utf8_name_only_when_needed() {
+        assert_eq!(
+            content_disposition("report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("r\u{e9}sum\u{e9} 2026.pdf"),
+            "attachment; filename=\"r_sum_ 2026.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%202026.pdf"
+        );
+        assert_eq!(
+            content_disposition("say \"hi\".txt"),
+            "attachment; filename=\"say _hi_.txt\"; filename*=UTF-8''say%20%22hi%22.txt"
+        );
+        assert!(!content_disposition("a\r\nSet-Cookie: x").contains('\n'));
+    }
+
+    #[test]
+    fn zip_keeps_every_file_under_a_distinct_name() {
+        let files = vec![
+            ("scan.jpg".to_string(), b"one".to_vec()),
+            ("scan.jpg".to_string(), b"two".to_vec()),
+            ("\u{5831}\u{544a}.txt".to_string(), b"three".to_vec()),
+        ];
+        let bytes = zip_files(&files).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, ["scan (2).jpg", "scan.jpg", "\u{5831}\u{544a}.txt"]);
+        let mut second = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("scan (2).jpg").unwrap(), &mut second)
+            .unwrap();
+        assert_eq!(second, "two");
+    }
+}
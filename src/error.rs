@@ -11,9 +11,35 @@ pub enum Error {
     NotConnected,
     NotFound(String),
     BadRequest(String),
+    /// Well-formed request whose content fails validation (422), e.g. a
+    /// split regex that doesn't compile.
+    Unprocessable(String),
     Conflict(String),
+    /// The server is in `read-only` mode and the request would change
+    /// something (409, `{"error": "read_only"}`). Not a 403, which the
+    /// clients take to mean the account needs signing in again.
+    ReadOnly,
     Internal(String),
-    RateLimited { retry_after: Option<Duration> },
+    /// The account's server doesn't offer what the request needs (501),
+    /// e.g. sending on a JMAP server without submission.
+    NotImplemented(String),
+    /// The send goes to guarded recipients and needs a TOTP code (428); see
+    /// `send_guard`. The response carries a challenge naming them.
+    ConfirmationRequired {
+        message: String,
+        recipients: Vec<String>,
+    },
+    /// The send falls outside work hours or into a recipient's night
+    /// (428); see `send_hours`. The response carries the reasons and a
+    /// suggested time to send instead.
+    SendTimeWarning {
+        message: String,
+        reasons: Vec<String>,
+        suggested_send_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -24,8 +50,13 @@ impl fmt::Display for Error {
             Error::NotConnected => write!(f, "not connected to email server"),
             Error::NotFound(msg) => write!(f, "not found: {msg}"),
             Error::BadRequest(msg) => write!(f, "bad request: {msg}"),
+            Error::Unprocessable(msg) => write!(f, "invalid: {msg}"),
             Error::Conflict(msg) => write!(f, "conflict: {msg}"),
+            Error::ReadOnly => write!(f, "the server is in read-only mode"),
             Error::Internal(msg) => write!(f, "internal error: {msg}"),
+            Error::NotImplemented(msg) => write!(f, "not supported: {msg}"),
+            Error::ConfirmationRequired { message, .. } => write!(f, "{message}"),
+            Error::SendTimeWarning { message, .. } => write!(f, "{message}"),
             Error::RateLimited { retry_after } => match retry_after {
                 Some(d) => write!(f, "rate limited — retry after {}s", d.as_secs()),
                 None => write!(f, "rate limited"),
@@ -56,12 +87,57 @@ impl From<serde_json::Error> for Error {
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
+        if let Error::ConfirmationRequired {
+            message,
+            recipients,
+        } = &self
+        {
+            let body = serde_json::json!({
+                "error": message,
+                "challenge": { "type": "totp", "recipients": recipients },
+            });
+            return (StatusCode::PRECONDITION_REQUIRED, axum::Json(body)).into_response();
+        }
+        if let Error::ReadOnly = &self {
+            let body = serde_json::json!({
+                "error": "read_only",
+                "message": self.to_string(),
+            });
+            return (StatusCode::CONFLICT, axum::Json(body)).into_response();
+        }
+        if let Error::SendTimeWarning {
+            message,
+            reasons,
+            suggested_send_at,
+        } = &self
+        {
+            let body = serde_json::json!({
+                "error": message,
+                "challenge": {
+                    "type": "send-time",
+                    "reasons": reasons,
+                    "suggestedSendAt": suggested_send_at,
+                },
+            });
+            return (StatusCode::PRECONDITION_REQUIRED, axum::Json(body)).into_response();
+        }
         let mut retry_after_header: Option<HeaderValue> = None;
         let (status, client_message) = match &self {
             Error::Auth(_) => (StatusCode::UNAUTHORIZED, "authentication failed".into()),
             Error::NotFound(msg) => (StatusCode::NOT_FOUND, format!("not found: {msg}")),
             Error::BadRequest(msg) => (StatusCode::BAD_REQUEST, format!("bad request: {msg}")),
+            Error::Unprocessable(msg) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, format!("invalid: {msg}"))
+            }
             Error::Conflict(msg) => (StatusCode::CONFLICT, format!("conflict: {msg}")),
+            Error::ConfirmationRequired { .. }
+            | Error::SendTimeWarning { .. }
+            | Error::ReadOnly => {
+                unreachable!("answered above")
+            }
+            Error::NotImplemented(msg) => {
+                (StatusCode::NOT_IMPLEMENTED, format!("not supported: {msg}"))
+            }
             Error::NotConnected => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 "not connected to email server".into(),
@@ -129,6 +205,67 @@ mod tests {
         assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 
+    #[tokio::test]
+    async fn unprocessable_returns_422_with_message() {
+        let (status, body) =
+            response_status_and_body(Error::Unprocessable("regex parse error".into())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("regex parse error"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn read_only_returns_409_with_its_own_code() {
+        let (status, body) = response_status_and_body(Error::ReadOnly).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "read_only");
+        assert_eq!(body["message"], "the server is in read-only mode");
+    }
+
+    #[tokio::test]
+    async fn not_implemented_returns_501_with_message() {
+        let (status, body) =
+            response_status_and_body(Error::NotImplemented("no JMAP submission".into())).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+        assert!(body.contains("no JMAP submission"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn confirmation_required_returns_428_with_challenge() {
+        let (status, body) = response_status_and_body(Error::ConfirmationRequired {
+            message: "needs a code".into(),
+            recipients: vec!["press@news.example".into()],
+        })
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["challenge"]["type"], "totp");
+        assert_eq!(json["challenge"]["recipients"][0], "press@news.example");
+    }
+
+    #[tokio::test]
+    async fn send_time_warning_returns_428_with_suggestion() {
+        let at = chrono::DateTime::parse_from_rfc3339("2026-01-15T08:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let (status, body) = response_status_and_body(Error::SendTimeWarning {
+            message: "odd hour".into(),
+            reasons: vec!["It's 03:00 for kenji@example.jp (UTC+09:00)".into()],
+            suggested_send_at: Some(at),
+        })
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["challenge"]["type"], "send-time");
+        assert_eq!(json["challenge"]["suggestedSendAt"], "2026-01-15T08:00:00Z");
+        assert!(
+            json["challenge"]["reasons"][0]
+                .as_str()
+                .unwrap()
+                .contains("kenji")
+        );
+    }
+
     #[tokio::test]
     async fn not_connected_returns_503() {
         let (status, _) = response_status_and_body(Error::NotConnected).await;
@@ -1,40 +1,306 @@
 /// Hand-rolled fnmatch-style glob matching.
-/// Supports `*` (any sequence) and `?` (any single char).
+///
+/// Supports `*` (any sequence), `?` (any single char), character classes
+/// (`[abc]`, `[a-z]`, negated with `[!...]` or `[^...]`) and brace
+/// alternatives (`*@{gmail,googlemail}.com`, nestable). A backslash makes
+/// the next character literal (`\*`, `\[`, `\{`, `\\`). A `[` or `{` with
+/// no closing partner, or a brace group without a comma, is literal text.
 /// Both pattern and text are lowercased before comparison (case-insensitive).
 pub fn glob_match(pattern: &str, text: &str) -> bool {
-    let pattern = pattern.to_lowercase();
-    let text = text.to_lowercase();
-    glob_match_bytes(pattern.as_bytes(), text.as_bytes())
+    Glob::new(pattern).matches(text)
 }
 
-fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+/// [`glob_match`] against any part of `text` rather than all of it, as if
+/// the pattern were wrapped in `*...*`.
+pub fn glob_match_anywhere(pattern: &str, text: &str) -> bool {
+    Glob::unanchored(pattern).matches(text)
+}
+
+/// A parsed, lowercased glob. Precompiled matchers hold one so a pattern is
+/// parsed once per config load rather than once per message.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    /// One token list per brace alternative; the glob matches if any does.
+    alternatives: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    AnySequence,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let alternatives = expand_braces(&pattern)
+            .iter()
+            .map(|alt| tokenize(alt))
+            .collect();
+        Self { alternatives }
+    }
+
+    /// See [`glob_match_anywhere`].
+    pub fn unanchored(pattern: &str) -> Self {
+        let mut glob = Self::new(pattern);
+        for tokens in &mut glob.alternatives {
+            if tokens.first() != Some(&Token::AnySequence) {
+                tokens.insert(0, Token::AnySequence);
+            }
+            if tokens.last() != Some(&Token::AnySequence) {
+                tokens.push(Token::AnySequence);
+            }
+        }
+        glob
+    }
+
+    /// Lowercases `text` only when it contains uppercase.
+    pub fn matches(&self, text: &str) -> bool {
+        let text = lowercase_if_needed(text);
+        self.alternatives.iter().any(|t| match_tokens(t, &text))
+    }
+}
+
+/// Borrow `text` unchanged when lowercasing would be a no-op, which is the
+/// common case for email addresses.
+pub(crate) fn lowercase_if_needed(text: &str) -> std::borrow::Cow<'_, str> {
+    if text.chars().any(char::is_uppercase) {
+        std::borrow::Cow::Owned(text.to_lowercase())
+    } else {
+        std::borrow::Cow::Borrowed(text)
+    }
+}
+
+/// The pattern's brace alternatives as plain wildcard strings — `*`, `?`
+/// and backslash escapes only — for matchers that have nothing richer
+/// (Sieve's `:matches`). `None` if any alternative uses a character class.
+pub(crate) fn wildcard_alternatives(pattern: &str) -> Option<Vec<String>> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    expand_braces(&pattern)
+        .into_iter()
+        .map(|alt| {
+            let mut out = String::with_capacity(alt.len());
+            let mut i = 0;
+            while i < alt.len() {
+                match alt[i] {
+                    '\\' => {
+                        out.push('\\');
+                        out.push(*alt.get(i + 1).unwrap_or(&'\\'));
+                        i += 2;
+                    }
+                    '[' if class_end(&alt, i).is_some() => return None,
+                    c => {
+                        out.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            Some(out)
+        })
+        .collect()
+}
+
+/// Expand the first brace group with a top-level comma, then recurse on
+/// each result so later and nested groups expand too. Escapes are kept in
+/// the output for [`tokenize`] to resolve.
+fn expand_braces(pattern: &[char]) -> Vec<Vec<char>> {
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 2,
+            '[' => i = class_end(pattern, i).map_or(i + 1, |end| end + 1),
+            '{' => {
+                if let Some((close, commas)) = brace_group(pattern, i)
+                    && !commas.is_empty()
+                {
+                    let mut bounds = vec![i];
+                    bounds.extend(&commas);
+                    bounds.push(close);
+                    return bounds
+                        .windows(2)
+                        .flat_map(|w| {
+                            let mut alt = pattern[..i].to_vec();
+                            alt.extend_from_slice(&pattern[w[0] + 1..w[1]]);
+                            alt.extend_from_slice(&pattern[close + 1..]);
+                            expand_braces(&alt)
+                        })
+                        .collect();
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    vec![pattern.to_vec()]
+}
+
+/// Index of the `}` closing the group opened at `open`, and the indices of
+/// its top-level commas.
+fn brace_group(pattern: &[char], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = open + 1;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 1,
+            '[' => i = class_end(pattern, i).unwrap_or(i),
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some((i, commas)),
+            '}' => depth -= 1,
+            ',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index of the `]` closing the class opened at `open`. A `]` right after
+/// the opening bracket (or its negation) is a member, not the end.
+fn class_end(pattern: &[char], open: usize) -> Option<usize> {
+    let mut i = open + 1;
+    if matches!(pattern.get(i), Some('!' | '^')) {
+        i += 1;
+    }
+    if pattern.get(i) == Some(&']') {
+        i += 1;
+    }
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 2,
+            ']' => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn tokenize(pattern: &[char]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => {
+                // A trailing backslash has nothing to escape; keep it.
+                tokens.push(Token::Literal(*pattern.get(i + 1).unwrap_or(&'\\')));
+                i += 2;
+            }
+            '*' => {
+                if tokens.last() != Some(&Token::AnySequence) {
+                    tokens.push(Token::AnySequence);
+                }
+                i += 1;
+            }
+            '?' => {
+                tokens.push(Token::AnyChar);
+                i += 1;
+            }
+            '[' => match class_end(pattern, i) {
+                Some(end) => {
+                    tokens.push(parse_class(&pattern[i + 1..end]));
+                    i = end + 1;
+                }
+                None => {
+                    tokens.push(Token::Literal('['));
+                    i += 1;
+                }
+            },
+            c => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// Parse the inside of `[...]`. `-` between two members is a range; at
+/// either end it is a literal dash.
+fn parse_class(body: &[char]) -> Token {
+    let (negated, body) = match body.first() {
+        Some('!' | '^') => (true, &body[1..]),
+        _ => (false, body),
+    };
+    let mut members = Vec::new();
+    let mut i = 0;
+    while i < body.len() {
+        let (c, escaped) = match body[i] {
+            '\\' if i + 1 < body.len() => {
+                i += 1;
+                (body[i], true)
+            }
+            c => (c, false),
+        };
+        members.push((c, escaped));
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut j = 0;
+    while j < members.len() {
+        let (lo, _) = members[j];
+        if let Some(&('-', false)) = members.get(j + 1)
+            && let Some(&(hi, _)) = members.get(j + 2)
+        {
+            ranges.push((lo, hi));
+            j += 3;
+        } else {
+            ranges.push((lo, lo));
+            j += 1;
+        }
+    }
+    Token::Class { negated, ranges }
+}
+
+impl Token {
+    fn matches_char(&self, c: char) -> bool {
+        match self {
+            Token::Literal(l) => *l == c,
+            Token::AnyChar => true,
+            Token::AnySequence => false,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+        }
+    }
+}
+
+/// Iterative wildcard matching with single-star backtracking. Indices are
+/// byte offsets into `text`, stepped a whole char at a time so `?` and
+/// classes consume characters, not bytes.
+fn match_tokens(tokens: &[Token], text: &str) -> bool {
+    let char_at = |i: usize| text[i..].chars().next();
     let mut pi = 0;
     let mut ti = 0;
     let mut star_pi = usize::MAX;
     let mut star_ti = 0;
 
-    while ti < text.len() {
-        if pi < pattern.len() && (pattern[pi] == b'?' || pattern[pi] == text[ti]) {
+    while let Some(c) = char_at(ti) {
+        if pi < tokens.len() && tokens[pi].matches_char(c) {
             pi += 1;
-            ti += 1;
-        } else if pi < pattern.len() && pattern[pi] == b'*' {
+            ti += c.len_utf8();
+        } else if pi < tokens.len() && tokens[pi] == Token::AnySequence {
             star_pi = pi;
             star_ti = ti;
             pi += 1;
         } else if star_pi != usize::MAX {
             pi = star_pi + 1;
-            star_ti += 1;
+            star_ti += char_at(star_ti).map_or(1, char::len_utf8);
             ti = star_ti;
         } else {
             return false;
         }
     }
 
-    while pi < pattern.len() && pattern[pi] == b'*' {
+    while pi < tokens.len() && tokens[pi] == Token::AnySequence {
         pi += 1;
     }
 
-    pi == pattern.len()
+    pi == tokens.len()
 }
 
 #[cfg(test)]
@@ -96,4 +362,151 @@ mod tests {
     fn no_match() {
         assert!(!glob_match("specific@email.com", "other@email.com"));
     }
+
+    #[test]
+    fn compiled_glob_matches_like_glob_match() {
+        let glob = Glob::new("*@Example.COM");
+        assert!(glob.matches("User@example.com"));
+        assert!(!glob.matches("user@other.com"));
+        assert!(Glob::new("ärger*").matches("Ärger@example.com"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_multibyte_char() {
+        assert!(glob_match("caf?@x.com", "café@x.com"));
+    }
+
+    #[test]
+    fn anywhere_matches_inside_the_text() {
+        assert!(glob_match_anywhere("example.com", "Me@Example.com"));
+        assert!(glob_match_anywhere(
+            "invoice #[0-9]",
+            "Re: Invoice #4 overdue"
+        ));
+        assert!(glob_match_anywhere("{gmail,outlook}", "me@outlook.com"));
+        assert!(!glob_match_anywhere("example.com", "me@example.org"));
+        assert!(!glob_match("example.com", "me@example.com"));
+    }
+
+    #[test]
+    fn anywhere_with_explicit_stars_is_unchanged() {
+        assert!(glob_match_anywhere("*@x.com", "a@x.com.evil"));
+        assert!(glob_match_anywhere("", "anything"));
+    }
+
+    // --- character classes ---
+
+    #[test]
+    fn class_matches_listed_chars() {
+        assert!(glob_match("[abc]@x.com", "b@x.com"));
+        assert!(!glob_match("[abc]@x.com", "d@x.com"));
+    }
+
+    #[test]
+    fn class_range() {
+        assert!(glob_match("user[0-9]@x.com", "user7@x.com"));
+        assert!(!glob_match("user[0-9]@x.com", "usera@x.com"));
+        assert!(glob_match("[a-c][x-z]", "bz"));
+    }
+
+    #[test]
+    fn class_negation() {
+        assert!(glob_match("[!a]*", "bob"));
+        assert!(!glob_match("[!a]*", "alice"));
+        assert!(glob_match("[^0-9]*", "x1"));
+        assert!(!glob_match("[^0-9]*", "1x"));
+    }
+
+    #[test]
+    fn class_is_case_insensitive() {
+        assert!(glob_match("[A-C]*", "bob"));
+        assert!(glob_match("[a-c]*", "BOB"));
+    }
+
+    #[test]
+    fn class_literal_bracket_and_dash() {
+        assert!(glob_match("[]x]", "]"));
+        assert!(glob_match("[-a]", "-"));
+        assert!(glob_match("[a-]", "-"));
+        assert!(!glob_match("[a-]", "b"));
+    }
+
+    #[test]
+    fn unclosed_class_is_literal() {
+        assert!(glob_match("[abc", "[abc"));
+        assert!(!glob_match("[abc", "a"));
+    }
+
+    // --- braces ---
+
+    #[test]
+    fn brace_alternatives() {
+        let pattern = "*@{gmail,googlemail}.com";
+        assert!(glob_match(pattern, "me@gmail.com"));
+        assert!(glob_match(pattern, "me@GoogleMail.com"));
+        assert!(!glob_match(pattern, "me@hotmail.com"));
+    }
+
+    #[test]
+    fn brace_with_empty_alternative() {
+        assert!(glob_match("a{,b}c", "ac"));
+        assert!(glob_match("a{,b}c", "abc"));
+    }
+
+    #[test]
+    fn nested_and_repeated_braces() {
+        let pattern = "{news,info}@{x.{com,org},y.net}";
+        assert!(glob_match(pattern, "news@x.org"));
+        assert!(glob_match(pattern, "info@y.net"));
+        assert!(!glob_match(pattern, "info@x.net"));
+    }
+
+    #[test]
+    fn brace_without_comma_or_close_is_literal() {
+        assert!(glob_match("{a}", "{a}"));
+        assert!(glob_match("a{b,c", "a{b,c"));
+    }
+
+    #[test]
+    fn brace_alternatives_can_hold_wildcards_and_classes() {
+        assert!(glob_match("{*@x.com,[0-9]*@y.com}", "7up@y.com"));
+        assert!(!glob_match("{*@x.com,[0-9]*@y.com}", "up@y.com"));
+    }
+
+    // --- escaping ---
+
+    #[test]
+    fn escaped_wildcards_are_literal() {
+        assert!(glob_match(r"a\*b", "a*b"));
+        assert!(!glob_match(r"a\*b", "axxb"));
+        assert!(glob_match(r"a\?b", "a?b"));
+        assert!(!glob_match(r"a\?b", "axb"));
+    }
+
+    #[test]
+    fn escaped_brackets_and_braces_are_literal() {
+        assert!(glob_match(r"\[x]", "[x]"));
+        assert!(!glob_match(r"\[x]", "x"));
+        assert!(glob_match(r"\{a,b}", "{a,b}"));
+        assert!(glob_match(r"{a\,b,c}", "a,b"));
+        assert!(glob_match(r"[\]]", "]"));
+    }
+
+    #[test]
+    fn escaped_backslash_and_trailing_backslash() {
+        assert!(glob_match(r"a\\b", r"a\b"));
+        assert!(glob_match(r"a\", r"a\"));
+    }
+
+    #[test]
+    fn wildcard_alternatives_expand_braces_and_refuse_classes() {
+        assert_eq!(
+            wildcard_alternatives(r"*@{gmail,googlemail}.com").unwrap(),
+            ["*@gmail.com", "*@googlemail.com"]
+        );
+        assert_eq!(wildcard_alternatives(r"a\*b?").unwrap(), [r"a\*b?"]);
+        assert_eq!(wildcard_alternatives(r"a\[x]").unwrap(), [r"a\[x]"]);
+        assert!(wildcard_alternatives("[ab]*@x.example").is_none());
+        assert_eq!(wildcard_alternatives("a[b").unwrap(), ["a[b"]);
+    }
 }
@@ -24,11 +24,15 @@ use crate::error::Error;
 use crate::oauth;
 use crate::platform::{self, TokenStore, Tokens};
 use crate::provider_utils::{
-    MAX_BLOB_BYTES, MAX_UPLOAD_CACHE_BYTES, UPLOAD_CACHE_CAP, encode_path_segment,
-    mime_type_from_filename, should_clear_tokens_on_refresh_failure,
+    ATTACHMENT_FETCH_CONCURRENCY, MAX_BLOB_BYTES, MAX_UPLOAD_CACHE_BYTES, UPLOAD_CACHE_CAP,
+    encode_path_segment, mime_type_from_filename, should_clear_tokens_on_refresh_failure,
+    try_join_bounded,
 };
 use crate::rate_limit::RateLimiter;
-use crate::types::{CalendarEvent, Email, EmailAddress, EmailSort, Identity, Mailbox, ParsedQuery};
+use crate::types::{
+    CalendarEvent, Email, EmailAddress, EmailChanges, EmailHeader, EmailSort, Identity, Mailbox,
+    ParsedQuery, SpamInfo,
+};
 
 // =============================================================================
 // Endpoints + constants
@@ -272,13 +276,6 @@ fn save_tokens(session: &GmailSession, token: &GmailToken) -> Result<(), Error>
     )
 }
 
-fn build_http_client() -> reqwest::Client {
-    reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .expect("failed to create HTTP client")
-}
-
 #[derive(Deserialize)]
 struct GmailProfile {
     #[serde(rename = "emailAddress")]
@@ -311,7 +308,7 @@ pub fn load_session(
 ) -> Option<GmailSession> {
     let tokens = token_store.load(account_id)?;
     Some(GmailSession {
-        client: build_http_client(),
+        client: crate::http_client::client(),
         token: tokio::sync::Mutex::new(GmailToken {
             access_token: tokens.access_token,
             refresh_token: tokens.refresh_token,
@@ -344,7 +341,7 @@ pub async fn oauth_flow(
 
     let callback = platform::acquire_oauth_callback(&url, &expected_state, CALLBACK_PORT).await?;
 
-    let client = build_http_client();
+    let client = crate::http_client::client();
     let token_resp = exchange_code(
         &client,
         client_id,
@@ -690,12 +687,63 @@ pub fn translate_query_to_q(query: &ParsedQuery) -> String {
     if !query.text.is_empty() {
         parts.push(query.text.clone());
     }
+    if let Some(larger) = query.larger {
+        parts.push(format!("larger:{larger}"));
+    }
+    if let Some(smaller) = query.smaller {
+        parts.push(format!("smaller:{smaller}"));
+    }
+    for term in &query.mailboxes {
+        // System labels (INBOX, SPAM, ...) go by id, user labels by name.
+        match term.id.as_deref() {
+            Some(id) if !id.starts_with("Label_") => {
+                parts.push(format!("in:{}", id.to_lowercase()))
+            }
+            Some(_) => parts.push(format!("label:{}", quote_if_needed(&term.name))),
+            None => {}
+        }
+    }
+    // Gmail's own syntax: `-term`, `(a b)` and `a OR b`.
+    for excluded in &query.exclude {
+        let q = translate_query_to_q(excluded);
+        if !q.is_empty() {
+            parts.push(format!("-{}", gmail_group(q)));
+        }
+    }
+    for group in &query.any_of {
+        let alternatives: Vec<String> = group
+            .iter()
+            .map(|alt| gmail_group(translate_query_to_q(alt)))
+            .collect();
+        // An empty alternative matches everything, and so does the group.
+        if alternatives.iter().all(|a| !a.is_empty()) {
+            parts.push(format!("({})", alternatives.join(" OR ")));
+        }
+    }
     // Mailbox scoping is applied via the `labelIds=` URL parameter in
     // `fetch_messages_page`, not via `q=`, so this function takes no mailbox arg.
 
     parts.join(" ")
 }
 
+/// `q` parenthesized when it's more than one term, so a `-` or `OR`
+/// applies to all of it. Spaces inside quoted values don't count.
+fn gmail_group(q: String) -> String {
+    let mut quoted = false;
+    let mut escaped = false;
+    let several = q.chars().any(|c| {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ' ' if !quoted => return true,
+            _ => {}
+        }
+        false
+    });
+    if several { format!("({q})") } else { q }
+}
+
 fn quote_if_needed(s: &str) -> String {
     let needs_quote = s.contains(' ') || s.contains(':') || s.contains('"');
     if needs_quote {
@@ -759,6 +807,214 @@ async fn fetch_messages_page(
     Ok(resp.json().await?)
 }
 
+/// Find the message whose Message-ID header is `message_id` (normalized,
+/// `<…>` form) via Gmail's `rfc822msgid:` operator, which takes the bare id.
+pub async fn find_by_message_id(
+    session: &GmailSession,
+    message_id: &str,
+) -> Result<Option<String>, Error> {
+    let token = access_token(session).await?;
+    let bare = message_id.trim_start_matches('<').trim_end_matches('>');
+    let resp =
+        fetch_messages_page(session, &token, None, &format!("rfc822msgid:{bare}"), None).await?;
+    Ok(resp.messages.into_iter().next().map(|m| m.id))
+}
+
+/// Every header field of one message, in message order, from
+/// `messages.get?format=metadata` (which returns all of them when no
+/// `metadataHeaders` are named).
+pub async fn get_headers(
+    session: &GmailSession,
+    email_id: &str,
+) -> Result<Vec<EmailHeader>, Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(email_id);
+    let url = format!("{GMAIL_BASE}/messages/{encoded}?format=metadata");
+    let resp = session
+        .limiter
+        .execute("messages.get", || async {
+            session.client.get(&url).bearer_auth(&token).send().await
+        })
+        .await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound("Email not found".into()));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error(
+            &format!("messages.get {email_id}"),
+            status,
+            &text,
+        ));
+    }
+    let msg: GmailMessage = resp.json().await?;
+    Ok(msg
+        .payload
+        .headers
+        .into_iter()
+        .map(|h| EmailHeader {
+            name: h.name,
+            value: h.value,
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    raw: String,
+}
+
+/// `messages.get?format=raw`: the RFC 5322 message, base64url-encoded.
+pub async fn get_raw_message(session: &GmailSession, email_id: &str) -> Result<Vec<u8>, Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(email_id);
+    let url = format!("{GMAIL_BASE}/messages/{encoded}?format=raw");
+    let resp = session
+        .limiter
+        .execute("messages.get", || async {
+            session.client.get(&url).bearer_auth(&token).send().await
+        })
+        .await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound("Email not found".into()));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error(
+            &format!("messages.get {email_id}"),
+            status,
+            &text,
+        ));
+    }
+    let msg: RawMessage = resp.json().await?;
+    base64url_decode(&msg.raw)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryProfile {
+    history_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryListResp {
+    #[serde(default)]
+    history: Vec<HistoryRecord>,
+    next_page_token: Option<String>,
+    history_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryRecord {
+    id: String,
+    #[serde(default)]
+    messages_added: Vec<HistoryMessage>,
+    #[serde(default)]
+    messages_deleted: Vec<HistoryMessage>,
+    #[serde(default)]
+    labels_added: Vec<HistoryMessage>,
+    #[serde(default)]
+    labels_removed: Vec<HistoryMessage>,
+}
+
+#[derive(Deserialize)]
+struct HistoryMessage {
+    message: MessageRef,
+}
+
+/// Email ids changed since history id `since` (`history.list`), shaped
+/// like JMAP `Email/changes`. Without `since`, only the current history id.
+pub async fn email_changes(
+    session: &GmailSession,
+    since: Option<&str>,
+    max_changes: usize,
+) -> Result<EmailChanges, Error> {
+    let token = access_token(session).await?;
+    let Some(since) = since else {
+        let resp = session
+            .client
+            .get(format!("{GMAIL_BASE}/profile"))
+            .bearer_auth(&token)
+            .send()
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(classify_gmail_error("getProfile", status, &text));
+        }
+        let profile: HistoryProfile = resp.json().await?;
+        return Ok(EmailChanges {
+            old_state: profile.history_id.clone(),
+            new_state: profile.history_id,
+            ..Default::default()
+        });
+    };
+    let mut url = url::Url::parse(&format!("{GMAIL_BASE}/history")).expect("valid base");
+    url.query_pairs_mut()
+        .append_pair("startHistoryId", since)
+        .append_pair("maxResults", &max_changes.to_string());
+    let resp = session.client.get(url).bearer_auth(&token).send().await?;
+    let status = resp.status();
+    // Gmail keeps roughly a week of history; an older start id is a 404.
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::Conflict(
+            "history id is too old to compute changes from; resync without `since`".into(),
+        ));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error("history.list", status, &text));
+    }
+    let parsed: HistoryListResp = resp.json().await?;
+    Ok(history_to_changes(since, parsed))
+}
+
+/// Fold history records into created/updated/destroyed id lists. Each id
+/// lands in one list: a deletion outranks an add, an add outranks a label
+/// change. With another page waiting, the new state is the last record's
+/// id rather than the mailbox's latest, so the next call resumes there.
+/// Pure — unit-tested.
+fn history_to_changes(since: &str, resp: HistoryListResp) -> EmailChanges {
+    let mut created = Vec::new();
+    let mut updated = Vec::new();
+    let mut destroyed = Vec::new();
+    let new_state = match (&resp.next_page_token, resp.history.last()) {
+        (Some(_), Some(last)) => last.id.clone(),
+        _ => resp.history_id.clone(),
+    };
+    for record in &resp.history {
+        for m in &record.messages_deleted {
+            push_unique(&mut destroyed, &m.message.id);
+        }
+        for m in &record.messages_added {
+            push_unique(&mut created, &m.message.id);
+        }
+        for m in record.labels_added.iter().chain(&record.labels_removed) {
+            push_unique(&mut updated, &m.message.id);
+        }
+    }
+    created.retain(|id| !destroyed.contains(id));
+    updated.retain(|id| !destroyed.contains(id) && !created.contains(id));
+    EmailChanges {
+        old_state: since.to_string(),
+        new_state,
+        has_more_changes: resp.next_page_token.is_some(),
+        created,
+        updated,
+        destroyed,
+    }
+}
+
+fn push_unique(ids: &mut Vec<String>, id: &str) {
+    if !ids.iter().any(|existing| existing == id) {
+        ids.push(id.to_string());
+    }
+}
+
 /// Translate a Gmail `nextPageToken` to the cache's `PageStart` for the *next*
 /// index. `None` from the API means "no more pages" (End sentinel); `Some(t)`
 /// means "use this token". Pure function — extracted for unit testing.
@@ -1067,15 +1323,38 @@ pub fn parse_message_to_email(msg: GmailMessage, fetch_body: bool) -> Email {
     let mut from: Vec<EmailAddress> = Vec::new();
     let mut to: Vec<EmailAddress> = Vec::new();
     let mut cc: Vec<EmailAddress> = Vec::new();
+    let mut list_unsubscribe: Option<String> = None;
+    let mut message_id: Option<String> = None;
+    let mut references: Vec<String> = Vec::new();
+    let mut reply_to: Vec<EmailAddress> = Vec::new();
     for h in &msg.payload.headers {
         match h.name.to_ascii_lowercase().as_str() {
             "subject" => subject = h.value.clone(),
             "from" => from = parse_address_list(&h.value),
             "to" => to = parse_address_list(&h.value),
             "cc" => cc = parse_address_list(&h.value),
+            "list-unsubscribe" if fetch_body => list_unsubscribe = Some(h.value.clone()),
+            "message-id" if fetch_body => {
+                message_id = crate::provider_utils::normalize_message_id(&h.value).ok();
+            }
+            "references" if fetch_body => {
+                references = h
+                    .value
+                    .split_whitespace()
+                    .filter_map(|id| crate::provider_utils::normalize_message_id(id).ok())
+                    .collect();
+            }
+            "reply-to" if fetch_body => reply_to = parse_address_list(&h.value),
             _ => {}
         }
     }
+    let spam = SpamInfo::from_headers(|name| {
+        msg.payload
+            .headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    });
 
     let received_at = msg
         .internal_date
@@ -1136,6 +1415,12 @@ pub fn parse_message_to_email(msg: GmailMessage, fetch_body: bool) -> Email {
         // Drafts (the only consumer) are Fastmail-only in v1 — not parsed
         // out of Gmail's In-Reply-To header yet.
         in_reply_to: None,
+        body_parts: Vec::new(),
+        list_unsubscribe,
+        message_id,
+        references,
+        reply_to,
+        spam,
     }
 }
 
@@ -1331,6 +1616,9 @@ fn walk_payload(
             name: filename.unwrap_or("").to_string(),
             mime_type: part.mime_type.clone(),
             size: part.body.as_ref().map(|b| b.size).unwrap_or(0),
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
         });
     }
 }
@@ -1480,6 +1768,118 @@ async fn modify_labels(
     ))
 }
 
+/// Apply the user label named `keyword`, creating it on first use. Gmail
+/// has no free-form keywords, so a label is the closest equivalent and
+/// shows up in every Gmail client.
+pub async fn add_keyword(
+    session: &GmailSession,
+    msg_id: &str,
+    keyword: &str,
+) -> Result<bool, Error> {
+    let existing = get_mailboxes(session)
+        .await?
+        .into_iter()
+        .find(|m| m.name.eq_ignore_ascii_case(keyword))
+        .map(|m| m.id);
+    let label_id = match existing {
+        Some(id) => id,
+        None => create_label(session, keyword).await?,
+    };
+    modify_labels(session, msg_id, &[label_id.as_str()], &[]).await
+}
+
+pub async fn create_label(session: &GmailSession, name: &str) -> Result<String, Error> {
+    let token = access_token(session).await?;
+    let body = serde_json::json!({
+        "name": name,
+        "labelListVisibility": "labelShow",
+        "messageListVisibility": "show",
+    });
+    let resp = session
+        .limiter
+        .execute("labels.create", || async {
+            session
+                .client
+                .post(format!("{GMAIL_BASE}/labels"))
+                .bearer_auth(&token)
+                .json(&body)
+                .send()
+                .await
+        })
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error("labels.create", status, &text));
+    }
+    invalidate_label_cache(session).await;
+    let created: serde_json::Value = resp.json().await?;
+    created["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| Error::Internal("labels.create returned no id".into()))
+}
+
+/// Rename a user label (`labels.patch`). Gmail nests labels by name, so
+/// this is also how one moves under another (`Parent/Child`).
+pub async fn rename_label(session: &GmailSession, label_id: &str, name: &str) -> Result<(), Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(label_id);
+    let body = serde_json::json!({ "name": name });
+    let resp = session
+        .limiter
+        .execute("labels.patch", || async {
+            session
+                .client
+                .patch(format!("{GMAIL_BASE}/labels/{encoded}"))
+                .bearer_auth(&token)
+                .json(&body)
+                .send()
+                .await
+        })
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error(
+            &format!("labels.patch {label_id}"),
+            status,
+            &text,
+        ));
+    }
+    invalidate_label_cache(session).await;
+    Ok(())
+}
+
+/// Delete a user label (`labels.delete`). Messages keep their other
+/// labels; ones that only had this label stay in All Mail.
+pub async fn delete_label(session: &GmailSession, label_id: &str) -> Result<(), Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(label_id);
+    let resp = session
+        .limiter
+        .execute("labels.delete", || async {
+            session
+                .client
+                .delete(format!("{GMAIL_BASE}/labels/{encoded}"))
+                .bearer_auth(&token)
+                .send()
+                .await
+        })
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error(
+            &format!("labels.delete {label_id}"),
+            status,
+            &text,
+        ));
+    }
+    invalidate_label_cache(session).await;
+    Ok(())
+}
+
 pub async fn mark_read(session: &GmailSession, msg_id: &str) -> Result<bool, Error> {
     modify_labels(session, msg_id, &[], &["UNREAD"]).await
 }
@@ -1523,6 +1923,16 @@ pub async fn move_to_mailbox(
     }
 }
 
+/// Swap label `from` for `to` in one `messages.modify`.
+pub async fn move_between(
+    session: &GmailSession,
+    msg_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<bool, Error> {
+    modify_labels(session, msg_id, &[to], &[from]).await
+}
+
 /// What `move_to_mailbox` should do for a given target. Pure — extracted for
 /// unit testing the INBOX special case + rejection list without HTTP.
 #[derive(Debug, PartialEq, Eq)]
@@ -1632,6 +2042,33 @@ pub async fn trash(session: &GmailSession, msg_id: &str) -> Result<bool, Error>
     Ok(true)
 }
 
+/// Take a message out of Trash (`messages.untrash`), then re-add
+/// `labels` in case the trash dropped any of them.
+pub async fn untrash(
+    session: &GmailSession,
+    msg_id: &str,
+    labels: &[String],
+) -> Result<bool, Error> {
+    let token = access_token(session).await?;
+    let url = format!("{GMAIL_BASE}/messages/{msg_id}/untrash");
+    let resp = session.client.post(&url).bearer_auth(&token).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_gmail_error(
+            &format!("messages.untrash {msg_id}"),
+            status,
+            &text,
+        ));
+    }
+    invalidate_label_cache(session).await;
+    if labels.is_empty() {
+        return Ok(true);
+    }
+    let add: Vec<&str> = labels.iter().map(String::as_str).collect();
+    modify_labels(session, msg_id, &add, &[]).await
+}
+
 /// Archive a batch of messages in one API call. Returns the count of IDs
 /// *submitted*, not necessarily archived — Gmail's `batchModify` returns 204
 /// with no body, so per-ID success isn't observable. If any single ID in the
@@ -1660,6 +2097,35 @@ pub async fn archive_batch(session: &GmailSession, msg_ids: &[String]) -> Result
     Ok(msg_ids.len())
 }
 
+/// `messages.batchModify` accepts at most 1000 ids per call.
+const BATCH_MODIFY_MAX_IDS: usize = 1000;
+
+/// Drop the UNREAD label from every id via `messages.batchModify`.
+pub async fn mark_read_batch(session: &GmailSession, msg_ids: &[String]) -> Result<usize, Error> {
+    if msg_ids.is_empty() {
+        return Ok(0);
+    }
+    let token = access_token(session).await?;
+    let url = format!("{GMAIL_BASE}/messages/batchModify");
+    for chunk in msg_ids.chunks(BATCH_MODIFY_MAX_IDS) {
+        let resp = session
+            .client
+            .post(&url)
+            .bearer_auth(&token)
+            .json(&batch_modify_body(chunk, &[], &["UNREAD"]))
+            .send()
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            invalidate_label_cache(session).await;
+            return Err(classify_gmail_error("messages.batchModify", status, &text));
+        }
+    }
+    invalidate_label_cache(session).await;
+    Ok(msg_ids.len())
+}
+
 // =============================================================================
 // download_blob — messages.attachments.get
 // =============================================================================
@@ -2043,8 +2509,11 @@ async fn build_rfc822(
         builder = builder.html_body(html.clone());
     }
 
-    for att in &sub.attachments {
-        let (resolved_mime, bytes) = peek_blob_bytes(session, &att.blob_id, &att.name).await?;
+    let resolved = try_join_bounded(&sub.attachments, ATTACHMENT_FETCH_CONCURRENCY, |att| {
+        peek_blob_bytes(session, &att.blob_id, &att.name)
+    })
+    .await?;
+    for (att, (resolved_mime, bytes)) in sub.attachments.iter().zip(resolved) {
         // Prefer the EmailSubmission's mime_type (from the original Email
         // metadata), fall back to whatever resolve returned (extension guess).
         let mime = if !att.mime_type.is_empty() {
@@ -2186,10 +2655,16 @@ async fn find_event_id_by_ical_uid(
 /// - `iCalUID` is preserved so `events.import` deduplicates across accounts.
 /// - Times always go out as UTC (we store UTC internally).
 /// - End time defaults to start+1h if missing (matches Outlook's path).
+/// - All-day events go out as `{date}` blocks (end exclusive, default +1 day)
+///   so Google keeps them floating instead of pinning them to UTC midnight.
 pub(crate) fn calendar_event_to_google_json(event: &CalendarEvent) -> serde_json::Value {
-    let dtend = event
-        .dtend
-        .unwrap_or_else(|| event.dtstart + chrono::Duration::hours(1));
+    let dtend = event.dtend.unwrap_or_else(|| {
+        if event.all_day {
+            event.dtstart + chrono::Duration::days(1)
+        } else {
+            event.dtstart + chrono::Duration::hours(1)
+        }
+    });
     let mut body = serde_json::json!({
         "iCalUID": event.uid,
         "summary": event.summary,
@@ -2207,6 +2682,10 @@ pub(crate) fn calendar_event_to_google_json(event: &CalendarEvent) -> serde_json
             "timeZone": "UTC",
         },
     });
+    if event.all_day {
+        body["start"] = serde_json::json!({ "date": event.dtstart.format("%Y-%m-%d").to_string() });
+        body["end"] = serde_json::json!({ "date": dtend.format("%Y-%m-%d").to_string() });
+    }
 
     if let Some(loc) = &event.location
         && !loc.is_empty()
@@ -2319,6 +2798,11 @@ pub(crate) fn parse_google_event(
         raw_ics: String::new(),
         user_rsvp_status: None,
         is_update: false,
+        changes: Vec::new(),
+        organizer_mismatch: None,
+        my_response: None,
+        alarms: Vec::new(),
+        all_day: event_json["start"]["date"].is_string(),
     })
 }
 
@@ -2625,14 +3109,17 @@ pub async fn get_calendar_data(
     Ok(find_calendar_ics(&msg.payload))
 }
 
-/// Walk the payload tree until a `text/calendar` part with inline data is
-/// found, then return its decoded UTF-8 string. Pure — testable with
+/// Walk the payload tree, decoding every `text/calendar` part with inline
+/// data, and return the invite among them (`calendar::pick_invite_ics`).
+/// Gmail expands attached `message/rfc822` parts into `parts`, so an
+/// invite inside a forward is found by the same walk. Pure — testable with
 /// hand-rolled GmailPayload fixtures.
 ///
 /// Iterative DFS rather than recursion so a pathological or malicious
 /// payload can't blow the stack. Pre-order traversal preserves the original
 /// "outer parts first, then nested" order.
 pub(crate) fn find_calendar_ics(part: &GmailPayload) -> Option<String> {
+    let mut found = Vec::new();
     let mut stack: Vec<&GmailPayload> = vec![part];
     while let Some(node) = stack.pop() {
         if node.mime_type.eq_ignore_ascii_case("text/calendar")
@@ -2641,7 +3128,7 @@ pub(crate) fn find_calendar_ics(part: &GmailPayload) -> Option<String> {
             && let Ok(bytes) = base64url_decode(data)
             && let Ok(s) = String::from_utf8(bytes)
         {
-            return Some(s);
+            found.push(s);
         }
         if let Some(parts) = &node.parts {
             // Push in reverse so pop() yields siblings in original order.
@@ -2650,7 +3137,7 @@ pub(crate) fn find_calendar_ics(part: &GmailPayload) -> Option<String> {
             }
         }
     }
-    None
+    crate::calendar::pick_invite_ics(found)
 }
 
 // =============================================================================
@@ -2894,6 +3381,31 @@ mod tests {
         assert!(!s.contains('-'));
     }
 
+    #[test]
+    fn q_translator_keeps_or_and_negation() {
+        let q = crate::search::parse_query(
+            "-from:bob NOT (is:unread has:attachment) (subject:invoice OR to:\"Ann Lee\")",
+        );
+        assert_eq!(
+            translate_query_to_q(&q),
+            "-from:bob -(has:attachment is:unread) (subject:invoice OR to:\"Ann Lee\")"
+        );
+    }
+
+    #[test]
+    fn q_translator_sizes_in_bytes() {
+        let q = crate::search::parse_query("larger:2M smaller:10M");
+        assert_eq!(translate_query_to_q(&q), "larger:2097152 smaller:10485760");
+    }
+
+    #[test]
+    fn q_translator_mailboxes() {
+        let mut q = crate::search::parse_query("in:inbox label:\"Client Work\" in:nowhere");
+        q.mailboxes[0].id = Some("INBOX".into());
+        q.mailboxes[1].id = Some("Label_12".into());
+        assert_eq!(translate_query_to_q(&q), "in:inbox label:\"Client Work\"");
+    }
+
     #[test]
     fn q_translator_free_text_passthrough() {
         let q = ParsedQuery {
@@ -3108,6 +3620,61 @@ mod tests {
         assert!(!email.is_flagged());
     }
 
+    #[test]
+    fn parse_message_captures_list_unsubscribe_on_body_fetch() {
+        let msg = |fetch_body| {
+            let m = GmailMessage {
+                id: "msg1".into(),
+                thread_id: "thr1".into(),
+                label_ids: vec!["INBOX".into()],
+                snippet: String::new(),
+                internal_date: "1700000000000".into(),
+                size_estimate: 10,
+                payload: GmailPayload {
+                    mime_type: "text/plain".into(),
+                    filename: String::new(),
+                    headers: vec![header(
+                        "List-Unsubscribe",
+                        "<mailto:unsub@example.com>, <https://example.com/u>",
+                    )],
+                    body: None,
+                    parts: None,
+                },
+            };
+            parse_message_to_email(m, fetch_body)
+        };
+        assert_eq!(
+            msg(true).list_unsubscribe.as_deref(),
+            Some("<mailto:unsub@example.com>, <https://example.com/u>")
+        );
+        assert!(msg(false).list_unsubscribe.is_none());
+    }
+
+    #[test]
+    fn parse_message_reads_spam_headers_on_metadata_fetch() {
+        let m = GmailMessage {
+            id: "msg1".into(),
+            thread_id: "thr1".into(),
+            label_ids: vec!["INBOX".into()],
+            snippet: String::new(),
+            internal_date: "1700000000000".into(),
+            size_estimate: 10,
+            payload: GmailPayload {
+                mime_type: "text/plain".into(),
+                filename: String::new(),
+                headers: vec![header(
+                    "x-spam-status",
+                    "No, score=2.4 required=5.0 tests=HTML_MESSAGE",
+                )],
+                body: None,
+                parts: None,
+            },
+        };
+        let spam = parse_message_to_email(m, false).spam.unwrap();
+        assert_eq!(spam.score, Some(2.4));
+        assert_eq!(spam.known_sender, None);
+    }
+
     #[test]
     fn parse_message_text_and_html_alternative() {
         let msg = GmailMessage {
@@ -3966,12 +4533,18 @@ mod tests {
                 name: "a.txt".into(),
                 mime_type: "text/plain".into(),
                 size: 1,
+                sniffed_mime_type: None,
+                mime_mismatch: false,
+                content_id: None,
             },
             crate::types::Attachment {
                 blob_id: "msg-abc:att-xyz".into(),
                 name: "b.pdf".into(),
                 mime_type: "application/pdf".into(),
                 size: 0,
+                sniffed_mime_type: None,
+                mime_mismatch: false,
+                content_id: None,
             },
         ];
         drain_consumed_synthetic_blobs(&session, &atts).await;
@@ -4052,6 +4625,7 @@ mod tests {
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         }
     }
 
@@ -4142,6 +4716,9 @@ mod tests {
             name: "report.pdf".into(),
             mime_type: "application/pdf".into(),
             size: 9,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
         }];
         let raw = build_rfc822(&session, &sub, "from@example.com", None, None, None)
             .await
@@ -4284,6 +4861,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         }
     }
 
@@ -4347,6 +4929,17 @@ mod tests {
         assert!(j.get("location").is_none());
     }
 
+    #[test]
+    fn google_json_all_day_uses_date_blocks() {
+        let mut ev = sample_event();
+        ev.dtstart = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 12, 25, 0, 0, 0).unwrap();
+        ev.dtend = None;
+        ev.all_day = true;
+        let j = calendar_event_to_google_json(&ev);
+        assert_eq!(j["start"], serde_json::json!({ "date": "2026-12-25" }));
+        assert_eq!(j["end"], serde_json::json!({ "date": "2026-12-26" }));
+    }
+
     #[test]
     fn google_json_omits_empty_description() {
         let mut ev = sample_event();
@@ -4477,6 +5070,7 @@ mod tests {
             "end": { "date": "2026-12-26" }
         });
         let parsed = parse_google_event("uid-holiday", &json).unwrap();
+        assert!(parsed.all_day);
         assert_eq!(parsed.dtstart.format("%Y-%m-%d").to_string(), "2026-12-25");
         assert_eq!(
             parsed.dtend.unwrap().format("%Y-%m-%d").to_string(),
@@ -4684,6 +5278,35 @@ mod tests {
         assert!(ics.contains("UID:first"));
     }
 
+    #[test]
+    fn find_calendar_ics_prefers_part_with_method() {
+        // A forward carrying an exported copy and the original iTIP invite
+        // (inside the attached message): the invite wins.
+        let forwarded = GmailPayload {
+            mime_type: "message/rfc822".into(),
+            filename: String::new(),
+            headers: vec![],
+            body: None,
+            parts: Some(vec![calendar_part(
+                "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nUID:invite\r\nEND:VEVENT\r\nEND:VCALENDAR",
+            )]),
+        };
+        let payload = GmailPayload {
+            mime_type: "multipart/mixed".into(),
+            filename: String::new(),
+            headers: vec![],
+            body: None,
+            parts: Some(vec![
+                calendar_part(
+                    "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:export\r\nEND:VEVENT\r\nEND:VCALENDAR",
+                ),
+                forwarded,
+            ]),
+        };
+        let ics = find_calendar_ics(&payload).unwrap();
+        assert!(ics.contains("UID:invite"));
+    }
+
     // ---- concurrency cap on get_emails fan-out ----
     //
     // get_emails fans out one messages.get per ID through a shared
@@ -4707,4 +5330,66 @@ mod tests {
         assert_eq!(session.limiter.concurrency(), 5);
         assert_eq!(session.limiter.spacing(), Duration::from_millis(80));
     }
+
+    // --- history.list → changes ---
+
+    fn history(json: serde_json::Value) -> HistoryListResp {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn history_to_changes_sorts_ids_by_kind() {
+        let resp = history(serde_json::json!({
+            "historyId": "120",
+            "history": [
+                { "id": "101", "messagesAdded": [{ "message": { "id": "new" } }] },
+                { "id": "102", "labelsAdded": [{ "message": { "id": "old" }, "labelIds": ["STARRED"] }] },
+                { "id": "103", "labelsRemoved": [{ "message": { "id": "new" }, "labelIds": ["UNREAD"] }] },
+                { "id": "104", "messagesDeleted": [{ "message": { "id": "gone" } }] }
+            ]
+        }));
+        let changes = history_to_changes("100", resp);
+        assert_eq!(changes.old_state, "100");
+        assert_eq!(changes.new_state, "120");
+        assert!(!changes.has_more_changes);
+        assert_eq!(changes.created, ["new"]);
+        assert_eq!(changes.updated, ["old"]);
+        assert_eq!(changes.destroyed, ["gone"]);
+    }
+
+    #[test]
+    fn history_to_changes_deletion_wins_and_ids_dedupe() {
+        let resp = history(serde_json::json!({
+            "historyId": "9",
+            "history": [
+                { "id": "5", "messagesAdded": [{ "message": { "id": "a" } }] },
+                { "id": "6", "labelsAdded": [{ "message": { "id": "b" } }, { "message": { "id": "b" } }] },
+                { "id": "7", "messagesDeleted": [{ "message": { "id": "a" } }] }
+            ]
+        }));
+        let changes = history_to_changes("4", resp);
+        assert!(changes.created.is_empty());
+        assert_eq!(changes.updated, ["b"]);
+        assert_eq!(changes.destroyed, ["a"]);
+    }
+
+    #[test]
+    fn history_to_changes_resumes_from_last_record_when_paged() {
+        let resp = history(serde_json::json!({
+            "historyId": "900",
+            "nextPageToken": "p2",
+            "history": [{ "id": "150", "messagesAdded": [{ "message": { "id": "x" } }] }]
+        }));
+        let changes = history_to_changes("100", resp);
+        assert!(changes.has_more_changes);
+        assert_eq!(changes.new_state, "150");
+    }
+
+    #[test]
+    fn history_to_changes_without_records_keeps_latest_id() {
+        let resp = history(serde_json::json!({ "historyId": "77" }));
+        let changes = history_to_changes("77", resp);
+        assert_eq!(changes.new_state, "77");
+        assert!(changes.created.is_empty() && changes.updated.is_empty());
+    }
 }
@@ -0,0 +1,208 @@
+//! Named recipient groups.
+//!
+//! `groups.json` lives next to `splits.json`. A compose recipient written as
+//! `group:<id>` is expanded server-side by [`expand_recipients`] into the
+//! group's member addresses before the submission reaches a provider, so
+//! the same group works on Fastmail, Outlook, and Gmail and the client
+//! never has to know the member list.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Recipient prefix that marks a group reference in a send body.
+pub const GROUP_PREFIX: &str = "group:";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecipientGroup {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupsConfig {
+    #[serde(default)]
+    pub groups: Vec<RecipientGroup>,
+}
+
+// =============================================================================
+// Config load/save
+// =============================================================================
+
+pub fn load_groups(config_path: &Path) -> GroupsConfig {
+    if config_path.exists() {
+        let content = match std::fs::read_to_string(config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read groups config: {e}");
+                return GroupsConfig::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    GroupsConfig::default()
+}
+
+pub fn save_groups(config: &GroupsConfig, config_path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(config)?;
+    crate::accounts::atomic_write_bytes(config_path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Validation
+// =============================================================================
+
+/// Reject groups that could never expand to something sendable. Members
+/// must be plain addresses: nesting (`group:` inside a group) is refused
+/// rather than resolved, so expansion stays a single flat lookup with no
+/// cycle handling.
+pub fn validate_group(group: &RecipientGroup) -> Result<(), Error> {
+    if group.id.trim().is_empty() {
+        return Err(Error::BadRequest("Group id must not be empty".into()));
+    }
+    if group.id.contains(char::is_whitespace) {
+        return Err(Error::BadRequest(format!(
+            "Group id '{}' must not contain whitespace",
+            group.id
+        )));
+    }
+    for member in &group.members {
+        if member.starts_with(GROUP_PREFIX) {
+            return Err(Error::BadRequest(format!(
+                "Group '{}' cannot contain another group",
+                group.id
+            )));
+        }
+        if !member.contains('@') {
+            return Err(Error::BadRequest(format!(
+                "Invalid member address '{member}' in group '{}'",
+                group.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+// =============================================================================
+// Expansion
+// =============================================================================
+
+/// Replace every `group:<id>` entry with that group's members. Plain
+/// addresses pass through unchanged. The result is de-duplicated
+/// case-insensitively, keeping the first occurrence, so a person who is
+/// both listed directly and via a group gets one copy. An unknown group id
+/// is an error — silently dropping it would send to fewer people than the
+/// user asked for.
+pub fn expand_recipients(
+    recipients: &[String],
+    config: &GroupsConfig,
+) -> Result<Vec<String>, Error> {
+    let mut out: Vec<String> = Vec::with_capacity(recipients.len());
+    let mut push = |addr: &str| {
+        if !out.iter().any(|a| a.eq_ignore_ascii_case(addr)) {
+            out.push(addr.to_string());
+        }
+    };
+    for r in recipients {
+        let r = r.trim();
+        match r.strip_prefix(GROUP_PREFIX) {
+            Some(id) => {
+                let group = config
+                    .groups
+                    .iter()
+                    .find(|g| g.id == id)
+                    .ok_or_else(|| Error::BadRequest(format!("Unknown group '{id}'")))?;
+                for member in &group.members {
+                    push(member.trim());
+                }
+            }
+            None => push(r),
+        }
+    }
+    Ok(out)
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GroupsConfig {
+        GroupsConfig {
+            groups: vec![RecipientGroup {
+                id: "team".into(),
+                name: "Team".into(),
+                members: vec!["a@x.com".into(), "b@x.com".into()],
+            }],
+        }
+    }
+
+    #[test]
+    fn expand_replaces_group_with_members() {
+        let out = expand_recipients(&["group:team".into()], &config()).unwrap();
+        assert_eq!(out, vec!["a@x.com", "b@x.com"]);
+    }
+
+    #[test]
+    fn expand_passes_plain_addresses_through() {
+        let out = expand_recipients(&["c@x.com".into(), "group:team".into()], &config()).unwrap();
+        assert_eq!(out, vec!["c@x.com", "a@x.com", "b@x.com"]);
+    }
+
+    #[test]
+    fn expand_dedupes_case_insensitively() {
+        let out = expand_recipients(&["A@X.com".into(), "group:team".into()], &config()).unwrap();
+        assert_eq!(out, vec!["A@X.com", "b@x.com"]);
+    }
+
+    #[test]
+    fn expand_unknown_group_is_error() {
+        let err = expand_recipients(&["group:nope".into()], &config()).unwrap_err();
+        assert!(matches!(err, Error::BadRequest(m) if m.contains("nope")));
+    }
+
+    #[test]
+    fn validate_rejects_nested_group() {
+        let g = RecipientGroup {
+            id: "outer".into(),
+            name: "Outer".into(),
+            members: vec!["group:team".into()],
+        };
+        assert!(validate_group(&g).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_blank_id_and_bad_member() {
+        let mut g = config().groups.remove(0);
+        g.id = " ".into();
+        assert!(validate_group(&g).is_err());
+        let mut g = config().groups.remove(0);
+        g.members.push("not-an-address".into());
+        assert!(validate_group(&g).is_err());
+    }
+
+    #[test]
+    fn save_then_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("supervillain/groups.json");
+        save_groups(&config(), &path).unwrap();
+        let loaded = load_groups(&path);
+        assert_eq!(loaded.groups, config().groups);
+    }
+
+    #[test]
+    fn load_missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(
+            load_groups(&dir.path().join("missing.json"))
+                .groups
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! The HTTP client every outbound call is built from.
+//!
+//! JMAP, CardDAV, Graph, Gmail, OAuth token exchanges and the package
+//! trackers all get their `reqwest::Client` from [`client`], so one place
+//! decides the timeout, the `User-Agent` and any extra headers. The last
+//! two come from the global section of the config file:
+//!
+//! ```ini
+//! user-agent = supervillain (ops@example.com)
+//! http-header = X-Corp-Proxy-Auth: abc123
+//! http-header = X-Team: mail
+//! ```
+//!
+//! `http-header` may repeat. Both are read once at startup ([`configure`]);
+//! sessions created later reuse the same settings, so a change needs a
+//! restart. Per-request headers (`Authorization`, `Content-Type`) win over
+//! configured ones of the same name.
+//!
+//! `proxy = socks5h://127.0.0.1:1080` (or an `http://`/`https://` proxy)
+//! sends everything through it. An account section may set its own
+//! `proxy`, used by that account's session instead ([`client_via`]);
+//! `proxy = none` there connects directly. With no `proxy` anywhere the
+//! usual `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY`
+//! environment variables apply.
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Sent when the config names no `user-agent`.
+pub const DEFAULT_USER_AGENT: &str = concat!("supervillain/", env!("CARGO_PKG_VERSION"));
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `proxy` value that turns proxying off, environment variables included.
+pub const NO_PROXY: &str = "none";
+
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// Global `user-agent` and `http-header` settings. `Default` sends
+/// [`DEFAULT_USER_AGENT`] and nothing extra.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpConfig {
+    pub user_agent: Option<String>,
+    /// `(name, value)` in config order; validated by [`parse_header`].
+    pub headers: Vec<(String, String)>,
+    /// Proxy URL or [`NO_PROXY`]; validated by [`parse_proxy`]. Unset
+    /// leaves it to the environment.
+    pub proxy: Option<String>,
+}
+
+impl HttpConfig {
+    fn user_agent(&self) -> &str {
+        self.user_agent
+            .as_deref()
+            .filter(|ua| !ua.is_empty())
+            .unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    fn header_map(&self) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            // Checked at parse time; skipping here only guards hand-built configs.
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                map.append(name, value);
+            }
+        }
+        map
+    }
+}
+
+/// Split an `http-header = Name: Value` line, rejecting what `reqwest`
+/// couldn't send.
+pub fn parse_header(line: &str) -> Result<(String, String), String> {
+    let (name, value) = line
+        .split_once(':')
+        .ok_or_else(|| format!("expected `Name: Value`, got `{line}`"))?;
+    let (name, value) = (name.trim(), value.trim());
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid header name `{name}`"))?;
+    HeaderValue::from_str(value).map_err(|_| format!("invalid value for header `{name}`"))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Check a `proxy` value: [`NO_PROXY`], or a URL with one of the schemes
+/// reqwest can tunnel through. `socks5h` resolves names at the proxy.
+pub fn parse_proxy(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case(NO_PROXY) {
+        return Ok(NO_PROXY.to_string());
+    }
+    let scheme = value
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase())
+        .ok_or_else(|| format!("expected `scheme://host:port` or `none`, got `{value}`"))?;
+    if !PROXY_SCHEMES.contains(&scheme.as_str()) {
+        return Err(format!(
+            "unsupported proxy scheme `{scheme}` (use {})",
+            PROXY_SCHEMES.join(", ")
+        ));
+    }
+    reqwest::Proxy::all(value).map_err(|e| format!("invalid proxy `{value}`: {e}"))?;
+    Ok(value.to_string())
+}
+
+static CONFIG: OnceLock<HttpConfig> = OnceLock::new();
+
+/// Install the settings [`client`] uses from now on. Called once at
+/// startup, before any session connects; later calls are ignored.
+pub fn configure(config: HttpConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("http client already configured; ignoring new settings");
+    }
+}
+
+/// A client with the configured user agent, headers and proxy.
+pub fn client() -> reqwest::Client {
+    client_via(None)
+}
+
+/// [`client`] for an account with its own `proxy`, which replaces the
+/// global one. `None` keeps the global setting.
+pub fn client_via(proxy: Option<&str>) -> reqwest::Client {
+    let config = CONFIG.get_or_init(HttpConfig::default);
+    build(config, proxy.or(config.proxy.as_deref()))
+}
+
+/// [`client`] that returns redirects instead of following them and looks
+/// names up through `resolver`, for callers that must vet each hop and
+/// every address they connect to (`image_proxy`, `unsubscribe`).
+pub fn client_without_redirects<R: reqwest::dns::Resolve + 'static>(
+    resolver: Arc<R>,
+) -> reqwest::Client {
+    let config = CONFIG.get_or_init(HttpConfig::default);
+    builder(config, config.proxy.as_deref())
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(resolver)
+        .build()
+        .expect("failed to create HTTP client")
+}
+
+/// Hosts of the proxies requests may go through: the configured `proxy`
+/// and the proxy environment variables.
+pub fn proxy_hosts() -> Vec<String> {
+    let config = CONFIG.get_or_init(HttpConfig::default);
+    let env = [
+        "HTTPS_PROXY",
+        "https_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+    ]
+    .into_iter()
+    .filter_map(|name| std::env::var(name).ok());
+    config
+        .proxy
+        .clone()
+        .into_iter()
+        .chain(env)
+        .filter_map(|proxy| {
+            url::Url::parse(&proxy)
+                .ok()?
+                .host_str()
+                .map(|h| h.trim_matches(['[', ']']).to_ascii_lowercase())
+        })
+        .collect()
+}
+
+fn build(config: &HttpConfig, proxy: Option<&str>) -> reqwest::Client {
+    builder(config, proxy)
+        .build()
+        .expect("failed to create HTTP client")
+}
+
+fn builder(config: &HttpConfig, proxy: Option<&str>) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(config.user_agent())
+        .default_headers(config.header_map());
+    match proxy {
+        Some(NO_PROXY) => builder = builder.no_proxy(),
+        Some(url) => match reqwest::Proxy::all(url) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            // Checked at parse time. Going direct instead would quietly
+            // bypass a proxy the network may require, so say so.
+            Err(e) => tracing::warn!("ignoring invalid proxy `{url}`: {e}"),
+        },
+        None => {}
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_header_trims_and_validates() {
+        assert_eq!(
+            parse_header(" X-Team :  mail "),
+            Ok(("X-Team".into(), "mail".into()))
+        );
+        assert!(parse_header("no colon").is_err());
+        assert!(parse_header("Bad Name: x").is_err());
+        assert!(parse_header("X-Ok: line\nbreak").is_err());
+    }
+
+    #[test]
+    fn header_map_keeps_repeated_names() {
+        let config = HttpConfig {
+            user_agent: Some(String::new()),
+            headers: vec![("X-Tag".into(), "a".into()), ("x-tag".into(), "b".into())],
+            proxy: None,
+        };
+        assert_eq!(config.user_agent(), DEFAULT_USER_AGENT);
+        let map = config.header_map();
+        let tags: Vec<_> = map.get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["a", "b"]);
+    }
+
+    #[test]
+    fn parse_proxy_accepts_supported_schemes() {
+        assert_eq!(
+            parse_proxy(" socks5h://127.0.0.1:1080 "),
+            Ok("socks5h://127.0.0.1:1080".into())
+        );
+        assert!(parse_proxy("http://proxy.corp:3128").is_ok());
+        assert_eq!(parse_proxy("None"), Ok(NO_PROXY.into()));
+        assert!(parse_proxy("ftp://proxy:21").is_err());
+        assert!(parse_proxy("proxy.corp:3128").is_err());
+    }
+
+    /// A plain-HTTP request through an HTTP proxy goes to the proxy with
+    /// the absolute URL as its target.
+    #[tokio::test]
+    async fn requests_go_through_the_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+
+        let client = build(&HttpConfig::default(), Some(&format!("http://{addr}")));
+        client
+            .get("http://mail.example.invalid/session")
+            .send()
+            .await
+            .unwrap();
+        let request = proxy.await.unwrap();
+        assert!(request.starts_with("GET http://mail.example.invalid/session HTTP/1.1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn requests_carry_the_user_agent_and_headers() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_lowercase()
+        });
+
+        let client = build(
+            &HttpConfig {
+                user_agent: Some("supervillain (ops@example.com)".into()),
+                headers: vec![("X-Corp-Proxy-Auth".into(), "abc123".into())],
+                proxy: None,
+            },
+            Some(NO_PROXY),
+        );
+        client.get(format!("http://{addr}/")).send().await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.contains("user-agent: supervillain (ops@example.com)\r\n"));
+        assert!(request.contains("x-corp-proxy-auth: abc123\r\n"));
+    }
+}
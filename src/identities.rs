@@ -0,0 +1,469 @@
+//! Reply identity selection.
+//!
+//! A reply should go out from the address the original was sent to, not
+//! the account's first identity. [`select_reply_identity`] looks for that
+//! address in the message (To/Cc, then `Delivered-To`-style headers, then
+//! plus-address and wildcard aliases, then a shared domain) and otherwise
+//! falls back to the per-mailbox and per-account defaults configured in
+//! `identities.json`, which lives next to `splits.json`:
+//!
+//! ```json
+//! { "accounts": { "work": {
+//!     "default": "me@work.example",
+//!     "mailboxes": { "Lists": "lists@work.example" } } } }
+//! ```
+//!
+//! Mailbox keys match a mailbox id, role or name (case-insensitive).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Email, EmailHeader, Identity, Mailbox};
+
+/// Headers that name the envelope recipient, which survives Bcc and
+/// mailing-list delivery where To/Cc don't mention the user at all.
+const DELIVERED_TO_HEADERS: &[&str] = &[
+    "Delivered-To",
+    "X-Original-To",
+    "X-Delivered-To",
+    "Envelope-To",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdentityRules {
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AccountIdentityRules>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountIdentityRules {
+    /// Identity address used when nothing in the message matches.
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Mailbox id, role or name → identity address.
+    #[serde(default)]
+    pub mailboxes: BTreeMap<String, String>,
+    /// Identity address (or `*`) → local logo sent under the signature;
+    /// see `signature_image`.
+    #[serde(default, rename = "signatureImages")]
+    pub signature_images: BTreeMap<String, String>,
+}
+
+impl AccountIdentityRules {
+    /// Whether there are mailbox rules at all, i.e. whether resolving them
+    /// may need the account's mailbox list (for role and name keys).
+    pub fn needs_mailboxes(&self) -> bool {
+        !self.mailboxes.is_empty()
+    }
+}
+
+/// Why [`select_reply_identity`] picked the identity it did, so the UI can
+/// explain a surprising From.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchReason {
+    /// Exact match on a To or Cc address.
+    Recipient,
+    /// Exact match on a `Delivered-To`-style header.
+    DeliveredTo,
+    /// Plus-addressed recipient (`me+tag@…`) or a `*@domain` identity.
+    Alias,
+    /// No address matched, but an identity shares a recipient's domain.
+    Domain,
+    /// Per-mailbox default from `identities.json`.
+    Mailbox,
+    /// Per-account default from `identities.json`.
+    Default,
+    /// Nothing matched and nothing is configured: the first identity.
+    First,
+}
+
+// =============================================================================
+// Config load
+// =============================================================================
+
+pub fn load_rules(config_path: &Path) -> IdentityRules {
+    if config_path.exists() {
+        let content = match std::fs::read_to_string(config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read identities config: {e}");
+                return IdentityRules::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring invalid identities config: {e}");
+            IdentityRules::default()
+        });
+    }
+    IdentityRules::default()
+}
+
+// =============================================================================
+// Selection
+// =============================================================================
+
+/// Pick the From identity for a reply to `email`. `headers` may be empty
+/// (the header fetch is best-effort); `mailboxes` is only consulted for
+/// mailbox rules keyed by role or name. Returns `None` only when the
+/// account has no identities at all.
+pub fn select_reply_identity<'a>(
+    identities: &'a [Identity],
+    email: &Email,
+    headers: &[EmailHeader],
+    mailboxes: &[Mailbox],
+    rules: Option<&AccountIdentityRules>,
+) -> Option<(&'a Identity, MatchReason)> {
+    let recipients: Vec<String> = email
+        .to
+        .iter()
+        .chain(&email.cc)
+        .map(|a| a.email.trim().to_lowercase())
+        .filter(|a| a.contains('@'))
+        .collect();
+    let delivered_to: Vec<String> = headers
+        .iter()
+        .filter(|h| {
+            DELIVERED_TO_HEADERS
+                .iter()
+                .any(|n| h.name.eq_ignore_ascii_case(n))
+        })
+        .map(|h| bare_address(&h.value))
+        .filter(|a| a.contains('@'))
+        .collect();
+    let candidates = || recipients.iter().chain(&delivered_to);
+
+    let exact = |addrs: &[String]| {
+        addrs
+            .iter()
+            .find_map(|addr| find_identity(identities, addr))
+    };
+    if let Some(id) = exact(&recipients) {
+        return Some((id, MatchReason::Recipient));
+    }
+    if let Some(id) = exact(&delivered_to) {
+        return Some((id, MatchReason::DeliveredTo));
+    }
+
+    let alias = candidates().find_map(|addr| {
+        let plain = strip_plus_tag(addr);
+        find_identity(identities, &plain).or_else(|| {
+            let domain = domain_of(addr)?;
+            identities.iter().find(|i| {
+                i.email
+                    .strip_prefix("*@")
+                    .is_some_and(|d| d.eq_ignore_ascii_case(domain))
+            })
+        })
+    });
+    if let Some(id) = alias {
+        return Some((id, MatchReason::Alias));
+    }
+
+    let domain = candidates().find_map(|addr| {
+        let domain = domain_of(addr)?;
+        identities.iter().find(|i| {
+            !i.email.starts_with("*@")
+                && domain_of(&i.email).is_some_and(|d| d.eq_ignore_ascii_case(domain))
+        })
+    });
+    if let Some(id) = domain {
+        return Some((id, MatchReason::Domain));
+    }
+
+    if let Some(rules) = rules {
+        let mailbox_pick = email
+            .mailbox_ids
+            .iter()
+            .filter(|(_, in_mailbox)| **in_mailbox)
+            .find_map(|(mailbox_id, _)| {
+                let address = mailbox_rule(rules, mailbox_id, mailboxes)?;
+                find_identity(identities, address)
+            });
+        if let Some(id) = mailbox_pick {
+            return Some((id, MatchReason::Mailbox));
+        }
+        if let Some(id) = rules
+            .default
+            .as_deref()
+            .and_then(|addr| find_identity(identities, addr))
+        {
+            return Some((id, MatchReason::Default));
+        }
+    }
+
+    identities.first().map(|id| (id, MatchReason::First))
+}
+
+/// The configured address for a mailbox the message is in, matching the
+/// rule key against the mailbox id first, then its role or name.
+fn mailbox_rule<'r>(
+    rules: &'r AccountIdentityRules,
+    mailbox_id: &str,
+    mailboxes: &[Mailbox],
+) -> Option<&'r str> {
+    if let Some(addr) = rules.mailboxes.get(mailbox_id) {
+        return Some(addr);
+    }
+    let mailbox = mailboxes.iter().find(|m| m.id == mailbox_id)?;
+    rules
+        .mailboxes
+        .iter()
+        .find(|(key, _)| {
+            key.eq_ignore_ascii_case(&mailbox.name)
+                || mailbox
+                    .role
+                    .as_deref()
+                    .is_some_and(|r| key.eq_ignore_ascii_case(r))
+        })
+        .map(|(_, addr)| addr.as_str())
+}
+
+fn find_identity<'a>(identities: &'a [Identity], addr: &str) -> Option<&'a Identity> {
+    identities
+        .iter()
+        .find(|i| i.email.trim().eq_ignore_ascii_case(addr.trim()))
+}
+
+/// `Name <a@b>` / `<a@b>` / `a@b` → `a@b`, lowercased.
+fn bare_address(value: &str) -> String {
+    let value = value.trim();
+    let inner = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    inner.trim().to_lowercase()
+}
+
+/// `me+lists@example.com` → `me@example.com`; anything else unchanged.
+fn strip_plus_tag(addr: &str) -> String {
+    match addr.split_once('@') {
+        Some((local, domain)) => match local.split_once('+') {
+            Some((base, _)) => format!("{base}@{domain}"),
+            None => addr.to_string(),
+        },
+        None => addr.to_string(),
+    }
+}
+
+fn domain_of(addr: &str) -> Option<&str> {
+    addr.rsplit_once('@')
+        .map(|(_, d)| d)
+        .filter(|d| !d.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EmailAddress;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn identity(email: &str) -> Identity {
+        Identity {
+            id: email.into(),
+            email: email.into(),
+            name: String::new(),
+        }
+    }
+
+    fn addr(email: &str) -> EmailAddress {
+        EmailAddress {
+            name: None,
+            email: email.into(),
+        }
+    }
+
+    fn email_to(to: &[&str], cc: &[&str], mailboxes: &[&str]) -> Email {
+        Email {
+            id: "e1".into(),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: mailboxes.iter().map(|m| (m.to_string(), true)).collect(),
+            keywords: HashMap::new(),
+            received_at: Utc::now(),
+            subject: "Hi".into(),
+            from: vec![addr("sender@else.example")],
+            to: to.iter().map(|a| addr(a)).collect(),
+            cc: cc.iter().map(|a| addr(a)).collect(),
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: Vec::new(),
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    fn header(name: &str, value: &str) -> EmailHeader {
+        EmailHeader {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    fn ids() -> Vec<Identity> {
+        vec![
+            identity("me@home.example"),
+            identity("me@work.example"),
+            identity("*@shop.example"),
+        ]
+    }
+
+    fn pick(
+        email: &Email,
+        headers: &[EmailHeader],
+        mailboxes: &[Mailbox],
+        rules: Option<&AccountIdentityRules>,
+    ) -> (String, MatchReason) {
+        let ids = ids();
+        let (id, reason) = select_reply_identity(&ids, email, headers, mailboxes, rules).unwrap();
+        (id.email.clone(), reason)
+    }
+
+    #[test]
+    fn to_beats_cc_and_is_case_insensitive() {
+        let email = email_to(&["ME@Work.example"], &["me@home.example"], &[]);
+        assert_eq!(
+            pick(&email, &[], &[], None),
+            ("me@work.example".into(), MatchReason::Recipient)
+        );
+    }
+
+    #[test]
+    fn delivered_to_covers_bcc_and_lists() {
+        let email = email_to(&["list@lists.example"], &[], &[]);
+        let headers = [header("Delivered-To", "<me@work.example>")];
+        assert_eq!(
+            pick(&email, &headers, &[], None),
+            ("me@work.example".into(), MatchReason::DeliveredTo)
+        );
+    }
+
+    #[test]
+    fn recipient_wins_over_delivered_to() {
+        // Gmail stamps Delivered-To with the primary address even when the
+        // mail was sent to a send-as alias listed in To.
+        let email = email_to(&["me@work.example"], &[], &[]);
+        let headers = [header("Delivered-To", "me@home.example")];
+        assert_eq!(pick(&email, &headers, &[], None).0, "me@work.example");
+    }
+
+    #[test]
+    fn plus_address_and_wildcard_are_aliases() {
+        let plus = email_to(&["me+news@home.example"], &[], &[]);
+        assert_eq!(
+            pick(&plus, &[], &[], None),
+            ("me@home.example".into(), MatchReason::Alias)
+        );
+        let wildcard = email_to(&["orders@shop.example"], &[], &[]);
+        assert_eq!(
+            pick(&wildcard, &[], &[], None),
+            ("*@shop.example".into(), MatchReason::Alias)
+        );
+    }
+
+    #[test]
+    fn shared_domain_is_the_last_address_match() {
+        let email = email_to(&["team@work.example"], &[], &[]);
+        assert_eq!(
+            pick(&email, &[], &[], None),
+            ("me@work.example".into(), MatchReason::Domain)
+        );
+    }
+
+    #[test]
+    fn mailbox_rule_by_id_name_or_role() {
+        let rules = AccountIdentityRules {
+            default: None,
+            mailboxes: BTreeMap::from([
+                ("mb-lists".to_string(), "me@work.example".to_string()),
+                ("archive".to_string(), "me@work.example".to_string()),
+            ]),
+            signature_images: BTreeMap::new(),
+        };
+        let mailboxes = vec![Mailbox {
+            id: "mb-7".into(),
+            name: "Old".into(),
+            role: Some("archive".into()),
+            total_emails: 0,
+            unread_emails: 0,
+            parent_id: None,
+        }];
+        let by_id = email_to(&["someone@else.example"], &[], &["mb-lists"]);
+        assert_eq!(
+            pick(&by_id, &[], &mailboxes, Some(&rules)),
+            ("me@work.example".into(), MatchReason::Mailbox)
+        );
+        let by_role = email_to(&[], &[], &["mb-7"]);
+        assert_eq!(
+            pick(&by_role, &[], &mailboxes, Some(&rules)).1,
+            MatchReason::Mailbox
+        );
+    }
+
+    #[test]
+    fn configured_default_then_first_identity() {
+        let email = email_to(&["someone@else.example"], &[], &["inbox"]);
+        let rules = AccountIdentityRules {
+            default: Some("Me@Work.example".into()),
+            mailboxes: BTreeMap::new(),
+            signature_images: BTreeMap::new(),
+        };
+        assert_eq!(
+            pick(&email, &[], &[], Some(&rules)),
+            ("me@work.example".into(), MatchReason::Default)
+        );
+        assert_eq!(
+            pick(&email, &[], &[], None),
+            ("me@home.example".into(), MatchReason::First)
+        );
+    }
+
+    #[test]
+    fn rules_naming_unknown_identities_are_skipped() {
+        let email = email_to(&[], &[], &["inbox"]);
+        let rules = AccountIdentityRules {
+            default: Some("gone@old.example".into()),
+            mailboxes: BTreeMap::from([("inbox".to_string(), "gone@old.example".to_string())]),
+            signature_images: BTreeMap::new(),
+        };
+        assert_eq!(pick(&email, &[], &[], Some(&rules)).1, MatchReason::First);
+    }
+
+    #[test]
+    fn no_identities_is_none() {
+        let email = email_to(&["me@home.example"], &[], &[]);
+        assert!(select_reply_identity(&[], &email, &[], &[], None).is_none());
+    }
+
+    #[test]
+    fn load_rules_tolerates_missing_and_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identities.json");
+        assert!(load_rules(&path).accounts.is_empty());
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(load_rules(&path).accounts.is_empty());
+        std::fs::write(
+            &path,
+            r#"{"accounts":{"work":{"default":"me@work.example","mailboxes":{"Lists":"a@b"}}}}"#,
+        )
+        .unwrap();
+        let rules = load_rules(&path);
+        assert_eq!(
+            rules.accounts["work"].default.as_deref(),
+            Some("me@work.example")
+        );
+        assert!(rules.accounts["work"].needs_mailboxes());
+    }
+}
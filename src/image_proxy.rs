@@ -0,0 +1,337 @@
+//! Remote images fetched through the server instead of the browser.
+//!
+//! `sanitize` blocks remote images by default. "Load remote content"
+//! swaps in a rendering whose image URLs point at `/api/proxy-image`
+//! ([`proxy_path`]), so the image host sees this server's request — no
+//! reader IP, cookies or `Referer` — rather than the reader's browser.
+//! Senders listed in `image-senders.json` (next to `splits.json`) get that
+//! rendering without asking; entries are addresses or `*@domain`, as in
+//! `protected`.
+//!
+//! The proxy only fetches `http(s)` URLs on public hosts, vets every
+//! redirect hop the same way, caps the body at [`MAX_IMAGE_BYTES`] and
+//! only serves bytes that sniff as a raster image, so it can't be pointed
+//! at the local network or used to serve active content from our origin.
+//! Names are looked up by [`PublicResolver`], which hands the connection
+//! only public addresses, so a hostname that resolves (or rebinds) to a
+//! private one is refused too. Through a proxy, the proxy does the lookup
+//! for the image host and only the literal-host check applies.
+
+use crate::error::Error;
+use crate::protected;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Largest image the proxy will pass through.
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Redirect hops followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// Senders whose mail loads remote images (through the proxy) by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ImageSendersConfig {
+    #[serde(default)]
+    pub senders: Vec<String>,
+}
+
+pub fn load_image_senders(config_path: &Path) -> ImageSendersConfig {
+    if config_path.exists() {
+        let content = match std::fs::read_to_string(config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read image senders config: {e}");
+                return ImageSendersConfig::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    ImageSendersConfig::default()
+}
+
+pub fn save_image_senders(config: &ImageSendersConfig, config_path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(config)?;
+    crate::accounts::atomic_write_bytes(config_path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+/// Normalize an entry for storage; same rules as protected senders.
+pub fn normalize_sender(raw: &str) -> Result<String, Error> {
+    protected::normalize_entry(raw, "Image sender")
+}
+
+/// True when mail from `address` should load remote images by default.
+pub fn allows(config: &ImageSendersConfig, address: &str) -> bool {
+    protected::matches_any(&config.senders, address)
+}
+
+/// The same-origin URL that fetches `url` through the proxy.
+pub fn proxy_path(url: &str) -> String {
+    // Protocol-relative URLs (`//cdn.example/a.png`) are fetched over https.
+    let url = match url.trim().strip_prefix("//") {
+        Some(rest) => format!("https://{rest}"),
+        None => url.trim().to_string(),
+    };
+    format!(
+        "/api/proxy-image?url={}",
+        utf8_percent_encode(&url, NON_ALPHANUMERIC)
+    )
+}
+
+/// Parse `raw` and refuse anything but `http(s)` to a public host.
+pub fn check_url(raw: &str) -> Result<url::Url, Error> {
+    let url = url::Url::parse(raw.trim())
+        .map_err(|e| Error::BadRequest(format!("Invalid image URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::BadRequest(format!(
+            "Image URL scheme '{}' is not http or https",
+            url.scheme()
+        )));
+    }
+    if !has_public_host(&url) {
+        return Err(Error::BadRequest(
+            "Image URL must point at a public host".into(),
+        ));
+    }
+    Ok(url)
+}
+
+/// `url` names a public host: no loopback, private, link-local or
+/// `.local` name. Also vets unsubscribe URLs (`unsubscribe`).
+pub(crate) fn has_public_host(url: &url::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(host)) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host != "localhost" && !host.ends_with(".localhost") && !host.ends_with(".local")
+        }
+        Some(url::Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    // fc00::/7 unique local, fe80::/10 link-local.
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT.
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+/// DNS for fetches of URLs taken from mail. Only the public addresses a
+/// name resolves to reach reqwest, so the connection goes to an address
+/// that was vetted rather than to a second lookup's answer. A name with no
+/// public address fails. Proxy hosts resolve as usual.
+#[derive(Debug, Default)]
+pub(crate) struct PublicResolver {
+    proxy_hosts: Vec<String>,
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        let is_proxy = self.proxy_hosts.contains(&host);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addrs = if is_proxy { addrs } else { public_addrs(addrs) };
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+fn public_addrs(addrs: impl IntoIterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    addrs.into_iter().filter(|a| is_public_ip(a.ip())).collect()
+}
+
+/// The client for URLs taken from mail (images here, one-click
+/// unsubscribes in `unsubscribe`): redirects come back unfollowed for the
+/// caller to vet, and names resolve through [`PublicResolver`].
+pub(crate) fn public_client() -> reqwest::Client {
+    crate::http_client::client_without_redirects(Arc::new(PublicResolver {
+        proxy_hosts: crate::http_client::proxy_hosts(),
+    }))
+}
+
+/// Fetch `url` and return its sniffed image type and bytes. Redirects are
+/// followed by hand so each hop goes through [`check_url`].
+pub async fn fetch(url: url::Url) -> Result<(&'static str, Vec<u8>), Error> {
+    let client = public_client();
+    let mut url = url;
+    for _ in 0..=MAX_REDIRECTS {
+        let resp = client
+            .get(url.clone())
+            .header(reqwest::header::ACCEPT, "image/*")
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("Image fetch failed: {e}")))?;
+        if resp.status().is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| Error::Network("Image redirect without a Location".into()))?;
+            let next = url
+                .join(location)
+                .map_err(|e| Error::Network(format!("Bad image redirect: {e}")))?;
+            url = check_url(next.as_str())?;
+            continue;
+        }
+        if !resp.status().is_success() {
+            return Err(Error::NotFound(format!(
+                "Image host answered {}",
+                resp.status()
+            )));
+        }
+        if resp
+            .content_length()
+            .is_some_and(|len| len > MAX_IMAGE_BYTES as u64)
+        {
+            return Err(Error::Unprocessable("Image is too large".into()));
+        }
+        let bytes = read_capped(resp).await?;
+        return match image_type(&bytes) {
+            Some(mime) => Ok((mime, bytes)),
+            None => Err(Error::Unprocessable(
+                "Remote resource is not a supported image".into(),
+            )),
+        };
+    }
+    Err(Error::Network("Too many image redirects".into()))
+}
+
+async fn read_capped(mut resp: reqwest::Response) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| Error::Network(format!("Image fetch failed: {e}")))?
+    {
+        if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+            return Err(Error::Unprocessable("Image is too large".into()));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// The raster image type `bytes` sniff as. SVG is never served: opened
+/// directly, it would run script on our origin.
+fn image_type(bytes: &[u8]) -> Option<&'static str> {
+    crate::provider_utils::sniff_mime_type(bytes).filter(|mime| mime.starts_with("image/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_url_refuses_local_and_non_http_targets() {
+        assert!(check_url("https://cdn.example.com/logo.png").is_ok());
+        assert!(check_url("http://93.184.216.34/a.gif").is_ok());
+        for bad in [
+            "file:///etc/passwd",
+            "ftp://cdn.example.com/a.png",
+            "http://localhost:8000/api/accounts",
+            "http://127.0.0.1/a.png",
+            "http://10.0.0.5/a.png",
+            "http://192.168.1.1/a.png",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/a.png",
+            "http://[::ffff:127.0.0.1]/a.png",
+            "http://[fd00::1]/a.png",
+            "http://printer.local/a.png",
+            "not a url",
+        ] {
+            assert!(check_url(bad).is_err(), "{bad} should be refused");
+        }
+    }
+
+    #[test]
+    fn resolved_private_addresses_are_dropped() {
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        assert_eq!(
+            public_addrs([
+                addr("127.0.0.1:0"),
+                addr("93.184.216.34:0"),
+                addr("[fe80::1]:0"),
+                addr("10.1.2.3:0"),
+            ]),
+            [addr("93.184.216.34:0")]
+        );
+    }
+
+    #[tokio::test]
+    async fn names_resolving_to_loopback_are_refused() {
+        use reqwest::dns::Resolve;
+        let name = "localhost".parse().unwrap();
+        assert!(PublicResolver::default().resolve(name).await.is_err());
+        let proxy = PublicResolver {
+            proxy_hosts: vec!["localhost".into()],
+        };
+        assert!(proxy.resolve("localhost".parse().unwrap()).await.is_ok());
+    }
+
+    #[test]
+    fn proxy_path_encodes_the_whole_url() {
+        assert_eq!(
+            proxy_path("https://t.example/p.gif?u=1&x=2"),
+            "/api/proxy-image?url=https%3A%2F%2Ft%2Eexample%2Fp%2Egif%3Fu%3D1%26x%3D2"
+        );
+        assert!(proxy_path("//cdn.example/a.png").contains("https%3A%2F%2Fcdn"));
+    }
+
+    #[test]
+    fn only_raster_images_are_served() {
+        assert_eq!(image_type(b"\x89PNG\r\n\x1a\nrest"), Some("image/png"));
+        assert_eq!(image_type(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(image_type(b"%PDF-1.7"), None);
+        assert_eq!(
+            image_type(b"<svg xmlns='http://www.w3.org/2000/svg'/>"),
+            None
+        );
+    }
+
+    #[test]
+    fn allowlist_matches_addresses_and_domains() {
+        let config = ImageSendersConfig {
+            senders: vec!["news@shop.example".into(), "*@friends.example".into()],
+        };
+        assert!(allows(&config, "News@Shop.example"));
+        assert!(allows(&config, "ann@friends.example"));
+        assert!(!allows(&config, "deals@shop.example"));
+        assert!(
+            matches!(normalize_sender("nobody"), Err(Error::BadRequest(m)) if m.starts_with("Image sender"))
+        );
+    }
+}
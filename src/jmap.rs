@@ -1,6 +1,7 @@
 use crate::calendar;
 use crate::error::Error;
 use crate::rate_limit::RateLimiter;
+use crate::sieve::SieveScript;
 use crate::types::ParsedQuery;
 use crate::types::*;
 use serde::{Deserialize, Deserializer};
@@ -31,6 +32,93 @@ pub(crate) struct JmapSessionResponse {
     pub download_url: Option<String>,
     #[serde(default)]
     pub primary_accounts: HashMap<String, String>,
+    #[serde(default)]
+    pub accounts: HashMap<String, JmapAccountInfo>,
+    #[serde(default)]
+    pub capabilities: serde_json::Value,
+}
+
+/// One entry of the session's `accounts` map (RFC 8620 §2).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct JmapAccountInfo {
+    pub name: String,
+    pub is_read_only: bool,
+    pub account_capabilities: HashMap<String, serde_json::Value>,
+}
+
+/// A mail account the login reaches besides its own: a shared mailbox or
+/// one delegated to the user. Routes address it as
+/// `<account>#<id>`; see `accounts::split_delegated`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedAccount {
+    pub id: String,
+    /// What the server calls it, usually the owner's address.
+    pub name: String,
+    pub read_only: bool,
+}
+
+impl JmapSessionResponse {
+    /// Mail-capable accounts other than the primary one, by name.
+    pub(crate) fn shared_accounts(&self) -> Vec<SharedAccount> {
+        let primary = self.primary_accounts.get(CAPABILITY_MAIL);
+        let mut shared: Vec<SharedAccount> = self
+            .accounts
+            .iter()
+            .filter(|(id, info)| {
+                Some(*id) != primary && info.account_capabilities.contains_key(CAPABILITY_MAIL)
+            })
+            .map(|(id, info)| SharedAccount {
+                id: id.clone(),
+                name: info.name.clone(),
+                read_only: info.is_read_only,
+            })
+            .collect();
+        shared.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        shared
+    }
+}
+
+/// Request-size limits from the session's `urn:ietf:params:jmap:core`
+/// capability. Servers reject oversized requests with `requestTooLarge`,
+/// so `jmap_call` splits single-method requests to fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JmapLimits {
+    pub max_objects_in_get: usize,
+    pub max_objects_in_set: usize,
+    pub max_calls_in_request: usize,
+}
+
+impl Default for JmapLimits {
+    /// RFC 8620's recommended minimums — safe before `connect` has run or
+    /// when a server omits a field.
+    fn default() -> Self {
+        Self {
+            max_objects_in_get: 500,
+            max_objects_in_set: 500,
+            max_calls_in_request: 16,
+        }
+    }
+}
+
+impl JmapLimits {
+    pub(crate) fn from_capabilities(capabilities: &serde_json::Value) -> Self {
+        let core = &capabilities["urn:ietf:params:jmap:core"];
+        let defaults = Self::default();
+        let read = |key: &str, fallback: usize| {
+            core[key]
+                .as_u64()
+                .filter(|&n| n > 0)
+                .map(|n| n as usize)
+                .unwrap_or(fallback)
+        };
+        Self {
+            max_objects_in_get: read("maxObjectsInGet", defaults.max_objects_in_get),
+            max_objects_in_set: read("maxObjectsInSet", defaults.max_objects_in_set),
+            max_calls_in_request: read("maxCallsInRequest", defaults.max_calls_in_request),
+        }
+    }
 }
 
 /// Recursive MIME body structure part
@@ -61,15 +149,30 @@ pub struct BodyStructurePart {
 struct BodyPartRef {
     #[serde(default)]
     pub part_id: String,
+    #[serde(default)]
+    pub blob_id: Option<String>,
+    #[serde(rename = "type", default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub charset: Option<String>,
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub size: i64,
 }
 
 /// Body value entry from the bodyValues map
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct BodyValue {
     #[serde(default)]
     pub value: String,
+    #[serde(default)]
+    pub is_truncated: bool,
 }
 
+/// Per-part cap on `bodyValues` in Email/get. Larger parts come back with
+/// `isTruncated` set; `/api/emails/{id}/body/full` streams the whole blob.
+const MAX_BODY_VALUE_BYTES: usize = 1_000_000;
+
 /// Raw JMAP Email/get response item. Converted to Email after body processing.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -107,6 +210,14 @@ struct JmapEmailRaw {
     /// restored draft keeps its threading (kata wm57 review follow-up).
     #[serde(default)]
     pub in_reply_to: Option<Vec<String>>,
+    /// `messageId`, `references` and `replyTo`: body fetches only, for
+    /// threading replies built by `compose`. Ids come without brackets.
+    #[serde(default)]
+    pub message_id: Option<Vec<String>>,
+    #[serde(default)]
+    pub references: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable_default")]
+    pub reply_to: Vec<EmailAddress>,
     #[serde(default)]
     pub text_body: Vec<BodyPartRef>,
     #[serde(default)]
@@ -114,6 +225,16 @@ struct JmapEmailRaw {
     #[serde(default)]
     pub body_values: HashMap<String, BodyValue>,
     pub body_structure: Option<BodyStructurePart>,
+    #[serde(rename = "header:List-Unsubscribe:asText", default)]
+    pub list_unsubscribe: Option<String>,
+    #[serde(rename = "header:X-Spam-Score:asText", default)]
+    pub spam_score: Option<String>,
+    #[serde(rename = "header:X-Spam-Status:asText", default)]
+    pub spam_status: Option<String>,
+    #[serde(rename = "header:X-Rspamd-Score:asText", default)]
+    pub rspamd_score: Option<String>,
+    #[serde(rename = "header:X-Spam-Known-Sender:asText", default)]
+    pub spam_known_sender: Option<String>,
 }
 
 // =============================================================================
@@ -137,15 +258,24 @@ pub struct JmapSession {
     /// conservative starting point. JMAP method-batching (used in
     /// `send_email`, `archive_batch`) further reduces request count.
     pub limiter: std::sync::Arc<RateLimiter>,
+    pub limits: JmapLimits,
+    /// Capability URIs the session resource advertises. Empty until
+    /// `connect`, in which case every capability is assumed.
+    pub capabilities: Vec<String>,
+    /// Names that stand in for a missing mailbox role; see
+    /// [`cache_mailboxes`].
+    pub role_aliases: crate::mailbox_roles::RoleAliases,
+    /// Other mail accounts this login can open, from `connect`.
+    pub shared_accounts: Vec<SharedAccount>,
+    /// Sessions opened on [`Self::shared_accounts`] so far, by account id.
+    /// They live and die with this one, so a reconnect starts them over.
+    pub delegated: std::sync::Mutex<HashMap<String, SessionLock>>,
 }
 
 impl JmapSession {
     pub fn new(username: &str, auth_header: &str) -> Self {
         Self {
-            client: reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .expect("failed to create HTTP client"),
+            client: crate::http_client::client(),
             username: username.into(),
             auth_header: auth_header.into(),
             api_url: None,
@@ -161,8 +291,86 @@ impl JmapSession {
                 std::time::Duration::from_millis(100),
                 3,
             )),
+            limits: JmapLimits::default(),
+            capabilities: Vec::new(),
+            role_aliases: crate::mailbox_roles::default_aliases(),
+            shared_accounts: Vec::new(),
+            delegated: Default::default(),
+        }
+    }
+
+    /// A session on the shared account `account_id`: same login, server
+    /// and rate limiter, its own mailboxes and identities.
+    pub fn for_shared_account(&self, account_id: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            username: self.username.clone(),
+            auth_header: self.auth_header.clone(),
+            api_url: self.api_url.clone(),
+            account_id: Some(account_id.to_string()),
+            upload_url: self.upload_url.clone(),
+            download_url: self.download_url.clone(),
+            mailbox_cache: HashMap::new(),
+            identity_id: None,
+            identities: None,
+            limiter: self.limiter.clone(),
+            limits: self.limits,
+            capabilities: self.capabilities.clone(),
+            role_aliases: self.role_aliases.clone(),
+            shared_accounts: Vec::new(),
+            delegated: Default::default(),
         }
     }
+
+    pub fn shared_account(&self, account_id: &str) -> Option<&SharedAccount> {
+        self.shared_accounts.iter().find(|a| a.id == account_id)
+    }
+
+    /// Whether the server advertises `capability` (always true before
+    /// `connect`).
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.is_empty() || self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+pub const CAPABILITY_CORE: &str = "urn:ietf:params:jmap:core";
+pub const CAPABILITY_MAIL: &str = "urn:ietf:params:jmap:mail";
+pub const CAPABILITY_SUBMISSION: &str = "urn:ietf:params:jmap:submission";
+pub const CAPABILITY_SIEVE: &str = "urn:ietf:params:jmap:sieve";
+
+/// The capability a method belongs to, by its `Type/` prefix.
+fn capability_for(method: &str) -> &'static str {
+    let kind = method.split('/').next().unwrap_or_default();
+    match kind {
+        "Identity" | "EmailSubmission" => CAPABILITY_SUBMISSION,
+        "Email" | "Mailbox" | "Thread" | "SearchSnippet" => CAPABILITY_MAIL,
+        "SieveScript" => CAPABILITY_SIEVE,
+        _ => CAPABILITY_CORE,
+    }
+}
+
+/// The `using` array for `method_calls`: core plus whatever the calls
+/// need, and nothing else, so a server that lacks submission still
+/// accepts mail-only requests. A call needing a capability the session
+/// doesn't advertise fails here with `NotImplemented` instead of as an
+/// opaque `unknownCapability` from the server.
+fn using_for(
+    s: &JmapSession,
+    method_calls: &[serde_json::Value],
+) -> Result<Vec<&'static str>, Error> {
+    let mut using = vec![CAPABILITY_CORE];
+    for call in method_calls {
+        let capability = capability_for(call[0].as_str().unwrap_or_default());
+        if !s.supports(capability) {
+            return Err(Error::NotImplemented(format!(
+                "this server does not offer {capability}"
+            )));
+        }
+        if !using.contains(&capability) {
+            using.push(capability);
+        }
+    }
+    Ok(using)
 }
 
 // =============================================================================
@@ -186,14 +394,26 @@ pub async fn connect(s: &mut JmapSession) -> Result<(), Error> {
 
     let session: JmapSessionResponse = resp.json().await?;
 
+    s.shared_accounts = session.shared_accounts();
+    s.delegated = Default::default();
     s.api_url = session.api_url;
     s.upload_url = session.upload_url;
     s.download_url = session.download_url;
-
-    s.account_id = session
-        .primary_accounts
-        .get("urn:ietf:params:jmap:mail")
-        .cloned();
+    s.limits = JmapLimits::from_capabilities(&session.capabilities);
+    s.capabilities = session
+        .capabilities
+        .as_object()
+        .map(|caps| caps.keys().cloned().collect())
+        .unwrap_or_default();
+
+    s.account_id = session.primary_accounts.get(CAPABILITY_MAIL).cloned();
+    if !s.shared_accounts.is_empty() {
+        tracing::info!(
+            "{} can open {} shared account(s)",
+            s.username,
+            s.shared_accounts.len()
+        );
+    }
 
     debug_assert!(s.api_url.is_some(), "JMAP session must have apiUrl");
     debug_assert!(s.account_id.is_some(), "JMAP session must have accountId");
@@ -202,18 +422,172 @@ pub async fn connect(s: &mut JmapSession) -> Result<(), Error> {
     Ok(())
 }
 
+/// Send `method_calls` in one request — or, when a lone /get, /set or
+/// /query call exceeds the session limits, as several chunked calls whose
+/// responses are merged back into a single `methodResponses[0]`. Callers
+/// see the same shape either way. Multi-call batches (which may use
+/// back-references between calls) are sent untouched.
 async fn jmap_call(
     s: &JmapSession,
     method_calls: Vec<serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    if method_calls.len() != 1 {
+        return jmap_call_once(s, method_calls).await;
+    }
+    let chunks = split_method_call(&method_calls[0], &s.limits);
+    if chunks.len() <= 1 {
+        return jmap_call_once(s, method_calls).await;
+    }
+    tracing::debug!(
+        "Splitting {} into {} calls to respect server limits",
+        method_calls[0][0].as_str().unwrap_or("JMAP call"),
+        chunks.len()
+    );
+    let mut responses = Vec::with_capacity(chunks.len());
+    for group in chunks.chunks(s.limits.max_calls_in_request.max(1)) {
+        let calls: Vec<serde_json::Value> = group
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut c = c.clone();
+                c[2] = serde_json::json!(i.to_string());
+                c
+            })
+            .collect();
+        let resp = jmap_call_once(s, calls).await?;
+        if let Some(list) = resp["methodResponses"].as_array() {
+            responses.extend(list.iter().cloned());
+        }
+    }
+    Ok(serde_json::json!({ "methodResponses": [merge_method_responses(responses)] }))
+}
+
+/// Split one method call into calls that each fit `limits`. Returns a single
+/// element (the original call) when no split is needed or the method isn't
+/// one we know how to split.
+fn split_method_call(call: &serde_json::Value, limits: &JmapLimits) -> Vec<serde_json::Value> {
+    let name = call[0].as_str().unwrap_or("");
+    let args = &call[1];
+    if name.ends_with("/get") {
+        if let Some(ids) = args["ids"].as_array()
+            && ids.len() > limits.max_objects_in_get
+        {
+            return ids
+                .chunks(limits.max_objects_in_get.max(1))
+                .map(|chunk| {
+                    let mut c = call.clone();
+                    c[1]["ids"] = serde_json::json!(chunk);
+                    c
+                })
+                .collect();
+        }
+    } else if name.ends_with("/set") {
+        // Flatten create/update/destroy into one op list so the per-call
+        // object count covers all three, as the spec counts them.
+        let mut ops: Vec<(&str, Option<&String>, &serde_json::Value)> = Vec::new();
+        for kind in ["create", "update"] {
+            if let Some(map) = args[kind].as_object() {
+                ops.extend(map.iter().map(|(k, v)| (kind, Some(k), v)));
+            }
+        }
+        if let Some(ids) = args["destroy"].as_array() {
+            ops.extend(ids.iter().map(|id| ("destroy", None, id)));
+        }
+        if ops.len() > limits.max_objects_in_set {
+            return ops
+                .chunks(limits.max_objects_in_set.max(1))
+                .map(|chunk| {
+                    let mut c = call.clone();
+                    for kind in ["create", "update", "destroy"] {
+                        if let Some(obj) = c[1].as_object_mut() {
+                            obj.remove(kind);
+                        }
+                    }
+                    for (kind, key, value) in chunk {
+                        match key {
+                            Some(key) => {
+                                if c[1][*kind].is_null() {
+                                    c[1][*kind] = serde_json::json!({});
+                                }
+                                c[1][*kind][key.as_str()] = (*value).clone();
+                            }
+                            None => {
+                                if c[1]["destroy"].is_null() {
+                                    c[1]["destroy"] = serde_json::json!([]);
+                                }
+                                if let Some(arr) = c[1]["destroy"].as_array_mut() {
+                                    arr.push((*value).clone());
+                                }
+                            }
+                        }
+                    }
+                    c
+                })
+                .collect();
+        }
+    } else if name.ends_with("/query")
+        && let Some(limit) = args["limit"].as_u64().map(|n| n as usize)
+        && limit > limits.max_objects_in_get
+        && args.get("anchor").is_none()
+    {
+        let start = args["position"].as_u64().unwrap_or(0) as usize;
+        let step = limits.max_objects_in_get.max(1);
+        return (0..limit)
+            .step_by(step)
+            .map(|offset| {
+                let mut c = call.clone();
+                c[1]["position"] = serde_json::json!(start + offset);
+                c[1]["limit"] = serde_json::json!(step.min(limit - offset));
+                c
+            })
+            .collect();
+    }
+    vec![call.clone()]
+}
+
+/// Merge the responses of a split call back into one method response.
+/// Any `error` response wins (the first one is returned as-is); otherwise
+/// list-shaped fields are concatenated and map-shaped ones unioned.
+fn merge_method_responses(responses: Vec<serde_json::Value>) -> serde_json::Value {
+    if let Some(err) = responses.iter().find(|r| r[0] == "error") {
+        return err.clone();
+    }
+    let mut iter = responses.into_iter();
+    let Some(mut merged) = iter.next() else {
+        return serde_json::Value::Null;
+    };
+    merged[2] = serde_json::json!("0");
+    for resp in iter {
+        let Some(args) = resp[1].as_object() else {
+            continue;
+        };
+        for (key, value) in args {
+            match (&mut merged[1][key.as_str()], value) {
+                (serde_json::Value::Array(into), serde_json::Value::Array(from)) => {
+                    into.extend(from.iter().cloned());
+                }
+                (serde_json::Value::Object(into), serde_json::Value::Object(from)) => {
+                    into.extend(from.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+                (slot @ serde_json::Value::Null, v) => *slot = v.clone(),
+                // Scalars (state, position, total…): the first chunk's value
+                // stands, except `newState`, which should reflect the last write.
+                (slot, v) if key == "newState" => *slot = v.clone(),
+                _ => {}
+            }
+        }
+    }
+    merged
+}
+
+async fn jmap_call_once(
+    s: &JmapSession,
+    method_calls: Vec<serde_json::Value>,
 ) -> Result<serde_json::Value, Error> {
     let api_url = s.api_url.as_ref().ok_or(Error::NotConnected)?;
 
     let payload = serde_json::json!({
-        "using": [
-            "urn:ietf:params:jmap:core",
-            "urn:ietf:params:jmap:mail",
-            "urn:ietf:params:jmap:submission"
-        ],
+        "using": using_for(s, &method_calls)?,
         "methodCalls": method_calls
     });
 
@@ -289,6 +663,18 @@ pub async fn get_mailboxes(s: &JmapSession) -> Result<Vec<Mailbox>, Error> {
     extract_list::<Mailbox>(&resp, 0, "Mailbox/get")
 }
 
+/// Refill `mailbox_cache` (keyed by role) from `mailboxes`. Roles the
+/// server didn't assign are inferred from mailbox names via
+/// `role_aliases`.
+pub fn cache_mailboxes(s: &mut JmapSession, mailboxes: &[Mailbox]) {
+    s.mailbox_cache = crate::mailbox_roles::by_role(mailboxes, &s.role_aliases);
+}
+
+/// The cached mailbox serving `role`, server-assigned or inferred.
+pub fn role_mailbox<'a>(s: &'a JmapSession, role: &str) -> Option<&'a Mailbox> {
+    s.mailbox_cache.get(role)
+}
+
 pub async fn get_identities(s: &mut JmapSession) -> Result<Vec<Identity>, Error> {
     if let Some(ref ids) = s.identities {
         return Ok(ids.clone());
@@ -340,47 +726,86 @@ fn to_jmap_filter(query: Option<&ParsedQuery>, mailbox_id: Option<&str>) -> serd
     if let Some(mb) = mailbox_id {
         conditions.push(serde_json::json!({"inMailbox": mb}));
     }
-
     if let Some(q) = query {
-        for from in &q.from {
-            conditions.push(serde_json::json!({"from": from}));
-        }
-        for to in &q.to {
-            conditions.push(serde_json::json!({"to": to}));
-        }
-        for subject in &q.subject {
-            conditions.push(serde_json::json!({"subject": subject}));
-        }
-        if q.has_attachment {
-            conditions.push(serde_json::json!({"hasAttachment": true}));
-        }
-        if let Some(true) = q.is_unread {
-            conditions.push(serde_json::json!({"notKeyword": "$seen"}));
-        }
-        if let Some(false) = q.is_unread {
-            conditions.push(serde_json::json!({"hasKeyword": "$seen"}));
-        }
-        if let Some(true) = q.is_flagged {
-            conditions.push(serde_json::json!({"hasKeyword": "$flagged"}));
-        }
-        if let Some(after) = q.after {
-            conditions.push(serde_json::json!({"after": format!("{}T00:00:00Z", after)}));
-        }
-        if let Some(before) = q.before {
-            conditions.push(serde_json::json!({"before": format!("{}T00:00:00Z", before)}));
-        }
-        if !q.text.is_empty() {
-            conditions.push(serde_json::json!({"text": q.text}));
-        }
+        push_query_conditions(q, &mut conditions);
     }
 
+    and_filter(conditions).unwrap_or_else(|| serde_json::json!({}))
+}
+
+/// `conditions` as one filter: `None` for none (matches everything), the
+/// condition itself for one, an `AND` operator for more.
+fn and_filter(mut conditions: Vec<serde_json::Value>) -> Option<serde_json::Value> {
     match conditions.len() {
-        0 => serde_json::json!({}),
-        1 => conditions.into_iter().next().unwrap(),
-        _ => serde_json::json!({
+        0 => None,
+        1 => conditions.pop(),
+        _ => Some(serde_json::json!({
             "operator": "AND",
             "conditions": conditions
-        }),
+        })),
+    }
+}
+
+/// Every condition `q` puts on a message, OR groups and exclusions as
+/// nested `OR`/`NOT` operators.
+fn push_query_conditions(q: &ParsedQuery, conditions: &mut Vec<serde_json::Value>) {
+    for from in &q.from {
+        conditions.push(serde_json::json!({"from": from}));
+    }
+    for to in &q.to {
+        conditions.push(serde_json::json!({"to": to}));
+    }
+    for subject in &q.subject {
+        conditions.push(serde_json::json!({"subject": subject}));
+    }
+    if q.has_attachment {
+        conditions.push(serde_json::json!({"hasAttachment": true}));
+    }
+    if let Some(true) = q.is_unread {
+        conditions.push(serde_json::json!({"notKeyword": "$seen"}));
+    }
+    if let Some(false) = q.is_unread {
+        conditions.push(serde_json::json!({"hasKeyword": "$seen"}));
+    }
+    if let Some(true) = q.is_flagged {
+        conditions.push(serde_json::json!({"hasKeyword": "$flagged"}));
+    }
+    if let Some(after) = q.after {
+        conditions.push(serde_json::json!({"after": format!("{}T00:00:00Z", after)}));
+    }
+    if let Some(before) = q.before {
+        conditions.push(serde_json::json!({"before": format!("{}T00:00:00Z", before)}));
+    }
+    if !q.text.is_empty() {
+        conditions.push(serde_json::json!({"text": q.text}));
+    }
+    for id in q.mailboxes.iter().filter_map(|m| m.id.as_ref()) {
+        conditions.push(serde_json::json!({"inMailbox": id}));
+    }
+    // `minSize` is inclusive and `maxSize` exclusive (RFC 8621 §4.4.1).
+    if let Some(larger) = q.larger {
+        conditions.push(serde_json::json!({"minSize": larger.saturating_add(1)}));
+    }
+    if let Some(smaller) = q.smaller {
+        conditions.push(serde_json::json!({"maxSize": smaller}));
+    }
+    let sub_filter = |sub: &ParsedQuery| {
+        let mut sub_conditions = Vec::new();
+        push_query_conditions(sub, &mut sub_conditions);
+        and_filter(sub_conditions)
+    };
+    for excluded in &q.exclude {
+        if let Some(filter) = sub_filter(excluded) {
+            conditions.push(serde_json::json!({"operator": "NOT", "conditions": [filter]}));
+        }
+    }
+    for group in &q.any_of {
+        // An alternative with no conditions matches everything, and so
+        // does the group.
+        let Some(alternatives) = group.iter().map(sub_filter).collect::<Option<Vec<_>>>() else {
+            continue;
+        };
+        conditions.push(serde_json::json!({"operator": "OR", "conditions": alternatives}));
     }
 }
 
@@ -429,6 +854,200 @@ pub async fn query_emails(
     Ok(ids)
 }
 
+/// `query_emails` plus what a pager needs: the result total
+/// (`calculateTotal`), the position the server actually used, and whether
+/// the query supports `Email/queryChanges`. An anchored `start` resolves
+/// to a position on the server; an anchor that's no longer in the results
+/// is a 404.
+pub async fn search_emails(
+    s: &JmapSession,
+    mailbox_id: Option<&str>,
+    limit: usize,
+    start: &WindowStart,
+    query: Option<&ParsedQuery>,
+    sort: EmailSort,
+) -> Result<SearchPage, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let mut args = serde_json::json!({
+        "accountId": account_id,
+        "filter": to_jmap_filter(query, mailbox_id),
+        "sort": jmap_sort_clause(sort),
+        "limit": limit,
+        "calculateTotal": true
+    });
+    match start {
+        WindowStart::Position(position) => args["position"] = serde_json::json!(position),
+        WindowStart::Anchor { id, offset } => {
+            args["anchor"] = serde_json::json!(id);
+            args["anchorOffset"] = serde_json::json!(offset);
+        }
+    }
+    let resp = jmap_call(s, vec![serde_json::json!(["Email/query", args, "0"])]).await?;
+    parse_search_page(&resp["methodResponses"][0])
+}
+
+/// `Email/query` response → [`SearchPage`]. The server may clamp
+/// `position` (past the end, say), so its value wins over the request's.
+fn parse_search_page(response: &serde_json::Value) -> Result<SearchPage, Error> {
+    if response[0] == "error" {
+        let kind = response[1]["type"].as_str().unwrap_or("unknown");
+        return Err(match kind {
+            "anchorNotFound" => Error::NotFound("anchor email is not in the results".into()),
+            _ => Error::Internal(format!("Email/query failed: {kind}")),
+        });
+    }
+    let query = &response[1];
+    let ids = query
+        .get("ids")
+        .ok_or_else(|| Error::Internal("Invalid Email/query response: missing ids".into()))?;
+    let ids: Vec<String> = serde_json::from_value(ids.clone())
+        .map_err(|e| Error::Internal(format!("Failed to parse Email/query ids: {e}")))?;
+    Ok(SearchPage {
+        ids,
+        position: query["position"].as_u64().unwrap_or(0) as usize,
+        total: query["total"].as_u64().map(|t| t as usize),
+        can_calculate_changes: query["canCalculateChanges"].as_bool().unwrap_or(false),
+        query_state: query["queryState"].as_str().map(String::from),
+    })
+}
+
+/// Find the email whose Message-ID header is `message_id` (normalized,
+/// `<…>` form). First match wins — duplicates across mailboxes are the same
+/// message as far as a permalink is concerned.
+pub async fn find_by_message_id(
+    s: &JmapSession,
+    message_id: &str,
+) -> Result<Option<String>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/query",
+            {
+                "accountId": account_id,
+                "filter": { "header": ["Message-ID", message_id] },
+                "limit": 1
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    Ok(resp["methodResponses"][0][1]["ids"][0]
+        .as_str()
+        .map(String::from))
+}
+
+/// Every header field of one email, in message order (`headers` property).
+pub async fn get_headers(s: &JmapSession, email_id: &str) -> Result<Vec<EmailHeader>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/get",
+            { "accountId": account_id, "ids": [email_id], "properties": ["headers"] },
+            "0"
+        ])],
+    )
+    .await?;
+    parse_headers_response(&resp["methodResponses"][0][1])
+}
+
+/// An email's `blobId` is the raw message; download it through the
+/// session's `downloadUrl`.
+pub async fn get_raw_message(s: &JmapSession, email_id: &str) -> Result<Vec<u8>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/get",
+            { "accountId": account_id, "ids": [email_id], "properties": ["blobId"] },
+            "0"
+        ])],
+    )
+    .await?;
+    let blob_id = resp["methodResponses"][0][1]["list"][0]["blobId"]
+        .as_str()
+        .ok_or_else(|| Error::NotFound("Email not found".into()))?
+        .to_string();
+    let (_, bytes) = download_blob(s, &blob_id, "message.eml").await?;
+    Ok(bytes)
+}
+
+/// JMAP returns header values raw (leading space, folding intact).
+fn parse_headers_response(get: &serde_json::Value) -> Result<Vec<EmailHeader>, Error> {
+    let raw = get["list"][0]
+        .get("headers")
+        .ok_or_else(|| Error::NotFound("Email not found".into()))?;
+    let headers: Vec<EmailHeader> = serde_json::from_value(raw.clone())
+        .map_err(|e| Error::Internal(format!("Failed to parse Email/get headers: {e}")))?;
+    Ok(headers
+        .into_iter()
+        .map(|h| EmailHeader {
+            value: crate::provider_utils::unfold_header_value(&h.value),
+            name: h.name,
+        })
+        .collect())
+}
+
+/// Email ids changed since `since` (`Email/changes`). Without `since`,
+/// only the current state token, as the starting point for a new client.
+pub async fn email_changes(
+    s: &JmapSession,
+    since: Option<&str>,
+    max_changes: usize,
+) -> Result<EmailChanges, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let Some(since) = since else {
+        let resp = jmap_call(
+            s,
+            vec![serde_json::json!([
+                "Email/get",
+                { "accountId": account_id, "ids": [], "properties": ["id"] },
+                "0"
+            ])],
+        )
+        .await?;
+        let state = resp["methodResponses"][0][1]["state"]
+            .as_str()
+            .ok_or_else(|| Error::Internal("Email/get response has no state".into()))?;
+        return Ok(EmailChanges {
+            old_state: state.to_string(),
+            new_state: state.to_string(),
+            ..Default::default()
+        });
+    };
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/changes",
+            {
+                "accountId": account_id,
+                "sinceState": since,
+                "maxChanges": max_changes
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    parse_email_changes(&resp["methodResponses"][0])
+}
+
+/// One `Email/changes` method response, which may be an `error` invocation.
+fn parse_email_changes(response: &serde_json::Value) -> Result<EmailChanges, Error> {
+    if response[0] == "error" {
+        let kind = response[1]["type"].as_str().unwrap_or("unknown");
+        return Err(match kind {
+            "cannotCalculateChanges" => Error::Conflict(
+                "state is too old to compute changes from; resync without `since`".into(),
+            ),
+            "invalidArguments" => Error::BadRequest("`since` is not a valid state token".into()),
+            _ => Error::Internal(format!("Email/changes failed: {kind}")),
+        });
+    }
+    serde_json::from_value(response[1].clone())
+        .map_err(|e| Error::Internal(format!("Failed to parse Email/changes: {e}")))
+}
+
 pub async fn get_emails(
     s: &JmapSession,
     ids: &[String],
@@ -440,7 +1059,25 @@ pub async fn get_emails(
     }
 
     let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let mut args = email_get_args(account_id, fetch_body, properties_override);
+    args.insert("ids".into(), serde_json::json!(ids));
 
+    let resp = jmap_call(s, vec![serde_json::json!(["Email/get", args, "0"])]).await?;
+    let raw_emails: Vec<JmapEmailRaw> = extract_list(&resp, 0, "Email/get")?;
+    Ok(parse_email_list(s, raw_emails, fetch_body).await)
+}
+
+/// Body part properties a metadata-only `Email/get` asks for: enough for
+/// `find_calendar_blob_ids` to see an invite without fetching bodies.
+const LIST_BODY_PROPERTIES: &[&str] = &["partId", "blobId", "type", "name", "subParts"];
+
+/// `Email/get` arguments minus `ids`, which the caller adds (or
+/// back-references).
+fn email_get_args(
+    account_id: &str,
+    fetch_body: bool,
+    properties_override: Option<&[&str]>,
+) -> serde_json::Map<String, serde_json::Value> {
     let mut properties = if let Some(overrides) = properties_override {
         overrides.to_vec()
     } else {
@@ -459,19 +1096,37 @@ pub async fn get_emails(
             "hasAttachment",
             "size",
             "inReplyTo",
+            "header:X-Spam-Score:asText",
+            "header:X-Spam-Status:asText",
+            "header:X-Rspamd-Score:asText",
+            "header:X-Spam-Known-Sender:asText",
+            // Part types only (see `LIST_BODY_PROPERTIES`), so list rows
+            // carry `has_calendar` as well.
+            "bodyStructure",
+            // Read by the priority score, which runs on list rows.
+            "header:List-Unsubscribe:asText",
         ]
     };
     if fetch_body {
-        properties.extend_from_slice(&["textBody", "htmlBody", "bodyValues", "bodyStructure"]);
+        properties.extend_from_slice(&[
+            "textBody",
+            "htmlBody",
+            "bodyValues",
+            "messageId",
+            "references",
+            "replyTo",
+        ]);
     }
 
     let mut extra_args = serde_json::Map::new();
     extra_args.insert("accountId".into(), serde_json::json!(account_id));
-    extra_args.insert("ids".into(), serde_json::json!(ids));
     extra_args.insert("properties".into(), serde_json::json!(properties));
     extra_args.insert("fetchHTMLBodyValues".into(), serde_json::json!(fetch_body));
     extra_args.insert("fetchTextBodyValues".into(), serde_json::json!(fetch_body));
-    extra_args.insert("maxBodyValueBytes".into(), serde_json::json!(1_000_000));
+    extra_args.insert(
+        "maxBodyValueBytes".into(),
+        serde_json::json!(MAX_BODY_VALUE_BYTES),
+    );
     if fetch_body {
         extra_args.insert(
             "bodyProperties".into(),
@@ -483,22 +1138,127 @@ pub async fn get_emails(
                 "size",
                 "disposition",
                 "subParts",
-                "cid"
+                "cid",
+                "charset"
             ]),
         );
+    } else {
+        extra_args.insert(
+            "bodyProperties".into(),
+            serde_json::json!(LIST_BODY_PROPERTIES),
+        );
     }
 
-    let resp = jmap_call(s, vec![serde_json::json!(["Email/get", extra_args, "0"])]).await?;
+    extra_args
+}
 
-    let raw_emails: Vec<JmapEmailRaw> = extract_list(&resp, 0, "Email/get")?;
-    let emails = raw_emails
+/// Parse a raw `Email/get` list, marking invites found in attached
+/// messages when bodies were fetched.
+async fn parse_email_list(
+    s: &JmapSession,
+    raw_emails: Vec<JmapEmailRaw>,
+    fetch_body: bool,
+) -> Vec<Email> {
+    let embedded: Vec<Vec<String>> = if fetch_body {
+        raw_emails
+            .iter()
+            .map(|raw| {
+                raw.body_structure
+                    .as_ref()
+                    .map(find_embedded_message_blob_ids)
+                    .unwrap_or_default()
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut emails: Vec<Email> = raw_emails
         .into_iter()
         .map(|raw| parse_jmap_email_from_raw(raw, fetch_body))
         .collect();
+    mark_embedded_calendars(s, &mut emails, &embedded).await;
+    emails
+}
+
+/// `Thread/get` for `thread_id` plus an `Email/get` (with bodies) that
+/// back-references its `emailIds`, so the conversation is one request.
+fn thread_request(account_id: &str, thread_id: &str) -> Vec<serde_json::Value> {
+    let mut args = email_get_args(account_id, true, None);
+    args.insert(
+        "#ids".into(),
+        serde_json::json!({
+            "resultOf": "t",
+            "name": "Thread/get",
+            "path": "/list/*/emailIds",
+        }),
+    );
+    vec![
+        serde_json::json!(["Thread/get", { "accountId": account_id, "ids": [thread_id] }, "t"]),
+        serde_json::json!(["Email/get", args, "e"]),
+    ]
+}
 
+/// Every message in `thread_id` with bodies, oldest first.
+pub async fn get_thread(s: &JmapSession, thread_id: &str) -> Result<Vec<Email>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(s, thread_request(account_id, thread_id)).await?;
+    let threads = &resp["methodResponses"][0][1];
+    if threads["list"].as_array().is_none_or(|l| l.is_empty()) {
+        return Err(Error::NotFound(format!("Thread '{thread_id}' not found")));
+    }
+    let raw_emails: Vec<JmapEmailRaw> = extract_list(&resp, 1, "Email/get")?;
+    let mut emails = parse_email_list(s, raw_emails, true).await;
+    emails.sort_by_key(|e| e.received_at);
     Ok(emails)
 }
 
+/// Ids of every message in `thread_id`, without fetching them.
+pub async fn thread_email_ids(s: &JmapSession, thread_id: &str) -> Result<Vec<String>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Thread/get",
+            { "accountId": account_id, "ids": [thread_id] },
+            "t"
+        ])],
+    )
+    .await?;
+    resp["methodResponses"][0][1]["list"][0]["emailIds"]
+        .as_array()
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str().map(String::from))
+                .collect()
+        })
+        .ok_or_else(|| Error::NotFound(format!("Thread '{thread_id}' not found")))
+}
+
+/// Set `has_calendar` on emails whose only invite sits inside an attached
+/// message. One `Email/parse` pass covers the whole batch; a failure is
+/// logged and leaves the flags as the direct parts set them.
+async fn mark_embedded_calendars(s: &JmapSession, emails: &mut [Email], embedded: &[Vec<String>]) {
+    let roots: Vec<String> = emails
+        .iter()
+        .zip(embedded)
+        .filter(|(e, _)| !e.has_calendar)
+        .flat_map(|(_, blobs)| blobs.iter().cloned())
+        .collect();
+    if roots.is_empty() {
+        return;
+    }
+    match embedded_calendar_blob_ids(s, &roots).await {
+        Ok(found) => {
+            for (email, blobs) in emails.iter_mut().zip(embedded) {
+                if blobs.iter().any(|b| found.contains_key(b)) {
+                    email.has_calendar = true;
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Email/parse of attached messages failed: {e}"),
+    }
+}
+
 fn parse_jmap_email_from_raw(mut raw: JmapEmailRaw, fetch_body: bool) -> Email {
     fix_empty_names(&mut raw.from);
     fix_empty_names(&mut raw.to);
@@ -513,7 +1273,6 @@ fn parse_jmap_email_from_raw(mut raw: JmapEmailRaw, fetch_body: bool) -> Email {
 
     let mut text_body = None;
     let mut html_body = None;
-    let mut has_calendar = false;
 
     let default_bs = BodyStructurePart::default();
     let body_structure = raw.body_structure.as_ref().unwrap_or(&default_bs);
@@ -560,17 +1319,25 @@ fn parse_jmap_email_from_raw(mut raw: JmapEmailRaw, fetch_body: bool) -> Email {
                 *html = replace_case_insensitive(html, &format!("cid:{cid}"), &safe_url);
             }
         }
-
-        // Check for calendar in body structure
-        has_calendar = find_calendar_blob_id(body_structure).is_some();
     }
 
+    // Check for calendar in body structure, which list fetches carry too.
+    // Invites inside attached messages are found afterwards by
+    // `mark_embedded_calendars`, when bodies were fetched.
+    let has_calendar = !find_calendar_blob_ids(body_structure).is_empty();
+
     let attachments = if fetch_body {
         find_attachments(body_structure)
     } else {
         vec![]
     };
 
+    let body_parts = if fetch_body {
+        body_parts_from_raw(&raw)
+    } else {
+        vec![]
+    };
+
     Email {
         id: raw.id,
         blob_id: raw.blob_id,
@@ -592,9 +1359,51 @@ fn parse_jmap_email_from_raw(mut raw: JmapEmailRaw, fetch_body: bool) -> Email {
         // JMAP inReplyTo is a list; a single parent is the only case this app
         // produces (build_draft_email) and all the restore path needs.
         in_reply_to: raw.in_reply_to.and_then(|v| v.into_iter().next()),
+        message_id: raw
+            .message_id
+            .and_then(|v| v.into_iter().next())
+            .and_then(|id| crate::provider_utils::normalize_message_id(&id).ok()),
+        references: raw
+            .references
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|id| crate::provider_utils::normalize_message_id(id).ok())
+            .collect(),
+        reply_to: raw.reply_to,
+        body_parts,
+        spam: SpamInfo::from_headers(|name| {
+            match name {
+                "X-Spam-Score" => &raw.spam_score,
+                "X-Spam-Status" => &raw.spam_status,
+                "X-Rspamd-Score" => &raw.rspamd_score,
+                "X-Spam-Known-Sender" => &raw.spam_known_sender,
+                _ => &None,
+            }
+            .as_deref()
+        }),
+        list_unsubscribe: raw.list_unsubscribe,
     }
 }
 
+/// Describe the fetched text/html parts, flagging any whose body value was
+/// cut at `MAX_BODY_VALUE_BYTES`.
+fn body_parts_from_raw(raw: &JmapEmailRaw) -> Vec<BodyPart> {
+    let text = raw.text_body.iter().map(|p| ("text", p));
+    let html = raw.html_body.iter().map(|p| ("html", p));
+    text.chain(html)
+        .filter(|(_, p)| raw.body_values.contains_key(&p.part_id))
+        .map(|(kind, p)| BodyPart {
+            part_id: p.part_id.clone(),
+            blob_id: p.blob_id.clone(),
+            kind: kind.to_string(),
+            mime_type: p.mime_type.clone(),
+            charset: p.charset.clone(),
+            size: p.size,
+            is_truncated: raw.body_values[&p.part_id].is_truncated,
+        })
+        .collect()
+}
+
 /// Test-only wrapper: deserializes JSON then delegates to typed parsing.
 /// Preserves existing test compatibility while adding deserialization validation.
 #[cfg(test)]
@@ -604,6 +1413,49 @@ fn parse_jmap_email(item: &serde_json::Value, fetch_body: bool) -> Email {
     parse_jmap_email_from_raw(raw, fetch_body)
 }
 
+/// Test-only: run raw `Email/get` items through `parse_email_list`, the
+/// path every fetch takes, so other modules can test on real parser output.
+#[cfg(test)]
+pub(crate) async fn parse_email_list_json(
+    items: Vec<serde_json::Value>,
+    fetch_body: bool,
+) -> Vec<Email> {
+    let raw: Vec<JmapEmailRaw> = items
+        .into_iter()
+        .map(|item| {
+            serde_json::from_value(item)
+                .unwrap_or_else(|e| panic!("Failed to deserialize JMAP email: {e}"))
+        })
+        .collect();
+    let s = JmapSession::new("me@example.com", "Bearer t");
+    parse_email_list(&s, raw, fetch_body).await
+}
+
+/// A metadata-only `Email/get` item: what list fetches return.
+#[cfg(test)]
+pub(crate) fn metadata_only_invite() -> serde_json::Value {
+    serde_json::json!({
+        "id": "m-invite",
+        "blobId": "b-invite",
+        "threadId": "t-invite",
+        "mailboxIds": {"mb-inbox": true},
+        "keywords": {},
+        "receivedAt": "2026-10-01T09:00:00Z",
+        "subject": "Invitation: Standup",
+        "from": [{"name": "Alice", "email": "alice@x.example"}],
+        "preview": "You have been invited",
+        "hasAttachment": true,
+        "size": 4096,
+        "bodyStructure": {
+            "type": "multipart/mixed",
+            "subParts": [
+                {"partId": "1", "blobId": "b1", "type": "text/plain"},
+                {"partId": "2", "blobId": "b2", "type": "text/calendar", "name": "invite.ics"}
+            ]
+        }
+    })
+}
+
 pub fn find_attachments(body_structure: &BodyStructurePart) -> Vec<Attachment> {
     let mut attachments = Vec::new();
     collect_attachments(body_structure, false, &mut attachments);
@@ -662,6 +1514,9 @@ fn collect_attachments(part: &BodyStructurePart, in_related: bool, out: &mut Vec
             },
             mime_type: mime_type.to_ascii_lowercase(),
             size: part.size,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
         });
     }
 }
@@ -801,6 +1656,40 @@ pub async fn download_blob(
     Ok((content_type, bytes.to_vec()))
 }
 
+/// Open a blob download without buffering it, for streaming straight to the
+/// client (`/api/emails/{id}/body/full`). Returns the upstream response once
+/// its status has been checked.
+pub async fn download_blob_response(
+    s: &JmapSession,
+    blob_id: &str,
+    mime_type: &str,
+) -> Result<reqwest::Response, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let download_url = s.download_url.as_ref().ok_or(Error::NotConnected)?;
+
+    let url = download_url
+        .replace("{accountId}", account_id)
+        .replace("{blobId}", blob_id)
+        .replace("{name}", "body")
+        .replace("{type}", &percent_encode_path(mime_type));
+
+    let resp = s
+        .limiter
+        .execute("blob.download", || async {
+            s.client
+                .get(&url)
+                .header("Authorization", &s.auth_header)
+                .send()
+                .await
+        })
+        .await?;
+
+    if !resp.status().is_success() {
+        return Err(Error::NotFound("Body part not found".into()));
+    }
+    Ok(resp)
+}
+
 // =============================================================================
 // Email actions
 // =============================================================================
@@ -885,6 +1774,57 @@ pub async fn toggle_flag(s: &JmapSession, email_id: &str) -> Result<bool, Error>
     }
 }
 
+/// Flag `email_id` and set its color bits; `None` leaves a plain star.
+pub async fn set_flag_color(
+    s: &JmapSession,
+    email_id: &str,
+    color: Option<FlagColor>,
+) -> Result<bool, Error> {
+    let mut patch = serde_json::Map::new();
+    patch.insert("keywords/$flagged".into(), serde_json::json!(true));
+    for (keyword, set) in FlagColor::keyword_bits(color) {
+        let value = if set {
+            serde_json::json!(true)
+        } else {
+            serde_json::Value::Null
+        };
+        patch.insert(format!("keywords/{keyword}"), value);
+    }
+    set_email_keywords(s, email_id, serde_json::Value::Object(patch)).await
+}
+
+/// Set a custom keyword (e.g. `receipt`) on a message. Idempotent.
+pub async fn add_keyword(s: &JmapSession, email_id: &str, keyword: &str) -> Result<bool, Error> {
+    set_email_keywords(
+        s,
+        email_id,
+        serde_json::json!({ format!("keywords/{keyword}"): true }),
+    )
+    .await
+}
+
+/// Add and remove custom keywords on a message in one `Email/set`.
+pub async fn set_keywords(
+    s: &JmapSession,
+    email_id: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<bool, Error> {
+    set_email_keywords(s, email_id, keywords_patch(add, remove)).await
+}
+
+/// `keywords/<k>` patch entries: `true` to add, `null` to remove.
+fn keywords_patch(add: &[String], remove: &[String]) -> serde_json::Value {
+    let mut patch = serde_json::Map::new();
+    for keyword in remove {
+        patch.insert(format!("keywords/{keyword}"), serde_json::Value::Null);
+    }
+    for keyword in add {
+        patch.insert(format!("keywords/{keyword}"), serde_json::json!(true));
+    }
+    serde_json::Value::Object(patch)
+}
+
 pub async fn archive(s: &JmapSession, email_id: &str) -> Result<bool, Error> {
     move_to_role(s, email_id, "archive").await
 }
@@ -929,6 +1869,38 @@ async fn move_to_role(s: &JmapSession, email_id: &str, role: &str) -> Result<boo
     Ok(updated)
 }
 
+/// Put `email_id` back into exactly `mailbox_ids` (e.g. out of Trash into
+/// where it was before).
+pub async fn set_mailboxes(
+    s: &JmapSession,
+    email_id: &str,
+    mailbox_ids: &[String],
+) -> Result<bool, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let ids: serde_json::Map<String, serde_json::Value> = mailbox_ids
+        .iter()
+        .map(|id| (id.clone(), serde_json::json!(true)))
+        .collect();
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/set",
+            {
+                "accountId": account_id,
+                "update": { email_id: { "mailboxIds": ids } }
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    if resp["methodResponses"][0][1]["notUpdated"][email_id]["type"].as_str() == Some("notFound") {
+        return Err(Error::NotFound(format!("email {email_id}")));
+    }
+    Ok(resp["methodResponses"][0][1]["updated"]
+        .as_object()
+        .is_some_and(|obj| obj.contains_key(email_id)))
+}
+
 pub async fn move_to_mailbox(
     s: &JmapSession,
     email_id: &str,
@@ -962,6 +1934,258 @@ pub async fn move_to_mailbox(
     Ok(updated)
 }
 
+/// Create a mailbox named `name` under `parent_id` (top-level when
+/// `None`); returns its id.
+pub async fn create_mailbox(
+    s: &JmapSession,
+    name: &str,
+    parent_id: Option<&str>,
+) -> Result<String, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Mailbox/set",
+            {
+                "accountId": account_id,
+                "create": { "new": { "name": name, "parentId": parent_id } }
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    let result = &resp["methodResponses"][0][1];
+    if let Some(id) = result["created"]["new"]["id"].as_str() {
+        return Ok(id.to_string());
+    }
+    if result["notCreated"]["new"].is_object() {
+        return Err(mailbox_set_error(
+            &format!("create '{name}'"),
+            &result["notCreated"]["new"],
+        ));
+    }
+    Err(Error::Internal(format!(
+        "Mailbox/set create '{name}' failed: no id returned"
+    )))
+}
+
+/// Rename and/or move a mailbox. `parent_id` is `Some(None)` to move it to
+/// the top level and `None` to leave its parent alone.
+pub async fn update_mailbox(
+    s: &JmapSession,
+    mailbox_id: &str,
+    name: Option<&str>,
+    parent_id: Option<Option<&str>>,
+) -> Result<(), Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Mailbox/set",
+            {
+                "accountId": account_id,
+                "update": { mailbox_id: mailbox_patch(name, parent_id) }
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    let result = &resp["methodResponses"][0][1];
+    if result["updated"].get(mailbox_id).is_some() {
+        return Ok(());
+    }
+    Err(mailbox_set_error(
+        &format!("update {mailbox_id}"),
+        &result["notUpdated"][mailbox_id],
+    ))
+}
+
+fn mailbox_patch(
+    name: Option<&str>,
+    parent_id: Option<Option<&str>>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut patch = serde_json::Map::new();
+    if let Some(name) = name {
+        patch.insert("name".into(), serde_json::json!(name));
+    }
+    if let Some(parent_id) = parent_id {
+        patch.insert("parentId".into(), serde_json::json!(parent_id));
+    }
+    patch
+}
+
+/// Destroy a mailbox. Without `remove_emails` the server refuses one that
+/// still holds mail (`mailboxHasEmail`); with it, messages only in this
+/// mailbox are destroyed along with it.
+pub async fn destroy_mailbox(
+    s: &JmapSession,
+    mailbox_id: &str,
+    remove_emails: bool,
+) -> Result<(), Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Mailbox/set",
+            {
+                "accountId": account_id,
+                "destroy": [mailbox_id],
+                "onDestroyRemoveEmails": remove_emails
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    let result = &resp["methodResponses"][0][1];
+    let destroyed = result["destroyed"]
+        .as_array()
+        .is_some_and(|ids| ids.iter().any(|id| id.as_str() == Some(mailbox_id)));
+    if destroyed {
+        return Ok(());
+    }
+    Err(mailbox_set_error(
+        &format!("destroy {mailbox_id}"),
+        &result["notDestroyed"][mailbox_id],
+    ))
+}
+
+/// Map a `Mailbox/set` SetError onto our error kinds.
+fn mailbox_set_error(op: &str, err: &serde_json::Value) -> Error {
+    let kind = err["type"].as_str().unwrap_or("unknown");
+    let detail = err["description"].as_str().unwrap_or(kind);
+    let message = format!("Mailbox/set {op} failed: {detail}");
+    match kind {
+        "notFound" => Error::NotFound(message),
+        "mailboxHasEmail" | "mailboxHasChild" | "alreadyExists" => Error::Conflict(message),
+        "invalidProperties" | "invalidPatch" | "forbidden" | "tooManyMailboxes" => {
+            Error::BadRequest(message)
+        }
+        _ => Error::Internal(message),
+    }
+}
+
+/// The active Sieve script with its content, or `None` when the account
+/// has no active script.
+pub async fn get_sieve_script(s: &JmapSession) -> Result<Option<SieveScript>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "SieveScript/get",
+            {
+                "accountId": account_id,
+                "ids": null,
+                "properties": ["id", "name", "blobId", "isActive"]
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    let response = &resp["methodResponses"][0];
+    if response[0] == "error" {
+        return Err(sieve_method_error("SieveScript/get", &response[1]));
+    }
+    let Some(active) = response[1]["list"]
+        .as_array()
+        .and_then(|list| list.iter().find(|script| script["isActive"] == true))
+    else {
+        return Ok(None);
+    };
+    let blob_id = active["blobId"]
+        .as_str()
+        .ok_or_else(|| Error::Internal("SieveScript/get: script has no blobId".into()))?;
+    let (_, bytes) = download_blob(s, blob_id, "script.siv").await?;
+    Ok(Some(SieveScript {
+        id: active["id"].as_str().map(str::to_string),
+        name: active["name"].as_str().map(str::to_string),
+        content: String::from_utf8_lossy(&bytes).into_owned(),
+    }))
+}
+
+/// Replace the content of script `script_id`, or create a script and make
+/// it active when `script_id` is `None`. Returns the script's id. The
+/// server validates the script; a rejected one is `Unprocessable`.
+pub async fn put_sieve_script(
+    s: &JmapSession,
+    script_id: Option<&str>,
+    content: &str,
+) -> Result<String, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let (blob_id, _) = upload_blob(s, "application/sieve", content.as_bytes()).await?;
+    let call = match script_id {
+        Some(id) => serde_json::json!([
+            "SieveScript/set",
+            {
+                "accountId": account_id,
+                "update": { id: { "blobId": blob_id } }
+            },
+            "0"
+        ]),
+        None => serde_json::json!([
+            "SieveScript/set",
+            {
+                "accountId": account_id,
+                "create": { "new": { "name": crate::sieve::SCRIPT_NAME, "blobId": blob_id } },
+                "onSuccessActivateScript": "#new"
+            },
+            "0"
+        ]),
+    };
+    let resp = jmap_call(s, vec![call]).await?;
+    let response = &resp["methodResponses"][0];
+    if response[0] == "error" {
+        return Err(sieve_method_error("SieveScript/set", &response[1]));
+    }
+    let result = &response[1];
+    match script_id {
+        Some(id) => {
+            if result["updated"].get(id).is_some() {
+                return Ok(id.to_string());
+            }
+            if let Some(err) = result["notUpdated"].get(id) {
+                return Err(sieve_set_error("update", err));
+            }
+        }
+        None => {
+            if let Some(id) = result["created"]["new"]["id"].as_str() {
+                return Ok(id.to_string());
+            }
+            if let Some(err) = result["notCreated"].get("new") {
+                return Err(sieve_set_error("create", err));
+            }
+        }
+    }
+    Err(Error::Internal(
+        "SieveScript/set failed: no result for the script".into(),
+    ))
+}
+
+/// Map a `SieveScript/set` SetError onto our error kinds.
+fn sieve_set_error(op: &str, err: &serde_json::Value) -> Error {
+    let kind = err["type"].as_str().unwrap_or("unknown");
+    let detail = err["description"].as_str().unwrap_or(kind);
+    let message = format!("SieveScript/set {op} failed: {detail}");
+    match kind {
+        "invalidSieve" => Error::Unprocessable(message),
+        "notFound" => Error::NotFound(message),
+        "alreadyExists" | "sieveIsActive" => Error::Conflict(message),
+        "invalidProperties" | "forbidden" | "overQuota" | "tooLarge" => Error::BadRequest(message),
+        _ => Error::Internal(message),
+    }
+}
+
+/// A method-level error from a `SieveScript/*` call. Accounts without
+/// Sieve answer `accountNotSupportedByMethod`.
+fn sieve_method_error(method: &str, err: &serde_json::Value) -> Error {
+    let kind = err["type"].as_str().unwrap_or("unknown");
+    match kind {
+        "unknownMethod" | "accountNotSupportedByMethod" => {
+            Error::NotImplemented(format!("this account has no Sieve scripts ({kind})"))
+        }
+        _ => Error::Internal(format!("{method} failed: {kind}")),
+    }
+}
+
 pub async fn archive_batch(s: &JmapSession, email_ids: &[String]) -> Result<usize, Error> {
     if email_ids.is_empty() {
         return Ok(0);
@@ -1006,22 +2230,87 @@ pub async fn archive_batch(s: &JmapSession, email_ids: &[String]) -> Result<usiz
     Ok(count)
 }
 
-// =============================================================================
-// Send email
-// =============================================================================
-
-fn build_draft_email(
-    sub: &EmailSubmission,
-    from_addr: &str,
-    drafts_mailbox_id: &str,
-) -> serde_json::Map<String, serde_json::Value> {
-    let mut m: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
-    m.insert(
-        "mailboxIds".into(),
-        serde_json::json!({ drafts_mailbox_id: true }),
-    );
-    m.insert("from".into(), serde_json::json!([{"email": from_addr}]));
-    m.insert(
+/// Set `$seen` on every id in one `Email/set` (split by `jmap_call` when
+/// it exceeds `maxObjectsInSet`). Returns how many the server reported
+/// updated.
+pub async fn mark_read_batch(s: &JmapSession, email_ids: &[String]) -> Result<usize, Error> {
+    if email_ids.is_empty() {
+        return Ok(0);
+    }
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/set",
+            {
+                "accountId": account_id,
+                "update": seen_updates(email_ids)
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    Ok(resp["methodResponses"][0][1]["updated"]
+        .as_object()
+        .map(|obj| obj.len())
+        .unwrap_or(0))
+}
+
+fn seen_updates(email_ids: &[String]) -> serde_json::Map<String, serde_json::Value> {
+    email_ids
+        .iter()
+        .map(|id| (id.clone(), serde_json::json!({ "keywords/$seen": true })))
+        .collect()
+}
+
+/// Permanently destroy `email_ids` in one `Email/set destroy` (split by
+/// `jmap_call` past `maxObjectsInSet`). There is no Trash round-trip.
+/// Ids the server reports `notFound` are already gone and count as
+/// destroyed.
+pub async fn destroy_batch(s: &JmapSession, email_ids: &[String]) -> Result<usize, Error> {
+    if email_ids.is_empty() {
+        return Ok(0);
+    }
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/set",
+            { "accountId": account_id, "destroy": email_ids },
+            "0"
+        ])],
+    )
+    .await?;
+    Ok(destroyed_count(&resp["methodResponses"][0][1]))
+}
+
+/// `destroyed` plus `notDestroyed` entries of type `notFound`.
+fn destroyed_count(set_response: &serde_json::Value) -> usize {
+    let destroyed = set_response["destroyed"].as_array().map_or(0, Vec::len);
+    let gone = set_response["notDestroyed"].as_object().map_or(0, |m| {
+        m.values()
+            .filter(|e| e["type"].as_str() == Some("notFound"))
+            .count()
+    });
+    destroyed + gone
+}
+
+// =============================================================================
+// Send email
+// =============================================================================
+
+fn build_draft_email(
+    sub: &EmailSubmission,
+    from_addr: &str,
+    drafts_mailbox_id: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut m: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+    m.insert(
+        "mailboxIds".into(),
+        serde_json::json!({ drafts_mailbox_id: true }),
+    );
+    m.insert("from".into(), serde_json::json!([{"email": from_addr}]));
+    m.insert(
         "to".into(),
         serde_json::json!(
             sub.to
@@ -1093,10 +2382,35 @@ fn build_draft_email(
         );
     }
 
-    // Stage 2: wrap with attachments if present
-    if !sub.attachments.is_empty() {
-        let attachment_parts: Vec<serde_json::Value> = sub
-            .attachments
+    // Stage 2: images the HTML shows by `cid:` go next to it in a
+    // multipart/related. Without an HTML part they're plain attachments.
+    let (inline, attached): (Vec<&Attachment>, Vec<&Attachment>) = sub
+        .attachments
+        .iter()
+        .partition(|a| a.content_id.is_some() && sub.html_body.is_some());
+    if !inline.is_empty()
+        && let Some(parts) = m
+            .get_mut("bodyStructure")
+            .and_then(|b| b["subParts"].as_array_mut())
+        && let Some(html) = parts.iter_mut().find(|p| p["partId"] == "html")
+    {
+        let mut related = vec![html.take()];
+        related.extend(inline.iter().map(|a| {
+            serde_json::json!({
+                "type": a.mime_type,
+                "blobId": a.blob_id,
+                "name": a.name,
+                "disposition": "inline",
+                "cid": a.content_id,
+                "size": a.size
+            })
+        }));
+        *html = serde_json::json!({"type": "multipart/related", "subParts": related});
+    }
+
+    // Stage 3: wrap with attachments if present
+    if !attached.is_empty() {
+        let attachment_parts: Vec<serde_json::Value> = attached
             .iter()
             .map(|a| {
                 serde_json::json!({
@@ -1180,6 +2494,11 @@ pub async fn send_email(
     from_addr: &str,
     identity_id_override: Option<&str>,
 ) -> Result<Option<String>, Error> {
+    if !s.supports(CAPABILITY_SUBMISSION) {
+        return Err(Error::NotImplemented(
+            "sending is unavailable: this server does not offer JMAP submission".into(),
+        ));
+    }
     let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?.clone();
 
     // Resolve identity
@@ -1228,7 +2547,7 @@ pub async fn send_email(
         .id
         .clone();
 
-    let email_create = build_draft_email(sub, from_addr, &drafts_id);
+    let email_create = send_draft_email(sub, from_addr, &drafts_id);
 
     // Build envelope
     let mut rcpt_to: Vec<serde_json::Value> = sub
@@ -1255,11 +2574,7 @@ pub async fn send_email(
                 "0"
             ]),
             {
-                // Build the patch to move from Drafts → Sent and clear $draft keyword
-                let mut patch = serde_json::Map::new();
-                patch.insert(format!("mailboxIds/{drafts_id}"), serde_json::Value::Null);
-                patch.insert(format!("mailboxIds/{sent_id}"), serde_json::json!(true));
-                patch.insert("keywords/$draft".into(), serde_json::Value::Null);
+                let patch = sent_patch(&drafts_id, &sent_id);
 
                 serde_json::json!([
                     "EmailSubmission/set",
@@ -1306,6 +2621,15 @@ pub async fn send_email(
         } else {
             not_created.to_string()
         };
+        // The draft was created but never sent; don't leave it in Drafts.
+        if let Some(draft_id) = email_created["id"].as_str() {
+            match jmap_call(s, draft_destroy_request(&account_id, draft_id)).await {
+                Ok(_) => tracing::info!("Destroyed draft {draft_id} after failed submission"),
+                Err(e) => tracing::warn!(
+                    "Failed to destroy draft {draft_id} after failed submission: {e}"
+                ),
+            }
+        }
         return Err(Error::Internal(format!(
             "Email submission failed: {detail}"
         )));
@@ -1320,6 +2644,98 @@ pub async fn send_email(
     Ok(email_id)
 }
 
+/// Keyword on the send flow's transient draft, cleared together with
+/// `$draft` when the submission succeeds. Anything still carrying it
+/// later is an orphan from a send that never completed.
+const SENDING_KEYWORD: &str = "$sv-sending";
+
+/// Orphans younger than this are left to a send that may still be in
+/// flight (another running instance) when sweeping.
+const ORPHAN_GRACE: chrono::Duration = chrono::Duration::minutes(10);
+
+/// The send flow's draft: `build_draft_email` marked `$draft` plus
+/// [`SENDING_KEYWORD`], so a failed send can be found and cleaned up.
+fn send_draft_email(
+    sub: &EmailSubmission,
+    from_addr: &str,
+    drafts_mailbox_id: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut email = build_draft_email(sub, from_addr, drafts_mailbox_id);
+    email.insert(
+        "keywords".into(),
+        serde_json::json!({ "$draft": true, SENDING_KEYWORD: true }),
+    );
+    email
+}
+
+/// `onSuccessUpdateEmail` patch: move from Drafts to Sent and clear the
+/// draft keywords.
+fn sent_patch(drafts_id: &str, sent_id: &str) -> serde_json::Map<String, serde_json::Value> {
+    let mut patch = serde_json::Map::new();
+    patch.insert(format!("mailboxIds/{drafts_id}"), serde_json::Value::Null);
+    patch.insert(format!("mailboxIds/{sent_id}"), serde_json::json!(true));
+    patch.insert("keywords/$draft".into(), serde_json::Value::Null);
+    patch.insert(
+        format!("keywords/{SENDING_KEYWORD}"),
+        serde_json::Value::Null,
+    );
+    patch
+}
+
+/// Destroy send-flow drafts whose submission never happened (the process
+/// died between the two calls, or the cleanup in `send_email` failed).
+/// Only drafts carrying [`SENDING_KEYWORD`] and older than
+/// [`ORPHAN_GRACE`] are touched, so user drafts are never swept. Returns
+/// how many were destroyed.
+pub async fn sweep_orphaned_send_drafts(s: &JmapSession) -> Result<usize, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let cutoff =
+        (chrono::Utc::now() - ORPHAN_GRACE).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/query",
+            {
+                "accountId": account_id,
+                "filter": {
+                    "operator": "AND",
+                    "conditions": [
+                        { "hasKeyword": SENDING_KEYWORD },
+                        { "hasKeyword": "$draft" },
+                        { "before": cutoff }
+                    ]
+                },
+                "limit": 100
+            },
+            "0"
+        ])],
+    )
+    .await?;
+    let ids: Vec<String> = resp["methodResponses"][0][1]["ids"]
+        .as_array()
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let resp = jmap_call(
+        s,
+        vec![serde_json::json!([
+            "Email/set",
+            { "accountId": account_id, "destroy": ids },
+            "0"
+        ])],
+    )
+    .await?;
+    Ok(resp["methodResponses"][0][1]["destroyed"]
+        .as_array()
+        .map_or(0, Vec::len))
+}
+
 // =============================================================================
 // Persistent drafts (kata wm57)
 // =============================================================================
@@ -1347,6 +2763,48 @@ fn drafts_mailbox_id(s: &JmapSession) -> Result<String, Error> {
         .ok_or_else(|| Error::Internal("No drafts mailbox found".into()))
 }
 
+/// Email/query for saved drafts in the drafts mailbox, newest first, plus
+/// an Email/get (list properties) that back-references the ids. Drafts a
+/// send is still using (`SENDING_KEYWORD`) aren't resumable and are left
+/// out. Pure so the request shape is testable.
+fn drafts_list_request(account_id: &str, drafts_id: &str, limit: usize) -> Vec<serde_json::Value> {
+    let mut get_args = email_get_args(account_id, false, None);
+    get_args.insert(
+        "#ids".into(),
+        serde_json::json!({ "resultOf": "q", "name": "Email/query", "path": "/ids" }),
+    );
+    vec![
+        serde_json::json!([
+            "Email/query",
+            {
+                "accountId": account_id,
+                "filter": {
+                    "operator": "AND",
+                    "conditions": [
+                        { "inMailbox": drafts_id },
+                        { "hasKeyword": "$draft" },
+                        { "notKeyword": SENDING_KEYWORD }
+                    ]
+                },
+                "sort": [{ "property": "receivedAt", "isAscending": false }],
+                "limit": limit
+            },
+            "q"
+        ]),
+        serde_json::json!(["Email/get", get_args, "g"]),
+    ]
+}
+
+/// Saved drafts, newest first, in list shape (no bodies; open one through
+/// `get_emails` to resume it).
+pub async fn list_drafts(s: &JmapSession, limit: usize) -> Result<Vec<Email>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let drafts_id = drafts_mailbox_id(s)?;
+    let resp = jmap_call(s, drafts_list_request(account_id, &drafts_id, limit)).await?;
+    let raw_emails: Vec<JmapEmailRaw> = extract_list(&resp, 1, "Email/get")?;
+    Ok(parse_email_list(s, raw_emails, false).await)
+}
+
 /// Email/set create for a persistent draft: `build_draft_email` plus the
 /// `$draft` keyword, no submission. Pure so the request shape is testable.
 fn draft_create_request(
@@ -1556,26 +3014,108 @@ pub async fn destroy_draft(s: &JmapSession, draft_id: &str) -> Result<bool, Erro
 // Calendar
 // =============================================================================
 
-pub fn find_calendar_blob_id(body_structure: &BodyStructurePart) -> Option<String> {
-    let mime_type = body_structure.mime_type.to_lowercase();
-    let filename = body_structure
-        .name
-        .as_deref()
-        .unwrap_or_default()
-        .to_lowercase();
+/// How many levels of attached messages (a forward of a forward…) the
+/// calendar lookup parses into.
+const MAX_EMBEDDED_DEPTH: usize = 3;
+
+/// Calendar parts beyond this many are ignored by `get_calendar_data`, so a
+/// message stuffed with .ics files can't fan out into unbounded downloads.
+const MAX_CALENDAR_PARTS: usize = 5;
+
+/// Blob ids of every calendar part (`text/calendar` or `*.ics`), outer
+/// parts first.
+pub fn find_calendar_blob_ids(body_structure: &BodyStructurePart) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_parts(body_structure, &mut found, &|part| {
+        part.mime_type.eq_ignore_ascii_case("text/calendar")
+            || part
+                .name
+                .as_deref()
+                .unwrap_or_default()
+                .to_lowercase()
+                .ends_with(".ics")
+    });
+    found
+}
+
+/// Blob ids of attached messages (`message/rfc822`). JMAP reports these
+/// as leaf parts; what's inside takes an `Email/parse` to see.
+pub fn find_embedded_message_blob_ids(body_structure: &BodyStructurePart) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_parts(body_structure, &mut found, &|part| {
+        part.mime_type.eq_ignore_ascii_case("message/rfc822")
+    });
+    found
+}
 
-    if mime_type == "text/calendar" || filename.ends_with(".ics") {
-        return body_structure.blob_id.clone();
+fn collect_parts(
+    part: &BodyStructurePart,
+    found: &mut Vec<String>,
+    wanted: &dyn Fn(&BodyStructurePart) -> bool,
+) {
+    if wanted(part) {
+        found.extend(part.blob_id.clone());
+        return;
+    }
+    for sub in &part.sub_parts {
+        collect_parts(sub, found, wanted);
     }
+}
 
-    // Recurse into sub-parts
-    for part in &body_structure.sub_parts {
-        if let Some(blob_id) = find_calendar_blob_id(part) {
-            return Some(blob_id);
+/// Calendar part blob ids inside each attached message in `roots`,
+/// following attached messages up to `MAX_EMBEDDED_DEPTH` levels deep.
+/// Keyed by the root blob; roots with nothing found are absent. Blobs the
+/// server can't parse are skipped.
+async fn embedded_calendar_blob_ids(
+    s: &JmapSession,
+    roots: &[String],
+) -> Result<HashMap<String, Vec<String>>, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let mut found: HashMap<String, Vec<String>> = HashMap::new();
+    // (blob to parse, root it came from)
+    let mut frontier: Vec<(String, String)> =
+        roots.iter().map(|b| (b.clone(), b.clone())).collect();
+    for _ in 0..MAX_EMBEDDED_DEPTH {
+        if frontier.is_empty() {
+            break;
         }
+        let blob_ids: Vec<&str> = frontier.iter().map(|(b, _)| b.as_str()).collect();
+        let resp = jmap_call(
+            s,
+            vec![serde_json::json!([
+                "Email/parse",
+                {
+                    "accountId": account_id,
+                    "blobIds": blob_ids,
+                    "properties": ["bodyStructure"],
+                    "bodyProperties": ["partId", "blobId", "type", "name", "subParts"]
+                },
+                "0"
+            ])],
+        )
+        .await?;
+        let parsed = &resp["methodResponses"][0][1]["parsed"];
+        let mut next = Vec::new();
+        for (blob_id, root) in frontier {
+            let Some(bs) = parsed[&blob_id]
+                .get("bodyStructure")
+                .and_then(|v| serde_json::from_value::<BodyStructurePart>(v.clone()).ok())
+            else {
+                continue;
+            };
+            let calendars = find_calendar_blob_ids(&bs);
+            if !calendars.is_empty() {
+                found.entry(root.clone()).or_default().extend(calendars);
+            }
+            next.extend(
+                find_embedded_message_blob_ids(&bs)
+                    .into_iter()
+                    .map(|b| (b, root.clone())),
+            );
+        }
+        frontier = next;
     }
-
-    None
+    Ok(found)
 }
 
 pub async fn get_calendar_data(s: &JmapSession, email_id: &str) -> Result<Option<String>, Error> {
@@ -1611,32 +3151,71 @@ pub async fn get_calendar_data(s: &JmapSession, email_id: &str) -> Result<Option
             .map_err(|e| Error::Internal(format!("Failed to parse bodyStructure: {e}")))?,
         _ => return Ok(None),
     };
-    let blob_id = match find_calendar_blob_id(&body_structure) {
-        Some(id) => id,
-        None => return Ok(None),
-    };
+    // Direct parts first, then any inside forwarded messages.
+    let mut blob_ids = find_calendar_blob_ids(&body_structure);
+    let embedded = find_embedded_message_blob_ids(&body_structure);
+    if !embedded.is_empty() {
+        let mut nested = embedded_calendar_blob_ids(s, &embedded).await?;
+        for root in &embedded {
+            blob_ids.extend(nested.remove(root).unwrap_or_default());
+        }
+    }
+    blob_ids.truncate(MAX_CALENDAR_PARTS);
 
-    // Download the blob
     let download_url = s.download_url.as_ref().ok_or(Error::NotConnected)?;
-    let url = download_url
-        .replace("{accountId}", account_id)
-        .replace("{blobId}", &blob_id)
-        .replace("{name}", "invite.ics")
-        .replace("{type}", "text/calendar");
+    let mut candidates = Vec::new();
+    for blob_id in &blob_ids {
+        let url = download_url
+            .replace("{accountId}", account_id)
+            .replace("{blobId}", blob_id)
+            .replace("{name}", "invite.ics")
+            .replace("{type}", "text/calendar");
+        let resp = s
+            .client
+            .get(&url)
+            .header("Authorization", &s.auth_header)
+            .send()
+            .await?;
+        if resp.status().is_success() {
+            candidates.push(resp.text().await?);
+        }
+    }
+    Ok(crate::calendar::pick_invite_ics(candidates))
+}
 
+/// CardDAV `addressbook-query` asking for every card's full vCard.
+const ADDRESSBOOK_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+  <D:prop><D:getetag/><C:address-data/></D:prop>
+</C:addressbook-query>"#;
+
+/// Every vCard in the default CardDAV address book, which Fastmail serves
+/// on the same credentials as CalDAV.
+pub async fn fetch_vcards(s: &JmapSession) -> Result<Vec<String>, Error> {
+    let url = format!(
+        "https://carddav.fastmail.com/dav/addressbooks/user/{}/Default/",
+        s.username
+    );
+    let report = reqwest::Method::from_bytes(b"REPORT").expect("valid method name");
     let resp = s
         .client
-        .get(&url)
+        .request(report, &url)
         .header("Authorization", &s.auth_header)
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(ADDRESSBOOK_QUERY)
         .send()
         .await?;
-
-    if !resp.status().is_success() {
-        return Ok(None);
+    match resp.status().as_u16() {
+        200..=299 => Ok(crate::contacts::extract_address_data(&resp.text().await?)),
+        401 | 403 => Err(Error::Auth(format!(
+            "CardDAV access denied ({})",
+            resp.status().as_u16()
+        ))),
+        code => Err(Error::Network(format!(
+            "CardDAV REPORT failed: HTTP {code}"
+        ))),
     }
-
-    let ics_data = resp.text().await?;
-    Ok(Some(ics_data))
 }
 
 /// Fetch the current calendar event from CalDAV by UID.
@@ -1726,108 +3305,635 @@ pub async fn remove_from_calendar(s: &JmapSession, uid: &str) -> Result<bool, Er
         return Ok(false);
     }
 
-    Ok(true)
-}
-
-pub async fn get_rsvp_status(s: &JmapSession, uid: &str, attendee_email: &str) -> Option<String> {
-    let caldav_url = format!(
-        "https://caldav.fastmail.com/dav/calendars/user/{}/Default/{}.ics",
-        s.username,
-        percent_encode_path(uid)
-    );
-
-    let resp = match s
-        .client
-        .get(&caldav_url)
-        .header("Authorization", &s.auth_header)
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            tracing::warn!("CalDAV GET failed for {uid}: {e}");
-            return None;
-        }
-    };
+    Ok(true)
+}
+
+pub async fn get_rsvp_status(s: &JmapSession, uid: &str, attendee_email: &str) -> Option<String> {
+    let caldav_url = format!(
+        "https://caldav.fastmail.com/dav/calendars/user/{}/Default/{}.ics",
+        s.username,
+        percent_encode_path(uid)
+    );
+
+    let resp = match s
+        .client
+        .get(&caldav_url)
+        .header("Authorization", &s.auth_header)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!("CalDAV GET failed for {uid}: {e}");
+            return None;
+        }
+    };
+
+    if !resp.status().is_success() {
+        return None;
+    }
+
+    let ics_data = match resp.text().await {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::warn!("CalDAV GET body read failed for {uid}: {e}");
+            return None;
+        }
+    };
+
+    attendee_status_from_ics(&ics_data, attendee_email)
+}
+
+/// Parse ICS data and extract a specific attendee's PARTSTAT.
+fn attendee_status_from_ics(ics_data: &str, attendee_email: &str) -> Option<String> {
+    let event = crate::calendar::parse_ics(ics_data)?;
+    let email_lower = attendee_email.to_lowercase();
+    event
+        .attendees
+        .iter()
+        .find(|a| a.email.to_lowercase() == email_lower)
+        .map(|a| a.status.clone())
+}
+
+/// UUID v4 generation using /dev/urandom for proper randomness.
+#[cfg(test)]
+fn uuid_v4() -> String {
+    let mut buf = [0u8; 16];
+    // Read exactly 16 bytes from /dev/urandom
+    let ok = (|| -> Result<(), std::io::Error> {
+        use std::io::Read;
+        let mut f = std::fs::File::open("/dev/urandom")?;
+        f.read_exact(&mut buf)?;
+        Ok(())
+    })();
+    if ok.is_err() {
+        // Fallback: combine time + stack address + counter for entropy
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let t = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let stack_addr = &buf as *const _ as u64;
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let seed = t ^ (stack_addr as u128) ^ ((count as u128) << 64);
+        buf[..8].copy_from_slice(&(seed as u64).to_le_bytes());
+        buf[8..].copy_from_slice(&((seed >> 64) as u64).to_le_bytes());
+    }
+    // Set version (4) and variant (10xx) bits per RFC 4122
+    buf[6] = (buf[6] & 0x0F) | 0x40;
+    buf[8] = (buf[8] & 0x3F) | 0x80;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]),
+        u16::from_be_bytes([buf[4], buf[5]]),
+        u16::from_be_bytes([buf[6], buf[7]]),
+        u16::from_be_bytes([buf[8], buf[9]]),
+        u64::from_be_bytes([0, 0, buf[10], buf[11], buf[12], buf[13], buf[14], buf[15]]),
+    )
+}
+
+// =============================================================================
+// Raw passthrough (`/api/jmap`)
+// =============================================================================
+
+/// Most method calls one `/api/jmap` request may carry, on top of the
+/// server's own `maxCallsInRequest`.
+pub const PASSTHROUGH_MAX_CALLS: usize = 16;
+
+/// Largest serialized `methodCalls` `/api/jmap` forwards.
+pub const PASSTHROUGH_MAX_BYTES: usize = 256 * 1024;
+
+/// Types whose reads `/api/jmap` forwards.
+const PASSTHROUGH_READ_TYPES: &[&str] = &[
+    "Email",
+    "Mailbox",
+    "Thread",
+    "SearchSnippet",
+    "Identity",
+    "EmailSubmission",
+    "SieveScript",
+];
+
+const PASSTHROUGH_READS: &[&str] = &["get", "query", "changes", "queryChanges"];
+
+/// The only writes `/api/jmap` forwards. Sending, identities and Sieve
+/// scripts stay behind their own routes, so the send guard, the undo
+/// window and the rules compiler can't be sidestepped. Within these,
+/// [`passthrough_write_allowed`] refuses what removes mail from where it
+/// is.
+const PASSTHROUGH_WRITES: &[&str] = &["Email/set", "Email/copy", "Email/import", "Mailbox/set"];
+
+/// `method` is a write `/api/jmap` allows.
+pub fn passthrough_writes(method: &str) -> bool {
+    PASSTHROUGH_WRITES.contains(&method)
+}
+
+/// Refuse the parts of a passthrough write that move or destroy existing
+/// mail: `Email/set` `destroy` and `mailboxIds` updates, `Email/copy`
+/// with `onSuccessDestroyOriginal`, and `Mailbox/set` with
+/// `onDestroyRemoveEmails`. Archiving, moving and deleting go through the
+/// email routes, which honour protected senders, the trash journal and
+/// the empty-folder confirmation. Creating mail and changing keywords
+/// pass.
+fn passthrough_write_allowed(
+    method: &str,
+    args: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), Error> {
+    let refused = |what: &str| {
+        Err(Error::BadRequest(format!(
+            "{method} {what} is not available through /api/jmap; use the email routes"
+        )))
+    };
+    let flag = |key: &str| args.get(key).is_some_and(|v| v.as_bool() == Some(true));
+    match method {
+        "Email/set" => {
+            if args.contains_key("destroy") || args.contains_key("#destroy") {
+                return refused("destroy");
+            }
+            let moves = args
+                .get("update")
+                .and_then(|u| u.as_object())
+                .is_some_and(|updates| {
+                    updates.values().any(|patch| {
+                        patch.as_object().is_none_or(|p| {
+                            p.keys()
+                                .any(|k| k == "mailboxIds" || k.starts_with("mailboxIds/"))
+                        })
+                    })
+                });
+            if moves || args.contains_key("#update") {
+                return refused("mailboxIds update");
+            }
+            Ok(())
+        }
+        "Email/copy" if flag("onSuccessDestroyOriginal") => refused("onSuccessDestroyOriginal"),
+        "Mailbox/set" if flag("onDestroyRemoveEmails") => refused("onDestroyRemoveEmails"),
+        _ => Ok(()),
+    }
+}
+
+/// Check `calls` (a JMAP `methodCalls` array) against the allowlist and
+/// size limits, and fill in `accountId` where a call leaves it out.
+pub fn prepare_passthrough(
+    mut calls: Vec<serde_json::Value>,
+    account_id: &str,
+    limits: &JmapLimits,
+) -> Result<Vec<serde_json::Value>, Error> {
+    let max_calls = PASSTHROUGH_MAX_CALLS.min(limits.max_calls_in_request);
+    if calls.is_empty() || calls.len() > max_calls {
+        return Err(Error::BadRequest(format!(
+            "methodCalls must hold 1 to {max_calls} calls"
+        )));
+    }
+    if serde_json::to_vec(&calls)?.len() > PASSTHROUGH_MAX_BYTES {
+        return Err(Error::BadRequest(format!(
+            "methodCalls is over {} KiB",
+            PASSTHROUGH_MAX_BYTES / 1024
+        )));
+    }
+    for call in &mut calls {
+        let well_formed = call.as_array().is_some_and(|c| c.len() == 3)
+            && call[1].is_object()
+            && call[2].is_string();
+        let Some(method) = call[0].as_str().filter(|_| well_formed).map(str::to_string) else {
+            return Err(Error::BadRequest(
+                "each method call must be [name, arguments, callId]".into(),
+            ));
+        };
+        let allowed = method == "Core/echo"
+            || method == "Email/parse"
+            || passthrough_writes(&method)
+            || method.split_once('/').is_some_and(|(kind, verb)| {
+                PASSTHROUGH_READ_TYPES.contains(&kind) && PASSTHROUGH_READS.contains(&verb)
+            });
+        if !allowed {
+            return Err(Error::BadRequest(format!(
+                "{method} is not available through /api/jmap"
+            )));
+        }
+        if let Some(args) = call[1].as_object() {
+            passthrough_write_allowed(&method, args)?;
+        }
+        if method != "Core/echo"
+            && let Some(args) = call[1].as_object_mut()
+        {
+            args.entry("accountId")
+                .or_insert_with(|| serde_json::json!(account_id));
+        }
+    }
+    Ok(calls)
+}
+
+/// Forward `calls` after [`prepare_passthrough`], returning the server's
+/// response as is.
+pub async fn passthrough(
+    s: &JmapSession,
+    calls: Vec<serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    let account_id = s.account_id.as_ref().ok_or(Error::NotConnected)?;
+    let calls = prepare_passthrough(calls, account_id, &s.limits)?;
+    jmap_call_once(s, calls).await
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deser_bs(json: serde_json::Value) -> BodyStructurePart {
+        serde_json::from_value(json).unwrap()
+    }
+
+    // --- request-limit chunking tests ---
+
+    fn small_limits() -> JmapLimits {
+        JmapLimits {
+            max_objects_in_get: 2,
+            max_objects_in_set: 2,
+            max_calls_in_request: 16,
+        }
+    }
+
+    #[test]
+    fn seen_updates_patch_only_the_seen_keyword() {
+        let updates = seen_updates(&["e1".into(), "e2".into()]);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates["e2"], serde_json::json!({ "keywords/$seen": true }));
+        // Large batches are chunked by jmap_call like any other /set.
+        let call = serde_json::json!(["Email/set", {"accountId": "a", "update": updates}, "0"]);
+        let ids: Vec<String> = (0..5).map(|i| format!("e{i}")).collect();
+        let big =
+            serde_json::json!(["Email/set", {"accountId": "a", "update": seen_updates(&ids)}, "0"]);
+        assert_eq!(split_method_call(&call, &small_limits()).len(), 1);
+        assert_eq!(split_method_call(&big, &small_limits()).len(), 3);
+    }
+
+    #[test]
+    fn limits_read_from_core_capability() {
+        let caps = serde_json::json!({
+            "urn:ietf:params:jmap:core": {
+                "maxObjectsInGet": 1000,
+                "maxObjectsInSet": 250,
+                "maxCallsInRequest": 32
+            }
+        });
+        let limits = JmapLimits::from_capabilities(&caps);
+        assert_eq!(limits.max_objects_in_get, 1000);
+        assert_eq!(limits.max_objects_in_set, 250);
+        assert_eq!(limits.max_calls_in_request, 32);
+    }
+
+    #[test]
+    fn limits_default_when_capability_missing_or_zero() {
+        let caps = serde_json::json!({ "urn:ietf:params:jmap:core": { "maxObjectsInSet": 0 } });
+        assert_eq!(JmapLimits::from_capabilities(&caps), JmapLimits::default());
+        assert_eq!(
+            JmapLimits::from_capabilities(&serde_json::Value::Null),
+            JmapLimits::default()
+        );
+    }
+
+    #[test]
+    fn passthrough_fills_the_account_and_keeps_reads() {
+        let calls = vec![
+            serde_json::json!(["Email/query", { "limit": 5 }, "q"]),
+            serde_json::json!(["Email/get", { "accountId": "other", "#ids": {} }, "g"]),
+            serde_json::json!(["Core/echo", { "hi": 1 }, "e"]),
+        ];
+        let out = prepare_passthrough(calls, "u1", &JmapLimits::default()).unwrap();
+        assert_eq!(out[0][1]["accountId"], "u1");
+        assert_eq!(out[1][1]["accountId"], "other");
+        assert!(out[2][1].get("accountId").is_none());
+    }
+
+    #[test]
+    fn passthrough_refuses_sends_and_oversized_requests() {
+        let limits = JmapLimits::default();
+        for method in [
+            "EmailSubmission/set",
+            "Identity/set",
+            "SieveScript/set",
+            "Blob/get",
+        ] {
+            let err =
+                prepare_passthrough(vec![serde_json::json!([method, {}, "0"])], "u1", &limits)
+                    .unwrap_err();
+            assert!(matches!(err, Error::BadRequest(_)), "{method}: {err:?}");
+        }
+        assert!(
+            prepare_passthrough(vec![serde_json::json!(["Email/get", {}])], "u1", &limits).is_err()
+        );
+        assert!(prepare_passthrough(vec![], "u1", &limits).is_err());
+        let many = vec![serde_json::json!(["Email/get", {}, "0"]); PASSTHROUGH_MAX_CALLS + 1];
+        assert!(prepare_passthrough(many, "u1", &limits).is_err());
+        let big = "x".repeat(PASSTHROUGH_MAX_BYTES);
+        let err = prepare_passthrough(
+            vec![serde_json::json!(["Email/set", { "create": { "a": { "subject": big } } }, "0"])],
+            "u1",
+            &limits,
+        );
+        assert!(err.is_err());
+        assert!(passthrough_writes("Mailbox/set") && !passthrough_writes("Email/get"));
+    }
+
+    #[test]
+    fn passthrough_refuses_moves_and_destroys() {
+        let limits = JmapLimits::default();
+        let run = |method: &str, args: serde_json::Value| {
+            prepare_passthrough(vec![serde_json::json!([method, args, "0"])], "u1", &limits)
+        };
+        for (method, args) in [
+            ("Email/set", serde_json::json!({ "destroy": ["e1"] })),
+            ("Email/set", serde_json::json!({ "#destroy": {} })),
+            (
+                "Email/set",
+                serde_json::json!({ "update": { "e1": { "mailboxIds": { "mb-trash": true } } } }),
+            ),
+            (
+                "Email/set",
+                serde_json::json!({ "update": { "e1": { "mailboxIds/mb-inbox": null } } }),
+            ),
+            (
+                "Email/copy",
+                serde_json::json!({ "fromAccountId": "u2", "onSuccessDestroyOriginal": true }),
+            ),
+            (
+                "Mailbox/set",
+                serde_json::json!({ "destroy": ["mb1"], "onDestroyRemoveEmails": true }),
+            ),
+        ] {
+            let err = run(method, args.clone()).unwrap_err();
+            assert!(
+                matches!(err, Error::BadRequest(_)),
+                "{method} {args}: {err:?}"
+            );
+        }
+        // Keywords, new mail and plain mailbox edits still pass.
+        for (method, args) in [
+            (
+                "Email/set",
+                serde_json::json!({ "update": { "e1": { "keywords/$flagged": true } } }),
+            ),
+            (
+                "Email/set",
+                serde_json::json!({ "create": { "d": { "mailboxIds": { "mb-drafts": true } } } }),
+            ),
+            ("Email/copy", serde_json::json!({ "fromAccountId": "u2" })),
+            ("Mailbox/set", serde_json::json!({ "destroy": ["mb1"] })),
+        ] {
+            assert!(run(method, args.clone()).is_ok(), "{method} {args}");
+        }
+    }
+
+    fn mail_only_session() -> JmapSession {
+        let mut s = JmapSession::new("me@example.com", "Bearer t");
+        s.capabilities = vec![CAPABILITY_CORE.into(), CAPABILITY_MAIL.into()];
+        s
+    }
+
+    #[test]
+    fn using_lists_only_what_the_calls_need() {
+        let s = JmapSession::new("me@example.com", "Bearer t");
+        let mail = [serde_json::json!(["Email/get", {}, "0"])];
+        assert_eq!(
+            using_for(&s, &mail).unwrap(),
+            vec![CAPABILITY_CORE, CAPABILITY_MAIL]
+        );
+        let send = [
+            serde_json::json!(["Email/set", {}, "0"]),
+            serde_json::json!(["EmailSubmission/set", {}, "1"]),
+        ];
+        assert_eq!(
+            using_for(&s, &send).unwrap(),
+            vec![CAPABILITY_CORE, CAPABILITY_MAIL, CAPABILITY_SUBMISSION]
+        );
+    }
+
+    #[test]
+    fn shared_accounts_are_the_other_mail_accounts() {
+        let session: JmapSessionResponse = serde_json::from_value(serde_json::json!({
+            "apiUrl": "https://api.example/jmap/api/",
+            "primaryAccounts": { "urn:ietf:params:jmap:mail": "u1" },
+            "accounts": {
+                "u1": { "name": "me@example.com", "isPersonal": true,
+                        "accountCapabilities": { "urn:ietf:params:jmap:mail": {} } },
+                "u3": { "name": "team@example.com", "isPersonal": false, "isReadOnly": true,
+                        "accountCapabilities": { "urn:ietf:params:jmap:mail": {} } },
+                "u2": { "name": "boss@example.com", "isPersonal": false,
+                        "accountCapabilities": { "urn:ietf:params:jmap:mail": {} } },
+                "c1": { "name": "Contacts only",
+                        "accountCapabilities": { "urn:ietf:params:jmap:contacts": {} } }
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            session.shared_accounts(),
+            vec![
+                SharedAccount {
+                    id: "u2".into(),
+                    name: "boss@example.com".into(),
+                    read_only: false,
+                },
+                SharedAccount {
+                    id: "u3".into(),
+                    name: "team@example.com".into(),
+                    read_only: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_account_sessions_share_the_login_and_limiter() {
+        let mut s = mail_only_session();
+        s.account_id = Some("u1".into());
+        s.identity_id = Some("I1".into());
+        let shared = s.for_shared_account("u2");
+        assert_eq!(shared.account_id.as_deref(), Some("u2"));
+        assert_eq!(shared.auth_header, s.auth_header);
+        assert!(std::sync::Arc::ptr_eq(&shared.limiter, &s.limiter));
+        assert_eq!(shared.identity_id, None);
+        assert!(shared.mailbox_cache.is_empty());
+    }
+
+    #[test]
+    fn using_rejects_calls_the_server_does_not_offer() {
+        let s = mail_only_session();
+        assert!(using_for(&s, &[serde_json::json!(["Mailbox/get", {}, "0"])]).is_ok());
+        let err = using_for(&s, &[serde_json::json!(["Identity/get", {}, "0"])]).unwrap_err();
+        assert!(matches!(err, Error::NotImplemented(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn send_without_submission_is_not_implemented() {
+        let mut s = mail_only_session();
+        s.account_id = Some("acct".into());
+        let err = send_email(&mut s, &simple_submission(), "me@example.com", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotImplemented(_)), "{err}");
+    }
+
+    #[test]
+    fn split_get_within_limit_is_untouched() {
+        let call = serde_json::json!(["Email/get", { "ids": ["a", "b"] }, "0"]);
+        assert_eq!(split_method_call(&call, &small_limits()), vec![call]);
+    }
+
+    #[test]
+    fn split_get_chunks_ids() {
+        let call =
+            serde_json::json!(["Email/get", { "accountId": "u1", "ids": ["a", "b", "c"] }, "0"]);
+        let chunks = split_method_call(&call, &small_limits());
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0][1]["ids"], serde_json::json!(["a", "b"]));
+        assert_eq!(chunks[1][1]["ids"], serde_json::json!(["c"]));
+        assert_eq!(chunks[1][1]["accountId"], "u1");
+    }
+
+    #[test]
+    fn mailbox_patch_sets_only_what_changes() {
+        assert_eq!(
+            serde_json::Value::Object(mailbox_patch(Some("Receipts"), None)),
+            serde_json::json!({ "name": "Receipts" })
+        );
+        assert_eq!(
+            serde_json::Value::Object(mailbox_patch(None, Some(None))),
+            serde_json::json!({ "parentId": null })
+        );
+        assert_eq!(
+            serde_json::Value::Object(mailbox_patch(Some("2026"), Some(Some("mb-1")))),
+            serde_json::json!({ "name": "2026", "parentId": "mb-1" })
+        );
+    }
+
+    #[test]
+    fn mailbox_set_errors_map_to_status() {
+        let err = |kind: &str| mailbox_set_error("destroy x", &serde_json::json!({ "type": kind }));
+        assert!(matches!(err("mailboxHasEmail"), Error::Conflict(_)));
+        assert!(matches!(err("notFound"), Error::NotFound(_)));
+        assert!(matches!(err("invalidProperties"), Error::BadRequest(_)));
+        assert!(matches!(err("serverFail"), Error::Internal(_)));
+    }
 
-    if !resp.status().is_success() {
-        return None;
+    #[test]
+    fn sieve_errors_map_to_status() {
+        let err = |kind: &str| sieve_set_error("update", &serde_json::json!({ "type": kind }));
+        assert!(matches!(err("invalidSieve"), Error::Unprocessable(_)));
+        assert!(matches!(err("notFound"), Error::NotFound(_)));
+        assert!(matches!(err("serverFail"), Error::Internal(_)));
+        assert!(matches!(
+            sieve_method_error(
+                "SieveScript/get",
+                &serde_json::json!({ "type": "accountNotSupportedByMethod" })
+            ),
+            Error::NotImplemented(_)
+        ));
+        assert_eq!(capability_for("SieveScript/set"), CAPABILITY_SIEVE);
     }
 
-    let ics_data = match resp.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            tracing::warn!("CalDAV GET body read failed for {uid}: {e}");
-            return None;
-        }
-    };
+    #[test]
+    fn keywords_patch_sets_and_clears() {
+        let patch = keywords_patch(&["acme".into()], &["old".into(), "acme".into()]);
+        assert_eq!(
+            patch,
+            serde_json::json!({ "keywords/acme": true, "keywords/old": null })
+        );
+    }
 
-    attendee_status_from_ics(&ics_data, attendee_email)
-}
+    #[test]
+    fn destroyed_count_treats_not_found_as_gone() {
+        let resp = serde_json::json!({
+            "destroyed": ["a", "b"],
+            "notDestroyed": {
+                "c": { "type": "notFound" },
+                "d": { "type": "forbidden" }
+            }
+        });
+        assert_eq!(destroyed_count(&resp), 3);
+        assert_eq!(destroyed_count(&serde_json::json!({})), 0);
+    }
 
-/// Parse ICS data and extract a specific attendee's PARTSTAT.
-fn attendee_status_from_ics(ics_data: &str, attendee_email: &str) -> Option<String> {
-    let event = crate::calendar::parse_ics(ics_data)?;
-    let email_lower = attendee_email.to_lowercase();
-    event
-        .attendees
-        .iter()
-        .find(|a| a.email.to_lowercase() == email_lower)
-        .map(|a| a.status.clone())
-}
+    #[test]
+    fn split_set_counts_update_and_destroy_together() {
+        let call = serde_json::json!([
+            "Email/set",
+            {
+                "accountId": "u1",
+                "update": { "a": { "keywords/$seen": true }, "b": { "keywords/$seen": true } },
+                "destroy": ["c"]
+            },
+            "0"
+        ]);
+        let chunks = split_method_call(&call, &small_limits());
+        assert_eq!(chunks.len(), 2);
+        let total: usize = chunks
+            .iter()
+            .map(|c| {
+                c[1]["update"].as_object().map_or(0, |m| m.len())
+                    + c[1]["destroy"].as_array().map_or(0, |a| a.len())
+            })
+            .sum();
+        assert_eq!(total, 3);
+        assert!(chunks.iter().all(|c| c[1]["accountId"] == "u1"));
+    }
 
-/// UUID v4 generation using /dev/urandom for proper randomness.
-#[cfg(test)]
-fn uuid_v4() -> String {
-    let mut buf = [0u8; 16];
-    // Read exactly 16 bytes from /dev/urandom
-    let ok = (|| -> Result<(), std::io::Error> {
-        use std::io::Read;
-        let mut f = std::fs::File::open("/dev/urandom")?;
-        f.read_exact(&mut buf)?;
-        Ok(())
-    })();
-    if ok.is_err() {
-        // Fallback: combine time + stack address + counter for entropy
-        use std::sync::atomic::{AtomicU64, Ordering};
-        use std::time::{SystemTime, UNIX_EPOCH};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        let t = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos();
-        let stack_addr = &buf as *const _ as u64;
-        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
-        let seed = t ^ (stack_addr as u128) ^ ((count as u128) << 64);
-        buf[..8].copy_from_slice(&(seed as u64).to_le_bytes());
-        buf[8..].copy_from_slice(&((seed >> 64) as u64).to_le_bytes());
+    #[test]
+    fn split_query_pages_by_position() {
+        let call = serde_json::json!(["Email/query", { "position": 10, "limit": 5 }, "0"]);
+        let chunks = split_method_call(&call, &small_limits());
+        let windows: Vec<(u64, u64)> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c[1]["position"].as_u64().unwrap(),
+                    c[1]["limit"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(windows, vec![(10, 2), (12, 2), (14, 1)]);
     }
-    // Set version (4) and variant (10xx) bits per RFC 4122
-    buf[6] = (buf[6] & 0x0F) | 0x40;
-    buf[8] = (buf[8] & 0x3F) | 0x80;
-    format!(
-        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
-        u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]),
-        u16::from_be_bytes([buf[4], buf[5]]),
-        u16::from_be_bytes([buf[6], buf[7]]),
-        u16::from_be_bytes([buf[8], buf[9]]),
-        u64::from_be_bytes([0, 0, buf[10], buf[11], buf[12], buf[13], buf[14], buf[15]]),
-    )
-}
 
-// =============================================================================
-// Tests
-// =============================================================================
+    #[test]
+    fn split_unknown_method_is_untouched() {
+        let call = serde_json::json!(["CalendarEvent/parse", { "blobIds": ["a", "b", "c"] }, "0"]);
+        assert_eq!(split_method_call(&call, &small_limits()).len(), 1);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn merge_concatenates_lists_and_unions_maps() {
+        let merged = merge_method_responses(vec![
+            serde_json::json!(["Email/set", { "newState": "s1", "updated": { "a": null } }, "0"]),
+            serde_json::json!(["Email/set", { "newState": "s2", "updated": { "b": null }, "notUpdated": { "c": {} } }, "1"]),
+        ]);
+        assert_eq!(merged[1]["updated"].as_object().unwrap().len(), 2);
+        assert!(merged[1]["notUpdated"]["c"].is_object());
+        assert_eq!(merged[1]["newState"], "s2");
+        assert_eq!(merged[2], "0");
+
+        let merged = merge_method_responses(vec![
+            serde_json::json!(["Email/get", { "list": [{ "id": "a" }], "state": "x" }, "0"]),
+            serde_json::json!(["Email/get", { "list": [{ "id": "b" }], "state": "x" }, "1"]),
+        ]);
+        assert_eq!(merged[1]["list"].as_array().unwrap().len(), 2);
+    }
 
-    fn deser_bs(json: serde_json::Value) -> BodyStructurePart {
-        serde_json::from_value(json).unwrap()
+    #[test]
+    fn merge_surfaces_first_error() {
+        let merged = merge_method_responses(vec![
+            serde_json::json!(["Email/set", { "updated": {} }, "0"]),
+            serde_json::json!(["error", { "type": "requestTooLarge" }, "1"]),
+        ]);
+        assert_eq!(merged[0], "error");
+        assert_eq!(merged[1]["type"], "requestTooLarge");
     }
 
-    // --- find_calendar_blob_id tests ---
+    // --- find_calendar_blob_ids tests ---
 
     #[test]
     fn detect_text_calendar_mime() {
@@ -1835,7 +3941,7 @@ mod tests {
             "type": "text/calendar",
             "blobId": "blob-cal-1"
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-cal-1".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-cal-1"]);
     }
 
     #[test]
@@ -1845,7 +3951,7 @@ mod tests {
             "name": "invite.ics",
             "blobId": "blob-cal-2"
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-cal-2".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-cal-2"]);
     }
 
     #[test]
@@ -1857,7 +3963,7 @@ mod tests {
                 { "type": "text/calendar", "blobId": "blob-cal-3" }
             ]
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-cal-3".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-cal-3"]);
     }
 
     #[test]
@@ -1869,18 +3975,18 @@ mod tests {
                 { "type": "text/html", "blobId": "blob-html" }
             ]
         }));
-        assert_eq!(find_calendar_blob_id(&body), None);
+        assert!(find_calendar_blob_ids(&body).is_empty());
     }
 
     #[test]
     fn null_body_returns_none() {
-        assert_eq!(find_calendar_blob_id(&BodyStructurePart::default()), None);
+        assert!(find_calendar_blob_ids(&BodyStructurePart::default()).is_empty());
     }
 
     #[test]
     fn empty_object_returns_none() {
         let body = deser_bs(serde_json::json!({}));
-        assert_eq!(find_calendar_blob_id(&body), None);
+        assert!(find_calendar_blob_ids(&body).is_empty());
     }
 
     #[test]
@@ -1889,7 +3995,7 @@ mod tests {
             "type": "text/calendar",
             "blobId": "blob-top"
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-top".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-top"]);
     }
 
     #[test]
@@ -1898,7 +4004,7 @@ mod tests {
             "type": "Text/Calendar",
             "blobId": "blob-case"
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-case".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-case"]);
     }
 
     #[test]
@@ -1908,7 +4014,35 @@ mod tests {
             "name": "Meeting.ICS",
             "blobId": "blob-case-file"
         }));
-        assert_eq!(find_calendar_blob_id(&body), Some("blob-case-file".into()));
+        assert_eq!(find_calendar_blob_ids(&body), vec!["blob-case-file"]);
+    }
+
+    #[test]
+    fn returns_every_calendar_part_in_order() {
+        let body = deser_bs(serde_json::json!({
+            "type": "multipart/mixed",
+            "subParts": [
+                { "type": "text/calendar", "blobId": "blob-reply" },
+                { "type": "application/ics", "name": "invite.ics", "blobId": "blob-invite" }
+            ]
+        }));
+        assert_eq!(
+            find_calendar_blob_ids(&body),
+            vec!["blob-reply", "blob-invite"]
+        );
+    }
+
+    #[test]
+    fn embedded_messages_are_listed_not_descended() {
+        let body = deser_bs(serde_json::json!({
+            "type": "multipart/mixed",
+            "subParts": [
+                { "type": "text/plain", "blobId": "blob-text" },
+                { "type": "message/rfc822", "blobId": "blob-fwd" }
+            ]
+        }));
+        assert!(find_calendar_blob_ids(&body).is_empty());
+        assert_eq!(find_embedded_message_blob_ids(&body), vec!["blob-fwd"]);
     }
 
     // --- find_attachments tests ---
@@ -2302,6 +4436,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         }
     }
 
@@ -2327,6 +4462,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts-456");
         let ids = draft.get("mailboxIds").expect("mailboxIds must be present");
@@ -2346,6 +4482,7 @@ END:VCALENDAR";
             references: Some(vec!["<msg-123@example.com>".into()]),
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts-789");
         assert!(draft.contains_key("mailboxIds"));
@@ -2389,6 +4526,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "a@b.com", "mb");
         assert_eq!(
@@ -2488,6 +4626,23 @@ END:VCALENDAR";
         );
     }
 
+    #[test]
+    fn send_draft_is_marked_and_the_sent_patch_clears_the_marks() {
+        let email = send_draft_email(&simple_submission(), "me@example.com", "drafts-id");
+        assert_eq!(
+            email["keywords"],
+            serde_json::json!({ "$draft": true, SENDING_KEYWORD: true })
+        );
+        let patch = sent_patch("drafts-id", "sent-id");
+        assert_eq!(patch["mailboxIds/drafts-id"], serde_json::Value::Null);
+        assert_eq!(patch["mailboxIds/sent-id"], serde_json::json!(true));
+        assert_eq!(patch["keywords/$draft"], serde_json::Value::Null);
+        assert_eq!(
+            patch[&format!("keywords/{SENDING_KEYWORD}")],
+            serde_json::Value::Null
+        );
+    }
+
     #[test]
     fn draft_create_request_has_no_submission() {
         // The distinguishing feature vs the send flow: no EmailSubmission,
@@ -2549,6 +4704,75 @@ END:VCALENDAR";
         );
     }
 
+    #[test]
+    fn thread_request_back_references_thread_email_ids() {
+        let calls = thread_request("acct-1", "T1");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0][0], "Thread/get");
+        assert_eq!(calls[0][1]["ids"], serde_json::json!(["T1"]));
+        assert_eq!(calls[1][0], "Email/get");
+        let args = &calls[1][1];
+        assert!(
+            args.get("ids").is_none(),
+            "ids come from the back-reference"
+        );
+        assert_eq!(
+            args["#ids"],
+            serde_json::json!({
+                "resultOf": calls[0][2],
+                "name": "Thread/get",
+                "path": "/list/*/emailIds",
+            })
+        );
+        assert_eq!(args["fetchHTMLBodyValues"], true);
+        assert!(
+            args["properties"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("bodyValues"))
+        );
+    }
+
+    #[test]
+    fn list_fetch_asks_for_part_types() {
+        let args = email_get_args("acct-1", false, None);
+        assert!(
+            args["properties"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("bodyStructure"))
+        );
+        assert_eq!(
+            args["bodyProperties"],
+            serde_json::json!(LIST_BODY_PROPERTIES)
+        );
+        assert_eq!(args["fetchTextBodyValues"], false);
+    }
+
+    #[tokio::test]
+    async fn metadata_only_fetch_still_marks_invites() {
+        let emails = parse_email_list_json(vec![metadata_only_invite()], false).await;
+        assert_eq!(emails.len(), 1);
+        assert!(emails[0].has_calendar);
+        assert!(emails[0].text_body.is_none());
+        assert!(emails[0].attachments.is_empty());
+    }
+
+    #[test]
+    fn drafts_list_request_skips_in_flight_sends() {
+        let calls = drafts_list_request("acct-1", "mb-drafts", 25);
+        assert_eq!(calls[0][0], "Email/query");
+        let query = &calls[0][1];
+        assert_eq!(query["limit"], 25);
+        let conditions = query["filter"]["conditions"].as_array().unwrap();
+        assert!(conditions.contains(&serde_json::json!({ "inMailbox": "mb-drafts" })));
+        assert!(conditions.contains(&serde_json::json!({ "hasKeyword": "$draft" })));
+        assert!(conditions.contains(&serde_json::json!({ "notKeyword": SENDING_KEYWORD })));
+        assert_eq!(calls[1][0], "Email/get");
+        assert_eq!(calls[1][1]["#ids"]["resultOf"], calls[0][2]);
+        assert_eq!(calls[1][1]["fetchTextBodyValues"], false);
+    }
+
     #[test]
     fn draft_destroy_request_targets_the_id() {
         let calls = draft_destroy_request("acct-1", "draft-xyz");
@@ -2689,6 +4913,22 @@ END:VCALENDAR";
         assert_eq!(null.in_reply_to, None);
     }
 
+    #[test]
+    fn parse_email_brackets_message_id_and_references() {
+        let email = parse_jmap_email(
+            &serde_json::json!({
+                "id": "e1",
+                "messageId": ["m2@example.com"],
+                "references": ["m0@example.com", "<m1@example.com>"],
+                "replyTo": [{ "name": "List", "email": "list@example.com" }]
+            }),
+            true,
+        );
+        assert_eq!(email.message_id.as_deref(), Some("<m2@example.com>"));
+        assert_eq!(email.references, ["<m0@example.com>", "<m1@example.com>"]);
+        assert_eq!(email.reply_to[0].email, "list@example.com");
+    }
+
     // --- parse_jmap_email tests (THE-153) ---
 
     #[test]
@@ -2747,6 +4987,40 @@ END:VCALENDAR";
         assert_eq!(email.html_body, Some("<p>Hello</p>".into()));
     }
 
+    #[test]
+    fn parse_records_body_part_sizes_and_truncation() {
+        let item = serde_json::json!({
+            "id": "email-big",
+            "textBody": [{"partId": "1", "blobId": "b1", "type": "text/plain", "size": 1200}],
+            "htmlBody": [{"partId": "2", "blobId": "b2", "type": "text/html", "charset": "iso-8859-1", "size": 3_000_000}],
+            "bodyValues": {
+                "1": {"value": "short"},
+                "2": {"value": "<p>cut", "isTruncated": true}
+            },
+            "bodyStructure": {"type": "multipart/alternative"}
+        });
+        let email = parse_jmap_email(&item, true);
+        assert_eq!(email.body_parts.len(), 2);
+        assert!(!email.body_parts[0].is_truncated);
+        let html = &email.body_parts[1];
+        assert_eq!(html.kind, "html");
+        assert_eq!(html.blob_id.as_deref(), Some("b2"));
+        assert_eq!(html.charset.as_deref(), Some("iso-8859-1"));
+        assert!(html.is_truncated);
+        assert!(email.is_truncated());
+        assert_eq!(email.body_size(), 3_001_200);
+    }
+
+    #[test]
+    fn parse_without_body_has_no_body_parts() {
+        let item = serde_json::json!({
+            "id": "email-x",
+            "textBody": [{"partId": "1", "type": "text/plain", "size": 10}],
+            "bodyValues": {"1": {"value": "hi"}}
+        });
+        assert!(parse_jmap_email(&item, false).body_parts.is_empty());
+    }
+
     #[test]
     fn parse_both_text_and_html_single_parts() {
         let item = serde_json::json!({
@@ -3042,6 +5316,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts");
         // RFC 8621: textBody/htmlBody must NOT appear when bodyStructure is set
@@ -3148,6 +5423,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: Some("BEGIN:VCALENDAR\r\nMETHOD:REPLY\r\nEND:VCALENDAR".into()),
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "bob@example.com", "mb-drafts");
         assert_eq!(
@@ -3186,6 +5462,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: Some(ics.into()),
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "bob@example.com", "mb-drafts");
         let body_values = draft["bodyValues"]
@@ -3209,6 +5486,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![],
             calendar_ics: Some("BEGIN:VCALENDAR\r\nEND:VCALENDAR".into()),
+            forward_of: None,
         };
         build_draft_email(&sub, "bob@example.com", "mb-drafts");
     }
@@ -3221,6 +5499,9 @@ END:VCALENDAR";
             name: "report.pdf".into(),
             mime_type: "application/pdf".into(),
             size: 12345,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
         }
     }
 
@@ -3237,6 +5518,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![pdf_attachment()],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts");
         assert_eq!(draft["bodyStructure"]["type"], "multipart/mixed");
@@ -3265,6 +5547,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![pdf_attachment()],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts");
         assert_eq!(draft["bodyStructure"]["type"], "multipart/mixed");
@@ -3280,6 +5563,42 @@ END:VCALENDAR";
         assert_eq!(parts[1]["blobId"], "blob-pdf-123");
     }
 
+    #[test]
+    fn draft_inline_image_sits_next_to_the_html() {
+        let logo = Attachment {
+            blob_id: "blob-logo".into(),
+            name: "logo.png".into(),
+            mime_type: "image/png".into(),
+            size: 120,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: Some("logo@x".into()),
+        };
+        let sub = EmailSubmission {
+            to: vec!["bob@example.com".into()],
+            cc: vec![],
+            subject: "Logo".into(),
+            text_body: "Hi".into(),
+            bcc: None,
+            html_body: Some("<p>Hi</p><img src=\"cid:logo@x\">".into()),
+            in_reply_to: None,
+            references: None,
+            attachments: vec![logo, pdf_attachment()],
+            calendar_ics: None,
+            forward_of: None,
+        };
+        let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts");
+        let parts = draft["bodyStructure"]["subParts"].as_array().unwrap();
+        assert_eq!(parts.len(), 2, "only the PDF is a separate attachment");
+        let alternative = parts[0]["subParts"].as_array().unwrap();
+        let related = &alternative[1];
+        assert_eq!(related["type"], "multipart/related");
+        assert_eq!(related["subParts"][0]["partId"], "html");
+        assert_eq!(related["subParts"][1]["cid"], "logo@x");
+        assert_eq!(related["subParts"][1]["disposition"], "inline");
+        assert_eq!(parts[1]["blobId"], "blob-pdf-123");
+    }
+
     #[test]
     fn draft_calendar_with_attachment_appends() {
         let sub = EmailSubmission {
@@ -3293,6 +5612,7 @@ END:VCALENDAR";
             references: None,
             attachments: vec![pdf_attachment()],
             calendar_ics: Some("BEGIN:VCALENDAR\r\nMETHOD:REPLY\r\nEND:VCALENDAR".into()),
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "bob@example.com", "mb-drafts");
         assert_eq!(draft["bodyStructure"]["type"], "multipart/mixed");
@@ -3324,15 +5644,22 @@ END:VCALENDAR";
                     name: "photo.jpg".into(),
                     mime_type: "image/jpeg".into(),
                     size: 54321,
+                    sniffed_mime_type: None,
+                    mime_mismatch: false,
+                    content_id: None,
                 },
                 Attachment {
                     blob_id: "blob-doc-789".into(),
                     name: "notes.txt".into(),
                     mime_type: "text/plain".into(),
                     size: 100,
+                    sniffed_mime_type: None,
+                    mime_mismatch: false,
+                    content_id: None,
                 },
             ],
             calendar_ics: None,
+            forward_of: None,
         };
         let draft = build_draft_email(&sub, "alice@example.com", "mb-drafts");
         assert_eq!(draft["bodyStructure"]["type"], "multipart/mixed");
@@ -3473,6 +5800,57 @@ END:VCALENDAR";
         assert_eq!(conditions.len(), 3);
     }
 
+    #[test]
+    fn jmap_filter_nests_or_and_not() {
+        let q = crate::search::parse_query("-from:bob (subject:invoice OR subject:receipt)");
+        assert_eq!(
+            to_jmap_filter(Some(&q), Some("inbox-id")),
+            serde_json::json!({
+                "operator": "AND",
+                "conditions": [
+                    {"inMailbox": "inbox-id"},
+                    {"operator": "NOT", "conditions": [{"from": "bob"}]},
+                    {"operator": "OR", "conditions": [
+                        {"subject": "invoice"},
+                        {"subject": "receipt"}
+                    ]}
+                ]
+            })
+        );
+        // An alternative that matches everything makes the OR a no-op.
+        let q = crate::search::parse_query("from:ann OR spamscore:>5");
+        assert_eq!(to_jmap_filter(Some(&q), None), serde_json::json!({}));
+    }
+
+    #[test]
+    fn jmap_filter_mailbox_terms() {
+        let mut q = crate::search::parse_query("in:archive -label:receipts");
+        q.mailboxes[0].id = Some("M7".into());
+        q.exclude[0].mailboxes[0].id = Some("M9".into());
+        assert_eq!(
+            to_jmap_filter(Some(&q), None),
+            serde_json::json!({
+                "operator": "AND",
+                "conditions": [
+                    {"inMailbox": "M7"},
+                    {"operator": "NOT", "conditions": [{"inMailbox": "M9"}]}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn jmap_filter_size_bounds() {
+        let q = crate::search::parse_query("larger:5M smaller:1G");
+        assert_eq!(
+            to_jmap_filter(Some(&q), None),
+            serde_json::json!({
+                "operator": "AND",
+                "conditions": [{"minSize": 5_242_881}, {"maxSize": 1_073_741_824u64}]
+            })
+        );
+    }
+
     #[test]
     fn jmap_filter_date_after() {
         let q = ParsedQuery {
@@ -3612,4 +5990,109 @@ END:VCALENDAR";
         assert_eq!(part.size, 0);
         assert!(part.sub_parts.is_empty());
     }
+
+    // --- Email/changes ---
+
+    #[test]
+    fn parse_email_changes_reads_jmap_response() {
+        let response = serde_json::json!(["Email/changes", {
+            "accountId": "u1",
+            "oldState": "s1",
+            "newState": "s2",
+            "hasMoreChanges": true,
+            "created": ["a"],
+            "updated": ["b", "c"],
+            "destroyed": []
+        }, "0"]);
+        let changes = parse_email_changes(&response).unwrap();
+        assert_eq!(changes.old_state, "s1");
+        assert_eq!(changes.new_state, "s2");
+        assert!(changes.has_more_changes);
+        assert_eq!(changes.created, ["a"]);
+        assert_eq!(changes.updated, ["b", "c"]);
+        assert!(changes.destroyed.is_empty());
+    }
+
+    #[test]
+    fn parse_email_changes_maps_method_errors() {
+        let too_old = serde_json::json!(["error", { "type": "cannotCalculateChanges" }, "0"]);
+        assert!(matches!(
+            parse_email_changes(&too_old),
+            Err(Error::Conflict(_))
+        ));
+        let bad = serde_json::json!(["error", { "type": "invalidArguments" }, "0"]);
+        assert!(matches!(
+            parse_email_changes(&bad),
+            Err(Error::BadRequest(_))
+        ));
+        let other = serde_json::json!(["error", { "type": "serverFail" }, "0"]);
+        assert!(matches!(
+            parse_email_changes(&other),
+            Err(Error::Internal(_))
+        ));
+    }
+
+    // --- headers ---
+
+    #[test]
+    fn parse_headers_response_keeps_order_and_unfolds() {
+        let get = serde_json::json!({
+            "list": [{ "id": "e1", "headers": [
+                { "name": "Received", "value": " from a\r\n by b" },
+                { "name": "Subject", "value": " Hi" },
+                { "name": "Received", "value": " from c" }
+            ]}],
+            "notFound": []
+        });
+        let headers = parse_headers_response(&get).unwrap();
+        let pairs: Vec<_> = headers
+            .iter()
+            .map(|h| (h.name.as_str(), h.value.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("Received", "from a by b"),
+                ("Subject", "Hi"),
+                ("Received", "from c")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_headers_response_missing_email_is_not_found() {
+        let get = serde_json::json!({ "list": [], "notFound": ["e1"] });
+        assert!(matches!(
+            parse_headers_response(&get),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn parse_search_page_reads_total_and_position() {
+        let query = serde_json::json!(["Email/query", {
+            "ids": ["e51", "e52"],
+            "position": 50,
+            "total": 3412,
+            "canCalculateChanges": true,
+            "queryState": "q9"
+        }, "0"]);
+        assert_eq!(
+            parse_search_page(&query).unwrap(),
+            SearchPage {
+                ids: vec!["e51".into(), "e52".into()],
+                position: 50,
+                total: Some(3412),
+                can_calculate_changes: true,
+                query_state: Some("q9".into()),
+            }
+        );
+        // A server that skips calculateTotal leaves the total unknown.
+        let bare = parse_search_page(&serde_json::json!(["Email/query", { "ids": [] }, "0"]));
+        let bare = bare.unwrap();
+        assert_eq!((bare.total, bare.can_calculate_changes), (None, false));
+        assert!(parse_search_page(&serde_json::json!(["Email/query", {}, "0"])).is_err());
+        let gone = serde_json::json!(["error", { "type": "anchorNotFound" }, "0"]);
+        assert!(matches!(parse_search_page(&gone), Err(Error::NotFound(_))));
+    }
 }
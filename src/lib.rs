@@ -1,20 +1,65 @@
 pub mod accounts;
+pub mod at_rest;
+pub mod authentication;
+pub mod blob_cache;
+pub mod cache;
 pub mod calendar;
+pub mod calendar_replies;
+pub mod commands;
+pub mod compose;
+pub mod compose_sessions;
+pub mod contacts;
+pub mod delivery;
+pub mod diagnostics;
+pub mod digest;
+pub mod downloads;
 pub mod error;
 pub mod glob;
 pub mod gmail;
+pub mod groups;
+pub mod http_client;
+pub mod identities;
+pub mod image_proxy;
 pub mod jmap;
+pub mod logs;
+pub mod mailbox_roles;
+pub mod mute;
+pub mod newsletters;
 pub mod oauth;
+pub mod otp;
 pub mod outlook;
+pub mod packages;
+pub mod perf;
 pub mod platform;
 pub mod prefetch;
+pub mod priority;
+pub mod protected;
 pub mod provider;
 pub mod provider_utils;
+pub mod purge;
 pub mod rate_limit;
+pub mod read_only;
+pub mod receipts;
+pub mod recently_deleted;
 pub mod routes;
+pub mod rsvp_log;
+pub mod rules;
+pub mod sanitize;
 pub mod search;
+pub mod send_guard;
+pub mod send_hours;
+pub mod sieve;
+pub mod signature_image;
+pub mod snooze;
 pub mod splits;
+pub mod storage;
+pub mod submission;
+pub mod template_vars;
 pub mod theme;
+pub mod thread_pdf;
 pub mod timezone;
 pub mod types;
+pub mod undo_send;
+pub mod unsubscribe;
+pub mod update;
 pub mod validate;
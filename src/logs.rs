@@ -0,0 +1,272 @@
+//! Live log tail for `GET /api/logs/tail`.
+//!
+//! [`TailLayer`] sits next to the console formatter in the tracing
+//! subscriber and copies every event into a process-wide ring of the last
+//! [`BACKLOG`] lines plus a broadcast channel, so the browser can follow
+//! a sync on a headless server without a shell. It captures this crate at
+//! `debug` and everything else at `info` whatever `RUST_LOG` says; the
+//! route filters down to the level asked for.
+//!
+//! Lines are [`redact`]ed before they're stored: the tail is reachable
+//! from any browser on the tailnet, and a debug line can carry a bearer
+//! token or an OAuth code.
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::Context;
+
+/// Lines kept for a new subscriber to start from.
+pub const BACKLOG: usize = 1000;
+
+/// Live lines buffered per subscriber before it's told it lagged.
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub at: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    /// The event's message followed by its other fields as `key=value`.
+    pub message: String,
+}
+
+impl LogLine {
+    /// Whether this line is at `level` or more severe.
+    pub fn at_least(&self, level: Level) -> bool {
+        self.level.parse::<Level>().is_ok_and(|own| own <= level)
+    }
+}
+
+pub struct LogTail {
+    recent: Mutex<VecDeque<LogLine>>,
+    tx: broadcast::Sender<LogLine>,
+}
+
+impl Default for LogTail {
+    fn default() -> Self {
+        Self {
+            recent: Mutex::new(VecDeque::with_capacity(BACKLOG)),
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+static TAIL: LazyLock<LogTail> = LazyLock::new(LogTail::default);
+
+impl LogTail {
+    /// The tail [`TailLayer`] feeds.
+    pub fn global() -> &'static LogTail {
+        &TAIL
+    }
+
+    pub fn push(&self, line: LogLine) {
+        let mut recent = self.lock();
+        if recent.len() == BACKLOG {
+            recent.pop_front();
+        }
+        recent.push_back(line.clone());
+        // Sent under the lock so `subscribe` sees each line exactly once.
+        let _ = self.tx.send(line);
+    }
+
+    /// The buffered lines, oldest first, and a receiver for every line
+    /// pushed after them.
+    pub fn subscribe(&self) -> (Vec<LogLine>, broadcast::Receiver<LogLine>) {
+        let recent = self.lock();
+        (recent.iter().cloned().collect(), self.tx.subscribe())
+    }
+
+    /// The buffered lines, oldest first.
+    pub fn recent(&self) -> Vec<LogLine> {
+        self.lock().iter().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<LogLine>> {
+        self.recent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// What [`TailLayer`] captures, independent of the console's `RUST_LOG`.
+pub fn capture_filter() -> Targets {
+    Targets::new()
+        .with_target("supervillain", LevelFilter::DEBUG)
+        .with_default(LevelFilter::INFO)
+}
+
+/// Tracing layer feeding [`LogTail::global`].
+pub struct TailLayer;
+
+impl<S: Subscriber> tracing_subscriber::Layer<S> for TailLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = FieldText::default();
+        event.record(&mut fields);
+        let meta = event.metadata();
+        LogTail::global().push(LogLine {
+            at: Utc::now(),
+            level: meta.level().to_string(),
+            target: meta.target().to_string(),
+            message: redact(&fields.finish()),
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldText {
+    message: String,
+    rest: String,
+}
+
+impl FieldText {
+    fn finish(self) -> String {
+        match (self.message.is_empty(), self.rest.is_empty()) {
+            (_, true) => self.message,
+            (true, false) => self.rest,
+            (false, false) => format!("{} {}", self.message, self.rest),
+        }
+    }
+
+    fn field(&mut self, name: &str, value: std::fmt::Arguments<'_>) {
+        if !self.rest.is_empty() {
+            self.rest.push(' ');
+        }
+        let _ = write!(self.rest, "{name}={value}");
+    }
+}
+
+impl Visit for FieldText {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.field(field.name(), format_args!("{value}"));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.field(field.name(), format_args!("{value:?}"));
+        }
+    }
+}
+
+/// `Authorization` header values; the scheme is kept.
+static AUTH_SCHEME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(bearer|basic)\s+[A-Za-z0-9._~+/=-]+").unwrap());
+static SECRET_PARAM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)\b(access_token|refresh_token|id_token|client_secret|api_?key|password|passphrase|secret|token)(["']?\s*[=:]\s*["']?)[^\s"'&,;]+"#,
+    )
+    .unwrap()
+});
+/// OAuth callback parameters; `code:` alone is too common to touch.
+static OAUTH_PARAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(code|state)=[^\s&\x22']+").unwrap());
+static JWT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\beyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*").unwrap());
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([A-Za-z0-9])[A-Za-z0-9._%+-]*@([A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)+)").unwrap()
+});
+
+/// `line` with credentials replaced by `[redacted]` and email addresses
+/// cut to their first letter and domain, which is usually enough to tell
+/// accounts apart.
+pub fn redact(line: &str) -> String {
+    let line = AUTH_SCHEME.replace_all(line, "$1 [redacted]");
+    let line = SECRET_PARAM.replace_all(&line, "$1$2[redacted]");
+    let line = OAUTH_PARAM.replace_all(&line, "$1=[redacted]");
+    let line = JWT.replace_all(&line, "[redacted]");
+    EMAIL.replace_all(&line, "$1***@$2").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: &str) -> LogLine {
+        LogLine {
+            at: Utc::now(),
+            level: level.into(),
+            target: "supervillain::jmap".into(),
+            message: level.into(),
+        }
+    }
+
+    #[test]
+    fn redacts_credentials_and_addresses() {
+        assert_eq!(
+            redact("GET /jmap Authorization: Bearer fmu1-abc.def"),
+            "GET /jmap Authorization: Bearer [redacted]"
+        );
+        assert_eq!(
+            redact(r#"refresh failed: {"refresh_token":"1//0abc","error":"invalid_grant"}"#),
+            r#"refresh failed: {"refresh_token":"[redacted]","error":"invalid_grant"}"#
+        );
+        assert_eq!(
+            redact("callback /callback?code=4/0Ab&state=xyz"),
+            "callback /callback?code=[redacted]&state=[redacted]"
+        );
+        assert_eq!(
+            redact("sync for alice.smith@example.co.uk done, status code: 500"),
+            "sync for a***@example.co.uk done, status code: 500"
+        );
+        assert_eq!(
+            redact("id eyJhbGciOi.eyJzdWIiOi.c2lnbmF0dXJl"),
+            "id [redacted]"
+        );
+    }
+
+    #[test]
+    fn subscribe_replays_the_backlog_then_follows() {
+        let tail = LogTail::default();
+        for i in 0..BACKLOG + 2 {
+            tail.push(LogLine {
+                message: i.to_string(),
+                ..line("INFO")
+            });
+        }
+        let (recent, mut rx) = tail.subscribe();
+        assert_eq!(recent.len(), BACKLOG);
+        assert_eq!(recent[0].message, "2");
+        tail.push(line("WARN"));
+        assert_eq!(rx.try_recv().unwrap().level, "WARN");
+    }
+
+    #[test]
+    fn level_filter_keeps_more_severe_lines() {
+        assert!(line("ERROR").at_least(Level::INFO));
+        assert!(line("INFO").at_least(Level::INFO));
+        assert!(!line("DEBUG").at_least(Level::INFO));
+        assert!(line("DEBUG").at_least(Level::DEBUG));
+    }
+
+    #[test]
+    fn layer_records_message_and_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+        let subscriber = tracing_subscriber::registry().with(TailLayer);
+        let (_, mut rx) = LogTail::global().subscribe();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(account = "work", count = 3, "sync slow for bob@example.com");
+        });
+        let line = loop {
+            let line = rx.try_recv().unwrap();
+            if line.message.starts_with("sync slow") {
+                break line;
+            }
+        };
+        assert_eq!(line.level, "WARN");
+        assert_eq!(
+            line.message,
+            "sync slow for b***@example.com account=work count=3"
+        );
+    }
+}
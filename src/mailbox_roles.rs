@@ -0,0 +1,209 @@
+//! Name-based role inference for mailboxes the server left without a
+//! `role`.
+//!
+//! Some JMAP accounts (typically migrated ones) have localized folders
+//! like "Papierkorb" or "Archiv" with no role set, so archive and trash
+//! find no target. When the JMAP session caches mailboxes by role
+//! (`jmap::cache_mailboxes`), each role still missing after the server's
+//! own roles is filled from the first top-level mailbox whose name matches
+//! an alias. Aliases are built in for common languages and can be
+//! extended or overridden in `mailbox-roles.json`:
+//!
+//! ```json
+//! { "aliases": { "Papierkorb": "trash", "Ablage": "archive" } }
+//! ```
+
+use crate::types::Mailbox;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Roles inference may assign. Anything else in the config is ignored.
+pub const ROLES: &[&str] = &["archive", "drafts", "junk", "sent", "trash"];
+
+/// Built-in aliases (name, role), matched case-insensitively.
+const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("archive", "archive"),
+    ("archiv", "archive"),
+    ("archives", "archive"),
+    ("archivio", "archive"),
+    ("archivo", "archive"),
+    ("arquivo", "archive"),
+    ("archief", "archive"),
+    ("trash", "trash"),
+    ("deleted items", "trash"),
+    ("deleted messages", "trash"),
+    ("papierkorb", "trash"),
+    ("gelöschte elemente", "trash"),
+    ("corbeille", "trash"),
+    ("éléments supprimés", "trash"),
+    ("papelera", "trash"),
+    ("cestino", "trash"),
+    ("lixeira", "trash"),
+    ("lixo", "trash"),
+    ("prullenbak", "trash"),
+    ("verwijderde items", "trash"),
+    ("sent", "sent"),
+    ("sent items", "sent"),
+    ("sent messages", "sent"),
+    ("gesendet", "sent"),
+    ("gesendete elemente", "sent"),
+    ("envoyés", "sent"),
+    ("éléments envoyés", "sent"),
+    ("enviados", "sent"),
+    ("inviati", "sent"),
+    ("posta inviata", "sent"),
+    ("verzonden", "sent"),
+    ("verzonden items", "sent"),
+    ("drafts", "drafts"),
+    ("entwürfe", "drafts"),
+    ("brouillons", "drafts"),
+    ("borradores", "drafts"),
+    ("rascunhos", "drafts"),
+    ("bozze", "drafts"),
+    ("concepten", "drafts"),
+    ("junk", "junk"),
+    ("spam", "junk"),
+    ("junk e-mail", "junk"),
+    ("junk email", "junk"),
+    ("courrier indésirable", "junk"),
+    ("correo no deseado", "junk"),
+    ("posta indesiderata", "junk"),
+    ("ongewenste e-mail", "junk"),
+];
+
+#[derive(Debug, Default, Deserialize)]
+struct RolesFile {
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/// Lowercased mailbox name → role.
+pub type RoleAliases = HashMap<String, String>;
+
+/// The built-in aliases plus any from `path`, which win on conflict.
+/// A missing or unparseable file leaves just the built-ins.
+pub fn load_aliases(path: &Path) -> RoleAliases {
+    let file: RolesFile = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| match serde_json::from_str(&s) {
+            Ok(f) => Some(f),
+            Err(e) => {
+                tracing::warn!("Ignoring {}: {e}", path.display());
+                None
+            }
+        })
+        .unwrap_or_default();
+    let mut aliases = default_aliases();
+    for (name, role) in file.aliases {
+        let role = role.to_lowercase();
+        if ROLES.contains(&role.as_str()) {
+            aliases.insert(name.trim().to_lowercase(), role);
+        } else {
+            tracing::warn!("Ignoring mailbox alias {name:?}: unknown role {role:?}");
+        }
+    }
+    aliases
+}
+
+pub fn default_aliases() -> RoleAliases {
+    DEFAULT_ALIASES
+        .iter()
+        .map(|(name, role)| (name.to_string(), role.to_string()))
+        .collect()
+}
+
+/// Role → mailbox: the server's roles first, then, for roles still
+/// missing, the first role-less top-level mailbox whose name is an alias.
+/// An inferred entry carries its role, so it reads like a server-assigned
+/// one. Nested folders are skipped so "Projects/Archive" can't be mistaken
+/// for the account's archive.
+pub fn by_role(mailboxes: &[Mailbox], aliases: &RoleAliases) -> HashMap<String, Mailbox> {
+    let mut found: HashMap<String, Mailbox> = mailboxes
+        .iter()
+        .filter_map(|mb| Some((mb.role.clone()?, mb.clone())))
+        .collect();
+    for mb in mailboxes {
+        if mb.role.is_some() || mb.parent_id.is_some() {
+            continue;
+        }
+        if let Some(role) = aliases.get(&mb.name.trim().to_lowercase())
+            && !found.contains_key(role)
+        {
+            tracing::info!(
+                "Using mailbox {:?} as {role} (inferred from its name)",
+                mb.name
+            );
+            found.insert(
+                role.clone(),
+                Mailbox {
+                    role: Some(role.clone()),
+                    ..mb.clone()
+                },
+            );
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailbox(id: &str, name: &str, role: Option<&str>, parent: Option<&str>) -> Mailbox {
+        Mailbox {
+            id: id.into(),
+            name: name.into(),
+            role: role.map(String::from),
+            total_emails: 0,
+            unread_emails: 0,
+            parent_id: parent.map(String::from),
+        }
+    }
+
+    #[test]
+    fn infers_missing_roles_from_localized_names() {
+        let mailboxes = vec![
+            mailbox("in", "Posteingang", Some("inbox"), None),
+            mailbox("t", "Papierkorb", None, None),
+            mailbox("a", "Archiv", None, None),
+        ];
+        let roles = by_role(&mailboxes, &default_aliases());
+        assert_eq!(roles["inbox"].id, "in");
+        assert_eq!(roles["trash"].id, "t");
+        assert_eq!(roles["trash"].role.as_deref(), Some("trash"));
+        assert_eq!(roles["archive"].id, "a");
+    }
+
+    #[test]
+    fn server_roles_win_and_nested_folders_are_skipped() {
+        let mailboxes = vec![
+            mailbox("nested", "Archive", None, Some("projects")),
+            mailbox("named", "Trash", None, None),
+            mailbox("real", "Bin", Some("trash"), None),
+        ];
+        let roles = by_role(&mailboxes, &default_aliases());
+        assert_eq!(roles["trash"].id, "real");
+        assert!(!roles.contains_key("archive"));
+    }
+
+    #[test]
+    fn config_adds_and_overrides_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mailbox-roles.json");
+        std::fs::write(
+            &path,
+            r#"{"aliases": {"Ablage": "Archive", "Archiv": "trash", "Misc": "bogus"}}"#,
+        )
+        .unwrap();
+        let aliases = load_aliases(&path);
+        assert_eq!(aliases["ablage"], "archive");
+        assert_eq!(aliases["archiv"], "trash");
+        assert!(!aliases.contains_key("misc"));
+        assert_eq!(aliases["papierkorb"], "trash");
+        assert_eq!(
+            load_aliases(&dir.path().join("missing.json")),
+            default_aliases()
+        );
+    }
+}
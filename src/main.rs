@@ -3,12 +3,14 @@ use std::sync::Arc;
 
 use supervillain::{
     accounts::{self, AccountConfig},
-    gmail, jmap, outlook, platform,
+    at_rest, blob_cache, cache, calendar_replies, contacts, digest, gmail, http_client, jmap,
+    mailbox_roles, mute, otp, outlook, packages, perf, platform,
     platform::{FsTokenStore, TokenStore},
     prefetch, provider,
     provider::ProviderSession,
-    routes, splits, timezone,
+    read_only, receipts, routes, rules, snooze, splits, storage, timezone,
     types::{AccountError, AccountRegistry, AppState, SessionLock},
+    undo_send, update,
 };
 
 #[tokio::main]
@@ -26,15 +28,62 @@ async fn main() {
     let tokens_dir = config_dir.join("supervillain/tokens");
     let splits_config_path = config_dir.join("supervillain/splits.json");
     let timezone_config_path = config_dir.join("supervillain/timezone.json");
+    let groups_config_path = config_dir.join("supervillain/groups.json");
+    let identities_config_path = config_dir.join("supervillain/identities.json");
+    let protected_senders_path = config_dir.join("supervillain/protected-senders.json");
+    let image_senders_path = config_dir.join("supervillain/image-senders.json");
+    let digest_config_path = config_dir.join("supervillain/digest.json");
+    let receipts_path = config_dir.join("supervillain/receipts.json");
+    let packages_config_path = config_dir.join("supervillain/packages.json");
+    let packages_path = config_dir.join("supervillain/packages-tracked.json");
+    let mailbox_roles_path = config_dir.join("supervillain/mailbox-roles.json");
+    let recently_deleted_path = config_dir.join("supervillain/recently-deleted.json");
+    let snoozed_path = config_dir.join("supervillain/snoozed.json");
+    let send_guard_path = config_dir.join("supervillain/send-guard.json");
+    let keybindings_path = config_dir.join("supervillain/keybindings.json");
+    let send_hours_path = config_dir.join("supervillain/send-hours.json");
+    let compose_sessions_path = config_dir.join("supervillain/compose-sessions.json");
+    let contacts_path = config_dir.join("supervillain/contacts.json");
     let prefetch_cache_path = config_dir.join("supervillain/prefetch-cache.json");
+    let email_cache_path = config_dir.join("supervillain/email-cache.sqlite");
 
     platform::init_tracing();
 
+    let at_rest = Arc::new(at_rest::AtRest::configure(
+        std::env::var("SUPERVILLAIN_ENCRYPT_CACHE")
+            .ok()
+            .as_deref()
+            .and_then(at_rest::Selection::parse),
+        || {
+            std::env::var("SUPERVILLAIN_CACHE_PASSPHRASE")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .or_else(|| platform::keyring_secret(at_rest::KEYRING_SERVICE))
+        },
+        &config_dir.join("supervillain/cache-key.json"),
+    ));
+    let blob_cache = blob_cache::BlobCache::new(
+        config_dir.join("supervillain/blob-cache"),
+        blob_cache_max_bytes(std::env::var("SUPERVILLAIN_BLOB_CACHE_MB").ok().as_deref()),
+    )
+    .with_at_rest(at_rest.clone());
+
+    let perf = if env_flag(
+        "SUPERVILLAIN_PERF",
+        std::env::var("SUPERVILLAIN_PERF").ok().as_deref(),
+    ) {
+        perf::PerfStats::open(config_dir.join("supervillain/perf.jsonl"))
+    } else {
+        perf::PerfStats::default()
+    };
+
     // Resolved once; the no-accounts hint and the listener bind below both
     // use it so they can't drift apart.
     let addr = bind_addr(std::env::var("SUPERVILLAIN_BIND").ok().as_deref());
 
     let (cfg, parse_errors) = accounts::parse_config(&config_path);
+    // Before any session connects, so every client gets the settings.
+    http_client::configure(cfg.http.clone());
     let token_store: Arc<dyn TokenStore> = Arc::new(FsTokenStore::new(tokens_dir.clone()));
 
     let mut sessions: HashMap<String, SessionLock> = HashMap::new();
@@ -45,14 +94,15 @@ async fn main() {
     let mut account_errors: Vec<AccountError> = accounts::startup_config_errors(
         &config_path,
         parse_errors.clone(),
-        &splits_config_path,
+        &splits::resolve_config_path(&splits_config_path),
         splits::try_load_splits(&splits_config_path),
         &timezone_config_path,
         timezone::try_load_config(&timezone_config_path),
     );
 
+    let role_aliases = mailbox_roles::load_aliases(&mailbox_roles_path);
     for (name, account) in &cfg.accounts {
-        match load_session(name, account, &tokens_dir, &token_store).await {
+        match load_session(name, account, &tokens_dir, &token_store, &role_aliases).await {
             Ok(session) => {
                 sessions.insert(
                     name.clone(),
@@ -106,11 +156,62 @@ async fn main() {
             sessions,
             account_configs: cfg.accounts.clone(),
             default_account,
+            default_reminder: cfg.default_reminder.clone(),
+            body_cache_mb: cfg.body_cache_mb,
+            read_only: cfg.read_only,
+            http: cfg.http.clone(),
+            startup: cfg.startup.clone(),
         }),
         account_errors: tokio::sync::RwLock::new(account_errors),
-        splits_config_path,
+        splits: splits::SplitsStore::new(
+            splits_config_path,
+            std::env::var("SUPERVILLAIN_SPLITS").ok(),
+        ),
         timezone_config_path,
+        groups_config_path,
+        identities_config_path,
+        protected_senders_path,
+        image_senders_path,
+        digest_config_path,
+        receipts_path,
+        packages_config_path,
+        packages_path,
+        mailbox_roles_path,
+        recently_deleted_path,
+        recently_deleted_lock: Default::default(),
+        snoozed_path,
+        snoozed_lock: Default::default(),
+        priority_lock: Default::default(),
+        newsletters_lock: Default::default(),
+        muted_lock: Default::default(),
+        empty_confirmations: Default::default(),
+        store: storage::open(
+            storage_backend(std::env::var("SUPERVILLAIN_STORAGE").ok().as_deref()),
+            &config_dir.join("supervillain"),
+        ),
+        perf,
+        signature_blobs: Default::default(),
+        update_check: update::UpdateCheck::new(env_flag(
+            "SUPERVILLAIN_UPDATE_CHECK",
+            std::env::var("SUPERVILLAIN_UPDATE_CHECK").ok().as_deref(),
+        )),
+        undo_send: undo_send::UndoSendQueue::new(undo_send_delay(
+            std::env::var("SUPERVILLAIN_UNDO_SEND_SECONDS")
+                .ok()
+                .as_deref(),
+        )),
+        send_guard_path,
+        keybindings_path,
+        send_hours_path,
+        compose_sessions_path,
+        compose_sessions: Default::default(),
+        blob_cache,
+        email_cache: cache::EmailCache::open(&email_cache_path).with_at_rest(at_rest.clone()),
+        contacts_path,
+        contacts_lock: Default::default(),
         timezone_write_lock: tokio::sync::Mutex::new(()),
+        calendar_reply_log: Default::default(),
+        otp: Default::default(),
         config_path,
         tokens_dir,
         token_store,
@@ -122,10 +223,23 @@ async fn main() {
         prefetch: std::sync::Arc::new(prefetch::PrefetchCache::load_from_disk(
             &prefetch_cache_path,
             &cfg.accounts.keys().cloned().collect::<Vec<_>>(),
+            &at_rest,
         )),
+        at_rest,
         prefetch_cache_path,
     });
 
+    *state
+        .calendar_reply_log
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = calendar_replies::load(&*state.store);
+
+    if let Some(mb) = cfg.body_cache_mb {
+        state
+            .prefetch
+            .set_body_cache_capacity((mb as usize).saturating_mul(1024 * 1024));
+    }
+
     // Kick off the background prefetch warmer. The first pass starts
     // ~200 ms after spawn (let the HTTP server bind first) and re-runs
     // every 5 minutes for every connected account, keeping the
@@ -134,7 +248,51 @@ async fn main() {
     // split-count requests.
     prefetch::spawn_warmer(state.clone(), std::time::Duration::from_secs(300));
 
-    let app = routes::router(state);
+    // Pick up splits edits made outside the app (editor, sync tool).
+    splits::spawn_watcher(state.clone(), std::time::Duration::from_secs(2));
+
+    // Daily digest: a no-op tick unless digest.json enables it.
+    digest::spawn_scheduler(state.clone(), std::time::Duration::from_secs(60));
+
+    // File receipts from recent inbox mail into receipts.json.
+    receipts::spawn_scanner(state.clone(), std::time::Duration::from_secs(15 * 60));
+
+    // Package status polling: a no-op tick unless packages.json enables it.
+    packages::spawn_poller(state.clone(), std::time::Duration::from_secs(60));
+
+    // Attendee replies in new Inbox mail, applied without opening them.
+    calendar_replies::spawn_watcher(state.clone(), std::time::Duration::from_secs(60));
+
+    // One-time codes from just-arrived mail, for /api/otp/latest and SSE.
+    otp::spawn_watcher(state.clone(), std::time::Duration::from_secs(10));
+
+    // CardDAV address books into contacts.json (Fastmail only).
+    contacts::spawn_sync(state.clone(), std::time::Duration::from_secs(15 * 60));
+
+    // Split actions on new inbox mail: a no-op tick unless a split has one.
+    rules::spawn_filer(state.clone(), std::time::Duration::from_secs(60));
+
+    // Latest GitHub release for /api/version, only with
+    // SUPERVILLAIN_UPDATE_CHECK on.
+    update::spawn_checker(state.clone(), std::time::Duration::from_secs(24 * 60 * 60));
+
+    // Snoozed mail back to the Inbox when its wake time passes.
+    snooze::spawn_waker(state.clone(), std::time::Duration::from_secs(60));
+
+    // New replies in muted threads out of the Inbox.
+    mute::spawn_archiver(state.clone(), std::time::Duration::from_secs(60));
+
+    // Drafts left in Drafts by sends that failed or were cut short.
+    tokio::spawn(sweep_orphaned_send_drafts(state.clone()));
+
+    let mut app = routes::router(state);
+    if let Some(min_bytes) = compress_min_bytes(
+        std::env::var("SUPERVILLAIN_COMPRESS_MIN_BYTES")
+            .ok()
+            .as_deref(),
+    ) {
+        app = app.layer(routes::compression_layer(min_bytes));
+    }
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap_or_else(|e| {
         panic!("Failed to bind to {addr}: {e}. Is another instance of supervillain already running? Try: kill $(lsof -ti :{port})", port = addr.split(':').next_back().unwrap_or("8000"));
@@ -150,6 +308,29 @@ async fn main() {
 }
 
 /// Bind address: `SUPERVILLAIN_BIND` env var, defaulting to loopback.
+/// Run [`provider::sweep_orphaned_send_drafts`] once for every connected
+/// account.
+async fn sweep_orphaned_send_drafts(state: Arc<AppState>) {
+    if read_only::enabled(&state).await {
+        return;
+    }
+    let sessions: Vec<(String, SessionLock)> = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .iter()
+            .map(|(name, lock)| (name.clone(), lock.clone()))
+            .collect()
+    };
+    for (name, lock) in sessions {
+        let session = lock.read().await;
+        match provider::sweep_orphaned_send_drafts(&session).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("[{name}] Destroyed {n} orphaned send drafts"),
+            Err(e) => tracing::warn!("[{name}] Orphaned draft sweep failed: {e}"),
+        }
+    }
+}
+
 /// Binding beyond loopback (e.g. `0.0.0.0:8000` for LAN/tailnet access,
 /// as scripts/upgrade.sh and the launcher do) is an explicit per-deploy
 /// opt-in — there is no authentication layer, so a non-loopback bind
@@ -161,6 +342,85 @@ fn bind_addr(env_value: Option<&str>) -> String {
     }
 }
 
+/// API JSON compression threshold from `SUPERVILLAIN_COMPRESS_MIN_BYTES`.
+/// Compression is opt-in: unset, blank, `off` or `0` leave it off; `on`
+/// uses the default threshold and a number sets it. Garbage leaves it off
+/// with a warning rather than refusing to start.
+fn compress_min_bytes(env_value: Option<&str>) -> Option<u16> {
+    match env_value.map(str::trim) {
+        None | Some("" | "off" | "0") => None,
+        Some("on") => Some(routes::DEFAULT_COMPRESS_MIN_BYTES),
+        Some(v) => match v.parse() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                tracing::warn!("ignoring invalid SUPERVILLAIN_COMPRESS_MIN_BYTES = {v}");
+                None
+            }
+        },
+    }
+}
+
+/// Attachment cache limit from `SUPERVILLAIN_BLOB_CACHE_MB`, in bytes.
+/// Unset or blank means the default; `off` (or `0`) disables the cache.
+/// Garbage falls back to the default with a warning.
+fn blob_cache_max_bytes(env_value: Option<&str>) -> u64 {
+    let mb = match env_value.map(str::trim) {
+        None | Some("") => blob_cache::DEFAULT_MAX_MB,
+        Some("off") => 0,
+        Some(v) => v.parse().unwrap_or_else(|_| {
+            tracing::warn!("ignoring invalid SUPERVILLAIN_BLOB_CACHE_MB = {v}");
+            blob_cache::DEFAULT_MAX_MB
+        }),
+    };
+    mb.saturating_mul(1024 * 1024)
+}
+
+/// Document store backend from `SUPERVILLAIN_STORAGE`: `file` (the
+/// default) or `sqlite`. Garbage falls back to files with a warning.
+fn storage_backend(env_value: Option<&str>) -> storage::Backend {
+    match env_value.map(str::trim) {
+        None | Some("" | "file") => storage::Backend::File,
+        Some("sqlite") => storage::Backend::Sqlite,
+        Some(v) => {
+            tracing::warn!("ignoring invalid SUPERVILLAIN_STORAGE = {v}");
+            storage::Backend::File
+        }
+    }
+}
+
+/// Undo-send window from `SUPERVILLAIN_UNDO_SEND_SECONDS`. Undo send is
+/// opt-in: unset, blank, `off` or `0` send immediately; `on` uses the
+/// default delay and a number sets it. Garbage, or more than the maximum,
+/// leaves it off with a warning.
+fn undo_send_delay(env_value: Option<&str>) -> std::time::Duration {
+    let secs = match env_value.map(str::trim) {
+        None | Some("" | "off") => 0,
+        Some("on") => undo_send::DEFAULT_DELAY_SECS,
+        Some(v) => match v.parse() {
+            Ok(n) if n <= undo_send::MAX_DELAY_SECS => n,
+            _ => {
+                tracing::warn!("ignoring invalid SUPERVILLAIN_UNDO_SEND_SECONDS = {v}");
+                0
+            }
+        },
+    };
+    std::time::Duration::from_secs(secs)
+}
+
+/// Whether an opt-in switch like `SUPERVILLAIN_PERF` is on. Off by
+/// default; `1`/`true`/`on` enable it, and anything else but blank or an
+/// explicit off value warns and stays off.
+fn env_flag(name: &str, env_value: Option<&str>) -> bool {
+    match env_value.map(str::trim) {
+        None | Some("" | "0" | "false" | "off") => false,
+        Some("1" | "true" | "on") => true,
+        Some(v) => {
+            tracing::warn!("ignoring invalid {name} = {v}");
+            false
+        }
+    }
+}
+
 /// Local UI URL for the auto-opened browser, derived from the bind
 /// address so the port is defined in exactly one place. A wildcard bind
 /// isn't routable in a URL; loopback always is.
@@ -199,6 +459,7 @@ async fn load_session(
     account: &AccountConfig,
     tokens_dir: &std::path::Path,
     token_store: &Arc<dyn TokenStore>,
+    role_aliases: &mailbox_roles::RoleAliases,
 ) -> Result<ProviderSession, AccountError> {
     // Fail fast on credentials that can't possibly work (e.g. a Fastmail
     // token pasted as an Azure client-id). Loading a session anyway would
@@ -218,6 +479,8 @@ async fn load_session(
             ..
         } => {
             let mut session = jmap::JmapSession::new(username, &format!("Bearer {api_token}"));
+            session.client = account.http_client();
+            session.role_aliases = role_aliases.clone();
             jmap::connect(&mut session)
                 .await
                 .map_err(|e| AccountError {
@@ -227,11 +490,7 @@ async fn load_session(
                 })?;
             match jmap::get_mailboxes(&session).await {
                 Ok(mailboxes) => {
-                    for mb in &mailboxes {
-                        if let Some(ref role) = mb.role {
-                            session.mailbox_cache.insert(role.clone(), mb.clone());
-                        }
-                    }
+                    jmap::cache_mailboxes(&mut session, &mailboxes);
                     tracing::info!(
                         "[{name}] Connected as {username}, {} mailboxes",
                         mailboxes.len()
@@ -248,7 +507,8 @@ async fn load_session(
 
         AccountConfig::Outlook { client_id, .. } => {
             let token_path = accounts::token_file_path(tokens_dir, name);
-            if let Some(s) = outlook::load_tokens(&token_path, client_id) {
+            if let Some(mut s) = outlook::load_tokens(&token_path, client_id) {
+                s.client = account.http_client();
                 tracing::info!("[{name}] Loaded Outlook tokens for {}", s.email);
                 Ok(ProviderSession::Outlook(Box::new(s)))
             } else {
@@ -265,9 +525,10 @@ async fn load_session(
             client_secret,
             ..
         } => {
-            if let Some(s) =
+            if let Some(mut s) =
                 gmail::load_session(token_store.clone(), name, client_id, client_secret)
             {
+                s.client = account.http_client();
                 tracing::info!("[{name}] Loaded Gmail tokens for {}", s.email);
                 Ok(ProviderSession::Gmail(Box::new(s)))
             } else {
@@ -315,6 +576,70 @@ mod tests {
         assert_eq!(resolve_default_account(String::new(), &sessions), "only");
     }
 
+    // ---- compress_min_bytes ----
+
+    #[test]
+    fn compress_min_bytes_is_off_unless_asked_for() {
+        assert_eq!(compress_min_bytes(None), None);
+        assert_eq!(compress_min_bytes(Some("  ")), None);
+        assert_eq!(compress_min_bytes(Some("lots")), None);
+        assert_eq!(compress_min_bytes(Some("off")), None);
+        assert_eq!(compress_min_bytes(Some("0")), None);
+    }
+
+    #[test]
+    fn compress_min_bytes_on_or_a_threshold_enables() {
+        assert_eq!(
+            compress_min_bytes(Some("on")),
+            Some(routes::DEFAULT_COMPRESS_MIN_BYTES)
+        );
+        assert_eq!(compress_min_bytes(Some("4096")), Some(4096));
+    }
+
+    // ---- blob_cache_max_bytes ----
+
+    #[test]
+    fn storage_backend_defaults_to_files() {
+        assert_eq!(storage_backend(None), storage::Backend::File);
+        assert_eq!(storage_backend(Some("file")), storage::Backend::File);
+        assert_eq!(storage_backend(Some(" sqlite ")), storage::Backend::Sqlite);
+        assert_eq!(storage_backend(Some("postgres")), storage::Backend::File);
+    }
+
+    #[test]
+    fn undo_send_delay_defaults_overrides_and_off() {
+        let secs = |v| undo_send_delay(v).as_secs();
+        assert_eq!(secs(None), 0);
+        assert_eq!(secs(Some("")), 0);
+        assert_eq!(secs(Some("on")), undo_send::DEFAULT_DELAY_SECS);
+        assert_eq!(secs(Some("30")), 30);
+        assert_eq!(secs(Some("off")), 0);
+        assert_eq!(secs(Some("0")), 0);
+        assert_eq!(secs(Some("9999")), 0);
+        assert_eq!(secs(Some("soon")), 0);
+    }
+
+    #[test]
+    fn env_flag_is_opt_in() {
+        let flag = |v| env_flag("SUPERVILLAIN_PERF", v);
+        assert!(!flag(None));
+        assert!(!flag(Some("")));
+        assert!(!flag(Some("off")));
+        assert!(!flag(Some("yes please")));
+        assert!(flag(Some("1")));
+        assert!(flag(Some(" true ")));
+    }
+
+    #[test]
+    fn blob_cache_max_bytes_defaults_overrides_and_off() {
+        let default = blob_cache::DEFAULT_MAX_MB * 1024 * 1024;
+        assert_eq!(blob_cache_max_bytes(None), default);
+        assert_eq!(blob_cache_max_bytes(Some("64")), 64 * 1024 * 1024);
+        assert_eq!(blob_cache_max_bytes(Some("huge")), default);
+        assert_eq!(blob_cache_max_bytes(Some("off")), 0);
+        assert_eq!(blob_cache_max_bytes(Some("0")), 0);
+    }
+
     // ---- bind_addr / browser_url (roborev 273) ----
 
     #[test]
@@ -0,0 +1,222 @@
+//! Muted conversations.
+//!
+//! `POST /api/threads/{id}/mute` archives what the thread has in the
+//! Inbox and records its id in the account's `muted` document in
+//! `storage`, up to [`MAX_MUTED_THREADS`]. Once a minute,
+//! [`spawn_archiver`] reads the newest [`ARCHIVER_SCAN`] Inbox messages
+//! of each account with mutes ([`in_muted_threads`]) and archives the ones
+//! in a muted thread. That costs one query and one metadata fetch per
+//! account, however many threads are muted. Protected senders are left
+//! in the Inbox, as on the other bulk archive paths. `DELETE` on the same
+//! route unmutes; mail already archived stays where it is. Fastmail only,
+//! like the thread view: Gmail and Outlook have no thread lookup here yet.
+
+use crate::error::Error;
+use crate::storage::{self, DocumentStore, Namespace};
+use crate::types::Email;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SUBSYSTEM: &str = "muted";
+const THREADS_KEY: &str = "threads";
+
+/// Threads muted per account at most; muting one more is refused.
+pub const MAX_MUTED_THREADS: usize = 1000;
+
+/// Newest Inbox messages the archiver looks at per account and tick.
+pub const ARCHIVER_SCAN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutedThread {
+    pub muted_at: DateTime<Utc>,
+    /// The conversation's subject when it was muted, for listing.
+    #[serde(default)]
+    pub subject: Option<String>,
+}
+
+/// One account's muted threads, by thread id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MutedThreads {
+    #[serde(default)]
+    pub threads: BTreeMap<String, MutedThread>,
+}
+
+impl MutedThreads {
+    /// Record `thread_id`, keeping the original mute time when it is
+    /// already muted. Refused once [`MAX_MUTED_THREADS`] are muted.
+    pub fn mute(
+        &mut self,
+        thread_id: &str,
+        subject: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        self.check_room(thread_id)?;
+        self.threads
+            .entry(thread_id.to_string())
+            .or_insert(MutedThread {
+                muted_at: now,
+                subject,
+            });
+        Ok(())
+    }
+
+    /// Whether `thread_id` can still be muted.
+    pub fn check_room(&self, thread_id: &str) -> Result<(), Error> {
+        if self.threads.len() >= MAX_MUTED_THREADS && !self.is_muted(thread_id) {
+            return Err(Error::BadRequest(format!(
+                "{MAX_MUTED_THREADS} threads are muted already; unmute some first"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns whether the thread was muted.
+    pub fn unmute(&mut self, thread_id: &str) -> bool {
+        self.threads.remove(thread_id).is_some()
+    }
+
+    pub fn is_muted(&self, thread_id: &str) -> bool {
+        self.threads.contains_key(thread_id)
+    }
+}
+
+/// Ids of `emails` (Inbox metadata) that belong to a muted thread.
+pub fn in_muted_threads(muted: &MutedThreads, emails: &[Email]) -> Vec<String> {
+    emails
+        .iter()
+        .filter(|e| muted.is_muted(&e.thread_id))
+        .map(|e| e.id.clone())
+        .collect()
+}
+
+pub fn load(store: &dyn DocumentStore, account: &str) -> MutedThreads {
+    storage::get_json(store, &Namespace::account(SUBSYSTEM, account), THREADS_KEY)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read muted threads for {account}: {e}");
+            None
+        })
+        .unwrap_or_default()
+}
+
+pub fn save(store: &dyn DocumentStore, account: &str, muted: &MutedThreads) -> Result<(), Error> {
+    storage::put_json(
+        store,
+        &Namespace::account(SUBSYSTEM, account),
+        THREADS_KEY,
+        muted,
+    )
+}
+
+/// Archive new Inbox mail in muted threads every `interval`; see the
+/// module docs.
+pub fn spawn_archiver(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("mute", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("mute");
+            if crate::read_only::enabled(&state).await {
+                continue;
+            }
+            let archived = crate::routes::archive_muted(&state).await;
+            if archived > 0 {
+                tracing::info!("Archived {archived} message(s) in muted threads");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, 9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn muting_again_keeps_the_first_time() {
+        let mut m = MutedThreads::default();
+        m.mute("T1", Some("Reply-all storm".into()), at(1)).unwrap();
+        m.mute("T1", None, at(2)).unwrap();
+        assert_eq!(m.threads["T1"].muted_at, at(1));
+        assert_eq!(m.threads["T1"].subject.as_deref(), Some("Reply-all storm"));
+        assert!(m.is_muted("T1"));
+        assert!(m.unmute("T1"));
+        assert!(!m.unmute("T1"));
+        assert!(!m.is_muted("T1"));
+    }
+
+    #[test]
+    fn muting_past_the_cap_is_refused() {
+        let mut m = MutedThreads::default();
+        for i in 0..MAX_MUTED_THREADS {
+            m.mute(&format!("T{i}"), None, at(2)).unwrap();
+        }
+        assert!(matches!(
+            m.mute("new", None, at(3)),
+            Err(Error::BadRequest(_))
+        ));
+        assert!(!m.is_muted("new"));
+        assert!(m.is_muted("T0"));
+        // Re-muting one that is already muted is still fine.
+        m.mute("T0", None, at(3)).unwrap();
+        assert_eq!(m.threads.len(), MAX_MUTED_THREADS);
+    }
+
+    #[test]
+    fn only_inbox_mail_in_muted_threads_is_picked() {
+        let mut m = MutedThreads::default();
+        m.mute("T1", None, at(1)).unwrap();
+        let email = |id: &str, thread: &str| Email {
+            id: id.into(),
+            blob_id: String::new(),
+            thread_id: thread.into(),
+            mailbox_ids: Default::default(),
+            keywords: Default::default(),
+            received_at: at(1),
+            subject: String::new(),
+            from: vec![],
+            to: vec![],
+            cc: vec![],
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        };
+        let inbox = [email("a", "T1"), email("b", "T2"), email("c", "T1")];
+        assert_eq!(in_muted_threads(&m, &inbox), ["a", "c"]);
+    }
+
+    #[test]
+    fn save_then_load_roundtrips_per_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::storage::FileStore::new(dir.path().to_path_buf());
+        let mut m = MutedThreads::default();
+        m.mute("T1", None, at(1)).unwrap();
+        save(&store, "work", &m).unwrap();
+        assert_eq!(load(&store, "work"), m);
+        assert_eq!(load(&store, "home"), MutedThreads::default());
+    }
+}
@@ -0,0 +1,285 @@
+//! Newsletter read rates, for unsubscribe suggestions.
+//!
+//! Every body fetch of a message carrying List-Unsubscribe passes through
+//! `get_email`: the adjacent-email prefetch (`mark_read=false`) when it
+//! arrives, the reading pane when I open it. Each one is noted per sender
+//! in the account's `newsletters` document in `storage`, with whether it
+//! was opened. A sender with plenty of mail and hardly any of it opened
+//! is an unsubscribe candidate ([`suggestions`]); each suggestion names
+//! its latest message so the client can hand it straight to
+//! `/api/emails/{id}/unsubscribe-and-archive-all`.
+
+use crate::error::Error;
+use crate::storage::{self, DocumentStore, Namespace};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const SUBSYSTEM: &str = "newsletters";
+const SENDERS_KEY: &str = "senders";
+
+/// Messages remembered per sender; older ones drop off.
+pub const MAX_MESSAGES_PER_SENDER: usize = 50;
+
+/// Senders remembered per account; the longest quiet go first.
+pub const MAX_SENDERS: usize = 1000;
+
+/// Defaults for `/api/suggestions/unsubscribe`.
+pub const DEFAULT_MIN_RECEIVED: usize = 5;
+pub const DEFAULT_MAX_READ_RATE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageActivity {
+    pub received_at: DateTime<Utc>,
+    pub opened: bool,
+}
+
+/// One sender's recent newsletter messages, by email id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderActivity {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub messages: BTreeMap<String, MessageActivity>,
+}
+
+impl SenderActivity {
+    fn last_received(&self) -> Option<(&String, DateTime<Utc>)> {
+        self.messages
+            .iter()
+            .map(|(id, m)| (id, m.received_at))
+            .max_by_key(|(_, at)| *at)
+    }
+}
+
+/// One account's newsletter senders, keyed by lowercased address.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewsletterActivity {
+    #[serde(default)]
+    pub senders: HashMap<String, SenderActivity>,
+}
+
+impl NewsletterActivity {
+    /// Note one message from `sender`. `opened` is sticky: a later
+    /// prefetch of a message already opened doesn't unmark it. Returns
+    /// false when the message was already noted in that state.
+    pub fn note(
+        &mut self,
+        sender: &str,
+        name: Option<&str>,
+        email_id: &str,
+        received_at: DateTime<Utc>,
+        opened: bool,
+    ) -> bool {
+        let entry = self
+            .senders
+            .entry(sender.trim().to_ascii_lowercase())
+            .or_default();
+        if let Some(message) = entry.messages.get_mut(email_id) {
+            if message.opened || !opened {
+                return false;
+            }
+            message.opened = true;
+            return true;
+        }
+        if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+            entry.name = Some(name.to_string());
+        }
+        entry.messages.insert(
+            email_id.to_string(),
+            MessageActivity {
+                received_at,
+                opened,
+            },
+        );
+        while entry.messages.len() > MAX_MESSAGES_PER_SENDER {
+            let Some(oldest) = entry
+                .messages
+                .iter()
+                .min_by_key(|(_, m)| m.received_at)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            entry.messages.remove(&oldest);
+        }
+        while self.senders.len() > MAX_SENDERS {
+            let Some(quietest) = self
+                .senders
+                .iter()
+                .min_by_key(|(_, s)| s.last_received().map(|(_, at)| at))
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            self.senders.remove(&quietest);
+        }
+        true
+    }
+
+    /// Drop `sender` (after an unsubscribe, so it stops being suggested).
+    pub fn forget(&mut self, sender: &str) -> bool {
+        self.senders
+            .remove(&sender.trim().to_ascii_lowercase())
+            .is_some()
+    }
+}
+
+/// An unsubscribe candidate and the numbers behind it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Suggestion {
+    pub sender: String,
+    pub name: Option<String>,
+    pub received: usize,
+    pub opened: usize,
+    pub read_rate: f64,
+    pub last_received: DateTime<Utc>,
+    /// Latest message from the sender, for unsubscribe-and-archive-all.
+    pub email_id: String,
+}
+
+/// Senders with at least `min_received` noted messages and at most
+/// `max_read_rate` of them opened, most mail first. `skip` drops senders
+/// that shouldn't be suggested (protected ones).
+pub fn suggestions(
+    activity: &NewsletterActivity,
+    min_received: usize,
+    max_read_rate: f64,
+    skip: impl Fn(&str) -> bool,
+) -> Vec<Suggestion> {
+    let mut out: Vec<Suggestion> = activity
+        .senders
+        .iter()
+        .filter(|(sender, _)| !skip(sender))
+        .filter_map(|(sender, s)| {
+            let received = s.messages.len();
+            if received == 0 || received < min_received {
+                return None;
+            }
+            let opened = s.messages.values().filter(|m| m.opened).count();
+            let read_rate = opened as f64 / received as f64;
+            if read_rate > max_read_rate {
+                return None;
+            }
+            let (email_id, last_received) = s.last_received()?;
+            Some(Suggestion {
+                sender: sender.clone(),
+                name: s.name.clone(),
+                received,
+                opened,
+                read_rate,
+                last_received,
+                email_id: email_id.clone(),
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.received
+            .cmp(&a.received)
+            .then(b.last_received.cmp(&a.last_received))
+    });
+    out
+}
+
+pub fn load(store: &dyn DocumentStore, account: &str) -> NewsletterActivity {
+    storage::get_json(store, &Namespace::account(SUBSYSTEM, account), SENDERS_KEY)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read newsletter activity for {account}: {e}");
+            None
+        })
+        .unwrap_or_default()
+}
+
+pub fn save(
+    store: &dyn DocumentStore,
+    account: &str,
+    activity: &NewsletterActivity,
+) -> Result<(), Error> {
+    storage::put_json(
+        store,
+        &Namespace::account(SUBSYSTEM, account),
+        SENDERS_KEY,
+        activity,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, 9, 0, 0).unwrap()
+    }
+
+    fn activity(sender: &str, opened: &[bool]) -> NewsletterActivity {
+        let mut a = NewsletterActivity::default();
+        for (i, o) in opened.iter().enumerate() {
+            a.note(
+                sender,
+                Some("Deals"),
+                &format!("m{i}"),
+                at(i as u32 + 1),
+                *o,
+            );
+        }
+        a
+    }
+
+    #[test]
+    fn unread_sender_is_suggested_with_latest_message() {
+        let a = activity("Deals@Shop.example", &[false, false, false, false, false]);
+        let found = suggestions(&a, 5, 0.1, |_| false);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].sender, "deals@shop.example");
+        assert_eq!(found[0].email_id, "m4");
+        assert_eq!(found[0].read_rate, 0.0);
+        assert_eq!(found[0].name.as_deref(), Some("Deals"));
+    }
+
+    #[test]
+    fn read_or_sparse_senders_are_not_suggested() {
+        let read = activity("a@x.example", &[true, false, true, false, false]);
+        assert!(suggestions(&read, 5, 0.1, |_| false).is_empty());
+        let sparse = activity("b@x.example", &[false, false]);
+        assert!(suggestions(&sparse, 5, 0.1, |_| false).is_empty());
+        let quiet = activity("c@x.example", &[false; 6]);
+        assert!(suggestions(&quiet, 5, 0.1, |s| s == "c@x.example").is_empty());
+    }
+
+    #[test]
+    fn opened_is_sticky_and_history_is_capped() {
+        let mut a = NewsletterActivity::default();
+        assert!(a.note("a@x.example", None, "m1", at(1), true));
+        assert!(!a.note("a@x.example", None, "m1", at(1), false));
+        assert!(a.senders["a@x.example"].messages["m1"].opened);
+
+        for i in 0..MAX_MESSAGES_PER_SENDER + 5 {
+            a.note(
+                "a@x.example",
+                None,
+                &format!("n{i}"),
+                at(2) + chrono::Duration::minutes(i as i64),
+                false,
+            );
+        }
+        let messages = &a.senders["a@x.example"].messages;
+        assert_eq!(messages.len(), MAX_MESSAGES_PER_SENDER);
+        assert!(!messages.contains_key("m1"));
+        assert!(a.forget("A@x.example"));
+        assert!(a.senders.is_empty());
+    }
+
+    #[test]
+    fn save_then_load_roundtrips_per_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::storage::FileStore::new(dir.path().to_path_buf());
+        let a = activity("a@x.example", &[false, true]);
+        save(&store, "work", &a).unwrap();
+        assert_eq!(load(&store, "work"), a);
+        assert_eq!(load(&store, "home"), NewsletterActivity::default());
+    }
+}
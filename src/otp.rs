@@ -0,0 +1,305 @@
+//! One-time code fast path.
+//!
+//! A watcher ([`spawn_watcher`]) checks each account's newest inbox mail
+//! every few seconds. For a message received in the last
+//! [`MAX_CODE_AGE`] that looks like a verification email, it pulls the code
+//! out of the subject or body. The latest code is kept in memory
+//! ([`OtpStore`]) for `GET /api/otp/latest` and pushed as an `otp` event
+//! on `/api/events`, so a 2FA prompt can be filled without opening the UI.
+//! Codes are never written to disk.
+
+use crate::types::Email;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+/// Codes from mail older than this are stale: not published, and no
+/// longer served by `/api/otp/latest`.
+pub const MAX_CODE_AGE: chrono::Duration = chrono::Duration::minutes(10);
+
+/// Newest inbox messages looked at per account per tick.
+const SCAN: usize = 10;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpCode {
+    pub code: String,
+    /// Sender address of the message the code came from.
+    pub sender: String,
+    pub account: String,
+    pub email_id: String,
+    pub received_at: DateTime<Utc>,
+}
+
+static CONTEXT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(verification|verify|one[- ]time|passcode|otp|2fa|two[- ]factor|security code|login code|sign[- ]in code|confirmation code|your code|code is|pin)\b",
+    )
+    .expect("valid regex")
+});
+
+/// 4–8 digits, or two groups of three split by a space or dash.
+static CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{3}[- ]\d{3}|\d{4,8})\b").expect("valid regex"));
+
+/// Whether `text` reads like a verification message at all.
+pub fn looks_like_otp(text: &str) -> bool {
+    CONTEXT.is_match(text)
+}
+
+/// The one-time code in `text`, which must read like a verification
+/// message. The first code within 80 bytes after a context phrase wins;
+/// failing that, the first six-digit code anywhere. Four-digit numbers
+/// that look like years are skipped.
+pub fn extract_code(text: &str) -> Option<String> {
+    if !looks_like_otp(text) {
+        return None;
+    }
+    let codes: Vec<regex::Match> = CODE
+        .find_iter(text)
+        .filter(|m| {
+            !(m.len() == 4 && (m.as_str().starts_with("19") || m.as_str().starts_with("20")))
+        })
+        .collect();
+    let near_context = CONTEXT.find_iter(text).find_map(|ctx| {
+        codes
+            .iter()
+            .find(|m| m.start() >= ctx.end() && m.start() - ctx.end() <= 80)
+    });
+    near_context
+        .or_else(|| codes.iter().find(|m| m.len() == 6))
+        .map(|m| m.as_str().replace([' ', '-'], ""))
+}
+
+/// Code for `email`, from the subject first, then the text body (or
+/// preview when the body wasn't fetched).
+pub fn code_for(email: &Email) -> Option<String> {
+    extract_code(&email.subject).or_else(|| {
+        let body = email.text_body.as_deref().unwrap_or(&email.preview);
+        extract_code(&format!("{}\n{body}", email.subject))
+    })
+}
+
+/// Latest code plus the broadcast that feeds `/api/events`.
+pub struct OtpStore {
+    latest: std::sync::Mutex<Option<OtpCode>>,
+    events: tokio::sync::broadcast::Sender<OtpCode>,
+}
+
+impl Default for OtpStore {
+    fn default() -> Self {
+        Self {
+            latest: Default::default(),
+            events: tokio::sync::broadcast::channel(16).0,
+        }
+    }
+}
+
+impl OtpStore {
+    /// Record `code` as the latest unless a newer one is already held, and
+    /// tell subscribers.
+    pub fn publish(&self, code: OtpCode) {
+        {
+            let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+            if latest
+                .as_ref()
+                .is_some_and(|l| l.received_at > code.received_at)
+            {
+                return;
+            }
+            *latest = Some(code.clone());
+        }
+        // No receivers is the normal idle case, not an error.
+        let _ = self.events.send(code);
+    }
+
+    /// The latest code still within [`MAX_CODE_AGE`] at `now`, optionally
+    /// limited to one account.
+    pub fn latest(&self, account: Option<&str>, now: DateTime<Utc>) -> Option<OtpCode> {
+        self.latest
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .filter(|c| now - c.received_at <= MAX_CODE_AGE)
+            .filter(|c| account.is_none_or(|a| a == c.account))
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<OtpCode> {
+        self.events.subscribe()
+    }
+}
+
+/// Check every connected account's newest inbox mail every `interval`.
+/// Each message is looked at once; only recent, code-bearing ones are
+/// body-fetched when the subject and preview don't already hold the code.
+pub fn spawn_watcher(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("otp", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut seen: HashMap<String, HashSet<String>> = HashMap::new();
+        loop {
+            ticker.tick().await;
+            health.ticked("otp");
+            let accounts: Vec<String> = {
+                let reg = state.accounts.read().await;
+                reg.sessions.keys().cloned().collect()
+            };
+            for account in accounts {
+                let seen = seen.entry(account.clone()).or_default();
+                match scan_account(&state, &account, seen).await {
+                    Ok(codes) => {
+                        for code in codes {
+                            tracing::info!("One-time code from {} on {account}", code.sender);
+                            state.otp.publish(code);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!("OTP scan failed for {account}: {e}");
+                        health.failed("otp", format!("{account}: {e}"));
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn scan_account(
+    state: &crate::types::AppState,
+    account: &str,
+    seen: &mut HashSet<String>,
+) -> Result<Vec<OtpCode>, crate::error::Error> {
+    use crate::provider;
+
+    let session_lock = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .get(account)
+            .cloned()
+            .ok_or(crate::error::Error::NotConnected)?
+    };
+    let session = session_lock.read().await;
+    let inbox = provider::get_mailboxes(&session)
+        .await?
+        .into_iter()
+        .find(|m| m.role.as_deref() == Some("inbox"))
+        .ok_or_else(|| crate::error::Error::NotFound("No inbox mailbox".into()))?;
+    let ids = provider::query_emails(
+        &session,
+        Some(&inbox.id),
+        SCAN,
+        0,
+        None,
+        crate::types::EmailSort::default(),
+    )
+    .await?;
+    let unseen: Vec<String> = ids
+        .iter()
+        .filter(|id| !seen.contains(*id))
+        .cloned()
+        .collect();
+    // Only the current window is remembered, so the set stays at SCAN ids.
+    *seen = ids.into_iter().collect();
+    if unseen.is_empty() {
+        return Ok(Vec::new());
+    }
+    let now = Utc::now();
+    let emails = provider::get_emails(&session, &unseen, false, None, false).await?;
+
+    let mut codes = Vec::new();
+    for email in emails {
+        if now - email.received_at > MAX_CODE_AGE
+            || !looks_like_otp(&format!("{} {}", email.subject, email.preview))
+        {
+            continue;
+        }
+        let code = match code_for(&email) {
+            Some(code) => Some(code),
+            None => {
+                provider::get_emails(&session, std::slice::from_ref(&email.id), true, None, false)
+                    .await?
+                    .first()
+                    .and_then(code_for)
+            }
+        };
+        if let Some(code) = code {
+            codes.push(OtpCode {
+                code,
+                sender: email
+                    .from
+                    .first()
+                    .map(|a| a.email.clone())
+                    .unwrap_or_default(),
+                account: account.to_string(),
+                email_id: email.id,
+                received_at: email.received_at,
+            });
+        }
+    }
+    codes.sort_by_key(|c| c.received_at);
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(account: &str, minutes_ago: i64, value: &str) -> OtpCode {
+        OtpCode {
+            code: value.into(),
+            sender: "no-reply@bank.example".into(),
+            account: account.into(),
+            email_id: "m1".into(),
+            received_at: Utc::now() - chrono::Duration::minutes(minutes_ago),
+        }
+    }
+
+    #[test]
+    fn extracts_code_near_context() {
+        assert_eq!(
+            extract_code("Your verification code is 482913. It expires in 10 minutes."),
+            Some("482913".into())
+        );
+        assert_eq!(
+            extract_code("Order 2024 update: your login code: 123-456"),
+            Some("123456".into())
+        );
+        assert_eq!(
+            extract_code("G-774201 is your Google verification code"),
+            Some("774201".into())
+        );
+    }
+
+    #[test]
+    fn ignores_numbers_without_context() {
+        assert_eq!(extract_code("Meeting moved to room 4821"), None);
+        assert_eq!(
+            extract_code("Your verification is pending since 2026"),
+            None
+        );
+    }
+
+    #[test]
+    fn store_keeps_newest_and_expires() {
+        let store = OtpStore::default();
+        let mut rx = store.subscribe();
+        store.publish(code("a", 1, "111111"));
+        store.publish(code("a", 5, "222222"));
+        let now = Utc::now();
+        assert_eq!(store.latest(None, now).unwrap().code, "111111");
+        assert_eq!(rx.try_recv().unwrap().code, "111111");
+        assert!(
+            rx.try_recv().is_err(),
+            "an older code must not be broadcast"
+        );
+        assert!(store.latest(Some("b"), now).is_none());
+        assert!(store.latest(None, now + MAX_CODE_AGE).is_none());
+    }
+}
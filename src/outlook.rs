@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use crate::error::Error;
 use crate::provider_utils::{MAX_BLOB_BYTES, MAX_UPLOAD_CACHE_BYTES, UPLOAD_CACHE_CAP};
 use crate::rate_limit::RateLimiter;
-use crate::types::{CalendarEvent, EmailSort, Mailbox};
+use crate::types::{CalendarEvent, EmailHeader, EmailSort, Mailbox};
 
 // =============================================================================
 // Outlook Session
@@ -261,10 +261,7 @@ pub fn load_tokens(token_path: &std::path::Path, client_id: &str) -> Option<Outl
     let content = std::fs::read_to_string(token_path).ok()?;
     let stored: StoredTokens = serde_json::from_str(&content).ok()?;
     Some(OutlookSession {
-        client: reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .expect("failed to create HTTP client"),
+        client: crate::http_client::client(),
         token: tokio::sync::Mutex::new(OutlookToken {
             access_token: stored.access_token,
             refresh_token: stored.refresh_token,
@@ -295,10 +292,7 @@ pub async fn oauth_flow(
 
     let callback = crate::platform::acquire_oauth_callback(&url, &expected_state, 8400).await?;
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .expect("failed to create HTTP client");
+    let client = crate::http_client::client();
 
     let token_resp = exchange_code(&client, client_id, &callback.code, &code_verifier).await?;
 
@@ -466,8 +460,35 @@ pub(crate) struct OdataQuery {
 ///   search; subject is well-supported there but rejected in `$filter` on
 ///   many tenants). Wrapped in `"…"`; inner `"` → `\"`, `\` → `\\`.
 pub(crate) fn translate_query_to_odata(q: &crate::types::ParsedQuery) -> OdataQuery {
+    let OdataParts {
+        filter: filter_parts,
+        search: search_parts,
+        ..
+    } = odata_parts(q);
+
+    let filter = (!filter_parts.is_empty()).then(|| filter_parts.join(" and "));
+    let search = (!search_parts.is_empty()).then(|| {
+        let joined = search_parts.join(" ");
+        format!("\"{}\"", escape_search_string(&joined))
+    });
+
+    OdataQuery { filter, search }
+}
+
+/// A query's `$filter` clauses and `$search` terms before joining.
+/// `mixed` marks an OR or NOT that spans both: Graph can't combine
+/// `$filter` and `$search` under one operator, so such a query is refused
+/// (see `query_emails`) rather than silently loosened.
+struct OdataParts {
+    filter: Vec<String>,
+    search: Vec<String>,
+    mixed: bool,
+}
+
+fn odata_parts(q: &crate::types::ParsedQuery) -> OdataParts {
     let mut filter_parts: Vec<String> = Vec::new();
     let mut search_parts: Vec<String> = Vec::new();
+    let mut mixed = false;
 
     if let Some(true) = q.is_unread {
         filter_parts.push("isRead eq false".into());
@@ -504,6 +525,9 @@ pub(crate) fn translate_query_to_odata(q: &crate::types::ParsedQuery) -> OdataQu
             d.format("%Y-%m-%d")
         ));
     }
+    for id in q.mailboxes.iter().filter_map(|m| m.id.as_ref()) {
+        filter_parts.push(format!("parentFolderId eq '{}'", escape_odata_literal(id)));
+    }
 
     // Subject and free text both flow into $search. Subject gets KQL prefix.
     // Roborev 179 #6: multi-word subject values must be wrapped in KQL
@@ -519,14 +543,58 @@ pub(crate) fn translate_query_to_odata(q: &crate::types::ParsedQuery) -> OdataQu
     if !q.text.is_empty() {
         search_parts.push(q.text.clone());
     }
+    // Graph can't $filter messages on size; KQL can.
+    if let Some(larger) = q.larger {
+        search_parts.push(format!("size>{larger}"));
+    }
+    if let Some(smaller) = q.smaller {
+        search_parts.push(format!("size<{smaller}"));
+    }
 
-    let filter = (!filter_parts.is_empty()).then(|| filter_parts.join(" and "));
-    let search = (!search_parts.is_empty()).then(|| {
-        let joined = search_parts.join(" ");
-        format!("\"{}\"", escape_search_string(&joined))
-    });
+    // Exclusions and OR groups: OData `not`/`or` in $filter, KQL `NOT`/`OR`
+    // in $search.
+    for excluded in &q.exclude {
+        let sub = odata_parts(excluded);
+        mixed |= sub.mixed;
+        match (sub.filter.is_empty(), sub.search.is_empty()) {
+            (false, true) => filter_parts.push(format!("not ({})", sub.filter.join(" and "))),
+            (true, false) => search_parts.push(format!("NOT ({})", sub.search.join(" "))),
+            (true, true) => {}
+            (false, false) => mixed = true,
+        }
+    }
+    for group in &q.any_of {
+        let subs: Vec<OdataParts> = group.iter().map(odata_parts).collect();
+        mixed |= subs.iter().any(|sub| sub.mixed);
+        // An empty alternative matches everything, and so does the group.
+        if subs
+            .iter()
+            .any(|sub| sub.filter.is_empty() && sub.search.is_empty())
+        {
+            continue;
+        }
+        if subs.iter().all(|sub| sub.search.is_empty()) {
+            let alternatives: Vec<String> = subs
+                .iter()
+                .map(|sub| format!("({})", sub.filter.join(" and ")))
+                .collect();
+            filter_parts.push(format!("({})", alternatives.join(" or ")));
+        } else if subs.iter().all(|sub| sub.filter.is_empty()) {
+            let alternatives: Vec<String> = subs
+                .iter()
+                .map(|sub| format!("({})", sub.search.join(" ")))
+                .collect();
+            search_parts.push(format!("({})", alternatives.join(" OR ")));
+        } else {
+            mixed = true;
+        }
+    }
 
-    OdataQuery { filter, search }
+    OdataParts {
+        filter: filter_parts,
+        search: search_parts,
+        mixed,
+    }
 }
 
 /// OData single-quote-doubling for string literals inside `$filter`.
@@ -595,6 +663,20 @@ pub(crate) fn parse_graph_message(
 
     let size = json["sizeEstimate"].as_i64().unwrap_or(0);
 
+    // Graph pre-parses List-Unsubscribe into `unsubscribeData` (one URI per
+    // entry); re-wrap it in header form so every provider reports the same
+    // shape.
+    let list_unsubscribe = json["unsubscribeData"]
+        .as_array()
+        .map(|uris| {
+            uris.iter()
+                .filter_map(|u| u.as_str())
+                .map(|u| format!("<{u}>"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .filter(|h| fetch_body && !h.is_empty());
+
     crate::types::Email {
         id: id.clone(),
         // Outlook doesn't have a separate blob namespace — use the message
@@ -618,6 +700,14 @@ pub(crate) fn parse_graph_message(
         // Drafts (the only consumer) are Fastmail-only in v1 — not read from
         // Graph's internetMessageHeaders yet.
         in_reply_to: None,
+        body_parts: Vec::new(),
+        list_unsubscribe,
+        message_id: json["internetMessageId"]
+            .as_str()
+            .and_then(|id| crate::provider_utils::normalize_message_id(id).ok()),
+        references: Vec::new(),
+        reply_to: parse_graph_recipient_list(&json["replyTo"]),
+        spam: None,
     }
 }
 
@@ -723,6 +813,9 @@ fn parse_graph_attachments(
                         name,
                         mime_type,
                         size,
+                        sniffed_mime_type: None,
+                        mime_mismatch: false,
+                        content_id: None,
                     })
                 })
                 .collect()
@@ -1279,6 +1372,13 @@ pub async fn query_emails(
     query: Option<&crate::types::ParsedQuery>,
     sort: EmailSort,
 ) -> Result<Vec<String>, Error> {
+    if query.is_some_and(|q| odata_parts(q).mixed) {
+        return Err(Error::BadRequest(
+            "Outlook can't combine OR or NOT across text and from:/to:/is:/has:/date \
+             operators; split the search"
+                .into(),
+        ));
+    }
     let token = access_token(session).await?;
     let odata = query.map(translate_query_to_odata).unwrap_or_default();
 
@@ -1338,6 +1438,99 @@ pub async fn query_emails(
     Ok(ids)
 }
 
+/// Find the message whose Message-ID header is `message_id` (normalized,
+/// `<…>` form) by filtering on Graph's `internetMessageId`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageHeadersResp {
+    /// Graph only has these for mail received over the internet; sent
+    /// items and drafts come back with none.
+    #[serde(default)]
+    internet_message_headers: Option<Vec<EmailHeader>>,
+}
+
+/// Every header field of one message, in message order
+/// (`internetMessageHeaders`).
+pub async fn get_headers(
+    session: &OutlookSession,
+    email_id: &str,
+) -> Result<Vec<EmailHeader>, Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(email_id);
+    let url = format!("{GRAPH_BASE}/me/messages/{encoded}?$select=internetMessageHeaders");
+    let resp = session
+        .limiter
+        .execute("messages.get", || async {
+            session.client.get(&url).bearer_auth(&token).send().await
+        })
+        .await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound("Email not found".into()));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_outlook_error(
+            &format!("messages.get {email_id}"),
+            status,
+            &text,
+        ));
+    }
+    let parsed: MessageHeadersResp = resp.json().await?;
+    Ok(parsed.internet_message_headers.unwrap_or_default())
+}
+
+/// `/me/messages/{id}/$value` serves the message as MIME.
+pub async fn get_raw_message(session: &OutlookSession, email_id: &str) -> Result<Vec<u8>, Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(email_id);
+    let url = format!("{GRAPH_BASE}/me/messages/{encoded}/$value");
+    let resp = session
+        .limiter
+        .execute("messages.get", || async {
+            session.client.get(&url).bearer_auth(&token).send().await
+        })
+        .await?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound("Email not found".into()));
+    }
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_outlook_error(
+            &format!("messages.get/$value {email_id}"),
+            status,
+            &text,
+        ));
+    }
+    Ok(resp.bytes().await?.to_vec())
+}
+
+pub async fn find_by_message_id(
+    session: &OutlookSession,
+    message_id: &str,
+) -> Result<Option<String>, Error> {
+    let token = access_token(session).await?;
+    let mut url = url::Url::parse(&format!("{GRAPH_BASE}/me/messages")).expect("valid Graph URL");
+    url.query_pairs_mut()
+        .append_pair("$top", "1")
+        .append_pair("$select", "id")
+        // normalize_message_id already rejects quotes; doubling is the
+        // OData escape should that ever loosen.
+        .append_pair(
+            "$filter",
+            &format!("internetMessageId eq '{}'", message_id.replace('\'', "''")),
+        );
+    let resp = session.client.get(url).bearer_auth(&token).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_outlook_error("messages.list", status, &text));
+    }
+    let parsed: MessageListResp = resp.json().await?;
+    Ok(parsed.value.into_iter().next().map(|m| m.id))
+}
+
 /// Build a `/me/messages` URL with `$filter`, `$search`, `$top`, `$skip`,
 /// and `$orderby` per `OdataQuery`. Pure-ish — no HTTP, just URL assembly.
 /// Folder scoping uses `/me/mailFolders/{id}/messages` when given.
@@ -1610,6 +1803,60 @@ pub async fn toggle_flag(session: &OutlookSession, msg_id: &str) -> Result<bool,
     .await
 }
 
+/// Tag a message with a category named `keyword`. Graph replaces the whole
+/// `categories` list on PATCH, so the current list is read first and the
+/// PATCH is skipped when the category is already there.
+pub async fn add_keyword(
+    session: &OutlookSession,
+    msg_id: &str,
+    keyword: &str,
+) -> Result<bool, Error> {
+    let token = access_token(session).await?;
+    let encoded = crate::provider_utils::encode_path_segment(msg_id);
+    let url = format!("{GRAPH_BASE}/me/messages/{encoded}?$select=categories");
+    let resp = session.client.get(&url).bearer_auth(&token).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(classify_outlook_error(
+            &format!("add_keyword.get {msg_id}"),
+            status,
+            &text,
+        ));
+    }
+    let parsed: serde_json::Value = resp.json().await?;
+    let existing: Vec<String> = parsed["categories"]
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(|c| c.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    match categories_with(existing, keyword) {
+        Some(categories) => {
+            patch_message(
+                session,
+                msg_id,
+                &serde_json::json!({ "categories": categories }),
+                "add_keyword",
+            )
+            .await
+        }
+        None => Ok(true),
+    }
+}
+
+/// `existing` plus `keyword`, or `None` when it's already present
+/// (case-insensitively, as Outlook matches category names).
+fn categories_with(mut existing: Vec<String>, keyword: &str) -> Option<Vec<String>> {
+    if existing.iter().any(|c| c.eq_ignore_ascii_case(keyword)) {
+        return None;
+    }
+    existing.push(keyword.to_string());
+    Some(existing)
+}
+
 /// Move a message to the destination folder. Pure routing happens via
 /// `move_plan_outlook`; the HTTP request is `POST .../move`. On 404 from
 /// Graph (folder missing — most commonly the Archive folder on accounts
@@ -1772,6 +2019,152 @@ pub async fn archive_batch(session: &OutlookSession, msg_ids: &[String]) -> Resu
     Ok(succeeded)
 }
 
+/// Mark every message read via `/$batch` PATCHes, 20 per batch. Unlike
+/// archive there's no folder to be missing, so per-item failures are
+/// reported together after the batch.
+pub async fn mark_read_batch(session: &OutlookSession, msg_ids: &[String]) -> Result<usize, Error> {
+    if msg_ids.is_empty() {
+        return Ok(0);
+    }
+    let token = access_token(session).await?;
+    let mut succeeded = 0usize;
+    let mut result = Ok(());
+    for chunk in chunk_batch_requests(msg_ids) {
+        let body = build_batch_mark_read_body(&chunk);
+        let resp = session
+            .limiter
+            .execute_prioritized(true, "$batch.mark_read", || async {
+                session
+                    .client
+                    .post(format!("{GRAPH_BASE}/$batch"))
+                    .bearer_auth(&token)
+                    .json(&body)
+                    .send()
+                    .await
+            })
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            result = Err(classify_outlook_error("$batch.mark_read", status, &text));
+            break;
+        }
+        let parsed: serde_json::Value = resp.json().await?;
+        match parse_batch_response(&parsed, chunk.len()) {
+            Ok(outcome) if outcome.errors.is_empty() => succeeded += outcome.succeeded,
+            Ok(outcome) => {
+                result = Err(Error::BadRequest(format!(
+                    "Outlook $batch mark_read: {} of {} failed: {}",
+                    outcome.errors.len(),
+                    chunk.len(),
+                    outcome.errors.join(" | ")
+                )));
+                break;
+            }
+            Err(e) => {
+                result = Err(Error::Internal(format!("Outlook $batch mark_read: {e}")));
+                break;
+            }
+        }
+    }
+    // Invalidate once whether or not a chunk failed: earlier chunks landed.
+    invalidate_caches_after_mutation(session).await;
+    result.map(|()| succeeded)
+}
+
+/// Permanently delete every message via `/$batch` `permanentDelete`
+/// POSTs, 20 per batch. Unlike a plain DELETE, which only moves the
+/// message to Recoverable Items, this purges it.
+pub async fn destroy_batch(session: &OutlookSession, msg_ids: &[String]) -> Result<usize, Error> {
+    if msg_ids.is_empty() {
+        return Ok(0);
+    }
+    let token = access_token(session).await?;
+    let mut succeeded = 0usize;
+    let mut result = Ok(());
+    for chunk in chunk_batch_requests(msg_ids) {
+        let body = build_batch_destroy_body(&chunk);
+        let resp = session
+            .limiter
+            .execute_prioritized(true, "$batch.permanent_delete", || async {
+                session
+                    .client
+                    .post(format!("{GRAPH_BASE}/$batch"))
+                    .bearer_auth(&token)
+                    .json(&body)
+                    .send()
+                    .await
+            })
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            result = Err(classify_outlook_error(
+                "$batch.permanent_delete",
+                status,
+                &text,
+            ));
+            break;
+        }
+        let parsed: serde_json::Value = resp.json().await?;
+        match parse_batch_response(&parsed, chunk.len()) {
+            Ok(outcome) if outcome.errors.is_empty() => succeeded += outcome.succeeded,
+            Ok(outcome) => {
+                succeeded += outcome.succeeded;
+                result = Err(Error::BadRequest(format!(
+                    "Outlook $batch permanent_delete: {} of {} failed: {}",
+                    outcome.errors.len(),
+                    chunk.len(),
+                    outcome.errors.join(" | ")
+                )));
+                break;
+            }
+            Err(e) => {
+                result = Err(Error::Internal(format!(
+                    "Outlook $batch permanent_delete: {e}"
+                )));
+                break;
+            }
+        }
+    }
+    invalidate_caches_after_mutation(session).await;
+    result.map(|()| succeeded)
+}
+
+fn build_batch_destroy_body(msg_ids: &[&str]) -> serde_json::Value {
+    let requests: Vec<serde_json::Value> = msg_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let encoded = crate::provider_utils::encode_path_segment(id);
+            serde_json::json!({
+                "id": (i + 1).to_string(),
+                "method": "POST",
+                "url": format!("/me/messages/{encoded}/permanentDelete")
+            })
+        })
+        .collect();
+    serde_json::json!({ "requests": requests })
+}
+
+fn build_batch_mark_read_body(msg_ids: &[&str]) -> serde_json::Value {
+    let requests: Vec<serde_json::Value> = msg_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let encoded = crate::provider_utils::encode_path_segment(id);
+            serde_json::json!({
+                "id": (i + 1).to_string(),
+                "method": "PATCH",
+                "url": format!("/me/messages/{encoded}"),
+                "headers": { "Content-Type": "application/json" },
+                "body": mark_read_body(true)
+            })
+        })
+        .collect();
+    serde_json::json!({ "requests": requests })
+}
+
 /// Build a Graph `/$batch` request body for archiving a chunk of msg IDs.
 /// Each entry POSTs to the per-message /move endpoint with destinationId
 /// "archive". Pure — extracted so the JSON shape is unit-testable.
@@ -2208,15 +2601,21 @@ async fn drain_consumed_synthetic_blobs(
 }
 
 /// Resolve every attachment in the EmailSubmission to its (name, mime,
-/// bytes) triple via `peek_blob_bytes`. Failure on any attachment aborts
+/// bytes) triple via `peek_blob_bytes`, a few at a time, keeping the
+/// submission's order. Failure on any attachment aborts
 /// the whole send — the upload cache stays intact so the user can retry.
 async fn resolve_all_attachments(
     session: &OutlookSession,
     attachments: &[crate::types::Attachment],
 ) -> Result<Vec<(String, String, Vec<u8>)>, Error> {
+    let resolved = crate::provider_utils::try_join_bounded(
+        attachments,
+        crate::provider_utils::ATTACHMENT_FETCH_CONCURRENCY,
+        |att| peek_blob_bytes(session, &att.blob_id),
+    )
+    .await?;
     let mut out = Vec::with_capacity(attachments.len());
-    for att in attachments {
-        let (mime, bytes) = peek_blob_bytes(session, &att.blob_id).await?;
+    for (att, (mime, bytes)) in attachments.iter().zip(resolved) {
         // Prefer the attachment's declared mime_type; fall back to what
         // peek returned. Matches Gmail's discipline.
         // Roborev 181 #8: surface empty-mime fallbacks in logs so a
@@ -2808,6 +3207,11 @@ fn parse_graph_event(uid: &str, event_json: &serde_json::Value) -> Option<Calend
         raw_ics: String::new(),
         user_rsvp_status: None,
         is_update: false,
+        changes: Vec::new(),
+        organizer_mismatch: None,
+        my_response: None,
+        alarms: Vec::new(),
+        all_day: event_json["isAllDay"].as_bool().unwrap_or(false),
     })
 }
 
@@ -2861,14 +3265,24 @@ fn build_graph_event(event: &CalendarEvent) -> serde_json::Value {
             "timeZone": "UTC"
         });
     } else {
-        // Default to 1 hour duration
-        let dtend = event.dtstart + chrono::Duration::hours(1);
+        // Default to 1 hour duration (one day for all-day events)
+        let dtend = if event.all_day {
+            event.dtstart + chrono::Duration::days(1)
+        } else {
+            event.dtstart + chrono::Duration::hours(1)
+        };
         body["end"] = serde_json::json!({
             "dateTime": dtend.format("%Y-%m-%dT%H:%M:%S").to_string(),
             "timeZone": "UTC"
         });
     }
 
+    // Graph requires midnight start/end for isAllDay; our all-day instants
+    // are already UTC midnight of the floating date.
+    if event.all_day {
+        body["isAllDay"] = serde_json::json!(true);
+    }
+
     if let Some(ref location) = event.location {
         body["location"] = serde_json::json!({"displayName": location});
     }
@@ -3007,6 +3421,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = build_graph_event(&event);
         assert_eq!(json["subject"], "Team Meeting");
@@ -3032,11 +3451,53 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = build_graph_event(&event);
         assert!(json["end"]["dateTime"].is_string());
     }
 
+    #[test]
+    fn build_graph_event_all_day_sets_flag_and_day_end() {
+        let event = CalendarEvent {
+            uid: "test".into(),
+            summary: "Offsite".into(),
+            dtstart: chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 12, 25, 0, 0, 0).unwrap(),
+            dtend: None,
+            location: None,
+            description: None,
+            organizer_email: "a@b.com".into(),
+            organizer_name: None,
+            attendees: vec![],
+            sequence: 0,
+            method: "REQUEST".into(),
+            raw_ics: String::new(),
+            user_rsvp_status: None,
+            is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: true,
+        };
+        let json = build_graph_event(&event);
+        assert_eq!(json["isAllDay"], true);
+        assert_eq!(json["start"]["dateTime"], "2026-12-25T00:00:00");
+        assert_eq!(json["end"]["dateTime"], "2026-12-26T00:00:00");
+    }
+
+    #[test]
+    fn parse_graph_event_reads_is_all_day() {
+        let mut json = graph_event_json();
+        assert!(!parse_graph_event("uid-123", &json).unwrap().all_day);
+        json["isAllDay"] = serde_json::json!(true);
+        assert!(parse_graph_event("uid-123", &json).unwrap().all_day);
+    }
+
     #[test]
     fn build_graph_event_with_attendees() {
         let event = CalendarEvent {
@@ -3065,6 +3526,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = build_graph_event(&event);
         let attendees = json["attendees"].as_array().unwrap();
@@ -3091,6 +3557,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = build_graph_event(&event);
         assert!(json.get("location").is_none());
@@ -3113,6 +3584,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = build_graph_event(&event);
         assert_eq!(json["body"]["content"], "");
@@ -3676,6 +4152,41 @@ mod tests {
         assert_eq!(r.search, None);
     }
 
+    #[test]
+    fn odata_translator_negation_and_or() {
+        let q = crate::search::parse_query("-from:bob (is:unread OR has:attachment) -lunch");
+        let r = translate_query_to_odata(&q);
+        assert_eq!(
+            r.filter.as_deref(),
+            Some(
+                "not (from/emailAddress/address eq 'bob') and \
+                 ((isRead eq false) or (hasAttachments eq true))"
+            )
+        );
+        assert_eq!(r.search.as_deref(), Some("\"NOT (lunch)\""));
+        assert!(!odata_parts(&q).mixed);
+
+        let q = crate::search::parse_query("from:ann OR lunch");
+        assert!(odata_parts(&q).mixed);
+    }
+
+    #[test]
+    fn odata_translator_size_routes_to_search() {
+        let q = crate::search::parse_query("larger:1K");
+        let r = translate_query_to_odata(&q);
+        assert_eq!(r.filter, None);
+        assert_eq!(r.search.as_deref(), Some("\"size>1024\""));
+    }
+
+    #[test]
+    fn odata_translator_mailbox_routes_to_filter() {
+        let mut q = crate::search::parse_query("in:\"Client Work\"");
+        q.mailboxes[0].id = Some("AAMk=".into());
+        let r = translate_query_to_odata(&q);
+        assert_eq!(r.filter.as_deref(), Some("parentFolderId eq 'AAMk='"));
+        assert_eq!(r.search, None);
+    }
+
     #[test]
     fn odata_translator_is_unread_routes_to_filter() {
         let q = ParsedQuery {
@@ -3968,6 +4479,18 @@ mod tests {
         assert!(!m.is_unread(), "isRead:true should make $seen present");
     }
 
+    #[test]
+    fn parse_graph_message_wraps_unsubscribe_data_as_header() {
+        let mut j = graph_message_minimal();
+        assert!(parse_graph_message(&j, true).list_unsubscribe.is_none());
+        j["unsubscribeData"] =
+            serde_json::json!(["mailto:unsub@example.com", "https://example.com/u"]);
+        assert_eq!(
+            parse_graph_message(&j, true).list_unsubscribe.as_deref(),
+            Some("<mailto:unsub@example.com>, <https://example.com/u>")
+        );
+    }
+
     #[test]
     fn parse_graph_message_unread_state() {
         let mut j = graph_message_minimal();
@@ -4458,6 +4981,15 @@ mod tests {
         assert_eq!(body["flag"]["flagStatus"], "notFlagged");
     }
 
+    #[test]
+    fn categories_with_appends_once() {
+        assert_eq!(
+            categories_with(vec!["Blue".into()], "receipt"),
+            Some(vec!["Blue".to_string(), "receipt".to_string()])
+        );
+        assert_eq!(categories_with(vec!["Receipt".into()], "receipt"), None);
+    }
+
     #[test]
     fn move_body_shape() {
         let body = move_body("inbox");
@@ -4466,6 +4998,17 @@ mod tests {
 
     // ---- chunk_batch_requests ----
 
+    #[test]
+    fn batch_mark_read_body_patches_each_message() {
+        let body = build_batch_mark_read_body(&["a", "b/c"]);
+        let reqs = body["requests"].as_array().unwrap();
+        assert_eq!(reqs.len(), 2);
+        assert_eq!(reqs[1]["id"], "2");
+        assert_eq!(reqs[1]["method"], "PATCH");
+        assert_eq!(reqs[1]["url"], "/me/messages/b%2Fc");
+        assert_eq!(reqs[1]["body"]["isRead"], true);
+    }
+
     #[test]
     fn chunk_batch_empty_returns_no_chunks() {
         let ids: Vec<String> = vec![];
@@ -4516,6 +5059,16 @@ mod tests {
     // can distinguish "all-succeeded" from "partial-failure" and aggregate
     // ALL failures (not just the first).
 
+    #[test]
+    fn batch_destroy_body_posts_permanent_delete() {
+        let body = build_batch_destroy_body(&["a/b", "c"]);
+        let requests = body["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["method"], "POST");
+        assert_eq!(requests[0]["url"], "/me/messages/a%2Fb/permanentDelete");
+        assert_eq!(requests[1]["id"], "2");
+    }
+
     #[test]
     fn parse_batch_response_all_succeed() {
         let body = serde_json::json!({
@@ -4655,6 +5208,7 @@ mod tests {
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         }
     }
 
@@ -4664,6 +5218,9 @@ mod tests {
             name: "a.pdf".into(),
             mime_type: "application/pdf".into(),
             size: 1,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
         }
     }
 
@@ -5301,6 +5858,9 @@ mod tests {
                 name: "a.txt".into(),
                 mime_type: "text/plain".into(),
                 size: 1,
+                sniffed_mime_type: None,
+                mime_mismatch: false,
+                content_id: None,
             },
             // Non-synthetic (OutlookAttachment) → drain should ignore.
             crate::types::Attachment {
@@ -5308,6 +5868,9 @@ mod tests {
                 name: "b.pdf".into(),
                 mime_type: "application/pdf".into(),
                 size: 0,
+                sniffed_mime_type: None,
+                mime_mismatch: false,
+                content_id: None,
             },
         ];
         drain_consumed_synthetic_blobs(&session, &atts).await;
@@ -5323,4 +5886,20 @@ mod tests {
         let err = peek_blob_bytes(&session, "msg-1:att-1").await.unwrap_err();
         assert!(matches!(err, Error::BadRequest(_)));
     }
+
+    #[test]
+    fn message_headers_resp_tolerates_missing_headers() {
+        let with: MessageHeadersResp = serde_json::from_value(serde_json::json!({
+            "internetMessageHeaders": [
+                { "name": "Received", "value": "from a" },
+                { "name": "DKIM-Signature", "value": "v=1" }
+            ]
+        }))
+        .unwrap();
+        let headers = with.internet_message_headers.unwrap();
+        assert_eq!(headers[1].name, "DKIM-Signature");
+        let sent: MessageHeadersResp =
+            serde_json::from_value(serde_json::json!({ "internetMessageHeaders": null })).unwrap();
+        assert!(sent.internet_message_headers.is_none());
+    }
 }
@@ -0,0 +1,764 @@
+//! Shipment tracking.
+//!
+//! [`detect`] pulls UPS/FedEx/DHL/USPS tracking numbers out of a message,
+//! from schema.org `ParcelDelivery` markup first and carrier number formats
+//! second. The email detail route shows them and records them in
+//! `packages-tracked.json`. When `packages.json` enables it and holds
+//! carrier API credentials, the poller ([`spawn_poller`]) asks each carrier
+//! for the status of every undelivered package; `GET /api/packages` serves
+//! the result.
+
+use crate::error::Error;
+use crate::types::Email;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+/// Packages first seen longer ago than this are no longer polled.
+const POLL_MAX_AGE_DAYS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Carrier {
+    Ups,
+    Fedex,
+    Dhl,
+    Usps,
+}
+
+impl Carrier {
+    /// Carrier named in free text such as schema.org `provider.name`.
+    fn from_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        if lower
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|w| w == "ups")
+        {
+            Some(Self::Ups)
+        } else if lower.contains("fedex") || lower.contains("federal express") {
+            Some(Self::Fedex)
+        } else if lower.contains("dhl") {
+            Some(Self::Dhl)
+        } else if lower.contains("usps") || lower.contains("postal service") {
+            Some(Self::Usps)
+        } else {
+            None
+        }
+    }
+
+    /// Public tracking page for `number`.
+    pub fn tracking_url(self, number: &str) -> String {
+        match self {
+            Self::Ups => format!("https://www.ups.com/track?tracknum={number}"),
+            Self::Fedex => format!("https://www.fedex.com/fedextrack/?trknbr={number}"),
+            Self::Dhl => {
+                format!("https://www.dhl.com/global-en/home/tracking.html?tracking-id={number}")
+            }
+            Self::Usps => format!("https://tools.usps.com/go/TrackConfirmAction?tLabels={number}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackingNumber {
+    pub carrier: Carrier,
+    pub number: String,
+    pub url: String,
+}
+
+impl TrackingNumber {
+    fn new(carrier: Carrier, number: &str) -> Self {
+        Self {
+            carrier,
+            number: number.to_string(),
+            url: carrier.tracking_url(number),
+        }
+    }
+}
+
+// =============================================================================
+// Detection
+// =============================================================================
+
+/// Formats distinctive enough to trust anywhere in a message.
+static SELF_EVIDENT: LazyLock<Vec<(Carrier, Regex)>> = LazyLock::new(|| {
+    vec![
+        (
+            Carrier::Ups,
+            Regex::new(r"\b1Z[0-9A-Z]{16}\b").expect("valid regex"),
+        ),
+        (
+            Carrier::Usps,
+            Regex::new(r"\b9[2345]\d{20}\b|\b[A-Z]{2}\d{9}US\b").expect("valid regex"),
+        ),
+        (
+            Carrier::Dhl,
+            Regex::new(r"\bJJD\d{18}\b").expect("valid regex"),
+        ),
+    ]
+});
+
+/// Bare digit runs that are only tracking numbers when the message also
+/// names the carrier; otherwise they're order numbers and phone numbers.
+static NEEDS_CARRIER_NAME: LazyLock<Vec<(Carrier, Regex)>> = LazyLock::new(|| {
+    vec![
+        (
+            Carrier::Fedex,
+            Regex::new(r"\b(?:\d{12}|\d{15})\b").expect("valid regex"),
+        ),
+        (
+            Carrier::Dhl,
+            Regex::new(r"\b\d{10}\b").expect("valid regex"),
+        ),
+    ]
+});
+
+static JSON_LD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<script[^>]*type=["']application/ld\+json["'][^>]*>(.*?)</script>"#)
+        .expect("valid regex")
+});
+
+static MICRODATA: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)itemprop=["']trackingNumber["'][^>]*?(?:content=["']([^"']+)["'][^>]*>|>\s*([^<\s]+))"#,
+    )
+    .expect("valid regex")
+});
+
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").expect("valid regex"));
+
+/// The carrier whose format all of `number` matches, if any.
+fn classify(number: &str, text_lower: &str) -> Option<Carrier> {
+    let whole = |re: &Regex| re.find(number).is_some_and(|m| m.len() == number.len());
+    SELF_EVIDENT
+        .iter()
+        .find(|(_, re)| whole(re))
+        .or_else(|| {
+            NEEDS_CARRIER_NAME
+                .iter()
+                .find(|(carrier, re)| whole(re) && mentions(*carrier, text_lower))
+        })
+        .map(|(carrier, _)| *carrier)
+}
+
+fn mentions(carrier: Carrier, text_lower: &str) -> bool {
+    text_lower.contains(match carrier {
+        Carrier::Ups => "ups",
+        Carrier::Fedex => "fedex",
+        Carrier::Dhl => "dhl",
+        Carrier::Usps => "usps",
+    })
+}
+
+/// `ParcelDelivery` objects anywhere in a JSON-LD value.
+fn collect_parcels(value: &serde_json::Value, text_lower: &str, out: &mut Vec<TrackingNumber>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_parcels(item, text_lower, out);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(number) = map.get("trackingNumber").and_then(|n| n.as_str()) {
+                let named = ["provider", "carrier"].iter().find_map(|key| {
+                    let v = map.get(*key)?;
+                    v.get("name").and_then(|n| n.as_str()).or(v.as_str())
+                });
+                if let Some(carrier) = named
+                    .and_then(Carrier::from_name)
+                    .or_else(|| classify(number, text_lower))
+                {
+                    out.push(TrackingNumber::new(carrier, number));
+                }
+            }
+            for v in map.values() {
+                collect_parcels(v, text_lower, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Tracking numbers in `email`, deduplicated, markup matches first.
+pub fn detect(email: &Email) -> Vec<TrackingNumber> {
+    let html = email.html_body.as_deref().unwrap_or_default();
+    let text = format!(
+        "{}\n{}\n{}",
+        email.subject,
+        email.text_body.as_deref().unwrap_or_default(),
+        HTML_TAG.replace_all(html, " ")
+    );
+    let text_lower = text.to_lowercase();
+
+    let mut found = Vec::new();
+    for block in JSON_LD.captures_iter(html) {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(&block[1]) {
+            collect_parcels(&value, &text_lower, &mut found);
+        }
+    }
+    for m in MICRODATA.captures_iter(html) {
+        let number = m
+            .get(1)
+            .or(m.get(2))
+            .map(|g| g.as_str())
+            .unwrap_or_default();
+        if let Some(carrier) = classify(number, &text_lower) {
+            found.push(TrackingNumber::new(carrier, number));
+        }
+    }
+    for (carrier, re) in SELF_EVIDENT.iter() {
+        for m in re.find_iter(&text) {
+            found.push(TrackingNumber::new(*carrier, m.as_str()));
+        }
+    }
+    for (carrier, re) in NEEDS_CARRIER_NAME.iter() {
+        if mentions(*carrier, &text_lower) {
+            for m in re.find_iter(&text) {
+                found.push(TrackingNumber::new(*carrier, m.as_str()));
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    found.retain(|t| seen.insert(t.number.clone()));
+    found
+}
+
+// =============================================================================
+// Config and tracked packages
+// =============================================================================
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// `packages.json`: hand-edited, since it holds carrier API secrets that
+/// no endpoint should hand back.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackagesConfig {
+    /// Run the status poller. Detection works without it.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+    #[serde(default)]
+    pub ups: Option<ClientCredentials>,
+    #[serde(default)]
+    pub fedex: Option<ClientCredentials>,
+    #[serde(default)]
+    pub usps: Option<ClientCredentials>,
+    #[serde(default)]
+    pub dhl_api_key: Option<String>,
+}
+
+fn default_interval_minutes() -> u64 {
+    30
+}
+
+impl Default for PackagesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_interval_minutes(),
+            ups: None,
+            fedex: None,
+            usps: None,
+            dhl_api_key: None,
+        }
+    }
+}
+
+impl PackagesConfig {
+    fn has_credentials(&self, carrier: Carrier) -> bool {
+        match carrier {
+            Carrier::Ups => self.ups.is_some(),
+            Carrier::Fedex => self.fedex.is_some(),
+            Carrier::Usps => self.usps.is_some(),
+            Carrier::Dhl => self.dhl_api_key.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageStatus {
+    /// Carrier's own wording, e.g. "Out for delivery".
+    pub summary: String,
+    pub delivered: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedPackage {
+    #[serde(flatten)]
+    pub tracking: TrackingNumber,
+    pub account: String,
+    pub email_id: String,
+    pub subject: String,
+    pub first_seen: DateTime<Utc>,
+    /// Last polled status; absent until the poller has reached the carrier.
+    #[serde(default)]
+    pub status: Option<PackageStatus>,
+}
+
+/// On-disk shape of `packages-tracked.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TrackedLog {
+    #[serde(default)]
+    pub packages: Vec<TrackedPackage>,
+}
+
+impl TrackedLog {
+    /// Start tracking the numbers in `email` not already tracked; returns
+    /// whether anything was added.
+    pub fn record(&mut self, account: &str, email: &Email, numbers: &[TrackingNumber]) -> bool {
+        let mut added = false;
+        for t in numbers {
+            if self.packages.iter().any(|p| p.tracking.number == t.number) {
+                continue;
+            }
+            self.packages.push(TrackedPackage {
+                tracking: t.clone(),
+                account: account.to_string(),
+                email_id: email.id.clone(),
+                subject: email.subject.clone(),
+                first_seen: Utc::now(),
+                status: None,
+            });
+            added = true;
+        }
+        added
+    }
+
+    /// Indices of packages worth polling at `now` with `config`'s keys.
+    fn pollable(&self, config: &PackagesConfig, now: DateTime<Utc>) -> Vec<usize> {
+        self.packages
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                !p.status.as_ref().is_some_and(|s| s.delivered)
+                    && now - p.first_seen < chrono::Duration::days(POLL_MAX_AGE_DAYS)
+                    && config.has_credentials(p.tracking.carrier)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+pub fn load_config(path: &Path) -> PackagesConfig {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read packages config: {e}");
+                return PackagesConfig::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    PackagesConfig::default()
+}
+
+pub fn load_tracked(path: &Path) -> TrackedLog {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read tracked packages: {e}");
+                return TrackedLog::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    TrackedLog::default()
+}
+
+pub fn save_tracked(log: &TrackedLog, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(log)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Carrier APIs
+// =============================================================================
+
+const UPS_BASE: &str = "https://onlinetools.ups.com";
+const FEDEX_BASE: &str = "https://apis.fedex.com";
+const USPS_BASE: &str = "https://apis.usps.com";
+const DHL_BASE: &str = "https://api-eu.dhl.com";
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+async fn json_or_error(resp: reqwest::Response, what: &str) -> Result<serde_json::Value, Error> {
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(match status.as_u16() {
+            401 | 403 => Error::Auth(format!("{what}: {text}")),
+            429 => Error::RateLimited { retry_after: None },
+            _ => Error::Network(format!("{what} returned {status}: {text}")),
+        });
+    }
+    Ok(resp.json().await?)
+}
+
+/// OAuth client-credentials token for one carrier, fetched once per poll.
+async fn carrier_token(
+    client: &reqwest::Client,
+    carrier: Carrier,
+    creds: &ClientCredentials,
+) -> Result<String, Error> {
+    let req = match carrier {
+        Carrier::Ups => client
+            .post(format!("{UPS_BASE}/security/v1/oauth/token"))
+            .basic_auth(&creds.client_id, Some(&creds.client_secret))
+            .form(&[("grant_type", "client_credentials")]),
+        Carrier::Fedex => client.post(format!("{FEDEX_BASE}/oauth/token")).form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", creds.client_id.as_str()),
+            ("client_secret", creds.client_secret.as_str()),
+        ]),
+        Carrier::Usps => {
+            client
+                .post(format!("{USPS_BASE}/oauth2/v3/token"))
+                .json(&serde_json::json!({
+                    "grant_type": "client_credentials",
+                    "client_id": creds.client_id,
+                    "client_secret": creds.client_secret,
+                }))
+        }
+        Carrier::Dhl => return Err(Error::Internal("DHL uses an API key, not OAuth".into())),
+    };
+    let value = json_or_error(req.send().await?, "carrier token").await?;
+    let token: TokenResponse = serde_json::from_value(value)?;
+    Ok(token.access_token)
+}
+
+async fn fetch_status(
+    client: &reqwest::Client,
+    config: &PackagesConfig,
+    token: Option<&str>,
+    tracking: &TrackingNumber,
+) -> Result<serde_json::Value, Error> {
+    let number = crate::provider_utils::encode_path_segment(&tracking.number);
+    let token = token.unwrap_or_default();
+    let req = match tracking.carrier {
+        Carrier::Ups => client
+            .get(format!("{UPS_BASE}/api/track/v1/details/{number}"))
+            .bearer_auth(token)
+            .header("transId", uuid::Uuid::new_v4().to_string())
+            .header("transactionSrc", "supervillain"),
+        Carrier::Fedex => client
+            .post(format!("{FEDEX_BASE}/track/v1/trackingnumbers"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "includeDetailedScans": false,
+                "trackingInfo": [{ "trackingNumberInfo": { "trackingNumber": tracking.number } }],
+            })),
+        Carrier::Usps => client
+            .get(format!(
+                "{USPS_BASE}/tracking/v3/tracking/{number}?expand=SUMMARY"
+            ))
+            .bearer_auth(token),
+        Carrier::Dhl => client
+            .get(format!("{DHL_BASE}/track/shipments"))
+            .query(&[("trackingNumber", tracking.number.as_str())])
+            .header(
+                "DHL-API-Key",
+                config.dhl_api_key.as_deref().unwrap_or_default(),
+            ),
+    };
+    json_or_error(req.send().await?, "carrier tracking").await
+}
+
+/// `(summary, delivered)` from a carrier tracking response, or `None` when
+/// the response doesn't have the expected shape.
+pub fn parse_status(carrier: Carrier, body: &serde_json::Value) -> Option<(String, bool)> {
+    let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+    match carrier {
+        Carrier::Ups => {
+            let package = &body["trackResponse"]["shipment"][0]["package"][0];
+            let latest = &package["activity"][0]["status"];
+            let summary = text(&package["currentStatus"]["description"])
+                .or_else(|| text(&latest["description"]))?;
+            Some((summary, latest["type"] == "D"))
+        }
+        Carrier::Fedex => {
+            let detail =
+                &body["output"]["completeTrackResults"][0]["trackResults"][0]["latestStatusDetail"];
+            Some((text(&detail["description"])?, detail["code"] == "DL"))
+        }
+        Carrier::Usps => {
+            let summary = text(&body["statusSummary"]).or_else(|| text(&body["status"]))?;
+            let delivered = body["statusCategory"]
+                .as_str()
+                .is_some_and(|c| c.eq_ignore_ascii_case("delivered"));
+            Some((summary, delivered))
+        }
+        Carrier::Dhl => {
+            let status = &body["shipments"][0]["status"];
+            let summary = text(&status["description"]).or_else(|| text(&status["status"]))?;
+            Some((summary, status["statusCode"] == "delivered"))
+        }
+    }
+}
+
+/// Poll every pollable package once and store what came back. Per-package
+/// failures are logged and leave the previous status in place.
+pub async fn poll_once(
+    client: &reqwest::Client,
+    config: &PackagesConfig,
+    tracked_path: &Path,
+) -> Result<usize, Error> {
+    let log = load_tracked(tracked_path);
+    let now = Utc::now();
+    let mut tokens: std::collections::HashMap<Carrier, Option<String>> = Default::default();
+    let mut updates: Vec<(String, PackageStatus)> = Vec::new();
+    for i in log.pollable(config, now) {
+        let tracking = &log.packages[i].tracking;
+        let carrier = tracking.carrier;
+        let creds = match carrier {
+            Carrier::Ups => config.ups.as_ref(),
+            Carrier::Fedex => config.fedex.as_ref(),
+            Carrier::Usps => config.usps.as_ref(),
+            Carrier::Dhl => None,
+        };
+        if let Some(creds) = creds
+            && !tokens.contains_key(&carrier)
+        {
+            let token = carrier_token(client, carrier, creds)
+                .await
+                .inspect_err(|e| tracing::warn!("{carrier:?} token request failed: {e}"))
+                .ok();
+            tokens.insert(carrier, token);
+        }
+        let token = tokens.get(&carrier).cloned().flatten();
+        if creds.is_some() && token.is_none() {
+            continue;
+        }
+        match fetch_status(client, config, token.as_deref(), tracking).await {
+            Ok(body) => match parse_status(carrier, &body) {
+                Some((summary, delivered)) => updates.push((
+                    tracking.number.clone(),
+                    PackageStatus {
+                        summary,
+                        delivered,
+                        checked_at: now,
+                    },
+                )),
+                None => tracing::warn!("Unrecognized {carrier:?} response for {}", tracking.number),
+            },
+            Err(e) => tracing::warn!("Tracking {} failed: {e}", tracking.number),
+        }
+    }
+
+    // Re-read so packages recorded while the carriers answered survive.
+    let mut latest = load_tracked(tracked_path);
+    for (number, status) in &updates {
+        if let Some(p) = latest
+            .packages
+            .iter_mut()
+            .find(|p| &p.tracking.number == number)
+        {
+            p.status = Some(status.clone());
+        }
+    }
+    save_tracked(&latest, tracked_path)?;
+    Ok(updates.len())
+}
+
+/// Check once a minute whether a poll is due under `packages.json`. A
+/// no-op unless the config enables polling.
+pub fn spawn_poller(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("packages", interval);
+    tokio::spawn(async move {
+        let client = crate::http_client::client();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_poll: Option<Instant> = None;
+        loop {
+            ticker.tick().await;
+            health.ticked("packages");
+            let config = load_config(&state.packages_config_path);
+            let every = Duration::from_secs(config.interval_minutes.max(1) * 60);
+            if !config.enabled || last_poll.is_some_and(|t| t.elapsed() < every) {
+                continue;
+            }
+            last_poll = Some(Instant::now());
+            match poll_once(&client, &config, &state.packages_path).await {
+                Ok(n) if n > 0 => tracing::info!("Updated {n} package status(es)"),
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Package poll failed: {e}");
+                    health.failed("packages", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(subject: &str, text: &str, html: Option<&str>) -> Email {
+        Email {
+            id: "m1".into(),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: Default::default(),
+            keywords: Default::default(),
+            received_at: Utc::now(),
+            subject: subject.into(),
+            from: vec![],
+            to: vec![],
+            cc: vec![],
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: Some(text.into()),
+            html_body: html.map(str::to_string),
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    fn numbers(found: &[TrackingNumber]) -> Vec<(Carrier, &str)> {
+        found
+            .iter()
+            .map(|t| (t.carrier, t.number.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_self_evident_formats() {
+        let found = detect(&email(
+            "Shipped",
+            "UPS: 1Z999AA10123456784\nUSPS: 9400111899223344556677 and EC123456789US",
+            None,
+        ));
+        assert_eq!(
+            numbers(&found),
+            [
+                (Carrier::Ups, "1Z999AA10123456784"),
+                (Carrier::Usps, "9400111899223344556677"),
+                (Carrier::Usps, "EC123456789US"),
+            ]
+        );
+        assert_eq!(
+            found[0].url,
+            "https://www.ups.com/track?tracknum=1Z999AA10123456784"
+        );
+    }
+
+    #[test]
+    fn bare_digits_need_the_carrier_named() {
+        let text = "Order 123456789012 confirmed. Call 5551234567.";
+        assert!(detect(&email("Your order", text, None)).is_empty());
+        let found = detect(&email("Shipped with FedEx", "Tracking: 123456789012", None));
+        assert_eq!(numbers(&found), [(Carrier::Fedex, "123456789012")]);
+    }
+
+    #[test]
+    fn detects_schema_org_markup() {
+        let html = r#"<script type="application/ld+json">
+            {"@context":"http://schema.org","@type":"ParcelDelivery",
+             "trackingNumber":"ABC123","provider":{"@type":"Organization","name":"DHL Express"}}
+            </script><span itemprop="trackingNumber">1Z999AA10123456784</span>"#;
+        let found = detect(&email("On its way", "", Some(html)));
+        assert_eq!(
+            numbers(&found),
+            [
+                (Carrier::Dhl, "ABC123"),
+                (Carrier::Ups, "1Z999AA10123456784")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_status_per_carrier() {
+        let ups = serde_json::json!({"trackResponse":{"shipment":[{"package":[{
+            "currentStatus":{"description":"Delivered"},
+            "activity":[{"status":{"type":"D","description":"DELIVERED"}}]}]}]}});
+        assert_eq!(
+            parse_status(Carrier::Ups, &ups),
+            Some(("Delivered".into(), true))
+        );
+        let fedex = serde_json::json!({"output":{"completeTrackResults":[{"trackResults":[{
+            "latestStatusDetail":{"code":"IT","description":"In transit"}}]}]}});
+        assert_eq!(
+            parse_status(Carrier::Fedex, &fedex),
+            Some(("In transit".into(), false))
+        );
+        let usps =
+            serde_json::json!({"statusCategory":"Delivered","statusSummary":"Left at front door"});
+        assert_eq!(
+            parse_status(Carrier::Usps, &usps),
+            Some(("Left at front door".into(), true))
+        );
+        let dhl = serde_json::json!({"shipments":[{"status":{"statusCode":"transit","description":"Processed"}}]});
+        assert_eq!(
+            parse_status(Carrier::Dhl, &dhl),
+            Some(("Processed".into(), false))
+        );
+        assert_eq!(parse_status(Carrier::Dhl, &serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn pollable_skips_delivered_stale_and_keyless() {
+        let mut log = TrackedLog::default();
+        let e = email("Shipped", "", None);
+        log.record(
+            "a",
+            &e,
+            &[
+                TrackingNumber::new(Carrier::Ups, "1Z999AA10123456784"),
+                TrackingNumber::new(Carrier::Dhl, "JJD000000000000000001"),
+                TrackingNumber::new(Carrier::Fedex, "123456789012"),
+            ],
+        );
+        assert!(!log.record(
+            "a",
+            &e,
+            &[TrackingNumber::new(Carrier::Ups, "1Z999AA10123456784")]
+        ));
+        let config = PackagesConfig {
+            ups: Some(ClientCredentials::default()),
+            dhl_api_key: Some("k".into()),
+            ..Default::default()
+        };
+        let now = Utc::now();
+        assert_eq!(log.pollable(&config, now), [0, 1]);
+        log.packages[0].status = Some(PackageStatus {
+            summary: "Delivered".into(),
+            delivered: true,
+            checked_at: now,
+        });
+        log.packages[1].first_seen = now - chrono::Duration::days(POLL_MAX_AGE_DAYS + 1);
+        assert!(log.pollable(&config, now).is_empty());
+    }
+}
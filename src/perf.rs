@@ -0,0 +1,350 @@
+//! Local request timings, for tuning.
+//!
+//! With `SUPERVILLAIN_PERF=1`, every API request is timed by [`track`]
+//! and broken into phases that handlers mark with [`phase`],
+//! [`phase_sync`] or [`add_phase`] (`provider`, `filter`, `serialize`). Whatever no phase
+//! claimed is reported as `other`. Samples are kept per route in memory
+//! for `GET /api/perf` and appended to `perf.jsonl` in the config dir,
+//! which rolls over to `perf.jsonl.1` at [`MAX_FILE_BYTES`] and seeds the
+//! in-memory window on the next start.
+//!
+//! A sample holds the route template (`/api/emails/{email_id}`), never
+//! the concrete path, query string, or account, and nothing leaves the
+//! machine.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Samples kept per route for the aggregates.
+pub const WINDOW: usize = 500;
+
+/// `perf.jsonl` size at which it is rolled to `perf.jsonl.1`.
+pub const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Phase name for request time no [`phase`] claimed.
+const OTHER: &str = "other";
+
+tokio::task_local! {
+    static PHASES: RefCell<Vec<(&'static str, Duration)>>;
+}
+
+/// Time `fut` as phase `name` of the request being tracked. Outside a
+/// tracked request (background tasks, perf disabled) it just awaits.
+pub async fn phase<F: Future>(name: &'static str, fut: F) -> F::Output {
+    let start = Instant::now();
+    let out = fut.await;
+    add_phase(name, start.elapsed());
+    out
+}
+
+/// [`phase`] for synchronous work.
+pub fn phase_sync<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    add_phase(name, start.elapsed());
+    out
+}
+
+/// Record `elapsed` as phase `name`, for work timed by hand.
+pub fn add_phase(name: &'static str, elapsed: Duration) {
+    let _ = PHASES.try_with(|p| p.borrow_mut().push((name, elapsed)));
+}
+
+/// One timed request. Durations are microseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sample {
+    pub route: String,
+    pub at: DateTime<Utc>,
+    pub status: u16,
+    pub total_us: u64,
+    pub phases: BTreeMap<String, u64>,
+}
+
+impl Sample {
+    fn new(route: &str, status: u16, total: Duration, phases: &[(&str, Duration)]) -> Self {
+        let mut by_name: BTreeMap<String, u64> = BTreeMap::new();
+        for (name, d) in phases {
+            *by_name.entry(name.to_string()).or_default() += micros(*d);
+        }
+        let claimed: u64 = by_name.values().sum();
+        let total_us = micros(total);
+        by_name.insert(OTHER.into(), total_us.saturating_sub(claimed));
+        Self {
+            route: route.to_string(),
+            at: Utc::now(),
+            status,
+            total_us,
+            phases: by_name,
+        }
+    }
+}
+
+fn micros(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Milliseconds distribution of one measure over a route's window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timing {
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Timing {
+    fn of(mut micros: Vec<u64>) -> Self {
+        micros.sort_unstable();
+        let ms = |us: u64| us as f64 / 1000.0;
+        let pct = |p: usize| ms(micros[(micros.len() - 1) * p / 100]);
+        Self {
+            mean_ms: ms(micros.iter().sum::<u64>() / micros.len() as u64),
+            p50_ms: pct(50),
+            p95_ms: pct(95),
+            max_ms: ms(*micros.last().unwrap_or(&0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteSummary {
+    pub route: String,
+    pub count: usize,
+    pub total: Timing,
+    /// Each phase over the samples that had it; `other` is in every one.
+    pub phases: BTreeMap<String, Timing>,
+}
+
+/// Per-route sample windows plus the `perf.jsonl` log. `Default` is
+/// disabled: nothing is tracked or written.
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    path: Option<PathBuf>,
+    windows: Mutex<HashMap<String, VecDeque<Sample>>>,
+}
+
+impl PerfStats {
+    /// Enabled stats logging to `path`, seeded from what it already holds.
+    pub fn open(path: PathBuf) -> Self {
+        let stats = Self {
+            path: None,
+            windows: Default::default(),
+        };
+        for sample in read_samples(&path) {
+            stats.remember(sample);
+        }
+        Self {
+            path: Some(path),
+            ..stats
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn remember(&self, sample: Sample) {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.entry(sample.route.clone()).or_default();
+        if window.len() == WINDOW {
+            window.pop_front();
+        }
+        window.push_back(sample);
+    }
+
+    /// Keep `sample` for the aggregates and append it to the log.
+    pub fn record(&self, sample: Sample) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = append(path, &sample) {
+            tracing::warn!("perf: failed to write {}: {e}", path.display());
+        }
+        self.remember(sample);
+    }
+
+    /// Every route's aggregates, slowest mean first.
+    pub fn summary(&self) -> Vec<RouteSummary> {
+        let windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<RouteSummary> = windows
+            .iter()
+            .filter(|(_, w)| !w.is_empty())
+            .map(|(route, window)| {
+                let mut phases: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+                for sample in window {
+                    for (name, us) in &sample.phases {
+                        phases.entry(name.clone()).or_default().push(*us);
+                    }
+                }
+                RouteSummary {
+                    route: route.clone(),
+                    count: window.len(),
+                    total: Timing::of(window.iter().map(|s| s.total_us).collect()),
+                    phases: phases
+                        .into_iter()
+                        .map(|(name, us)| (name, Timing::of(us)))
+                        .collect(),
+                }
+            })
+            .collect();
+        out.sort_by(|a, b| b.total.mean_ms.total_cmp(&a.total.mean_ms));
+        out
+    }
+}
+
+fn append(path: &Path, sample: &Sample) -> std::io::Result<()> {
+    if std::fs::metadata(path).is_ok_and(|m| m.len() >= MAX_FILE_BYTES) {
+        std::fs::rename(path, path.with_extension("jsonl.1"))?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_vec(sample)?;
+    line.push(b'\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
+
+/// Samples in `path`, skipping lines that don't parse (a torn last
+/// write, an older format).
+fn read_samples(path: &Path) -> Vec<Sample> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Middleware timing every matched route into `state.perf`.
+pub async fn track(
+    State(state): State<Arc<crate::types::AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.perf.is_enabled() {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+    let Some(route) = route else {
+        return next.run(request).await;
+    };
+    let start = Instant::now();
+    let (response, phases) = PHASES
+        .scope(RefCell::new(Vec::new()), async {
+            let response = next.run(request).await;
+            (response, PHASES.with(|p| p.take()))
+        })
+        .await;
+    let sample = Sample::new(&route, response.status().as_u16(), start.elapsed(), &phases);
+    state.perf.record(sample);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(route: &str, total_ms: u64, provider_ms: u64) -> Sample {
+        Sample::new(
+            route,
+            200,
+            Duration::from_millis(total_ms),
+            &[("provider", Duration::from_millis(provider_ms))],
+        )
+    }
+
+    #[test]
+    fn unclaimed_time_is_other() {
+        let s = Sample::new(
+            "/api/emails",
+            200,
+            Duration::from_millis(50),
+            &[
+                ("provider", Duration::from_millis(30)),
+                ("serialize", Duration::from_millis(5)),
+                ("provider", Duration::from_millis(10)),
+            ],
+        );
+        assert_eq!(s.phases["provider"], 40_000);
+        assert_eq!(s.phases["serialize"], 5_000);
+        assert_eq!(s.phases[OTHER], 5_000);
+    }
+
+    #[test]
+    fn summary_aggregates_per_route_slowest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let stats = PerfStats::open(dir.path().join("perf.jsonl"));
+        for ms in [10, 20, 30, 40, 100] {
+            stats.record(sample("/api/emails", ms, ms / 2));
+        }
+        stats.record(sample("/api/mailboxes", 5, 1));
+
+        let summary = stats.summary();
+        assert_eq!(summary[0].route, "/api/emails");
+        assert_eq!(summary[0].count, 5);
+        assert_eq!(summary[0].total.mean_ms, 40.0);
+        assert_eq!(summary[0].total.p50_ms, 30.0);
+        assert_eq!(summary[0].total.max_ms, 100.0);
+        assert_eq!(summary[0].phases["provider"].max_ms, 50.0);
+        assert_eq!(summary[1].route, "/api/mailboxes");
+    }
+
+    #[test]
+    fn log_seeds_the_next_start_and_window_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("perf.jsonl");
+        let stats = PerfStats::open(path.clone());
+        for _ in 0..WINDOW + 10 {
+            stats.record(sample("/api/emails", 1, 0));
+        }
+        std::fs::write(
+            &path,
+            format!("{}\nnot json\n", std::fs::read_to_string(&path).unwrap()),
+        )
+        .unwrap();
+        let reopened = PerfStats::open(path);
+        assert_eq!(reopened.summary()[0].count, WINDOW);
+    }
+
+    #[test]
+    fn disabled_records_nothing() {
+        let stats = PerfStats::default();
+        stats.record(sample("/api/emails", 1, 0));
+        assert!(stats.summary().is_empty());
+    }
+
+    #[tokio::test]
+    async fn phases_only_collect_inside_a_tracked_request() {
+        // Outside a scope the helpers are plain wrappers.
+        assert_eq!(phase("provider", async { 7 }).await, 7);
+        let phases = PHASES
+            .scope(RefCell::new(Vec::new()), async {
+                phase("provider", async {}).await;
+                phase_sync("serialize", || ());
+                PHASES.with(|p| p.take())
+            })
+            .await;
+        let names: Vec<&str> = phases.iter().map(|(n, _)| *n).collect();
+        assert_eq!(names, ["provider", "serialize"]);
+    }
+}
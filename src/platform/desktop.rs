@@ -69,14 +69,44 @@ pub fn open_browser(url: &str) {
     }
 }
 
+/// A secret stored in the OS keyring under `service`, via the stock CLI:
+/// `security` (Keychain) on macOS, `secret-tool` (Secret Service) on
+/// Linux. None when the tool or the entry is missing.
+pub fn keyring_secret(service: &str) -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        std::process::Command::new("security")
+            .args(["find-generic-password", "-s", service, "-w"])
+            .output()
+    } else {
+        std::process::Command::new("secret-tool")
+            .args(["lookup", "service", service])
+            .output()
+    }
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let secret = String::from_utf8(output.stdout).ok()?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    (!secret.is_empty()).then(|| secret.to_string())
+}
+
 /// Initialize the tracing subscriber. Reads `RUST_LOG` env var; defaults to `info`.
-/// iOS will bridge tracing to `os_log` instead.
+/// Events also feed `/api/logs/tail` through `logs::TailLayer`, which has
+/// its own filter. iOS will bridge tracing to `os_log` instead.
 pub fn init_tracing() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer().with_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+            ),
         )
+        .with(crate::logs::TailLayer.with_filter(crate::logs::capture_filter()))
         .init();
 }
 
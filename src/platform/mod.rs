@@ -12,7 +12,9 @@ use crate::error::Error;
 
 pub mod desktop;
 
-pub use desktop::{FsTokenStore, acquire_oauth_callback, config_dir, init_tracing, open_browser};
+pub use desktop::{
+    FsTokenStore, acquire_oauth_callback, config_dir, init_tracing, keyring_secret, open_browser,
+};
 
 /// OAuth tokens persisted between sessions. Same shape across all providers
 /// that use OAuth2 (Outlook, Gmail today; O365 email later).
@@ -7,8 +7,12 @@
 //! in <10 ms instead of waiting on ~1500 provider API calls (~24 s for
 //! Gmail split-counts).
 
+use crate::at_rest::AtRest;
 use crate::error::Error;
 use crate::types::{Email, EmailSort, Identity, Mailbox};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as B64;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -55,19 +59,243 @@ struct InboxList {
     stale: bool,
 }
 
+/// Cached split-counts for one mailbox. An invalidation (mutation or
+/// splits edit) only flags the entry: `get_split_counts` stops serving it,
+/// but `/api/split-counts` still answers from it instantly while a
+/// background recompute runs, instead of blocking on a ~24 s Gmail crawl.
+#[derive(Clone)]
+struct SplitCountsEntry {
+    mailbox_id: String,
+    counts: HashMap<String, u32>,
+    computed_at: DateTime<Utc>,
+    invalidated: bool,
+}
+
+impl SplitCountsEntry {
+    fn fresh(mailbox_id: String, counts: HashMap<String, u32>) -> Self {
+        Self {
+            mailbox_id,
+            counts,
+            computed_at: Utc::now(),
+            invalidated: false,
+        }
+    }
+}
+
+/// Last-known split-counts with their age, as served by `/api/split-counts`.
+#[derive(Debug, Clone)]
+pub struct CachedSplitCounts {
+    pub counts: HashMap<String, u32>,
+    pub computed_at: DateTime<Utc>,
+    /// A mutation or splits edit happened since `computed_at`.
+    pub invalidated: bool,
+}
+
+impl CachedSplitCounts {
+    pub fn stale_seconds(&self, now: DateTime<Utc>) -> i64 {
+        (now - self.computed_at).num_seconds().max(0)
+    }
+}
+
+/// Default memory budget for cached bodies across all accounts, when the
+/// config sets no `body-cache-mb`. Roughly 2000 typical HTML newsletters.
+pub const DEFAULT_BODY_CACHE_BYTES: usize = 128 * 1024 * 1024;
+
+/// Counters and occupancy for the body cache, served by `/metrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BodyCacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub capacity_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub evicted_bytes: u64,
+}
+
+struct BodySlot {
+    email: Email,
+    bytes: usize,
+    tick: u64,
+}
+
+/// Size-aware LRU over every account's cached bodies, keyed by (account,
+/// email id). The budget is in estimated bytes rather than entries: one
+/// 2 MB newsletter should push out a lot more than one 3 KB reply. `order`
+/// maps a monotonically increasing use tick to its key, so the first entry
+/// is always the least recently used.
+struct BodyLru {
+    slots: HashMap<(String, String), BodySlot>,
+    order: std::collections::BTreeMap<u64, (String, String)>,
+    next_tick: u64,
+    stats: BodyCacheStats,
+}
+
+impl BodyLru {
+    fn new(capacity_bytes: usize) -> Self {
+        Self {
+            slots: HashMap::new(),
+            order: std::collections::BTreeMap::new(),
+            next_tick: 0,
+            stats: BodyCacheStats {
+                capacity_bytes,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    fn get(&mut self, account: &str, email_id: &str) -> Option<Email> {
+        let key = (account.to_string(), email_id.to_string());
+        let tick = self.tick();
+        let Some(slot) = self.slots.get_mut(&key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.order.remove(&slot.tick);
+        slot.tick = tick;
+        self.order.insert(tick, key);
+        Some(slot.email.clone())
+    }
+
+    /// Insert as most recently used, then evict from the cold end until the
+    /// budget holds. A body larger than the whole budget is not cached.
+    fn insert(&mut self, account: &str, email_id: String, email: Email) {
+        let key = (account.to_string(), email_id);
+        self.remove(&key);
+        let bytes = approx_email_bytes(&email);
+        if bytes > self.stats.capacity_bytes {
+            return;
+        }
+        let tick = self.tick();
+        self.order.insert(tick, key.clone());
+        self.slots.insert(key, BodySlot { email, bytes, tick });
+        self.stats.bytes += bytes;
+        self.evict_to_capacity();
+    }
+
+    fn remove(&mut self, key: &(String, String)) -> Option<BodySlot> {
+        let slot = self.slots.remove(key)?;
+        self.order.remove(&slot.tick);
+        self.stats.bytes -= slot.bytes;
+        Some(slot)
+    }
+
+    fn evict_to_capacity(&mut self) {
+        while self.stats.bytes > self.stats.capacity_bytes {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(slot) = self.slots.remove(&key) {
+                self.stats.bytes -= slot.bytes;
+                self.stats.evictions += 1;
+                self.stats.evicted_bytes += slot.bytes as u64;
+            }
+        }
+    }
+
+    fn set_capacity(&mut self, capacity_bytes: usize) {
+        self.stats.capacity_bytes = capacity_bytes;
+        self.evict_to_capacity();
+    }
+
+    /// Drop one account's bodies (or every account's with `None`).
+    /// Returns (entries, bytes) freed. Not counted as evictions.
+    fn clear(&mut self, account: Option<&str>) -> (usize, usize) {
+        let keys: Vec<(String, String)> = self
+            .slots
+            .keys()
+            .filter(|(a, _)| account.is_none_or(|acc| a == acc))
+            .cloned()
+            .collect();
+        let mut bytes = 0;
+        for key in &keys {
+            if let Some(slot) = self.remove(key) {
+                bytes += slot.bytes;
+            }
+        }
+        (keys.len(), bytes)
+    }
+
+    /// One account's bodies, unordered.
+    fn account_bodies(&self, account: &str) -> Vec<(&String, &Email)> {
+        self.slots
+            .iter()
+            .filter(|((a, _), _)| a == account)
+            .map(|((_, id), slot)| (id, &slot.email))
+            .collect()
+    }
+
+    /// Mutate a cached body in place without touching its recency or
+    /// size accounting. Returns false when the body isn't cached.
+    fn update(&mut self, account: &str, email_id: &str, f: impl FnOnce(&mut Email)) -> bool {
+        let key = (account.to_string(), email_id.to_string());
+        match self.slots.get_mut(&key) {
+            Some(slot) => {
+                f(&mut slot.email);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn stats(&self) -> BodyCacheStats {
+        BodyCacheStats {
+            entries: self.slots.len(),
+            ..self.stats
+        }
+    }
+}
+
+/// Rough heap footprint of a cached email: its strings plus the struct
+/// itself. An estimate, not an accounting — good enough to keep the
+/// budget's order of magnitude honest.
+fn approx_email_bytes(e: &Email) -> usize {
+    let addrs = |v: &[crate::types::EmailAddress]| -> usize {
+        v.iter()
+            .map(|a| a.email.len() + a.name.as_deref().map_or(0, str::len))
+            .sum()
+    };
+    std::mem::size_of::<Email>()
+        + e.id.len()
+        + e.blob_id.len()
+        + e.thread_id.len()
+        + e.subject.len()
+        + e.preview.len()
+        + e.text_body.as_deref().map_or(0, str::len)
+        + e.html_body.as_deref().map_or(0, str::len)
+        + e.in_reply_to.as_deref().map_or(0, str::len)
+        + e.list_unsubscribe.as_deref().map_or(0, str::len)
+        + addrs(&e.from)
+        + addrs(&e.to)
+        + addrs(&e.cc)
+        + e.mailbox_ids.keys().map(String::len).sum::<usize>()
+        + e.keywords.keys().map(String::len).sum::<usize>()
+        + e.attachments
+            .iter()
+            .map(|a| a.name.len() + a.blob_id.len() + a.mime_type.len())
+            .sum::<usize>()
+        + e.body_parts.len() * std::mem::size_of::<crate::types::BodyPart>()
+}
+
 /// Per-account cache. `inbox_lists` is a map (one entry per (mailbox, limit))
 /// so the warmer can warm Inbox / Archive / Sent / labels in parallel without
-/// evicting each other. `body_cache` is a flat-by-id store for individual
-/// email bodies — fed both by the warmer's top-N prefetch and by `get_email`
-/// route hits, keyed by provider message id (unique within an account, so no
-/// account-id prefix needed).
+/// evicting each other. Bodies live outside the entry, in the cache-wide
+/// [`BodyLru`], so a single byte budget covers every account.
 #[derive(Default)]
 struct AccountEntry {
     mailboxes: Option<Vec<Mailbox>>,
     identities: Option<Vec<Identity>>,
     inbox_lists: HashMap<InboxKey, InboxList>,
-    split_counts: Option<(String, HashMap<String, u32>)>,
-    body_cache: HashMap<String, Email>,
+    split_counts: Option<SplitCountsEntry>,
+    /// A background split-counts recompute is in flight; see
+    /// `begin_split_counts_refresh`.
+    split_counts_refreshing: bool,
     /// Monotonic version bumped on every `invalidate`. The warmer snapshots
     /// this before each provider call and discards its result if the version
     /// changed mid-flight — otherwise a slow in-flight refresh could
@@ -77,15 +305,41 @@ struct AccountEntry {
 
 pub struct PrefetchCache {
     inner: RwLock<HashMap<String, Arc<Mutex<AccountEntry>>>>,
+    /// Email bodies, fed both by the warmer's top-N prefetch and by
+    /// `get_email` route hits. Sync lock: never held across `.await`. When
+    /// both are needed, the account entry is locked first.
+    bodies: std::sync::Mutex<BodyLru>,
 }
 
 impl PrefetchCache {
     pub fn new() -> Self {
         Self {
             inner: RwLock::new(HashMap::new()),
+            bodies: std::sync::Mutex::new(BodyLru::new(DEFAULT_BODY_CACHE_BYTES)),
         }
     }
 
+    fn bodies(&self) -> std::sync::MutexGuard<'_, BodyLru> {
+        self.bodies.lock().expect("body cache lock poisoned")
+    }
+
+    /// Change the body budget (from `body-cache-mb`), evicting down to it.
+    pub fn set_body_cache_capacity(&self, capacity_bytes: usize) {
+        self.bodies().set_capacity(capacity_bytes);
+    }
+
+    pub fn body_cache_stats(&self) -> BodyCacheStats {
+        self.bodies().stats()
+    }
+
+    /// Drop cached bodies for one account, or for all with `None`, as
+    /// `/api/cache/clear` does. Returns (bodies, bytes) freed. Lists and
+    /// counts are left alone; the warmer refills the bodies on its next
+    /// pass.
+    pub fn clear_bodies(&self, account: Option<&str>) -> (usize, usize) {
+        self.bodies().clear(account)
+    }
+
     async fn entry(&self, account: &str) -> Arc<Mutex<AccountEntry>> {
         {
             let r = self.inner.read().await;
@@ -136,14 +390,35 @@ impl PrefetchCache {
         );
     }
 
+    /// Record what a download sniffed for one of a cached email's
+    /// attachments, so the next `get_email` reports it. No-op when the
+    /// body isn't cached.
+    pub fn note_sniffed_attachment(
+        &self,
+        account: &str,
+        email_id: &str,
+        blob_id: &str,
+        sniffed: &str,
+        mismatch: bool,
+    ) {
+        self.bodies().update(account, email_id, |email| {
+            for att in email
+                .attachments
+                .iter_mut()
+                .filter(|a| a.blob_id == blob_id)
+            {
+                att.sniffed_mime_type = Some(sniffed.to_string());
+                att.mime_mismatch = mismatch;
+            }
+        });
+    }
+
     pub async fn get_body(&self, account: &str, email_id: &str) -> Option<Email> {
-        let entry = self.entry(account).await;
-        entry.lock().await.body_cache.get(email_id).cloned()
+        self.bodies().get(account, email_id)
     }
 
     pub async fn set_body(&self, account: &str, email_id: String, email: Email) {
-        let entry = self.entry(account).await;
-        entry.lock().await.body_cache.insert(email_id, email);
+        self.bodies().insert(account, email_id, email);
     }
 
     pub async fn get_split_counts(
@@ -154,11 +429,50 @@ impl PrefetchCache {
         let entry = self.entry(account).await;
         let guard = entry.lock().await;
         match &guard.split_counts {
-            Some((m, c)) if m == mailbox_id => Some(c.clone()),
+            Some(c) if c.mailbox_id == mailbox_id && !c.invalidated => Some(c.counts.clone()),
             _ => None,
         }
     }
 
+    /// Last-known counts for `mailbox_id` even when invalidated, with their
+    /// age, so the route can answer instantly and refresh behind it.
+    pub async fn cached_split_counts(
+        &self,
+        account: &str,
+        mailbox_id: &str,
+    ) -> Option<CachedSplitCounts> {
+        let entry = self.entry(account).await;
+        let guard = entry.lock().await;
+        guard
+            .split_counts
+            .as_ref()
+            .filter(|c| c.mailbox_id == mailbox_id)
+            .map(|c| CachedSplitCounts {
+                counts: c.counts.clone(),
+                computed_at: c.computed_at,
+                invalidated: c.invalidated,
+            })
+    }
+
+    /// Claim the account's background split-counts refresh. Returns the
+    /// version to pass to `try_set_split_counts`, or `None` when a refresh
+    /// is already running; the claimant must call
+    /// `end_split_counts_refresh` whatever the outcome.
+    pub async fn begin_split_counts_refresh(&self, account: &str) -> Option<u64> {
+        let entry = self.entry(account).await;
+        let mut e = entry.lock().await;
+        if e.split_counts_refreshing {
+            return None;
+        }
+        e.split_counts_refreshing = true;
+        Some(e.version)
+    }
+
+    pub async fn end_split_counts_refresh(&self, account: &str) {
+        let entry = self.entry(account).await;
+        entry.lock().await.split_counts_refreshing = false;
+    }
+
     pub async fn set_split_counts(
         &self,
         account: &str,
@@ -166,10 +480,11 @@ impl PrefetchCache {
         counts: HashMap<String, u32>,
     ) {
         let entry = self.entry(account).await;
-        entry.lock().await.split_counts = Some((mailbox_id, counts));
+        entry.lock().await.split_counts = Some(SplitCountsEntry::fresh(mailbox_id, counts));
     }
 
-    /// Clears all four cached fields and bumps the version counter. Called
+    /// Clears all four cached fields (split-counts are only flagged, see
+    /// `SplitCountsEntry`) and bumps the version counter. Called
     /// from mutation routes (archive / mark-read / delete / move / star) so
     /// the next read repopulates from the live provider instead of serving
     /// pre-mutation data.
@@ -187,19 +502,19 @@ impl PrefetchCache {
         e.mailboxes = None;
         e.identities = None;
         e.inbox_lists.clear();
-        e.split_counts = None;
-        // body_cache deliberately survives: per-mutation invalidates fire
+        mark_split_counts_invalidated(&mut e);
+        // Bodies deliberately survive: per-mutation invalidates fire
         // on every mark-read / archive / flag-toggle, but the email's
         // text/html content doesn't change with those operations. The
         // frontend's emailCache (a50f1f8) carries the optimistically-
         // updated metadata for any email the user has touched, so stale
-        // keywords in body_cache don't reach the UI. Wholesale-wiping
+        // keywords in cached bodies don't reach the UI. Wholesale-wiping
         // bodies on every read action would turn the cache into a one-
         // shot buffer that the next mutation always drains.
         e.version = e.version.wrapping_add(1);
     }
 
-    /// Wholesale-clear, including body_cache. Use only for "the account
+    /// Wholesale-clear, including the account's bodies. Use only for "the account
     /// was removed / tokens were revoked" type events, where keeping any
     /// previous content would be a leak rather than a freshness issue.
     pub async fn invalidate_full(&self, account: &str) {
@@ -209,12 +524,12 @@ impl PrefetchCache {
         e.identities = None;
         e.inbox_lists.clear();
         e.split_counts = None;
-        e.body_cache.clear();
+        self.bodies().clear(Some(account));
         e.version = e.version.wrapping_add(1);
     }
 
-    /// Clears only the cached split-counts, leaving mailboxes / identities /
-    /// inbox_lists / body_cache untouched. Splits CRUD writes touch exactly
+    /// Flags only the cached split-counts, leaving mailboxes / identities /
+    /// inbox_lists / bodies untouched. Splits CRUD writes touch exactly
     /// this one derived field; a full `invalidate` would also discard
     /// perfectly fresh mailbox/inbox data for no reason. Still bumps the
     /// version counter so an in-flight warmer fetch computed against the
@@ -223,7 +538,7 @@ impl PrefetchCache {
     pub async fn invalidate_split_counts(&self, account: &str) {
         let entry = self.entry(account).await;
         let mut e = entry.lock().await;
-        e.split_counts = None;
+        mark_split_counts_invalidated(&mut e);
         e.version = e.version.wrapping_add(1);
     }
 
@@ -305,11 +620,11 @@ impl PrefetchCache {
         email: Email,
     ) -> bool {
         let entry = self.entry(account).await;
-        let mut e = entry.lock().await;
+        let e = entry.lock().await;
         if e.version != expected_version {
             return false;
         }
-        e.body_cache.insert(email_id, email);
+        self.bodies().insert(account, email_id, email);
         true
     }
 
@@ -325,7 +640,7 @@ impl PrefetchCache {
         if e.version != expected_version {
             return false;
         }
-        e.split_counts = Some((mailbox_id, counts));
+        e.split_counts = Some(SplitCountsEntry::fresh(mailbox_id, counts));
         true
     }
 
@@ -435,6 +750,14 @@ impl PrefetchCache {
     }
 }
 
+/// Flag the cached split-counts as out of date without dropping them (see
+/// `SplitCountsEntry`).
+fn mark_split_counts_invalidated(e: &mut AccountEntry) {
+    if let Some(c) = e.split_counts.as_mut() {
+        c.invalidated = true;
+    }
+}
+
 impl Default for PrefetchCache {
     fn default() -> Self {
         Self::new()
@@ -454,10 +777,9 @@ impl Default for PrefetchCache {
 // entries within its first pass.
 
 /// Newest bodies kept per account in the snapshot, ranked by `received_at`.
-/// `body_cache` has no eviction in memory (bounded per warm cycle by
-/// `BODY_PREFETCH_PER_MAILBOX`), but persisting it verbatim would compound
-/// across restarts; the cap keeps the file to roughly what one warm cycle
-/// produces anyway.
+/// The in-memory cache is bounded by bytes (`BodyLru`), but a full budget's
+/// worth of JSON would make every save slow; the cap keeps the file to
+/// roughly what one warm cycle produces anyway.
 const SNAPSHOT_BODY_CAP: usize = 500;
 
 /// Serialized form of one account's cache entry. `inbox_lists` is a Vec of
@@ -470,6 +792,9 @@ struct AccountSnapshot {
     identities: Option<Vec<Identity>>,
     inbox_lists: Vec<(InboxKey, Vec<Email>)>,
     split_counts: Option<(String, HashMap<String, u32>)>,
+    /// When `split_counts` was computed; absent in pre-timestamp snapshots.
+    #[serde(default)]
+    split_counts_at: Option<DateTime<Utc>>,
     body_cache: HashMap<String, Email>,
 }
 
@@ -477,35 +802,57 @@ impl PrefetchCache {
     /// Snapshot every account entry to `path` (JSON, 0600, tmp + rename so
     /// a crash mid-write can't leave a torn file). Called by the warmer
     /// after each account pass — mail bodies are user data, so the file
-    /// gets the same permissions treatment as the token store.
-    pub async fn save_to_disk(&self, path: &std::path::Path) -> Result<(), Error> {
-        let mut snapshot: HashMap<String, AccountSnapshot> = HashMap::new();
+    /// gets the same permissions treatment as the token store. Accounts
+    /// `at_rest` encrypts are stored as a sealed base64 string, or left
+    /// out when there's no key to seal them with.
+    pub async fn save_to_disk(
+        &self,
+        path: &std::path::Path,
+        at_rest: &AtRest,
+    ) -> Result<(), Error> {
+        let mut snapshot: HashMap<String, serde_json::Value> = HashMap::new();
         let entries: Vec<(String, Arc<Mutex<AccountEntry>>)> = {
             let r = self.inner.read().await;
             r.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
         };
         for (account, entry) in entries {
             let e = entry.lock().await;
-            let mut bodies: Vec<(&String, &Email)> = e.body_cache.iter().collect();
+            let lru = self.bodies();
+            let mut bodies = lru.account_bodies(&account);
             bodies.sort_by_key(|b| std::cmp::Reverse(b.1.received_at));
-            snapshot.insert(
-                account,
-                AccountSnapshot {
-                    mailboxes: e.mailboxes.clone(),
-                    identities: e.identities.clone(),
-                    inbox_lists: e
-                        .inbox_lists
-                        .iter()
-                        .map(|(k, l)| (k.clone(), l.emails.clone()))
-                        .collect(),
-                    split_counts: e.split_counts.clone(),
-                    body_cache: bodies
-                        .into_iter()
-                        .take(SNAPSHOT_BODY_CAP)
-                        .map(|(id, email)| (id.clone(), email.clone()))
-                        .collect(),
-                },
-            );
+            let account_snapshot = AccountSnapshot {
+                mailboxes: e.mailboxes.clone(),
+                identities: e.identities.clone(),
+                inbox_lists: e
+                    .inbox_lists
+                    .iter()
+                    .map(|(k, l)| (k.clone(), l.emails.clone()))
+                    .collect(),
+                split_counts: e
+                    .split_counts
+                    .as_ref()
+                    .map(|c| (c.mailbox_id.clone(), c.counts.clone())),
+                split_counts_at: e.split_counts.as_ref().map(|c| c.computed_at),
+                body_cache: bodies
+                    .into_iter()
+                    .take(SNAPSHOT_BODY_CAP)
+                    .map(|(id, email)| (id.clone(), email.clone()))
+                    .collect(),
+            };
+            let value = if at_rest.encrypts(&account) {
+                let plain = serde_json::to_vec(&account_snapshot).map_err(|e| {
+                    Error::Internal(format!("Failed to serialize prefetch snapshot: {e}"))
+                })?;
+                let Some(sealed) = at_rest.seal(&account, &plain) else {
+                    continue;
+                };
+                serde_json::Value::String(B64.encode(sealed))
+            } else {
+                serde_json::to_value(&account_snapshot).map_err(|e| {
+                    Error::Internal(format!("Failed to serialize prefetch snapshot: {e}"))
+                })?
+            };
+            snapshot.insert(account, value);
         }
 
         let json = serde_json::to_vec(&snapshot)
@@ -521,14 +868,19 @@ impl PrefetchCache {
     /// inbox list stale. Restores only accounts in `allowed_accounts` so a
     /// removed account's mail doesn't outlive its configuration. Any
     /// read/parse failure just means starting cold — exactly the pre-
-    /// persistence behavior.
-    pub fn load_from_disk(path: &std::path::Path, allowed_accounts: &[String]) -> Self {
+    /// persistence behavior, as does an account whose entry `at_rest`
+    /// can't open.
+    pub fn load_from_disk(
+        path: &std::path::Path,
+        allowed_accounts: &[String],
+        at_rest: &AtRest,
+    ) -> Self {
         let cache = Self::new();
         let raw = match std::fs::read(path) {
             Ok(b) => b,
             Err(_) => return cache, // no snapshot yet — normal on first run
         };
-        let snapshot: HashMap<String, AccountSnapshot> = match serde_json::from_slice(&raw) {
+        let snapshot: HashMap<String, serde_json::Value> = match serde_json::from_slice(&raw) {
             Ok(s) => s,
             Err(e) => {
                 tracing::warn!(
@@ -540,11 +892,21 @@ impl PrefetchCache {
         };
         let mut restored = 0usize;
         let mut inner = HashMap::new();
-        for (account, snap) in snapshot {
+        let mut restored_bodies: Vec<(String, String, Email)> = Vec::new();
+        for (account, value) in snapshot {
             if !allowed_accounts.contains(&account) {
                 continue;
             }
+            let Some(snap) = open_account_snapshot(&account, value, at_rest) else {
+                tracing::warn!("prefetch: skipping unreadable snapshot entry for {account}");
+                continue;
+            };
             restored += 1;
+            restored_bodies.extend(
+                snap.body_cache
+                    .into_iter()
+                    .map(|(id, email)| (account.clone(), id, email)),
+            );
             inner.insert(
                 account,
                 Arc::new(Mutex::new(AccountEntry {
@@ -563,8 +925,17 @@ impl PrefetchCache {
                             )
                         })
                         .collect(),
-                    split_counts: snap.split_counts,
-                    body_cache: snap.body_cache,
+                    // An unknown age reads as "as old as it gets", so the
+                    // UI treats it as stale rather than fresh.
+                    split_counts: snap
+                        .split_counts
+                        .map(|(mailbox_id, counts)| SplitCountsEntry {
+                            mailbox_id,
+                            counts,
+                            computed_at: snap.split_counts_at.unwrap_or(DateTime::UNIX_EPOCH),
+                            invalidated: false,
+                        }),
+                    split_counts_refreshing: false,
                     version: 0,
                 })),
             );
@@ -576,12 +947,33 @@ impl PrefetchCache {
                 path.display()
             );
         }
+        // Oldest first, so the newest bodies end up most recently used.
+        restored_bodies.sort_by_key(|b| b.2.received_at);
+        let mut bodies = BodyLru::new(DEFAULT_BODY_CACHE_BYTES);
+        for (account, id, email) in restored_bodies {
+            bodies.insert(&account, id, email);
+        }
         Self {
             inner: RwLock::new(inner),
+            bodies: std::sync::Mutex::new(bodies),
         }
     }
 }
 
+/// One account's entry as [`PrefetchCache::save_to_disk`] wrote it:
+/// an object in the clear, or a sealed base64 string.
+fn open_account_snapshot(
+    account: &str,
+    value: serde_json::Value,
+    at_rest: &AtRest,
+) -> Option<AccountSnapshot> {
+    let bytes = match value {
+        serde_json::Value::String(sealed) => B64.decode(sealed).ok()?,
+        plain => serde_json::to_vec(&plain).ok()?,
+    };
+    serde_json::from_slice(&at_rest.open(account, bytes)?).ok()
+}
+
 /// Write with 0600 on Unix — snapshots contain mail bodies. Mirrors the
 /// token store's `write_token_file`.
 fn write_snapshot_file(path: &std::path::Path, contents: &[u8]) -> Result<(), Error> {
@@ -631,6 +1023,11 @@ pub async fn prefetch_account(state: Arc<crate::types::AppState>, account_id: &s
     // between phases so an un-invalidated phase can still land.
     let v = cache.version(account_id).await;
 
+    // --- on-disk email cache ---
+    if let Err(e) = sync_email_cache(&state, account_id).await {
+        tracing::warn!(account = %account_id, "prefetch: email cache sync failed: {e}");
+    }
+
     // --- mailboxes ---
     let mailboxes = match fetch_mailboxes(&state, account_id).await {
         Ok(m) => {
@@ -964,6 +1361,12 @@ async fn fetch_bodies(
     crate::provider::get_emails_chunked(&session_lock, ids, true, None, 10).await
 }
 
+async fn sync_email_cache(state: &crate::types::AppState, account_id: &str) -> Result<(), Error> {
+    let session_lock = session_for(state, account_id).await?;
+    let session = session_lock.read().await;
+    state.email_cache.sync(&session, account_id).await
+}
+
 async fn fetch_mailboxes(
     state: &crate::types::AppState,
     account_id: &str,
@@ -1012,24 +1415,58 @@ async fn fetch_inbox(
     .await
 }
 
+/// Counts older than this are refreshed in the background when
+/// `/api/split-counts` serves them, on top of the warmer's own cycle.
+pub const SPLIT_COUNTS_REFRESH_AFTER_SECS: i64 = 300;
+
+/// Recompute one account's split-counts off the request path. At most one
+/// refresh runs per account; a mutation landing mid-flight bumps the
+/// version and the result is discarded, leaving the entry invalidated for
+/// the next read to retry.
+pub fn spawn_split_counts_refresh(
+    state: Arc<crate::types::AppState>,
+    account_id: String,
+    mailbox_id: String,
+) {
+    tokio::spawn(async move {
+        let Some(v) = state.prefetch.begin_split_counts_refresh(&account_id).await else {
+            return;
+        };
+        match fetch_split_counts(&state, &account_id, &mailbox_id).await {
+            Ok(counts) => {
+                if !state
+                    .prefetch
+                    .try_set_split_counts(&account_id, v, mailbox_id, counts)
+                    .await
+                {
+                    tracing::debug!(
+                        account = %account_id,
+                        "prefetch: split-counts refresh discarded — version changed mid-fetch"
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(account = %account_id, "prefetch: split-counts refresh failed: {e}")
+            }
+        }
+        state.prefetch.end_split_counts_refresh(&account_id).await;
+    });
+}
+
 async fn fetch_split_counts(
     state: &crate::types::AppState,
     account_id: &str,
     mailbox_id: &str,
 ) -> Result<HashMap<String, u32>, Error> {
-    let config = crate::splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    )
-    .scoped_to(Some(account_id));
-    if config.splits.is_empty() {
+    let matchers = state.splits.compiled().scoped_to(Some(account_id));
+    if matchers.is_empty() {
         return Ok(HashMap::new());
     }
     // Delegate to the same function the `/api/split-counts` handler
     // calls — drift between warmer and route would mean the cached
     // value disagrees with what the route would have produced on a
     // miss, which then flips visibly to the user every invalidate.
-    crate::routes::compute_split_counts(state, Some(account_id), mailbox_id, &config, None).await
+    crate::routes::compute_split_counts(state, Some(account_id), mailbox_id, &matchers, None).await
 }
 
 async fn session_for(
@@ -1098,6 +1535,8 @@ pub fn spawn_warmer(
     let pass_done = std::sync::Arc::new(AtomicUsize::new(0));
     let total_for_list = pass_total.clone();
     let done_for_list = pass_done.clone();
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("prefetch-warmer", interval);
     tokio::spawn(async move {
         warm_loop(
             move || {
@@ -1105,6 +1544,7 @@ pub fn spawn_warmer(
                 let total = total_for_list.clone();
                 let done = done_for_list.clone();
                 async move {
+                    health.ticked("prefetch-warmer");
                     let reg = s.accounts.read().await;
                     let default = reg.default_account.clone();
                     let mut ids = reg.sessions.keys().cloned().collect::<Vec<_>>();
@@ -1128,7 +1568,10 @@ pub fn spawn_warmer(
                     prefetch_account(s.clone(), &account).await;
                     let done = pass_done.fetch_add(1, Ordering::SeqCst) + 1;
                     if (done == 1 || done == pass_total.load(Ordering::SeqCst))
-                        && let Err(e) = s.prefetch.save_to_disk(&s.prefetch_cache_path).await
+                        && let Err(e) = s
+                            .prefetch
+                            .save_to_disk(&s.prefetch_cache_path, &s.at_rest)
+                            .await
                     {
                         tracing::warn!(
                             account = %account,
@@ -1186,6 +1629,12 @@ mod tests {
             has_calendar: false,
             attachments: vec![],
             in_reply_to: None,
+            body_parts: Vec::new(),
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
         }
     }
 
@@ -1859,6 +2308,115 @@ mod tests {
         assert_eq!(cache.version("acc-1").await, v0 + 1);
     }
 
+    fn body_of(id: &str, len: usize) -> Email {
+        Email {
+            html_body: Some("x".repeat(len)),
+            ..email(id)
+        }
+    }
+
+    #[tokio::test]
+    async fn body_cache_evicts_least_recently_used_by_bytes() {
+        let cache = PrefetchCache::new();
+        let one = approx_email_bytes(&body_of("m1", 1000));
+        cache.set_body_cache_capacity(one * 2);
+        cache
+            .set_body("acc-1", "m1".into(), body_of("m1", 1000))
+            .await;
+        cache
+            .set_body("acc-2", "m2".into(), body_of("m2", 1000))
+            .await;
+        // Touch m1 so m2 is the cold end when m3 pushes the cache over.
+        assert!(cache.get_body("acc-1", "m1").await.is_some());
+        cache
+            .set_body("acc-1", "m3".into(), body_of("m3", 1000))
+            .await;
+
+        assert!(cache.get_body("acc-2", "m2").await.is_none());
+        assert!(cache.get_body("acc-1", "m1").await.is_some());
+        assert!(cache.get_body("acc-1", "m3").await.is_some());
+        let stats = cache.body_cache_stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.bytes, one * 2);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.evicted_bytes, one as u64);
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn body_cache_is_bounded_by_bytes_not_entries() {
+        let cache = PrefetchCache::new();
+        let small = approx_email_bytes(&body_of("s", 10));
+        cache.set_body_cache_capacity(small * 10);
+        for i in 0..10 {
+            cache
+                .set_body("acc-1", format!("s{i}"), body_of("s", 10))
+                .await;
+        }
+        assert_eq!(cache.body_cache_stats().entries, 10);
+        // One large body displaces several small ones.
+        cache
+            .set_body("acc-1", "big".into(), body_of("big", small * 4))
+            .await;
+        let stats = cache.body_cache_stats();
+        assert!(stats.entries < 10, "{stats:?}");
+        assert!(stats.bytes <= stats.capacity_bytes);
+    }
+
+    #[tokio::test]
+    async fn body_larger_than_budget_is_not_cached() {
+        let cache = PrefetchCache::new();
+        cache.set_body_cache_capacity(1024);
+        cache
+            .set_body("acc-1", "m1".into(), body_of("m1", 4096))
+            .await;
+        assert!(cache.get_body("acc-1", "m1").await.is_none());
+        assert_eq!(cache.body_cache_stats().bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn shrinking_capacity_evicts_and_replacing_recounts() {
+        let cache = PrefetchCache::new();
+        cache
+            .set_body("acc-1", "m1".into(), body_of("m1", 1000))
+            .await;
+        cache
+            .set_body("acc-1", "m1".into(), body_of("m1", 10))
+            .await;
+        assert_eq!(
+            cache.body_cache_stats().bytes,
+            approx_email_bytes(&body_of("m1", 10))
+        );
+        cache.set_body_cache_capacity(0);
+        let stats = cache.body_cache_stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (0, 0, 1));
+    }
+
+    #[tokio::test]
+    async fn note_sniffed_attachment_updates_cached_body() {
+        let cache = PrefetchCache::new();
+        let mut e = email("m1");
+        e.attachments.push(crate::types::Attachment {
+            blob_id: "b1".into(),
+            name: "scan".into(),
+            mime_type: "application/octet-stream".into(),
+            size: 10,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
+        });
+        cache.set_body("acc-1", "m1".into(), e).await;
+        cache.note_sniffed_attachment("acc-1", "m1", "b1", "application/pdf", true);
+        cache.note_sniffed_attachment("acc-1", "missing", "b1", "image/png", true);
+
+        let got = cache.get_body("acc-1", "m1").await.unwrap();
+        assert_eq!(
+            got.attachments[0].sniffed_mime_type.as_deref(),
+            Some("application/pdf")
+        );
+        assert!(got.attachments[0].mime_mismatch);
+    }
+
     #[tokio::test]
     async fn invalidate_clears_all_fields_and_bumps_version() {
         let cache = PrefetchCache::new();
@@ -1925,6 +2483,58 @@ mod tests {
         assert_eq!(cache.version("acc-1").await, v0 + 1);
     }
 
+    #[tokio::test]
+    async fn invalidated_split_counts_stay_readable_with_their_age() {
+        let cache = PrefetchCache::new();
+        let mut counts = HashMap::new();
+        counts.insert("split-a".into(), 4);
+        cache
+            .set_split_counts("acc-1", "inbox".into(), counts)
+            .await;
+        cache.invalidate("acc-1").await;
+
+        let cached = cache.cached_split_counts("acc-1", "inbox").await.unwrap();
+        assert!(cached.invalidated);
+        assert_eq!(cached.counts.get("split-a"), Some(&4));
+        assert!(cached.stale_seconds(Utc::now()) < 5);
+        assert!(cache.cached_split_counts("acc-1", "other").await.is_none());
+
+        // A fresh write clears the flag.
+        let v = cache.version("acc-1").await;
+        assert!(
+            cache
+                .try_set_split_counts("acc-1", v, "inbox".into(), HashMap::new())
+                .await
+        );
+        assert!(
+            !cache
+                .cached_split_counts("acc-1", "inbox")
+                .await
+                .unwrap()
+                .invalidated
+        );
+    }
+
+    #[tokio::test]
+    async fn invalidate_full_drops_split_counts() {
+        let cache = PrefetchCache::new();
+        cache
+            .set_split_counts("acc-1", "inbox".into(), HashMap::new())
+            .await;
+        cache.invalidate_full("acc-1").await;
+        assert!(cache.cached_split_counts("acc-1", "inbox").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn split_counts_refresh_is_single_flight() {
+        let cache = PrefetchCache::new();
+        let v = cache.begin_split_counts_refresh("acc-1").await;
+        assert_eq!(v, Some(cache.version("acc-1").await));
+        assert!(cache.begin_split_counts_refresh("acc-1").await.is_none());
+        cache.end_split_counts_refresh("acc-1").await;
+        assert!(cache.begin_split_counts_refresh("acc-1").await.is_some());
+    }
+
     #[tokio::test]
     async fn set_then_get_split_counts_roundtrip() {
         let cache = PrefetchCache::new();
@@ -1946,6 +2556,32 @@ mod tests {
         ))
     }
 
+    #[tokio::test]
+    async fn snapshot_keeps_split_counts_age() {
+        let cache = PrefetchCache::new();
+        cache
+            .set_split_counts("acc-1", "inbox".into(), HashMap::new())
+            .await;
+        let computed_at = cache
+            .cached_split_counts("acc-1", "inbox")
+            .await
+            .unwrap()
+            .computed_at;
+
+        let path = snapshot_path("split-age");
+        cache.save_to_disk(&path, &AtRest::default()).await.unwrap();
+        let restored =
+            PrefetchCache::load_from_disk(&path, &["acc-1".to_string()], &AtRest::default());
+        std::fs::remove_file(&path).ok();
+
+        let cached = restored
+            .cached_split_counts("acc-1", "inbox")
+            .await
+            .unwrap();
+        assert_eq!(cached.computed_at, computed_at);
+        assert!(!cached.invalidated);
+    }
+
     #[tokio::test]
     async fn snapshot_roundtrip_restores_data_and_marks_lists_stale() {
         let cache = PrefetchCache::new();
@@ -1967,8 +2603,9 @@ mod tests {
             .await;
 
         let path = snapshot_path("roundtrip");
-        cache.save_to_disk(&path).await.unwrap();
-        let restored = PrefetchCache::load_from_disk(&path, &["acc-1".to_string()]);
+        cache.save_to_disk(&path, &AtRest::default()).await.unwrap();
+        let restored =
+            PrefetchCache::load_from_disk(&path, &["acc-1".to_string()], &AtRest::default());
         std::fs::remove_file(&path).ok();
 
         // Everything comes back...
@@ -2039,8 +2676,9 @@ mod tests {
         cache.set_mailboxes("acc-removed", vec![mb("b")]).await;
 
         let path = snapshot_path("skip-removed");
-        cache.save_to_disk(&path).await.unwrap();
-        let restored = PrefetchCache::load_from_disk(&path, &["acc-keep".to_string()]);
+        cache.save_to_disk(&path, &AtRest::default()).await.unwrap();
+        let restored =
+            PrefetchCache::load_from_disk(&path, &["acc-keep".to_string()], &AtRest::default());
         std::fs::remove_file(&path).ok();
 
         assert!(restored.get_mailboxes("acc-keep").await.is_some());
@@ -2050,17 +2688,52 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn snapshot_seals_encrypted_accounts() {
+        let cache = PrefetchCache::new();
+        cache
+            .set_mailboxes("acc-secret", vec![mb("Hidden Folder")])
+            .await;
+        cache
+            .set_mailboxes("acc-plain", vec![mb("Open Folder")])
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let at_rest = || {
+            AtRest::configure(
+                crate::at_rest::Selection::parse("acc-secret"),
+                || Some("pass".into()),
+                &dir.path().join("cache-key.json"),
+            )
+        };
+        let accounts = ["acc-secret".to_string(), "acc-plain".to_string()];
+
+        let path = dir.path().join("prefetch-cache.json");
+        cache.save_to_disk(&path, &at_rest()).await.unwrap();
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("Hidden Folder"));
+        assert!(raw.contains("Open Folder"));
+
+        let restored = PrefetchCache::load_from_disk(&path, &accounts, &at_rest());
+        assert!(restored.get_mailboxes("acc-secret").await.is_some());
+        // Without the key only the sealed account starts cold.
+        let keyless = PrefetchCache::load_from_disk(&path, &accounts, &AtRest::default());
+        assert!(keyless.get_mailboxes("acc-secret").await.is_none());
+        assert!(keyless.get_mailboxes("acc-plain").await.is_some());
+    }
+
     #[tokio::test]
     async fn snapshot_load_missing_or_garbage_file_starts_cold() {
         let missing = PrefetchCache::load_from_disk(
             std::path::Path::new("/nonexistent/prefetch.json"),
             &["acc-1".to_string()],
+            &AtRest::default(),
         );
         assert!(missing.get_mailboxes("acc-1").await.is_none());
 
         let path = snapshot_path("garbage");
         std::fs::write(&path, b"not json{{{").unwrap();
-        let garbage = PrefetchCache::load_from_disk(&path, &["acc-1".to_string()]);
+        let garbage =
+            PrefetchCache::load_from_disk(&path, &["acc-1".to_string()], &AtRest::default());
         std::fs::remove_file(&path).ok();
         assert!(garbage.get_mailboxes("acc-1").await.is_none());
     }
@@ -0,0 +1,353 @@
+//! Focused-inbox priority scoring.
+//!
+//! Every list row gets a 0–100 score from cheap signals already on hand
+//! at listing time: whether I've written to the sender (the harvested
+//! correspondents in `contacts`), whether the message is addressed to me
+//! directly or reached me through a list or Bcc, whether it carries a
+//! calendar invite, and how many of the page's rows the same sender
+//! accounts for. `priority:high` filters on the bucket.
+//!
+//! On top of the heuristics, each account keeps a per-sender tally of
+//! what I did with their mail ([`SenderHistory`]): archiving it counts
+//! against the sender, replying counts for them. Once a sender has
+//! enough history, a clear lean one way overrides the heuristics. The
+//! tallies are the `priority` documents in `storage`.
+
+use crate::error::Error;
+use crate::storage::{self, DocumentStore, Namespace};
+use crate::types::{Email, Priority, PriorityLevel};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Storage subsystem holding each account's sender tallies.
+const SUBSYSTEM: &str = "priority";
+const SENDERS_KEY: &str = "senders";
+
+/// Scores at or above this are [`PriorityLevel::High`].
+pub const HIGH_THRESHOLD: u8 = 70;
+/// Scores below this are [`PriorityLevel::Low`].
+pub const LOW_THRESHOLD: u8 = 35;
+
+/// Archives plus replies before a sender's history can override the
+/// heuristics.
+pub const MIN_LEARNED_ACTIONS: u32 = 3;
+
+/// Rows from one sender in a page past which they read as bulk.
+const BULK_BATCH_COUNT: usize = 3;
+
+/// What I've done with one sender's mail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SenderHistory {
+    pub replies: u32,
+    pub archives: u32,
+}
+
+impl SenderHistory {
+    /// The level this history forces, when there's enough of it and at
+    /// least two thirds of it leans one way.
+    pub fn learned_level(&self) -> Option<PriorityLevel> {
+        let total = self.replies + self.archives;
+        if total < MIN_LEARNED_ACTIONS {
+            return None;
+        }
+        if self.replies * 3 >= total * 2 {
+            Some(PriorityLevel::High)
+        } else if self.archives * 3 >= total * 2 {
+            Some(PriorityLevel::Low)
+        } else {
+            None
+        }
+    }
+}
+
+/// One account's sender tallies, keyed by lowercased address.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SenderStats {
+    #[serde(default)]
+    pub senders: HashMap<String, SenderHistory>,
+}
+
+/// Something I did with a sender's mail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Archived,
+    Replied,
+}
+
+pub fn load_stats(store: &dyn DocumentStore, account: &str) -> SenderStats {
+    storage::get_json(store, &Namespace::account(SUBSYSTEM, account), SENDERS_KEY)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read priority history for {account}: {e}");
+            None
+        })
+        .unwrap_or_default()
+}
+
+/// Count `action` against each of `addresses` and save. Callers hold
+/// `AppState::priority_lock` across the load→save.
+pub fn record(
+    store: &dyn DocumentStore,
+    account: &str,
+    addresses: &[&str],
+    action: Action,
+) -> Result<(), Error> {
+    if addresses.is_empty() {
+        return Ok(());
+    }
+    let mut stats = load_stats(store, account);
+    for address in addresses {
+        let entry = stats
+            .senders
+            .entry(address.trim().to_ascii_lowercase())
+            .or_default();
+        match action {
+            Action::Archived => entry.archives = entry.archives.saturating_add(1),
+            Action::Replied => entry.replies = entry.replies.saturating_add(1),
+        }
+    }
+    storage::put_json(
+        store,
+        &Namespace::account(SUBSYSTEM, account),
+        SENDERS_KEY,
+        &stats,
+    )
+}
+
+/// Scores the rows of one listing. Built per request from the account's
+/// own addresses, correspondents and sender tallies.
+pub struct Scorer {
+    own: HashSet<String>,
+    /// Correspondent address → messages I sent them in the harvested sample.
+    written_to: HashMap<String, u32>,
+    learned: SenderStats,
+    /// Sender address → rows of theirs in this page.
+    batch: HashMap<String, usize>,
+}
+
+impl Scorer {
+    pub fn new<'a>(
+        own: impl IntoIterator<Item = &'a str>,
+        written_to: impl IntoIterator<Item = (&'a str, u32)>,
+        learned: SenderStats,
+        page: &[Email],
+    ) -> Self {
+        let mut batch = HashMap::new();
+        for email in page {
+            if let Some(from) = sender(email) {
+                *batch.entry(from).or_insert(0) += 1;
+            }
+        }
+        Self {
+            own: own.into_iter().map(|a| a.to_ascii_lowercase()).collect(),
+            written_to: written_to
+                .into_iter()
+                .map(|(a, n)| (a.to_ascii_lowercase(), n))
+                .collect(),
+            learned,
+            batch,
+        }
+    }
+
+    pub fn score(&self, email: &Email) -> Priority {
+        let from = sender(email);
+        if let Some(level) = from
+            .as_ref()
+            .and_then(|f| self.learned.senders.get(f))
+            .and_then(SenderHistory::learned_level)
+        {
+            let score = match level {
+                PriorityLevel::High => 90,
+                PriorityLevel::Low => 10,
+                PriorityLevel::Normal => 50,
+            };
+            return Priority {
+                score,
+                level,
+                learned: true,
+            };
+        }
+
+        let mut score: i32 = 50;
+        let written = from.as_ref().and_then(|f| self.written_to.get(f)).copied();
+        if let Some(count) = written {
+            score += 20 + count.min(10) as i32;
+        }
+        let mine = |list: &[crate::types::EmailAddress]| {
+            list.iter()
+                .any(|a| self.own.contains(&a.email.to_ascii_lowercase()))
+        };
+        if mine(&email.to) {
+            score += 15;
+        } else if mine(&email.cc) {
+            score += 5;
+        } else {
+            // Reached me through a list or Bcc.
+            score -= 15;
+        }
+        if email
+            .list_unsubscribe
+            .as_deref()
+            .is_some_and(|h| !h.is_empty())
+            || from.as_deref().is_some_and(is_automated_address)
+        {
+            score -= 15;
+        }
+        if email.has_calendar {
+            score += 15;
+        }
+        if written.is_none()
+            && from
+                .as_ref()
+                .and_then(|f| self.batch.get(f))
+                .is_some_and(|n| *n >= BULK_BATCH_COUNT)
+        {
+            score -= 10;
+        }
+
+        let score = score.clamp(0, 100) as u8;
+        Priority {
+            score,
+            level: level_for(score),
+            learned: false,
+        }
+    }
+}
+
+pub fn level_for(score: u8) -> PriorityLevel {
+    if score >= HIGH_THRESHOLD {
+        PriorityLevel::High
+    } else if score < LOW_THRESHOLD {
+        PriorityLevel::Low
+    } else {
+        PriorityLevel::Normal
+    }
+}
+
+fn sender(email: &Email) -> Option<String> {
+    email.from.first().map(|a| a.email.to_ascii_lowercase())
+}
+
+/// `noreply@`, `no-reply@`, `notifications@` and the like.
+fn is_automated_address(address: &str) -> bool {
+    let local = address.split('@').next().unwrap_or_default();
+    let local = local.replace(['-', '_', '.'], "");
+    ["noreply", "donotreply", "notification", "mailer", "bounce"]
+        .iter()
+        .any(|p| local.starts_with(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStore;
+
+    fn email(id: &str, from: &str, to: &[&str]) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "blob_id": "", "thread_id": "", "mailbox_ids": {}, "keywords": {},
+            "received_at": "2026-01-01T00:00:00Z", "subject": "",
+            "from": [{"name": null, "email": from}],
+            "to": to.iter().map(|a| serde_json::json!({"name": null, "email": a})).collect::<Vec<_>>(),
+            "cc": [], "preview": "", "has_attachment": false, "size": 0,
+            "text_body": null, "html_body": null, "has_calendar": false, "attachments": [],
+        }))
+        .unwrap()
+    }
+
+    fn scorer(written: &[(&str, u32)], learned: SenderStats, page: &[Email]) -> Scorer {
+        Scorer::new(["me@x.example"], written.iter().copied(), learned, page)
+    }
+
+    #[test]
+    fn correspondent_writing_to_me_directly_is_high() {
+        let mail = email("1", "Ann@Friends.example", &["ME@x.example"]);
+        let p = scorer(&[("ann@friends.example", 4)], SenderStats::default(), &[]).score(&mail);
+        assert_eq!(p.level, PriorityLevel::High);
+        assert!(!p.learned);
+    }
+
+    #[test]
+    fn bulk_sender_not_addressed_to_me_is_low() {
+        let page: Vec<_> = (0..3)
+            .map(|i| {
+                email(
+                    &i.to_string(),
+                    "no-reply@shop.example",
+                    &["list@shop.example"],
+                )
+            })
+            .collect();
+        let p = scorer(&[], SenderStats::default(), &page).score(&page[0]);
+        assert_eq!(p.level, PriorityLevel::Low);
+    }
+
+    #[test]
+    fn calendar_invite_lifts_a_stranger() {
+        let mut mail = email("1", "someone@corp.example", &["me@x.example"]);
+        let plain = scorer(&[], SenderStats::default(), &[]).score(&mail);
+        mail.has_calendar = true;
+        let invite = scorer(&[], SenderStats::default(), &[]).score(&mail);
+        assert_eq!(invite.score, plain.score + 15);
+        assert_eq!(invite.level, PriorityLevel::High);
+    }
+
+    #[tokio::test]
+    async fn list_row_signals_survive_a_metadata_only_fetch() {
+        // Scores run on list rows, fetched without bodies.
+        let mut invite = crate::jmap::metadata_only_invite();
+        invite["to"] = serde_json::json!([{"name": null, "email": "me@x.example"}]);
+        let mut newsletter = invite.clone();
+        newsletter["id"] = "m-news".into();
+        newsletter["header:List-Unsubscribe:asText"] = "<mailto:u@x.example>".into();
+        let rows = crate::jmap::parse_email_list_json(vec![invite, newsletter], false).await;
+        assert!(rows[0].has_calendar);
+        let s = scorer(&[], SenderStats::default(), &[]);
+        let plain = s.score(&email("p", "alice@x.example", &["me@x.example"]));
+        assert_eq!(s.score(&rows[0]).score, plain.score + 15);
+        assert_eq!(s.score(&rows[1]).score, plain.score);
+    }
+
+    #[test]
+    fn learned_history_overrides_heuristics_once_it_leans() {
+        let mut learned = SenderStats::default();
+        learned.senders.insert(
+            "ann@friends.example".into(),
+            SenderHistory {
+                replies: 0,
+                archives: 3,
+            },
+        );
+        let mail = email("1", "ann@friends.example", &["me@x.example"]);
+        let p = scorer(&[("ann@friends.example", 9)], learned, &[]).score(&mail);
+        assert_eq!(p.level, PriorityLevel::Low);
+        assert!(p.learned);
+
+        let split = SenderHistory {
+            replies: 2,
+            archives: 2,
+        };
+        assert_eq!(split.learned_level(), None);
+        let short = SenderHistory {
+            replies: 2,
+            archives: 0,
+        };
+        assert_eq!(short.learned_level(), None);
+    }
+
+    #[test]
+    fn record_accumulates_per_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().to_path_buf());
+        record(&store, "work", &["Ann@Friends.example"], Action::Replied).unwrap();
+        record(&store, "work", &["ann@friends.example"], Action::Archived).unwrap();
+        record(&store, "work", &["ann@friends.example"], Action::Replied).unwrap();
+        let stats = load_stats(&store, "work");
+        assert_eq!(
+            stats.senders["ann@friends.example"],
+            SenderHistory {
+                replies: 2,
+                archives: 1
+            }
+        );
+        assert!(load_stats(&store, "home").senders.is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Protected senders: mail that bulk operations must never move.
+//!
+//! `protected-senders.json` lives next to `groups.json`. An entry is either
+//! an exact address (`boss@example.com`) or a whole domain
+//! (`*@example.com`); both match case-insensitively. Bulk archive paths
+//! (unsubscribe-and-archive, cancelled-event cleanup) partition their ids
+//! through [`partition`] before touching the provider, so a protected
+//! message stays put even when a query or rule would have swept it up.
+//! Single-message actions the user triggers by hand are not filtered.
+
+use crate::error::Error;
+use crate::types::Email;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Prefix that turns an entry into a domain wildcard.
+pub const DOMAIN_WILDCARD: &str = "*@";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProtectedSendersConfig {
+    #[serde(default)]
+    pub senders: Vec<String>,
+}
+
+// =============================================================================
+// Config load/save
+// =============================================================================
+
+pub fn load_protected(config_path: &Path) -> ProtectedSendersConfig {
+    if config_path.exists() {
+        let content = match std::fs::read_to_string(config_path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read protected senders config: {e}");
+                return ProtectedSendersConfig::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    ProtectedSendersConfig::default()
+}
+
+pub fn save_protected(config: &ProtectedSendersConfig, config_path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(config)?;
+    crate::accounts::atomic_write_bytes(config_path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Validation & matching
+// =============================================================================
+
+/// Normalize an entry for storage (trimmed, lowercased) and reject anything
+/// that could never match a sender: no `@`, an empty local part on a
+/// non-wildcard, or a wildcard without a domain.
+pub fn normalize_sender(raw: &str) -> Result<String, Error> {
+    normalize_entry(raw, "Protected sender")
+}
+
+/// [`normalize_sender`] for any sender list; `label` starts the error
+/// message (`"Protected sender"`).
+pub fn normalize_entry(raw: &str, label: &str) -> Result<String, Error> {
+    let sender = raw.trim().to_ascii_lowercase();
+    if sender.contains(char::is_whitespace) {
+        return Err(Error::BadRequest(format!(
+            "{label} '{raw}' must not contain whitespace"
+        )));
+    }
+    let Some((local, domain)) = sender.rsplit_once('@') else {
+        return Err(Error::BadRequest(format!(
+            "{label} '{raw}' is not an email address or *@domain"
+        )));
+    };
+    if domain.is_empty() || local.is_empty() || (local.contains('*') && local != "*") {
+        return Err(Error::BadRequest(format!(
+            "{label} '{raw}' is not an email address or *@domain"
+        )));
+    }
+    Ok(sender)
+}
+
+/// True when `address` matches any entry in `config`.
+pub fn is_protected(config: &ProtectedSendersConfig, address: &str) -> bool {
+    matches_any(&config.senders, address)
+}
+
+/// True when `address` matches one of `entries`, each an exact address or
+/// a [`DOMAIN_WILDCARD`] domain.
+pub fn matches_any(entries: &[String], address: &str) -> bool {
+    let address = address.trim().to_ascii_lowercase();
+    let domain = address.rsplit_once('@').map(|(_, d)| d);
+    entries
+        .iter()
+        .any(|entry| match entry.strip_prefix(DOMAIN_WILDCARD) {
+            Some(wild) => domain == Some(wild),
+            None => entry.eq_ignore_ascii_case(&address),
+        })
+}
+
+/// Split `emails` into ids safe to act on and ids whose sender is
+/// protected. Any `From` address matching is enough to protect a message.
+pub fn partition(config: &ProtectedSendersConfig, emails: &[Email]) -> (Vec<String>, Vec<String>) {
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for email in emails {
+        if email.from.iter().any(|a| is_protected(config, &a.email)) {
+            skipped.push(email.id.clone());
+        } else {
+            kept.push(email.id.clone());
+        }
+    }
+    (kept, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[&str]) -> ProtectedSendersConfig {
+        ProtectedSendersConfig {
+            senders: entries.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn exact_entry_matches_case_insensitively() {
+        let cfg = config(&["boss@example.com"]);
+        assert!(is_protected(&cfg, "Boss@Example.COM"));
+        assert!(!is_protected(&cfg, "intern@example.com"));
+    }
+
+    #[test]
+    fn domain_wildcard_matches_whole_domain_only() {
+        let cfg = config(&["*@bank.com"]);
+        assert!(is_protected(&cfg, "alerts@bank.com"));
+        assert!(!is_protected(&cfg, "alerts@notbank.com"));
+        assert!(!is_protected(&cfg, "alerts@mail.bank.com"));
+    }
+
+    #[test]
+    fn normalize_rejects_malformed_entries() {
+        assert_eq!(
+            normalize_sender("  Boss@Example.com ").unwrap(),
+            "boss@example.com"
+        );
+        assert_eq!(normalize_sender("*@Bank.com").unwrap(), "*@bank.com");
+        for bad in [
+            "nobody",
+            "@example.com",
+            "*@",
+            "a*b@example.com",
+            "a b@x.com",
+        ] {
+            assert!(
+                matches!(normalize_sender(bad), Err(Error::BadRequest(_))),
+                "{bad} should be rejected"
+            );
+        }
+    }
+
+    fn email_from(id: &str, from: &str) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "blob_id": "", "thread_id": "", "mailbox_ids": {}, "keywords": {},
+            "received_at": "2026-01-01T00:00:00Z", "subject": "", "from": [{"name": null, "email": from}],
+            "to": [], "cc": [], "preview": "", "has_attachment": false, "size": 0,
+            "text_body": null, "html_body": null, "has_calendar": false, "attachments": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn partition_skips_protected_and_keeps_order() {
+        let cfg = config(&["*@bank.com"]);
+        let emails = [
+            email_from("1", "news@shop.com"),
+            email_from("2", "alerts@bank.com"),
+            email_from("3", "deals@shop.com"),
+        ];
+        let (kept, skipped) = partition(&cfg, &emails);
+        assert_eq!(kept, vec!["1", "3"]);
+        assert_eq!(skipped, vec!["2"]);
+    }
+
+    #[test]
+    fn save_then_load_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("protected-senders.json");
+        let cfg = config(&["boss@example.com", "*@bank.com"]);
+        save_protected(&cfg, &path).unwrap();
+        assert_eq!(load_protected(&path), cfg);
+    }
+}
@@ -2,8 +2,9 @@ use crate::error::Error;
 use crate::gmail::GmailSession;
 use crate::jmap::JmapSession;
 use crate::outlook::OutlookSession;
+use crate::sieve::SieveScript;
 use crate::types::*;
-use crate::{calendar, gmail, jmap, outlook};
+use crate::{calendar, gmail, jmap, outlook, provider_utils};
 
 // =============================================================================
 // Provider Session — concrete enum, no traits
@@ -37,6 +38,16 @@ impl ProviderSession {
         }
     }
 
+    /// Swap the HTTP client, e.g. after the account's `proxy` changed.
+    /// Later requests, token refreshes included, go through the new one.
+    pub fn set_http_client(&mut self, client: reqwest::Client) {
+        match self {
+            Self::Fastmail(s) => s.client = client,
+            Self::Outlook(s) => s.client = client,
+            Self::Gmail(s) => s.client = client,
+        }
+    }
+
     /// Whether this provider sends RSVP emails automatically (via Graph API)
     /// so the caller should NOT send a manual iTIP reply.
     /// Gmail PATCHes Calendar attendees with sendUpdates=all (Milestone D),
@@ -44,6 +55,44 @@ impl ProviderSession {
     pub fn sends_rsvp_automatically(&self) -> bool {
         matches!(self, Self::Outlook(_) | Self::Gmail(_))
     }
+
+    /// Whether a message keeps its id when moved between mailboxes, so a
+    /// recorded id still finds it afterwards. Graph assigns a new one.
+    pub fn keeps_ids_on_move(&self) -> bool {
+        !matches!(self, Self::Outlook(_))
+    }
+
+    /// Whether [`set_flag_color`] works for this session.
+    pub fn supports_flag_colors(&self) -> bool {
+        matches!(self, Self::Fastmail(_))
+    }
+
+    /// Whether the draft functions ([`create_draft`] and friends) work for
+    /// this session.
+    pub fn supports_drafts(&self) -> bool {
+        matches!(self, Self::Fastmail(_))
+    }
+
+    /// Whether [`destroy_batch`] works for this session.
+    pub fn supports_permanent_delete(&self) -> bool {
+        !matches!(self, Self::Gmail(_))
+    }
+
+    /// Capabilities the server advertised at connect; only JMAP has them.
+    pub fn capabilities(&self) -> &[String] {
+        match self {
+            Self::Fastmail(s) => &s.capabilities,
+            Self::Outlook(_) | Self::Gmail(_) => &[],
+        }
+    }
+
+    pub fn limiter(&self) -> &crate::rate_limit::RateLimiter {
+        match self {
+            Self::Fastmail(s) => &s.limiter,
+            Self::Outlook(s) => &s.limiter,
+            Self::Gmail(s) => &s.limiter,
+        }
+    }
 }
 
 // =============================================================================
@@ -87,6 +136,37 @@ pub async fn query_emails(
     }
 }
 
+/// A page of search results with its total and position. Only JMAP
+/// counts results (`calculateTotal`) and resolves anchors; Graph and Gmail
+/// return the ids at a position with the total left unknown, so a pager
+/// falls back to "more" / "no more".
+pub async fn search_emails(
+    s: &ProviderSession,
+    mailbox_id: Option<&str>,
+    limit: usize,
+    start: &WindowStart,
+    query: Option<&ParsedQuery>,
+    sort: EmailSort,
+) -> Result<SearchPage, Error> {
+    match (s, start) {
+        (ProviderSession::Fastmail(s), _) => {
+            jmap::search_emails(s, mailbox_id, limit, start, query, sort).await
+        }
+        (_, WindowStart::Position(position)) => {
+            let ids = query_emails(s, mailbox_id, limit, *position, query, sort).await?;
+            Ok(SearchPage {
+                ids,
+                position: *position,
+                ..Default::default()
+            })
+        }
+        (_, WindowStart::Anchor { .. }) => Err(Error::BadRequest(format!(
+            "anchored windows are not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
 /// `priority: true` puts the underlying requests on the limiter's reserved
 /// interactive lane (see `RateLimiter::execute_prioritized`). Use it only
 /// for fetches a user is actively waiting on — a single email open, an
@@ -133,6 +213,12 @@ pub const GET_EMAILS_CHUNK: usize = 25;
 /// matches the pre-chunking split-counts batch size.
 pub const JMAP_GET_EMAILS_CHUNK: usize = 500;
 
+/// Chunks [`get_emails_chunked`] keeps in flight at once. Each takes its
+/// own read guard, so a queued writer still gets in after at most one
+/// round of chunks; the overlap keeps the provider limiter busy across
+/// chunk boundaries instead of idling while one chunk's stragglers finish.
+pub const GET_EMAILS_CHUNK_CONCURRENCY: usize = 3;
+
 /// Like [`get_emails`], but re-acquires the session read guard per chunk of
 /// ids instead of holding one guard across the whole fan-out.
 ///
@@ -159,15 +245,19 @@ pub async fn get_emails_chunked(
             _ => chunk_size.max(1),
         }
     };
-    let mut out = Vec::with_capacity(ids.len());
-    for chunk in ids.chunks(chunk_size) {
-        let session = session_lock.read().await;
-        // Chunked fetches are bulk by definition (lists, split counts,
-        // warm passes) — always the main limiter pool, never the
-        // interactive priority lane.
-        out.extend(get_emails(&session, chunk, fetch_body, properties_override, false).await?);
-    }
-    Ok(out)
+    let chunks = provider_utils::try_join_bounded(
+        ids.chunks(chunk_size),
+        GET_EMAILS_CHUNK_CONCURRENCY,
+        |chunk| async move {
+            let session = session_lock.read().await;
+            // Chunked fetches are bulk by definition (lists, split counts,
+            // warm passes) — always the main limiter pool, never the
+            // interactive priority lane.
+            get_emails(&session, chunk, fetch_body, properties_override, false).await
+        },
+    )
+    .await?;
+    Ok(chunks.into_iter().flatten().collect())
 }
 
 pub async fn mark_read(s: &ProviderSession, email_id: &str) -> Result<bool, Error> {
@@ -194,6 +284,82 @@ pub async fn toggle_flag(s: &ProviderSession, email_id: &str) -> Result<bool, Er
     }
 }
 
+/// Flag a message in `color` (`None`: a plain star). Only JMAP has flag
+/// colors; Gmail stars and Outlook flags are single-color.
+pub async fn set_flag_color(
+    s: &ProviderSession,
+    email_id: &str,
+    color: Option<FlagColor>,
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::set_flag_color(s, email_id, color).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(Error::BadRequest(format!(
+            "flag colors are not supported for {}",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// Tag a message with a custom keyword: a JMAP keyword, an Outlook
+/// category, or a Gmail user label.
+pub async fn add_keyword(
+    s: &ProviderSession,
+    email_id: &str,
+    keyword: &str,
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::add_keyword(s, email_id, keyword).await,
+        ProviderSession::Outlook(s) => outlook::add_keyword(s, email_id, keyword).await,
+        ProviderSession::Gmail(s) => gmail::add_keyword(s, email_id, keyword).await,
+    }
+}
+
+/// Add and remove custom keywords (Fastmail labels) on a message. JMAP
+/// only: elsewhere they map onto categories and labels that the message
+/// list doesn't read back yet.
+pub async fn set_keywords(
+    s: &ProviderSession,
+    email_id: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::set_keywords(s, email_id, add, remove).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(Error::BadRequest(format!(
+            "custom keywords are not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// The active Sieve script, Fastmail only; see `sieve`.
+pub async fn get_sieve_script(s: &ProviderSession) -> Result<Option<SieveScript>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::get_sieve_script(s).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(sieve_unsupported(s)),
+    }
+}
+
+/// Replace the active Sieve script, or create and activate one when
+/// `script_id` is `None`. Returns the script's id.
+pub async fn put_sieve_script(
+    s: &ProviderSession,
+    script_id: Option<&str>,
+    content: &str,
+) -> Result<String, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::put_sieve_script(s, script_id, content).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(sieve_unsupported(s)),
+    }
+}
+
+fn sieve_unsupported(s: &ProviderSession) -> Error {
+    Error::BadRequest(format!(
+        "Sieve filters are not supported for {} yet",
+        s.provider_name()
+    ))
+}
+
 pub async fn archive(s: &ProviderSession, email_id: &str) -> Result<bool, Error> {
     match s {
         ProviderSession::Fastmail(s) => jmap::archive(s, email_id).await,
@@ -210,6 +376,45 @@ pub async fn trash(s: &ProviderSession, email_id: &str) -> Result<bool, Error> {
     }
 }
 
+/// Restore a trashed message into `mailbox_ids`, the mailboxes (or Gmail
+/// labels) it was in before. Outlook moves give messages new ids, so a
+/// recorded id can't be restored there.
+pub async fn restore(
+    s: &ProviderSession,
+    email_id: &str,
+    mailbox_ids: &[String],
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::set_mailboxes(s, email_id, mailbox_ids).await,
+        ProviderSession::Gmail(s) => gmail::untrash(s, email_id, mailbox_ids).await,
+        ProviderSession::Outlook(_) => Err(Error::BadRequest(format!(
+            "restoring from trash is not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// The trash mailbox id messages are recorded against, or `None` when
+/// the provider can't restore (Outlook).
+pub fn trash_mailbox_id(s: &ProviderSession) -> Option<String> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::role_mailbox(s, "trash").map(|mb| mb.id.clone()),
+        ProviderSession::Gmail(_) => Some("TRASH".into()),
+        ProviderSession::Outlook(_) => None,
+    }
+}
+
+/// The mailbox archive or trash moves into for `role`, when the provider
+/// files into a specific mailbox. Fastmail's may have been inferred from
+/// a localized name; Outlook uses well-known folders and Gmail labels, so
+/// they report none.
+pub fn role_mailbox(s: &ProviderSession, role: &str) -> Option<Mailbox> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::role_mailbox(s, role).cloned(),
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => None,
+    }
+}
+
 pub async fn move_to_mailbox(
     s: &ProviderSession,
     email_id: &str,
@@ -222,6 +427,176 @@ pub async fn move_to_mailbox(
     }
 }
 
+/// Move a message out of `from_mailbox_id` into `to_mailbox_id`. A JMAP
+/// or Graph move replaces the old mailbox anyway; Gmail labels are
+/// additive, so the source label is dropped explicitly.
+pub async fn move_between(
+    s: &ProviderSession,
+    email_id: &str,
+    from_mailbox_id: &str,
+    to_mailbox_id: &str,
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::move_to_mailbox(s, email_id, to_mailbox_id).await,
+        ProviderSession::Outlook(s) => outlook::move_to_mailbox(s, email_id, to_mailbox_id).await,
+        ProviderSession::Gmail(s) => {
+            gmail::move_between(s, email_id, from_mailbox_id, to_mailbox_id).await
+        }
+    }
+}
+
+/// Create a mailbox (a Gmail label) named `name`, under `parent_id` when
+/// given, and return its id.
+pub async fn create_mailbox(
+    s: &ProviderSession,
+    name: &str,
+    parent_id: Option<&str>,
+) -> Result<String, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::create_mailbox(s, name, parent_id).await,
+        ProviderSession::Gmail(g) => {
+            let parent = match parent_id {
+                Some(id) => Some(gmail_label(g, id).await?.name),
+                None => None,
+            };
+            gmail::create_label(g, &nested_label_name(parent.as_deref(), name)).await
+        }
+        ProviderSession::Outlook(_) => Err(mailboxes_unsupported(s, "creating")),
+    }
+}
+
+/// Rename and/or move a mailbox. `parent_id` is `Some(None)` for the top
+/// level and `None` to keep the current parent. Gmail nests labels by
+/// name, so there both come down to a rename; labels nested under the
+/// renamed one keep their old names.
+pub async fn update_mailbox(
+    s: &ProviderSession,
+    mailbox_id: &str,
+    name: Option<&str>,
+    parent_id: Option<Option<&str>>,
+) -> Result<(), Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::update_mailbox(s, mailbox_id, name, parent_id).await,
+        ProviderSession::Gmail(g) => {
+            let current = gmail_label(g, mailbox_id).await?.name;
+            let (current_parent, current_leaf) = match current.rsplit_once('/') {
+                Some((parent, leaf)) => (Some(parent.to_string()), leaf.to_string()),
+                None => (None, current),
+            };
+            let parent = match parent_id {
+                None => current_parent,
+                Some(None) => None,
+                Some(Some(id)) => Some(gmail_label(g, id).await?.name),
+            };
+            let leaf = name.unwrap_or(&current_leaf);
+            gmail::rename_label(g, mailbox_id, &nested_label_name(parent.as_deref(), leaf)).await
+        }
+        ProviderSession::Outlook(_) => Err(mailboxes_unsupported(s, "changing")),
+    }
+}
+
+/// Delete a mailbox. On Fastmail a mailbox that still holds mail is
+/// refused unless `remove_emails`; a Gmail label goes without its mail,
+/// which keeps its other labels.
+pub async fn destroy_mailbox(
+    s: &ProviderSession,
+    mailbox_id: &str,
+    remove_emails: bool,
+) -> Result<(), Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::destroy_mailbox(s, mailbox_id, remove_emails).await,
+        ProviderSession::Gmail(_) if remove_emails => Err(Error::BadRequest(
+            "Gmail labels can't be deleted together with their mail".into(),
+        )),
+        ProviderSession::Gmail(g) => gmail::delete_label(g, mailbox_id).await,
+        ProviderSession::Outlook(_) => Err(mailboxes_unsupported(s, "deleting")),
+    }
+}
+
+/// Re-read the mailbox list into the session after a mailbox changed.
+/// Only Fastmail keeps one on the session (its role lookup); Gmail's
+/// label cache is dropped by the label calls themselves.
+pub async fn refresh_mailbox_cache(s: &mut ProviderSession) -> Result<(), Error> {
+    if let ProviderSession::Fastmail(s) = s {
+        let mailboxes = jmap::get_mailboxes(s).await?;
+        jmap::cache_mailboxes(s, &mailboxes);
+    }
+    Ok(())
+}
+
+/// Mail accounts shared with or delegated to this login, besides its own.
+/// Fastmail only: Gmail and Outlook delegation isn't wired up.
+pub fn shared_accounts(s: &ProviderSession) -> Vec<jmap::SharedAccount> {
+    match s {
+        ProviderSession::Fastmail(s) => s.shared_accounts.clone(),
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Vec::new(),
+    }
+}
+
+/// The session for `shared_id`, one of `primary`'s [`shared_accounts`],
+/// opened (with its mailbox cache filled) on first use and kept with
+/// `primary` after that.
+pub async fn delegated_session(
+    primary: &SessionLock,
+    shared_id: &str,
+) -> Result<SessionLock, Error> {
+    let mut opened = {
+        let session = primary.read().await;
+        let ProviderSession::Fastmail(s) = &*session else {
+            return Err(Error::BadRequest(format!(
+                "Shared accounts are not supported for {}",
+                session.provider_name()
+            )));
+        };
+        if let Some(existing) = lock_delegated(s).get(shared_id) {
+            return Ok(existing.clone());
+        }
+        if s.shared_account(shared_id).is_none() {
+            return Err(Error::NotFound(format!("No shared account '{shared_id}'")));
+        }
+        ProviderSession::Fastmail(Box::new(s.for_shared_account(shared_id)))
+    };
+    refresh_mailbox_cache(&mut opened).await?;
+    let opened = std::sync::Arc::new(tokio::sync::RwLock::new(opened));
+    let session = primary.read().await;
+    let ProviderSession::Fastmail(s) = &*session else {
+        return Ok(opened);
+    };
+    Ok(lock_delegated(s)
+        .entry(shared_id.to_string())
+        .or_insert(opened)
+        .clone())
+}
+
+fn lock_delegated(
+    s: &JmapSession,
+) -> std::sync::MutexGuard<'_, std::collections::HashMap<String, SessionLock>> {
+    s.delegated.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn gmail_label(s: &GmailSession, label_id: &str) -> Result<Mailbox, Error> {
+    gmail::get_mailboxes(s)
+        .await?
+        .into_iter()
+        .find(|l| l.id == label_id)
+        .ok_or_else(|| Error::NotFound(format!("Mailbox {label_id} not found")))
+}
+
+/// Gmail's name for label `leaf` nested under `parent`.
+fn nested_label_name(parent: Option<&str>, leaf: &str) -> String {
+    match parent {
+        Some(parent) => format!("{parent}/{leaf}"),
+        None => leaf.to_string(),
+    }
+}
+
+fn mailboxes_unsupported(s: &ProviderSession, what: &str) -> Error {
+    Error::BadRequest(format!(
+        "{what} mailboxes is not supported for {} yet",
+        s.provider_name()
+    ))
+}
+
 pub async fn archive_batch(s: &ProviderSession, email_ids: &[String]) -> Result<usize, Error> {
     match s {
         ProviderSession::Fastmail(s) => jmap::archive_batch(s, email_ids).await,
@@ -230,12 +605,40 @@ pub async fn archive_batch(s: &ProviderSession, email_ids: &[String]) -> Result<
     }
 }
 
+/// Permanently delete messages, bypassing Trash. Gmail is refused: the
+/// `gmail.modify` scope we ask for can't delete, and Gmail purges its
+/// Trash after 30 days by itself.
+pub async fn destroy_batch(s: &ProviderSession, email_ids: &[String]) -> Result<usize, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::destroy_batch(s, email_ids).await,
+        ProviderSession::Outlook(s) => outlook::destroy_batch(s, email_ids).await,
+        ProviderSession::Gmail(_) => Err(permanent_delete_unsupported(s)),
+    }
+}
+
+pub fn permanent_delete_unsupported(s: &ProviderSession) -> Error {
+    Error::BadRequest(format!(
+        "permanent delete is not supported for {}; its Trash empties itself after 30 days",
+        s.provider_name()
+    ))
+}
+
+pub async fn mark_read_batch(s: &ProviderSession, email_ids: &[String]) -> Result<usize, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::mark_read_batch(s, email_ids).await,
+        ProviderSession::Outlook(s) => outlook::mark_read_batch(s, email_ids).await,
+        ProviderSession::Gmail(s) => gmail::mark_read_batch(s, email_ids).await,
+    }
+}
+
 pub async fn send_email(
     s: &mut ProviderSession,
     sub: &EmailSubmission,
     from_addr: &str,
     identity_id_override: Option<&str>,
 ) -> Result<Option<String>, Error> {
+    let sub = crate::submission::prepare(sub)?;
+    let sub = &attach_forwarded(s, sub).await?;
     match s {
         ProviderSession::Fastmail(s) => {
             jmap::send_email(s, sub, from_addr, identity_id_override).await
@@ -261,6 +664,26 @@ pub async fn send_email(
     }
 }
 
+/// Resolve `sub.forward_of`: fetch that email and attach its attachments
+/// by their existing blob ids. JMAP references the blobs in place; Gmail
+/// and Outlook re-read them server-side when building the message, as
+/// they already do for a reply that keeps the original's attachments.
+/// Without `forward_of` the submission comes back unchanged.
+pub async fn attach_forwarded(
+    s: &ProviderSession,
+    sub: EmailSubmission,
+) -> Result<EmailSubmission, Error> {
+    let Some(id) = sub.forward_of.clone() else {
+        return Ok(sub);
+    };
+    let original = get_emails(s, std::slice::from_ref(&id), true, None, true)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NotFound(format!("Forwarded email {id} not found")))?;
+    Ok(crate::submission::with_forwarded(sub, original.attachments))
+}
+
 // =============================================================================
 // Persistent drafts (kata wm57) — Fastmail-only in v1
 // =============================================================================
@@ -277,6 +700,13 @@ fn drafts_unsupported(s: &ProviderSession) -> Error {
     ))
 }
 
+pub async fn list_drafts(s: &ProviderSession, limit: usize) -> Result<Vec<Email>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::list_drafts(s, limit).await,
+        other => Err(drafts_unsupported(other)),
+    }
+}
+
 pub async fn create_draft(
     s: &ProviderSession,
     sub: &EmailSubmission,
@@ -307,6 +737,49 @@ pub async fn destroy_draft(s: &ProviderSession, draft_id: &str) -> Result<bool,
     }
 }
 
+/// Destroy drafts left behind by sends that never completed. Only the
+/// JMAP send flow creates a separate draft before submitting, so the
+/// other providers have nothing to sweep.
+pub async fn sweep_orphaned_send_drafts(s: &ProviderSession) -> Result<usize, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::sweep_orphaned_send_drafts(s).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Ok(0),
+    }
+}
+
+/// Every message in a conversation, oldest first, with bodies. JMAP only
+/// so far.
+pub async fn get_thread(s: &ProviderSession, thread_id: &str) -> Result<Vec<Email>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::get_thread(s, thread_id).await,
+        _ => Err(Error::BadRequest(format!(
+            "Thread view not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// Ids of every message in a conversation. JMAP only, like
+/// [`get_thread`].
+pub async fn thread_email_ids(s: &ProviderSession, thread_id: &str) -> Result<Vec<String>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::thread_email_ids(s, thread_id).await,
+        _ => Err(Error::BadRequest(format!(
+            "Thread view not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// Every vCard in the account's address book, or `None` for providers
+/// without CardDAV (see `contacts`).
+pub async fn address_book_cards(s: &ProviderSession) -> Result<Option<Vec<String>>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::fetch_vcards(s).await.map(Some),
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Ok(None),
+    }
+}
+
 pub async fn get_calendar_data(
     s: &ProviderSession,
     email_id: &str,
@@ -361,6 +834,94 @@ pub async fn download_blob(
     }
 }
 
+/// Map a Message-ID header value (normalized `<…>` form) to this
+/// account's email id, if the message is there.
+pub async fn find_by_message_id(
+    s: &ProviderSession,
+    message_id: &str,
+) -> Result<Option<String>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::find_by_message_id(s, message_id).await,
+        ProviderSession::Outlook(s) => outlook::find_by_message_id(s, message_id).await,
+        ProviderSession::Gmail(s) => gmail::find_by_message_id(s, message_id).await,
+    }
+}
+
+/// The whole message as the provider stores it (RFC 5322 bytes), for
+/// `/api/emails/{id}/raw`.
+pub async fn get_raw_message(s: &ProviderSession, email_id: &str) -> Result<Vec<u8>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::get_raw_message(s, email_id).await,
+        ProviderSession::Outlook(s) => outlook::get_raw_message(s, email_id).await,
+        ProviderSession::Gmail(s) => gmail::get_raw_message(s, email_id).await,
+    }
+}
+
+/// Every header field of one email as name/value pairs, in message order.
+pub async fn get_headers(s: &ProviderSession, email_id: &str) -> Result<Vec<EmailHeader>, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::get_headers(s, email_id).await,
+        ProviderSession::Outlook(s) => outlook::get_headers(s, email_id).await,
+        ProviderSession::Gmail(s) => gmail::get_headers(s, email_id).await,
+    }
+}
+
+/// Email ids changed since a state token, for external sync tools. Graph
+/// only tracks changes per folder (delta queries), so Outlook has no
+/// account-wide journal to expose.
+pub async fn email_changes(
+    s: &ProviderSession,
+    since: Option<&str>,
+    max_changes: usize,
+) -> Result<EmailChanges, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::email_changes(s, since, max_changes).await,
+        ProviderSession::Gmail(s) => gmail::email_changes(s, since, max_changes).await,
+        ProviderSession::Outlook(_) => Err(Error::BadRequest(format!(
+            "change tracking is not supported for {} yet",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// Forward raw JMAP method calls for `/api/jmap` (see
+/// `jmap::prepare_passthrough` for what is allowed). Graph and the Gmail
+/// API aren't JMAP, so only Fastmail accounts have it.
+pub async fn jmap_passthrough(
+    s: &ProviderSession,
+    method_calls: Vec<serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::passthrough(s, method_calls).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(Error::BadRequest(format!(
+            "/api/jmap needs a JMAP account; {} isn't one",
+            s.provider_name()
+        ))),
+    }
+}
+
+/// Stream the full content of a truncated body part. Only JMAP truncates
+/// (`maxBodyValueBytes`); Gmail and Outlook return whole bodies, so they
+/// never have a part to stream.
+pub async fn stream_body_part(
+    s: &ProviderSession,
+    part: &BodyPart,
+) -> Result<axum::body::Body, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => {
+            let blob_id = part
+                .blob_id
+                .as_deref()
+                .ok_or_else(|| Error::NotFound("Body part has no blob".into()))?;
+            let resp = jmap::download_blob_response(s, blob_id, &part.mime_type).await?;
+            Ok(axum::body::Body::from_stream(resp.bytes_stream()))
+        }
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Err(Error::NotFound(
+            "Body parts are never truncated for this provider".into(),
+        )),
+    }
+}
+
 // =============================================================================
 // Calendar dispatch — Outlook uses Graph API, Fastmail uses CalDAV
 // =============================================================================
@@ -443,6 +1004,25 @@ pub async fn add_to_calendar(
     }
 }
 
+/// Write an organizer-side PARTSTAT update (from an attendee's
+/// `METHOD:REPLY`) back to the stored event. `updated_ics` is the stored
+/// event's ICS with `calendar::update_partstat` already applied.
+///
+/// Fastmail only: Outlook and Google Calendar process iTIP replies
+/// server-side when they're delivered, so the stored event is already
+/// current and overwriting it from here would race the provider. Those
+/// arms return `Ok(false)` (nothing written).
+pub async fn apply_calendar_reply(
+    s: &ProviderSession,
+    updated_ics: &str,
+    uid: &str,
+) -> Result<bool, Error> {
+    match s {
+        ProviderSession::Fastmail(s) => jmap::add_to_calendar(s, updated_ics, uid, false).await,
+        ProviderSession::Outlook(_) | ProviderSession::Gmail(_) => Ok(false),
+    }
+}
+
 pub async fn remove_from_calendar(s: &ProviderSession, uid: &str) -> Result<bool, Error> {
     match s {
         ProviderSession::Fastmail(s) => jmap::remove_from_calendar(s, uid).await,
@@ -492,6 +1072,7 @@ pub async fn rsvp(
                 references: None,
                 attachments: vec![],
                 calendar_ics: Some(rsvp_ics),
+                forward_of: None,
             };
 
             if let Err(e) = jmap::send_email(s, &submission, attendee_email, None).await {
@@ -571,6 +1152,22 @@ mod tests {
     use crate::platform::{FsTokenStore, TokenStore, Tokens};
     use std::sync::Arc;
 
+    #[test]
+    fn nested_label_name_joins_with_slash() {
+        assert_eq!(nested_label_name(None, "Receipts"), "Receipts");
+        assert_eq!(
+            nested_label_name(Some("Work/Clients"), "Acme"),
+            "Work/Clients/Acme"
+        );
+    }
+
+    #[tokio::test]
+    async fn outlook_mailbox_changes_are_refused() {
+        let s = make_outlook_session();
+        let err = destroy_mailbox(&s, "f1", false).await.unwrap_err();
+        assert!(matches!(err, Error::BadRequest(m) if m.starts_with("deleting mailboxes")));
+    }
+
     fn make_fastmail_session() -> ProviderSession {
         ProviderSession::Fastmail(Box::new(JmapSession::new(
             "user@fastmail.com",
@@ -662,6 +1259,20 @@ mod tests {
         assert!(!s.sends_rsvp_automatically());
     }
 
+    #[tokio::test]
+    async fn email_changes_rejected_for_outlook() {
+        let s = make_outlook_session();
+        match email_changes(&s, None, 10).await {
+            Err(Error::BadRequest(msg)) => {
+                assert!(
+                    msg.contains("not supported") && msg.contains("outlook"),
+                    "{msg}"
+                )
+            }
+            other => panic!("expected BadRequest, got {other:?}"),
+        }
+    }
+
     // --- draft provider gating (kata wm57) ---
 
     fn draft_submission() -> EmailSubmission {
@@ -676,6 +1287,7 @@ mod tests {
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         }
     }
 
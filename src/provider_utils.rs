@@ -28,6 +28,11 @@ pub const MAX_BLOB_BYTES: usize = 25 * 1024 * 1024;
 /// Aggregate per-session cap. Pins RAM at 50 MiB worst-case.
 pub const MAX_UPLOAD_CACHE_BYTES: usize = 50 * 1024 * 1024;
 
+/// Attachment bodies a send resolves concurrently (re-downloads of an
+/// original's attachments for reply/forward). Each download still goes
+/// through the provider's rate limiter.
+pub const ATTACHMENT_FETCH_CONCURRENCY: usize = 4;
+
 // =============================================================================
 // Pure helpers
 // =============================================================================
@@ -119,6 +124,134 @@ pub fn mime_type_from_filename(filename: &str) -> &'static str {
     }
 }
 
+/// Recognize a blob from its leading bytes. Deliberately an allowlist of
+/// formats that are safe to render inline — PDF and raster images — so a
+/// sniff can only ever upgrade a download to one of these, never to
+/// HTML, SVG or script.
+pub fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+    ];
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+/// Content-Type to serve for a downloaded blob, plus whether the declared
+/// type disagreed with the sniffed one. A recognized signature wins over
+/// whatever the sender declared; anything unrecognized keeps the declared
+/// type. Parameters and case are ignored when comparing, and `image/jpg` /
+/// `image/pjpeg` count as JPEG.
+pub fn corrected_content_type(declared: &str, bytes: &[u8]) -> (String, bool) {
+    let Some(sniffed) = sniff_mime_type(bytes) else {
+        return (declared.to_string(), false);
+    };
+    let base = declared
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let base = match base.as_str() {
+        "image/jpg" | "image/pjpeg" => "image/jpeg",
+        "image/x-ms-bmp" => "image/bmp",
+        other => other,
+    };
+    if base == sniffed {
+        (declared.to_string(), false)
+    } else {
+        (sniffed.to_string(), true)
+    }
+}
+
+/// Normalize a Message-ID as pasted from another tool into its header form,
+/// `<local@domain>`. Accepts the bracketed header value, a bare id, or an
+/// RFC 2392 `mid:` URL (percent-decoded). Rejects anything that can't be a
+/// single msg-id — whitespace, control characters, stray brackets, quotes —
+/// so the value is safe to splice into provider query languages.
+pub fn normalize_message_id(raw: &str) -> Result<String, Error> {
+    let raw = raw.trim();
+    let raw = match raw.strip_prefix("mid:") {
+        Some(rest) => percent_encoding::percent_decode_str(rest)
+            .decode_utf8()
+            .map_err(|_| Error::BadRequest("Invalid mid: URL".into()))?
+            .into_owned(),
+        None => raw.to_string(),
+    };
+    let inner = raw
+        .strip_prefix('<')
+        .and_then(|r| r.strip_suffix('>'))
+        .unwrap_or(&raw);
+    let valid = !inner.is_empty()
+        && inner.contains('@')
+        && !inner
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "<>\"'\\".contains(c));
+    if !valid {
+        return Err(Error::BadRequest(format!("Invalid Message-ID: {raw}")));
+    }
+    Ok(format!("<{inner}>"))
+}
+
+/// Unfold a raw header value (RFC 5322 §2.2.3): drop the line breaks of
+/// folded continuation lines, keeping their leading whitespace, and trim.
+pub fn unfold_header_value(raw: &str) -> String {
+    raw.split(['\r', '\n'])
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Run `f` over `items` with at most `limit` futures in flight, returning
+/// the results in input order. The first error wins: remaining futures are
+/// dropped (cancelling their requests) and the error is returned. Used
+/// where a handful of independent provider round-trips would otherwise be
+/// awaited one after another — chunked Email/get, per-message calendar
+/// lookups, attachment bytes for a send.
+pub async fn try_join_bounded<I, T, F, Fut>(
+    items: I,
+    limit: usize,
+    mut f: F,
+) -> Result<Vec<T>, Error>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: std::future::Future<Output = Result<T, Error>>,
+{
+    use futures_util::StreamExt;
+    use futures_util::stream::FuturesUnordered;
+
+    let permits = tokio::sync::Semaphore::new(limit.max(1));
+    let mut pending: FuturesUnordered<_> = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let fut = f(item);
+            let permits = &permits;
+            async move {
+                // The semaphore lives for the whole call and is never
+                // closed, so acquire can't fail.
+                let _permit = permits.acquire().await.ok();
+                (i, fut.await)
+            }
+        })
+        .collect();
+    let mut out: Vec<Option<T>> = (0..pending.len()).map(|_| None).collect();
+    while let Some((i, result)) = pending.next().await {
+        out[i] = Some(result?);
+    }
+    Ok(out.into_iter().flatten().collect())
+}
+
 // Suppress unused warning until Outlook starts using Error directly in this
 // module's tests; the function is exposed for cross-provider use.
 #[allow(dead_code)]
@@ -279,4 +412,158 @@ mod tests {
         assert_eq!(mime_type_from_filename("a.tgz"), "application/gzip");
         assert_eq!(mime_type_from_filename("a.json"), "application/json");
     }
+
+    // ---- normalize_message_id ----
+
+    #[test]
+    fn message_id_accepts_bracketed_bare_and_mid_url() {
+        for raw in [
+            "<abc.123@example.com>",
+            "abc.123@example.com",
+            "  <abc.123@example.com>\n",
+            "mid:abc.123%40example.com",
+        ] {
+            assert_eq!(
+                normalize_message_id(raw).unwrap(),
+                "<abc.123@example.com>",
+                "{raw:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn message_id_rejects_injection_shaped_values() {
+        for raw in [
+            "",
+            "<>",
+            "no-at-sign",
+            "a b@example.com",
+            "a'@example.com",
+            "a\"@example.com",
+            "<a@example.com> OR from:x",
+        ] {
+            assert!(normalize_message_id(raw).is_err(), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn unfold_header_value_joins_continuation_lines() {
+        assert_eq!(
+            unfold_header_value(" from a.example\r\n\tby b.example;\r\n Tue, 1 Oct 2024"),
+            "from a.example\tby b.example; Tue, 1 Oct 2024"
+        );
+        assert_eq!(unfold_header_value(" plain "), "plain");
+    }
+
+    // ---- try_join_bounded ----
+
+    #[tokio::test]
+    async fn try_join_bounded_preserves_input_order() {
+        // Later items finish first; output still follows input order.
+        let out = try_join_bounded(0..5u64, 5, |i| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(25 - i * 5)).await;
+            Ok(i * 10)
+        })
+        .await
+        .unwrap();
+        assert_eq!(out, vec![0, 10, 20, 30, 40]);
+    }
+
+    #[tokio::test]
+    async fn try_join_bounded_caps_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let out = try_join_bounded(0..12, 3, |i| {
+            let (in_flight, peak) = (&in_flight, &peak);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(i)
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(out.len(), 12);
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(peak.load(Ordering::SeqCst) > 1, "should actually overlap");
+    }
+
+    #[tokio::test]
+    async fn try_join_bounded_returns_first_error() {
+        let err = try_join_bounded(0..4, 2, |i| async move {
+            if i == 2 {
+                Err(Error::NotFound(format!("item {i}")))
+            } else {
+                Ok(i)
+            }
+        })
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::NotFound(m) if m == "item 2"));
+    }
+
+    #[tokio::test]
+    async fn try_join_bounded_empty_and_zero_limit() {
+        let out: Vec<u8> = try_join_bounded(Vec::<u8>::new(), 4, |i| async move { Ok(i) })
+            .await
+            .unwrap();
+        assert!(out.is_empty());
+        // A zero limit is treated as one rather than deadlocking.
+        let out = try_join_bounded([1, 2], 0, |i| async move { Ok(i) })
+            .await
+            .unwrap();
+        assert_eq!(out, vec![1, 2]);
+    }
+
+    // ---- sniff_mime_type / corrected_content_type ----
+
+    #[test]
+    fn sniff_recognizes_allowlisted_signatures() {
+        assert_eq!(sniff_mime_type(b"%PDF-1.7\n..."), Some("application/pdf"));
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WAVEfmt "), None);
+    }
+
+    #[test]
+    fn sniff_never_yields_active_content() {
+        for bytes in [
+            &b"<!DOCTYPE html><script>"[..],
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\">",
+            b"PK\x03\x04",
+            b"",
+        ] {
+            assert_eq!(sniff_mime_type(bytes), None, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn corrected_content_type_upgrades_octet_stream() {
+        assert_eq!(
+            corrected_content_type("application/octet-stream", b"%PDF-1.4"),
+            ("application/pdf".to_string(), true)
+        );
+        // A declared HTML type over PDF bytes is served as the PDF it is.
+        assert_eq!(
+            corrected_content_type("text/html", b"%PDF-1.4"),
+            ("application/pdf".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn corrected_content_type_keeps_agreeing_or_unknown_declared() {
+        assert_eq!(
+            corrected_content_type("IMAGE/JPG; name=a.jpg", b"\xff\xd8\xff\xdb"),
+            ("IMAGE/JPG; name=a.jpg".to_string(), false)
+        );
+        assert_eq!(
+            corrected_content_type("application/zip", b"PK\x03\x04"),
+            ("application/zip".to_string(), false)
+        );
+    }
 }
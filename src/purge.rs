@@ -0,0 +1,123 @@
+//! Permanent deletion.
+//!
+//! `DELETE /api/emails/{id}` destroys one message outright. Emptying a
+//! mailbox takes two calls: `POST /api/mailboxes/{id}/empty` on its own
+//! only reports how many messages would go and hands back a confirm
+//! token; repeating it with `?confirm=<token>` within [`CONFIRM_TTL_SECS`]
+//! purges them. Tokens are single-use, tied to the account and mailbox,
+//! and live in memory only. Only Trash and Spam can be emptied
+//! ([`is_purgeable`]); everything else goes through Trash first.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How long a confirm token from the first empty call stays good.
+pub const CONFIRM_TTL_SECS: i64 = 120;
+
+/// Mailbox roles whose contents may be purged wholesale.
+const PURGEABLE_ROLES: &[&str] = &["trash", "junk"];
+
+pub fn is_purgeable(role: Option<&str>) -> bool {
+    role.is_some_and(|r| PURGEABLE_ROLES.contains(&r))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEmpty {
+    pub account: String,
+    pub mailbox_id: String,
+    /// Messages the mailbox held when the token was issued.
+    pub count: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Outstanding confirm tokens for emptying a mailbox, by token.
+#[derive(Debug, Default)]
+pub struct EmptyConfirmations {
+    pending: Mutex<HashMap<String, PendingEmpty>>,
+}
+
+impl EmptyConfirmations {
+    /// Issue a token for emptying `mailbox_id`, dropping expired ones.
+    pub fn issue(
+        &self,
+        account: &str,
+        mailbox_id: &str,
+        count: i64,
+        now: DateTime<Utc>,
+    ) -> (String, PendingEmpty) {
+        let token = uuid::Uuid::new_v4().to_string();
+        let pending = PendingEmpty {
+            account: account.to_string(),
+            mailbox_id: mailbox_id.to_string(),
+            count,
+            expires_at: now + Duration::seconds(CONFIRM_TTL_SECS),
+        };
+        let mut map = self.lock();
+        map.retain(|_, p| p.expires_at > now);
+        map.insert(token.clone(), pending.clone());
+        (token, pending)
+    }
+
+    /// Spend `token`. None when it doesn't exist, has expired, or was
+    /// issued for another account or mailbox; a mismatched token stays
+    /// unspent.
+    pub fn take(
+        &self,
+        token: &str,
+        account: &str,
+        mailbox_id: &str,
+        now: DateTime<Utc>,
+    ) -> Option<PendingEmpty> {
+        let mut map = self.lock();
+        let pending = map.get(token)?;
+        if pending.account != account || pending.mailbox_id != mailbox_id {
+            return None;
+        }
+        let pending = map.remove(token)?;
+        (pending.expires_at > now).then_some(pending)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingEmpty>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_trash_and_spam_are_purgeable() {
+        assert!(is_purgeable(Some("trash")));
+        assert!(is_purgeable(Some("junk")));
+        assert!(!is_purgeable(Some("inbox")));
+        assert!(!is_purgeable(Some("archive")));
+        assert!(!is_purgeable(None));
+    }
+
+    #[test]
+    fn token_is_single_use_and_scoped() {
+        let confirmations = EmptyConfirmations::default();
+        let now = Utc::now();
+        let (token, pending) = confirmations.issue("work", "trash-1", 12, now);
+        assert_eq!(pending.count, 12);
+        assert!(confirmations.take(&token, "home", "trash-1", now).is_none());
+        assert!(confirmations.take(&token, "work", "spam-1", now).is_none());
+        assert!(confirmations.take(&token, "work", "trash-1", now).is_some());
+        assert!(confirmations.take(&token, "work", "trash-1", now).is_none());
+    }
+
+    #[test]
+    fn token_expires() {
+        let confirmations = EmptyConfirmations::default();
+        let now = Utc::now();
+        let (token, _) = confirmations.issue("work", "trash-1", 3, now);
+        let later = now + Duration::seconds(CONFIRM_TTL_SECS + 1);
+        assert!(
+            confirmations
+                .take(&token, "work", "trash-1", later)
+                .is_none()
+        );
+    }
+}
@@ -133,6 +133,14 @@ impl RateLimiter {
     pub fn spacing(&self) -> Duration {
         self.spacing
     }
+    /// Requests currently holding a permit, main pool and interactive lane.
+    /// A main pool pinned at `concurrency` means callers are queueing.
+    pub fn in_flight(&self) -> (usize, usize) {
+        (
+            self.concurrency - self.sem.available_permits(),
+            PRIORITY_PERMITS - self.priority_sem.available_permits(),
+        )
+    }
 
     /// Run `make_req` under the limiter, retrying on 429/503 with
     /// `Retry-After` honored and exponential backoff with jitter as
@@ -519,6 +527,17 @@ mod tests {
         assert_eq!(lim.spacing(), Duration::from_millis(50));
     }
 
+    #[tokio::test]
+    async fn in_flight_counts_held_permits() {
+        let lim = RateLimiter::new("test", 3, Duration::ZERO, 1);
+        assert_eq!(lim.in_flight(), (0, 0));
+        let main = lim.sem.acquire().await.unwrap();
+        let lane = lim.priority_sem.acquire().await.unwrap();
+        assert_eq!(lim.in_flight(), (1, 1));
+        drop((main, lane));
+        assert_eq!(lim.in_flight(), (0, 0));
+    }
+
     // ---- execute() against a local axum server ----
 
     use axum::Router;
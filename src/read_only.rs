@@ -0,0 +1,91 @@
+//! Read-only mode (`read-only = true` in the config).
+//!
+//! For demoing the UI on a real account or letting someone else look at
+//! a mailbox: [`guard`] answers 409 `{"error": "read_only"}` to every
+//! request that could change mail, settings or accounts, which is every
+//! POST, PUT, PATCH and DELETE except the few in [`ALLOWED_WRITES`] that
+//! only compute something. Not 403: the clients read that as a session
+//! that needs signing in again.
+//! Opening an email doesn't mark it read, and `/api/jmap` forwards reads
+//! only. `GET /api/status` reports the mode so the UI can say so.
+//!
+//! Work the server does on its own checks [`enabled`] before writing to
+//! the account, too:
+//! - calendar invites, updates, cancellations and attendee replies seen
+//!   when an email is opened are shown but not written to the calendar;
+//! - the daily digest isn't sent;
+//! - found receipts are recorded but not tagged in the mailbox;
+//! - split actions, snooze wake-ups, the muted-thread archiver and the
+//!   attendee-reply watcher pause, and pick up where they left off once
+//!   the mode is turned off;
+//! - orphaned send drafts aren't swept at startup.
+//!
+//! What still writes is local to the server: caches, newsletter read
+//! rates, the receipt log, the synced address book and package statuses.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// Non-GET routes that change nothing on the server or the account:
+/// previews, simulations, dropping local caches and `/api/jmap`, whose
+/// handler refuses writes itself.
+pub const ALLOWED_WRITES: &[&str] = &[
+    "/api/splits/preview",
+    "/api/rules/simulate",
+    "/api/cache/clear",
+    "/api/jmap",
+];
+
+/// A `method` request to `route` (the matched route pattern) is refused
+/// in read-only mode.
+pub fn blocks(method: &Method, route: &str) -> bool {
+    let writes = !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    writes && !ALLOWED_WRITES.contains(&route)
+}
+
+pub fn refused() -> Error {
+    Error::ReadOnly
+}
+
+/// Whether read-only mode is on, for writes that don't go through
+/// [`guard`].
+pub async fn enabled(state: &crate::types::AppState) -> bool {
+    state.accounts.read().await.read_only
+}
+
+/// Middleware refusing writes while `read-only` is set.
+pub async fn guard(
+    State(state): State<Arc<crate::types::AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    if blocks(request.method(), &route) && state.accounts.read().await.read_only {
+        return refused().into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_are_blocked_but_reads_and_previews_pass() {
+        assert!(blocks(&Method::POST, "/api/emails/{email_id}/archive"));
+        assert!(blocks(&Method::PUT, "/api/sieve"));
+        assert!(blocks(&Method::DELETE, "/api/emails/{email_id}"));
+        assert!(!blocks(&Method::GET, "/api/emails/{email_id}"));
+        assert!(!blocks(&Method::POST, "/api/splits/preview"));
+        assert!(!blocks(&Method::POST, "/api/jmap"));
+    }
+}
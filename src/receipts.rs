@@ -0,0 +1,519 @@
+//! Receipt and invoice auto-filing.
+//!
+//! A background scan ([`spawn_scanner`]) walks recent inbox mail, picks out
+//! receipts by keyword, currency amount and invoice PDF, tags them with the
+//! [`RECEIPT_KEYWORD`] keyword, and appends a [`ReceiptRecord`] to
+//! `receipts.json`. `GET /api/receipts?month=` reads that log back for
+//! expense review. The heuristics here are pure; `routes::scan_receipts`
+//! does the fetching and tagging.
+
+use crate::error::Error;
+use crate::types::Email;
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+/// Keyword (JMAP), category (Outlook) or label (Gmail) put on filed mail.
+pub const RECEIPT_KEYWORD: &str = "receipt";
+
+/// Subject/preview phrases that mark a message as a receipt candidate.
+const RECEIPT_PHRASES: &[&str] = &[
+    "receipt",
+    "invoice",
+    "order confirmation",
+    "your order",
+    "payment received",
+    "payment confirmation",
+    "billing statement",
+    "thanks for your purchase",
+    "thank you for your purchase",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptRecord {
+    pub account: String,
+    pub email_id: String,
+    /// Sender display name, or the sender's domain when there is none.
+    pub vendor: String,
+    /// Total charged, when one could be found in the message text.
+    #[serde(default)]
+    pub amount: Option<f64>,
+    /// ISO 4217 code for `amount`.
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Local date the message was received.
+    pub date: NaiveDate,
+    pub subject: String,
+    /// Name of the attached invoice/receipt PDF, if any.
+    #[serde(default)]
+    pub invoice_pdf: Option<String>,
+}
+
+/// On-disk shape of `receipts.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReceiptLog {
+    #[serde(default)]
+    pub receipts: Vec<ReceiptRecord>,
+}
+
+impl ReceiptLog {
+    pub fn contains(&self, account: &str, email_id: &str) -> bool {
+        self.receipts
+            .iter()
+            .any(|r| r.account == account && r.email_id == email_id)
+    }
+
+    /// Append the records not already logged; returns how many were added.
+    pub fn merge(&mut self, records: Vec<ReceiptRecord>) -> usize {
+        let mut added = 0;
+        for record in records {
+            if !self.contains(&record.account, &record.email_id) {
+                self.receipts.push(record);
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+// =============================================================================
+// Log load/save
+// =============================================================================
+
+pub fn load_receipts(path: &Path) -> ReceiptLog {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read receipts log: {e}");
+                return ReceiptLog::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    ReceiptLog::default()
+}
+
+pub fn save_receipts(log: &ReceiptLog, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(log)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Heuristics
+// =============================================================================
+
+fn has_receipt_phrase(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    RECEIPT_PHRASES.iter().any(|p| lower.contains(p))
+}
+
+/// The first PDF attachment whose name says invoice or receipt.
+fn invoice_pdf(email: &Email) -> Option<&str> {
+    email
+        .attachments
+        .iter()
+        .filter(|a| {
+            a.mime_type.eq_ignore_ascii_case("application/pdf")
+                || a.name.to_lowercase().ends_with(".pdf")
+        })
+        .map(|a| a.name.as_str())
+        .find(|name| {
+            let lower = name.to_lowercase();
+            lower.contains("invoice") || lower.contains("receipt")
+        })
+}
+
+/// Cheap check on list metadata (subject, preview, attachment names), so
+/// only likely receipts pay for a body fetch.
+pub fn is_candidate(email: &Email) -> bool {
+    has_receipt_phrase(&email.subject)
+        || has_receipt_phrase(&email.preview)
+        || invoice_pdf(email).is_some()
+}
+
+// The number is any run of digits, `,` and `.`; `parse_amount` decides
+// which separator is the decimal one.
+static SYMBOL_AMOUNT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(US\$|CA\$|A\$|\$|€|£|¥)\s?(\d[\d.,]*\d|\d)").expect("valid regex")
+});
+
+static CODE_AMOUNT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d[\d.,]*\d|\d)\s?(USD|EUR|GBP|CAD|AUD|JPY)\b").expect("valid regex")
+});
+
+static TOTAL_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(grand total|order total|total charged|amount due|amount paid|total)\b")
+        .expect("valid regex")
+});
+
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").expect("valid regex"));
+
+fn symbol_currency(symbol: &str) -> &'static str {
+    match symbol {
+        "€" => "EUR",
+        "£" => "GBP",
+        "¥" => "JPY",
+        "CA$" => "CAD",
+        "A$" => "AUD",
+        _ => "USD",
+    }
+}
+
+/// A written amount, in either convention: `1,234.50` or `1.234,50`.
+/// With both separators the last is the decimal point. With one kind, a
+/// single separator before one or two digits is a decimal point (`12,99`)
+/// and separators before groups of three group thousands (`1.234`).
+/// Anything else (`1,2,3`, `12.99,5`, `1.234,567`) is too ambiguous and
+/// gives `None`.
+fn parse_amount(s: &str) -> Option<f64> {
+    let decimal = match (s.rfind(','), s.rfind('.')) {
+        (Some(c), Some(d)) => Some(c.max(d)),
+        (Some(at), None) | (None, Some(at)) => {
+            let sep = s.as_bytes()[at] as char;
+            let tail = s.len() - at - 1;
+            (s.matches(sep).count() == 1 && (1..=2).contains(&tail)).then_some(at)
+        }
+        (None, None) => None,
+    };
+    let (whole, fraction) = match decimal {
+        Some(at) => (&s[..at], &s[at + 1..]),
+        None => (s, ""),
+    };
+    if fraction.len() > 2 || fraction.contains([',', '.']) {
+        return None;
+    }
+    let mut groups = whole.split([',', '.']);
+    let first = groups.next()?;
+    let grouped = whole.contains([',', '.']);
+    let separators: Vec<char> = whole.chars().filter(|c| !c.is_ascii_digit()).collect();
+    if grouped
+        && (first.is_empty()
+            || first.len() > 3
+            || separators.windows(2).any(|w| w[0] != w[1])
+            || groups.any(|g| g.len() != 3))
+    {
+        return None;
+    }
+    let digits: String = whole.chars().filter(char::is_ascii_digit).collect();
+    format!(
+        "{digits}.{}",
+        if fraction.is_empty() { "0" } else { fraction }
+    )
+    .parse()
+    .ok()
+}
+
+/// Every `(offset, amount, currency)` written as `$12.34`, `€12,34` or
+/// `12.34 EUR`.
+fn amounts(text: &str) -> Vec<(usize, f64, String)> {
+    let mut found: Vec<(usize, f64, String)> = SYMBOL_AMOUNT
+        .captures_iter(text)
+        .filter_map(|c| {
+            let value = parse_amount(&c[2])?;
+            Some((c.get(0)?.start(), value, symbol_currency(&c[1]).to_string()))
+        })
+        .chain(CODE_AMOUNT.captures_iter(text).filter_map(|c| {
+            let value = parse_amount(&c[1])?;
+            Some((c.get(0)?.start(), value, c[2].to_uppercase()))
+        }))
+        .collect();
+    found.sort_by_key(|(offset, ..)| *offset);
+    found
+}
+
+/// The charged amount in `text`: the first amount after a "total"-style
+/// label when there is one, otherwise the largest amount mentioned.
+pub fn extract_amount(text: &str) -> Option<(f64, String)> {
+    let found = amounts(text);
+    let after_total = TOTAL_LABEL.find_iter(text).find_map(|label| {
+        found
+            .iter()
+            .find(|(offset, ..)| *offset >= label.end() && *offset - label.end() <= 40)
+    });
+    after_total
+        .or_else(|| found.iter().max_by(|a, b| a.1.total_cmp(&b.1)))
+        .map(|(_, value, currency)| (*value, currency.clone()))
+}
+
+/// Sender display name, falling back to the registrable-looking tail of
+/// the sender's domain (`billing.example.com` → `example.com`).
+pub fn vendor(email: &Email) -> String {
+    let Some(from) = email.from.first() else {
+        return String::new();
+    };
+    if let Some(name) = from
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        return name.to_string();
+    }
+    let domain = from.email.rsplit('@').next().unwrap_or(&from.email);
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels[labels.len().saturating_sub(2)..].join(".")
+}
+
+/// A receipt record for `email`, or `None` when it doesn't look like one.
+/// A phrase match alone isn't enough ("your order has shipped"): it also
+/// needs an amount or an invoice PDF. `email` should carry its body; the
+/// amount is looked for in the subject, then the text (or tag-stripped
+/// HTML) body, then the preview.
+pub fn extract(email: &Email, account: &str, tz: chrono_tz::Tz) -> Option<ReceiptRecord> {
+    if !is_candidate(email) {
+        return None;
+    }
+    let pdf = invoice_pdf(email);
+    let body = match (&email.text_body, &email.html_body) {
+        (Some(text), _) if !text.trim().is_empty() => text.clone(),
+        (_, Some(html)) => HTML_TAG.replace_all(html, " ").into_owned(),
+        _ => String::new(),
+    };
+    let amount = [
+        email.subject.as_str(),
+        body.as_str(),
+        email.preview.as_str(),
+    ]
+    .into_iter()
+    .find_map(extract_amount);
+    if amount.is_none() && pdf.is_none() {
+        return None;
+    }
+    Some(ReceiptRecord {
+        account: account.to_string(),
+        email_id: email.id.clone(),
+        vendor: vendor(email),
+        amount: amount.as_ref().map(|(v, _)| *v),
+        currency: amount.map(|(_, c)| c),
+        date: email.received_at.with_timezone(&tz).date_naive(),
+        subject: email.subject.clone(),
+        invoice_pdf: pdf.map(str::to_string),
+    })
+}
+
+/// Parse a `YYYY-MM` month filter.
+pub fn parse_month(month: &str) -> Result<(i32, u32), Error> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map(|d| (d.year(), d.month()))
+        .map_err(|_| Error::BadRequest(format!("Month must be YYYY-MM, got '{month}'")))
+}
+
+/// Records dated in `month`, oldest first.
+pub fn in_month(log: &ReceiptLog, (year, month): (i32, u32)) -> Vec<ReceiptRecord> {
+    let mut records: Vec<ReceiptRecord> = log
+        .receipts
+        .iter()
+        .filter(|r| r.date.year() == year && r.date.month() == month)
+        .cloned()
+        .collect();
+    records.sort_by_key(|r| r.date);
+    records
+}
+
+// =============================================================================
+// Scanner
+// =============================================================================
+
+/// Scan every connected account every `interval`. A failed account is
+/// logged and retried on the next tick; its mail stays unfiled until then.
+pub fn spawn_scanner(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("receipts", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("receipts");
+            let accounts: Vec<String> = {
+                let reg = state.accounts.read().await;
+                reg.sessions.keys().cloned().collect()
+            };
+            for account in accounts {
+                match crate::routes::scan_receipts(&state, &account).await {
+                    Ok(added) if !added.is_empty() => {
+                        tracing::info!("Filed {} receipt(s) for {account}", added.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("Receipt scan failed for {account}: {e}");
+                        health.failed("receipts", format!("{account}: {e}"));
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Attachment, EmailAddress};
+
+    fn email(subject: &str, preview: &str) -> Email {
+        Email {
+            id: "m1".into(),
+            subject: subject.into(),
+            preview: preview.into(),
+            from: vec![EmailAddress {
+                name: None,
+                email: "billing@mail.shop.example.com".into(),
+            }],
+            received_at: "2026-10-01T03:00:00Z".parse().unwrap(),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: Default::default(),
+            keywords: Default::default(),
+            to: vec![],
+            cc: vec![],
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    fn pdf(name: &str) -> Attachment {
+        Attachment {
+            blob_id: "b1".into(),
+            name: name.into(),
+            mime_type: "application/pdf".into(),
+            size: 1,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
+        }
+    }
+
+    #[test]
+    fn extract_amount_prefers_total_over_largest() {
+        let text = "Subtotal $120.00\nDiscount -$30.00\nTotal: $90.00\nGift card balance $500.00";
+        assert_eq!(extract_amount(text), Some((90.0, "USD".into())));
+    }
+
+    #[test]
+    fn extract_amount_handles_codes_symbols_and_grouping() {
+        assert_eq!(
+            extract_amount("You paid 1,234.50 EUR"),
+            Some((1234.5, "EUR".into()))
+        );
+        assert_eq!(extract_amount("£7 and £12.99"), Some((12.99, "GBP".into())));
+        assert_eq!(extract_amount("no money here, order 1234"), None);
+    }
+
+    #[test]
+    fn extract_amount_reads_decimal_commas() {
+        assert_eq!(
+            extract_amount("Summe: 12,99 EUR"),
+            Some((12.99, "EUR".into()))
+        );
+        assert_eq!(extract_amount("Total €12,99"), Some((12.99, "EUR".into())));
+        assert_eq!(
+            extract_amount("Gesamt 1.234,50 EUR"),
+            Some((1234.5, "EUR".into()))
+        );
+        assert_eq!(extract_amount("Total €1.234"), Some((1234.0, "EUR".into())));
+        assert_eq!(extract_amount("Total $1,234"), Some((1234.0, "USD".into())));
+    }
+
+    #[test]
+    fn ambiguous_amounts_are_skipped() {
+        assert_eq!(parse_amount("1,2,3"), None);
+        assert_eq!(parse_amount("12.99,5"), None);
+        assert_eq!(parse_amount("1.234,567"), None);
+        assert_eq!(parse_amount("1,234.567.8"), None);
+        assert_eq!(parse_amount("1.234.567,8"), Some(1234567.8));
+        assert_eq!(parse_amount("7"), Some(7.0));
+    }
+
+    #[test]
+    fn extract_needs_amount_or_pdf_beyond_phrase() {
+        let tz = chrono_tz::UTC;
+        assert!(extract(&email("Your order has shipped", "Track it"), "a", tz).is_none());
+
+        let mut with_amount = email("Your receipt from Shop", "Total $42.10");
+        with_amount.text_body = Some("Thanks!".into());
+        let record = extract(&with_amount, "a", tz).unwrap();
+        assert_eq!(record.amount, Some(42.1));
+        assert_eq!(record.currency.as_deref(), Some("USD"));
+        assert_eq!(record.vendor, "example.com");
+
+        let mut with_pdf = email("October statement", "");
+        with_pdf.attachments = vec![pdf("Invoice-0042.pdf")];
+        let record = extract(&with_pdf, "a", tz).unwrap();
+        assert_eq!(record.amount, None);
+        assert_eq!(record.invoice_pdf.as_deref(), Some("Invoice-0042.pdf"));
+    }
+
+    #[test]
+    fn extract_dates_in_local_timezone() {
+        let record = extract(
+            &email("Receipt", "Total $5.00"),
+            "a",
+            chrono_tz::America::New_York,
+        )
+        .unwrap();
+        assert_eq!(record.date, "2026-09-30".parse::<NaiveDate>().unwrap());
+    }
+
+    #[test]
+    fn parse_month_and_filter() {
+        assert!(matches!(parse_month("2026-13"), Err(Error::BadRequest(_))));
+        let month = parse_month("2026-10").unwrap();
+        let record = |id: &str, date: &str| ReceiptRecord {
+            account: "a".into(),
+            email_id: id.into(),
+            vendor: "Shop".into(),
+            amount: Some(1.0),
+            currency: Some("USD".into()),
+            date: date.parse().unwrap(),
+            subject: "Receipt".into(),
+            invoice_pdf: None,
+        };
+        let mut log = ReceiptLog::default();
+        assert_eq!(
+            log.merge(vec![
+                record("late", "2026-10-20"),
+                record("early", "2026-10-02"),
+                record("sept", "2026-09-30"),
+            ]),
+            3
+        );
+        assert_eq!(log.merge(vec![record("late", "2026-10-20")]), 0);
+        let ids: Vec<String> = in_month(&log, month)
+            .into_iter()
+            .map(|r| r.email_id)
+            .collect();
+        assert_eq!(ids, ["early", "late"]);
+    }
+
+    #[test]
+    fn save_then_load_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("receipts.json");
+        let mut log = ReceiptLog::default();
+        log.merge(vec![
+            extract(&email("Receipt", "Total €3.50"), "a", chrono_tz::UTC).unwrap(),
+        ]);
+        save_receipts(&log, &path).unwrap();
+        assert_eq!(load_receipts(&path), log);
+    }
+}
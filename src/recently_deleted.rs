@@ -0,0 +1,243 @@
+//! Grace period for trash.
+//!
+//! Trashing a message through `/api/emails/{id}/trash` records where it
+//! was first: a [`TrashedRecord`] with the message's original mailboxes,
+//! appended to `recently-deleted.json`. `GET /api/recently-deleted` lists
+//! an account's records from the last few days, and
+//! `POST /api/recently-deleted/{id}/restore` puts the message back into
+//! those mailboxes and drops the record. Records older than
+//! [`RETENTION_DAYS`] are pruned whenever the journal is written.
+//!
+//! Outlook isn't journaled: Graph gives a moved message a new id, so the
+//! recorded one couldn't be restored.
+
+use crate::error::Error;
+use crate::types::{Email, EmailAddress};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How long a trashed message stays restorable from the journal, and the
+/// most `?days=` may ask for.
+pub const RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedRecord {
+    pub account: String,
+    pub email_id: String,
+    /// Mailboxes (labels on Gmail) the message was in before trashing.
+    pub original_mailbox_ids: Vec<String>,
+    pub trashed_at: DateTime<Utc>,
+    pub subject: String,
+    #[serde(default)]
+    pub from: Option<EmailAddress>,
+}
+
+impl TrashedRecord {
+    /// Record for `email` as it was just before trashing, or `None` when
+    /// it's already in `trash_id` and there is nothing to go back to.
+    pub fn before_trash(
+        account: &str,
+        email: &Email,
+        trash_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Option<Self> {
+        let mut original: Vec<String> = email
+            .mailbox_ids
+            .iter()
+            .filter(|(_, v)| **v)
+            .map(|(k, _)| k.clone())
+            .collect();
+        if original.is_empty() || trash_id.is_some_and(|t| original.iter().any(|id| id == t)) {
+            return None;
+        }
+        original.sort();
+        Some(Self {
+            account: account.to_string(),
+            email_id: email.id.clone(),
+            original_mailbox_ids: original,
+            trashed_at: now,
+            subject: email.subject.clone(),
+            from: email.from.first().cloned(),
+        })
+    }
+}
+
+/// On-disk shape of `recently-deleted.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TrashJournal {
+    #[serde(default)]
+    pub trashed: Vec<TrashedRecord>,
+}
+
+impl TrashJournal {
+    /// Add `record`, replacing an older one for the same message, and drop
+    /// records past [`RETENTION_DAYS`].
+    pub fn record(&mut self, record: TrashedRecord) {
+        let now = record.trashed_at;
+        self.remove(&record.account, &record.email_id);
+        self.trashed.push(record);
+        self.prune(now);
+    }
+
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - chrono::Duration::days(RETENTION_DAYS);
+        self.trashed.retain(|r| r.trashed_at >= cutoff);
+    }
+
+    pub fn remove(&mut self, account: &str, email_id: &str) -> Option<TrashedRecord> {
+        let pos = self
+            .trashed
+            .iter()
+            .position(|r| r.account == account && r.email_id == email_id)?;
+        Some(self.trashed.remove(pos))
+    }
+
+    pub fn find(&self, account: &str, email_id: &str) -> Option<&TrashedRecord> {
+        self.trashed
+            .iter()
+            .find(|r| r.account == account && r.email_id == email_id)
+    }
+
+    /// `account`'s records from the last `days` days, newest first.
+    pub fn recent(&self, account: &str, days: i64, now: DateTime<Utc>) -> Vec<TrashedRecord> {
+        let cutoff = now - chrono::Duration::days(days);
+        let mut out: Vec<TrashedRecord> = self
+            .trashed
+            .iter()
+            .filter(|r| r.account == account && r.trashed_at >= cutoff)
+            .cloned()
+            .collect();
+        out.sort_by_key(|r| std::cmp::Reverse(r.trashed_at));
+        out
+    }
+}
+
+// =============================================================================
+// Journal load/save
+// =============================================================================
+
+pub fn load_journal(path: &Path) -> TrashJournal {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read trash journal: {e}");
+                return TrashJournal::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    TrashJournal::default()
+}
+
+pub fn save_journal(journal: &TrashJournal, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(journal)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn email(id: &str, mailboxes: &[&str]) -> Email {
+        Email {
+            id: id.into(),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: mailboxes.iter().map(|m| (m.to_string(), true)).collect(),
+            keywords: HashMap::new(),
+            received_at: Utc::now(),
+            subject: "Quarterly report".into(),
+            from: vec![EmailAddress {
+                name: Some("Ann".into()),
+                email: "ann@example.com".into(),
+            }],
+            to: vec![],
+            cc: vec![],
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    fn record(account: &str, id: &str, days_ago: i64) -> TrashedRecord {
+        let now = Utc::now() - chrono::Duration::days(days_ago);
+        TrashedRecord::before_trash(account, &email(id, &["inbox"]), Some("trash"), now).unwrap()
+    }
+
+    #[test]
+    fn before_trash_captures_original_mailboxes() {
+        let r = TrashedRecord::before_trash(
+            "a",
+            &email("m1", &["work", "inbox"]),
+            Some("trash"),
+            Utc::now(),
+        )
+        .unwrap();
+        assert_eq!(r.original_mailbox_ids, vec!["inbox", "work"]);
+        assert_eq!(r.from.unwrap().email, "ann@example.com");
+        assert!(
+            TrashedRecord::before_trash("a", &email("m1", &["trash"]), Some("trash"), Utc::now())
+                .is_none(),
+            "a message already in trash has nothing to restore to"
+        );
+    }
+
+    #[test]
+    fn recent_filters_by_account_and_age_newest_first() {
+        let mut journal = TrashJournal::default();
+        journal.record(record("a", "old", 5));
+        journal.record(record("a", "new", 1));
+        journal.record(record("b", "other", 0));
+        let ids: Vec<String> = journal
+            .recent("a", 7, Utc::now())
+            .into_iter()
+            .map(|r| r.email_id)
+            .collect();
+        assert_eq!(ids, vec!["new", "old"]);
+        assert_eq!(journal.recent("a", 3, Utc::now()).len(), 1);
+    }
+
+    #[test]
+    fn record_replaces_and_prunes() {
+        let mut journal = TrashJournal::default();
+        journal
+            .trashed
+            .push(record("a", "stale", RETENTION_DAYS + 1));
+        journal.record(record("a", "m1", 2));
+        journal.record(record("a", "m1", 0));
+        assert_eq!(journal.trashed.len(), 1);
+        assert!(journal.find("a", "m1").is_some());
+        assert!(journal.remove("a", "m1").is_some());
+        assert!(journal.find("a", "m1").is_none());
+    }
+
+    #[test]
+    fn save_then_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recently-deleted.json");
+        let mut journal = TrashJournal::default();
+        journal.record(record("a", "m1", 0));
+        save_journal(&journal, &path).unwrap();
+        assert_eq!(load_journal(&path), journal);
+        assert_eq!(
+            load_journal(&dir.path().join("missing.json")),
+            TrashJournal::default()
+        );
+    }
+}
@@ -1,18 +1,25 @@
 use axum::{
     Router,
     body::Bytes,
-    extract::{Json, Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Json, OriginalUri, Path, Query, State},
+    http::{HeaderMap, StatusCode, Uri},
     response::IntoResponse,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
 };
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use crate::error::Error;
 use crate::types::*;
-use crate::{accounts, calendar, provider, search, splits, theme, timezone};
+use crate::{
+    accounts, cache, calendar, calendar_replies, commands, compose, compose_sessions, contacts,
+    diagnostics, digest, downloads, groups, image_proxy, logs, mute, newsletters, otp, packages,
+    perf, prefetch, priority, protected, provider, provider_utils, purge, read_only, receipts,
+    recently_deleted, rsvp_log, rules, sanitize, search, send_guard, send_hours, sieve,
+    signature_image, snooze, splits, template_vars, theme, thread_pdf, timezone, undo_send,
+    unsubscribe, update,
+};
 
 pub(crate) const SPLIT_OVERFETCH_MULTIPLIER: usize = 10;
 
@@ -47,27 +54,85 @@ const FONT_JBM_BOLD: &[u8] = include_bytes!("../static/fonts/JetBrainsMono-Bold.
 // Router
 // =============================================================================
 
+/// Threshold for `SUPERVILLAIN_COMPRESS_MIN_BYTES=on`: below this, gzip framing
+/// costs more than it saves.
+pub const DEFAULT_COMPRESS_MIN_BYTES: u16 = 1024;
+
+/// Gzip for API JSON only. Keyed on content type rather than path so the
+/// SSE stream (`text/event-stream`), attachment and `/body/full` streams,
+/// and the embedded static assets are never buffered through an encoder.
+pub fn compression_layer(
+    min_bytes: u16,
+) -> tower_http::compression::CompressionLayer<impl tower_http::compression::Predicate> {
+    use tower_http::compression::{Predicate, predicate::SizeAbove};
+    let json_only = |_: StatusCode,
+                     _: axum::http::Version,
+                     headers: &HeaderMap,
+                     _: &axum::http::Extensions| { is_json_response(headers) };
+    tower_http::compression::CompressionLayer::new()
+        .compress_when(SizeAbove::new(min_bytes).and(json_only))
+}
+
+fn is_json_response(headers: &HeaderMap) -> bool {
+    headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"))
+}
+
 pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .merge(accounts::router())
         .route("/api/accounts", get(list_accounts))
         .route("/api/identities", get(list_identities))
         .route("/api/theme", get(get_theme))
-        .route("/api/mailboxes", get(list_mailboxes))
+        .route(
+            "/api/mailboxes",
+            get(list_mailboxes).post(create_mailbox_handler),
+        )
+        .route(
+            "/api/mailboxes/{mailbox_id}",
+            put(update_mailbox_handler).delete(delete_mailbox_handler),
+        )
+        .route(
+            "/api/mailboxes/{mailbox_id}/mark-all-read",
+            post(mark_mailbox_read),
+        )
+        .route("/api/mailboxes/{mailbox_id}/empty", post(empty_mailbox))
         .route("/api/emails", get(list_emails))
+        .route("/api/search", get(search_emails))
         .route("/api/upload", post(upload_blob))
         .route("/api/emails/send", post(send_email_handler))
-        .route("/api/drafts", post(create_draft_handler))
+        .route("/api/emails/send/{token}", get(send_status))
+        .route("/api/emails/send/{token}/cancel", post(cancel_send))
+        .route(
+            "/api/drafts",
+            get(list_drafts_handler).post(create_draft_handler),
+        )
         .route(
             "/api/drafts/{draft_id}",
             put(update_draft_handler).delete(delete_draft_handler),
         )
-        .route("/api/emails/{email_id}", get(get_email))
+        .route(
+            "/api/emails/{email_id}",
+            get(get_email).delete(delete_email),
+        )
+        .route("/api/threads/{thread_id}", get(get_thread))
+        .route("/api/threads/{thread_id}/mark-read", post(mark_thread_read))
+        .route(
+            "/api/threads/{thread_id}/mute",
+            post(mute_thread).delete(unmute_thread),
+        )
+        .route("/api/muted-threads", get(list_muted_threads))
+        .route("/api/threads/{thread_id}/pdf", get(export_thread_pdf))
         .route("/api/emails/{email_id}/archive", post(archive_email))
         .route("/api/emails/{email_id}/trash", post(trash_email))
         .route("/api/emails/{email_id}/mark-read", post(mark_read))
         .route("/api/emails/{email_id}/mark-unread", post(mark_unread))
         .route("/api/emails/{email_id}/toggle-flag", post(toggle_flag))
+        .route("/api/emails/{email_id}/flag-color", post(set_flag_color))
+        .route("/api/emails/{email_id}/keywords", post(set_keywords))
+        .route("/api/flag-colors", get(flag_colors))
         .route("/api/emails/{email_id}/move", post(move_email))
         .route("/api/emails/{email_id}/rsvp", post(rsvp))
         .route(
@@ -78,16 +143,94 @@ pub fn router(state: Arc<AppState>) -> Router {
             "/api/emails/{email_id}/attachments/{blob_id}/{filename}",
             get(download_attachment),
         )
+        .route(
+            "/api/emails/{email_id}/attachments.zip",
+            get(download_attachments_zip),
+        )
+        .route("/api/emails/{email_id}/event.ics", get(event_ics))
+        .route("/api/emails/{email_id}/body/full", get(get_full_body))
+        .route("/api/emails/{email_id}/headers", get(get_headers))
+        .route("/api/emails/{email_id}/raw", get(get_raw_message))
+        .route(
+            "/api/emails/{email_id}/compose-context",
+            get(compose_context),
+        )
+        .route("/api/emails/{email_id}/reply", get(reply_draft))
         .route(
             "/api/emails/{email_id}/unsubscribe-and-archive-all",
             post(unsubscribe_and_archive),
         )
+        .route("/api/suggestions/unsubscribe", get(unsubscribe_suggestions))
         .route("/api/split-counts", get(split_counts))
+        .route("/api/dashboard", get(dashboard))
+        .route("/api/digest", get(get_digest).put(put_digest))
+        .route("/api/digest/run", post(run_digest_handler))
+        .route("/api/receipts", get(list_receipts))
+        .route("/api/receipts/scan", post(scan_receipts_handler))
+        .route("/api/packages", get(list_packages))
+        .route("/api/otp/latest", get(latest_otp))
+        .route("/api/recently-deleted", get(list_recently_deleted))
+        .route("/api/snoozed", get(list_snoozed))
+        .route(
+            "/api/emails/{email_id}/snooze",
+            post(snooze_email).delete(unsnooze_email),
+        )
+        .route(
+            "/api/recently-deleted/{email_id}/restore",
+            post(restore_recently_deleted),
+        )
+        .route("/api/contacts", get(list_contacts))
+        .route("/api/contacts/groups", get(list_contact_groups))
+        .route("/api/contacts/sync", post(sync_contacts))
+        .route(
+            "/api/compose-sessions",
+            get(list_compose_sessions).post(create_compose_session),
+        )
+        .route(
+            "/api/compose-sessions/{session_id}",
+            get(get_compose_session)
+                .put(update_compose_session)
+                .delete(delete_compose_session),
+        )
         .route("/api/splits", get(list_splits).post(create_split))
+        .route("/api/splits/export", get(export_splits))
+        .route("/api/splits/import", post(import_splits))
+        .route("/api/splits/preview", post(preview_split))
         .route(
             "/api/splits/{split_id}",
             put(update_split).delete(delete_split),
         )
+        .route("/api/rules/simulate", post(simulate_rule))
+        .route("/api/sieve", get(get_sieve).put(put_sieve))
+        .route("/api/sieve/from-split/{split_id}", post(sieve_from_split))
+        .route("/api/commands", get(list_commands))
+        .route("/api/help", get(help))
+        .route("/api/events", get(server_events))
+        .route("/api/logs/tail", get(logs_tail))
+        .route("/api/diagnostics", get(diagnostics_bundle))
+        .route("/api/resolve", get(resolve_message_id))
+        .route("/api/changes", get(email_changes))
+        .route("/api/groups", get(list_groups).post(create_group))
+        .route(
+            "/api/groups/{group_id}",
+            put(update_group).delete(delete_group),
+        )
+        .route(
+            "/api/protected-senders",
+            get(list_protected_senders).post(add_protected_sender),
+        )
+        .route(
+            "/api/protected-senders/{sender}",
+            delete(remove_protected_sender),
+        )
+        .route("/api/proxy-image", get(proxy_image))
+        .route(
+            "/api/image-senders",
+            get(list_image_senders).post(add_image_sender),
+        )
+        .route("/api/image-senders/{sender}", delete(remove_image_sender))
+        .route("/api/preferences", get(get_preferences))
+        .route("/api/status", get(get_status))
         .route("/api/timezone", get(get_timezone).put(put_timezone))
         .route("/api/timezone/accept-system", post(accept_system_timezone))
         .route(
@@ -96,10 +239,30 @@ pub fn router(state: Arc<AppState>) -> Router {
         )
         .route("/api/timezone/zones", get(list_timezones))
         .route("/api/calendar/invite", post(send_invite_handler))
+        .route("/api/calendar/replies", get(list_applied_replies))
+        .route("/api/events/{uid}/cancel", post(cancel_event))
+        .route("/api/events/{uid}/responses", get(event_responses))
+        .route("/api/jmap", post(jmap_passthrough))
         .route("/api/build-id", get(build_id))
+        .route("/api/cache/clear", post(clear_cache))
+        .route("/metrics", get(metrics))
+        .route("/api/perf", get(perf_summary))
+        .route("/api/version", get(version))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            read_only::guard,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            perf::track,
+        ))
         .with_state(state)
         .route("/", get(index_html))
         .route("/index.html", get(index_html))
+        // Permalinks: the SPA reads its initial state from the URL (see
+        // applyPermalink in app.js).
+        .route("/m/{email_id}", get(index_html))
+        .route("/search", get(index_html))
         .route("/app.js", get(app_js))
         .route("/api.js", get(api_js))
         .route("/style.css", get(style_css))
@@ -178,6 +341,113 @@ async fn build_id() -> impl IntoResponse {
     )
 }
 
+// Prometheus text exposition of the body cache's occupancy and counters.
+async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
+        render_metrics(&state.prefetch.body_cache_stats()),
+    )
+}
+
+/// `GET /api/perf`: per-route timing aggregates from `perf::track`.
+/// `enabled` is false (and `routes` empty) unless `SUPERVILLAIN_PERF` is on.
+async fn perf_summary(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "enabled": state.perf.is_enabled(),
+        "routes": state.perf.summary(),
+    }))
+}
+
+/// `GET /api/version`: crate version, commit and build date. `update` is
+/// the latest release from the daily check, null until it has run or
+/// unless `SUPERVILLAIN_UPDATE_CHECK` is on.
+async fn version(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let build = update::BuildInfo::current();
+    Json(serde_json::json!({
+        "version": build.version,
+        "gitHash": build.git_hash,
+        "buildDate": build.build_date,
+        "updateCheck": state.update_check.enabled(),
+        "update": state.update_check.status(),
+    }))
+}
+
+fn render_metrics(stats: &prefetch::BodyCacheStats) -> String {
+    let series = [
+        (
+            "supervillain_body_cache_entries",
+            "gauge",
+            "Cached email bodies.",
+            stats.entries as u64,
+        ),
+        (
+            "supervillain_body_cache_bytes",
+            "gauge",
+            "Estimated bytes held by cached bodies.",
+            stats.bytes as u64,
+        ),
+        (
+            "supervillain_body_cache_capacity_bytes",
+            "gauge",
+            "Body cache budget (body-cache-mb).",
+            stats.capacity_bytes as u64,
+        ),
+        (
+            "supervillain_body_cache_hits_total",
+            "counter",
+            "Body lookups served from cache.",
+            stats.hits,
+        ),
+        (
+            "supervillain_body_cache_misses_total",
+            "counter",
+            "Body lookups that missed the cache.",
+            stats.misses,
+        ),
+        (
+            "supervillain_body_cache_evictions_total",
+            "counter",
+            "Bodies evicted to stay within budget.",
+            stats.evictions,
+        ),
+        (
+            "supervillain_body_cache_evicted_bytes_total",
+            "counter",
+            "Estimated bytes evicted to stay within budget.",
+            stats.evicted_bytes,
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in series {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    }
+    out
+}
+
+/// `POST /api/cache/clear[?account=]`: drop cached bodies, in memory and
+/// in `email_cache`, for one account or for every account when none is
+/// given. Lists and counts stay; the warmer refetches bodies on its next
+/// pass.
+async fn clear_cache(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<Json<serde_json::Value>, Error> {
+    let account = match params.account.as_deref() {
+        Some(a) => Some(resolve_account_id(&state, Some(a)).await?),
+        None => None,
+    };
+    let (bodies, bytes) = state.prefetch.clear_bodies(account.as_deref());
+    let stored = state.email_cache.clear(account.as_deref());
+    tracing::info!(account = ?account, bodies, bytes, stored, "cleared body cache");
+    Ok(Json(serde_json::json!({
+        "clearedBodies": bodies,
+        "freedBytes": bytes,
+        "clearedStored": stored,
+    })))
+}
+
 async fn api_js() -> impl IntoResponse {
     (
         [("content-type", "application/javascript; charset=utf-8")],
@@ -286,6 +556,15 @@ struct ListEmailsParams {
     /// deserialization (400), never silently coerced to the default —
     /// see `EmailSort`'s doc comment (kata 09ef).
     sort: Option<EmailSort>,
+    /// Wrap the list as `{emails, paging}` instead of a bare array. The
+    /// `Link` header is sent either way; the envelope is opt-in so the
+    /// bundled frontends keep their array shape.
+    paging: Option<bool>,
+    /// Window mode: the position of the first row; see `list_window`.
+    window: Option<usize>,
+    /// Window mode anchored on this email id instead of a position.
+    anchor: Option<String>,
+    anchor_offset: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -304,9 +583,30 @@ struct SendEmailBody {
     body: String,
     html_body: Option<String>,
     in_reply_to: Option<String>,
+    /// Message-IDs for the References header, e.g. from
+    /// `/api/emails/{id}/reply`. Gmail fills it in from `in_reply_to`
+    /// when absent.
+    references: Option<Vec<String>>,
     from_address: Option<String>,
     #[serde(default)]
     attachments: Vec<Attachment>,
+    /// Email id whose attachments are sent along, e.g. for a forward.
+    /// They keep their provider blob ids instead of being uploaded again.
+    forward_of: Option<String>,
+    /// Send one copy per `to` recipient instead of a single message with a
+    /// shared To line — e.g. a `group:` expansion where members shouldn't
+    /// see each other's addresses.
+    #[serde(default)]
+    individual: bool,
+    /// Authenticator code for sends to guarded recipients (`send_guard`).
+    totp_code: Option<String>,
+    /// Send anyway after a send-time warning (`send_hours`).
+    #[serde(default)]
+    confirm_send_time: bool,
+    /// The compose's autosaved draft. During the undo window it's replaced
+    /// with the message being sent, so a cancelled send leaves that in
+    /// Drafts rather than a second copy.
+    draft_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -345,6 +645,14 @@ struct GetEmailParams {
     mark_read: Option<bool>,
 }
 
+/// `/api/emails/{id}/body/full` query. `part` picks a body part by JMAP
+/// partId; absent, the first truncated part (HTML preferred) is served.
+#[derive(Deserialize, Default)]
+struct FullBodyParams {
+    account: Option<String>,
+    part: Option<String>,
+}
+
 // =============================================================================
 // Account resolution
 // =============================================================================
@@ -353,19 +661,32 @@ struct GetEmailParams {
 /// client-supplied account id. One message format, one place to change
 /// validation (e.g. future case normalization).
 fn ensure_known_account(reg: &AccountRegistry, id: &str) -> Result<(), Error> {
-    if !reg.account_configs.contains_key(id) {
+    let (name, _) = accounts::split_delegated(id);
+    if !reg.account_configs.contains_key(name) {
         return Err(Error::BadRequest(format!("Unknown account '{id}'")));
     }
     Ok(())
 }
 
+/// The session for `account` (default if None). A `work#<id>` key opens
+/// the shared account `<id>` through `work`'s login; see
+/// `accounts::split_delegated`.
 async fn resolve_session(state: &AppState, account: Option<&str>) -> Result<SessionLock, Error> {
-    let reg = state.accounts.read().await;
-    let key = account.unwrap_or(&reg.default_account);
-    reg.sessions
-        .get(key)
-        .cloned()
-        .ok_or_else(|| Error::BadRequest(format!("Unknown account '{key}'")))
+    let (session, shared) = {
+        let reg = state.accounts.read().await;
+        let key = account.unwrap_or(&reg.default_account);
+        let (name, shared) = accounts::split_delegated(key);
+        let session = reg
+            .sessions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::BadRequest(format!("Unknown account '{key}'")))?;
+        (session, shared.map(String::from))
+    };
+    match shared {
+        Some(shared) => provider::delegated_session(&session, &shared).await,
+        None => Ok(session),
+    }
 }
 
 /// Resolve just the account ID (default if None), without requiring the
@@ -399,6 +720,7 @@ async fn list_accounts(State(state): State<Arc<AppState>>) -> impl IntoResponse
 
     let reg = state.accounts.read().await;
     let mut live = std::collections::HashMap::new();
+    let mut shared = std::collections::HashMap::new();
     for (name, session_lock) in &reg.sessions {
         let session = session_lock.read().await;
         live.insert(
@@ -408,9 +730,28 @@ async fn list_accounts(State(state): State<Arc<AppState>>) -> impl IntoResponse
                 session.provider_name().to_string(),
             ),
         );
+        shared.insert(name.clone(), provider::shared_accounts(&session));
     }
-    let accounts =
+    let mut accounts =
         crate::accounts::wire_account_list(&reg.account_configs, &live, &reg.default_account);
+    // Shared and delegated mailboxes the login can open, with the
+    // `?account=` key that addresses each.
+    for account in &mut accounts {
+        let id = account["id"].as_str().unwrap_or_default().to_string();
+        let list: Vec<_> = shared
+            .remove(&id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| {
+                serde_json::json!({
+                    "account": crate::accounts::delegated_key(&id, &a.id),
+                    "name": a.name,
+                    "readOnly": a.read_only,
+                })
+            })
+            .collect();
+        account["sharedAccounts"] = serde_json::json!(list);
+    }
 
     let mut errors = state.account_errors.read().await.clone();
     // Hand-edits made after startup never take effect (config is loaded once
@@ -491,7 +832,424 @@ async fn list_mailboxes(
             provider::get_mailboxes(&session).await
         })
         .await?;
-    Ok(Json(serde_json::json!(mailboxes)))
+    let mut out = serde_json::json!(mailboxes);
+    if let Some(list) = out.as_array_mut() {
+        list.extend(shared_mailboxes(&state, &id).await);
+    }
+    Ok(Json(out))
+}
+
+/// Mailboxes of the accounts shared with `account`'s login, each labeled
+/// with the key that addresses it (`account`) and its owner
+/// (`accountName`). A shared account that fails to load is left out.
+async fn shared_mailboxes(state: &AppState, account: &str) -> Vec<serde_json::Value> {
+    if accounts::split_delegated(account).1.is_some() {
+        return Vec::new();
+    }
+    let shared = match resolve_session(state, Some(account)).await {
+        Ok(lock) => provider::shared_accounts(&*lock.read().await),
+        Err(_) => return Vec::new(),
+    };
+    let mut out = Vec::new();
+    for shared in shared {
+        let key = accounts::delegated_key(account, &shared.id);
+        let mailboxes = state
+            .prefetch
+            .mailboxes_or_fetch(&key, || async {
+                let session_lock = resolve_session(state, Some(&key)).await?;
+                let session = session_lock.read().await;
+                provider::get_mailboxes(&session).await
+            })
+            .await;
+        let mailboxes = match mailboxes {
+            Ok(m) => m,
+            Err(e) => {
+                tracing::warn!("Shared account {} of {account} skipped: {e}", shared.name);
+                continue;
+            }
+        };
+        out.extend(mailboxes.into_iter().map(|m| {
+            let mut json = serde_json::json!(m);
+            json["account"] = serde_json::json!(key);
+            json["accountName"] = serde_json::json!(shared.name);
+            json["readOnly"] = serde_json::json!(shared.read_only);
+            json
+        }));
+    }
+    out
+}
+
+#[derive(Deserialize)]
+struct CreateMailboxBody {
+    name: String,
+    #[serde(default)]
+    parent_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpdateMailboxBody {
+    #[serde(default)]
+    name: Option<String>,
+    /// Absent keeps the parent; `null` moves the mailbox to the top level.
+    #[serde(default, deserialize_with = "present_or_null")]
+    parent_id: Option<Option<String>>,
+}
+
+#[derive(Deserialize)]
+struct DeleteMailboxParams {
+    account: Option<String>,
+    /// Destroy the mailbox's messages with it instead of refusing.
+    #[serde(default)]
+    remove_emails: bool,
+}
+
+/// Tells a JSON `null` (`Some(None)`) apart from a missing key (`None`,
+/// via `#[serde(default)]`).
+fn present_or_null<'de, D>(deserializer: D) -> Result<Option<Option<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+/// A mailbox name as given, trimmed; `/` is refused because Gmail reads
+/// it as nesting.
+fn mailbox_name(raw: &str) -> Result<&str, Error> {
+    let name = raw.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest("Mailbox name must not be empty".into()));
+    }
+    if name.contains('/') {
+        return Err(Error::BadRequest(
+            "Mailbox name must not contain '/'; set parent_id to nest it".into(),
+        ));
+    }
+    Ok(name)
+}
+
+/// The account's mailbox `mailbox_id`, refusing provider-managed ones
+/// (Inbox, Sent, Trash and the other role mailboxes) for `action`.
+async fn user_mailbox(
+    session: &provider::ProviderSession,
+    mailbox_id: &str,
+    action: &str,
+) -> Result<Mailbox, Error> {
+    let mailbox = provider::get_mailboxes(session)
+        .await?
+        .into_iter()
+        .find(|mb| mb.id == mailbox_id)
+        .ok_or_else(|| Error::NotFound(format!("Mailbox {mailbox_id} not found")))?;
+    if let Some(role) = &mailbox.role {
+        return Err(Error::BadRequest(format!(
+            "The {role} mailbox '{}' can't be {action}",
+            mailbox.name
+        )));
+    }
+    Ok(mailbox)
+}
+
+/// `POST /api/mailboxes`: create a mailbox, nested under `parent_id` when
+/// given. Returns `{id}`.
+async fn create_mailbox_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<CreateMailboxBody>,
+) -> Result<impl IntoResponse, Error> {
+    let name = mailbox_name(&body.name)?;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let mut session = session_lock.write().await;
+    let parent_id = body.parent_id.as_deref().filter(|p| !p.is_empty());
+    let mailbox_id = provider::create_mailbox(&session, name, parent_id).await?;
+    mailboxes_changed(&state, &id, &mut session).await;
+    Ok(Json(serde_json::json!({ "id": mailbox_id })))
+}
+
+/// `PUT /api/mailboxes/{id}`: rename (`name`) and/or move (`parent_id`,
+/// `null` for the top level) a mailbox.
+async fn update_mailbox_handler(
+    State(state): State<Arc<AppState>>,
+    Path(mailbox_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<UpdateMailboxBody>,
+) -> Result<impl IntoResponse, Error> {
+    let name = body.name.as_deref().map(mailbox_name).transpose()?;
+    if name.is_none() && body.parent_id.is_none() {
+        return Err(Error::BadRequest(
+            "Nothing to change: give name and/or parent_id".into(),
+        ));
+    }
+    if body.parent_id.as_ref().and_then(Option::as_deref) == Some(mailbox_id.as_str()) {
+        return Err(Error::BadRequest(
+            "A mailbox can't be its own parent".into(),
+        ));
+    }
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let mut session = session_lock.write().await;
+    user_mailbox(&session, &mailbox_id, "renamed or moved").await?;
+    let parent_id = body.parent_id.as_ref().map(Option::as_deref);
+    provider::update_mailbox(&session, &mailbox_id, name, parent_id).await?;
+    mailboxes_changed(&state, &id, &mut session).await;
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// `DELETE /api/mailboxes/{id}?remove_emails=`: delete a mailbox. Role
+/// mailboxes are refused.
+async fn delete_mailbox_handler(
+    State(state): State<Arc<AppState>>,
+    Path(mailbox_id): Path<String>,
+    Query(params): Query<DeleteMailboxParams>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let mut session = session_lock.write().await;
+    user_mailbox(&session, &mailbox_id, "deleted").await?;
+    provider::destroy_mailbox(&session, &mailbox_id, params.remove_emails).await?;
+    mailboxes_changed(&state, &id, &mut session).await;
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// After a mailbox create/update/delete: refresh the session's mailbox
+/// lookup and drop the prefetched list.
+async fn mailboxes_changed(
+    state: &AppState,
+    account: &str,
+    session: &mut provider::ProviderSession,
+) {
+    if let Err(e) = provider::refresh_mailbox_cache(session).await {
+        tracing::warn!("[{account}] Couldn't refresh mailboxes after a change: {e}");
+    }
+    state.prefetch.invalidate(account).await;
+}
+
+/// Unread ids fetched per `mark_mailbox_read` round.
+const MARK_ALL_READ_PAGE: usize = 500;
+
+/// Upper bound on `mark_mailbox_read` rounds (50,000 messages), so a
+/// provider whose unread filter lags its writes can't spin forever.
+const MARK_ALL_READ_MAX_ROUNDS: usize = 100;
+
+/// `POST /api/mailboxes/{id}/mark-all-read`: page through the mailbox's
+/// unread ids and mark them read in bulk. Each round re-queries from
+/// offset 0 — the messages just marked drop out of the unread filter —
+/// and stops when a page has nothing new or nothing in it changed.
+async fn mark_mailbox_read(
+    State(state): State<Arc<AppState>>,
+    Path(mailbox_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let unread = ParsedQuery {
+        is_unread: Some(true),
+        ..Default::default()
+    };
+    let mut updated = 0usize;
+    let mut seen = std::collections::HashSet::new();
+    let mut result = Ok(());
+    for _ in 0..MARK_ALL_READ_MAX_ROUNDS {
+        let ids = match provider::query_emails(
+            &session,
+            Some(&mailbox_id),
+            MARK_ALL_READ_PAGE,
+            0,
+            Some(&unread),
+            EmailSort::default(),
+        )
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        // A provider whose search index lags its writes can hand back ids
+        // marked in an earlier round; only new ones count.
+        let ids: Vec<String> = ids.into_iter().filter(|i| seen.insert(i.clone())).collect();
+        if ids.is_empty() {
+            break;
+        }
+        match provider::mark_read_batch(&session, &ids).await {
+            Ok(0) => break,
+            Ok(n) => updated += n,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    drop(session);
+    // Earlier rounds landed even if a later one failed.
+    if updated > 0 {
+        state.prefetch.invalidate(&id).await;
+    }
+    result?;
+    Ok(Json(serde_json::json!({ "updated": updated })))
+}
+
+/// Ids fetched per `empty_mailbox` round, and the most rounds (50,000
+/// messages) before it stops.
+const EMPTY_MAILBOX_PAGE: usize = 500;
+const EMPTY_MAILBOX_MAX_ROUNDS: usize = 100;
+
+#[derive(Deserialize)]
+struct EmptyMailboxParams {
+    account: Option<String>,
+    /// Token from an earlier unconfirmed call; see `purge`.
+    confirm: Option<String>,
+}
+
+/// `POST /api/mailboxes/{id}/empty`: permanently delete everything in
+/// Trash or Spam. Without `?confirm=` nothing is deleted; the response
+/// carries the message count and a confirm token to repeat the call with.
+async fn empty_mailbox(
+    State(state): State<Arc<AppState>>,
+    Path(mailbox_id): Path<String>,
+    Query(params): Query<EmptyMailboxParams>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    if !session.supports_permanent_delete() {
+        return Err(provider::permanent_delete_unsupported(&session));
+    }
+    let mailboxes = provider::get_mailboxes(&session).await?;
+    let mailbox = mailboxes
+        .iter()
+        .find(|mb| mb.id == mailbox_id)
+        .ok_or_else(|| Error::NotFound(format!("Mailbox {mailbox_id} not found")))?;
+    if !purge::is_purgeable(mailbox.role.as_deref()) {
+        return Err(Error::BadRequest(format!(
+            "Only Trash and Spam can be emptied, not '{}'",
+            mailbox.name
+        )));
+    }
+    let now = chrono::Utc::now();
+    let Some(token) = params.confirm.as_deref() else {
+        let (token, pending) =
+            state
+                .empty_confirmations
+                .issue(&id, &mailbox_id, mailbox.total_emails, now);
+        return Ok(Json(serde_json::json!({
+            "confirm": token,
+            "count": pending.count,
+            "mailbox": mailbox.name,
+            "expiresAt": pending.expires_at,
+        })));
+    };
+    if state
+        .empty_confirmations
+        .take(token, &id, &mailbox_id, now)
+        .is_none()
+    {
+        return Err(Error::Conflict(
+            "confirm token is invalid or expired; request a new one".into(),
+        ));
+    }
+
+    let mut destroyed_ids: Vec<String> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut result = Ok(());
+    for _ in 0..EMPTY_MAILBOX_MAX_ROUNDS {
+        let ids = match provider::query_emails(
+            &session,
+            Some(&mailbox_id),
+            EMPTY_MAILBOX_PAGE,
+            0,
+            None,
+            EmailSort::default(),
+        )
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let ids: Vec<String> = ids.into_iter().filter(|i| seen.insert(i.clone())).collect();
+        if ids.is_empty() {
+            break;
+        }
+        match provider::destroy_batch(&session, &ids).await {
+            Ok(0) => break,
+            Ok(_) => destroyed_ids.extend(ids),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    drop(session);
+    if !destroyed_ids.is_empty() {
+        forget_trashed(&state, &id, &destroyed_ids).await;
+        state.prefetch.invalidate(&id).await;
+    }
+    result?;
+    Ok(Json(
+        serde_json::json!({ "destroyed": destroyed_ids.len() }),
+    ))
+}
+
+/// Drop trash-journal records for messages that no longer exist.
+async fn forget_trashed(state: &AppState, account: &str, email_ids: &[String]) {
+    let _guard = state.recently_deleted_lock.lock().await;
+    let mut journal = recently_deleted::load_journal(&state.recently_deleted_path);
+    let before = journal.trashed.len();
+    for email_id in email_ids {
+        journal.remove(account, email_id);
+    }
+    if journal.trashed.len() == before {
+        return;
+    }
+    if let Err(e) = recently_deleted::save_journal(&journal, &state.recently_deleted_path) {
+        tracing::warn!("Failed to save trash journal: {e}");
+    }
+}
+
+/// Prefix marking a built-in smart mailbox in `/api/emails?mailbox_id=`.
+pub const VIRTUAL_MAILBOX_PREFIX: &str = "virtual:";
+
+/// Built-in smart mailboxes: cross-mailbox views that need no split config.
+/// Attachments and Flagged translate to provider query filters; Calendar has
+/// no provider-side filter on any backend, so it over-fetches and keeps rows
+/// whose `hasCalendar` is set, the same way split filtering works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VirtualMailbox {
+    Attachments,
+    Flagged,
+    Calendar,
+}
+
+impl VirtualMailbox {
+    /// `None` for a real mailbox id; an unknown `virtual:` name is a 400
+    /// rather than a provider lookup of a mailbox that can't exist.
+    fn parse(mailbox_id: &str) -> Option<Result<Self, Error>> {
+        let name = mailbox_id.strip_prefix(VIRTUAL_MAILBOX_PREFIX)?;
+        Some(match name {
+            "attachments" => Ok(Self::Attachments),
+            "flagged" => Ok(Self::Flagged),
+            "calendar" => Ok(Self::Calendar),
+            _ => Err(Error::BadRequest(format!(
+                "Unknown virtual mailbox '{mailbox_id}'"
+            ))),
+        })
+    }
+
+    fn apply(self, query: &mut crate::types::ParsedQuery) {
+        match self {
+            Self::Attachments => query.has_attachment = true,
+            Self::Flagged => query.is_flagged = Some(true),
+            Self::Calendar => {}
+        }
+    }
+
+    fn keeps(self, email: &Email) -> bool {
+        self != Self::Calendar || email.has_calendar
+    }
 }
 
 /// Whether a `list_emails` request is eligible for the prefetch cache.
@@ -503,6 +1261,8 @@ async fn list_mailboxes(
 ///
 /// - mailbox/split/search/starred/offset/limit: cache key would explode,
 ///   and the data is per-query anyway.
+/// - virtual mailboxes: the warmer only walks real mailboxes, so a cached
+///   `virtual:*` slot would never be refreshed.
 /// - sort: the background warmer only ever re-warms the `DateDesc` slot
 ///   (see `prefetch::warm_all_mailboxes`), and there's no TTL on cache
 ///   entries. If a non-default sort were cacheable, a user sitting in
@@ -511,7 +1271,10 @@ async fn list_mailboxes(
 ///   invalidated the whole account's cache (roborev 291). Simplest fix:
 ///   non-default sorts just aren't cacheable, full stop.
 fn list_is_cacheable(params: &ListEmailsParams, offset: usize, sort: EmailSort) -> bool {
-    params.mailbox_id.is_some()
+    params
+        .mailbox_id
+        .as_deref()
+        .is_some_and(|m| !m.starts_with(VIRTUAL_MAILBOX_PREFIX))
         && params.split_id.is_none()
         && params.search.is_none()
         && params.starred != Some(true)
@@ -520,14 +1283,88 @@ fn list_is_cacheable(params: &ListEmailsParams, offset: usize, sort: EmailSort)
         && sort == EmailSort::default()
 }
 
+/// Where the next `/api/emails` page starts. `has_more` is inferred from
+/// the provider filling the whole id window (there is no cheap total on
+/// every backend), so the last page of an exactly-divisible list costs one
+/// extra empty request — the same stopping rule a script would use.
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListPaging {
+    next_offset: Option<usize>,
+    has_more: bool,
+    /// Id of the last row on this page, so a client can notice that new
+    /// mail shifted the window between requests.
+    anchor: Option<String>,
+}
+
+/// `consumed` is how many provider ids this page used up — more than the
+/// rows returned when a split filter dropped non-matches. `truncated` means
+/// matches were cut to fit `limit`, so there is more regardless of how
+/// full the id window was.
+fn list_paging(
+    offset: usize,
+    fetch_limit: usize,
+    consumed: usize,
+    truncated: bool,
+    anchor: Option<&str>,
+) -> ListPaging {
+    let has_more = truncated || (fetch_limit > 0 && consumed >= fetch_limit);
+    ListPaging {
+        next_offset: has_more.then_some(offset + consumed),
+        has_more,
+        anchor: anchor.map(str::to_string),
+    }
+}
+
+/// RFC 5988 `Link` value for `/api/emails`: `rel="next"` when there is
+/// more, `rel="prev"` when this page didn't start at zero. Every query
+/// parameter except `offset` is carried over untouched.
+fn list_link_header(uri: &Uri, offset: usize, limit: usize, paging: &ListPaging) -> Option<String> {
+    let with_offset = |o: usize| {
+        let mut pairs: Vec<&str> = uri
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|p| !p.is_empty() && !p.starts_with("offset="))
+            .collect();
+        let offset_pair = format!("offset={o}");
+        pairs.push(&offset_pair);
+        format!("<{}?{}>", uri.path(), pairs.join("&"))
+    };
+    let mut links = Vec::new();
+    if let Some(next) = paging.next_offset {
+        links.push(format!("{}; rel=\"next\"", with_offset(next)));
+    }
+    if offset > 0 {
+        links.push(format!(
+            "{}; rel=\"prev\"",
+            with_offset(offset.saturating_sub(limit))
+        ));
+    }
+    (!links.is_empty()).then(|| links.join(", "))
+}
+
 async fn list_emails(
     State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
     Query(params): Query<ListEmailsParams>,
 ) -> Result<impl IntoResponse, Error> {
     let limit = params.limit.unwrap_or(DEFAULT_INBOX_LIMIT);
     let offset = params.offset.unwrap_or(0);
     let sort = params.sort.unwrap_or_default();
 
+    let virtual_mailbox = params
+        .mailbox_id
+        .as_deref()
+        .and_then(VirtualMailbox::parse)
+        .transpose()?;
+    // A virtual mailbox spans every mailbox, so the provider sees no
+    // mailbox scope at all.
+    let mailbox_id = match virtual_mailbox {
+        Some(_) => None,
+        None => params.mailbox_id.as_deref(),
+    };
+
     let mut query = params.search.as_deref().map(search::parse_query);
     // The sidebar Starred toggle takes precedence: when ?starred=true is
     // set we always restrict to flagged mail, regardless of any is_flagged
@@ -535,35 +1372,75 @@ async fn list_emails(
     if params.starred == Some(true) {
         query.get_or_insert_with(Default::default).is_flagged = Some(true);
     }
-    let query_ref = query.as_ref();
+    if let Some(v) = virtual_mailbox {
+        v.apply(query.get_or_insert_with(Default::default));
+    }
 
     // Resolved once and reused for both the fetch (cached or live) and the
     // split-filter block below — a second resolve_account_id call can't
     // disagree with this one since both apply the same default-account
     // fallback, but there's no reason to pay for the lock twice.
     let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
-
+    if let Some(q) = query.as_mut() {
+        resolve_query_mailboxes(&state, &account_id, q).await?;
+    }
+    let query_ref = query.as_ref();
+
+    // No provider can filter on spam headers, so `spamscore:` is matched
+    // here against the fetched rows like a split.
+    let spam_score = query.as_ref().and_then(|q| q.spam_score);
+    let priority_filter = query.as_ref().and_then(|q| q.priority);
+    let post_filtered = params.split_id.is_some()
+        || virtual_mailbox == Some(VirtualMailbox::Calendar)
+        || spam_score.is_some()
+        || priority_filter.is_some();
+
+    if let Some(start) = window_start(&params)? {
+        // A window's positions are the provider's; rows dropped after the
+        // fetch would leave holes the total doesn't account for.
+        if post_filtered {
+            return Err(Error::BadRequest(
+                "window mode can't be combined with split_id, virtual:calendar, \
+                 spamscore: or priority:"
+                    .into(),
+            ));
+        }
+        let body = list_window(
+            &state,
+            &account_id,
+            mailbox_id,
+            limit,
+            &start,
+            query_ref,
+            sort,
+        )
+        .await?;
+        return Ok((HeaderMap::new(), Json(body)));
+    }
+
     // Split-filtered requests need the scoped config before the fetch: an
     // id that matches neither "primary" nor a split in scope (a deleted
     // split, a stale client tab) can bail out here without spending a
     // provider round-trip on mail we'd throw away below.
-    let split_config = params.split_id.is_some().then(|| {
-        splits::load_splits(
-            &state.splits_config_path,
-            std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-        )
-        .scoped_to(Some(&account_id))
-    });
+    let split_matchers = params
+        .split_id
+        .is_some()
+        .then(|| state.splits.compiled().scoped_to(Some(&account_id)));
 
     if let Some(split_id) = params.split_id.as_deref()
         && split_id != "primary"
-        && let Some(config) = split_config.as_ref()
-        && !config.splits.iter().any(|s| s.id == split_id)
+        && let Some(matchers) = split_matchers.as_ref()
+        && !matchers.contains(split_id)
     {
-        return Ok((HeaderMap::new(), Json(Vec::<serde_json::Value>::new())));
+        let body = if params.paging == Some(true) {
+            serde_json::json!({ "emails": [], "paging": list_paging(offset, 0, 0, false, None) })
+        } else {
+            serde_json::json!([])
+        };
+        return Ok((HeaderMap::new(), Json(body)));
     }
 
-    let fetch_limit = if params.split_id.is_some() {
+    let fetch_limit = if post_filtered {
         limit * SPLIT_OVERFETCH_MULTIPLIER
     } else {
         limit
@@ -588,85 +1465,121 @@ async fn list_emails(
             limit,
             sort,
         };
-        state
-            .prefetch
-            .inbox_list_or_fetch(&account_id, key, || async {
-                let session_lock = resolve_session(&state, Some(&account_id)).await?;
-                let email_ids = {
-                    let session = session_lock.read().await;
-                    provider::query_emails(
-                        &session,
-                        params.mailbox_id.as_deref(),
-                        fetch_limit,
-                        offset,
-                        query_ref,
-                        sort,
+        perf::phase(
+            "provider",
+            state
+                .prefetch
+                .inbox_list_or_fetch(&account_id, key, || async {
+                    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+                    let email_ids = {
+                        let session = session_lock.read().await;
+                        provider::query_emails(
+                            &session,
+                            params.mailbox_id.as_deref(),
+                            fetch_limit,
+                            offset,
+                            query_ref,
+                            sort,
+                        )
+                        .await?
+                    };
+                    provider::get_emails_chunked(
+                        &session_lock,
+                        &email_ids,
+                        false,
+                        None,
+                        provider::GET_EMAILS_CHUNK,
                     )
-                    .await?
-                };
-                provider::get_emails_chunked(
-                    &session_lock,
-                    &email_ids,
-                    false,
-                    None,
-                    provider::GET_EMAILS_CHUNK,
-                )
-                .await
-            })
-            .await?
+                    .await
+                }),
+        )
+        .await?
     } else {
         let session_lock = resolve_session(&state, Some(&account_id)).await?;
-        let email_ids = {
-            let session = session_lock.read().await;
-            provider::query_emails(
-                &session,
-                params.mailbox_id.as_deref(),
-                fetch_limit,
-                offset,
-                query_ref,
-                sort,
+        let live = perf::phase("provider", async {
+            let email_ids = {
+                let session = session_lock.read().await;
+                provider::query_emails(&session, mailbox_id, fetch_limit, offset, query_ref, sort)
+                    .await?
+            };
+            provider::get_emails_chunked(
+                &session_lock,
+                &email_ids,
+                false,
+                None,
+                provider::GET_EMAILS_CHUNK,
             )
-            .await?
-        };
-        let live = provider::get_emails_chunked(
-            &session_lock,
-            &email_ids,
-            false,
-            None,
-            provider::GET_EMAILS_CHUNK,
-        )
+            .await
+        })
         .await?;
         (live, false)
     };
 
     // Apply split filtering, scoped to this account's splits so "primary"
     // means "not matching any of *this account's* splits". Reuses the
-    // config loaded above the fetch — no second load/scope pass.
-    if let (Some(split_id), Some(config)) = (params.split_id.as_deref(), split_config.as_ref()) {
-        emails = splits::filter_by_split(emails, split_id, config);
-        emails.truncate(limit);
-    }
+    // matchers scoped above the fetch — no second scope or compile pass.
+    // Scored over the whole fetched page, before filtering, so a sender's
+    // share of the page counts every row of theirs.
+    let scorer = priority_scorer(&state, &account_id, &emails).await;
+    let mut consumed = emails.len();
+    let mut truncated = false;
+    if post_filtered {
+        let filter_start = std::time::Instant::now();
+        let raw_ids: Vec<String> = emails.iter().map(|e| e.id.clone()).collect();
+        if let Some(v) = virtual_mailbox {
+            emails.retain(|e| v.keeps(e));
+        }
+        if let Some(filter) = spam_score {
+            emails.retain(|e| filter.matches(e.spam.as_ref()));
+        }
+        if let Some(level) = priority_filter {
+            emails.retain(|e| scorer.score(e).level == level);
+        }
+        if let (Some(split_id), Some(matchers)) =
+            (params.split_id.as_deref(), split_matchers.as_ref())
+        {
+            emails = matchers.filter(emails, split_id);
+        }
+        if emails.len() > limit {
+            emails.truncate(limit);
+            truncated = true;
+            // Matches past the cut must come back on the next page, so
+            // resume right after the last row kept, not after the window.
+            if let Some(pos) = emails
+                .last()
+                .and_then(|last| raw_ids.iter().position(|id| *id == last.id))
+            {
+                consumed = pos + 1;
+            }
+        }
+        perf::add_phase("filter", filter_start.elapsed());
+    }
+    let paging = list_paging(
+        offset,
+        fetch_limit,
+        consumed,
+        truncated,
+        emails.last().map(|e| e.id.as_str()),
+    );
 
     // Serialize emails for frontend
-    let response: Vec<serde_json::Value> = emails
-        .iter()
-        .map(|e| {
-            serde_json::json!({
-                "id": e.id,
-                "threadId": e.thread_id,
-                "subject": e.subject,
-                "from": e.from,
-                "to": e.to,
-                "cc": e.cc,
-                "preview": e.preview,
-                "receivedAt": e.received_at,
-                "isUnread": e.is_unread(),
-                "isFlagged": e.is_flagged(),
-                "hasAttachment": e.has_attachment,
-                "hasCalendar": e.has_calendar,
+    let names = contact_names(&state, &account_id);
+    let response = perf::phase_sync("serialize", || {
+        let rows: Vec<serde_json::Value> = emails
+            .iter()
+            .map(|e| {
+                let mut row = email_summary_json(e, &names);
+                row["priority"] = serde_json::json!(scorer.score(e));
+                row
             })
-        })
-        .collect();
+            .collect();
+        if params.paging == Some(true) {
+            serde_json::json!({ "emails": rows, "paging": paging })
+        } else {
+            serde_json::json!(rows)
+        }
+    });
+    let link = list_link_header(&uri, offset, limit, &paging);
 
     // A stale response is a disk-restored snapshot from the previous run,
     // served for instant first paint. The header tells the frontend to keep
@@ -679,9 +1592,537 @@ async fn list_emails(
             axum::http::HeaderValue::from_static("1"),
         );
     }
+    if let Some(link) = link
+        && let Ok(value) = axum::http::HeaderValue::from_str(&link)
+    {
+        headers.insert(axum::http::header::LINK, value);
+    }
     Ok((headers, Json(response)))
 }
 
+/// Window mode is on when `window` or `anchor` is given. `anchor` places
+/// the window `anchor_offset` rows from that email (negative: before it)
+/// and wins over `window`.
+fn window_start(params: &ListEmailsParams) -> Result<Option<WindowStart>, Error> {
+    match (&params.anchor, params.anchor_offset, params.window) {
+        (Some(id), offset, _) => Ok(Some(WindowStart::Anchor {
+            id: id.clone(),
+            offset: offset.unwrap_or(0),
+        })),
+        (None, Some(_), _) => Err(Error::BadRequest("anchor_offset needs an anchor".into())),
+        (None, None, start) => Ok(start.map(WindowStart::Position)),
+    }
+}
+
+/// `/api/emails` in window mode, for virtualized lists:
+/// `{total, window: {start, items}, ordering}`. Items are keyed by email
+/// id, which is stable across windows. `ordering` says what the positions
+/// mean: the sort property and direction, and the `queryState` they hold
+/// for. While two windows report the same `queryState` their positions
+/// line up; when it changes, mail has arrived or left and the client
+/// should re-anchor on a row it already shows. A null `queryState`
+/// (Outlook, Gmail) means the provider can't tell, and `total` is null
+/// there too.
+async fn list_window(
+    state: &AppState,
+    account_id: &str,
+    mailbox_id: Option<&str>,
+    limit: usize,
+    start: &WindowStart,
+    query: Option<&crate::types::ParsedQuery>,
+    sort: EmailSort,
+) -> Result<serde_json::Value, Error> {
+    let session_lock = resolve_session(state, Some(account_id)).await?;
+    let page = {
+        let session = session_lock.read().await;
+        provider::search_emails(&session, mailbox_id, limit, start, query, sort).await?
+    };
+    let emails = provider::get_emails_chunked(
+        &session_lock,
+        &page.ids,
+        false,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+    let scorer = priority_scorer(state, account_id, &emails).await;
+    let names = contact_names(state, account_id);
+    let items: Vec<serde_json::Value> = emails
+        .iter()
+        .map(|e| {
+            let mut row = email_summary_json(e, &names);
+            row["priority"] = serde_json::json!(scorer.score(e));
+            row
+        })
+        .collect();
+    Ok(serde_json::json!({
+        "total": page.total,
+        "window": { "start": page.position, "items": items },
+        "ordering": {
+            "property": "receivedAt",
+            "ascending": sort == EmailSort::DateAsc,
+            "queryState": page.query_state,
+            "canCalculateChanges": page.can_calculate_changes,
+        },
+    }))
+}
+
+/// Page size for `/api/search`, and the most a caller may ask for.
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+    /// Restrict to one mailbox; absent searches every mailbox.
+    mailbox_id: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    sort: Option<EmailSort>,
+    account: Option<String>,
+}
+
+/// Look up the mailboxes `in:` / `label:` name. Only queries that name one
+/// pay for the (usually cached) mailbox list.
+async fn resolve_query_mailboxes(
+    state: &Arc<AppState>,
+    account_id: &str,
+    query: &mut ParsedQuery,
+) -> Result<(), Error> {
+    if !query.has_mailbox_terms() {
+        return Ok(());
+    }
+    let mailboxes = state
+        .prefetch
+        .mailboxes_or_fetch(account_id, || async {
+            let session_lock = resolve_session(state, Some(account_id)).await?;
+            let session = session_lock.read().await;
+            provider::get_mailboxes(&session).await
+        })
+        .await?;
+    let aliases = crate::mailbox_roles::load_aliases(&state.mailbox_roles_path);
+    search::resolve_mailboxes(query, &mailboxes, &aliases).map_err(Error::BadRequest)
+}
+
+/// One page of search results across mailboxes, with the total and
+/// position so the UI can show "1–50 of 3,412". `total` is null where the
+/// provider can't count (Outlook, Gmail).
+async fn search_emails(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse, Error> {
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(Error::BadRequest(format!(
+            "limit must be between 1 and {MAX_SEARCH_LIMIT}"
+        )));
+    }
+    let mut query = search::parse_query(&params.q);
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    resolve_query_mailboxes(&state, &account_id, &mut query).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+    let page = {
+        let session = session_lock.read().await;
+        provider::search_emails(
+            &session,
+            params.mailbox_id.as_deref(),
+            limit,
+            &WindowStart::Position(params.offset.unwrap_or(0)),
+            Some(&query),
+            params.sort.unwrap_or_default(),
+        )
+        .await?
+    };
+    let emails = provider::get_emails_chunked(
+        &session_lock,
+        &page.ids,
+        false,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+    let names = contact_names(&state, &account_id);
+    let rows: Vec<serde_json::Value> = emails
+        .iter()
+        .map(|e| email_summary_json(e, &names))
+        .collect();
+    Ok(Json(serde_json::json!({
+        "emails": rows,
+        "position": page.position,
+        "total": page.total,
+        "limit": limit,
+        "canCalculateChanges": page.can_calculate_changes,
+        "queryState": page.query_state,
+    })))
+}
+
+/// Priority scorer for one page of `account_id`'s list: its own
+/// addresses, harvested correspondents and learned sender tallies.
+async fn priority_scorer(state: &AppState, account_id: &str, page: &[Email]) -> priority::Scorer {
+    let username = match resolve_session(state, Some(account_id)).await {
+        Ok(session_lock) => Some(session_lock.read().await.username().to_string()),
+        Err(_) => None,
+    };
+    let identities = state
+        .prefetch
+        .get_identities(account_id)
+        .await
+        .unwrap_or_default();
+    let book = contacts::load_index(&state.contacts_path)
+        .accounts
+        .remove(account_id)
+        .unwrap_or_default();
+    let own = username
+        .iter()
+        .map(String::as_str)
+        .chain(identities.iter().map(|i| i.email.as_str()));
+    let written_to = book
+        .correspondents
+        .iter()
+        .map(|c| (c.email.as_str(), c.count));
+    priority::Scorer::new(
+        own,
+        written_to,
+        priority::load_stats(&*state.store, account_id),
+        page,
+    )
+}
+
+/// Count `action` against `addresses` in `account`'s priority tallies. Best effort: a failed write only costs the lesson.
+async fn record_priority(
+    state: &AppState,
+    account: &str,
+    addresses: &[&str],
+    action: priority::Action,
+) {
+    let _guard = state.priority_lock.lock().await;
+    if let Err(e) = priority::record(&*state.store, account, addresses, action) {
+        tracing::warn!("[{account}] couldn't record priority history: {e}");
+    }
+}
+
+/// `account_id`'s address book names (`contacts::AddressBook::display_names`),
+/// empty when it has no synced book.
+fn contact_names(state: &AppState, account_id: &str) -> HashMap<String, String> {
+    contacts::load_index(&state.contacts_path)
+        .accounts
+        .get(account_id)
+        .map(|book| book.display_names())
+        .unwrap_or_default()
+}
+
+/// The list-row shape the frontend renders (`/api/emails`, split preview).
+/// `displayName` is the sender's address book name, falling back to the
+/// name in the From header; null when neither has one.
+fn email_summary_json(e: &Email, contact_names: &HashMap<String, String>) -> serde_json::Value {
+    let display_name = e.from.first().and_then(|from| {
+        contact_names
+            .get(&from.email.to_lowercase())
+            .cloned()
+            .or_else(|| from.name.clone().filter(|n| !n.trim().is_empty()))
+    });
+    serde_json::json!({
+        "id": e.id,
+        "threadId": e.thread_id,
+        "subject": e.subject,
+        "from": e.from,
+        "displayName": display_name,
+        "to": e.to,
+        "cc": e.cc,
+        "preview": e.preview,
+        "receivedAt": e.received_at,
+        "isUnread": e.is_unread(),
+        "isFlagged": e.is_flagged(),
+        "flagColor": e.flag_color(),
+        "keywords": e.custom_keywords(),
+        "hasAttachment": e.has_attachment,
+        "hasCalendar": e.has_calendar,
+        "spam": e.spam,
+    })
+}
+
+/// One message of `GET /api/threads/{id}`: the list-row shape plus the
+/// bodies and attachments the reader needs.
+fn thread_message_json(e: &Email, contact_names: &HashMap<String, String>) -> serde_json::Value {
+    let mut row = email_summary_json(e, contact_names);
+    row["textBody"] = serde_json::json!(e.text_body);
+    row["htmlBody"] = serde_json::json!(e.html_body);
+    row["inReplyTo"] = serde_json::json!(e.in_reply_to);
+    row["attachments"] = serde_json::json!(e.attachments);
+    row["isTruncated"] = serde_json::json!(e.is_truncated());
+    row["bodyParts"] = serde_json::json!(e.body_parts);
+    row
+}
+
+/// The whole conversation `thread_id` belongs to, oldest first. Read-only:
+/// unlike `GET /api/emails/{id}` nothing is marked read and invites aren't
+/// resolved; open a single message for that.
+async fn get_thread(
+    State(state): State<Arc<AppState>>,
+    Path(thread_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+    let emails = perf::phase("provider", async {
+        let session = session_lock.read().await;
+        provider::get_thread(&session, &thread_id).await
+    })
+    .await?;
+    let names = contact_names(&state, &account_id);
+    let unread_count = thread_unread_count(&emails);
+    let body = perf::phase_sync("serialize", || {
+        let messages: Vec<serde_json::Value> = emails
+            .iter()
+            .map(|e| thread_message_json(e, &names))
+            .collect();
+        serde_json::json!({
+            "threadId": thread_id,
+            "emails": messages,
+            "anyUnread": unread_count > 0,
+            "unreadCount": unread_count,
+        })
+    });
+    Ok(Json(body))
+}
+
+/// `GET /api/threads/{id}/pdf`: the whole conversation as a PDF, for
+/// archiving (see `thread_pdf`). Read-only, like [`get_thread`].
+async fn export_thread_pdf(
+    State(state): State<Arc<AppState>>,
+    Path(thread_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+    let emails = {
+        let session = session_lock.read().await;
+        provider::get_thread(&session, &thread_id).await?
+    };
+    if emails.is_empty() {
+        return Err(Error::NotFound("Thread not found".into()));
+    }
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    let pdf = thread_pdf::render(&emails, tz, chrono::Utc::now());
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", "application/pdf".to_string()),
+            (
+                "content-disposition",
+                downloads::content_disposition(&thread_pdf::filename(&emails)),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        pdf,
+    ))
+}
+
+/// How many of a conversation's messages are unread — what a collapsed
+/// thread row shows instead of any one member's state.
+fn thread_unread_count(emails: &[Email]) -> usize {
+    emails.iter().filter(|e| e.is_unread()).count()
+}
+
+/// `POST /api/threads/{id}/mark-read`: mark every message in the
+/// conversation read in one batch, including ones outside the current
+/// mailbox (a sent reply, an archived earlier message).
+async fn mark_thread_read(
+    State(state): State<Arc<AppState>>,
+    Path(thread_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let updated = {
+        let session = session_lock.read().await;
+        let ids = provider::thread_email_ids(&session, &thread_id).await?;
+        provider::mark_read_batch(&session, &ids).await?
+    };
+    if updated > 0 {
+        state.prefetch.invalidate(&id).await;
+    }
+    Ok(Json(serde_json::json!({ "updated": updated })))
+}
+
+/// `POST /api/threads/{id}/mute`: archive the conversation's Inbox
+/// messages and keep archiving new ones; see `mute`.
+async fn mute_thread(
+    State(state): State<Arc<AppState>>,
+    Path(thread_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    // Refuse at the cap before anything is archived.
+    mute::load(&*state.store, &id).check_room(&thread_id)?;
+    let protected = protected::load_protected(&state.protected_senders_path);
+    let (archived, subject) = {
+        let session = session_lock.read().await;
+        let inbox = inbox_mailbox_id(&session).await?;
+        archive_thread_inbox(&session, &protected, &inbox, &thread_id).await?
+    };
+    {
+        let _guard = state.muted_lock.lock().await;
+        let mut muted = mute::load(&*state.store, &id);
+        muted.mute(&thread_id, subject, chrono::Utc::now())?;
+        mute::save(&*state.store, &id, &muted)?;
+    }
+    if archived > 0 {
+        state.prefetch.invalidate(&id).await;
+    }
+    Ok(Json(
+        serde_json::json!({ "muted": true, "archived": archived }),
+    ))
+}
+
+/// `DELETE /api/threads/{id}/mute`: stop archiving the conversation's new
+/// mail. What was already archived stays archived.
+async fn unmute_thread(
+    State(state): State<Arc<AppState>>,
+    Path(thread_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let _guard = state.muted_lock.lock().await;
+    let mut muted = mute::load(&*state.store, &id);
+    if !muted.unmute(&thread_id) {
+        return Err(Error::NotFound(format!(
+            "Thread '{thread_id}' is not muted"
+        )));
+    }
+    mute::save(&*state.store, &id, &muted)?;
+    Ok(Json(serde_json::json!({ "muted": false })))
+}
+
+/// The account's muted threads, most recently muted first.
+async fn list_muted_threads(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let mut threads: Vec<_> = mute::load(&*state.store, &id)
+        .threads
+        .into_iter()
+        .map(|(thread_id, t)| {
+            serde_json::json!({
+                "threadId": thread_id,
+                "mutedAt": t.muted_at,
+                "subject": t.subject,
+            })
+        })
+        .collect();
+    threads.sort_by(|a, b| b["mutedAt"].as_str().cmp(&a["mutedAt"].as_str()));
+    Ok(Json(threads))
+}
+
+async fn inbox_mailbox_id(session: &provider::ProviderSession) -> Result<String, Error> {
+    provider::get_mailboxes(session)
+        .await?
+        .into_iter()
+        .find(|m| m.role.as_deref() == Some("inbox"))
+        .map(|m| m.id)
+        .ok_or_else(|| Error::NotFound("No inbox mailbox".into()))
+}
+
+/// Archive the messages of `thread_id` that sit in the Inbox (`inbox`),
+/// leaving sent and already filed ones alone. Returns how many moved and
+/// the conversation's subject.
+async fn archive_thread_inbox(
+    session: &provider::ProviderSession,
+    protected: &protected::ProtectedSendersConfig,
+    inbox: &str,
+    thread_id: &str,
+) -> Result<(usize, Option<String>), Error> {
+    let ids = provider::thread_email_ids(session, thread_id).await?;
+    let emails = provider::get_emails(session, &ids, false, None, false).await?;
+    let subject = emails.first().map(|e| e.subject.clone());
+    let in_inbox: Vec<String> = emails
+        .iter()
+        .filter(|e| e.mailbox_ids.get(inbox).copied().unwrap_or(false))
+        .map(|e| e.id.clone())
+        .collect();
+    let (archived, _) = archive_batch_unprotected(session, protected, &in_inbox).await?;
+    Ok((archived, subject))
+}
+
+/// One pass of `mute::spawn_archiver`: the newest Inbox mail of each
+/// connected account with mutes, archived where its thread is muted.
+/// Returns how many messages moved.
+pub(crate) async fn archive_muted(state: &AppState) -> usize {
+    let accounts: Vec<(String, SessionLock)> = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .iter()
+            .map(|(id, s)| (id.clone(), s.clone()))
+            .collect()
+    };
+    let mut total = 0;
+    for (account, session_lock) in accounts {
+        let muted = mute::load(&*state.store, &account);
+        if muted.threads.is_empty() {
+            continue;
+        }
+        let protected = protected::load_protected(&state.protected_senders_path);
+        let session = session_lock.read().await;
+        let archived = match archive_muted_inbox(&session, &protected, &muted).await {
+            Ok(n) => n,
+            Err(e) => {
+                tracing::warn!("Muted threads skipped for {account}: {e}");
+                0
+            }
+        };
+        drop(session);
+        if archived > 0 {
+            state.prefetch.invalidate(&account).await;
+        }
+        total += archived;
+    }
+    total
+}
+
+/// Archive the newest `mute::ARCHIVER_SCAN` Inbox messages that belong to
+/// a muted thread, protected senders excepted.
+async fn archive_muted_inbox(
+    session: &provider::ProviderSession,
+    protected: &protected::ProtectedSendersConfig,
+    muted: &mute::MutedThreads,
+) -> Result<usize, Error> {
+    let inbox = inbox_mailbox_id(session).await?;
+    let ids = provider::query_emails(
+        session,
+        Some(&inbox),
+        mute::ARCHIVER_SCAN,
+        0,
+        None,
+        EmailSort::default(),
+    )
+    .await?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let emails = provider::get_emails(session, &ids, false, None, false).await?;
+    let in_muted = mute::in_muted_threads(muted, &emails);
+    if in_muted.is_empty() {
+        return Ok(0);
+    }
+    let emails: Vec<_> = emails
+        .into_iter()
+        .filter(|e| in_muted.contains(&e.id))
+        .collect();
+    // Metadata is in hand already, so partition here rather than through
+    // archive_batch_unprotected, which would fetch it again.
+    let (kept, _) = protected::partition(protected, &emails);
+    if kept.is_empty() {
+        return Ok(0);
+    }
+    provider::archive_batch(session, &kept).await
+}
+
 async fn get_email(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
@@ -700,32 +2141,36 @@ async fn get_email(
     // fire-and-forget warm-ups must not queue full bodies ahead of a
     // genuinely user-blocking open (roborev 315).
     let priority = params.mark_read.unwrap_or(true);
-    let email = state
-        .prefetch
-        .body_or_fetch(&account_key, &email_id, || async {
+    let email = perf::phase(
+        "provider",
+        state.prefetch.body_or_fetch(&account_key, &email_id, || {
             // priority: when set, the user is staring at a spinner for exactly
             // this response — it must not queue behind a warm pass's fan-out.
-            let emails = provider::get_emails(
-                &session,
-                std::slice::from_ref(&email_id),
-                true,
-                None,
-                priority,
-            )
-            .await?;
-            emails
-                .into_iter()
-                .next()
-                .ok_or_else(|| Error::NotFound("Email not found".into()))
-        })
-        .await?;
+            fetch_full_email(&state, &account_key, &session, &email_id, priority)
+        }),
+    )
+    .await?;
     let email = &email;
 
-    // Auto mark-read (skippable via ?mark_read=false — see GetEmailParams)
-    if params.mark_read.unwrap_or(true) && email.is_unread() {
+    // Auto mark-read (skippable via ?mark_read=false — see GetEmailParams;
+    // never in read-only mode, which also keeps the calendar writes below
+    // from happening)
+    let read_only = state.accounts.read().await.read_only;
+    if params.mark_read.unwrap_or(true) && email.is_unread() && !read_only {
         let _ = provider::mark_read(&session, &email_id).await;
     }
 
+    // Newsletter read rates for unsubscribe suggestions: a prefetch notes
+    // the message as received, an open as opened.
+    if email
+        .list_unsubscribe
+        .as_deref()
+        .is_some_and(|h| !h.trim().is_empty())
+    {
+        let opened = params.mark_read.unwrap_or(true) || !email.is_unread();
+        note_newsletter(&state, &account_key, email, opened).await;
+    }
+
     // Check for calendar event
     let mut calendar_event = None;
     if email.has_calendar
@@ -747,6 +2192,14 @@ async fn get_email(
             }
         };
 
+        // Warn, don't block: a domain mismatch can be a legitimate sending
+        // service, so the reader decides.
+        event.organizer_mismatch =
+            calendar::organizer_mismatch(&event, email.from.first().map(|a| a.email.as_str()));
+        if let Some(warning) = &event.organizer_mismatch {
+            tracing::warn!("Suspicious calendar invite {}: {warning}", event.uid);
+        }
+
         // On an Update (rescheduled invite from the verified organizer) we must
         // NOT re-apply the stale stored PARTSTAT — the response is being reset.
         let mut skip_partstat_merge = false;
@@ -787,24 +2240,40 @@ async fn get_email(
             } else {
                 decision
             };
+            // Revised invite: summarize what moved relative to the stored
+            // copy. Skipped on a rejected spoof (don't dress up a forged
+            // "update" as a real one). Once the Update arm below overwrites
+            // the stored event, a later re-open diffs equal and shows none.
+            if event.sequence > 0
+                && decision != calendar::InviteAction::RejectSpoof
+                && let Some(stored) = &stored_event
+            {
+                let tz = timezone::primary_tz(&timezone::load_config(
+                    &state.timezone_config_path,
+                    timezone_env_override().as_deref(),
+                ));
+                event.changes = calendar::describe_changes(stored, &event, tz);
+            }
             match decision {
                 // First-time add or idempotent re-receipt: today's behavior —
                 // add if missing, never overwrite (only_if_new = true).
                 calendar::InviteAction::NoStored | calendar::InviteAction::Unchanged => {
                     let state_clone = state.clone();
-                    let ics_clone = ics_data.clone();
+                    let ics_clone = with_default_reminder(&state, &ics_data).await;
                     let uid = event.uid.clone();
                     let acct = account_key.clone();
-                    tokio::spawn(async move {
-                        if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
-                            let s = s_lock.read().await;
-                            if let Err(e) =
-                                provider::add_to_calendar(&s, &ics_clone, &uid, true).await
-                            {
-                                tracing::warn!("Calendar auto-add failed for {uid}: {e}");
+                    if !read_only {
+                        tokio::spawn(async move {
+                            if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
+                                let s = s_lock.read().await;
+                                if let Err(e) =
+                                    provider::add_to_calendar(&s, &ics_clone, &uid, true).await
+                                {
+                                    tracing::warn!("Calendar auto-add failed for {uid}: {e}");
+                                }
                             }
-                        }
-                    });
+                        });
+                    }
                 }
                 // Rescheduled invite (higher SEQUENCE, organizer verified):
                 // overwrite the stored event (only_if_new = false) and reset the
@@ -813,19 +2282,21 @@ async fn get_email(
                     event.is_update = true;
                     skip_partstat_merge = true;
                     let state_clone = state.clone();
-                    let ics_clone = ics_data.clone();
+                    let ics_clone = with_default_reminder(&state, &ics_data).await;
                     let uid = event.uid.clone();
                     let acct = account_key.clone();
-                    tokio::spawn(async move {
-                        if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
-                            let s = s_lock.read().await;
-                            if let Err(e) =
-                                provider::add_to_calendar(&s, &ics_clone, &uid, false).await
-                            {
-                                tracing::warn!("Calendar update failed for {uid}: {e}");
+                    if !read_only {
+                        tokio::spawn(async move {
+                            if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
+                                let s = s_lock.read().await;
+                                if let Err(e) =
+                                    provider::add_to_calendar(&s, &ics_clone, &uid, false).await
+                                {
+                                    tracing::warn!("Calendar update failed for {uid}: {e}");
+                                }
                             }
-                        }
-                    });
+                        });
+                    }
                 }
                 // Higher SEQUENCE but the sender is not the stored organizer.
                 // Touch nothing: no calendar write, no status reset. Render the
@@ -839,6 +2310,60 @@ async fn get_email(
                     );
                 }
             }
+        } else if event.method == "REPLY" {
+            // An attendee answering an invite the user organized. Show the
+            // reply's own PARTSTAT, not the stored copy's (which is what
+            // this reply is about to update).
+            skip_partstat_merge = true;
+            let sender_email = email.from.first().map(|a| a.email.as_str());
+            let identities = state
+                .prefetch
+                .get_identities(&account_key)
+                .await
+                .unwrap_or_default();
+            let my_addresses: Vec<&str> = std::iter::once(session.username())
+                .chain(identities.iter().map(|i| i.email.as_str()))
+                .collect();
+            match calendar::reply_decision(
+                stored_event.as_ref(),
+                &event,
+                sender_email,
+                &my_addresses,
+            ) {
+                calendar::ReplyAction::Apply {
+                    attendee_email,
+                    status,
+                } => {
+                    let (updated, entry) = reply_update(
+                        &account_key,
+                        &email_id,
+                        &event,
+                        stored_event.as_ref(),
+                        attendee_email,
+                        &status,
+                    );
+                    let state_clone = state.clone();
+                    let acct = account_key.clone();
+                    if !read_only {
+                        tokio::spawn(async move {
+                            if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
+                                let s = s_lock.read().await;
+                                write_calendar_reply(&state_clone, &s, &updated, entry).await;
+                            }
+                        });
+                    }
+                }
+                calendar::ReplyAction::RejectSpoof => {
+                    tracing::warn!(
+                        "Rejected calendar reply for {} (sender {:?} is not a matching attendee)",
+                        event.uid,
+                        sender_email,
+                    );
+                }
+                calendar::ReplyAction::NoStored
+                | calendar::ReplyAction::NotOrganizer
+                | calendar::ReplyAction::Unchanged => {}
+            }
         } else if event.method == "CANCEL" {
             // Anti-spoof gate (roborev 292): the CANCEL arm has no SEQUENCE to
             // compare, so without this check anyone who learns a UID could
@@ -854,14 +2379,16 @@ async fn get_email(
                     let state_clone = state.clone();
                     let uid = event.uid.clone();
                     let acct = account_key.clone();
-                    tokio::spawn(async move {
-                        if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
-                            let s = s_lock.read().await;
-                            if let Err(e) = provider::remove_from_calendar(&s, &uid).await {
-                                tracing::warn!("Calendar auto-remove failed for {uid}: {e}");
+                    if !read_only {
+                        tokio::spawn(async move {
+                            if let Ok(s_lock) = resolve_session(&state_clone, Some(&acct)).await {
+                                let s = s_lock.read().await;
+                                if let Err(e) = provider::remove_from_calendar(&s, &uid).await {
+                                    tracing::warn!("Calendar auto-remove failed for {uid}: {e}");
+                                }
                             }
-                        }
-                    });
+                        });
+                    }
                 }
                 calendar::CancelAction::NoStored => {
                     tracing::debug!("Event {} not in calendar yet, nothing to cancel", event.uid);
@@ -908,10 +2435,53 @@ async fn get_email(
             {
                 event.user_rsvp_status = Some(att.status.clone());
             }
+            // The calendar's answer when there's a stored copy to read it
+            // from, otherwise the one recorded when the RSVP was sent.
+            let log = rsvp_log::load(&*state.store, &account_key);
+            event.my_response = rsvp_log::my_response(
+                stored_event.as_ref().and(event.user_rsvp_status.as_deref()),
+                log.get(&event.uid, event.sequence),
+            );
+            if let Some(response) = &event.my_response {
+                event.user_rsvp_status = Some(response.status.clone());
+            }
         }
         calendar_event = Some(event);
     }
 
+    let tracking_numbers = packages::detect(email);
+    if !tracking_numbers.is_empty() {
+        let mut tracked = packages::load_tracked(&state.packages_path);
+        if tracked.record(&account_key, email, &tracking_numbers)
+            && let Err(e) = packages::save_tracked(&tracked, &state.packages_path)
+        {
+            tracing::warn!("Failed to record tracking numbers: {e}");
+        }
+    }
+
+    // Senders on the image allowlist get the proxied rendering up front;
+    // everyone else gets remote loads blocked, with the proxied copy on
+    // the side for "Load remote content".
+    let images_allowed = email.from.first().is_some_and(|a| {
+        image_proxy::allows(
+            &image_proxy::load_image_senders(&state.image_senders_path),
+            &a.email,
+        )
+    });
+    let safe_html = email.html_body.as_deref().map(|html| {
+        if images_allowed {
+            sanitize::render_proxied(html)
+        } else {
+            sanitize::render(html)
+        }
+    });
+    let proxied_html = match (&safe_html, email.html_body.as_deref()) {
+        (Some(safe), Some(html)) if safe.remote_blocked > 0 => {
+            Some(sanitize::render_proxied(html).html)
+        }
+        _ => None,
+    };
+
     Ok(Json(serde_json::json!({
         "id": email.id,
         "threadId": email.thread_id,
@@ -923,2538 +2493,8649 @@ async fn get_email(
         "receivedAt": email.received_at,
         "isUnread": email.is_unread(),
         "isFlagged": email.is_flagged(),
+        "flagColor": email.flag_color(),
+        "keywords": email.custom_keywords(),
         "hasAttachment": email.has_attachment,
         "hasCalendar": email.has_calendar,
         "textBody": email.text_body,
         "htmlBody": email.html_body,
+        // What the reading pane renders by default: `htmlBody` through
+        // `sanitize::render`, with remote loads blocked and counted.
+        "htmlBodySafe": safe_html.as_ref().map(|s| &s.html),
+        "remoteResourcesBlocked": safe_html.as_ref().map_or(0, |s| s.remote_blocked),
+        // The same body with remote images fetched via `/api/proxy-image`;
+        // only sent when something was blocked.
+        "htmlBodyProxied": proxied_html,
+        "remoteImagesAllowed": images_allowed,
         // Threading parent — lets a restored draft rehydrate its reply
         // context so subsequent saves/sends keep in_reply_to (kata wm57).
         "inReplyTo": email.in_reply_to,
         "calendarEvent": calendar_event,
+        "trackingNumbers": tracking_numbers,
         "attachments": email.attachments,
+        // Size-aware body fetching: parts over the JMAP fetch cap come back
+        // cut, flagged here so the client can offer /body/full.
+        "size": email.size,
+        "bodySize": email.body_size(),
+        "isTruncated": email.is_truncated(),
+        "bodyParts": email.body_parts,
+        "spam": email.spam,
     })))
 }
 
-fn is_safe_path_segment(s: &str) -> bool {
-    !s.is_empty()
-        && !s.contains('/')
-        && !s.contains('\\')
-        && !s.contains('\0')
-        && s != "."
-        && s != ".."
-}
-
-fn sanitize_filename_for_header(name: &str) -> String {
-    name.chars()
-        .filter(|&c| c != '"' && c != '\\' && c != '\r' && c != '\n' && c != '\0')
-        .collect()
+/// Pick the body part `/body/full` should serve. An explicit `part_id` must
+/// exist; otherwise the first truncated part wins, HTML over text (the
+/// client renders HTML when present). None means nothing was truncated.
+fn select_full_body_part<'a>(
+    email: &'a Email,
+    part_id: Option<&str>,
+) -> Result<Option<&'a BodyPart>, Error> {
+    if let Some(part_id) = part_id {
+        return email
+            .body_parts
+            .iter()
+            .find(|p| p.part_id == part_id)
+            .map(Some)
+            .ok_or_else(|| Error::NotFound("Body part not found".into()));
+    }
+    let truncated = |kind: &str| {
+        email
+            .body_parts
+            .iter()
+            .find(|p| p.is_truncated && p.kind == kind)
+    };
+    Ok(truncated("html").or_else(|| truncated("text")))
 }
 
-async fn download_attachment(
+/// Serve the untruncated body of an email. Truncated parts are streamed
+/// from the provider without buffering; anything else is already whole in
+/// the fetched email and is returned as-is. Sandboxed via CSP so HTML
+/// fetched directly from our origin can't run script.
+async fn get_full_body(
     State(state): State<Arc<AppState>>,
-    Path((_email_id, blob_id, filename)): Path<(String, String, String)>,
-    Query(params): Query<AccountParam>,
-) -> Result<impl IntoResponse, Error> {
-    if !is_safe_path_segment(&blob_id) || !is_safe_path_segment(&filename) {
-        return Err(Error::BadRequest("Invalid blob_id or filename".into()));
-    }
-
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    Path(email_id): Path<String>,
+    Query(params): Query<FullBodyParams>,
+) -> Result<axum::response::Response, Error> {
+    let account_key = match params.account.clone() {
+        Some(a) => a,
+        None => state.accounts.read().await.default_account.clone(),
+    };
+    let session_lock = resolve_session(&state, Some(&account_key)).await?;
     let session = session_lock.read().await;
 
-    let (content_type, bytes) = provider::download_blob(&session, &blob_id, &filename).await?;
+    let email = state
+        .prefetch
+        .body_or_fetch(&account_key, &email_id, || {
+            fetch_full_email(&state, &account_key, &session, &email_id, true)
+        })
+        .await?;
+
+    let part = select_full_body_part(&email, params.part.as_deref())?;
+    let (content_type, body) = match part {
+        Some(part) if part.is_truncated => {
+            let charset = part.charset.as_deref().unwrap_or("utf-8");
+            let content_type = format!("{}; charset={charset}", part.mime_type);
+            (
+                content_type,
+                provider::stream_body_part(&session, part).await?,
+            )
+        }
+        _ => {
+            let want_html = part.is_none_or(|p| p.kind == "html");
+            let (mime, body) = match (&email.html_body, &email.text_body) {
+                (Some(html), _) if want_html => ("text/html", html.clone()),
+                (_, Some(text)) => ("text/plain", text.clone()),
+                (Some(html), None) => ("text/html", html.clone()),
+                (None, None) => return Err(Error::NotFound("Email has no body".into())),
+            };
+            (
+                format!("{mime}; charset=utf-8"),
+                axum::body::Body::from(body),
+            )
+        }
+    };
 
-    let safe_filename = sanitize_filename_for_header(&filename);
-    // X-Content-Type-Options: nosniff prevents browsers from sniffing past the
-    // declared Content-Type. Combined with Content-Disposition: attachment,
-    // this neutralizes the sender-controlled-filename-→-mime-type attack
-    // surface (a sender mailing `pwned.html` doesn't get HTML rendered from
-    // our origin if the user clicks "open" rather than "save").
     Ok((
         StatusCode::OK,
         [
             ("content-type", content_type),
-            (
-                "content-disposition",
-                format!("attachment; filename=\"{}\"", safe_filename),
-            ),
             ("x-content-type-options", "nosniff".to_string()),
+            ("content-security-policy", "sandbox".to_string()),
         ],
-        bytes,
-    ))
+        body,
+    )
+        .into_response())
 }
 
-async fn archive_email(
+/// All header fields of one email, in message order, plus the Received
+/// chain as a `deliveryPath` (hops, per-hop and total delay), the
+/// receiving server's SPF/DKIM/DMARC/ARC verdicts as `authentication` and
+/// the parsed `listId`, for the "view headers" panel when debugging
+/// delivery. Always fetched live; headers aren't part of the body cache.
+async fn get_headers(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
     let session = session_lock.read().await;
-    let success = provider::archive(&session, &email_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+    let headers = provider::get_headers(&session, &email_id).await?;
+    let delivery_path = crate::delivery::analyze(&headers);
+    Ok(Json(serde_json::json!({
+        "deliveryPath": delivery_path,
+        "authentication": crate::authentication::analyze(&headers),
+        "listId": crate::authentication::list_id(&headers),
+        "headers": headers,
+    })))
 }
 
-async fn trash_email(
-    State(state): State<Arc<AppState>>,
-    Path(email_id): Path<String>,
-    Query(params): Query<AccountParam>,
-) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
-    let session = session_lock.read().await;
-    let success = provider::trash(&session, &email_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+#[derive(Deserialize)]
+struct JmapPassthroughRequest {
+    #[serde(rename = "methodCalls")]
+    method_calls: Vec<serde_json::Value>,
 }
 
-async fn mark_read(
+/// Raw JMAP for power users and the mobile client: `methodCalls` go to
+/// the account's server through its own session and the response comes
+/// back untouched. Reads of mail objects and writes to emails and
+/// mailboxes only (`jmap::prepare_passthrough`), minus moving or
+/// destroying existing mail; a write drops the account's cached lists,
+/// and a `Mailbox/set` refreshes the mailboxes.
+async fn jmap_passthrough(
     State(state): State<Arc<AppState>>,
-    Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
+    Json(req): Json<JmapPassthroughRequest>,
 ) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
-    let session = session_lock.read().await;
-    let success = provider::mark_read(&session, &email_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account)).await?;
+    let methods: Vec<String> = req
+        .method_calls
+        .iter()
+        .filter_map(|c| c[0].as_str().map(str::to_string))
+        .collect();
+    if state.accounts.read().await.read_only
+        && methods.iter().any(|m| crate::jmap::passthrough_writes(m))
+    {
+        return Err(read_only::refused());
+    }
+    let response = {
+        let session = session_lock.read().await;
+        provider::jmap_passthrough(&session, req.method_calls).await?
+    };
+    if methods.iter().any(|m| m == "Mailbox/set") {
+        let mut session = session_lock.write().await;
+        mailboxes_changed(&state, &account, &mut session).await;
+    } else if methods.iter().any(|m| crate::jmap::passthrough_writes(m)) {
+        state.prefetch.invalidate(&account).await;
+    }
+    Ok(Json(response))
 }
 
-async fn mark_unread(
+/// The message source as stored by the provider, downloaded as
+/// `<email id>.eml` for checking headers and DKIM by hand or importing
+/// elsewhere. Never cached, and served as an attachment with `nosniff`
+/// so a browser doesn't render any of it from our origin.
+async fn get_raw_message(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
     let session = session_lock.read().await;
-    let success = provider::mark_unread(&session, &email_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+    let bytes = provider::get_raw_message(&session, &email_id).await?;
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", "message/rfc822".to_string()),
+            (
+                "content-disposition",
+                downloads::content_disposition(&format!("{email_id}.eml")),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        bytes,
+    ))
 }
 
-async fn toggle_flag(
+/// What compose needs to reply to one email: the From identity, picked by
+/// `identities::select_reply_identity` from the message's recipients and
+/// delivery headers, falling back to the `identities.json` mailbox and
+/// account defaults. `reason` says which rule fired. The header fetch is
+/// best-effort — without it only To/Cc and the configured defaults apply.
+async fn compose_context(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
     let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    // Identities before taking the read lock below: a cache miss fetches
+    // under the session write lock.
+    let identities = state
+        .prefetch
+        .identities_or_fetch(&id, || async {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            let mut session = session_lock.write().await;
+            provider::get_identities(&mut session).await
+        })
+        .await?;
+    let rules = crate::identities::load_rules(&state.identities_config_path)
+        .accounts
+        .remove(&id);
+
     let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
-    let success = provider::toggle_flag(&session, &email_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+    let mut email =
+        provider::get_emails(&session, std::slice::from_ref(&email_id), false, None, true)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotFound("Email not found".into()))?;
+    let headers = provider::get_headers(&session, &email_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("[{id}] compose-context: headers unavailable for {email_id}: {e}");
+            Vec::new()
+        });
+    let mailboxes = match &rules {
+        Some(r) if r.needs_mailboxes() => state
+            .prefetch
+            .mailboxes_or_fetch(&id, || provider::get_mailboxes(&session))
+            .await
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    let selected = crate::identities::select_reply_identity(
+        &identities,
+        &email,
+        &headers,
+        &mailboxes,
+        rules.as_ref(),
+    );
+    // The list fetch leaves List-Unsubscribe unset; the headers have it.
+    if email.list_unsubscribe.is_none() {
+        email.list_unsubscribe = headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("list-unsubscribe"))
+            .map(|h| h.value.clone());
+    }
+    let own: HashSet<String> = std::iter::once(session.username())
+        .chain(identities.iter().map(|i| i.email.as_str()))
+        .map(str::to_lowercase)
+        .collect();
+    Ok(Json(serde_json::json!({
+        "from": selected.map(|(identity, _)| identity),
+        "reason": selected.map(|(_, reason)| reason),
+        "bccWarning": compose::bcc_warning(&email, &own),
+    })))
 }
 
-async fn move_email(
+#[derive(Deserialize)]
+struct ReplyParams {
+    /// `reply` (default), `reply_all` or `forward`.
+    mode: Option<String>,
+    account: Option<String>,
+}
+
+/// Recipients, subject, quoted body and threading headers for replying to
+/// or forwarding one email; see `compose`. Own addresses (the account and
+/// its identities) are left out of reply-all.
+async fn reply_draft(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
-    Query(params): Query<AccountParam>,
-    Json(body): Json<MoveBody>,
+    Query(params): Query<ReplyParams>,
 ) -> Result<impl IntoResponse, Error> {
+    let mode = compose::Mode::parse(params.mode.as_deref().unwrap_or("reply"))?;
     let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let identities = state
+        .prefetch
+        .identities_or_fetch(&id, || async {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            let mut session = session_lock.write().await;
+            provider::get_identities(&mut session).await
+        })
+        .await
+        .unwrap_or_default();
+
     let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
-    let success = provider::move_to_mailbox(&session, &email_id, &body.mailbox_id).await?;
-    drop(session);
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({"success": success})))
+    let email = state
+        .prefetch
+        .body_or_fetch(&id, &email_id, || {
+            fetch_full_email(&state, &id, &session, &email_id, true)
+        })
+        .await?;
+    let own: Vec<&str> = std::iter::once(session.username())
+        .chain(identities.iter().map(|i| i.email.as_str()))
+        .collect();
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    Ok(Json(compose::build(&email, mode, &own, tz)))
 }
 
-// Defense in depth for outbound HTML: scrubs scripts, event handlers,
-// dangerous URL schemes (javascript:/vbscript:/non-image data:), and other
-// well-known XSS vectors before the message hits the wire. The iframe sandbox
-// protects *our* viewer, but reply/forward bodies carry the original sender's
-// HTML out to recipients whose clients may render it unsafely. Ammonia's
-// defaults are a vetted allowlist sanitizer; this prevents us from being a
-// laundering vector for an attacker's payload across the address book.
-fn sanitize_outgoing_html(html: &str) -> String {
-    ammonia::clean(html)
+/// `ics` with the configured `default-reminder` VALARM added when the
+/// organizer sent none (see `calendar::inject_default_alarm`). Unset or
+/// unparseable settings leave the data untouched; a bad value is logged
+/// rather than failing the calendar write it decorates.
+async fn with_default_reminder(state: &AppState, ics: &str) -> String {
+    let setting = state.accounts.read().await.default_reminder.clone();
+    let Some(setting) = setting else {
+        return ics.to_string();
+    };
+    match calendar::reminder_trigger(&setting) {
+        Some(trigger) => calendar::inject_default_alarm(ics, &trigger),
+        None => {
+            tracing::warn!("Ignoring invalid default-reminder '{setting}' (expected e.g. 10m, 1h)");
+            ics.to_string()
+        }
+    }
 }
 
-async fn send_email_handler(
-    State(state): State<Arc<AppState>>,
-    Query(params): Query<AccountParam>,
-    Json(body): Json<SendEmailBody>,
-) -> Result<impl IntoResponse, Error> {
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
-    let mut session = session_lock.write().await;
-    let from_addr = body
-        .from_address
-        .as_deref()
-        .unwrap_or(session.username())
-        .to_string();
+/// Cap for `AppState::calendar_reply_log`.
+pub(crate) const REPLY_LOG_CAP: usize = 200;
 
-    let submission = EmailSubmission {
-        to: body.to,
-        cc: body.cc,
-        subject: body.subject,
-        text_body: body.body,
-        bcc: if body.bcc.is_empty() {
-            None
-        } else {
-            Some(body.bcc)
-        },
-        html_body: body.html_body.map(|h| sanitize_outgoing_html(&h)),
-        in_reply_to: body.in_reply_to,
-        references: None,
-        attachments: body.attachments,
-        calendar_ics: None,
-    };
+/// Log an applied reply and save the log (see `calendar_replies`).
+fn record_applied_reply(state: &AppState, entry: AppliedReply) {
+    let mut log = state
+        .calendar_reply_log
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if log.len() >= REPLY_LOG_CAP {
+        log.pop_front();
+    }
+    log.push_back(entry);
+    if let Err(e) = calendar_replies::save(&*state.store, &log) {
+        tracing::warn!("Failed to save the calendar reply log: {e}");
+    }
+}
 
-    let result = provider::send_email(&mut session, &submission, &from_addr, None).await?;
+/// What applying `reply` (from email `email_id`) does: the stored event's
+/// ICS with the attendee's new PARTSTAT, and the log entry for it.
+fn reply_update(
+    account: &str,
+    email_id: &str,
+    reply: &CalendarEvent,
+    stored: Option<&CalendarEvent>,
+    attendee_email: String,
+    status: &RsvpStatus,
+) -> (String, AppliedReply) {
+    // `stored` is Some whenever reply_decision says Apply.
+    let stored_ics = stored.map(|e| e.raw_ics.as_str()).unwrap_or_default();
+    let updated = calendar::update_partstat(stored_ics, &attendee_email, status);
+    let entry = AppliedReply {
+        account: account.to_string(),
+        email_id: email_id.to_string(),
+        uid: reply.uid.clone(),
+        summary: reply.summary.clone(),
+        attendee: attendee_email,
+        status: status.as_ics_str().to_string(),
+        applied_at: chrono::Utc::now(),
+    };
+    (updated, entry)
+}
 
-    match result {
-        Some(id) => Ok(Json(serde_json::json!({"success": true, "emailId": id}))),
-        None => Err(Error::Internal("Failed to send email".into())),
+/// Write a `reply_update` to the calendar and log it. Returns whether the
+/// calendar changed.
+async fn write_calendar_reply(
+    state: &AppState,
+    session: &provider::ProviderSession,
+    updated: &str,
+    entry: AppliedReply,
+) -> bool {
+    match provider::apply_calendar_reply(session, updated, &entry.uid).await {
+        Ok(true) => {
+            tracing::info!(
+                "Applied {} reply from {} to {}",
+                entry.status,
+                entry.attendee,
+                entry.uid
+            );
+            record_applied_reply(state, entry);
+            true
+        }
+        Ok(false) => false,
+        Err(e) => {
+            tracing::warn!("Calendar reply update failed for {}: {e}", entry.uid);
+            false
+        }
     }
 }
 
-// --- Persistent drafts (kata wm57) -----------------------------------------
+/// One pass of `calendar_replies::spawn_watcher` over `account`: apply the
+/// attendee replies among the newest Inbox messages not in `seen`, which
+/// becomes the current window. Returns how many were applied.
+pub(crate) async fn apply_inbox_replies(
+    state: &AppState,
+    account: &str,
+    seen: &mut HashSet<String>,
+) -> Result<usize, Error> {
+    let session_lock = resolve_session(state, Some(account)).await?;
+    let session = session_lock.read().await;
+    let inbox = inbox_mailbox_id(&session).await?;
+    let ids = provider::query_emails(
+        &session,
+        Some(&inbox),
+        calendar_replies::WATCH_SCAN,
+        0,
+        None,
+        EmailSort::default(),
+    )
+    .await?;
+    let unseen: Vec<String> = ids
+        .iter()
+        .filter(|id| !seen.contains(*id))
+        .cloned()
+        .collect();
+    *seen = ids.into_iter().collect();
+    if unseen.is_empty() {
+        return Ok(0);
+    }
+    let emails = provider::get_emails(&session, &unseen, false, None, false).await?;
+    let identities = state
+        .prefetch
+        .get_identities(account)
+        .await
+        .unwrap_or_default();
+    let my_addresses: Vec<&str> = std::iter::once(session.username())
+        .chain(identities.iter().map(|i| i.email.as_str()))
+        .collect();
+    let mut applied = 0;
+    for email in emails.iter().filter(|e| e.has_calendar) {
+        let reply = match provider::get_calendar_data(&session, &email.id).await {
+            Ok(ics) => ics.as_deref().and_then(calendar::parse_ics),
+            Err(e) => {
+                tracing::warn!("Calendar reply pass: couldn't read {}: {e}", email.id);
+                continue;
+            }
+        };
+        let Some(reply) = reply.filter(|r| r.method.eq_ignore_ascii_case("REPLY")) else {
+            continue;
+        };
+        let stored = match provider::get_calendar_event(&session, &reply.uid).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                tracing::warn!("Calendar reply pass: couldn't look up {}: {e}", reply.uid);
+                continue;
+            }
+        };
+        let sender = email.from.first().map(|a| a.email.as_str());
+        match calendar::reply_decision(stored.as_ref(), &reply, sender, &my_addresses) {
+            calendar::ReplyAction::Apply {
+                attendee_email,
+                status,
+            } => {
+                let (updated, entry) = reply_update(
+                    account,
+                    &email.id,
+                    &reply,
+                    stored.as_ref(),
+                    attendee_email,
+                    &status,
+                );
+                applied +=
+                    usize::from(write_calendar_reply(state, &session, &updated, entry).await);
+            }
+            calendar::ReplyAction::RejectSpoof => tracing::warn!(
+                "Rejected calendar reply for {} (sender {sender:?} is not a matching attendee)",
+                reply.uid,
+            ),
+            calendar::ReplyAction::NoStored
+            | calendar::ReplyAction::NotOrganizer
+            | calendar::ReplyAction::Unchanged => {}
+        }
+    }
+    Ok(applied)
+}
 
-/// Build a plain-text `EmailSubmission` from a draft body. v1 persists no
-/// html_body, attachments, or calendar — those live only in the live compose
-/// session, never in the stored draft.
-fn draft_submission(body: DraftBody) -> EmailSubmission {
-    EmailSubmission {
-        to: body.to,
-        cc: body.cc,
-        subject: body.subject,
-        text_body: body.body,
-        bcc: None,
-        html_body: None,
-        in_reply_to: body.in_reply_to,
-        references: None,
-        attachments: Vec::new(),
-        calendar_ics: None,
+/// `GET /api/calendar/replies` — attendee replies applied to the user's own
+/// events (the last `REPLY_LOG_CAP`, kept across restarts), newest first.
+/// `?account=` narrows to one account.
+async fn list_applied_replies(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    if let Some(ref acct) = params.account {
+        let reg = state.accounts.read().await;
+        ensure_known_account(&reg, acct)?;
     }
+    let log = state
+        .calendar_reply_log
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let entries: Vec<&AppliedReply> = log
+        .iter()
+        .rev()
+        .filter(|e| params.account.as_deref().is_none_or(|a| a == e.account))
+        .collect();
+    Ok(Json(serde_json::json!(entries)))
 }
 
-async fn create_draft_handler(
+fn is_safe_path_segment(s: &str) -> bool {
+    !s.is_empty()
+        && !s.contains('/')
+        && !s.contains('\\')
+        && !s.contains('\0')
+        && s != "."
+        && s != ".."
+}
+
+fn sanitize_filename_for_header(name: &str) -> String {
+    name.chars()
+        .filter(|&c| c != '"' && c != '\\' && c != '\r' && c != '\n' && c != '\0')
+        .collect()
+}
+
+/// [`provider::download_blob`] through the on-disk `blob_cache`.
+async fn download_blob_cached(
+    state: &AppState,
+    account: &str,
+    session: &provider::ProviderSession,
+    blob_id: &str,
+    filename: &str,
+) -> Result<(String, Vec<u8>), Error> {
+    if let Some(hit) = state.blob_cache.get(account, blob_id) {
+        return Ok(hit);
+    }
+    let (content_type, bytes) = provider::download_blob(session, blob_id, filename).await?;
+    state
+        .blob_cache
+        .put(account, blob_id, &content_type, &bytes);
+    Ok((content_type, bytes))
+}
+
+async fn download_attachment(
     State(state): State<Arc<AppState>>,
+    Path((email_id, blob_id, filename)): Path<(String, String, String)>,
     Query(params): Query<AccountParam>,
-    Json(body): Json<DraftBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
+    if !is_safe_path_segment(&blob_id) || !is_safe_path_segment(&filename) {
+        return Err(Error::BadRequest("Invalid blob_id or filename".into()));
+    }
+
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account)).await?;
     let session = session_lock.read().await;
-    let from_addr = body
-        .from_address
-        .clone()
-        .unwrap_or_else(|| session.username().to_string());
-    let submission = draft_submission(body);
-    let draft_id = provider::create_draft(&session, &submission, &from_addr).await?;
+
+    let (declared, bytes) =
+        download_blob_cached(&state, &account, &session, &blob_id, &filename).await?;
     drop(session);
-    // A newly created draft belongs in a warmed Drafts-list cache too — without
-    // this a prefetched list can hide it for up to 5 minutes (review follow-up).
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({ "id": draft_id })))
+
+    // Providers hand back whatever the sender declared, often
+    // `application/octet-stream` for PDFs and scans. Serve the sniffed type
+    // instead (allowlisted, so never something active) and remember it on
+    // the cached body for the attachment list.
+    let (content_type, mismatch) = provider_utils::corrected_content_type(&declared, &bytes);
+    if let Some(sniffed) = provider_utils::sniff_mime_type(&bytes) {
+        if mismatch {
+            tracing::debug!(%blob_id, %declared, sniffed, "attachment type corrected");
+        }
+        state
+            .prefetch
+            .note_sniffed_attachment(&account, &email_id, &blob_id, sniffed, mismatch);
+    }
+
+    // X-Content-Type-Options: nosniff prevents browsers from sniffing past the
+    // declared Content-Type. Combined with Content-Disposition: attachment,
+    // this neutralizes the sender-controlled-filename-→-mime-type attack
+    // surface (a sender mailing `pwned.html` doesn't get HTML rendered from
+    // our origin if the user clicks "open" rather than "save").
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", content_type),
+            (
+                "content-disposition",
+                downloads::content_disposition(&filename),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        bytes,
+    ))
 }
 
-async fn update_draft_handler(
+/// Most attachment bytes `attachments.zip` will bundle.
+const MAX_ZIP_BYTES: i64 = 100 * 1024 * 1024;
+
+/// Every attachment of an email as one zip, names deduped so repeats
+/// don't overwrite each other on unpack (see `downloads`).
+async fn download_attachments_zip(
     State(state): State<Arc<AppState>>,
-    Path(draft_id): Path<String>,
+    Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
-    Json(body): Json<DraftBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account)).await?;
     let session = session_lock.read().await;
-    let from_addr = body
-        .from_address
-        .clone()
-        .unwrap_or_else(|| session.username().to_string());
-    let submission = draft_submission(body);
-    // Editing a draft is destroy+recreate (JMAP bodies aren't patchable), so
-    // this returns a NEW id the client must adopt.
-    let new_id = provider::update_draft(&session, &draft_id, &submission, &from_addr).await?;
+
+    let email = provider::get_emails(&session, std::slice::from_ref(&email_id), true, None, true)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NotFound("Email not found".into()))?;
+    if email.attachments.is_empty() {
+        return Err(Error::NotFound("Email has no attachments".into()));
+    }
+    let total: i64 = email.attachments.iter().map(|a| a.size).sum();
+    if total > MAX_ZIP_BYTES {
+        return Err(Error::Unprocessable(format!(
+            "Attachments total {total} bytes, over the {MAX_ZIP_BYTES}-byte zip limit; download them one at a time"
+        )));
+    }
+    let mut files = Vec::with_capacity(email.attachments.len());
+    for att in &email.attachments {
+        let (_, bytes) =
+            download_blob_cached(&state, &account, &session, &att.blob_id, &att.name).await?;
+        files.push((att.name.clone(), bytes));
+    }
     drop(session);
-    // The destroy+recreate rotates the draft's id — a warmed Drafts list must
-    // be invalidated or it keeps serving the now-destroyed old id (review
-    // follow-up; same gap as create/delete below).
-    state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({ "id": new_id })))
+
+    let zip = downloads::zip_files(&files)?;
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", "application/zip".to_string()),
+            (
+                "content-disposition",
+                downloads::content_disposition("attachments.zip"),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        zip,
+    ))
 }
 
-async fn delete_draft_handler(
+/// `{success, mailbox}` for archive/trash. `mailbox` names the folder the
+/// message went to (possibly one whose role was inferred from its name),
+/// or is null when the provider doesn't file into a specific mailbox.
+fn moved_response(success: bool, mailbox: Option<Mailbox>) -> serde_json::Value {
+    serde_json::json!({
+        "success": success,
+        "mailbox": mailbox.map(|mb| serde_json::json!({ "id": mb.id, "name": mb.name })),
+    })
+}
+
+async fn archive_email(
     State(state): State<Arc<AppState>>,
-    Path(draft_id): Path<String>,
+    Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
     let id = resolve_account_id(&state, params.account.as_deref()).await?;
     let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
-    let success = provider::destroy_draft(&session, &draft_id).await?;
+    // The sender, for the focused-inbox tallies: from the cache when the
+    // message was opened, else a header-only fetch.
+    let sender = match state.email_cache.get(&id, cache::Kind::Full, &email_id) {
+        Some(email) => email.from.first().map(|a| a.email.clone()),
+        None => provider::get_emails(
+            &session,
+            std::slice::from_ref(&email_id),
+            false,
+            None,
+            false,
+        )
+        .await
+        .ok()
+        .and_then(|emails| emails.into_iter().next())
+        .and_then(|email| email.from.into_iter().next())
+        .map(|a| a.email),
+    };
+    let success = provider::archive(&session, &email_id).await?;
+    let mailbox = provider::role_mailbox(&session, "archive");
     drop(session);
-    // Without this a warmed Drafts list keeps serving the destroyed id for up
-    // to 5 minutes (review follow-up), same as every other mutation route.
     state.prefetch.invalidate(&id).await;
-    Ok(Json(serde_json::json!({ "success": success })))
+    if success && let Some(sender) = sender.as_deref() {
+        record_priority(&state, &id, &[sender], priority::Action::Archived).await;
+    }
+    Ok(Json(moved_response(success, mailbox)))
 }
 
-const MAX_UPLOAD_SIZE: usize = 25 * 1024 * 1024; // 25 MB
-
-async fn upload_blob(
+async fn trash_email(
     State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
-    headers: HeaderMap,
-    body: Bytes,
 ) -> Result<impl IntoResponse, Error> {
-    if body.len() > MAX_UPLOAD_SIZE {
-        return Err(Error::BadRequest(format!(
-            "File too large ({} bytes, max {})",
-            body.len(),
-            MAX_UPLOAD_SIZE
-        )));
-    }
-
-    let content_type = headers
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("application/octet-stream");
-
-    let raw_filename = headers
-        .get("x-filename")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("attachment");
-    let filename = sanitize_filename_for_header(raw_filename);
-
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
-
-    let (blob_id, size) = provider::upload_blob(&session, content_type, &body).await?;
-
-    Ok(Json(serde_json::json!({
-        "blob_id": blob_id,
-        "name": filename,
-        "mime_type": content_type,
-        "size": size,
-    })))
-}
-
-fn determine_attendee_email(email: &Email, event: &CalendarEvent, fallback: &str) -> String {
-    for addr in email.to.iter().chain(email.cc.iter()) {
-        if event
-            .attendees
-            .iter()
-            .any(|a| a.email.eq_ignore_ascii_case(&addr.email))
+    // Where the message is now, so /api/recently-deleted can put it back.
+    let record = match provider::trash_mailbox_id(&session) {
+        Some(trash_id) => match provider::get_emails(
+            &session,
+            std::slice::from_ref(&email_id),
+            false,
+            None,
+            false,
+        )
+        .await
         {
-            return addr.email.clone();
+            Ok(emails) => emails.first().and_then(|email| {
+                recently_deleted::TrashedRecord::before_trash(
+                    &id,
+                    email,
+                    Some(&trash_id),
+                    chrono::Utc::now(),
+                )
+            }),
+            Err(e) => {
+                tracing::warn!("Not journaling trash of {email_id}: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+    let success = provider::trash(&session, &email_id).await?;
+    let mailbox = provider::role_mailbox(&session, "trash");
+    drop(session);
+    if success && let Some(record) = record {
+        let _guard = state.recently_deleted_lock.lock().await;
+        let mut journal = recently_deleted::load_journal(&state.recently_deleted_path);
+        journal.record(record);
+        if let Err(e) = recently_deleted::save_journal(&journal, &state.recently_deleted_path) {
+            tracing::warn!("Failed to save trash journal: {e}");
         }
     }
-    fallback.to_string()
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(moved_response(success, mailbox)))
 }
 
-async fn rsvp(
+/// `DELETE /api/emails/{id}`: destroy the message for good, skipping
+/// Trash.
+async fn delete_email(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
-    Json(body): Json<RsvpBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
-    let mut session_guard = session_lock.write().await;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let ids = std::slice::from_ref(&email_id);
+    let success = provider::destroy_batch(&session, ids).await? > 0;
+    drop(session);
+    if success {
+        forget_trashed(&state, &id, ids).await;
+    }
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
+}
 
-    // Get calendar data
-    let ics_data = provider::get_calendar_data(&session_guard, &email_id)
-        .await?
-        .ok_or_else(|| Error::NotFound("No calendar data found".into()))?;
-
-    let event = calendar::parse_ics(&ics_data)
-        .ok_or_else(|| Error::Internal("Failed to parse calendar data".into()))?;
-
-    // Determine attendee email (use account username as fallback)
-    let attendee_email = {
-        let emails = provider::get_emails(
-            &session_guard,
-            std::slice::from_ref(&email_id),
-            false,
-            None,
-            true, // user-blocking: RSVP click
-        )
-        .await?;
-        let email = emails
-            .first()
-            .ok_or_else(|| Error::NotFound("Email not found".into()))?;
-        determine_attendee_email(email, &event, session_guard.username())
-    };
-
-    let reply_tz = timezone::primary_tz(&timezone::load_config(
-        &state.timezone_config_path,
-        timezone_env_override().as_deref(),
-    ));
-
-    // Dispatch full RSVP flow to provider (Fastmail: iTIP email + CalDAV, Outlook: Graph API)
-    provider::rsvp(
-        &mut session_guard,
-        &ics_data,
-        &event,
-        &attendee_email,
-        &body.status,
-        reply_tz,
-    )
-    .await?;
-
-    // Update the parsed event's attendee status for the frontend response
-    let mut updated_event = event;
-    if let Some(att) = updated_event
-        .attendees
-        .iter_mut()
-        .find(|a| a.email.eq_ignore_ascii_case(&attendee_email))
-    {
-        att.status = body.status.as_ics_str().to_string();
-    }
-    updated_event.user_rsvp_status = Some(body.status.as_ics_str().to_string());
-    Ok(Json(serde_json::json!({ "calendarEvent": updated_event })))
+async fn mark_read(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let success = provider::mark_read(&session, &email_id).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
 }
 
-async fn add_to_calendar(
+async fn mark_unread(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
+    let success = provider::mark_unread(&session, &email_id).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
+}
 
-    let ics_data = provider::get_calendar_data(&session, &email_id)
-        .await?
-        .ok_or_else(|| Error::NotFound("No calendar data found".into()))?;
-
-    let event = calendar::parse_ics(&ics_data)
-        .ok_or_else(|| Error::Internal("Failed to parse calendar data".into()))?;
-
-    // Cancellations should remove, not add
-    let success = if event.method == "CANCEL" {
-        provider::remove_from_calendar(&session, &event.uid).await?
-    } else {
-        provider::add_to_calendar(&session, &ics_data, &event.uid, false).await?
-    };
+async fn toggle_flag(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let success = provider::toggle_flag(&session, &email_id).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
+}
 
-    if success {
-        Ok(Json(serde_json::json!({"success": true})))
-    } else {
-        Err(Error::Internal("Failed to update calendar".into()))
-    }
+#[derive(Deserialize)]
+struct FlagColorBody {
+    /// `None` (or omitted) turns a colored flag back into a plain star.
+    #[serde(default)]
+    color: Option<FlagColor>,
 }
 
-async fn unsubscribe_and_archive(
+/// Flag a message in a color. Always leaves it flagged; unflag with
+/// `toggle-flag`.
+async fn set_flag_color(
     State(state): State<Arc<AppState>>,
     Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
+    Json(body): Json<FlagColorBody>,
 ) -> Result<impl IntoResponse, Error> {
     let id = resolve_account_id(&state, params.account.as_deref()).await?;
     let session_lock = resolve_session(&state, Some(&id)).await?;
     let session = session_lock.read().await;
+    let success = provider::set_flag_color(&session, &email_id, body.color).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
+}
 
-    // Get the email to find the sender
-    let emails = provider::get_emails(
-        &session,
-        std::slice::from_ref(&email_id),
-        true,
-        None,
-        true, // user-blocking: unsubscribe click
-    )
-    .await?;
-    let email = emails
-        .first()
-        .ok_or_else(|| Error::NotFound("Email not found".into()))?;
-
-    let sender_email = email
-        .from
-        .first()
-        .map(|a| a.email.clone())
-        .unwrap_or_default();
+#[derive(Deserialize)]
+struct KeywordsBody {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
 
-    if sender_email.is_empty() {
-        return Err(Error::BadRequest("No sender found".into()));
+/// Add and remove custom keywords (Fastmail labels) on a message. Names
+/// are lowercased; `$` system keywords are refused, as they have their
+/// own endpoints.
+async fn set_keywords(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<KeywordsBody>,
+) -> Result<impl IntoResponse, Error> {
+    if body.add.is_empty() && body.remove.is_empty() {
+        return Err(Error::BadRequest(
+            "Nothing to change: give add and/or remove".into(),
+        ));
     }
-
-    // Query all emails from this sender using structured filter (not string interpolation)
-    let query = crate::types::ParsedQuery {
-        from: vec![sender_email.clone()],
-        ..Default::default()
+    let normalize = |list: &[String]| -> Result<Vec<String>, Error> {
+        list.iter()
+            .map(|k| crate::types::normalize_keyword(k).map_err(Error::BadRequest))
+            .collect()
     };
-    // Order doesn't matter here — every match gets archived regardless of
-    // the sequence they're fetched in — so the default is fine.
-    let all_ids =
-        provider::query_emails(&session, None, 500, 0, Some(&query), EmailSort::default()).await?;
-
-    // Archive all
-    let archived = provider::archive_batch(&session, &all_ids).await?;
+    let add = normalize(&body.add)?;
+    let remove = normalize(&body.remove)?;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let success = provider::set_keywords(&session, &email_id, &add, &remove).await?;
     drop(session);
     state.prefetch.invalidate(&id).await;
-
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "archived": archived,
-        "sender": sender_email
-    })))
+    Ok(Json(serde_json::json!({"success": success})))
 }
 
-// =============================================================================
-// Splits CRUD
-//
-// Definitions live in the single ~/.config/supervillain/splits.json, but
-// each split may be tagged with an owning account. Reads (`list_splits`)
-// scope to ?account=; writes validate the tag against the registry.
-// `/api/split-counts` and `/api/emails?split_id=` scope to the resolved
-// account before counting/filtering.
-// =============================================================================
+/// The flag colors `flag-color` accepts, empty for providers without them.
+async fn flag_colors(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let supported = session_lock.read().await.supports_flag_colors();
+    let colors: &[FlagColor] = if supported { &FlagColor::ALL } else { &[] };
+    Ok(Json(serde_json::json!({ "colors": colors })))
+}
 
-/// splits.json is an input to the per-account split-counts cache; any
-/// write must leave no window where disk and cache disagree. Splits
-/// writes are rare, so invalidating every account's entry is fine.
-async fn invalidate_all_split_caches(state: &AppState) {
-    let ids: Vec<String> = {
-        let reg = state.accounts.read().await;
-        reg.account_configs.keys().cloned().collect()
-    };
-    for id in &ids {
-        state.prefetch.invalidate_split_counts(id).await;
-    }
+async fn move_email(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<MoveBody>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let success = provider::move_to_mailbox(&session, &email_id, &body.mailbox_id).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({"success": success})))
 }
 
-#[derive(Deserialize)]
-struct SplitCountsParams {
-    mailbox_id: String,
-    account: Option<String>,
-    starred: Option<bool>,
+// Defense in depth for outbound HTML: scrubs scripts, event handlers,
+// dangerous URL schemes (javascript:/vbscript:/non-image data:), and other
+// well-known XSS vectors before the message hits the wire. The iframe sandbox
+// protects *our* viewer, but reply/forward bodies carry the original sender's
+// HTML out to recipients whose clients may render it unsafely. Ammonia's
+// defaults are a vetted allowlist sanitizer; this prevents us from being a
+// laundering vector for an attacker's payload across the address book.
+fn sanitize_outgoing_html(html: &str) -> String {
+    ammonia::clean(html)
 }
 
-async fn split_counts(
+async fn send_email_handler(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<SplitCountsParams>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<SendEmailBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let start = std::time::Instant::now();
-
-    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
-    let config = splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    )
-    .scoped_to(Some(&account_id));
-    if config.splits.is_empty() {
-        return Ok(Json(serde_json::json!({})));
+    // Expand `group:<id>` recipients before touching the session so an
+    // unknown group 400s without taking the account's write lock. The
+    // account's address book groups count too.
+    let account = {
+        let reg = state.accounts.read().await;
+        params
+            .account
+            .clone()
+            .unwrap_or_else(|| reg.default_account.clone())
+    };
+    let groups_config = contacts::with_address_book_groups(
+        groups::load_groups(&state.groups_config_path),
+        contacts::load_index(&state.contacts_path)
+            .accounts
+            .get(&account),
+    );
+    let to = groups::expand_recipients(&body.to, &groups_config)?;
+    let cc = groups::expand_recipients(&body.cc, &groups_config)?;
+    let bcc = groups::expand_recipients(&body.bcc, &groups_config)?;
+    if body.individual && (!cc.is_empty() || !bcc.is_empty()) {
+        return Err(Error::BadRequest(
+            "Individual sends do not support cc or bcc".into(),
+        ));
     }
 
-    // Default-view counts (no starred filter) go through the prefetch
-    // cache; the starred-filter variant is per-session ephemeral and
-    // bypasses the cache so it doesn't pollute the steady-state entry.
-    let is_cacheable = params.starred != Some(true);
-
-    let counts: HashMap<String, u32> = if is_cacheable {
-        let id = account_id.clone();
-        let mbox_for_key = params.mailbox_id.clone();
-        let mbox_for_fetch = params.mailbox_id.clone();
-        let cfg = config.clone();
-        let state_for_fetch = state.clone();
-        let acct_for_fetch = params.account.clone();
-        state
-            .prefetch
-            .split_counts_or_fetch(&id, &mbox_for_key, || async move {
-                compute_split_counts(
-                    &state_for_fetch,
-                    acct_for_fetch.as_deref(),
-                    &mbox_for_fetch,
-                    &cfg,
-                    None,
-                )
-                .await
-            })
-            .await?
+    // Validated and deduped up front too, so a bad address fails before
+    // an individual send has delivered to anyone.
+    let prepare = if body.individual {
+        crate::submission::prepare_individual
     } else {
-        let query = crate::types::ParsedQuery {
-            is_flagged: Some(true),
-            ..Default::default()
-        };
-        compute_split_counts(
-            &state,
-            params.account.as_deref(),
-            &params.mailbox_id,
-            &config,
-            Some(query),
-        )
-        .await?
+        crate::submission::prepare
     };
+    let submission = prepare(&EmailSubmission {
+        to,
+        cc,
+        subject: body.subject,
+        text_body: body.body,
+        bcc: if bcc.is_empty() { None } else { Some(bcc) },
+        html_body: body.html_body.map(|h| sanitize_outgoing_html(&h)),
+        in_reply_to: body.in_reply_to,
+        references: body.references.filter(|r| !r.is_empty()),
+        attachments: body.attachments,
+        calendar_ics: None,
+        forward_of: body.forward_of.filter(|id| !id.is_empty()),
+    })?;
+    send_guard::load_config(&state.send_guard_path).check(
+        &submission,
+        body.totp_code.as_deref(),
+        chrono::Utc::now(),
+    )?;
+
+    let send_hours = send_hours::load_config(&state.send_hours_path);
+    if !body.confirm_send_time && send_hours.is_enabled() {
+        let zones = if send_hours.recipient_night.is_some() {
+            let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+            let session = session_lock.read().await;
+            recipient_zones(&session, &submission).await
+        } else {
+            Vec::new()
+        };
+        let tz = timezone::primary_tz(&timezone::load_config(
+            &state.timezone_config_path,
+            timezone_env_override().as_deref(),
+        ));
+        send_hours.check(chrono::Utc::now(), tz, &zones)?;
+    }
 
-    tracing::debug!(
-        "split-counts: {} splits, {:.0}ms",
-        counts.len(),
-        start.elapsed().as_millis()
-    );
+    // A reply teaches the focused inbox that its recipients matter.
+    if submission.in_reply_to.is_some() {
+        let to: Vec<&str> = submission.to.iter().map(String::as_str).collect();
+        record_priority(&state, &account, &to, priority::Action::Replied).await;
+    }
+
+    let session_lock = resolve_session(&state, Some(&account)).await?;
+    // Resolved once here rather than per copy, and before the undo window
+    // so a bad id fails while the user is still looking at the compose.
+    let submission = provider::attach_forwarded(&*session_lock.read().await, submission).await?;
+    let from_addr = match body.from_address {
+        Some(addr) => addr,
+        None => session_lock.read().await.username().to_string(),
+    };
+    let submission =
+        with_signature_image(&state, &account, &session_lock, submission, &from_addr).await;
 
-    Ok(Json(serde_json::json!(counts)))
+    if state.undo_send.is_enabled() {
+        return queue_send(
+            &state,
+            &account,
+            submission,
+            from_addr,
+            body.individual,
+            body.draft_id,
+        )
+        .await
+        .map(Json);
+    }
+    deliver_submission(&state, &account, &submission, &from_addr, body.individual)
+        .await
+        .map(Json)
 }
 
-/// Shared splits-counting implementation used by the `/api/split-counts`
-/// handler *and* the prefetch warmer. Drift between the two would mean
-/// the cached value the warmer wrote was computed from a different
-/// sample size than the route would have produced on a miss, so the
-/// user-facing count would flip every time the cache invalidated. One
-/// function, one constant.
-pub(crate) async fn compute_split_counts(
+/// `submission` with the sending identity's signature logo embedded
+/// (`signature_image`), when one is configured and the message still
+/// carries the account's signature. Best-effort: an unreadable image or a
+/// failed upload is logged and the message goes out without it.
+async fn with_signature_image(
     state: &AppState,
-    account: Option<&str>,
-    mailbox_id: &str,
-    config: &SplitsConfig,
-    query: Option<crate::types::ParsedQuery>,
-) -> Result<HashMap<String, u32>, Error> {
-    let session_lock = resolve_session(state, account).await?;
+    account: &str,
+    session_lock: &SessionLock,
+    mut submission: EmailSubmission,
+    from_addr: &str,
+) -> EmailSubmission {
+    let rules = crate::identities::load_rules(&state.identities_config_path);
+    let Some(path) = rules
+        .accounts
+        .get(account)
+        .and_then(|r| signature_image::image_for(r, from_addr))
+    else {
+        return submission;
+    };
+    let signature = state
+        .accounts
+        .read()
+        .await
+        .account_configs
+        .get(account)
+        .and_then(|c| c.signature().map(str::to_string));
+    if !signature.is_some_and(|sig| signature_image::carries_signature(&submission, &sig)) {
+        return submission;
+    }
+    let session = session_lock.read().await;
+    if !matches!(*session, provider::ProviderSession::Fastmail(_)) {
+        tracing::debug!(
+            "Signature images are not supported for {} yet",
+            session.provider_name()
+        );
+        return submission;
+    }
+    let path = std::path::PathBuf::from(path);
+    let image = match signature_image::read_image(&path) {
+        Ok(image) => image,
+        Err(e) => {
+            tracing::warn!("[{account}] skipping signature image: {e}");
+            return submission;
+        }
+    };
+    let blob = match state.signature_blobs.get(account, &path, image.modified) {
+        Some(blob) => blob,
+        None => match provider::upload_blob(&session, image.mime_type, &image.bytes).await {
+            Ok((blob_id, size)) => {
+                let blob = Attachment {
+                    blob_id,
+                    name: image.name,
+                    mime_type: image.mime_type.to_string(),
+                    size,
+                    sniffed_mime_type: None,
+                    mime_mismatch: false,
+                    content_id: None,
+                };
+                state
+                    .signature_blobs
+                    .insert(account, &path, image.modified, blob.clone());
+                blob
+            }
+            Err(e) => {
+                tracing::warn!("[{account}] signature image upload failed: {e}");
+                return submission;
+            }
+        },
+    };
+    signature_image::embed(&mut submission, blob);
+    submission
+}
 
-    let fetch_limit = DEFAULT_INBOX_LIMIT * SPLIT_OVERFETCH_MULTIPLIER;
-    // Split counts are order-independent (just counting matches), so the
-    // default sort is fine here regardless of the user's list sort choice.
-    let email_ids = {
-        let session = session_lock.read().await;
-        provider::query_emails(
-            &session,
-            Some(mailbox_id),
-            fetch_limit,
-            0,
-            query.as_ref(),
-            EmailSort::default(),
-        )
-        .await?
+/// Submit a prepared message now: one message, or with `individual` one
+/// per `to` recipient. The JSON is what `/api/emails/send` reports.
+async fn deliver_submission(
+    state: &AppState,
+    account: &str,
+    submission: &EmailSubmission,
+    from_addr: &str,
+    individual: bool,
+) -> Result<serde_json::Value, Error> {
+    let session_lock = resolve_session(state, Some(account)).await?;
+    let mut session = session_lock.write().await;
+    let template = if template_vars::has_vars(submission) {
+        Some(SendTemplateContext::load(state, account, &mut session, from_addr).await)
+    } else {
+        None
     };
 
-    // This is the single longest provider fan-out in the app (~1500 gets,
-    // minutes on a rate-limited Gmail account), so releasing the session
-    // guard between chunks matters most here: a send queued behind one
-    // monolithic guard used to stall until the whole sample finished.
-    let minimal_props: &[&str] = &["id", "from", "to", "cc", "subject"];
-    let all_emails = provider::get_emails_chunked(
-        &session_lock,
-        &email_ids,
-        false,
-        Some(minimal_props),
-        provider::GET_EMAILS_CHUNK,
-    )
-    .await?;
+    if individual {
+        // One submission per recipient. Stops at the first failure and
+        // reports how far it got: recipients before it already have their
+        // copy, so a blind retry of the whole list would double-send.
+        let mut ids = Vec::with_capacity(submission.to.len());
+        for recipient in &submission.to {
+            let mut single = EmailSubmission {
+                to: vec![recipient.clone()],
+                ..submission.clone()
+            };
+            if let Some(template) = &template {
+                single = template.expand(&single);
+            }
+            match provider::send_email(&mut session, &single, from_addr, None).await {
+                Ok(Some(id)) => ids.push(id),
+                // Nothing went out yet: surface the provider error as-is.
+                Err(e) if ids.is_empty() => return Err(e),
+                result => {
+                    let reason = match result {
+                        Err(e) => e.to_string(),
+                        _ => "no email id returned".into(),
+                    };
+                    return Err(Error::Internal(format!(
+                        "Failed to send to {recipient} ({} of {} sent): {reason}",
+                        ids.len(),
+                        submission.to.len()
+                    )));
+                }
+            }
+        }
+        return Ok(serde_json::json!({"success": true, "emailIds": ids}));
+    }
 
-    let mut counts = HashMap::new();
-    for split in &config.splits {
-        let count = all_emails
+    let expanded = template.as_ref().map(|t| t.expand(submission));
+    let submission = expanded.as_ref().unwrap_or(submission);
+    let result = provider::send_email(&mut session, submission, from_addr, None).await?;
+
+    match result {
+        Some(id) => Ok(serde_json::json!({"success": true, "emailId": id})),
+        None => Err(Error::Internal("Failed to send email".into())),
+    }
+}
+
+/// What `template_vars` needs besides the recipient, gathered once per
+/// send.
+struct SendTemplateContext {
+    now: chrono::DateTime<chrono_tz::Tz>,
+    contact_names: HashMap<String, String>,
+    my_name: String,
+}
+
+impl SendTemplateContext {
+    async fn load(
+        state: &AppState,
+        account: &str,
+        session: &mut provider::ProviderSession,
+        from_addr: &str,
+    ) -> Self {
+        let tz = timezone::primary_tz(&timezone::load_config(
+            &state.timezone_config_path,
+            timezone_env_override().as_deref(),
+        ));
+        let identities = match state.prefetch.get_identities(account).await {
+            Some(identities) => identities,
+            None => provider::get_identities(session)
+                .await
+                .inspect_err(|e| tracing::warn!("template variables: no identities: {e}"))
+                .unwrap_or_default(),
+        };
+        let my_name = identities
             .iter()
-            .filter(|e| splits::matches_split(e, split))
-            .count();
-        counts.insert(split.id.clone(), count as u32);
+            .find(|i| i.email.eq_ignore_ascii_case(from_addr))
+            .map(|i| i.name.clone())
+            .unwrap_or_default();
+        Self {
+            now: chrono::Utc::now().with_timezone(&tz),
+            contact_names: contact_names(state, account),
+            my_name,
+        }
+    }
+
+    /// `sub` with its variables expanded for its first `to` recipient.
+    fn expand(&self, sub: &EmailSubmission) -> EmailSubmission {
+        let vars = template_vars::Vars::new(
+            self.now,
+            sub.to.first().map(String::as_str),
+            &self.contact_names,
+            &self.my_name,
+        );
+        template_vars::expand_submission(sub, &vars)
     }
-    Ok(counts)
 }
 
-#[derive(Deserialize)]
-struct ListSplitsParams {
-    account: Option<String>,
+/// Hold a validated send for the undo window (see `undo_send`). The
+/// message replaces `draft_id` in Drafts (or becomes a new draft) so a
+/// cancel leaves it there; the draft is removed once the send goes out.
+async fn queue_send(
+    state: &Arc<AppState>,
+    account: &str,
+    submission: EmailSubmission,
+    from_addr: String,
+    individual: bool,
+    draft_id: Option<String>,
+) -> Result<serde_json::Value, Error> {
+    let draft_id = {
+        let session_lock = resolve_session(state, Some(account)).await?;
+        let session = session_lock.read().await;
+        if session.supports_drafts() {
+            let saved = match draft_id.as_deref() {
+                Some(id) => provider::update_draft(&session, id, &submission, &from_addr).await,
+                None => provider::create_draft(&session, &submission, &from_addr).await,
+            };
+            // The undo copy is a convenience: failing to save it shouldn't
+            // block the send the user asked for.
+            saved
+                .inspect_err(|e| tracing::warn!("undo send: couldn't save draft: {e}"))
+                .ok()
+        } else {
+            None
+        }
+    };
+
+    let delay = state.undo_send.delay();
+    let send_at = chrono::Utc::now() + delay;
+    let pending = undo_send::PendingSend::new(account, draft_id.clone(), send_at);
+    let (token_tx, token_rx) = tokio::sync::oneshot::channel::<String>();
+    let task = tokio::spawn({
+        let state = state.clone();
+        async move {
+            tokio::time::sleep(delay).await;
+            let Ok(token) = token_rx.await else {
+                return;
+            };
+            let Some(pending) = state.undo_send.take(&token) else {
+                return;
+            };
+            let status = match deliver_submission(
+                &state,
+                &pending.account,
+                &submission,
+                &from_addr,
+                individual,
+            )
+            .await
+            {
+                Ok(_) => {
+                    if let Some(draft_id) = &pending.draft_id
+                        && let Ok(session_lock) =
+                            resolve_session(&state, Some(&pending.account)).await
+                        && let Err(e) =
+                            provider::destroy_draft(&*session_lock.read().await, draft_id).await
+                    {
+                        tracing::warn!("undo send: couldn't remove draft {draft_id}: {e}");
+                    }
+                    undo_send::SendStatus::Sent
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "[{}] delayed send failed, message kept in Drafts: {e}",
+                        pending.account
+                    );
+                    // As in the error responses, network and internal
+                    // details stay in the log.
+                    let error = match e {
+                        Error::Network(_) => "network error".to_string(),
+                        Error::Internal(_) => "internal error".to_string(),
+                        e => e.to_string(),
+                    };
+                    undo_send::SendStatus::Failed { error }
+                }
+            };
+            state.undo_send.finish(&token, &pending.account, status);
+        }
+    });
+    let token = state.undo_send.insert(pending, task.abort_handle());
+    let _ = token_tx.send(token.clone());
+    Ok(serde_json::json!({
+        "success": true,
+        "pending": true,
+        "token": token,
+        "sendAt": send_at,
+        "draftId": draft_id,
+    }))
 }
 
-async fn list_splits(
+/// `GET /api/emails/send/{token}`: how a held send ended, or `pending`
+/// while it waits. 404 for unknown tokens and long-finished sends.
+async fn send_status(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<ListSplitsParams>,
+    Path(token): Path<String>,
+    Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
-    // No ?account= → full list (management/debugging view). The UI always
-    // sends the active account via the api() helper. When an account IS
-    // given, validate it — a typo would otherwise silently compute against
-    // the untagged-only split list instead of 400ing.
-    if let Some(ref acct) = params.account {
-        let reg = state.accounts.read().await;
-        ensure_known_account(&reg, acct)?;
-    }
-
-    let config = splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    );
-    Ok(Json(serde_json::json!(
-        config.scoped_to(params.account.as_deref()).splits
-    )))
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    state
+        .undo_send
+        .status(&token, &account)
+        .map(Json)
+        .ok_or_else(|| Error::NotFound("no send with that token".into()))
 }
 
-async fn create_split(
+/// `POST /api/emails/send/{token}/cancel`: call back a send still in its
+/// undo window. 404 once it has gone out (or was already cancelled).
+async fn cancel_send(
     State(state): State<Arc<AppState>>,
-    Json(new_split): Json<SplitInbox>,
+    Path(token): Path<String>,
+    Query(params): Query<AccountParam>,
 ) -> Result<impl IntoResponse, Error> {
-    let mut config = splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    );
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let pending = state
+        .undo_send
+        .cancel(&token, &account)
+        .ok_or_else(|| Error::NotFound("no pending send with that token".into()))?;
+    Ok(Json(serde_json::json!({
+        "cancelled": true,
+        "draftId": pending.draft_id,
+    })))
+}
 
-    // Check for duplicate ID
-    if config.splits.iter().any(|s| s.id == new_split.id) {
-        return Err(Error::BadRequest(format!(
-            "Split with id '{}' already exists",
-            new_split.id
-        )));
+/// Recipients whose zone `send_hours` infers; the rest go unchecked so a
+/// long To line doesn't turn one send into dozens of lookups.
+const SEND_HOURS_RECIPIENTS: usize = 5;
+
+/// Messages from each recipient whose `Date` offsets are sampled.
+const SEND_HOURS_SAMPLE: usize = 3;
+
+/// Likely UTC offset of the first few to/cc recipients, from the `Date`
+/// headers of their most recent mail to us. Recipients we've never heard
+/// from, or whose lookups fail, are left out rather than failing the send.
+async fn recipient_zones(
+    session: &provider::ProviderSession,
+    submission: &EmailSubmission,
+) -> Vec<send_hours::RecipientZone> {
+    let mut zones = Vec::new();
+    for address in submission
+        .to
+        .iter()
+        .chain(&submission.cc)
+        .take(SEND_HOURS_RECIPIENTS)
+    {
+        let query = ParsedQuery {
+            from: vec![address.clone()],
+            ..Default::default()
+        };
+        let ids = match provider::query_emails(
+            session,
+            None,
+            SEND_HOURS_SAMPLE,
+            0,
+            Some(&query),
+            EmailSort::DateDesc,
+        )
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                tracing::warn!("send-hours: lookup for {address} failed: {e}");
+                continue;
+            }
+        };
+        let mut dates = Vec::new();
+        for id in &ids {
+            let Ok(headers) = provider::get_headers(session, id).await else {
+                continue;
+            };
+            dates.extend(
+                headers
+                    .iter()
+                    .find(|h| h.name.eq_ignore_ascii_case("Date"))
+                    .and_then(|h| crate::delivery::parse_date(&h.value)),
+            );
+        }
+        if let Some(offset) = send_hours::likely_offset(&dates) {
+            zones.push(send_hours::RecipientZone {
+                address: address.clone(),
+                offset,
+            });
+        }
     }
+    zones
+}
 
-    // Reject typos early: a split tagged to an unknown account would
-    // silently never render anywhere.
-    if let Some(ref acct) = new_split.account {
-        let reg = state.accounts.read().await;
-        ensure_known_account(&reg, acct)?;
+// --- Persistent drafts (kata wm57) -----------------------------------------
+
+/// Build a plain-text `EmailSubmission` from a draft body. v1 persists no
+/// html_body, attachments, or calendar — those live only in the live compose
+/// session, never in the stored draft.
+fn draft_submission(body: DraftBody) -> EmailSubmission {
+    EmailSubmission {
+        to: body.to,
+        cc: body.cc,
+        subject: body.subject,
+        text_body: body.body,
+        bcc: None,
+        html_body: None,
+        in_reply_to: body.in_reply_to,
+        references: None,
+        attachments: Vec::new(),
+        calendar_ics: None,
+        forward_of: None,
     }
+}
 
-    config.splits.push(new_split);
-    splits::save_splits(&config, &state.splits_config_path)?;
-    invalidate_all_split_caches(&state).await;
+/// Saved drafts cap for `GET /api/drafts`, and its default.
+const MAX_DRAFTS_LIST: usize = 100;
 
-    Ok(Json(serde_json::json!(config.splits)))
+#[derive(Deserialize)]
+struct ListDraftsParams {
+    account: Option<String>,
+    limit: Option<usize>,
 }
 
-async fn update_split(
+/// Saved drafts to resume, newest first, in the list-row shape plus
+/// `inReplyTo`. Bodies come from `GET /api/emails/{id}` when one is opened.
+async fn list_drafts_handler(
     State(state): State<Arc<AppState>>,
-    Path(split_id): Path<String>,
-    Json(updated): Json<SplitInbox>,
+    Query(params): Query<ListDraftsParams>,
 ) -> Result<impl IntoResponse, Error> {
-    let mut config = splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    );
-
-    // Reject typos early: a split tagged to an unknown account would
-    // silently never render anywhere.
-    if let Some(ref acct) = updated.account {
-        let reg = state.accounts.read().await;
-        ensure_known_account(&reg, acct)?;
-    }
-
-    if updated.id != split_id {
+    let limit = params.limit.unwrap_or(MAX_DRAFTS_LIST);
+    if !(1..=MAX_DRAFTS_LIST).contains(&limit) {
         return Err(Error::BadRequest(format!(
-            "Split id is immutable ('{split_id}' != '{}')",
-            updated.id
+            "limit must be between 1 and {MAX_DRAFTS_LIST}"
         )));
     }
-
-    let existing = config
-        .splits
-        .iter_mut()
-        .find(|s| s.id == split_id)
-        .ok_or_else(|| Error::NotFound(format!("Split '{split_id}' not found")))?;
-
-    // PUT replaces the whole split: a body without `account` UNTAGS it
-    // (makes it global). Deliberate — the body is the full new state.
-    *existing = updated;
-    splits::save_splits(&config, &state.splits_config_path)?;
-    invalidate_all_split_caches(&state).await;
-
-    Ok(Json(serde_json::json!(config.splits)))
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let drafts = provider::list_drafts(&*session_lock.read().await, limit).await?;
+    let names = contact_names(&state, &id);
+    let rows: Vec<serde_json::Value> = drafts
+        .iter()
+        .map(|e| {
+            let mut row = email_summary_json(e, &names);
+            row["inReplyTo"] = serde_json::json!(e.in_reply_to);
+            row
+        })
+        .collect();
+    Ok(Json(rows))
 }
 
-async fn delete_split(
+async fn create_draft_handler(
     State(state): State<Arc<AppState>>,
-    Path(split_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<DraftBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let mut config = splits::load_splits(
-        &state.splits_config_path,
-        std::env::var("SUPERVILLAIN_SPLITS").ok().as_deref(),
-    );
-
-    let original_len = config.splits.len();
-    config.splits.retain(|s| s.id != split_id);
-
-    if config.splits.len() == original_len {
-        return Err(Error::NotFound(format!("Split '{split_id}' not found")));
-    }
-
-    splits::save_splits(&config, &state.splits_config_path)?;
-    invalidate_all_split_caches(&state).await;
-
-    Ok(Json(serde_json::json!(config.splits)))
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let from_addr = body
+        .from_address
+        .clone()
+        .unwrap_or_else(|| session.username().to_string());
+    let submission = draft_submission(body);
+    let draft_id = provider::create_draft(&session, &submission, &from_addr).await?;
+    drop(session);
+    // A newly created draft belongs in a warmed Drafts-list cache too — without
+    // this a prefetched list can hide it for up to 5 minutes (review follow-up).
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({ "id": draft_id })))
 }
 
-// =============================================================================
-// Timezone settings
-// =============================================================================
-
-#[derive(serde::Deserialize)]
-struct TimezoneConfigBody {
-    #[serde(default = "default_true_bool")]
-    use_system: bool,
-    #[serde(default)]
-    manual_primary: Option<String>,
-    #[serde(default)]
-    additional: Vec<String>,
-}
-
-fn default_true_bool() -> bool {
-    true
+async fn update_draft_handler(
+    State(state): State<Arc<AppState>>,
+    Path(draft_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<DraftBody>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let from_addr = body
+        .from_address
+        .clone()
+        .unwrap_or_else(|| session.username().to_string());
+    let submission = draft_submission(body);
+    // Editing a draft is destroy+recreate (JMAP bodies aren't patchable), so
+    // this returns a NEW id the client must adopt.
+    let new_id = provider::update_draft(&session, &draft_id, &submission, &from_addr).await?;
+    drop(session);
+    // The destroy+recreate rotates the draft's id — a warmed Drafts list must
+    // be invalidated or it keeps serving the now-destroyed old id (review
+    // follow-up; same gap as create/delete below).
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({ "id": new_id })))
 }
 
-fn timezone_env_override() -> Option<String> {
-    std::env::var("SUPERVILLAIN_TIMEZONE").ok()
+async fn delete_draft_handler(
+    State(state): State<Arc<AppState>>,
+    Path(draft_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let success = provider::destroy_draft(&session, &draft_id).await?;
+    drop(session);
+    // Without this a warmed Drafts list keeps serving the destroyed id for up
+    // to 5 minutes (review follow-up), same as every other mutation route.
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({ "success": success })))
 }
 
-async fn get_timezone(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let cfg = timezone::load_config(
-        &state.timezone_config_path,
-        timezone_env_override().as_deref(),
-    );
-    Json(serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default())
-}
+const MAX_UPLOAD_SIZE: usize = 25 * 1024 * 1024; // 25 MB
 
-async fn put_timezone(
+async fn upload_blob(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<TimezoneConfigBody>,
+    Query(params): Query<AccountParam>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> Result<impl IntoResponse, Error> {
-    if let Some(ref primary) = body.manual_primary
-        && !primary.is_empty()
-        && !timezone::validate_iana(primary)
-    {
+    if body.len() > MAX_UPLOAD_SIZE {
         return Err(Error::BadRequest(format!(
-            "Unknown IANA timezone: {primary}"
+            "File too large ({} bytes, max {})",
+            body.len(),
+            MAX_UPLOAD_SIZE
         )));
     }
-    for tz in &body.additional {
-        if !timezone::validate_iana(tz) {
-            return Err(Error::BadRequest(format!("Unknown IANA timezone: {tz}")));
-        }
-    }
 
-    let _guard = state.timezone_write_lock.lock().await;
-    let mut existing = timezone::load_config(
-        &state.timezone_config_path,
-        timezone_env_override().as_deref(),
-    );
-    existing.use_system = body.use_system;
-    existing.manual_primary = body.manual_primary.filter(|s| !s.is_empty());
-    existing.additional = body.additional;
-    // Seed last_known_system_tz on first save so the change banner has a baseline.
-    if existing.last_known_system_tz.is_none() {
-        existing.last_known_system_tz = Some(timezone::detect_system_tz());
-    }
-    timezone::save_config(&existing, &state.timezone_config_path)?;
+    let content_type = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream");
 
-    Ok(Json(
-        serde_json::to_value(timezone::resolve(&existing)).unwrap_or_default(),
-    ))
+    let raw_filename = headers
+        .get("x-filename")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("attachment");
+    let filename = sanitize_filename_for_header(raw_filename);
+
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let session = session_lock.read().await;
+
+    let (blob_id, size) = provider::upload_blob(&session, content_type, &body).await?;
+
+    Ok(Json(serde_json::json!({
+        "blob_id": blob_id,
+        "name": filename,
+        "mime_type": content_type,
+        "size": size,
+    })))
 }
 
-async fn accept_system_timezone(
+fn determine_attendee_email(email: &Email, event: &CalendarEvent, fallback: &str) -> String {
+    for addr in email.to.iter().chain(email.cc.iter()) {
+        if event
+            .attendees
+            .iter()
+            .any(|a| a.email.eq_ignore_ascii_case(&addr.email))
+        {
+            return addr.email.clone();
+        }
+    }
+    fallback.to_string()
+}
+
+async fn rsvp(
     State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<RsvpBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let _guard = state.timezone_write_lock.lock().await;
-    let mut cfg = timezone::load_config(
+    let account_key = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account_key)).await?;
+    let mut session_guard = session_lock.write().await;
+
+    // Get calendar data
+    let ics_data = provider::get_calendar_data(&session_guard, &email_id)
+        .await?
+        .ok_or_else(|| Error::NotFound("No calendar data found".into()))?;
+
+    let event = calendar::parse_ics(&ics_data)
+        .ok_or_else(|| Error::Internal("Failed to parse calendar data".into()))?;
+
+    // Determine attendee email (use account username as fallback)
+    let attendee_email = {
+        let emails = provider::get_emails(
+            &session_guard,
+            std::slice::from_ref(&email_id),
+            false,
+            None,
+            true, // user-blocking: RSVP click
+        )
+        .await?;
+        let email = emails
+            .first()
+            .ok_or_else(|| Error::NotFound("Email not found".into()))?;
+        determine_attendee_email(email, &event, session_guard.username())
+    };
+
+    let reply_tz = timezone::primary_tz(&timezone::load_config(
         &state.timezone_config_path,
         timezone_env_override().as_deref(),
-    );
-    let system = timezone::detect_system_tz();
-    cfg.last_known_system_tz = Some(system.clone());
-    cfg.dismissed_change_to = None;
-    // If the user wants to track system, that's already wired through use_system.
-    // Don't toggle use_system here; "Accept system" means "the new system TZ is fine".
-    timezone::save_config(&cfg, &state.timezone_config_path)?;
-    Ok(Json(
-        serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default(),
-    ))
-}
+    ));
 
-#[derive(serde::Deserialize, Default)]
-struct DismissTimezoneBody {
-    /// The system TZ value the client was looking at when the user dismissed
-    /// the banner. If it no longer matches the currently detected system TZ,
-    /// we refuse — the user can't "Keep current" on a change they never saw.
-    /// Optional for backwards-compat with clients that don't send it.
-    #[serde(default)]
-    seen_system: Option<String>,
-}
+    // Dispatch full RSVP flow to provider (Fastmail: iTIP email + CalDAV, Outlook: Graph API)
+    provider::rsvp(
+        &mut session_guard,
+        &ics_data,
+        &event,
+        &attendee_email,
+        &body.status,
+        reply_tz,
+    )
+    .await?;
 
-async fn dismiss_timezone_change(
-    State(state): State<Arc<AppState>>,
-    body: Option<Json<DismissTimezoneBody>>,
-) -> Result<impl IntoResponse, Error> {
-    let _guard = state.timezone_write_lock.lock().await;
-    let current_system = timezone::detect_system_tz();
-    if let Some(Json(b)) = body
-        && let Some(seen) = b.seen_system
-        && !seen.is_empty()
-        && seen != current_system
+    // Update the parsed event's attendee status for the frontend response
+    let mut updated_event = event;
+    if let Some(att) = updated_event
+        .attendees
+        .iter_mut()
+        .find(|a| a.email.eq_ignore_ascii_case(&attendee_email))
     {
-        return Err(Error::Conflict(format!(
-            "system timezone changed from {seen} to {current_system} since the banner was shown — please recheck"
-        )));
+        att.status = body.status.as_ics_str().to_string();
     }
-    let mut cfg = timezone::load_config(
-        &state.timezone_config_path,
-        timezone_env_override().as_deref(),
-    );
-    cfg.dismissed_change_to = Some(current_system);
-    timezone::save_config(&cfg, &state.timezone_config_path)?;
-    Ok(Json(
-        serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default(),
-    ))
-}
+    updated_event.user_rsvp_status = Some(body.status.as_ics_str().to_string());
 
-async fn list_timezones() -> impl IntoResponse {
-    let names: Vec<&'static str> = chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
-    Json(serde_json::json!(names))
+    // Remember the answer so reopening the invite doesn't prompt again
+    // where the provider keeps no calendar copy to read it back from.
+    let record = rsvp_log::RsvpRecord {
+        status: body.status.as_ics_str().to_string(),
+        sequence: updated_event.sequence,
+        responded_at: chrono::Utc::now(),
+    };
+    let mut log = rsvp_log::load(&*state.store, &account_key);
+    log.record(&updated_event.uid, record.clone());
+    if let Err(e) = rsvp_log::save(&*state.store, &account_key, &log) {
+        tracing::warn!("Failed to record RSVP for {}: {e}", updated_event.uid);
+    }
+    updated_event.my_response = rsvp_log::my_response(None, Some(&record));
+    Ok(Json(serde_json::json!({ "calendarEvent": updated_event })))
 }
 
-// =============================================================================
-// Calendar invite composition
-// =============================================================================
+async fn add_to_calendar(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let session = session_lock.read().await;
 
-#[derive(serde::Deserialize)]
-struct InviteAttendee {
-    email: String,
-    #[serde(default)]
-    name: Option<String>,
-}
+    let ics_data = provider::get_calendar_data(&session, &email_id)
+        .await?
+        .ok_or_else(|| Error::NotFound("No calendar data found".into()))?;
 
-#[derive(serde::Deserialize)]
-struct SendInviteBody {
-    to: Vec<String>,
-    #[serde(default)]
-    cc: Vec<String>,
-    #[serde(default)]
-    bcc: Vec<String>,
-    subject: String,
-    #[serde(default)]
-    body: String,
-    summary: String,
-    #[serde(default)]
-    description: Option<String>,
-    #[serde(default)]
-    location: Option<String>,
-    start: String, // "2026-06-01T10:00:00"
-    end: String,
-    #[serde(default)]
-    tz: Option<String>,
-    #[serde(default)]
-    attendees: Vec<InviteAttendee>,
-    #[serde(default)]
-    from_address: Option<String>,
-    /// File attachments resolved at send time the same way `/emails/send`
-    /// resolves them. Without this, users who attached files AND enabled
-    /// the invite toggle would silently lose the attachments (roborev 186 #6).
-    #[serde(default)]
-    attachments: Vec<Attachment>,
+    let event = calendar::parse_ics(&ics_data)
+        .ok_or_else(|| Error::Internal("Failed to parse calendar data".into()))?;
+
+    // Cancellations should remove, not add
+    let success = if event.method == "CANCEL" {
+        provider::remove_from_calendar(&session, &event.uid).await?
+    } else {
+        let ics_data = with_default_reminder(&state, &ics_data).await;
+        provider::add_to_calendar(&session, &ics_data, &event.uid, false).await?
+    };
+
+    if success {
+        Ok(Json(serde_json::json!({"success": true})))
+    } else {
+        Err(Error::Internal("Failed to update calendar".into()))
+    }
 }
 
-/// Send an email with an embedded iTIP REQUEST.
-///
-/// Attendee list comes from `body.attendees` only — `to`/`cc`/`bcc` control
-/// envelope routing, not ICS ATTENDEE properties. BCC privacy is preserved
-/// (BCC recipients are not visible in the ICS) but the caller must include
-/// them in `attendees` if it wants them tracked in calendar attendees.
-/// The frontend builds `attendees` from `to + cc` deliberately; if BCC
-/// support is added to the compose UI, decide policy then (include + warn,
-/// or exclude + warn).
-async fn send_invite_handler(
+/// `GET /api/emails/{id}/event.ics`: the message's invite as an importable
+/// file, for calendars the app doesn't sync with. A cancellation has nothing
+/// to import and is rejected.
+async fn event_ics(
     State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
     Query(params): Query<AccountParam>,
-    Json(body): Json<SendInviteBody>,
 ) -> Result<impl IntoResponse, Error> {
-    let tz_cfg = timezone::load_config(
-        &state.timezone_config_path,
-        timezone_env_override().as_deref(),
-    );
-    let resolved = timezone::resolve(&tz_cfg);
-    let tz_name = body
-        .tz
-        .clone()
-        .filter(|s| !s.is_empty())
-        .unwrap_or(resolved.primary.clone());
-    if !timezone::validate_iana(&tz_name) {
-        return Err(Error::BadRequest(format!(
-            "Unknown IANA timezone: {tz_name}"
-        )));
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let session = session_lock.read().await;
+    let ics_data = provider::get_calendar_data(&session, &email_id)
+        .await?
+        .ok_or_else(|| Error::NotFound("No calendar data found".into()))?;
+    drop(session);
+
+    let event = calendar::parse_ics(&ics_data)
+        .ok_or_else(|| Error::Internal("Failed to parse calendar data".into()))?;
+    if event.method == "CANCEL" {
+        return Err(Error::Unprocessable(
+            "This message cancels an event; there is nothing to import".into(),
+        ));
     }
-    let tz: chrono_tz::Tz = std::str::FromStr::from_str(&tz_name)
-        .map_err(|_| Error::BadRequest(format!("Unknown IANA timezone: {tz_name}")))?;
+    let ics_data = with_default_reminder(&state, &ics_data).await;
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", "text/calendar; charset=utf-8".to_string()),
+            (
+                "content-disposition",
+                downloads::content_disposition(&calendar::export_filename(&event.summary)),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        calendar::export_ics(&ics_data),
+    ))
+}
 
-    let start_naive = chrono::NaiveDateTime::parse_from_str(&body.start, "%Y-%m-%dT%H:%M:%S")
-        .or_else(|_| chrono::NaiveDateTime::parse_from_str(&body.start, "%Y-%m-%dT%H:%M"))
-        .map_err(|e| Error::BadRequest(format!("Invalid start time: {e}")))?;
-    let end_naive = chrono::NaiveDateTime::parse_from_str(&body.end, "%Y-%m-%dT%H:%M:%S")
-        .or_else(|_| chrono::NaiveDateTime::parse_from_str(&body.end, "%Y-%m-%dT%H:%M"))
-        .map_err(|e| Error::BadRequest(format!("Invalid end time: {e}")))?;
-    let dtstart = chrono::TimeZone::from_local_datetime(&tz, &start_naive)
-        .earliest()
-        .ok_or_else(|| Error::BadRequest("start time has no valid mapping in tz".into()))?;
-    let dtend = chrono::TimeZone::from_local_datetime(&tz, &end_naive)
-        .earliest()
-        .ok_or_else(|| Error::BadRequest("end time has no valid mapping in tz".into()))?;
-    // Roborev 186 #7: reject negative-duration invites at the boundary rather
-    // than relying on the recipient's calendar client.
-    if dtend <= dtstart {
+/// Record `email` in `account`'s newsletter read rates. Best effort.
+async fn note_newsletter(state: &AppState, account: &str, email: &Email, opened: bool) {
+    let Some(from) = email.from.first() else {
+        return;
+    };
+    let _guard = state.newsletters_lock.lock().await;
+    let mut activity = newsletters::load(&*state.store, account);
+    if activity.note(
+        &from.email,
+        from.name.as_deref(),
+        &email.id,
+        email.received_at,
+        opened,
+    ) && let Err(e) = newsletters::save(&*state.store, account, &activity)
+    {
+        tracing::warn!("[{account}] couldn't record newsletter activity: {e}");
+    }
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeSuggestionsParams {
+    account: Option<String>,
+    min_received: Option<usize>,
+    max_read_rate: Option<f64>,
+}
+
+/// Newsletter senders I rarely open; see `newsletters`. Protected senders
+/// are never suggested.
+async fn unsubscribe_suggestions(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UnsubscribeSuggestionsParams>,
+) -> Result<impl IntoResponse, Error> {
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let min_received = params
+        .min_received
+        .unwrap_or(newsletters::DEFAULT_MIN_RECEIVED);
+    let max_read_rate = params
+        .max_read_rate
+        .unwrap_or(newsletters::DEFAULT_MAX_READ_RATE);
+    if !(0.0..=1.0).contains(&max_read_rate) {
         return Err(Error::BadRequest(
-            "end time must be after start time".into(),
+            "max_read_rate must be between 0 and 1".into(),
         ));
     }
+    let protected = protected::load_protected(&state.protected_senders_path);
+    let activity = newsletters::load(&*state.store, &account);
+    let found = newsletters::suggestions(&activity, min_received, max_read_rate, |sender| {
+        protected::is_protected(&protected, sender)
+    });
+    Ok(Json(serde_json::json!({ "suggestions": found })))
+}
 
-    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
-    let mut session = session_lock.write().await;
-    let from_addr = body
-        .from_address
-        .clone()
-        .unwrap_or_else(|| session.username().to_string());
-
-    let organizer_email = from_addr.clone();
-    let attendees: Vec<Attendee> = body
-        .attendees
-        .iter()
-        .map(|a| Attendee {
-            email: a.email.clone(),
-            name: a.name.clone(),
-            status: "NEEDS-ACTION".into(),
+/// Unsubscribe from the email's list, then archive everything from its
+/// sender. The unsubscribe is best-effort and comes first (see
+/// `unsubscribe`): one-click POST, a `mailto:` message from the identity
+/// the list writes to, or `unsubscribeUrl` for the UI to open when there
+/// is only a web page or the attempt failed. `unsubscribeMethod` says
+/// which, `unsubscribeError` why it didn't work; the archive happens
+/// either way.
+async fn unsubscribe_and_archive(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    // Identities before the read lock below: a cache miss fetches under
+    // the session write lock.
+    let identities = state
+        .prefetch
+        .identities_or_fetch(&id, || async {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            let mut session = session_lock.write().await;
+            provider::get_identities(&mut session).await
         })
-        .collect();
+        .await
+        .unwrap_or_default();
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
 
-    let ics = calendar::generate_invite(
-        &organizer_email,
-        None,
-        &body.summary,
-        body.description.as_deref(),
-        body.location.as_deref(),
-        dtstart,
-        dtend,
-        &attendees,
+    // Get the email to find the sender
+    let emails = provider::get_emails(
+        &session,
+        std::slice::from_ref(&email_id),
+        true,
         None,
-    );
+        true, // user-blocking: unsubscribe click
+    )
+    .await?;
+    let email = emails
+        .first()
+        .ok_or_else(|| Error::NotFound("Email not found".into()))?;
 
-    let submission = EmailSubmission {
-        to: body.to,
-        cc: body.cc,
-        subject: body.subject,
-        text_body: body.body,
-        bcc: if body.bcc.is_empty() {
-            None
-        } else {
-            Some(body.bcc)
-        },
-        html_body: None,
-        in_reply_to: None,
-        references: None,
-        attachments: body.attachments,
-        calendar_ics: Some(ics),
+    let sender_email = email
+        .from
+        .first()
+        .map(|a| a.email.clone())
+        .unwrap_or_default();
+
+    if sender_email.is_empty() {
+        return Err(Error::BadRequest("No sender found".into()));
+    }
+
+    let headers = provider::get_headers(&session, &email_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("[{id}] unsubscribe: headers unavailable for {email_id}: {e}");
+            Vec::new()
+        });
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
     };
+    let action = email
+        .list_unsubscribe
+        .as_deref()
+        .or(header("List-Unsubscribe"))
+        .and_then(|h| unsubscribe::choose(h, header("List-Unsubscribe-Post")));
+    let from_addr =
+        crate::identities::select_reply_identity(&identities, email, &headers, &[], None)
+            .map(|(identity, _)| identity.email.clone())
+            .unwrap_or_else(|| session.username().to_string());
+    drop(session);
 
-    let result = provider::send_email(&mut session, &submission, &from_addr, None).await?;
-    match result {
-        Some(id) => Ok(Json(serde_json::json!({"success": true, "emailId": id}))),
-        None => Err(Error::Internal("Failed to send invite".into())),
+    let mut unsubscribe_url = None;
+    let mut unsubscribe_error = None;
+    match &action {
+        Some(unsubscribe::Action::Browser(url)) => unsubscribe_url = Some(url.clone()),
+        Some(action) => {
+            if let Err(e) = perform_unsubscribe(&session_lock, action, &from_addr).await {
+                tracing::warn!(
+                    "[{id}] {} unsubscribe from {sender_email} failed: {e}",
+                    action.method()
+                );
+                unsubscribe_error = Some(e.to_string());
+                if let unsubscribe::Action::OneClick(url) = action {
+                    unsubscribe_url = Some(url.to_string());
+                }
+            }
+        }
+        None => {}
+    }
+
+    let session = session_lock.read().await;
+    // Query all emails from this sender using structured filter (not string interpolation)
+    let query = crate::types::ParsedQuery {
+        from: vec![sender_email.clone()],
+        ..Default::default()
+    };
+    // Order doesn't matter here — every match gets archived regardless of
+    // the sequence they're fetched in — so the default is fine.
+    let all_ids =
+        provider::query_emails(&session, None, 500, 0, Some(&query), EmailSort::default()).await?;
+
+    // Archive all, minus anything from a protected sender
+    let protected = protected::load_protected(&state.protected_senders_path);
+    let (archived, skipped) = archive_batch_unprotected(&session, &protected, &all_ids).await?;
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+    {
+        let _guard = state.newsletters_lock.lock().await;
+        let mut activity = newsletters::load(&*state.store, &id);
+        if activity.forget(&sender_email)
+            && let Err(e) = newsletters::save(&*state.store, &id, &activity)
+        {
+            tracing::warn!("[{id}] couldn't drop {sender_email} from newsletter activity: {e}");
+        }
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "archived": archived,
+        "archivedCount": archived,
+        "protected": skipped,
+        "sender": sender_email,
+        "unsubscribeMethod": action.as_ref().map(unsubscribe::Action::method),
+        "unsubscribeUrl": unsubscribe_url,
+        "unsubscribeError": unsubscribe_error,
+    })))
+}
+
+/// Carry out a one-click or `mailto:` unsubscribe. The message goes
+/// straight to the provider: it is the list's own address, so the undo
+/// window and send guards of `/api/emails/send` don't apply.
+async fn perform_unsubscribe(
+    session_lock: &SessionLock,
+    action: &unsubscribe::Action,
+    from_addr: &str,
+) -> Result<(), Error> {
+    match action {
+        unsubscribe::Action::OneClick(url) => unsubscribe::one_click(url).await,
+        unsubscribe::Action::Mailto(mailto) => {
+            let submission = EmailSubmission {
+                to: vec![mailto.to.clone()],
+                cc: vec![],
+                subject: mailto.subject.clone(),
+                text_body: mailto.body.clone(),
+                bcc: None,
+                html_body: None,
+                in_reply_to: None,
+                references: None,
+                attachments: vec![],
+                calendar_ics: None,
+                forward_of: None,
+            };
+            let mut session = session_lock.write().await;
+            provider::send_email(&mut session, &submission, from_addr, None)
+                .await?
+                .map(|_| ())
+                .ok_or_else(|| Error::Internal("unsubscribe message was not sent".into()))
+        }
+        unsubscribe::Action::Browser(_) => Ok(()),
+    }
+}
+
+// =============================================================================
+// Splits CRUD
+//
+// Definitions live in the single ~/.config/supervillain/splits.json (or
+// splits.toml beside it, which takes precedence when present), but
+// each split may be tagged with an owning account. Reads (`list_splits`)
+// scope to ?account=; writes validate the tag against the registry.
+// `/api/split-counts` and `/api/emails?split_id=` scope to the resolved
+// account before counting/filtering.
+// =============================================================================
+
+/// splits.json is an input to the per-account split-counts cache; any
+/// write must leave no window where disk and cache disagree. Splits
+/// writes are rare, so invalidating every account's entry is fine.
+/// Subscribers to `/api/events` are told only after the invalidation, so
+/// the refetch they trigger can't read a stale count. Shared with the
+/// splits file watcher.
+pub(crate) async fn splits_changed(state: &AppState) {
+    let ids: Vec<String> = {
+        let reg = state.accounts.read().await;
+        reg.account_configs.keys().cloned().collect()
+    };
+    for id in &ids {
+        state.prefetch.invalidate_split_counts(id).await;
+    }
+    state.splits.publish();
+}
+
+#[derive(Deserialize)]
+struct SplitCountsParams {
+    mailbox_id: String,
+    account: Option<String>,
+    starred: Option<bool>,
+}
+
+/// Per-split counts for a mailbox, served from the prefetch cache as soon
+/// as any value exists (see `prefetch::CachedSplitCounts`) and refreshed in
+/// the background when invalidated or older than
+/// `prefetch::SPLIT_COUNTS_REFRESH_AFTER_SECS`.
+async fn split_counts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SplitCountsParams>,
+) -> Result<impl IntoResponse, Error> {
+    let start = std::time::Instant::now();
+
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let matchers = Arc::new(state.splits.compiled().scoped_to(Some(&account_id)));
+    if matchers.is_empty() {
+        return Ok(Json(split_counts_json(HashMap::new(), 0, false)));
+    }
+
+    // Default-view counts (no starred filter) go through the prefetch
+    // cache; the starred-filter variant is per-session ephemeral and
+    // bypasses the cache so it doesn't pollute the steady-state entry.
+    let is_cacheable = params.starred != Some(true);
+
+    let body = if is_cacheable {
+        let cached = state
+            .prefetch
+            .cached_split_counts(&account_id, &params.mailbox_id)
+            .await;
+        match cached {
+            // Any last-known value answers instantly. Invalidated or aged
+            // counts are recomputed behind the response; `refreshing`
+            // tells the UI to re-poll for the fresh numbers.
+            Some(c) => {
+                let stale_seconds = c.stale_seconds(chrono::Utc::now());
+                let refreshing =
+                    c.invalidated || stale_seconds > prefetch::SPLIT_COUNTS_REFRESH_AFTER_SECS;
+                if refreshing {
+                    prefetch::spawn_split_counts_refresh(
+                        state.clone(),
+                        account_id.clone(),
+                        params.mailbox_id.clone(),
+                    );
+                }
+                split_counts_json(c.counts, stale_seconds, refreshing)
+            }
+            // Cold: nothing to serve yet, so compute inline once.
+            None => {
+                let id = account_id.clone();
+                let mbox_for_key = params.mailbox_id.clone();
+                let mbox_for_fetch = params.mailbox_id.clone();
+                let cfg = matchers.clone();
+                let state_for_fetch = state.clone();
+                let acct_for_fetch = params.account.clone();
+                let counts = state
+                    .prefetch
+                    .split_counts_or_fetch(&id, &mbox_for_key, || async move {
+                        compute_split_counts(
+                            &state_for_fetch,
+                            acct_for_fetch.as_deref(),
+                            &mbox_for_fetch,
+                            &cfg,
+                            None,
+                        )
+                        .await
+                    })
+                    .await?;
+                split_counts_json(counts, 0, false)
+            }
+        }
+    } else {
+        let query = crate::types::ParsedQuery {
+            is_flagged: Some(true),
+            ..Default::default()
+        };
+        let counts = compute_split_counts(
+            &state,
+            params.account.as_deref(),
+            &params.mailbox_id,
+            &matchers,
+            Some(query),
+        )
+        .await?;
+        split_counts_json(counts, 0, false)
+    };
+
+    tracing::debug!(
+        "split-counts: {} splits, {:.0}ms",
+        body["counts"].as_object().map_or(0, |c| c.len()),
+        start.elapsed().as_millis()
+    );
+
+    Ok(Json(body))
+}
+
+/// `/api/split-counts` body: the per-split counts plus how old they are.
+/// `staleSeconds` is 0 for counts computed by this request.
+fn split_counts_json(
+    counts: HashMap<String, u32>,
+    stale_seconds: i64,
+    refreshing: bool,
+) -> serde_json::Value {
+    serde_json::json!({
+        "counts": counts,
+        "staleSeconds": stale_seconds,
+        "refreshing": refreshing,
+    })
+}
+
+/// Shared splits-counting implementation used by the `/api/split-counts`
+/// handler *and* the prefetch warmer. Drift between the two would mean
+/// the cached value the warmer wrote was computed from a different
+/// sample size than the route would have produced on a miss, so the
+/// user-facing count would flip every time the cache invalidated. One
+/// function, one constant.
+pub(crate) async fn compute_split_counts(
+    state: &AppState,
+    account: Option<&str>,
+    mailbox_id: &str,
+    matchers: &splits::CompiledSplits,
+    query: Option<crate::types::ParsedQuery>,
+) -> Result<HashMap<String, u32>, Error> {
+    let account_key = resolve_account_id(state, account).await?;
+    let session_lock = resolve_session(state, Some(&account_key)).await?;
+
+    let fetch_limit = DEFAULT_INBOX_LIMIT * SPLIT_OVERFETCH_MULTIPLIER;
+    // Split counts are order-independent (just counting matches), so the
+    // default sort is fine here regardless of the user's list sort choice.
+    let email_ids = {
+        let session = session_lock.read().await;
+        provider::query_emails(
+            &session,
+            Some(mailbox_id),
+            fetch_limit,
+            0,
+            query.as_ref(),
+            EmailSort::default(),
+        )
+        .await?
+    };
+
+    // The projection never changes for an email id, so ids counted before
+    // come from `email_cache` and only new mail is fetched.
+    let mut all_emails: Vec<Email> = state
+        .email_cache
+        .get_many(&account_key, cache::Kind::SplitFields, &email_ids)
+        .into_values()
+        .collect();
+    let cached: HashSet<&str> = all_emails.iter().map(|e| e.id.as_str()).collect();
+    let missing: Vec<String> = email_ids
+        .iter()
+        .filter(|id| !cached.contains(id.as_str()))
+        .cloned()
+        .collect();
+
+    // This is the single longest provider fan-out in the app (~1500 gets,
+    // minutes on a rate-limited Gmail account), so releasing the session
+    // guard between chunks matters most here: a send queued behind one
+    // monolithic guard used to stall until the whole sample finished.
+    let minimal_props: &[&str] = &["id", "from", "to", "cc", "subject"];
+    let fetched = provider::get_emails_chunked(
+        &session_lock,
+        &missing,
+        false,
+        Some(minimal_props),
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+    state
+        .email_cache
+        .put_many(&account_key, cache::Kind::SplitFields, &fetched);
+    all_emails.extend(fetched);
+
+    Ok(matchers.counts(&all_emails))
+}
+
+#[derive(Deserialize)]
+struct ListSplitsParams {
+    account: Option<String>,
+}
+
+async fn list_splits(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListSplitsParams>,
+) -> Result<impl IntoResponse, Error> {
+    // No ?account= → full list (management/debugging view). The UI always
+    // sends the active account via the api() helper. When an account IS
+    // given, validate it — a typo would otherwise silently compute against
+    // the untagged-only split list instead of 400ing.
+    if let Some(ref acct) = params.account {
+        let reg = state.accounts.read().await;
+        ensure_known_account(&reg, acct)?;
+    }
+
+    let config = state.splits.current().as_ref().clone();
+    Ok(Json(serde_json::json!(
+        config.scoped_to(params.account.as_deref()).splits
+    )))
+}
+
+async fn create_split(
+    State(state): State<Arc<AppState>>,
+    Json(new_split): Json<SplitInbox>,
+) -> Result<impl IntoResponse, Error> {
+    let mut config = state.splits.load_fresh();
+
+    splits::validate_split(&new_split).map_err(Error::Unprocessable)?;
+
+    // Check for duplicate ID
+    if config.splits.iter().any(|s| s.id == new_split.id) {
+        return Err(Error::BadRequest(format!(
+            "Split with id '{}' already exists",
+            new_split.id
+        )));
+    }
+
+    // Reject typos early: a split tagged to an unknown account would
+    // silently never render anywhere.
+    if let Some(ref acct) = new_split.account {
+        let reg = state.accounts.read().await;
+        ensure_known_account(&reg, acct)?;
+    }
+
+    config.splits.push(new_split);
+    state.splits.save(&config)?;
+    splits_changed(&state).await;
+
+    Ok(Json(serde_json::json!(config.splits)))
+}
+
+async fn update_split(
+    State(state): State<Arc<AppState>>,
+    Path(split_id): Path<String>,
+    Json(updated): Json<SplitInbox>,
+) -> Result<impl IntoResponse, Error> {
+    let mut config = state.splits.load_fresh();
+
+    // Reject typos early: a split tagged to an unknown account would
+    // silently never render anywhere.
+    if let Some(ref acct) = updated.account {
+        let reg = state.accounts.read().await;
+        ensure_known_account(&reg, acct)?;
+    }
+
+    if updated.id != split_id {
+        return Err(Error::BadRequest(format!(
+            "Split id is immutable ('{split_id}' != '{}')",
+            updated.id
+        )));
+    }
+    splits::validate_split(&updated).map_err(Error::Unprocessable)?;
+
+    let existing = config
+        .splits
+        .iter_mut()
+        .find(|s| s.id == split_id)
+        .ok_or_else(|| Error::NotFound(format!("Split '{split_id}' not found")))?;
+
+    // PUT replaces the whole split: a body without `account` UNTAGS it
+    // (makes it global). Deliberate — the body is the full new state.
+    // Keys the UI doesn't know about (hand-added in the config file) are
+    // the exception: it can't send what it never models, so keep them.
+    let mut updated = updated;
+    for (key, value) in std::mem::take(&mut existing.extra) {
+        updated.extra.entry(key).or_insert(value);
+    }
+    *existing = updated;
+    state.splits.save(&config)?;
+    splits_changed(&state).await;
+
+    Ok(Json(serde_json::json!(config.splits)))
+}
+
+async fn delete_split(
+    State(state): State<Arc<AppState>>,
+    Path(split_id): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let mut config = state.splits.load_fresh();
+
+    let original_len = config.splits.len();
+    config.splits.retain(|s| s.id != split_id);
+
+    if config.splits.len() == original_len {
+        return Err(Error::NotFound(format!("Split '{split_id}' not found")));
+    }
+
+    state.splits.save(&config)?;
+    splits_changed(&state).await;
+
+    Ok(Json(serde_json::json!(config.splits)))
+}
+
+/// How many matching messages a split preview returns.
+const SPLIT_PREVIEW_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+struct SplitPreviewParams {
+    account: Option<String>,
+    /// Defaults to the account's inbox.
+    mailbox_id: Option<String>,
+}
+
+/// Scale the matches found in the newest `sampled` messages up to the
+/// whole mailbox. Exact when the sample covered everything.
+fn estimate_split_total(matched: usize, sampled: usize, mailbox_total: usize) -> usize {
+    if sampled == 0 || mailbox_total <= sampled {
+        return matched;
+    }
+    ((matched as f64) * (mailbox_total as f64) / (sampled as f64)).round() as usize
+}
+
+/// Dry-run an unsaved split definition: the newest matching messages and
+/// an estimated match count, from the same sample `/api/split-counts`
+/// uses. Nothing is persisted or cached.
+async fn preview_split(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SplitPreviewParams>,
+    Json(split): Json<SplitInbox>,
+) -> Result<impl IntoResponse, Error> {
+    if split.filters.is_empty() {
+        return Err(Error::BadRequest("Split has no filters".into()));
+    }
+    splits::validate_split(&split).map_err(Error::Unprocessable)?;
+    // A split tagged to an account previews against that account unless
+    // the caller picks one explicitly.
+    let account = params.account.as_deref().or(split.account.as_deref());
+    let account_id = resolve_account_id(&state, account).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+
+    let mailboxes = match state.prefetch.get_mailboxes(&account_id).await {
+        Some(m) => m,
+        None => provider::get_mailboxes(&*session_lock.read().await).await?,
+    };
+    let mailbox = match params.mailbox_id.as_deref() {
+        Some(id) => mailboxes.iter().find(|m| m.id == id),
+        None => mailboxes
+            .iter()
+            .find(|m| m.role.as_deref() == Some("inbox")),
+    }
+    .ok_or_else(|| Error::NotFound("Mailbox not found".into()))?;
+
+    let sample_size = DEFAULT_INBOX_LIMIT * SPLIT_OVERFETCH_MULTIPLIER;
+    let email_ids = {
+        let session = session_lock.read().await;
+        provider::query_emails(
+            &session,
+            Some(&mailbox.id),
+            sample_size,
+            0,
+            None,
+            EmailSort::default(),
+        )
+        .await?
+    };
+    let sample = provider::get_emails_chunked(
+        &session_lock,
+        &email_ids,
+        false,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+
+    let matcher = splits::CompiledSplit::new(&split);
+    let matched: Vec<&Email> = sample.iter().filter(|e| matcher.matches(e)).collect();
+    let mailbox_total = usize::try_from(mailbox.total_emails).unwrap_or(0);
+    let names = contact_names(&state, &account_id);
+    let emails: Vec<serde_json::Value> = matched
+        .iter()
+        .take(SPLIT_PREVIEW_LIMIT)
+        .map(|e| email_summary_json(e, &names))
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "account": account_id,
+        "mailboxId": mailbox.id,
+        "emails": emails,
+        "matched": matched.len(),
+        "sampled": sample.len(),
+        "mailboxTotal": mailbox_total,
+        "estimatedCount": estimate_split_total(matched.len(), sample.len(), mailbox_total),
+    })))
+}
+
+/// Default and largest look-back for `/api/rules/simulate`.
+const RULE_SIMULATION_DEFAULT_DAYS: u32 = 30;
+const RULE_SIMULATION_MAX_DAYS: u32 = 365;
+
+/// Most messages one simulation reads; past it the result is `truncated`.
+const RULE_SIMULATION_MAX_MESSAGES: usize = 1000;
+
+#[derive(Deserialize)]
+struct RuleSimulationParams {
+    account: Option<String>,
+    /// Defaults to the account's inbox.
+    mailbox_id: Option<String>,
+    days: Option<u32>,
+}
+
+/// Dry-run a rule over the last `days` of a mailbox: for each action, the
+/// messages it would change. Read-only.
+async fn simulate_rule(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RuleSimulationParams>,
+    Json(rule): Json<rules::Rule>,
+) -> Result<impl IntoResponse, Error> {
+    rules::validate(&rule).map_err(Error::Unprocessable)?;
+    let days = params.days.unwrap_or(RULE_SIMULATION_DEFAULT_DAYS);
+    if !(1..=RULE_SIMULATION_MAX_DAYS).contains(&days) {
+        return Err(Error::BadRequest(format!(
+            "days must be between 1 and {RULE_SIMULATION_MAX_DAYS}"
+        )));
+    }
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+
+    let mailboxes = match state.prefetch.get_mailboxes(&account_id).await {
+        Some(m) => m,
+        None => provider::get_mailboxes(&*session_lock.read().await).await?,
+    };
+    let mailbox = match params.mailbox_id.as_deref() {
+        Some(id) => mailboxes.iter().find(|m| m.id == id),
+        None => mailboxes
+            .iter()
+            .find(|m| m.role.as_deref() == Some("inbox")),
+    }
+    .ok_or_else(|| Error::NotFound("Mailbox not found".into()))?;
+
+    let since = chrono::Utc::now().date_naive() - chrono::Days::new(u64::from(days));
+    let query = ParsedQuery {
+        after: Some(since),
+        ..Default::default()
+    };
+    let email_ids = {
+        let session = session_lock.read().await;
+        provider::query_emails(
+            &session,
+            Some(&mailbox.id),
+            RULE_SIMULATION_MAX_MESSAGES,
+            0,
+            Some(&query),
+            EmailSort::default(),
+        )
+        .await?
+    };
+    let emails = provider::get_emails_chunked(
+        &session_lock,
+        &email_ids,
+        false,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+
+    let protected = protected::load_protected(&state.protected_senders_path);
+    let simulation = rules::simulate(&rule, &emails, &protected);
+    let names = contact_names(&state, &account_id);
+    let summaries = |list: &[&Email]| -> Vec<serde_json::Value> {
+        list.iter().map(|e| email_summary_json(e, &names)).collect()
+    };
+    let groups: Vec<serde_json::Value> = simulation
+        .groups
+        .iter()
+        .map(|g| {
+            serde_json::json!({
+                "action": g.action,
+                "count": g.emails.len(),
+                "emails": summaries(&g.emails),
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "account": account_id,
+        "mailboxId": mailbox.id,
+        "days": days,
+        "scanned": emails.len(),
+        "truncated": email_ids.len() >= RULE_SIMULATION_MAX_MESSAGES,
+        "matched": simulation.matched,
+        "groups": groups,
+        "protected": summaries(&simulation.protected),
+    })))
+}
+
+/// `GET /api/sieve`: the account's active Sieve script as
+/// `{id, name, content}`; all empty when none is active.
+async fn get_sieve(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let script = provider::get_sieve_script(&session)
+        .await?
+        .unwrap_or_default();
+    Ok(Json(serde_json::json!(script)))
+}
+
+#[derive(Deserialize)]
+struct SieveBody {
+    content: String,
+}
+
+/// `PUT /api/sieve`: replace the active script's content, creating and
+/// activating a script when there is none. Returns `{id}`.
+async fn put_sieve(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<SieveBody>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let active = provider::get_sieve_script(&session).await?;
+    let script_id = active.as_ref().and_then(|s| s.id.as_deref());
+    let script_id = provider::put_sieve_script(&session, script_id, &body.content).await?;
+    Ok(Json(serde_json::json!({ "id": script_id })))
+}
+
+#[derive(Deserialize)]
+struct SieveFromSplitBody {
+    mailbox_id: String,
+}
+
+/// `POST /api/sieve/from-split/{split_id}`: the split as a Sieve rule
+/// filing into `mailbox_id`, as `{require, rule, script}`. Saves nothing.
+async fn sieve_from_split(
+    State(state): State<Arc<AppState>>,
+    Path(split_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<SieveFromSplitBody>,
+) -> Result<impl IntoResponse, Error> {
+    let config = state.splits.current();
+    let split = config
+        .splits
+        .iter()
+        .find(|s| s.id == split_id)
+        .ok_or_else(|| Error::NotFound(format!("Split '{split_id}' not found")))?;
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let mailboxes = match state.prefetch.get_mailboxes(&id).await {
+        Some(m) => m,
+        None => {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            provider::get_mailboxes(&*session_lock.read().await).await?
+        }
+    };
+    let mailbox = mailboxes
+        .iter()
+        .find(|m| m.id == body.mailbox_id)
+        .ok_or_else(|| Error::NotFound(format!("Mailbox {} not found", body.mailbox_id)))?;
+    let rule =
+        sieve::split_rule(split, &mailbox.id, &mailbox.name).map_err(Error::Unprocessable)?;
+    Ok(Json(serde_json::json!({
+        "require": rule.require,
+        "rule": rule.rule,
+        "script": rule.script(),
+    })))
+}
+
+#[derive(Deserialize)]
+struct SplitsFormatParams {
+    format: Option<String>,
+}
+
+fn parse_splits_format(format: Option<&str>) -> Result<splits::SplitsFormat, Error> {
+    match format.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("json") => Ok(splits::SplitsFormat::Json),
+        Some("toml") => Ok(splits::SplitsFormat::Toml),
+        Some(other) => Err(Error::BadRequest(format!(
+            "Unknown splits format '{other}' (expected json or toml)"
+        ))),
+    }
+}
+
+/// The full config (every account's splits) as a download. Exporting the
+/// format the config is already kept in returns the file verbatim,
+/// comments included.
+async fn export_splits(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SplitsFormatParams>,
+) -> Result<impl IntoResponse, Error> {
+    let format = parse_splits_format(params.format.as_deref())?;
+    let active = splits::resolve_config_path(state.splits.path());
+    let content = match std::fs::read_to_string(&active) {
+        Ok(raw) if splits::SplitsFormat::from_path(&active) == format => raw,
+        _ => splits::render(&state.splits.load_fresh(), format)?,
+    };
+    let (content_type, filename) = match format {
+        splits::SplitsFormat::Json => ("application/json", "splits.json"),
+        splits::SplitsFormat::Toml => ("application/toml", "splits.toml"),
+    };
+    Ok((
+        [
+            ("content-type", content_type.to_string()),
+            (
+                "content-disposition",
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        content,
+    ))
+}
+
+/// Replace the whole config with an uploaded JSON or TOML document.
+/// Importing TOML makes `splits.toml` the primary config and keeps the
+/// upload's comments; importing JSON is written in whatever format the
+/// config is already kept in.
+async fn import_splits(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SplitsFormatParams>,
+    body: String,
+) -> Result<impl IntoResponse, Error> {
+    let format = parse_splits_format(params.format.as_deref())?;
+    let config = format.parse(&body).map_err(Error::BadRequest)?;
+
+    let mut ids = std::collections::HashSet::new();
+    if let Some(dup) = config.splits.iter().find(|s| !ids.insert(s.id.as_str())) {
+        return Err(Error::BadRequest(format!(
+            "Split with id '{}' appears more than once",
+            dup.id
+        )));
+    }
+    for split in &config.splits {
+        splits::validate_split(split).map_err(Error::Unprocessable)?;
+    }
+
+    match format {
+        splits::SplitsFormat::Toml => state.splits.import_toml(&body, config.clone())?,
+        splits::SplitsFormat::Json => state.splits.save(&config)?,
+    }
+    splits_changed(&state).await;
+
+    Ok(Json(serde_json::json!(config.splits)))
+}
+
+// =============================================================================
+// Server-sent events
+// =============================================================================
+
+/// `splits` event payload: the client refetches `/api/splits` and the
+/// counts on receipt.
+fn splits_event(version: u64) -> axum::response::sse::Event {
+    axum::response::sse::Event::default()
+        .event("splits")
+        .data(serde_json::json!({ "version": version }).to_string())
+}
+
+/// `otp` event payload: just the code and who sent it, enough to fill a
+/// 2FA prompt. `/api/otp/latest` has the rest.
+fn otp_event(code: &otp::OtpCode) -> axum::response::sse::Event {
+    axum::response::sse::Event::default()
+        .event("otp")
+        .data(serde_json::json!({ "code": code.code, "sender": code.sender }).to_string())
+}
+
+/// `compose` event payload: which session changed and its new version.
+fn compose_event(event: &compose_sessions::ComposeEvent) -> axum::response::sse::Event {
+    axum::response::sse::Event::default()
+        .event("compose")
+        .data(serde_json::json!(event).to_string())
+}
+
+/// Push notifications for state the UI would otherwise have to poll for.
+/// A subscriber that falls behind on splits gets one catch-up event at the
+/// current version rather than the backlog it missed; missed codes are
+/// skipped, since only the newest is worth typing. A subscriber that
+/// misses compose changes gets a `compose` event with `resync: true` and
+/// should refetch `/api/compose-sessions`.
+async fn server_events(
+    State(state): State<Arc<AppState>>,
+) -> axum::response::sse::Sse<
+    impl futures_util::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>,
+> {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let rx = state.splits.subscribe();
+    let splits = futures_util::stream::unfold((rx, state.clone()), |(mut rx, state)| async move {
+        let version = match rx.recv().await {
+            Ok(version) => version,
+            Err(RecvError::Lagged(_)) => state.splits.version(),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(splits_event(version)), (rx, state)))
+    });
+    let codes = futures_util::stream::unfold(state.otp.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(code) => return Some((Ok(otp_event(&code)), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let compose =
+        futures_util::stream::unfold(state.compose_sessions.subscribe(), |mut rx| async move {
+            let event = match rx.recv().await {
+                Ok(event) => compose_event(&event),
+                Err(RecvError::Lagged(_)) => axum::response::sse::Event::default()
+                    .event("compose")
+                    .data(serde_json::json!({ "resync": true }).to_string()),
+                Err(RecvError::Closed) => return None,
+            };
+            Some((Ok(event), rx))
+        });
+    let stream = futures_util::stream::select(futures_util::stream::select(splits, codes), compose);
+    axum::response::sse::Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
+/// Lines `/api/logs/tail` replays before following, unless `backlog` says.
+const LOG_TAIL_DEFAULT_BACKLOG: usize = 200;
+
+#[derive(Deserialize)]
+struct LogTailParams {
+    /// `error`, `warn`, `info` (default) or `debug`.
+    level: Option<String>,
+    /// How many buffered lines to send first, up to `logs::BACKLOG`.
+    backlog: Option<usize>,
+}
+
+/// `log` event payload: one redacted `logs::LogLine`.
+fn log_event(line: &logs::LogLine) -> axum::response::sse::Event {
+    axum::response::sse::Event::default()
+        .event("log")
+        .data(serde_json::json!(line).to_string())
+}
+
+/// Stream tracing events at `level` or more severe: the most recent
+/// buffered ones first, then live. A subscriber that falls behind gets a
+/// `lagged` event with how many lines it missed.
+async fn logs_tail(
+    Query(params): Query<LogTailParams>,
+) -> Result<
+    axum::response::sse::Sse<
+        impl futures_util::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>,
+    >,
+    Error,
+> {
+    use futures_util::StreamExt;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let level = match params.level.as_deref() {
+        None => tracing::Level::INFO,
+        Some(level) => level
+            .parse()
+            .map_err(|_| Error::BadRequest(format!("Unknown log level: {level}")))?,
+    };
+    let (recent, rx) = logs::LogTail::global().subscribe();
+    let recent: Vec<logs::LogLine> = recent.into_iter().filter(|l| l.at_least(level)).collect();
+    let skip = recent.len().saturating_sub(
+        params
+            .backlog
+            .unwrap_or(LOG_TAIL_DEFAULT_BACKLOG)
+            .min(logs::BACKLOG),
+    );
+    let replay = futures_util::stream::iter(
+        recent
+            .into_iter()
+            .skip(skip)
+            .map(|line| Ok(log_event(&line))),
+    );
+    let live = futures_util::stream::unfold(rx, move |mut rx| async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(line) if line.at_least(level) => log_event(&line),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => axum::response::sse::Event::default()
+                    .event("lagged")
+                    .data(serde_json::json!({ "skipped": skipped }).to_string()),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), rx));
+        }
+    });
+    Ok(axum::response::sse::Sse::new(replay.chain(live))
+        .keep_alive(axum::response::sse::KeepAlive::default()))
+}
+
+/// `GET /api/diagnostics`: a redacted bundle to attach to a bug report
+/// (see `diagnostics`), as a JSON download.
+async fn diagnostics_bundle(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let now = chrono::Utc::now();
+    let (config, sessions) = {
+        let reg = state.accounts.read().await;
+        let sessions: Vec<(String, SessionLock)> = reg
+            .sessions
+            .iter()
+            .map(|(name, lock)| (name.clone(), lock.clone()))
+            .collect();
+        (reg.snapshot(), sessions)
+    };
+    let errors = state.account_errors.read().await.clone();
+    let mut accounts = Vec::new();
+    for (name, lock) in sessions {
+        let session = lock.read().await;
+        accounts.push(diagnostics::AccountReport {
+            name,
+            provider: session.provider_name().to_string(),
+            connected: true,
+            capabilities: session.capabilities().to_vec(),
+            error: None,
+        });
+    }
+    accounts.extend(errors.into_iter().map(|e| diagnostics::AccountReport {
+        name: e.account,
+        provider: e.provider,
+        connected: false,
+        capabilities: Vec::new(),
+        error: Some(logs::redact(&e.error)),
+    }));
+    accounts.sort_by(|a, b| a.name.cmp(&b.name));
+    let bundle = diagnostics::Bundle {
+        generated_at: now,
+        version: diagnostics::VersionInfo::current(),
+        config: diagnostics::redacted_config(&config),
+        accounts,
+        tasks: diagnostics::TaskHealth::global().snapshot(now),
+        recent_errors: diagnostics::recent_errors(logs::LogTail::global().recent()),
+    };
+    (
+        StatusCode::OK,
+        [
+            ("content-type", "application/json".to_string()),
+            (
+                "content-disposition",
+                downloads::content_disposition(&diagnostics::filename(now)),
+            ),
+            ("x-content-type-options", "nosniff".to_string()),
+        ],
+        serde_json::to_string_pretty(&bundle).unwrap_or_default(),
+    )
+}
+
+/// The newest one-time code from the last ten minutes of mail; 404 when
+/// there isn't one.
+async fn latest_otp(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    state
+        .otp
+        .latest(params.account.as_deref(), chrono::Utc::now())
+        .map(Json)
+        .ok_or_else(|| Error::NotFound("No recent one-time code".into()))
+}
+
+// =============================================================================
+// Permalink resolution
+// =============================================================================
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    message_id: String,
+    account: Option<String>,
+}
+
+/// Map a Message-ID header value to an email id so links from other tools
+/// can open the right message. Searches the given account, or every
+/// connected account (default first) when none is named; a provider error
+/// on one account doesn't stop the search of the rest.
+async fn resolve_message_id(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ResolveParams>,
+) -> Result<impl IntoResponse, Error> {
+    let message_id = provider_utils::normalize_message_id(&params.message_id)?;
+
+    let candidates: Vec<String> = match params.account.as_deref() {
+        Some(account) => vec![resolve_account_id(&state, Some(account)).await?],
+        None => {
+            let reg = state.accounts.read().await;
+            let mut ids: Vec<String> = reg.sessions.keys().cloned().collect();
+            ids.sort_by_key(|id| (*id != reg.default_account, id.clone()));
+            ids
+        }
+    };
+
+    for account in candidates {
+        let session_lock = resolve_session(&state, Some(&account)).await?;
+        let session = session_lock.read().await;
+        match provider::find_by_message_id(&session, &message_id).await {
+            Ok(Some(email_id)) => {
+                let permalink = format!(
+                    "/m/{}?account={}",
+                    provider_utils::encode_path_segment(&email_id),
+                    provider_utils::encode_path_segment(&account)
+                );
+                return Ok(Json(serde_json::json!({
+                    "account": account,
+                    "emailId": email_id,
+                    "permalink": permalink,
+                })));
+            }
+            Ok(None) => {}
+            Err(e) if params.account.is_some() => return Err(e),
+            Err(e) => tracing::warn!("Message-ID lookup failed on {account}: {e}"),
+        }
+    }
+    Err(Error::NotFound(format!(
+        "No message with Message-ID {message_id}"
+    )))
+}
+
+// =============================================================================
+// Change journal
+// =============================================================================
+
+/// Page size for `/api/changes`, and the most a caller may ask for —
+/// Gmail's `history.list` returns at most 500 records per call.
+const MAX_CHANGES: usize = 500;
+
+#[derive(Deserialize)]
+struct ChangesParams {
+    since: Option<String>,
+    account: Option<String>,
+    max_changes: Option<usize>,
+}
+
+/// Email ids created, updated and destroyed since `since`, for external
+/// tools mirroring a mailbox through this server. Without `since` it
+/// returns only the current state to start from; a `since` the provider
+/// can no longer compute from is a 409, meaning resync from scratch.
+async fn email_changes(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ChangesParams>,
+) -> Result<impl IntoResponse, Error> {
+    let max_changes = params.max_changes.unwrap_or(MAX_CHANGES);
+    if !(1..=MAX_CHANGES).contains(&max_changes) {
+        return Err(Error::BadRequest(format!(
+            "max_changes must be between 1 and {MAX_CHANGES}"
+        )));
+    }
+    if params.since.as_deref() == Some("") {
+        return Err(Error::BadRequest("since must not be empty".into()));
+    }
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let session = session_lock.read().await;
+    let changes = provider::email_changes(&session, params.since.as_deref(), max_changes).await?;
+    Ok(Json(changes))
+}
+
+// =============================================================================
+// Command registry
+// =============================================================================
+
+/// `/api/commands` query. `email_id` is the message the client has focused;
+/// without it only global commands are applicable.
+#[derive(Deserialize, Default)]
+struct CommandsParams {
+    account: Option<String>,
+    email_id: Option<String>,
+}
+
+/// The command registry (see `commands`) with keys from
+/// `keybindings.json` and applicability resolved against the focused
+/// message. The body fetch normally hits the prefetch cache — the client
+/// asks about the message it is already showing.
+async fn list_commands(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CommandsParams>,
+) -> Result<impl IntoResponse, Error> {
+    let email = match params.email_id {
+        None => None,
+        Some(email_id) => Some(focused_email(&state, params.account.as_deref(), &email_id).await?),
+    };
+    let ctx = commands::CommandContext::from_email(email.as_ref());
+    let keymap = commands::Keymap::load(&state.keybindings_path);
+    Ok(Json(
+        serde_json::json!({ "commands": commands::describe(&ctx, &keymap) }),
+    ))
+}
+
+/// Keyboard cheatsheet for the help overlay, from the command registry
+/// with the keys `keybindings.json` sets.
+async fn help(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let keymap = commands::Keymap::load(&state.keybindings_path);
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "sections": commands::help(&keymap),
+    }))
+}
+
+/// One email with bodies: the on-disk `email_cache` copy when there is
+/// one, else a provider fetch that is stored there.
+async fn fetch_full_email(
+    state: &AppState,
+    account: &str,
+    session: &provider::ProviderSession,
+    email_id: &str,
+    priority: bool,
+) -> Result<Email, Error> {
+    if let Some(email) = state.email_cache.get(account, cache::Kind::Full, email_id) {
+        return Ok(email);
+    }
+    let email = provider::get_emails(session, &[email_id.to_string()], true, None, priority)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NotFound("Email not found".into()))?;
+    state.email_cache.put(account, cache::Kind::Full, &email);
+    Ok(email)
+}
+
+/// The full email the client is focused on: the cached body when there is
+/// one (checked before taking a session, so a cache hit never waits on the
+/// provider), else a priority body fetch that also populates the cache.
+async fn focused_email(
+    state: &AppState,
+    account: Option<&str>,
+    email_id: &str,
+) -> Result<Email, Error> {
+    let account_key = resolve_account_id(state, account).await?;
+    if let Some(email) = state.prefetch.get_body(&account_key, email_id).await {
+        return Ok(email);
+    }
+    let session_lock = resolve_session(state, Some(&account_key)).await?;
+    let session = session_lock.read().await;
+    state
+        .prefetch
+        .body_or_fetch(&account_key, email_id, || {
+            fetch_full_email(state, &account_key, &session, email_id, true)
+        })
+        .await
+}
+
+// =============================================================================
+// Dashboard
+// =============================================================================
+
+/// Per-mailbox id window for the dashboard's week of mail. A busy week past
+/// this is reported as `truncated` rather than paged through — the widget
+/// wants a quick shape, not an exact census.
+const DASHBOARD_SCAN: usize = 500;
+const DASHBOARD_TOP_SENDERS: usize = 5;
+
+/// One call for a home-screen widget: unread per split, received/sent per
+/// day since Monday (in the configured timezone), this week's top senders,
+/// and provider queue health.
+async fn dashboard(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&account_id)).await?;
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    let start = week_start(chrono::Utc::now().with_timezone(&tz).date_naive());
+
+    let mailboxes = {
+        let session = session_lock.read().await;
+        provider::get_mailboxes(&session).await?
+    };
+    let by_role = |role: &str| mailboxes.iter().find(|m| m.role.as_deref() == Some(role));
+    let inbox = by_role("inbox");
+
+    let (received, received_truncated) = match inbox {
+        Some(m) => week_of_mail(&session_lock, &m.id, start).await?,
+        None => (Vec::new(), false),
+    };
+    let (sent, sent_truncated) = match by_role("sent") {
+        Some(m) => week_of_mail(&session_lock, &m.id, start).await?,
+        None => (Vec::new(), false),
+    };
+    let in_week = |e: &&Email| e.received_at.with_timezone(&tz).date_naive() >= start;
+    let received: Vec<Email> = received.into_iter().filter(|e| in_week(&e)).collect();
+    let sent: Vec<Email> = sent.into_iter().filter(|e| in_week(&e)).collect();
+
+    let matchers = state.splits.compiled().scoped_to(Some(&account_id));
+    let unread_by_split = match inbox {
+        Some(m) if !matchers.is_empty() => {
+            let unread = crate::types::ParsedQuery {
+                is_unread: Some(true),
+                ..Default::default()
+            };
+            compute_split_counts(&state, Some(&account_id), &m.id, &matchers, Some(unread)).await?
+        }
+        _ => HashMap::new(),
+    };
+
+    let queue = {
+        let session = session_lock.read().await;
+        let limiter = session.limiter();
+        let (in_flight, priority_in_flight) = limiter.in_flight();
+        serde_json::json!({
+            "provider": session.provider_name(),
+            "concurrency": limiter.concurrency(),
+            "inFlight": in_flight,
+            "priorityInFlight": priority_in_flight,
+        })
+    };
+    let account_error = state
+        .account_errors
+        .read()
+        .await
+        .iter()
+        .find(|e| e.account == account_id)
+        .map(|e| e.error.clone());
+
+    Ok(Json(serde_json::json!({
+        "account": account_id,
+        "weekStart": start,
+        "inboxUnread": inbox.map(|m| m.unread_emails).unwrap_or(0),
+        "unreadBySplit": unread_by_split,
+        "perDay": per_day_counts(&received, &sent, start, tz),
+        "topSenders": top_senders(&received, DASHBOARD_TOP_SENDERS),
+        "truncated": received_truncated || sent_truncated,
+        "queue": queue,
+        "accountError": account_error,
+    })))
+}
+
+/// Monday of `today`'s ISO week.
+fn week_start(today: chrono::NaiveDate) -> chrono::NaiveDate {
+    use chrono::Datelike;
+    today - chrono::Days::new(u64::from(today.weekday().num_days_from_monday()))
+}
+
+/// Lightweight rows for `mailbox_id` since `since`. The provider `after`
+/// filter is a UTC date, so the query starts a day early and the caller
+/// trims to the local week.
+async fn week_of_mail(
+    session_lock: &Arc<tokio::sync::RwLock<provider::ProviderSession>>,
+    mailbox_id: &str,
+    since: chrono::NaiveDate,
+) -> Result<(Vec<Email>, bool), Error> {
+    let query = crate::types::ParsedQuery {
+        after: since.pred_opt(),
+        ..Default::default()
+    };
+    let ids = {
+        let session = session_lock.read().await;
+        provider::query_emails(
+            &session,
+            Some(mailbox_id),
+            DASHBOARD_SCAN,
+            0,
+            Some(&query),
+            EmailSort::default(),
+        )
+        .await?
+    };
+    let truncated = ids.len() >= DASHBOARD_SCAN;
+    let emails = provider::get_emails_chunked(
+        session_lock,
+        &ids,
+        false,
+        Some(&["id", "from", "receivedAt"]),
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?;
+    Ok((emails, truncated))
+}
+
+/// Seven `{date, received, sent}` buckets starting at `start`, by local day.
+fn per_day_counts(
+    received: &[Email],
+    sent: &[Email],
+    start: chrono::NaiveDate,
+    tz: chrono_tz::Tz,
+) -> Vec<serde_json::Value> {
+    let count_on = |emails: &[Email], day: chrono::NaiveDate| {
+        emails
+            .iter()
+            .filter(|e| e.received_at.with_timezone(&tz).date_naive() == day)
+            .count()
+    };
+    (0..7)
+        .map(|offset| {
+            let day = start + chrono::Days::new(offset);
+            serde_json::json!({
+                "date": day,
+                "received": count_on(received, day),
+                "sent": count_on(sent, day),
+            })
+        })
+        .collect()
+}
+
+/// Most frequent `From` addresses, case-folded, busiest first (ties by
+/// address so the order is stable). The display name is the first one seen.
+fn top_senders(emails: &[Email], n: usize) -> Vec<serde_json::Value> {
+    let mut counts: HashMap<String, (usize, Option<String>)> = HashMap::new();
+    for addr in emails.iter().filter_map(|e| e.from.first()) {
+        let entry = counts
+            .entry(addr.email.to_ascii_lowercase())
+            .or_insert((0, addr.name.clone()));
+        entry.0 += 1;
+    }
+    let mut ranked: Vec<_> = counts.into_iter().collect();
+    ranked.sort_by(|(a, (ca, _)), (b, (cb, _))| cb.cmp(ca).then_with(|| a.cmp(b)));
+    ranked
+        .into_iter()
+        .take(n)
+        .map(|(email, (count, name))| {
+            serde_json::json!({ "email": email, "name": name, "count": count })
+        })
+        .collect()
+}
+
+// =============================================================================
+// Daily digest
+// =============================================================================
+
+/// Wall-clock time in the configured primary timezone.
+pub(crate) fn local_now(state: &AppState) -> chrono::NaiveDateTime {
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    chrono::Utc::now().with_timezone(&tz).naive_local()
+}
+
+/// Build today's digest for `config` and, unless `preview`, deliver it.
+/// "Today" is the local date; the provider date filter is UTC, so the query
+/// starts a day early and rows are trimmed locally, as on the dashboard.
+pub(crate) async fn run_digest(
+    state: &AppState,
+    config: &digest::DigestConfig,
+    preview: bool,
+) -> Result<digest::RenderedDigest, Error> {
+    let account_id = resolve_account_id(state, config.account.as_deref()).await?;
+    let session_lock = resolve_session(state, Some(&account_id)).await?;
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    let today = chrono::Utc::now().with_timezone(&tz).date_naive();
+
+    let mailboxes = {
+        let session = session_lock.read().await;
+        provider::get_mailboxes(&session).await?
+    };
+    let inbox = mailboxes
+        .iter()
+        .find(|m| m.role.as_deref() == Some("inbox"))
+        .ok_or_else(|| Error::NotFound("No inbox mailbox".into()))?;
+    let (emails, _) = week_of_mail(&session_lock, &inbox.id, today).await?;
+    let emails: Vec<Email> = emails
+        .into_iter()
+        .filter(|e| e.received_at.with_timezone(&tz).date_naive() == today)
+        .collect();
+
+    let split_names: HashMap<String, String> = state
+        .splits
+        .current()
+        .splits
+        .iter()
+        .map(|s| (s.id.clone(), s.name.clone()))
+        .collect();
+    let matchers = state.splits.compiled().scoped_to(Some(&account_id));
+    let sections: Vec<digest::DigestSection> = config
+        .splits
+        .iter()
+        .filter(|id| matchers.contains(id))
+        .map(|id| digest::DigestSection {
+            title: split_names.get(id).cloned().unwrap_or_else(|| id.clone()),
+            items: matchers
+                .filter(emails.clone(), id)
+                .into_iter()
+                .map(|e| digest::DigestItem {
+                    from: e
+                        .from
+                        .first()
+                        .map(|a| a.name.clone().unwrap_or_else(|| a.email.clone()))
+                        .unwrap_or_default(),
+                    subject: e.subject,
+                    preview: e.preview,
+                })
+                .collect(),
+        })
+        .collect();
+    let rendered = digest::render(today, &sections);
+    if preview {
+        return Ok(rendered);
+    }
+
+    let mut session = session_lock.write().await;
+    let me = session.username().to_string();
+    let submission = EmailSubmission {
+        to: vec![me.clone()],
+        cc: Vec::new(),
+        subject: rendered.subject.clone(),
+        text_body: rendered.text_body.clone(),
+        bcc: None,
+        html_body: Some(rendered.html_body.clone()),
+        in_reply_to: None,
+        references: None,
+        attachments: Vec::new(),
+        calendar_ics: None,
+        forward_of: None,
+    };
+    match config.delivery {
+        digest::DigestDelivery::Send => {
+            provider::send_email(&mut session, &submission, &me, None)
+                .await?
+                .ok_or_else(|| Error::Internal("Failed to send digest".into()))?;
+        }
+        digest::DigestDelivery::Draft => {
+            provider::create_draft(&session, &submission, &me).await?;
+        }
+    }
+    Ok(rendered)
+}
+
+async fn get_digest(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(digest::load_digest(&state.digest_config_path))
+}
+
+async fn put_digest(
+    State(state): State<Arc<AppState>>,
+    Json(mut config): Json<digest::DigestConfig>,
+) -> Result<impl IntoResponse, Error> {
+    digest::validate_digest(&config)?;
+    // The schedule's bookkeeping isn't a setting; keep the stored date so
+    // saving settings after today's digest doesn't send a second one.
+    config.last_sent = digest::load_digest(&state.digest_config_path).last_sent;
+    digest::save_digest(&config, &state.digest_config_path)?;
+    Ok(Json(config))
+}
+
+#[derive(Deserialize)]
+struct RunDigestParams {
+    #[serde(default)]
+    preview: bool,
+}
+
+/// Run the digest now, outside the schedule. `?preview=true` renders
+/// without delivering, for checking the split selection.
+async fn run_digest_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RunDigestParams>,
+) -> Result<impl IntoResponse, Error> {
+    let config = digest::load_digest(&state.digest_config_path);
+    if config.splits.is_empty() {
+        return Err(Error::BadRequest(
+            "No splits are included in the digest".into(),
+        ));
+    }
+    let rendered = run_digest(&state, &config, params.preview).await?;
+    Ok(Json(rendered))
+}
+
+// =============================================================================
+// Receipts
+// =============================================================================
+
+/// Newest inbox messages looked at per receipt scan.
+const RECEIPT_SCAN: usize = 200;
+/// How far back a receipt scan reaches, in days.
+const RECEIPT_SCAN_DAYS: u64 = 30;
+
+/// File receipts from `account_id`'s recent inbox: extract a record for
+/// each new one, tag it with the receipt keyword, and append it to
+/// `receipts.json`. Returns the newly filed records.
+///
+/// JMAP list metadata has no attachment list, so any message with an
+/// attachment is body-fetched too, to find invoice PDFs. The log is
+/// re-read right before the save; a record lost to a concurrent scan's
+/// write is simply filed again next time.
+pub(crate) async fn scan_receipts(
+    state: &AppState,
+    account_id: &str,
+) -> Result<Vec<receipts::ReceiptRecord>, Error> {
+    let session_lock = resolve_session(state, Some(account_id)).await?;
+    let tz = timezone::primary_tz(&timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    ));
+    let since =
+        chrono::Utc::now().with_timezone(&tz).date_naive() - chrono::Days::new(RECEIPT_SCAN_DAYS);
+
+    let ids = {
+        let session = session_lock.read().await;
+        let mailboxes = provider::get_mailboxes(&session).await?;
+        let inbox = mailboxes
+            .iter()
+            .find(|m| m.role.as_deref() == Some("inbox"))
+            .ok_or_else(|| Error::NotFound("No inbox mailbox".into()))?;
+        let query = crate::types::ParsedQuery {
+            after: Some(since),
+            ..Default::default()
+        };
+        provider::query_emails(
+            &session,
+            Some(&inbox.id),
+            RECEIPT_SCAN,
+            0,
+            Some(&query),
+            EmailSort::default(),
+        )
+        .await?
+    };
+    let filed = receipts::load_receipts(&state.receipts_path);
+    let unseen: Vec<String> = ids
+        .into_iter()
+        .filter(|id| !filed.contains(account_id, id))
+        .collect();
+    let candidates: Vec<String> = provider::get_emails_chunked(
+        &session_lock,
+        &unseen,
+        false,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?
+    .into_iter()
+    .filter(|e| e.has_attachment || receipts::is_candidate(e))
+    .map(|e| e.id)
+    .collect();
+    let records: Vec<receipts::ReceiptRecord> = provider::get_emails_chunked(
+        &session_lock,
+        &candidates,
+        true,
+        None,
+        provider::GET_EMAILS_CHUNK,
+    )
+    .await?
+    .iter()
+    .filter_map(|e| receipts::extract(e, account_id, tz))
+    .collect();
+
+    // The record is what expense review reads; a failed (or, read-only,
+    // skipped) tag only costs the in-mailbox marker.
+    if !read_only::enabled(state).await {
+        for record in &records {
+            let session = session_lock.read().await;
+            if let Err(e) =
+                provider::add_keyword(&session, &record.email_id, receipts::RECEIPT_KEYWORD).await
+            {
+                tracing::warn!("Failed to tag receipt {}: {e}", record.email_id);
+            }
+        }
+    }
+
+    let mut log = receipts::load_receipts(&state.receipts_path);
+    log.merge(records.clone());
+    receipts::save_receipts(&log, &state.receipts_path)?;
+    Ok(records)
+}
+
+#[derive(Deserialize)]
+struct ReceiptsParams {
+    /// `YYYY-MM`; the current local month when absent.
+    month: Option<String>,
+    /// Limit to one account; every account when absent.
+    account: Option<String>,
+}
+
+/// Filed receipts for one month, oldest first, with per-currency totals.
+async fn list_receipts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReceiptsParams>,
+) -> Result<impl IntoResponse, Error> {
+    let month = match params.month {
+        Some(m) => m,
+        None => local_now(&state).format("%Y-%m").to_string(),
+    };
+    let mut records = receipts::in_month(
+        &receipts::load_receipts(&state.receipts_path),
+        receipts::parse_month(&month)?,
+    );
+    if let Some(account) = params.account.as_deref() {
+        records.retain(|r| r.account == account);
+    }
+    let mut totals: std::collections::BTreeMap<String, f64> = Default::default();
+    for r in &records {
+        if let (Some(amount), Some(currency)) = (r.amount, r.currency.as_ref()) {
+            *totals.entry(currency.clone()).or_default() += amount;
+        }
+    }
+    for total in totals.values_mut() {
+        *total = (*total * 100.0).round() / 100.0;
+    }
+    Ok(Json(serde_json::json!({
+        "month": month,
+        "receipts": records,
+        "totals": totals,
+    })))
+}
+
+/// Run a receipt scan now instead of waiting for the background one.
+async fn scan_receipts_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account_id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let filed = scan_receipts(&state, &account_id).await?;
+    Ok(Json(serde_json::json!({ "filed": filed })))
+}
+
+// =============================================================================
+// Packages
+// =============================================================================
+
+/// Tracked packages, newest first, with whether the status poller is on.
+/// Packages with no `status` haven't been polled (poller off, no key for
+/// that carrier, or no answer yet).
+async fn list_packages(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> impl IntoResponse {
+    let mut tracked = packages::load_tracked(&state.packages_path).packages;
+    if let Some(account) = params.account.as_deref() {
+        tracked.retain(|p| p.account == account);
+    }
+    tracked.sort_by_key(|p| std::cmp::Reverse(p.first_seen));
+    Json(serde_json::json!({
+        "packages": tracked,
+        "polling": packages::load_config(&state.packages_config_path).enabled,
+    }))
+}
+
+// =============================================================================
+// Recently deleted
+// =============================================================================
+
+#[derive(Deserialize)]
+struct RecentlyDeletedParams {
+    account: Option<String>,
+    days: Option<i64>,
+}
+
+/// Messages this server trashed for the account in the last `days` days
+/// (default and maximum `recently_deleted::RETENTION_DAYS`), newest first.
+async fn list_recently_deleted(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RecentlyDeletedParams>,
+) -> Result<impl IntoResponse, Error> {
+    let days = params.days.unwrap_or(recently_deleted::RETENTION_DAYS);
+    if !(1..=recently_deleted::RETENTION_DAYS).contains(&days) {
+        return Err(Error::BadRequest(format!(
+            "days must be between 1 and {}",
+            recently_deleted::RETENTION_DAYS
+        )));
+    }
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let journal = recently_deleted::load_journal(&state.recently_deleted_path);
+    Ok(Json(serde_json::json!({
+        "trashed": journal.recent(&account, days, chrono::Utc::now()),
+        "days": days,
+    })))
+}
+
+/// Move a journaled message out of Trash into its original mailboxes and
+/// drop its record. A message the provider no longer has (emptied from
+/// Trash) is a 404 and its record is dropped too.
+async fn restore_recently_deleted(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let record = recently_deleted::load_journal(&state.recently_deleted_path)
+        .find(&id, &email_id)
+        .cloned()
+        .ok_or_else(|| Error::NotFound(format!("no recently deleted email {email_id}")))?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let result = provider::restore(&session, &email_id, &record.original_mailbox_ids).await;
+    drop(session);
+    if matches!(result, Ok(true) | Err(Error::NotFound(_))) {
+        let _guard = state.recently_deleted_lock.lock().await;
+        let mut journal = recently_deleted::load_journal(&state.recently_deleted_path);
+        journal.remove(&id, &email_id);
+        recently_deleted::save_journal(&journal, &state.recently_deleted_path)?;
+    }
+    let success = result?;
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({
+        "success": success,
+        "mailboxIds": record.original_mailbox_ids,
+    })))
+}
+
+// =============================================================================
+// Snooze
+// =============================================================================
+
+#[derive(Deserialize)]
+struct SnoozeBody {
+    wake_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The account's Snoozed mailbox: its `snoozed` role mailbox, else one
+/// named [`snooze::SNOOZED_MAILBOX_NAME`], created when neither exists.
+async fn snoozed_mailbox_id(session: &provider::ProviderSession) -> Result<String, Error> {
+    let mailboxes = provider::get_mailboxes(session).await?;
+    let existing = mailboxes
+        .iter()
+        .find(|m| m.role.as_deref() == Some("snoozed"))
+        .or_else(|| {
+            mailboxes
+                .iter()
+                .find(|m| m.name.eq_ignore_ascii_case(snooze::SNOOZED_MAILBOX_NAME))
+        });
+    match existing {
+        Some(m) => Ok(m.id.clone()),
+        None => provider::create_mailbox(session, snooze::SNOOZED_MAILBOX_NAME, None).await,
+    }
+}
+
+/// `POST /api/emails/{id}/snooze`: move the message to Snoozed until
+/// `wake_at`, when the waker brings it back to the Inbox unread.
+async fn snooze_email(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<SnoozeBody>,
+) -> Result<impl IntoResponse, Error> {
+    let now = chrono::Utc::now();
+    if body.wake_at <= now {
+        return Err(Error::BadRequest("wake_at must be in the future".into()));
+    }
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    if !session.keeps_ids_on_move() {
+        return Err(Error::BadRequest(format!(
+            "snooze is not supported for {} yet",
+            session.provider_name()
+        )));
+    }
+    let email = provider::get_emails(&session, std::slice::from_ref(&email_id), false, None, true)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NotFound(format!("Email {email_id} not found")))?;
+    let snoozed_id = snoozed_mailbox_id(&session).await?;
+    let success = provider::move_to_mailbox(&session, &email_id, &snoozed_id).await?;
+    drop(session);
+    let record = snooze::SnoozedRecord::new(&id, &email, &snoozed_id, body.wake_at, now);
+    if success {
+        let _guard = state.snoozed_lock.lock().await;
+        let mut journal = snooze::load_journal(&state.snoozed_path);
+        journal.record(record.clone());
+        snooze::save_journal(&journal, &state.snoozed_path)?;
+    }
+    state.prefetch.invalidate(&id).await;
+    Ok(Json(serde_json::json!({
+        "success": success,
+        "snoozed": record,
+    })))
+}
+
+/// The account's pending snoozes, next to wake first.
+async fn list_snoozed(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let journal = snooze::load_journal(&state.snoozed_path);
+    Ok(Json(serde_json::json!({
+        "snoozed": journal.for_account(&account),
+    })))
+}
+
+/// `DELETE /api/emails/{id}/snooze`: wake the message now.
+async fn unsnooze_email(
+    State(state): State<Arc<AppState>>,
+    Path(email_id): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let record = snooze::load_journal(&state.snoozed_path)
+        .find(&id, &email_id)
+        .cloned()
+        .ok_or_else(|| Error::NotFound(format!("no snoozed email {email_id}")))?;
+    wake_snoozed(&state, &record).await?;
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// Move `record`'s message back to the Inbox, mark it unread, and drop
+/// the record. A message the provider no longer has is dropped too.
+async fn wake_snoozed(state: &AppState, record: &snooze::SnoozedRecord) -> Result<(), Error> {
+    let result = async {
+        let session_lock = resolve_session(state, Some(&record.account)).await?;
+        let session = session_lock.read().await;
+        let inbox = provider::get_mailboxes(&session)
+            .await?
+            .into_iter()
+            .find(|m| m.role.as_deref() == Some("inbox"))
+            // Not NotFound: that would drop the record as if the message
+            // were gone.
+            .ok_or_else(|| Error::Internal("No inbox mailbox".into()))?;
+        provider::move_between(
+            &session,
+            &record.email_id,
+            &record.snoozed_mailbox_id,
+            &inbox.id,
+        )
+        .await?;
+        provider::mark_unread(&session, &record.email_id).await
+    }
+    .await;
+    if matches!(result, Ok(_) | Err(Error::NotFound(_))) {
+        let _guard = state.snoozed_lock.lock().await;
+        let mut journal = snooze::load_journal(&state.snoozed_path);
+        journal.remove(&record.account, &record.email_id);
+        snooze::save_journal(&journal, &state.snoozed_path)?;
+    }
+    result?;
+    state.prefetch.invalidate(&record.account).await;
+    Ok(())
+}
+
+/// Wake every snooze whose time has passed (`snooze::spawn_waker`).
+/// Failures stay in the journal and are retried next tick. Returns how
+/// many woke.
+pub(crate) async fn wake_due_snoozes(state: &AppState) -> usize {
+    let due = snooze::load_journal(&state.snoozed_path).due(chrono::Utc::now());
+    let mut woken = 0;
+    for record in due {
+        match wake_snoozed(state, &record).await {
+            Ok(()) => woken += 1,
+            Err(e) => tracing::warn!(
+                "Failed to wake snoozed {} for {}: {e}",
+                record.email_id,
+                record.account
+            ),
+        }
+    }
+    woken
+}
+
+// =============================================================================
+// Address book
+// =============================================================================
+
+#[derive(Deserialize)]
+struct ContactsParams {
+    account: Option<String>,
+    #[serde(default)]
+    q: String,
+    limit: Option<usize>,
+}
+
+/// Synced address book entries for the account matching `q` (all of them
+/// when empty), one row per address.
+async fn list_contacts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ContactsParams>,
+) -> Result<impl IntoResponse, Error> {
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let index = contacts::load_index(&state.contacts_path);
+    let matches = index
+        .accounts
+        .get(&account)
+        .map(|book| book.search(&params.q, params.limit.unwrap_or(usize::MAX)))
+        .unwrap_or_default();
+    Ok(Json(matches))
+}
+
+/// The account's address book groups, usable as `group:<id>` recipients.
+async fn list_contact_groups(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let mut index = contacts::load_index(&state.contacts_path);
+    let groups = index
+        .accounts
+        .remove(&account)
+        .map(|book| book.groups)
+        .unwrap_or_default();
+    Ok(Json(groups))
+}
+
+/// Sync the account's address book now instead of waiting for the
+/// background pass.
+async fn sync_contacts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let account = resolve_account_id(&state, params.account.as_deref()).await?;
+    let book = contacts::sync_account(&state, &account)
+        .await?
+        .ok_or_else(|| {
+            Error::BadRequest(format!(
+                "Address book sync not supported for account '{account}' yet"
+            ))
+        })?;
+    Ok(Json(serde_json::json!({
+        "contacts": book.contacts.len(),
+        "groups": book.groups.len(),
+        "syncedAt": book.synced_at,
+    })))
+}
+
+// =============================================================================
+// Compose sessions
+// =============================================================================
+
+async fn list_compose_sessions(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(
+        compose_sessions::load_sessions(&state.compose_sessions_path).recent()
+    ))
+}
+
+async fn get_compose_session(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    compose_sessions::load_sessions(&state.compose_sessions_path)
+        .find(&session_id)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| Error::NotFound(format!("Compose session '{session_id}' not found")))
+}
+
+async fn create_compose_session(
+    State(state): State<Arc<AppState>>,
+    Json(fields): Json<compose_sessions::ComposeFields>,
+) -> Result<impl IntoResponse, Error> {
+    let session = {
+        let _guard = state.compose_sessions.write_lock.lock().await;
+        let mut store = compose_sessions::load_sessions(&state.compose_sessions_path);
+        let session = store.create(fields, chrono::Utc::now());
+        compose_sessions::save_sessions(&store, &state.compose_sessions_path)?;
+        session
+    };
+    state.compose_sessions.publish(&session, false);
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// Body for `PUT /api/compose-sessions/{id}`: the full compose state, plus
+/// the version it was edited from when the client wants a 409 rather than
+/// overwriting another device's newer edits.
+#[derive(Deserialize)]
+struct ComposeSessionUpdate {
+    #[serde(flatten)]
+    fields: compose_sessions::ComposeFields,
+    version: Option<u64>,
+}
+
+async fn update_compose_session(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Json(body): Json<ComposeSessionUpdate>,
+) -> Result<impl IntoResponse, Error> {
+    let session = {
+        let _guard = state.compose_sessions.write_lock.lock().await;
+        let mut store = compose_sessions::load_sessions(&state.compose_sessions_path);
+        let session = store.update(&session_id, body.fields, body.version, chrono::Utc::now())?;
+        compose_sessions::save_sessions(&store, &state.compose_sessions_path)?;
+        session
+    };
+    state.compose_sessions.publish(&session, false);
+    Ok(Json(session))
+}
+
+/// Called once the message is sent or discarded, so other devices close
+/// their window.
+async fn delete_compose_session(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let session = {
+        let _guard = state.compose_sessions.write_lock.lock().await;
+        let mut store = compose_sessions::load_sessions(&state.compose_sessions_path);
+        let session = store
+            .remove(&session_id)
+            .ok_or_else(|| Error::NotFound(format!("Compose session '{session_id}' not found")))?;
+        compose_sessions::save_sessions(&store, &state.compose_sessions_path)?;
+        session
+    };
+    state.compose_sessions.publish(&session, true);
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+// =============================================================================
+// Recipient groups
+// =============================================================================
+
+async fn list_groups(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(
+        groups::load_groups(&state.groups_config_path).groups
+    ))
+}
+
+async fn create_group(
+    State(state): State<Arc<AppState>>,
+    Json(new_group): Json<groups::RecipientGroup>,
+) -> Result<impl IntoResponse, Error> {
+    groups::validate_group(&new_group)?;
+    let mut config = groups::load_groups(&state.groups_config_path);
+    if config.groups.iter().any(|g| g.id == new_group.id) {
+        return Err(Error::BadRequest(format!(
+            "Group with id '{}' already exists",
+            new_group.id
+        )));
+    }
+    config.groups.push(new_group);
+    groups::save_groups(&config, &state.groups_config_path)?;
+    Ok(Json(serde_json::json!(config.groups)))
+}
+
+async fn update_group(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<String>,
+    Json(updated): Json<groups::RecipientGroup>,
+) -> Result<impl IntoResponse, Error> {
+    if updated.id != group_id {
+        return Err(Error::BadRequest(format!(
+            "Group id is immutable ('{group_id}' != '{}')",
+            updated.id
+        )));
+    }
+    groups::validate_group(&updated)?;
+    let mut config = groups::load_groups(&state.groups_config_path);
+    let existing = config
+        .groups
+        .iter_mut()
+        .find(|g| g.id == group_id)
+        .ok_or_else(|| Error::NotFound(format!("Group '{group_id}' not found")))?;
+    *existing = updated;
+    groups::save_groups(&config, &state.groups_config_path)?;
+    Ok(Json(serde_json::json!(config.groups)))
+}
+
+async fn delete_group(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let mut config = groups::load_groups(&state.groups_config_path);
+    let original_len = config.groups.len();
+    config.groups.retain(|g| g.id != group_id);
+    if config.groups.len() == original_len {
+        return Err(Error::NotFound(format!("Group '{group_id}' not found")));
+    }
+    groups::save_groups(&config, &state.groups_config_path)?;
+    Ok(Json(serde_json::json!(config.groups)))
+}
+
+// =============================================================================
+// Protected senders
+// =============================================================================
+
+async fn list_protected_senders(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(
+        protected::load_protected(&state.protected_senders_path).senders
+    ))
+}
+
+#[derive(Deserialize)]
+struct ProtectedSenderBody {
+    sender: String,
+}
+
+async fn add_protected_sender(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<ProtectedSenderBody>,
+) -> Result<impl IntoResponse, Error> {
+    let sender = protected::normalize_sender(&body.sender)?;
+    let mut config = protected::load_protected(&state.protected_senders_path);
+    if config.senders.contains(&sender) {
+        return Err(Error::Conflict(format!(
+            "'{sender}' is already a protected sender"
+        )));
+    }
+    config.senders.push(sender);
+    protected::save_protected(&config, &state.protected_senders_path)?;
+    Ok(Json(serde_json::json!(config.senders)))
+}
+
+async fn remove_protected_sender(
+    State(state): State<Arc<AppState>>,
+    Path(sender): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let sender = sender.trim().to_ascii_lowercase();
+    let mut config = protected::load_protected(&state.protected_senders_path);
+    let original_len = config.senders.len();
+    config.senders.retain(|s| *s != sender);
+    if config.senders.len() == original_len {
+        return Err(Error::NotFound(format!(
+            "'{sender}' is not a protected sender"
+        )));
+    }
+    protected::save_protected(&config, &state.protected_senders_path)?;
+    Ok(Json(serde_json::json!(config.senders)))
+}
+
+// =============================================================================
+// Remote images
+// =============================================================================
+
+#[derive(Deserialize)]
+struct ProxyImageParams {
+    url: String,
+}
+
+/// A remote image fetched on the reader's behalf; see `image_proxy`.
+async fn proxy_image(Query(params): Query<ProxyImageParams>) -> Result<impl IntoResponse, Error> {
+    let url = image_proxy::check_url(&params.url)?;
+    let (content_type, bytes) = image_proxy::fetch(url).await?;
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", content_type),
+            ("x-content-type-options", "nosniff"),
+            ("cache-control", "private, max-age=86400"),
+            ("referrer-policy", "no-referrer"),
+        ],
+        bytes,
+    ))
+}
+
+async fn list_image_senders(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!(
+        image_proxy::load_image_senders(&state.image_senders_path).senders
+    ))
+}
+
+#[derive(Deserialize)]
+struct ImageSenderBody {
+    sender: String,
+}
+
+async fn add_image_sender(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<ImageSenderBody>,
+) -> Result<impl IntoResponse, Error> {
+    let sender = image_proxy::normalize_sender(&body.sender)?;
+    let mut config = image_proxy::load_image_senders(&state.image_senders_path);
+    if config.senders.contains(&sender) {
+        return Err(Error::Conflict(format!("'{sender}' already loads images")));
+    }
+    config.senders.push(sender);
+    image_proxy::save_image_senders(&config, &state.image_senders_path)?;
+    Ok(Json(serde_json::json!(config.senders)))
+}
+
+async fn remove_image_sender(
+    State(state): State<Arc<AppState>>,
+    Path(sender): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let sender = sender.trim().to_ascii_lowercase();
+    let mut config = image_proxy::load_image_senders(&state.image_senders_path);
+    let original_len = config.senders.len();
+    config.senders.retain(|s| *s != sender);
+    if config.senders.len() == original_len {
+        return Err(Error::NotFound(format!(
+            "'{sender}' is not an image sender"
+        )));
+    }
+    image_proxy::save_image_senders(&config, &state.image_senders_path)?;
+    Ok(Json(serde_json::json!(config.senders)))
+}
+
+// =============================================================================
+// Timezone settings
+// =============================================================================
+
+#[derive(serde::Deserialize)]
+struct TimezoneConfigBody {
+    #[serde(default = "default_true_bool")]
+    use_system: bool,
+    #[serde(default)]
+    manual_primary: Option<String>,
+    #[serde(default)]
+    additional: Vec<String>,
+}
+
+fn default_true_bool() -> bool {
+    true
+}
+
+/// `GET /api/preferences`: the account to open on load and each
+/// account's startup view (`startup-mailbox` / `startup-split` in the
+/// config). Accounts without one open their inbox.
+async fn get_preferences(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let reg = state.accounts.read().await;
+    let startup: serde_json::Map<String, serde_json::Value> = reg
+        .startup
+        .iter()
+        .filter(|(name, _)| reg.account_configs.contains_key(*name))
+        .map(|(name, view)| (name.clone(), serde_json::json!(view)))
+        .collect();
+    Json(serde_json::json!({
+        "defaultAccount": Some(&reg.default_account).filter(|d| !d.is_empty()),
+        "startup": startup,
+    }))
+}
+
+/// Server-wide switches the UI adapts to: `readOnly` is the config's
+/// `read-only`, under which every mutating route answers 409
+/// `{"error": "read_only"}`.
+async fn get_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "readOnly": state.accounts.read().await.read_only,
+    }))
+}
+
+fn timezone_env_override() -> Option<String> {
+    std::env::var("SUPERVILLAIN_TIMEZONE").ok()
+}
+
+async fn get_timezone(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let cfg = timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    );
+    Json(serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default())
+}
+
+async fn put_timezone(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<TimezoneConfigBody>,
+) -> Result<impl IntoResponse, Error> {
+    if let Some(ref primary) = body.manual_primary
+        && !primary.is_empty()
+        && !timezone::validate_iana(primary)
+    {
+        return Err(Error::BadRequest(format!(
+            "Unknown IANA timezone: {primary}"
+        )));
+    }
+    for tz in &body.additional {
+        if !timezone::validate_iana(tz) {
+            return Err(Error::BadRequest(format!("Unknown IANA timezone: {tz}")));
+        }
+    }
+
+    let _guard = state.timezone_write_lock.lock().await;
+    let mut existing = timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    );
+    existing.use_system = body.use_system;
+    existing.manual_primary = body.manual_primary.filter(|s| !s.is_empty());
+    existing.additional = body.additional;
+    // Seed last_known_system_tz on first save so the change banner has a baseline.
+    if existing.last_known_system_tz.is_none() {
+        existing.last_known_system_tz = Some(timezone::detect_system_tz());
+    }
+    timezone::save_config(&existing, &state.timezone_config_path)?;
+
+    Ok(Json(
+        serde_json::to_value(timezone::resolve(&existing)).unwrap_or_default(),
+    ))
+}
+
+async fn accept_system_timezone(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Error> {
+    let _guard = state.timezone_write_lock.lock().await;
+    let mut cfg = timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    );
+    let system = timezone::detect_system_tz();
+    cfg.last_known_system_tz = Some(system.clone());
+    cfg.dismissed_change_to = None;
+    // If the user wants to track system, that's already wired through use_system.
+    // Don't toggle use_system here; "Accept system" means "the new system TZ is fine".
+    timezone::save_config(&cfg, &state.timezone_config_path)?;
+    Ok(Json(
+        serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default(),
+    ))
+}
+
+#[derive(serde::Deserialize, Default)]
+struct DismissTimezoneBody {
+    /// The system TZ value the client was looking at when the user dismissed
+    /// the banner. If it no longer matches the currently detected system TZ,
+    /// we refuse — the user can't "Keep current" on a change they never saw.
+    /// Optional for backwards-compat with clients that don't send it.
+    #[serde(default)]
+    seen_system: Option<String>,
+}
+
+async fn dismiss_timezone_change(
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<DismissTimezoneBody>>,
+) -> Result<impl IntoResponse, Error> {
+    let _guard = state.timezone_write_lock.lock().await;
+    let current_system = timezone::detect_system_tz();
+    if let Some(Json(b)) = body
+        && let Some(seen) = b.seen_system
+        && !seen.is_empty()
+        && seen != current_system
+    {
+        return Err(Error::Conflict(format!(
+            "system timezone changed from {seen} to {current_system} since the banner was shown — please recheck"
+        )));
+    }
+    let mut cfg = timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    );
+    cfg.dismissed_change_to = Some(current_system);
+    timezone::save_config(&cfg, &state.timezone_config_path)?;
+    Ok(Json(
+        serde_json::to_value(timezone::resolve(&cfg)).unwrap_or_default(),
+    ))
+}
+
+async fn list_timezones() -> impl IntoResponse {
+    let names: Vec<&'static str> = chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
+    Json(serde_json::json!(names))
+}
+
+// =============================================================================
+// Calendar invite composition
+// =============================================================================
+
+#[derive(serde::Deserialize)]
+struct InviteAttendee {
+    email: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct SendInviteBody {
+    to: Vec<String>,
+    #[serde(default)]
+    cc: Vec<String>,
+    #[serde(default)]
+    bcc: Vec<String>,
+    subject: String,
+    #[serde(default)]
+    body: String,
+    summary: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    start: String, // "2026-06-01T10:00:00"
+    end: String,
+    #[serde(default)]
+    tz: Option<String>,
+    #[serde(default)]
+    attendees: Vec<InviteAttendee>,
+    #[serde(default)]
+    from_address: Option<String>,
+    /// File attachments resolved at send time the same way `/emails/send`
+    /// resolves them. Without this, users who attached files AND enabled
+    /// the invite toggle would silently lose the attachments (roborev 186 #6).
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    /// Authenticator code for invites to guarded recipients (`send_guard`).
+    #[serde(default)]
+    totp_code: Option<String>,
+}
+
+/// Send an email with an embedded iTIP REQUEST. Invites to guarded
+/// recipients need a `totp_code`, as `/api/emails/send` does.
+///
+/// Attendee list comes from `body.attendees` only — `to`/`cc`/`bcc` control
+/// envelope routing, not ICS ATTENDEE properties. BCC privacy is preserved
+/// (BCC recipients are not visible in the ICS) but the caller must include
+/// them in `attendees` if it wants them tracked in calendar attendees.
+/// The frontend builds `attendees` from `to + cc` deliberately; if BCC
+/// support is added to the compose UI, decide policy then (include + warn,
+/// or exclude + warn).
+async fn send_invite_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AccountParam>,
+    Json(body): Json<SendInviteBody>,
+) -> Result<impl IntoResponse, Error> {
+    let tz_cfg = timezone::load_config(
+        &state.timezone_config_path,
+        timezone_env_override().as_deref(),
+    );
+    let resolved = timezone::resolve(&tz_cfg);
+    let tz_name = body
+        .tz
+        .clone()
+        .filter(|s| !s.is_empty())
+        .unwrap_or(resolved.primary.clone());
+    if !timezone::validate_iana(&tz_name) {
+        return Err(Error::BadRequest(format!(
+            "Unknown IANA timezone: {tz_name}"
+        )));
+    }
+    let tz: chrono_tz::Tz = std::str::FromStr::from_str(&tz_name)
+        .map_err(|_| Error::BadRequest(format!("Unknown IANA timezone: {tz_name}")))?;
+
+    let start_naive = chrono::NaiveDateTime::parse_from_str(&body.start, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(&body.start, "%Y-%m-%dT%H:%M"))
+        .map_err(|e| Error::BadRequest(format!("Invalid start time: {e}")))?;
+    let end_naive = chrono::NaiveDateTime::parse_from_str(&body.end, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(&body.end, "%Y-%m-%dT%H:%M"))
+        .map_err(|e| Error::BadRequest(format!("Invalid end time: {e}")))?;
+    let dtstart = chrono::TimeZone::from_local_datetime(&tz, &start_naive)
+        .earliest()
+        .ok_or_else(|| Error::BadRequest("start time has no valid mapping in tz".into()))?;
+    let dtend = chrono::TimeZone::from_local_datetime(&tz, &end_naive)
+        .earliest()
+        .ok_or_else(|| Error::BadRequest("end time has no valid mapping in tz".into()))?;
+    // Roborev 186 #7: reject negative-duration invites at the boundary rather
+    // than relying on the recipient's calendar client.
+    if dtend <= dtstart {
+        return Err(Error::BadRequest(
+            "end time must be after start time".into(),
+        ));
+    }
+
+    // The ICS is filled in once the organizer is known; the guard only
+    // looks at recipients, so it runs before connecting.
+    let mut submission = EmailSubmission {
+        to: body.to,
+        cc: body.cc,
+        subject: body.subject,
+        text_body: body.body,
+        bcc: if body.bcc.is_empty() {
+            None
+        } else {
+            Some(body.bcc)
+        },
+        html_body: None,
+        in_reply_to: None,
+        references: None,
+        attachments: body.attachments,
+        calendar_ics: None,
+        forward_of: None,
+    };
+    send_guard::load_config(&state.send_guard_path).check(
+        &submission,
+        body.totp_code.as_deref(),
+        chrono::Utc::now(),
+    )?;
+
+    let session_lock = resolve_session(&state, params.account.as_deref()).await?;
+    let mut session = session_lock.write().await;
+    let from_addr = body
+        .from_address
+        .clone()
+        .unwrap_or_else(|| session.username().to_string());
+
+    let organizer_email = from_addr.clone();
+    let attendees: Vec<Attendee> = body
+        .attendees
+        .iter()
+        .map(|a| Attendee {
+            email: a.email.clone(),
+            name: a.name.clone(),
+            status: "NEEDS-ACTION".into(),
+        })
+        .collect();
+
+    submission.calendar_ics = Some(calendar::generate_invite(
+        &organizer_email,
+        None,
+        &body.summary,
+        body.description.as_deref(),
+        body.location.as_deref(),
+        dtstart,
+        dtend,
+        &attendees,
+        None,
+    ));
+
+    let result = provider::send_email(&mut session, &submission, &from_addr, None).await?;
+    match result {
+        Some(id) => Ok(Json(serde_json::json!({"success": true, "emailId": id}))),
+        None => Err(Error::Internal("Failed to send invite".into())),
+    }
+}
+
+/// How many same-subject inbox messages `cancel_event` inspects when
+/// looking for replies and updates that belong to the cancelled event.
+const CANCEL_RELATED_SCAN: usize = 50;
+
+/// Calendar-part fetches `cancel_event` keeps in flight while matching
+/// those messages by UID.
+const CANCEL_RELATED_CONCURRENCY: usize = 4;
+
+/// The messages among `emails` that carry a calendar part, the only ones
+/// worth a `get_calendar_data` round trip. Works on list rows: metadata-only
+/// fetches report `has_calendar` from the part types.
+fn with_calendar(emails: &[Email]) -> Vec<&Email> {
+    emails.iter().filter(|e| e.has_calendar).collect()
+}
+
+/// Cancel an event the user organized: mail a `METHOD:CANCEL` to every
+/// attendee, delete the calendar copy, then archive inbox messages that
+/// carry the same UID (attendee replies, earlier updates). Only the
+/// organizer may cancel — anyone else should decline instead. The CANCEL
+/// goes out first so a send failure leaves the calendar untouched;
+/// archiving is best-effort and reported as a count. The send guard
+/// doesn't apply: the notice is server-written and goes only to the
+/// event's own attendees.
+async fn cancel_event(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let identities = state
+        .prefetch
+        .identities_or_fetch(&id, || async {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            let mut session = session_lock.write().await;
+            provider::get_identities(&mut session).await
+        })
+        .await?;
+
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let mut session = session_lock.write().await;
+    let event = provider::get_calendar_event(&session, &uid)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Event {uid} is not in the calendar")))?;
+
+    let organizer = event.organizer_email.trim();
+    if !organized_by_me(&event, session.username(), &identities) {
+        return Err(Error::BadRequest(format!(
+            "Only the organizer can cancel this event (organized by {organizer})"
+        )));
+    }
+
+    let recipients: Vec<String> = event
+        .attendees
+        .iter()
+        .map(|a| a.email.clone())
+        .filter(|e| !e.is_empty() && !e.eq_ignore_ascii_case(organizer))
+        .collect();
+    if !recipients.is_empty() {
+        let submission = EmailSubmission {
+            to: recipients.clone(),
+            cc: Vec::new(),
+            subject: format!("Cancelled: {}", event.summary),
+            text_body: format!("This event has been cancelled: {}", event.summary),
+            bcc: None,
+            html_body: None,
+            in_reply_to: None,
+            references: None,
+            attachments: Vec::new(),
+            calendar_ics: Some(calendar::generate_cancel(&event)),
+            forward_of: None,
+        };
+        provider::send_email(&mut session, &submission, organizer, None)
+            .await?
+            .ok_or_else(|| Error::Internal("Failed to send cancellation".into()))?;
+    }
+
+    let removed = provider::remove_from_calendar(&session, &uid).await?;
+    let protected = protected::load_protected(&state.protected_senders_path);
+    let archived = match archive_event_emails(&session, &event, &protected).await {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::warn!("[{id}] cancel {uid}: archiving related mail failed: {e}");
+            0
+        }
+    };
+    drop(session);
+    state.prefetch.invalidate(&id).await;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "notified": recipients,
+        "removed": removed,
+        "archived": archived,
+    })))
+}
+
+/// `event`'s organizer is the account's login or one of its identities.
+fn organized_by_me(event: &CalendarEvent, username: &str, identities: &[Identity]) -> bool {
+    let organizer = event.organizer_email.trim();
+    !organizer.is_empty()
+        && (organizer.eq_ignore_ascii_case(username)
+            || identities
+                .iter()
+                .any(|i| i.email.eq_ignore_ascii_case(organizer)))
+}
+
+/// `GET /api/events/{uid}/responses` — for an event the user organizes,
+/// each attendee's answer and the totals. Answers come from the stored
+/// calendar copy, REPLY mail about the event in any mailbox, and replies
+/// applied this run; the newest reply per attendee wins.
+async fn event_responses(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<String>,
+    Query(params): Query<AccountParam>,
+) -> Result<impl IntoResponse, Error> {
+    let id = resolve_account_id(&state, params.account.as_deref()).await?;
+    let identities = state
+        .prefetch
+        .identities_or_fetch(&id, || async {
+            let session_lock = resolve_session(&state, Some(&id)).await?;
+            let mut session = session_lock.write().await;
+            provider::get_identities(&mut session).await
+        })
+        .await?;
+
+    let session_lock = resolve_session(&state, Some(&id)).await?;
+    let session = session_lock.read().await;
+    let event = provider::get_calendar_event(&session, &uid)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Event {uid} is not in the calendar")))?;
+    if !organized_by_me(&event, session.username(), &identities) {
+        return Err(Error::BadRequest(format!(
+            "Only the organizer can see responses to this event (organized by {})",
+            event.organizer_email
+        )));
+    }
+
+    let my_addresses: Vec<&str> = std::iter::once(session.username())
+        .chain(identities.iter().map(|i| i.email.as_str()))
+        .collect();
+    let mut replies = match event_reply_emails(&session, &event, &id, &my_addresses).await {
+        Ok(replies) => replies,
+        Err(e) => {
+            tracing::warn!("[{id}] responses {uid}: scanning reply mail failed: {e}");
+            Vec::new()
+        }
+    };
+    drop(session);
+    replies.extend(
+        state
+            .calendar_reply_log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|r| r.account == id && r.uid == uid)
+            .cloned(),
+    );
+
+    let attendees = calendar::attendee_responses(&event, &replies);
+    Ok(Json(serde_json::json!({
+        "uid": event.uid,
+        "summary": event.summary,
+        "dtstart": event.dtstart,
+        "counts": calendar::response_counts(&attendees),
+        "attendees": attendees,
+    })))
+}
+
+/// REPLY messages about `event`, found like `archive_event_emails` finds
+/// related mail but in every mailbox (answered invites often get
+/// archived). Each must pass `calendar::reply_decision`, so only a reply
+/// from an attendee of the event counts.
+async fn event_reply_emails(
+    session: &provider::ProviderSession,
+    event: &CalendarEvent,
+    account: &str,
+    my_addresses: &[&str],
+) -> Result<Vec<AppliedReply>, Error> {
+    let query = ParsedQuery {
+        subject: vec![event.summary.clone()],
+        ..Default::default()
+    };
+    let ids = provider::query_emails(
+        session,
+        None,
+        CANCEL_RELATED_SCAN,
+        0,
+        Some(&query),
+        EmailSort::default(),
+    )
+    .await?;
+    let emails = provider::get_emails(session, &ids, false, None, false).await?;
+    let invites = with_calendar(&emails);
+    let calendars = provider_utils::try_join_bounded(
+        invites.iter().map(|e| e.id.as_str()),
+        CANCEL_RELATED_CONCURRENCY,
+        |id| provider::get_calendar_data(session, id),
+    )
+    .await?;
+    let mut replies = Vec::new();
+    for (email, ics) in invites.into_iter().zip(calendars) {
+        let Some(reply) = ics.as_deref().and_then(calendar::parse_ics) else {
+            continue;
+        };
+        if reply.uid != event.uid || !reply.method.eq_ignore_ascii_case("REPLY") {
+            continue;
+        }
+        let sender = email.from.first().map(|a| a.email.as_str());
+        let (attendee, status) =
+            match calendar::reply_decision(Some(event), &reply, sender, my_addresses) {
+                calendar::ReplyAction::Apply {
+                    attendee_email,
+                    status,
+                } => (attendee_email, status.as_ics_str().to_string()),
+                calendar::ReplyAction::Unchanged => {
+                    let Some(att) = reply
+                        .attendees
+                        .iter()
+                        .find(|a| sender.is_some_and(|s| a.email.eq_ignore_ascii_case(s)))
+                    else {
+                        continue;
+                    };
+                    (att.email.clone(), att.status.clone())
+                }
+                _ => continue,
+            };
+        replies.push(AppliedReply {
+            account: account.to_string(),
+            email_id: email.id.clone(),
+            uid: event.uid.clone(),
+            summary: event.summary.clone(),
+            attendee,
+            status,
+            applied_at: email.received_at,
+        });
+    }
+    Ok(replies)
+}
+
+/// Archive inbox messages whose calendar part has `event`'s UID. Candidates
+/// come from a subject search (replies quote the summary); the UID check
+/// keeps an unrelated same-titled meeting's mail in place.
+async fn archive_event_emails(
+    session: &provider::ProviderSession,
+    event: &crate::types::CalendarEvent,
+    protected: &protected::ProtectedSendersConfig,
+) -> Result<usize, Error> {
+    let mailboxes = provider::get_mailboxes(session).await?;
+    let Some(inbox) = mailboxes
+        .iter()
+        .find(|m| m.role.as_deref() == Some("inbox"))
+    else {
+        return Ok(0);
+    };
+    let query = crate::types::ParsedQuery {
+        subject: vec![event.summary.clone()],
+        ..Default::default()
+    };
+    let ids = provider::query_emails(
+        session,
+        Some(&inbox.id),
+        CANCEL_RELATED_SCAN,
+        0,
+        Some(&query),
+        EmailSort::default(),
+    )
+    .await?;
+    let emails = provider::get_emails(session, &ids, false, None, false).await?;
+    let invites: Vec<&str> = with_calendar(&emails)
+        .into_iter()
+        .map(|e| e.id.as_str())
+        .collect();
+    let calendars = provider_utils::try_join_bounded(
+        invites.iter().copied(),
+        CANCEL_RELATED_CONCURRENCY,
+        |id| provider::get_calendar_data(session, id),
+    )
+    .await?;
+    let related: Vec<String> = invites
+        .iter()
+        .zip(calendars)
+        .filter(|(_, ics)| {
+            ics.as_deref()
+                .and_then(calendar::parse_ics)
+                .is_some_and(|e| e.uid == event.uid)
+        })
+        .map(|(id, _)| id.to_string())
+        .collect();
+    if related.is_empty() {
+        return Ok(0);
+    }
+    let (archived, _) = archive_batch_unprotected(session, protected, &related).await?;
+    Ok(archived)
+}
+
+/// The one door bulk archive paths go through: drops ids whose sender is on
+/// the protected list, archives the rest, and returns `(archived, skipped)`.
+/// Sender metadata is only fetched when the list is non-empty, so the
+/// common case costs nothing over a plain `archive_batch`.
+async fn archive_batch_unprotected(
+    session: &provider::ProviderSession,
+    protected: &protected::ProtectedSendersConfig,
+    ids: &[String],
+) -> Result<(usize, usize), Error> {
+    if ids.is_empty() {
+        return Ok((0, 0));
+    }
+    if protected.senders.is_empty() {
+        return Ok((provider::archive_batch(session, ids).await?, 0));
+    }
+    let emails = provider::get_emails(session, ids, false, None, false).await?;
+    let (kept, skipped) = protected::partition(protected, &emails);
+    if !skipped.is_empty() {
+        tracing::info!(
+            "Skipping {} protected message(s) in bulk archive",
+            skipped.len()
+        );
+    }
+    let archived = if kept.is_empty() {
+        0
+    } else {
+        provider::archive_batch(session, &kept).await?
+    };
+    Ok((archived, skipped.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn index_html_contains_html() {
+        let resp = index_html().await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ct = resp
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert_eq!(ct, "text/html; charset=utf-8");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = std::str::from_utf8(&body).unwrap();
+        assert!(
+            text.contains("<html"),
+            "index.html should contain <html tag"
+        );
+    }
+
+    #[tokio::test]
+    async fn app_js_contains_javascript() {
+        let resp = app_js().await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ct = resp
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert_eq!(ct, "application/javascript; charset=utf-8");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!body.is_empty(), "app.js should not be empty");
+    }
+
+    #[tokio::test]
+    async fn style_css_contains_css() {
+        let resp = style_css().await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ct = resp
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert_eq!(ct, "text/css; charset=utf-8");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!body.is_empty(), "style.css should not be empty");
+    }
+
+    #[test]
+    fn identity_serialization_preserves_email_field() {
+        let identity = crate::types::Identity {
+            id: "id1".into(),
+            email: "test@example.com".into(),
+            name: "Test User".into(),
+        };
+        let json = serde_json::to_string(&identity).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["email"], "test@example.com");
+        assert_eq!(parsed["name"], "Test User");
+    }
+
+    #[test]
+    fn safe_path_segment_rejects_traversal() {
+        assert!(!is_safe_path_segment("../etc/passwd"));
+        assert!(!is_safe_path_segment(".."));
+        assert!(!is_safe_path_segment("."));
+        assert!(!is_safe_path_segment("foo/bar"));
+        assert!(!is_safe_path_segment("foo\\bar"));
+        assert!(!is_safe_path_segment(""));
+        assert!(!is_safe_path_segment("foo\0bar"));
+    }
+
+    #[test]
+    fn safe_path_segment_accepts_valid() {
+        assert!(is_safe_path_segment("blob-abc123"));
+        assert!(is_safe_path_segment("report.pdf"));
+        assert!(is_safe_path_segment("G1234abcdef"));
+        assert!(is_safe_path_segment("file..backup.pdf"));
+    }
+
+    #[test]
+    fn sanitize_filename_strips_dangerous_chars() {
+        assert_eq!(sanitize_filename_for_header("normal.pdf"), "normal.pdf");
+        assert_eq!(sanitize_filename_for_header("file\".txt"), "file.txt");
+        assert_eq!(
+            sanitize_filename_for_header("file\r\ninjected"),
+            "fileinjected"
+        );
+        assert_eq!(
+            sanitize_filename_for_header("file\\\"name.txt"),
+            "filename.txt"
+        );
+    }
+
+    #[test]
+    fn compose_defaults_to_first_identity() {
+        assert!(
+            APP_JS.contains("state.identities[0].email"),
+            "clearCompose should default to the first identity's email"
+        );
+    }
+
+    #[test]
+    fn app_js_signature_prefilled_via_clear_compose() {
+        // clearCompose is the single choke point startCompose/startReply/
+        // startForward all call first, so prefilling there covers new,
+        // reply, and forward uniformly — this pins that the prefill lives
+        // there rather than being duplicated (or, worse, injected at send
+        // time, which the design explicitly forbids: the user must see
+        // exactly what sends).
+        let start = APP_JS
+            .find("function clearCompose()")
+            .expect("clearCompose must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("clearCompose must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("composeSignaturePrefill()"),
+            "clearCompose must prefill the body via composeSignaturePrefill()"
+        );
+        assert!(
+            block.contains("setSelectionRange(0, 0)"),
+            "clearCompose must place the cursor at position 0 after prefilling"
+        );
+        assert!(
+            APP_JS.contains("function composeSignaturePrefill"),
+            "composeSignaturePrefill helper must exist"
+        );
+        assert!(
+            APP_JS.contains("-- \\n"),
+            "signature prefill must use the RFC 3676 delimiter '-- ' \
+             (trailing space significant) before a newline"
+        );
+    }
+
+    // ====================================================================
+    // Contact autocomplete on To/Cc (kata e64s, task B6) — client-side only,
+    // no server surface. These are string-invariant tests per repo
+    // convention (no JS harness): they pin the shape of static/app.js
+    // rather than executing it.
+    // ====================================================================
+
+    #[test]
+    fn contact_index_state_present() {
+        assert!(
+            APP_JS.contains("contactIndex: new Map()"),
+            "state.contactIndex must be a Map, built by harvesting from \
+             loaded email list pages and the Sent mailbox (kata e64s)"
+        );
+    }
+
+    #[test]
+    fn contact_index_is_account_scoped() {
+        // selectAccount's documented convention: caches are account-scoped
+        // (splitListCache/emailCache/scrollPositions all key by account and
+        // are never shared across a switch). The contact index must follow
+        // it — Account A's contacts leaking into Account B's compose would
+        // cross-pollinate address books. Pins: a per-account accessor
+        // exists, harvest writes through it, and rankContactMatches only
+        // reads the CURRENT account's entries.
+        assert!(
+            APP_JS.contains("function contactIndexFor("),
+            "a per-account contact-map accessor must exist"
+        );
+
+        let start = APP_JS
+            .find("function harvestContacts(")
+            .expect("harvestContacts must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("harvestContacts must close");
+        assert!(
+            rest[..end].contains("contactIndexFor(accountId)"),
+            "harvestContacts must write into the harvested account's own map"
+        );
+
+        let start2 = APP_JS
+            .find("function rankContactMatches(")
+            .expect("rankContactMatches must exist");
+        let rest2 = &APP_JS[start2..];
+        let end2 = rest2.find("\n}").expect("rankContactMatches must close");
+        assert!(
+            rest2[..end2].contains("state.currentAccount"),
+            "rankContactMatches must only surface the current account's contacts"
+        );
+    }
+
+    #[test]
+    fn contact_rank_function_sorts_by_count_then_last_seen_and_excludes_self() {
+        let start = APP_JS
+            .find("function rankContactMatches(")
+            .expect("a pure rank/match helper must exist for contact autocomplete");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("rankContactMatches must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("b.count - a.count"),
+            "contacts must rank by frequency count descending first"
+        );
+        assert!(
+            block.contains("lastSeen") && block.contains("localeCompare"),
+            "ties must break by lastSeen descending"
+        );
+        assert!(
+            block.contains("ownIdentityEmails"),
+            "rank/match must exclude the account's own identity addresses"
+        );
+    }
+
+    #[test]
+    fn contact_harvest_hooks_list_load_and_refill_paths() {
+        assert!(
+            APP_JS.contains("function harvestContacts("),
+            "harvestContacts must exist to build the contact index from loaded emails"
+        );
+
+        // Primary list-load path.
+        let start = APP_JS
+            .find("async function loadEmails()")
+            .expect("loadEmails must exist");
+        let rest = &APP_JS[start..];
+        let end = rest
+            .find("\nasync function maybeRefillEmails")
+            .expect("loadEmails must be followed by maybeRefillEmails");
+        assert!(
+            rest[..end].contains("harvestContacts("),
+            "loadEmails must harvest contacts from the fetched page"
+        );
+
+        // Infinite-scroll append path.
+        let start2 = APP_JS
+            .find("async function maybeRefillEmails()")
+            .expect("maybeRefillEmails must exist");
+        let rest2 = &APP_JS[start2..];
+        let end2 = rest2
+            .find("\nasync function loadEmailDetail")
+            .expect("maybeRefillEmails must be followed by loadEmailDetail");
+        assert!(
+            rest2[..end2].contains("harvestContacts("),
+            "maybeRefillEmails must harvest contacts from newly appended emails"
+        );
+    }
+
+    #[test]
+    fn address_book_names_are_not_overwritten_by_harvest() {
+        let start = APP_JS
+            .find("function loadAddressBookOnce")
+            .expect("loadAddressBookOnce must exist");
+        let rest = &APP_JS[start..];
+        let block = &rest[..rest.find("\n}").expect("loadAddressBookOnce must close")];
+        assert!(
+            block.contains("contactIndexFor(accountId)"),
+            "address book entries must land in the loaded account's own map"
+        );
+        assert!(
+            APP_JS.contains("if (addr.name && !existing.fromAddressBook)"),
+            "harvested header names must not replace an address book name"
+        );
+    }
+
+    #[test]
+    fn sent_mailbox_background_harvest_degrades_silently() {
+        let start = APP_JS
+            .find("function harvestSentContactsOnce")
+            .expect("harvestSentContactsOnce must exist");
+        let rest = &APP_JS[start..];
+        let end = rest
+            .find("\n}")
+            .expect("harvestSentContactsOnce must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("role === 'sent'"),
+            "must resolve the Sent mailbox from state.mailboxes by role"
+        );
+        assert!(
+            block.contains("console.warn"),
+            "a failed Sent-mailbox fetch must degrade silently (console.warn only)"
+        );
+        assert!(
+            !block.contains("showStatus"),
+            "a failed Sent-mailbox fetch must not surface an error banner to the user"
+        );
+    }
+
+    #[test]
+    fn contact_dropdown_keydown_scoped_to_focused_input_when_open() {
+        // The compose insert-mode key handler must only intercept dropdown
+        // navigation keys when the contact dropdown is actually open AND the
+        // event target is the To/Cc input it belongs to — otherwise it must
+        // fall through untouched to the existing Escape/Ctrl+Enter handling
+        // (autosave/send-lock from kata wm57 must not be disturbed).
+        let start = APP_JS
+            .find("state.view === 'compose' && state.mode === 'insert'")
+            .expect("compose insert-mode key handler must exist");
+        let rest = &APP_JS[start..];
+        let end = rest
+            .find("\n    // Compose normal-mode:")
+            .expect("compose insert-mode block must be followed by the normal-mode 'a' handler");
+        let block = &rest[..end];
+        assert!(
+            block.contains("state.contactAcField"),
+            "compose keydown must gate contact-dropdown key handling on an open dropdown"
+        );
+        assert!(
+            block.contains("e.target === els.composeTo")
+                || block.contains("e.target === els.composeCc"),
+            "contact-dropdown key handling must be scoped to the focused To/Cc input"
+        );
+        assert!(
+            block.contains("sendEmail()"),
+            "existing Ctrl+Enter send handling must still be present"
+        );
+    }
+
+    #[test]
+    fn contact_autocomplete_inserts_bare_email_not_display_name() {
+        // To/Cc are parsed downstream as plain comma-separated address
+        // strings (see sendEmail / build_draft_email) — inserting a
+        // "Name <email>" form here would ship as a literally-invalid
+        // recipient address.
+        let start = APP_JS
+            .find("function acceptContactAutocomplete(")
+            .expect("acceptContactAutocomplete must exist");
+        let rest = &APP_JS[start..];
+        let end = rest
+            .find("\n}")
+            .expect("acceptContactAutocomplete must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("contact.email"),
+            "acceptContactAutocomplete must insert the bare contact.email"
+        );
+        assert!(
+            !block.contains("contact.name"),
+            "acceptContactAutocomplete must not compose a 'Name <email>' string into the field"
+        );
+    }
+
+    #[test]
+    fn contact_segment_bounds_shared_by_match_and_accept() {
+        assert!(
+            APP_JS.contains("function contactSegmentBounds("),
+            "a single segment-bounds helper must exist so the matcher (read) \
+             and acceptContactAutocomplete (replace) agree on comma-segment \
+             boundaries — otherwise mid-field edits could replace the wrong span"
+        );
+    }
+
+    #[test]
+    fn contact_autocomplete_html_and_css_wired() {
+        assert!(INDEX_HTML.contains(r#"id="compose-to-autocomplete""#));
+        assert!(INDEX_HTML.contains(r#"id="compose-cc-autocomplete""#));
+        assert!(
+            STYLE_CSS.contains(".contact-autocomplete"),
+            "dropdown container styling must exist"
+        );
+        assert!(
+            STYLE_CSS.contains(".autocomplete-item"),
+            "contact dropdown rows should reuse the .autocomplete-item idiom from #search-autocomplete"
+        );
+    }
+
+    // ====================================================================
+    // Threading / conversation grouping in the desktop list view
+    // (kata 64z6, task B7) — client-side v1. String-invariant tests per
+    // repo convention (no JS harness): they pin the shape of static/app.js
+    // rather than executing it.
+    // ====================================================================
+
+    #[test]
+    fn thread_group_state_present() {
+        // The append-time grouping structure and the expand set both live on
+        // state. threadGroups: Map threadId -> ordered array of email ids;
+        // expandedThreads: Set of threadIds the user has expanded inline.
+        assert!(
+            APP_JS.contains("threadGroups: new Map()"),
+            "state.threadGroups must be a Map (threadId -> ordered email ids), \
+             built at append time (kata 64z6)"
+        );
+        assert!(
+            APP_JS.contains("expandedThreads: new Set()"),
+            "state.expandedThreads must be a Set of expanded threadIds (kata 64z6)"
+        );
+    }
+
+    #[test]
+    fn thread_groups_built_at_append_sites() {
+        // Muratori constraint: the grouping map is built/extended where pages
+        // enter state.emails, never rebuilt per-render. Full replace ->
+        // rebuildThreadGroups; infinite-scroll append -> extendThreadGroups.
+        assert!(
+            APP_JS.contains("function extendThreadGroups(")
+                && APP_JS.contains("function rebuildThreadGroups("),
+            "both an append-extend and a full-rebuild grouping helper must exist"
+        );
+
+        // Full list replace path harvests via rebuild.
+        let start = APP_JS
+            .find("async function loadEmails()")
+            .expect("loadEmails must exist");
+        let rest = &APP_JS[start..];
+        let end = rest
+            .find("\nasync function maybeRefillEmails")
+            .expect("loadEmails must be followed by maybeRefillEmails");
+        assert!(
+            rest[..end].contains("rebuildThreadGroups("),
+            "loadEmails must rebuild thread groups on a full list replace"
+        );
+
+        // Infinite-scroll append path extends the existing groups in place —
+        // at the same concat site pages enter state.emails.
+        let start2 = APP_JS
+            .find("async function maybeRefillEmails()")
+            .expect("maybeRefillEmails must exist");
+        let rest2 = &APP_JS[start2..];
+        let end2 = rest2
+            .find("\nasync function loadEmailDetail")
+            .expect("maybeRefillEmails must be followed by loadEmailDetail");
+        let block = &rest2[..end2];
+        assert!(
+            block.contains("state.emails.concat(newEmails)")
+                && block.contains("extendThreadGroups("),
+            "maybeRefillEmails must extend thread groups at the append site"
+        );
+    }
+
+    #[test]
+    fn visible_rows_derivation_exists_and_keyboard_nav_consumes_it() {
+        // The single seam: selection / auto-advance / undo index into a
+        // derived VISIBLE row model (collapsed thread = 1 row, expanded = row
+        // per member), not into state.emails directly. Keyboard nav must go
+        // through it so a collapsed thread counts as one step.
+        assert!(
+            APP_JS.contains("function visibleRows("),
+            "a visibleRows() derivation must exist as the flat-row seam (kata 64z6)"
+        );
+
+        let start = APP_JS
+            .find("function moveSelection(")
+            .expect("moveSelection must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("moveSelection must close");
+        assert!(
+            rest[..end].contains("visibleRows("),
+            "j/k navigation must index into visibleRows(), not state.emails"
+        );
+    }
+
+    #[test]
+    fn thread_row_shows_count_badge_and_aggregate_flags() {
+        // A thread with 2+ loaded members collapses to one row carrying a
+        // count badge; it reads unread if ANY member is unread and starred if
+        // ANY member is starred (aggregate, computed live from state.emails so
+        // the badge stays accurate after a member is archived/undone).
+        let start = APP_JS
+            .find("function visibleRows(")
+            .expect("visibleRows must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}\n").expect("visibleRows must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("anyUnread") && block.contains("anyStarred"),
+            "a collapsed thread must aggregate unread/starred across members"
+        );
+        assert!(
+            block.contains("kind: 'thread'"),
+            "visibleRows must emit a collapsed-thread row kind"
+        );
+
+        assert!(
+            APP_JS.contains("email-thread-count"),
+            "the collapsed-thread row must render a count-badge element"
+        );
+        let rstart = APP_JS
+            .find("function renderEmailList(")
+            .expect("renderEmailList must exist");
+        let rrest = &APP_JS[rstart..];
+        let rend = rrest.find("\n}\n").expect("renderEmailList must close");
+        assert!(
+            rrest[..rend].contains("row.count"),
+            "the rendered count badge must show the thread's live member count"
+        );
+    }
+
+    #[test]
+    fn undo_reinsert_selects_by_visible_row_not_flat_index() {
+        // The flat-row landmine: performUndo used to set selectedIndex to the
+        // state.emails insert index. Under grouping, selection indexes the
+        // VISIBLE rows, so it must resolve the re-inserted email's visible row.
+        let start = APP_JS
+            .find("async function performUndo()")
+            .expect("performUndo must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}\n").expect("performUndo must close");
+        let block = &rest[..end];
+        assert!(
+            block.contains("visibleRowIndexForEmailId("),
+            "undo must select the re-inserted email's VISIBLE row, not a raw \
+             state.emails index"
+        );
+    }
+
+    #[test]
+    fn list_selection_reads_visible_row_email_id() {
+        // Acting on a collapsed thread row acts on the NEWEST message (v1, no
+        // bulk thread actions): the selected id comes from the visible row.
+        let start = APP_JS
+            .find("function getSelectedEmailId()")
+            .expect("getSelectedEmailId must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("getSelectedEmailId must close");
+        assert!(
+            rest[..end].contains("visibleRows("),
+            "the selected email id (list view) must come from the visible row model"
+        );
+    }
+
+    #[test]
+    fn expanded_member_rows_suppress_date_dividers() {
+        // An expanded thread's member sub-rows are older than the collapsed
+        // header they sit under, so letting them participate in the date-
+        // divider computation injects a stray divider mid-thread AND forces a
+        // duplicate divider for the next non-member row. The divider logic
+        // must skip member rows entirely: no divider emitted for them and
+        // lastGroup must not advance on them.
+        let start = APP_JS
+            .find("function renderEmailList(")
+            .expect("renderEmailList must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}\n").expect("renderEmailList must close");
+        let block = &rest[..end];
+
+        let guard = block
+            .find("!isMember")
+            .expect("the divider computation must be gated off member rows");
+        let advance = block
+            .find("lastGroup = group")
+            .expect("the divider computation must advance lastGroup");
+        assert!(
+            guard < advance,
+            "the member-row guard must wrap the divider/lastGroup advance — \
+             member sub-rows must neither emit a divider nor move lastGroup"
+        );
+    }
+
+    // ====================================================================
+    // Settings view (account management) regression sentinels
+    // ====================================================================
+
+    #[test]
+    fn settings_view_present_in_html() {
+        assert!(INDEX_HTML.contains(r#"id="settings-view""#));
+        assert!(INDEX_HTML.contains(r#"id="account-pane-list""#));
+        assert!(INDEX_HTML.contains(r#"id="account-form""#));
+    }
+
+    #[test]
+    fn settings_form_uses_data_provider_attribute() {
+        // Locality of behaviour: visibility-by-provider rule lives in HTML.
+        assert!(
+            INDEX_HTML.contains(r#"data-provider="fastmail""#),
+            "fastmail-only fields must declare visibility via data-provider"
+        );
+        assert!(
+            INDEX_HTML.contains(r#"data-provider="outlook,gmail""#),
+            "OAuth shared fields must declare visibility via data-provider"
+        );
+    }
+
+    #[test]
+    fn settings_styles_present() {
+        assert!(STYLE_CSS.contains(".account-pane"));
+        assert!(STYLE_CSS.contains(".secret-input"));
+        assert!(STYLE_CSS.contains(".confirm-delete"));
+        assert!(STYLE_CSS.contains("#mode-indicator.awaiting"));
+        assert!(STYLE_CSS.contains(".auth-status-pill"));
+    }
+
+    #[test]
+    fn settings_authorize_uses_long_poll_not_polling() {
+        // Long-poll: one POST to /authorize. No /auth-status polling.
+        assert!(
+            APP_JS.contains("/authorize"),
+            "settings must call POST /authorize"
+        );
+        assert!(
+            !APP_JS.contains("/auth-status"),
+            "must not poll a /auth-status endpoint — long-poll is the state machine"
+        );
+        assert!(APP_JS.contains("AbortController"));
+    }
+
+    #[test]
+    fn settings_first_run_auto_routes() {
+        assert!(
+            APP_JS.contains("openSettings({ firstRun: true })"),
+            "loadAccounts must auto-route to settings when no accounts exist"
+        );
+    }
+
+    #[test]
+    fn help_overlay_documents_new_shortcuts() {
+        assert!(
+            INDEX_HTML.contains(">g s<"),
+            "help overlay should document the g s chord"
+        );
+        assert!(
+            INDEX_HTML.contains(">Shift+D<"),
+            "help overlay should document Shift+D (set default)"
+        );
+        assert!(
+            INDEX_HTML.contains(">Ctrl+Enter<"),
+            "help overlay should document Ctrl+Enter (save)"
+        );
+    }
+
+    #[test]
+    fn load_emails_renders_cached_snapshot_before_network_refresh() {
+        // The Superhuman-style "instant switch" contract: when loadEmails is
+        // called for a mailbox/split/account with a cached entry, the cached
+        // list renders immediately (no awaiting the network). The fresh fetch
+        // races in the background and replaces the snapshot on arrival. Pins
+        // both halves: (1) the cache-hit branch exists, (2) no caller wipes
+        // the cache wholesale — that would force a cold reload on every
+        // switch and defeat the optimization.
+        assert!(
+            APP_JS.contains("if (splitListCache[context]) {"),
+            "loadEmails must render the cached snapshot before awaiting the network"
+        );
+        assert!(
+            !APP_JS.contains("clearSplitListCache()"),
+            "no caller should wipe splitListCache wholesale — keys are already (account, mailbox, split, starred, search)-scoped"
+        );
+    }
+
+    #[test]
+    fn email_caches_are_account_scoped() {
+        // Cross-account isolation is enforced by prefixing every cache key
+        // with the active account id (`cacheKey(emailId)`), not by wiping
+        // caches on account switch. Wiping forced every revisit to refetch
+        // from the provider, which was unusably slow (Gmail in particular
+        // takes seconds per body). The scoped-key approach is both safer
+        // (no leak window between wipe and refill) and preserves state
+        // across switches — returning to an email finds its cached body.
+        assert!(
+            APP_JS.contains("function cacheKey(emailId)"),
+            "static/app.js must define cacheKey() so emailCache/scrollPositions are account-scoped"
+        );
+        assert!(
+            APP_JS.contains("state.currentEmail = null"),
+            "selectAccount must null out state.currentEmail (no cross-account detail residue)"
+        );
+        assert!(
+            !APP_JS.contains("for (const k in emailCache) delete emailCache[k]"),
+            "selectAccount must NOT wipe emailCache — cacheKey() scoping makes the wipe both unnecessary and a performance regression"
+        );
+        assert!(
+            !APP_JS.contains("for (const k in scrollPositions) delete scrollPositions[k]"),
+            "selectAccount must NOT wipe scrollPositions — cacheKey() scoping handles isolation"
+        );
+    }
+
+    #[test]
+    fn api_helper_excludes_settings_from_account_param() {
+        // The shared api client allowlists which paths receive ?account=.
+        // Settings paths (`/accounts/...`) must NOT be auto-tagged.
+        assert!(
+            API_JS.contains("ACCOUNT_SCOPED_API"),
+            "api.js must use an allowlist regex for ?account= injection"
+        );
+        assert!(
+            API_JS.contains(
+                "/(emails|mailboxes|identities|splits|upload|split-counts|calendar|drafts|contacts|threads|muted-threads|flag-colors|suggestions|commands)"
+            ),
+            "allowlist regex must enumerate account-scoped path prefixes"
+        );
+    }
+
+    #[test]
+    fn thread_mark_read_is_account_scoped() {
+        // `/threads/{id}/mark-read` goes through api(), whose regex must
+        // tag it, or a second account's thread is looked up on the default.
+        assert!(
+            APP_JS.contains(
+                "api('POST', `/threads/${encodeURIComponent(email.threadId)}/mark-read`)"
+            )
+        );
+        let regex = API_JS
+            .lines()
+            .find(|l| l.starts_with("const ACCOUNT_SCOPED_API"))
+            .unwrap();
+        assert!(regex.contains("|threads|"), "{regex}");
+    }
+
+    #[test]
+    fn load_splits_goes_through_account_scoped_api_helper() {
+        // A raw fetch skips ?account= and renders every account's tabs.
+        assert!(
+            APP_JS.contains("const splits = await api('GET', '/splits')"),
+            "loadSplits must use the api() helper so ?account= is appended"
+        );
+        assert!(
+            !APP_JS.contains("fetch('/api/splits')"),
+            "loadSplits must not bypass api() with a raw fetch"
+        );
+    }
+
+    #[test]
+    fn load_splits_guards_against_stale_account_switch() {
+        // On rapid account switches, account A's in-flight response can
+        // land after B's and overwrite state.splits while B is active.
+        let start = APP_JS
+            .find("async function loadSplits()")
+            .expect("loadSplits must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("loadSplits must close");
+        let body = &rest[..end];
+        assert!(
+            body.matches("state.currentAccount?.id !== accountId")
+                .count()
+                >= 2,
+            "loadSplits must discard BOTH a stale success and a stale failure — \
+             a failed request from the previous account must not wipe the new \
+             account's already-loaded splits in the catch branch"
+        );
+    }
+
+    #[test]
+    fn init_does_not_call_load_splits_directly() {
+        // init() runs before account selection, so state.currentAccount is
+        // still null — a direct call here fetches the unscoped split list,
+        // which can race and overwrite the account-scoped load triggered by
+        // selectAccount() (called from loadAccounts()).
+        let start = APP_JS.find("function init() {").expect("init must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("init must close");
+        let body = &rest[..end];
+        assert!(
+            !body.contains("loadSplits()"),
+            "init must not call loadSplits() directly; selectAccount() loads it once an account is chosen"
+        );
+    }
+
+    #[test]
+    fn select_account_reloads_splits() {
+        // Tab sets differ per account; switching must rebuild the row.
+        let start = APP_JS
+            .find("function selectAccount")
+            .expect("selectAccount must exist");
+        let rest = &APP_JS[start..];
+        // Slice to the function's own closing brace (first `}` at column
+        // 0) so a match can only come from selectAccount's body, not the
+        // loadSplits() declaration that follows it in the file.
+        let end = rest.find("\n}").expect("selectAccount must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("loadSplits()"),
+            "selectAccount must call loadSplits()"
+        );
+    }
+
+    #[test]
+    fn select_account_resets_stale_split_state() {
+        // Regression test: before loadSplits()'s async response lands, the
+        // previous account's tabs/counts must not linger — otherwise a fast
+        // click into the new account briefly renders the old account's
+        // split tabs and counts.
+        let start = APP_JS
+            .find("function selectAccount")
+            .expect("selectAccount must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("selectAccount must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("state.splits = []"),
+            "selectAccount must clear state.splits before the new account's loadSplits() resolves"
+        );
+        assert!(
+            body.contains("state.splitCounts = {}"),
+            "selectAccount must clear state.splitCounts before the new account's loadSplitCounts() resolves"
+        );
+    }
+
+    // =========================================================================
+    // Desktop sort control (kata 09ef) — buildEmailListUrl appends &sort=,
+    // splitCacheKey discriminates by it (mirrors starredOnly), and
+    // selectAccount resets it to the default like other per-session view state.
+    // =========================================================================
+
+    #[test]
+    fn build_email_list_url_appends_sort_param() {
+        let start = APP_JS
+            .find("function buildEmailListUrl")
+            .expect("buildEmailListUrl must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("buildEmailListUrl must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("sort="),
+            "buildEmailListUrl must append &sort= so the sort control reaches the server"
+        );
+        assert!(
+            body.contains("state.sortOrder"),
+            "buildEmailListUrl must read the sort value from state.sortOrder"
+        );
+    }
+
+    #[test]
+    fn split_cache_key_includes_sort_order() {
+        // Same bug class as the server's prefetch InboxKey: without this,
+        // toggling sort would render a stale, wrong-order splitListCache
+        // entry before the network refresh corrects it, and the refresh
+        // would then overwrite the *other* order's cached entry.
+        let start = APP_JS
+            .find("function splitCacheKey")
+            .expect("splitCacheKey must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("splitCacheKey must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("state.sortOrder"),
+            "splitCacheKey must fold state.sortOrder into the cache key"
+        );
+    }
+
+    #[test]
+    fn render_sort_toggle_flags_gmail_paged_ascending_order() {
+        // Regression test (roborev 291): Gmail's "oldest first" is only
+        // oldest-first *within each fetched page* (see gmail.rs's
+        // apply_sort_order doc comment) — paginating forward still walks
+        // Gmail's underlying pages newest-block-first. Fastmail/Outlook
+        // sort globally, so without a per-provider affordance the desktop
+        // toggle reads identically for a guarantee that's actually weaker
+        // on Gmail. renderSortToggle must call out the Gmail case in both
+        // the visible label and a title attribute.
+        let start = APP_JS
+            .find("function renderSortToggle")
+            .expect("renderSortToggle must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("renderSortToggle must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("state.currentAccount?.provider === 'gmail'"),
+            "renderSortToggle must special-case the Gmail provider"
+        );
+        assert!(
+            body.contains("(per page)"),
+            "renderSortToggle must append a per-page hint to the label for Gmail ascending order"
+        );
+        assert!(
+            body.contains("els.sortToggle.title"),
+            "renderSortToggle must set a title attribute explaining the per-page limitation"
+        );
+    }
+
+    #[test]
+    fn select_account_resets_sort_order_to_default() {
+        let start = APP_JS
+            .find("function selectAccount")
+            .expect("selectAccount must exist");
+        let rest = &APP_JS[start..];
+        let end = rest.find("\n}").expect("selectAccount must close");
+        let body = &rest[..end];
+        assert!(
+            body.contains("state.sortOrder"),
+            "selectAccount must reset state.sortOrder to the default on account switch"
+        );
+    }
+
+    #[test]
+    fn save_split_tags_current_account() {
+        assert!(
+            APP_JS.contains("account: state.currentAccount?.id"),
+            "saveSplit must scope new splits to the active account"
+        );
+    }
+
+    // =========================================================================
+    // resolve_account_id / list_splits — reject unknown account ids (roborev 271)
+    // =========================================================================
+
+    fn test_state(known: &[&str], default_account: &str) -> AppState {
+        let mut account_configs = std::collections::BTreeMap::new();
+        for id in known {
+            account_configs.insert(
+                id.to_string(),
+                accounts::AccountConfig::Fastmail {
+                    username: format!("{id}@example.com"),
+                    api_token: "tok".into(),
+                    signature: None,
+                    proxy: None,
+                },
+            );
+        }
+        AppState {
+            accounts: tokio::sync::RwLock::new(AccountRegistry {
+                sessions: HashMap::new(),
+                account_configs,
+                default_account: default_account.to_string(),
+                default_reminder: None,
+                body_cache_mb: None,
+                read_only: false,
+                http: Default::default(),
+                startup: Default::default(),
+            }),
+            account_errors: tokio::sync::RwLock::new(Vec::new()),
+            splits: splits::SplitsStore::new(
+                std::path::PathBuf::from("/tmp/nonexistent-splits.json"),
+                None,
+            ),
+            timezone_config_path: std::path::PathBuf::from("/tmp/nonexistent-timezone.json"),
+            groups_config_path: std::path::PathBuf::from("/tmp/nonexistent-groups.json"),
+            identities_config_path: std::path::PathBuf::from("/tmp/nonexistent-identities.json"),
+            protected_senders_path: std::path::PathBuf::from(
+                "/tmp/nonexistent-protected-senders.json",
+            ),
+            image_senders_path: std::path::PathBuf::from("/tmp/nonexistent-image-senders.json"),
+            digest_config_path: std::path::PathBuf::from("/tmp/nonexistent-digest.json"),
+            receipts_path: std::path::PathBuf::from("/tmp/nonexistent-receipts.json"),
+            packages_config_path: std::path::PathBuf::from("/tmp/nonexistent-packages.json"),
+            packages_path: std::path::PathBuf::from("/tmp/nonexistent-packages-tracked.json"),
+            mailbox_roles_path: std::path::PathBuf::from("/tmp/nonexistent-mailbox-roles.json"),
+            recently_deleted_path: std::path::PathBuf::from(
+                "/tmp/nonexistent-recently-deleted.json",
+            ),
+            recently_deleted_lock: Default::default(),
+            snoozed_path: std::path::PathBuf::from("/tmp/nonexistent-snoozed.json"),
+            snoozed_lock: Default::default(),
+            priority_lock: Default::default(),
+            newsletters_lock: Default::default(),
+            muted_lock: Default::default(),
+            empty_confirmations: Default::default(),
+            store: Arc::new(crate::storage::FileStore::new(std::path::PathBuf::from(
+                "/tmp/nonexistent-store",
+            ))),
+            perf: Default::default(),
+            update_check: Default::default(),
+            signature_blobs: Default::default(),
+            undo_send: Default::default(),
+            at_rest: Default::default(),
+            send_guard_path: std::path::PathBuf::from("/tmp/nonexistent-send-guard.json"),
+            keybindings_path: std::path::PathBuf::from("/tmp/nonexistent-keybindings.json"),
+            send_hours_path: std::path::PathBuf::from("/tmp/nonexistent-send-hours.json"),
+            compose_sessions_path: std::path::PathBuf::from(
+                "/tmp/nonexistent-compose-sessions.json",
+            ),
+            compose_sessions: Default::default(),
+            blob_cache: Default::default(),
+            email_cache: Default::default(),
+            contacts_path: std::path::PathBuf::from("/tmp/nonexistent-contacts.json"),
+            contacts_lock: Default::default(),
+            timezone_write_lock: tokio::sync::Mutex::new(()),
+            calendar_reply_log: Default::default(),
+            otp: Default::default(),
+            config_path: std::path::PathBuf::from("/tmp/nonexistent-config"),
+            tokens_dir: std::path::PathBuf::from("/tmp/nonexistent-tokens"),
+            token_store: std::sync::Arc::new(crate::platform::FsTokenStore::new(
+                std::path::PathBuf::from("/tmp/nonexistent-tokens"),
+            )),
+            authorizing: accounts::AuthorizingSlot::default(),
+            config_error_baseline: std::sync::RwLock::new(Vec::new()),
+            prefetch: std::sync::Arc::new(crate::prefetch::PrefetchCache::new()),
+            prefetch_cache_path: std::env::temp_dir().join("supervillain-test-prefetch-cache.json"),
+        }
+    }
+
+    // --- permalinks ---
+
+    #[tokio::test]
+    async fn resolve_rejects_malformed_message_id() {
+        let state = test_state(&["a"], "a");
+        let err = resolve_message_id(
+            State(Arc::new(state)),
+            Query(ResolveParams {
+                message_id: "not a message id".into(),
+                account: None,
+            }),
+        )
+        .await
+        .err()
+        .expect("malformed Message-ID must be rejected");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn resolve_not_found_when_no_account_is_connected() {
+        let state = test_state(&["a"], "a");
+        let err = resolve_message_id(
+            State(Arc::new(state)),
+            Query(ResolveParams {
+                message_id: "<x@example.com>".into(),
+                account: None,
+            }),
+        )
+        .await
+        .err()
+        .expect("nothing to search");
+        assert!(matches!(err, Error::NotFound(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn resolve_rejects_unknown_explicit_account() {
+        let state = test_state(&["a"], "a");
+        let err = resolve_message_id(
+            State(Arc::new(state)),
+            Query(ResolveParams {
+                message_id: "<x@example.com>".into(),
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn headers_rejects_unknown_account() {
+        let state = test_state(&["a"], "a");
+        let err = get_headers(
+            State(Arc::new(state)),
+            Path("e1".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn status_reports_read_only_mode() {
+        let mut state = test_state(&["a"], "a");
+        state.accounts.get_mut().read_only = true;
+        let resp = get_status(State(Arc::new(state))).await;
+        let body = axum::body::to_bytes(resp.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["readOnly"], true);
+    }
+
+    #[tokio::test]
+    async fn jmap_passthrough_rejects_unknown_account() {
+        let state = test_state(&["a"], "a");
+        let err = jmap_passthrough(
+            State(Arc::new(state)),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+            Json(JmapPassthroughRequest {
+                method_calls: vec![serde_json::json!(["Email/get", {}, "0"])],
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn raw_message_rejects_unknown_account() {
+        let state = test_state(&["a"], "a");
+        let err = get_raw_message(
+            State(Arc::new(state)),
+            Path("e1".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn compose_context_rejects_unknown_account() {
+        let state = test_state(&["a"], "a");
+        let err = compose_context(
+            State(Arc::new(state)),
+            Path("e1".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn reply_draft_rejects_unknown_mode() {
+        let state = test_state(&["a"], "a");
+        let err = reply_draft(
+            State(Arc::new(state)),
+            Path("e1".into()),
+            Query(ReplyParams {
+                mode: Some("reply-everyone".into()),
+                account: None,
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown mode");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn cancel_event_rejects_unknown_account() {
+        let state = test_state(&["a"], "a");
+        let err = cancel_event(
+            State(Arc::new(state)),
+            Path("uid-1".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn cancel_related_candidates_come_from_list_rows() {
+        let invite = crate::jmap::metadata_only_invite();
+        let mut plain = invite.clone();
+        plain["id"] = "m-plain".into();
+        plain["bodyStructure"] = serde_json::json!({"type": "text/html", "partId": "1"});
+        let rows = crate::jmap::parse_email_list_json(vec![plain, invite], false).await;
+        let ids: Vec<&str> = with_calendar(&rows).iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["m-invite"]);
+    }
+
+    #[tokio::test]
+    async fn event_reply_candidates_come_from_list_rows() {
+        // An attendee's REPLY, fetched without bodies like the responses
+        // lookup fetches it.
+        let mut reply = crate::jmap::metadata_only_invite();
+        reply["id"] = "m-reply".into();
+        reply["subject"] = "Accepted: Standup".into();
+        reply["bodyStructure"] = serde_json::json!({
+            "type": "text/calendar", "partId": "1", "blobId": "b-reply"
+        });
+        let rows = crate::jmap::parse_email_list_json(vec![reply], false).await;
+        assert_eq!(with_calendar(&rows).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_rejects_out_of_range_limit() {
+        for limit in [0, MAX_SEARCH_LIMIT + 1] {
+            let state = test_state(&["a"], "a");
+            let err = search_emails(
+                State(Arc::new(state)),
+                Query(SearchParams {
+                    q: "from:ann".into(),
+                    mailbox_id: None,
+                    limit: Some(limit),
+                    offset: None,
+                    sort: None,
+                    account: None,
+                }),
+            )
+            .await
+            .err()
+            .expect("limit must be rejected");
+            assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+        }
+    }
+
+    async fn changes_error(params: ChangesParams) -> Error {
+        let state = test_state(&["a"], "a");
+        email_changes(State(Arc::new(state)), Query(params))
+            .await
+            .err()
+            .expect("request must be rejected")
+    }
+
+    #[tokio::test]
+    async fn changes_rejects_out_of_range_max_changes() {
+        for max in [0, MAX_CHANGES + 1] {
+            let err = changes_error(ChangesParams {
+                since: Some("s1".into()),
+                account: None,
+                max_changes: Some(max),
+            })
+            .await;
+            assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_rejects_empty_since() {
+        let err = changes_error(ChangesParams {
+            since: Some(String::new()),
+            account: None,
+            max_changes: None,
+        })
+        .await;
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn changes_rejects_account_without_session() {
+        let err = changes_error(ChangesParams {
+            since: None,
+            account: None,
+            max_changes: None,
+        })
+        .await;
+        assert!(
+            matches!(err, Error::BadRequest(ref m) if m.contains("Unknown account")),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn index_html_uses_absolute_asset_paths() {
+        // /m/{id} and /search serve index.html from a nested path; relative
+        // asset URLs would resolve under /m/ and 404.
+        for asset in ["/style.css", "/api.js", "/app.js"] {
+            assert!(
+                INDEX_HTML.contains(&format!("\"{asset}\"")),
+                "index.html must reference {asset} absolutely"
+            );
+        }
+    }
+
+    // --- command registry ---
+
+    #[tokio::test]
+    async fn list_commands_without_focus_marks_message_commands_inapplicable() {
+        let state = test_state(&["a"], "a");
+        let resp = list_commands(State(Arc::new(state)), Query(CommandsParams::default()))
+            .await
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let commands = json["commands"].as_array().unwrap();
+        let find = |id: &str| commands.iter().find(|c| c["id"] == id).unwrap();
+        assert_eq!(find("compose")["applicable"], true);
+        assert_eq!(find("archive")["applicable"], false);
+        assert_eq!(find("unsubscribe")["key"], "U");
+    }
+
+    #[tokio::test]
+    async fn list_commands_uses_cached_focused_message() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        email.id = "m1".into();
+        email.list_unsubscribe = Some("<mailto:u@example.com>".into());
+        state.prefetch.set_body("a", "m1".into(), email).await;
+        // No live session exists for "a" in test_state, so this only passes
+        // if the handler is answered from the prefetch cache.
+        let resp = list_commands(
+            State(state),
+            Query(CommandsParams {
+                account: Some("a".into()),
+                email_id: Some("m1".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        let body = axum::body::to_bytes(resp.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let commands = json["commands"].as_array().unwrap();
+        let unsub = commands.iter().find(|c| c["id"] == "unsubscribe").unwrap();
+        assert_eq!(unsub["applicable"], true);
+    }
+
+    #[test]
+    fn command_registry_keys_are_bound_in_app_js() {
+        // The registry is the shortcut table; every key it advertises must
+        // have a matching case in the desktop keydown handler.
+        for c in commands::COMMANDS {
+            if let Some(key) = c.key {
+                assert!(
+                    APP_JS.contains(&format!("case '{key}':")),
+                    "registry key {key:?} for {} has no handler in app.js",
+                    c.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn app_js_runs_remapped_keys_through_the_registry() {
+        let handler = &APP_JS[APP_JS.find("function handleNormalModeKey").unwrap()..];
+        let remap = handler.find("state.keyRemap.has(key)").unwrap();
+        assert!(
+            remap < handler.find("switch (key)").unwrap(),
+            "remapped keys must be looked up before the built-in switch"
+        );
+        assert!(APP_JS.contains("c.key !== c.defaultKey"));
+        assert!(APP_JS.contains("executeCommand(command)"));
+    }
+
+    // --- /body/full part selection ---
+
+    fn email_with_parts(parts: &[(&str, &str, bool)]) -> Email {
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        email.body_parts = parts
+            .iter()
+            .map(|(id, kind, truncated)| BodyPart {
+                part_id: id.to_string(),
+                blob_id: Some(format!("blob-{id}")),
+                kind: kind.to_string(),
+                mime_type: format!("text/{kind}"),
+                charset: None,
+                size: 10,
+                is_truncated: *truncated,
+            })
+            .collect();
+        email
+    }
+
+    #[test]
+    fn full_body_prefers_truncated_html_part() {
+        let email = email_with_parts(&[("1", "text", true), ("2", "html", true)]);
+        let part = select_full_body_part(&email, None).unwrap().unwrap();
+        assert_eq!(part.part_id, "2");
+    }
+
+    #[test]
+    fn full_body_falls_back_to_truncated_text_part() {
+        let email = email_with_parts(&[("1", "text", true), ("2", "html", false)]);
+        let part = select_full_body_part(&email, None).unwrap().unwrap();
+        assert_eq!(part.part_id, "1");
+    }
+
+    #[test]
+    fn full_body_none_when_nothing_truncated() {
+        let email = email_with_parts(&[("1", "text", false)]);
+        assert!(select_full_body_part(&email, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn full_body_explicit_part_must_exist() {
+        let email = email_with_parts(&[("1", "text", false)]);
+        assert_eq!(
+            select_full_body_part(&email, Some("1"))
+                .unwrap()
+                .unwrap()
+                .part_id,
+            "1"
+        );
+        assert!(matches!(
+            select_full_body_part(&email, Some("9")),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_account_id_rejects_unknown_account() {
+        let state = test_state(&["known"], "known");
+        let err = resolve_account_id(&state, Some("typo"))
+            .await
+            .expect_err("an id absent from account_configs must be rejected");
+        assert!(
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
+            "expected a BadRequest naming the unknown account, got {err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_account_id_accepts_known_account() {
+        let state = test_state(&["known"], "known");
+        let id = resolve_account_id(&state, Some("known"))
+            .await
+            .expect("a configured account must resolve");
+        assert_eq!(id, "known");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::response::IntoResponse;
+    #[tokio::test]
+    async fn resolve_account_id_accepts_shared_accounts_of_known_ones() {
+        let state = test_state(&["known"], "known");
+        let id = resolve_account_id(&state, Some("known#u2")).await.unwrap();
+        assert_eq!(id, "known#u2");
+        assert!(resolve_account_id(&state, Some("typo#u2")).await.is_err());
+    }
 
     #[tokio::test]
-    async fn index_html_contains_html() {
-        let resp = index_html().await.into_response();
-        assert_eq!(resp.status(), StatusCode::OK);
-        let ct = resp
-            .headers()
-            .get("content-type")
-            .unwrap()
-            .to_str()
-            .unwrap();
-        assert_eq!(ct, "text/html; charset=utf-8");
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+    async fn resolve_account_id_default_none_still_works() {
+        // The None (default-account) path must keep working — default_account
+        // is always kept in sync with account_configs by the mutation
+        // handlers, so this can't regress into a false rejection.
+        let state = test_state(&["known"], "known");
+        let id = resolve_account_id(&state, None)
             .await
-            .unwrap();
-        let text = std::str::from_utf8(&body).unwrap();
-        assert!(
-            text.contains("<html"),
-            "index.html should contain <html tag"
-        );
+            .expect("omitting ?account= must fall back to the default account");
+        assert_eq!(id, "known");
     }
 
     #[tokio::test]
-    async fn app_js_contains_javascript() {
-        let resp = app_js().await.into_response();
-        assert_eq!(resp.status(), StatusCode::OK);
-        let ct = resp
-            .headers()
-            .get("content-type")
-            .unwrap()
-            .to_str()
-            .unwrap();
-        assert_eq!(ct, "application/javascript; charset=utf-8");
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+    async fn list_splits_rejects_unknown_account_param() {
+        let state = Arc::new(test_state(&["known"], "known"));
+        let params = ListSplitsParams {
+            account: Some("typo".into()),
+        };
+        let err = list_splits(State(state), Query(params))
             .await
-            .unwrap();
-        assert!(!body.is_empty(), "app.js should not be empty");
+            .err()
+            .expect("an unknown ?account= must 400, not silently scope to untagged splits");
+        assert!(
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
+            "expected a BadRequest naming the unknown account, got {err:?}"
+        );
     }
 
     #[tokio::test]
-    async fn style_css_contains_css() {
-        let resp = style_css().await.into_response();
-        assert_eq!(resp.status(), StatusCode::OK);
-        let ct = resp
-            .headers()
-            .get("content-type")
-            .unwrap()
-            .to_str()
-            .unwrap();
-        assert_eq!(ct, "text/css; charset=utf-8");
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+    async fn list_splits_without_account_param_is_unaffected() {
+        // No ?account= → full list (management/debugging view); this must
+        // stay reachable even though it never hits the new validation gate.
+        let state = Arc::new(test_state(&["known"], "known"));
+        let params = ListSplitsParams { account: None };
+        let resp = list_splits(State(state), Query(params))
             .await
-            .unwrap();
-        assert!(!body.is_empty(), "style.css should not be empty");
+            .expect("omitting ?account= must not be rejected")
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    // =========================================================================
+    // GetEmailParams deserialization (roborev 285) — mark_read opt-out lets
+    // mobile's prefetch warm the body cache without consuming unread state.
+    // =========================================================================
+
     #[test]
-    fn identity_serialization_preserves_email_field() {
-        let identity = crate::types::Identity {
-            id: "id1".into(),
-            email: "test@example.com".into(),
-            name: "Test User".into(),
-        };
-        let json = serde_json::to_string(&identity).unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
-        assert_eq!(parsed["email"], "test@example.com");
-        assert_eq!(parsed["name"], "Test User");
+    fn update_mailbox_body_tells_null_parent_from_absent() {
+        let body: UpdateMailboxBody = serde_json::from_str(r#"{"name":"Receipts"}"#).unwrap();
+        assert_eq!(body.parent_id, None);
+        let body: UpdateMailboxBody = serde_json::from_str(r#"{"parent_id":null}"#).unwrap();
+        assert_eq!(body.parent_id, Some(None));
+        let body: UpdateMailboxBody = serde_json::from_str(r#"{"parent_id":"mb-1"}"#).unwrap();
+        assert_eq!(body.parent_id, Some(Some("mb-1".into())));
     }
 
     #[test]
-    fn safe_path_segment_rejects_traversal() {
-        assert!(!is_safe_path_segment("../etc/passwd"));
-        assert!(!is_safe_path_segment(".."));
-        assert!(!is_safe_path_segment("."));
-        assert!(!is_safe_path_segment("foo/bar"));
-        assert!(!is_safe_path_segment("foo\\bar"));
-        assert!(!is_safe_path_segment(""));
-        assert!(!is_safe_path_segment("foo\0bar"));
+    fn mailbox_name_is_trimmed_and_flat() {
+        assert_eq!(mailbox_name("  Receipts ").unwrap(), "Receipts");
+        assert!(mailbox_name("   ").is_err());
+        assert!(mailbox_name("Work/Receipts").is_err());
     }
 
     #[test]
-    fn safe_path_segment_accepts_valid() {
-        assert!(is_safe_path_segment("blob-abc123"));
-        assert!(is_safe_path_segment("report.pdf"));
-        assert!(is_safe_path_segment("G1234abcdef"));
-        assert!(is_safe_path_segment("file..backup.pdf"));
+    fn get_email_params_mark_read_absent_defaults_to_read_semantics() {
+        let uri: axum::http::Uri = "/api/emails/e1".parse().unwrap();
+        let Query(params) = Query::<GetEmailParams>::try_from_uri(&uri)
+            .expect("no query string at all must still deserialize");
+        assert_eq!(params.mark_read, None);
+        assert!(
+            params.mark_read.unwrap_or(true),
+            "absent mark_read must preserve the current (auto-mark-read) behavior"
+        );
     }
 
     #[test]
-    fn sanitize_filename_strips_dangerous_chars() {
-        assert_eq!(sanitize_filename_for_header("normal.pdf"), "normal.pdf");
-        assert_eq!(sanitize_filename_for_header("file\".txt"), "file.txt");
-        assert_eq!(
-            sanitize_filename_for_header("file\r\ninjected"),
-            "fileinjected"
-        );
-        assert_eq!(
-            sanitize_filename_for_header("file\\\"name.txt"),
-            "filename.txt"
-        );
+    fn get_email_params_mark_read_false_parses() {
+        let uri: axum::http::Uri = "/api/emails/e1?mark_read=false".parse().unwrap();
+        let Query(params) =
+            Query::<GetEmailParams>::try_from_uri(&uri).expect("mark_read=false must deserialize");
+        assert_eq!(params.mark_read, Some(false));
     }
 
     #[test]
-    fn compose_defaults_to_first_identity() {
-        assert!(
-            APP_JS.contains("state.identities[0].email"),
-            "clearCompose should default to the first identity's email"
-        );
+    fn get_email_params_mark_read_false_with_account_parses() {
+        // Mirrors the URL mobile actually sends: mark_read=false alongside
+        // ?account=, joined with '&' since the path already has a '?'.
+        let uri: axum::http::Uri = "/api/emails/e1?mark_read=false&account=work"
+            .parse()
+            .unwrap();
+        let Query(params) = Query::<GetEmailParams>::try_from_uri(&uri)
+            .expect("mark_read=false&account=... must deserialize");
+        assert_eq!(params.mark_read, Some(false));
+        assert_eq!(params.account.as_deref(), Some("work"));
     }
 
     #[test]
-    fn app_js_signature_prefilled_via_clear_compose() {
-        // clearCompose is the single choke point startCompose/startReply/
-        // startForward all call first, so prefilling there covers new,
-        // reply, and forward uniformly — this pins that the prefill lives
-        // there rather than being duplicated (or, worse, injected at send
-        // time, which the design explicitly forbids: the user must see
-        // exactly what sends).
-        let start = APP_JS
-            .find("function clearCompose()")
-            .expect("clearCompose must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("clearCompose must close");
+    fn get_email_keeps_prefetch_off_the_interactive_lane() {
+        // provider::get_emails' reserved lane is "only for fetches a user is
+        // actively waiting on … never for bulk fan-outs" — but both bundles'
+        // prefetchAdjacentEmails hit this same route fire-and-forget (three
+        // full-body fetches per email open). mark_read=false is the prefetch
+        // signature, so the handler must derive the lane from it instead of
+        // hardcoding priority for every caller (roborev 315).
+        let src = include_str!("routes.rs");
+        let handler_src = src.split("mod tests").next().unwrap_or(src);
+        let start = handler_src
+            .find("async fn get_email(")
+            .expect("get_email must exist");
+        let rest = &handler_src[start..];
+        let end = rest.find("\n}").expect("get_email must close");
         let block = &rest[..end];
         assert!(
-            block.contains("composeSignaturePrefill()"),
-            "clearCompose must prefill the body via composeSignaturePrefill()"
-        );
-        assert!(
-            block.contains("setSelectionRange(0, 0)"),
-            "clearCompose must place the cursor at position 0 after prefilling"
-        );
-        assert!(
-            APP_JS.contains("function composeSignaturePrefill"),
-            "composeSignaturePrefill helper must exist"
+            block.contains("let priority = params.mark_read.unwrap_or(true)"),
+            "get_email must derive the interactive-lane flag from mark_read"
         );
         assert!(
-            APP_JS.contains("-- \\n"),
-            "signature prefill must use the RFC 3676 delimiter '-- ' \
-             (trailing space significant) before a newline"
+            !block.contains("None, true)"),
+            "the fetch closure must pass the derived flag, not hardcode priority"
         );
     }
 
-    // ====================================================================
-    // Contact autocomplete on To/Cc (kata e64s, task B6) — client-side only,
-    // no server surface. These are string-invariant tests per repo
-    // convention (no JS harness): they pin the shape of static/app.js
-    // rather than executing it.
-    // ====================================================================
+    // =========================================================================
+    // ListEmailsParams sort deserialization (kata 09ef) — accept both known
+    // values and absence, hard-reject anything else so a typo'd sort=
+    // param can never silently fall back to the default order.
+    // =========================================================================
 
     #[test]
-    fn contact_index_state_present() {
-        assert!(
-            APP_JS.contains("contactIndex: new Map()"),
-            "state.contactIndex must be a Map, built by harvesting from \
-             loaded email list pages and the Sent mailbox (kata e64s)"
+    fn list_emails_params_sort_absent_deserializes_to_none() {
+        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox".parse().unwrap();
+        let Query(params) = Query::<ListEmailsParams>::try_from_uri(&uri)
+            .expect("no sort param must still deserialize");
+        assert_eq!(params.sort, None);
+        assert_eq!(
+            params.sort.unwrap_or_default(),
+            EmailSort::DateDesc,
+            "absent sort must resolve to today's default (newest-first) behavior"
         );
     }
 
     #[test]
-    fn contact_index_is_account_scoped() {
-        // selectAccount's documented convention: caches are account-scoped
-        // (splitListCache/emailCache/scrollPositions all key by account and
-        // are never shared across a switch). The contact index must follow
-        // it — Account A's contacts leaking into Account B's compose would
-        // cross-pollinate address books. Pins: a per-account accessor
-        // exists, harvest writes through it, and rankContactMatches only
-        // reads the CURRENT account's entries.
-        assert!(
-            APP_JS.contains("function contactIndexFor("),
-            "a per-account contact-map accessor must exist"
-        );
-
-        let start = APP_JS
-            .find("function harvestContacts(")
-            .expect("harvestContacts must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("harvestContacts must close");
-        assert!(
-            rest[..end].contains("contactIndexFor(accountId)"),
-            "harvestContacts must write into the harvested account's own map"
-        );
-
-        let start2 = APP_JS
-            .find("function rankContactMatches(")
-            .expect("rankContactMatches must exist");
-        let rest2 = &APP_JS[start2..];
-        let end2 = rest2.find("\n}").expect("rankContactMatches must close");
-        assert!(
-            rest2[..end2].contains("state.currentAccount"),
-            "rankContactMatches must only surface the current account's contacts"
-        );
+    fn list_emails_params_sort_date_desc_parses() {
+        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=date_desc"
+            .parse()
+            .unwrap();
+        let Query(params) =
+            Query::<ListEmailsParams>::try_from_uri(&uri).expect("sort=date_desc must deserialize");
+        assert_eq!(params.sort, Some(EmailSort::DateDesc));
     }
 
     #[test]
-    fn contact_rank_function_sorts_by_count_then_last_seen_and_excludes_self() {
-        let start = APP_JS
-            .find("function rankContactMatches(")
-            .expect("a pure rank/match helper must exist for contact autocomplete");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("rankContactMatches must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("b.count - a.count"),
-            "contacts must rank by frequency count descending first"
-        );
-        assert!(
-            block.contains("lastSeen") && block.contains("localeCompare"),
-            "ties must break by lastSeen descending"
-        );
-        assert!(
-            block.contains("ownIdentityEmails"),
-            "rank/match must exclude the account's own identity addresses"
-        );
+    fn list_emails_params_sort_date_asc_parses() {
+        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=date_asc"
+            .parse()
+            .unwrap();
+        let Query(params) =
+            Query::<ListEmailsParams>::try_from_uri(&uri).expect("sort=date_asc must deserialize");
+        assert_eq!(params.sort, Some(EmailSort::DateAsc));
     }
 
     #[test]
-    fn contact_harvest_hooks_list_load_and_refill_paths() {
+    fn list_emails_params_sort_garbage_is_rejected() {
+        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=banana".parse().unwrap();
+        let result = Query::<ListEmailsParams>::try_from_uri(&uri);
         assert!(
-            APP_JS.contains("function harvestContacts("),
-            "harvestContacts must exist to build the contact index from loaded emails"
+            result.is_err(),
+            "an unrecognized sort value must be a 400, not a silent default"
         );
+    }
 
-        // Primary list-load path.
-        let start = APP_JS
-            .find("async function loadEmails()")
-            .expect("loadEmails must exist");
-        let rest = &APP_JS[start..];
-        let end = rest
-            .find("\nasync function maybeRefillEmails")
-            .expect("loadEmails must be followed by maybeRefillEmails");
-        assert!(
-            rest[..end].contains("harvestContacts("),
-            "loadEmails must harvest contacts from the fetched page"
-        );
+    // =========================================================================
+    // Response compression predicate
+    // =========================================================================
 
-        // Infinite-scroll append path.
-        let start2 = APP_JS
-            .find("async function maybeRefillEmails()")
-            .expect("maybeRefillEmails must exist");
-        let rest2 = &APP_JS[start2..];
-        let end2 = rest2
-            .find("\nasync function loadEmailDetail")
-            .expect("maybeRefillEmails must be followed by loadEmailDetail");
-        assert!(
-            rest2[..end2].contains("harvestContacts("),
-            "maybeRefillEmails must harvest contacts from newly appended emails"
-        );
+    #[test]
+    fn compression_applies_to_json_only() {
+        let with_type = |ct: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                axum::http::header::CONTENT_TYPE,
+                axum::http::HeaderValue::from_str(ct).unwrap(),
+            );
+            headers
+        };
+        assert!(is_json_response(&with_type("application/json")));
+        assert!(is_json_response(&with_type(
+            "application/json; charset=utf-8"
+        )));
+        assert!(!is_json_response(&with_type("text/event-stream")));
+        assert!(!is_json_response(&with_type("application/pdf")));
+        assert!(!is_json_response(&with_type("text/html; charset=utf-8")));
+        assert!(!is_json_response(&HeaderMap::new()));
     }
 
+    // =========================================================================
+    // /api/emails paging metadata and Link header
+    // =========================================================================
+
     #[test]
-    fn sent_mailbox_background_harvest_degrades_silently() {
-        let start = APP_JS
-            .find("function harvestSentContactsOnce")
-            .expect("harvestSentContactsOnce must exist");
-        let rest = &APP_JS[start..];
-        let end = rest
-            .find("\n}")
-            .expect("harvestSentContactsOnce must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("role === 'sent'"),
-            "must resolve the Sent mailbox from state.mailboxes by role"
-        );
-        assert!(
-            block.contains("console.warn"),
-            "a failed Sent-mailbox fetch must degrade silently (console.warn only)"
-        );
-        assert!(
-            !block.contains("showStatus"),
-            "a failed Sent-mailbox fetch must not surface an error banner to the user"
+    fn list_paging_full_window_has_more() {
+        let paging = list_paging(50, 50, 50, false, Some("e99"));
+        assert_eq!(
+            paging,
+            ListPaging {
+                next_offset: Some(100),
+                has_more: true,
+                anchor: Some("e99".into()),
+            }
         );
     }
 
     #[test]
-    fn contact_dropdown_keydown_scoped_to_focused_input_when_open() {
-        // The compose insert-mode key handler must only intercept dropdown
-        // navigation keys when the contact dropdown is actually open AND the
-        // event target is the To/Cc input it belongs to — otherwise it must
-        // fall through untouched to the existing Escape/Ctrl+Enter handling
-        // (autosave/send-lock from kata wm57 must not be disturbed).
-        let start = APP_JS
-            .find("state.view === 'compose' && state.mode === 'insert'")
-            .expect("compose insert-mode key handler must exist");
-        let rest = &APP_JS[start..];
-        let end = rest
-            .find("\n    // Compose normal-mode:")
-            .expect("compose insert-mode block must be followed by the normal-mode 'a' handler");
-        let block = &rest[..end];
-        assert!(
-            block.contains("state.contactAcField"),
-            "compose keydown must gate contact-dropdown key handling on an open dropdown"
-        );
-        assert!(
-            block.contains("e.target === els.composeTo")
-                || block.contains("e.target === els.composeCc"),
-            "contact-dropdown key handling must be scoped to the focused To/Cc input"
-        );
-        assert!(
-            block.contains("sendEmail()"),
-            "existing Ctrl+Enter send handling must still be present"
-        );
+    fn list_paging_short_window_is_last_page() {
+        let paging = list_paging(50, 50, 12, false, Some("e61"));
+        assert!(!paging.has_more);
+        assert_eq!(paging.next_offset, None);
     }
 
     #[test]
-    fn contact_autocomplete_inserts_bare_email_not_display_name() {
-        // To/Cc are parsed downstream as plain comma-separated address
-        // strings (see sendEmail / build_draft_email) — inserting a
-        // "Name <email>" form here would ship as a literally-invalid
-        // recipient address.
-        let start = APP_JS
-            .find("function acceptContactAutocomplete(")
-            .expect("acceptContactAutocomplete must exist");
-        let rest = &APP_JS[start..];
-        let end = rest
-            .find("\n}")
-            .expect("acceptContactAutocomplete must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("contact.email"),
-            "acceptContactAutocomplete must insert the bare contact.email"
-        );
-        assert!(
-            !block.contains("contact.name"),
-            "acceptContactAutocomplete must not compose a 'Name <email>' string into the field"
-        );
+    fn list_paging_truncated_split_resumes_after_last_kept_row() {
+        // A split page cut at `limit` resumes mid-window even though the
+        // provider didn't fill it.
+        let paging = list_paging(0, 500, 37, true, Some("e36"));
+        assert!(paging.has_more);
+        assert_eq!(paging.next_offset, Some(37));
     }
 
     #[test]
-    fn contact_segment_bounds_shared_by_match_and_accept() {
-        assert!(
-            APP_JS.contains("function contactSegmentBounds("),
-            "a single segment-bounds helper must exist so the matcher (read) \
-             and acceptContactAutocomplete (replace) agree on comma-segment \
-             boundaries — otherwise mid-field edits could replace the wrong span"
+    fn list_link_header_replaces_offset_and_keeps_other_params() {
+        let uri: Uri = "/api/emails?mailbox_id=inbox&offset=50&limit=50&search=from%3Abob"
+            .parse()
+            .unwrap();
+        let paging = list_paging(50, 50, 50, false, None);
+        let link = list_link_header(&uri, 50, 50, &paging).unwrap();
+        assert_eq!(
+            link,
+            "</api/emails?mailbox_id=inbox&limit=50&search=from%3Abob&offset=100>; rel=\"next\", \
+             </api/emails?mailbox_id=inbox&limit=50&search=from%3Abob&offset=0>; rel=\"prev\""
         );
     }
 
     #[test]
-    fn contact_autocomplete_html_and_css_wired() {
-        assert!(INDEX_HTML.contains(r#"id="compose-to-autocomplete""#));
-        assert!(INDEX_HTML.contains(r#"id="compose-cc-autocomplete""#));
-        assert!(
-            STYLE_CSS.contains(".contact-autocomplete"),
-            "dropdown container styling must exist"
-        );
-        assert!(
-            STYLE_CSS.contains(".autocomplete-item"),
-            "contact dropdown rows should reuse the .autocomplete-item idiom from #search-autocomplete"
-        );
+    fn list_link_header_absent_on_single_page() {
+        let uri: Uri = "/api/emails?mailbox_id=inbox".parse().unwrap();
+        let paging = list_paging(0, 50, 3, false, None);
+        assert_eq!(list_link_header(&uri, 0, 50, &paging), None);
     }
 
-    // ====================================================================
-    // Threading / conversation grouping in the desktop list view
-    // (kata 64z6, task B7) — client-side v1. String-invariant tests per
-    // repo convention (no JS harness): they pin the shape of static/app.js
-    // rather than executing it.
-    // ====================================================================
+    // =========================================================================
+    // list_is_cacheable sort gating (roborev 291)
+    // =========================================================================
+
+    fn cacheable_shape_params(sort: Option<EmailSort>) -> ListEmailsParams {
+        ListEmailsParams {
+            mailbox_id: Some("inbox".into()),
+            limit: None,
+            offset: None,
+            split_id: None,
+            search: None,
+            account: None,
+            starred: None,
+            sort,
+            paging: None,
+            window: None,
+            anchor: None,
+            anchor_offset: None,
+        }
+    }
 
     #[test]
-    fn thread_group_state_present() {
-        // The append-time grouping structure and the expand set both live on
-        // state. threadGroups: Map threadId -> ordered array of email ids;
-        // expandedThreads: Set of threadIds the user has expanded inline.
-        assert!(
-            APP_JS.contains("threadGroups: new Map()"),
-            "state.threadGroups must be a Map (threadId -> ordered email ids), \
-             built at append time (kata 64z6)"
-        );
+    fn list_is_cacheable_true_for_default_shape_and_sort() {
+        let params = cacheable_shape_params(None);
         assert!(
-            APP_JS.contains("expandedThreads: new Set()"),
-            "state.expandedThreads must be a Set of expanded threadIds (kata 64z6)"
+            list_is_cacheable(&params, 0, EmailSort::DateDesc),
+            "default-inbox shape with default sort must remain cacheable"
         );
     }
 
     #[test]
-    fn thread_groups_built_at_append_sites() {
-        // Muratori constraint: the grouping map is built/extended where pages
-        // enter state.emails, never rebuilt per-render. Full replace ->
-        // rebuildThreadGroups; infinite-scroll append -> extendThreadGroups.
+    fn list_is_cacheable_false_for_date_asc_sort() {
+        // Regression test (roborev 291): a DateAsc request used to populate
+        // its own InboxKey slot, keyed apart from the warmer's DateDesc
+        // entry (kata 09ef). But the background warmer only ever re-warms
+        // the DateDesc slot and there's no TTL, so that DateAsc slot was
+        // never refreshed — a user sitting in "Oldest first" would never
+        // see new mail until some unrelated local mutation invalidated the
+        // whole account's cache. The fix: non-default sorts are no longer
+        // cacheable at all, so they always fetch live (no read, no write).
+        // DateDesc behavior is untouched — see the sibling
+        // `list_is_cacheable_true_for_default_shape_and_sort` test.
+        let params = cacheable_shape_params(Some(EmailSort::DateAsc));
         assert!(
-            APP_JS.contains("function extendThreadGroups(")
-                && APP_JS.contains("function rebuildThreadGroups("),
-            "both an append-extend and a full-rebuild grouping helper must exist"
+            !list_is_cacheable(&params, 0, EmailSort::DateAsc),
+            "a DateAsc request must always bypass the prefetch cache"
         );
+    }
 
-        // Full list replace path harvests via rebuild.
-        let start = APP_JS
-            .find("async function loadEmails()")
-            .expect("loadEmails must exist");
-        let rest = &APP_JS[start..];
-        let end = rest
-            .find("\nasync function maybeRefillEmails")
-            .expect("loadEmails must be followed by maybeRefillEmails");
-        assert!(
-            rest[..end].contains("rebuildThreadGroups("),
-            "loadEmails must rebuild thread groups on a full list replace"
-        );
+    #[test]
+    fn list_is_cacheable_still_false_for_non_default_shape() {
+        // Sanity check that extracting `list_is_cacheable` didn't change
+        // the pre-existing (non-sort) gating conditions.
+        let mut params = cacheable_shape_params(None);
+        params.split_id = Some("primary".into());
+        assert!(!list_is_cacheable(&params, 0, EmailSort::DateDesc));
 
-        // Infinite-scroll append path extends the existing groups in place —
-        // at the same concat site pages enter state.emails.
-        let start2 = APP_JS
-            .find("async function maybeRefillEmails()")
-            .expect("maybeRefillEmails must exist");
-        let rest2 = &APP_JS[start2..];
-        let end2 = rest2
-            .find("\nasync function loadEmailDetail")
-            .expect("maybeRefillEmails must be followed by loadEmailDetail");
-        let block = &rest2[..end2];
+        let mut params = cacheable_shape_params(None);
+        params.starred = Some(true);
+        assert!(!list_is_cacheable(&params, 0, EmailSort::DateDesc));
+
+        let params = cacheable_shape_params(None);
         assert!(
-            block.contains("state.emails.concat(newEmails)")
-                && block.contains("extendThreadGroups("),
-            "maybeRefillEmails must extend thread groups at the append site"
+            !list_is_cacheable(&params, 10, EmailSort::DateDesc),
+            "non-zero offset must not be cacheable"
         );
     }
 
+    // =========================================================================
+    // Virtual mailboxes
+    // =========================================================================
+
     #[test]
-    fn visible_rows_derivation_exists_and_keyboard_nav_consumes_it() {
-        // The single seam: selection / auto-advance / undo index into a
-        // derived VISIBLE row model (collapsed thread = 1 row, expanded = row
-        // per member), not into state.emails directly. Keyboard nav must go
-        // through it so a collapsed thread counts as one step.
-        assert!(
-            APP_JS.contains("function visibleRows("),
-            "a visibleRows() derivation must exist as the flat-row seam (kata 64z6)"
+    fn virtual_mailbox_parse_known_unknown_and_real() {
+        assert_eq!(
+            VirtualMailbox::parse("virtual:flagged").unwrap().unwrap(),
+            VirtualMailbox::Flagged
         );
+        assert_eq!(
+            VirtualMailbox::parse("virtual:attachments")
+                .unwrap()
+                .unwrap(),
+            VirtualMailbox::Attachments
+        );
+        assert!(matches!(
+            VirtualMailbox::parse("virtual:nope"),
+            Some(Err(Error::BadRequest(_)))
+        ));
+        assert!(VirtualMailbox::parse("inbox-id").is_none());
+    }
 
-        let start = APP_JS
-            .find("function moveSelection(")
-            .expect("moveSelection must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("moveSelection must close");
+    #[test]
+    fn virtual_mailbox_filters() {
+        let mut query = crate::types::ParsedQuery::default();
+        VirtualMailbox::Attachments.apply(&mut query);
+        assert!(query.has_attachment);
+        VirtualMailbox::Flagged.apply(&mut query);
+        assert_eq!(query.is_flagged, Some(true));
+
+        // Calendar has no provider filter; it post-filters instead.
+        let mut query = crate::types::ParsedQuery::default();
+        VirtualMailbox::Calendar.apply(&mut query);
+        assert!(query.is_empty());
+        let mut invite = test_email_with_recipients(vec![], vec![]);
+        assert!(!VirtualMailbox::Calendar.keeps(&invite));
+        assert!(VirtualMailbox::Flagged.keeps(&invite));
+        invite.has_calendar = true;
+        assert!(VirtualMailbox::Calendar.keeps(&invite));
+    }
+
+    #[tokio::test]
+    async fn virtual_calendar_keeps_invites_from_list_rows() {
+        // List rows come from a metadata-only fetch; run them through the
+        // real parser rather than setting `has_calendar` by hand.
+        let invite = crate::jmap::metadata_only_invite();
+        let mut plain = invite.clone();
+        plain["id"] = "m-plain".into();
+        plain["bodyStructure"] = serde_json::json!({"type": "text/plain", "partId": "1"});
+        let mut rows = crate::jmap::parse_email_list_json(vec![invite, plain], false).await;
+        rows.retain(|e| VirtualMailbox::Calendar.keeps(e));
+        let ids: Vec<&str> = rows.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["m-invite"]);
+    }
+
+    #[test]
+    fn list_is_cacheable_false_for_virtual_mailbox() {
+        let mut params = cacheable_shape_params(None);
+        params.mailbox_id = Some("virtual:flagged".into());
+        assert!(!list_is_cacheable(&params, 0, EmailSort::DateDesc));
+    }
+
+    #[tokio::test]
+    async fn list_emails_unknown_virtual_mailbox_is_bad_request() {
+        // No sessions are registered, so reaching the provider would fail
+        // with "Unknown account" instead.
+        let state = test_state(&["known"], "known");
+        let uri: Uri = "/api/emails?mailbox_id=virtual:bogus".parse().unwrap();
+        let Query(params) = Query::<ListEmailsParams>::try_from_uri(&uri).unwrap();
+        let err = list_emails(State(Arc::new(state)), OriginalUri(uri), Query(params))
+            .await
+            .err()
+            .expect("unknown virtual mailbox must be rejected");
         assert!(
-            rest[..end].contains("visibleRows("),
-            "j/k navigation must index into visibleRows(), not state.emails"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("virtual:bogus")),
+            "got {err:?}"
         );
     }
 
+    fn list_params(uri: &str) -> ListEmailsParams {
+        let uri: Uri = uri.parse().unwrap();
+        Query::<ListEmailsParams>::try_from_uri(&uri).unwrap().0
+    }
+
     #[test]
-    fn thread_row_shows_count_badge_and_aggregate_flags() {
-        // A thread with 2+ loaded members collapses to one row carrying a
-        // count badge; it reads unread if ANY member is unread and starred if
-        // ANY member is starred (aggregate, computed live from state.emails so
-        // the badge stays accurate after a member is archived/undone).
-        let start = APP_JS
-            .find("function visibleRows(")
-            .expect("visibleRows must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}\n").expect("visibleRows must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("anyUnread") && block.contains("anyStarred"),
-            "a collapsed thread must aggregate unread/starred across members"
+    fn window_start_prefers_the_anchor() {
+        let start = |uri| window_start(&list_params(uri));
+        assert_eq!(start("/api/emails?mailbox_id=i").unwrap(), None);
+        assert_eq!(
+            start("/api/emails?window=200").unwrap(),
+            Some(WindowStart::Position(200))
         );
+        assert_eq!(
+            start("/api/emails?window=200&anchor=e9&anchor_offset=-25").unwrap(),
+            Some(WindowStart::Anchor {
+                id: "e9".into(),
+                offset: -25
+            })
+        );
+        assert!(matches!(
+            start("/api/emails?window=0&anchor_offset=3"),
+            Err(Error::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn window_mode_refuses_post_filtered_lists() {
+        for uri in [
+            "/api/emails?window=0&split_id=primary",
+            "/api/emails?window=0&mailbox_id=virtual:calendar",
+            "/api/emails?anchor=e1&search=spamscore:%3E5",
+        ] {
+            let state = test_state(&["a"], "a");
+            let params = list_params(uri);
+            let err = list_emails(
+                State(Arc::new(state)),
+                OriginalUri(uri.parse().unwrap()),
+                Query(params),
+            )
+            .await
+            .err()
+            .expect("post-filtered window must be rejected");
+            assert!(
+                matches!(err, Error::BadRequest(ref m) if m.contains("window")),
+                "{uri}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn mobile_app_js_prefetch_requests_mark_read_false() {
+        let start = MOBILE_APP_JS
+            .find("function prefetchAdjacentEmails")
+            .expect("prefetchAdjacentEmails must exist");
+        let rest = &MOBILE_APP_JS[start..];
+        let end = rest.find("\n}").expect("prefetchAdjacentEmails must close");
+        let block = &rest[..end];
         assert!(
-            block.contains("kind: 'thread'"),
-            "visibleRows must emit a collapsed-thread row kind"
+            block.contains("mark_read=false"),
+            "prefetching adjacent emails must not silently mark them read \
+             (pass ?mark_read=false on the GET)"
         );
+    }
 
+    #[test]
+    fn mobile_app_js_cache_hit_detail_marks_read_on_server() {
+        // Opening a prefetched (mark_read=false) email from cache issues no
+        // GET, so nothing tells the server it's now read unless
+        // renderScreenDetail does so explicitly — otherwise the local
+        // isUnread flip masks a still-unread server record until the next
+        // refresh resurfaces it (roborev 286).
+        let start = MOBILE_APP_JS
+            .find("async function renderScreenDetail")
+            .expect("renderScreenDetail must exist");
+        let rest = &MOBILE_APP_JS[start..];
+        let end = rest.find("\n}").expect("renderScreenDetail must close");
+        let block = &rest[..end];
         assert!(
-            APP_JS.contains("email-thread-count"),
-            "the collapsed-thread row must render a count-badge element"
+            block.contains("/mark-read"),
+            "renderScreenDetail's cache-hit path must explicitly mark the \
+             email read on the server"
         );
-        let rstart = APP_JS
-            .find("function renderEmailList(")
-            .expect("renderEmailList must exist");
-        let rrest = &APP_JS[rstart..];
-        let rend = rrest.find("\n}\n").expect("renderEmailList must close");
         assert!(
-            rrest[..rend].contains("row.count"),
-            "the rendered count badge must show the thread's live member count"
+            block.contains("cacheHit"),
+            "the mark-read call must be gated to the cache-hit path only — \
+             the network-fetch path's GET already auto-marks read server-side"
         );
     }
 
     #[test]
-    fn undo_reinsert_selects_by_visible_row_not_flat_index() {
-        // The flat-row landmine: performUndo used to set selectedIndex to the
-        // state.emails insert index. Under grouping, selection indexes the
-        // VISIBLE rows, so it must resolve the re-inserted email's visible row.
+    fn app_js_prefetch_requests_mark_read_false() {
+        // Desktop counterpart to mobile_app_js_prefetch_requests_mark_read_false
+        // (roborev 302, fix 2): the bare GET auto-marks read server-side, so
+        // background warm-up must never silently consume unread state for an
+        // email the user hasn't actually opened.
         let start = APP_JS
-            .find("async function performUndo()")
-            .expect("performUndo must exist");
+            .find("function prefetchAdjacentEmails")
+            .expect("prefetchAdjacentEmails must exist");
         let rest = &APP_JS[start..];
-        let end = rest.find("\n}\n").expect("performUndo must close");
+        let end = rest.find("\n}").expect("prefetchAdjacentEmails must close");
         let block = &rest[..end];
         assert!(
-            block.contains("visibleRowIndexForEmailId("),
-            "undo must select the re-inserted email's VISIBLE row, not a raw \
-             state.emails index"
+            block.contains("mark_read=false"),
+            "prefetching adjacent emails must not silently mark them read \
+             (pass ?mark_read=false on the GET)"
         );
     }
 
     #[test]
-    fn list_selection_reads_visible_row_email_id() {
-        // Acting on a collapsed thread row acts on the NEWEST message (v1, no
-        // bulk thread actions): the selected id comes from the visible row.
+    fn app_js_cache_hit_detail_marks_read_on_server() {
+        // Desktop counterpart to mobile_app_js_cache_hit_detail_marks_read_on_server
+        // (roborev 302, fix 2): opening a prefetched (mark_read=false) email
+        // from cache issues no GET, so nothing tells the server it's now read
+        // unless loadEmailDetail's cache-hit branch does so explicitly.
         let start = APP_JS
-            .find("function getSelectedEmailId()")
-            .expect("getSelectedEmailId must exist");
+            .find("async function loadEmailDetail")
+            .expect("loadEmailDetail must exist");
         let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("getSelectedEmailId must close");
+        let end = rest.find("\n}").expect("loadEmailDetail must close");
+        let block = &rest[..end];
         assert!(
-            rest[..end].contains("visibleRows("),
-            "the selected email id (list view) must come from the visible row model"
+            block.contains("/mark-read"),
+            "loadEmailDetail's cache-hit path must explicitly mark the email \
+             read on the server"
         );
     }
 
     #[test]
-    fn expanded_member_rows_suppress_date_dividers() {
-        // An expanded thread's member sub-rows are older than the collapsed
-        // header they sit under, so letting them participate in the date-
-        // divider computation injects a stray divider mid-thread AND forces a
-        // duplicate divider for the next non-member row. The divider logic
-        // must skip member rows entirely: no divider emitted for them and
-        // lastGroup must not advance on them.
+    fn app_js_load_email_detail_never_adjusts_split_counts() {
+        // roborev 303, fix 1: split-tab counts are presence counts —
+        // compute_split_counts counts every matching email regardless of
+        // read state, so only archive/trash/removal (membership changes)
+        // should ever touch them. Marking an email read here, on either the
+        // cache-hit or network path, must not call adjustSplitCounts —
+        // mirroring toggleUnread, which never does either. The previous
+        // wave's cache-hit branch wrongly called adjustSplitCounts(-1) (and
+        // +1 on revert) with no mailbox/account guard, so a late revert
+        // could corrupt another mailbox's counts.
         let start = APP_JS
-            .find("function renderEmailList(")
-            .expect("renderEmailList must exist");
+            .find("async function loadEmailDetail")
+            .expect("loadEmailDetail must exist");
         let rest = &APP_JS[start..];
-        let end = rest.find("\n}\n").expect("renderEmailList must close");
+        let end = rest.find("\n}").expect("loadEmailDetail must close");
         let block = &rest[..end];
-
-        let guard = block
-            .find("!isMember")
-            .expect("the divider computation must be gated off member rows");
-        let advance = block
-            .find("lastGroup = group")
-            .expect("the divider computation must advance lastGroup");
         assert!(
-            guard < advance,
-            "the member-row guard must wrap the divider/lastGroup advance — \
-             member sub-rows must neither emit a divider nor move lastGroup"
+            !block.contains("adjustSplitCounts"),
+            "loadEmailDetail must never adjust split-tab counts on mark-read"
         );
     }
 
-    // ====================================================================
-    // Settings view (account management) regression sentinels
-    // ====================================================================
-
-    #[test]
-    fn settings_view_present_in_html() {
-        assert!(INDEX_HTML.contains(r#"id="settings-view""#));
-        assert!(INDEX_HTML.contains(r#"id="account-pane-list""#));
-        assert!(INDEX_HTML.contains(r#"id="account-form""#));
-    }
-
     #[test]
-    fn settings_form_uses_data_provider_attribute() {
-        // Locality of behaviour: visibility-by-provider rule lives in HTML.
+    fn app_js_network_open_marks_email_read_locally() {
+        // roborev 303, fix 2: get_email (src/routes.rs) fetches the email,
+        // then marks it read as a side effect — the JSON it returns still
+        // reflects the pre-mark isUnread state. Without a local flip,
+        // emailCache and the list row are left stale, so a later cache-hit
+        // reopen of loadEmailDetail sees isUnread=true and misfires the
+        // mark-read POST it issues from the cache-hit branch above. Mirrors
+        // mobile app.js's network path (renderScreenDetail).
+        let start = APP_JS
+            .find("async function loadEmailDetail")
+            .expect("loadEmailDetail must exist");
+        let rest = &APP_JS[start..];
+        let fetch_pos = rest
+            .find("const email = await api('GET', `/emails/${emailId}`);")
+            .expect("loadEmailDetail's network path must fetch the email");
+        let after_fetch = &rest[fetch_pos..];
+        let end = after_fetch
+            .find("} catch (err)")
+            .expect("loadEmailDetail's network path must have a catch block");
+        let block = &after_fetch[..end];
         assert!(
-            INDEX_HTML.contains(r#"data-provider="fastmail""#),
-            "fastmail-only fields must declare visibility via data-provider"
+            block.contains("email.isUnread = false"),
+            "loadEmailDetail's network path must flip isUnread on the cached email object"
         );
         assert!(
-            INDEX_HTML.contains(r#"data-provider="outlook,gmail""#),
-            "OAuth shared fields must declare visibility via data-provider"
+            block.contains("listItem.isUnread = false"),
+            "loadEmailDetail's network path must flip isUnread on the matching list row"
         );
-    }
-
-    #[test]
-    fn settings_styles_present() {
-        assert!(STYLE_CSS.contains(".account-pane"));
-        assert!(STYLE_CSS.contains(".secret-input"));
-        assert!(STYLE_CSS.contains(".confirm-delete"));
-        assert!(STYLE_CSS.contains("#mode-indicator.awaiting"));
-        assert!(STYLE_CSS.contains(".auth-status-pill"));
-    }
-
-    #[test]
-    fn settings_authorize_uses_long_poll_not_polling() {
-        // Long-poll: one POST to /authorize. No /auth-status polling.
+        // roborev 304: the flip alone leaves the row's unread styling stale —
+        // returning to the list only toggles CSS classes — so the network
+        // path must also re-render the list (guarded on the email actually
+        // having been unread).
         assert!(
-            APP_JS.contains("/authorize"),
-            "settings must call POST /authorize"
+            block.contains("if (wasUnread) renderEmailList()"),
+            "loadEmailDetail's network path must re-render the list after the flip"
         );
+        // roborev 305: the guard must consider the ROW's pre-flip state too —
+        // a stale-unread row whose email was read elsewhere comes back with
+        // isUnread: false, and the row still needs its restyle.
         assert!(
-            !APP_JS.contains("/auth-status"),
-            "must not poll a /auth-status endpoint — long-poll is the state machine"
+            block.contains("email.isUnread || Boolean(listItem?.isUnread)"),
+            "the re-render guard must capture both the email's and the row's pre-flip state"
         );
-        assert!(APP_JS.contains("AbortController"));
     }
 
     #[test]
-    fn settings_first_run_auto_routes() {
+    fn mobile_app_js_signature_prefilled_via_clear_compose_fields() {
+        // Mirrors app_js_signature_prefilled_via_clear_compose: mobile's
+        // startCompose/startReply/startForward all call clearComposeFields
+        // first, so that's the single place to prefill from.
+        let start = MOBILE_APP_JS
+            .find("function clearComposeFields()")
+            .expect("clearComposeFields must exist");
+        let rest = &MOBILE_APP_JS[start..];
+        let end = rest.find("\n}").expect("clearComposeFields must close");
+        let block = &rest[..end];
         assert!(
-            APP_JS.contains("openSettings({ firstRun: true })"),
-            "loadAccounts must auto-route to settings when no accounts exist"
+            block.contains("composeSignaturePrefill()"),
+            "clearComposeFields must prefill the body via composeSignaturePrefill()"
         );
-    }
-
-    #[test]
-    fn help_overlay_documents_new_shortcuts() {
         assert!(
-            INDEX_HTML.contains(">g s<"),
-            "help overlay should document the g s chord"
+            block.contains("setSelectionRange(0, 0)"),
+            "clearComposeFields must place the cursor at position 0 after prefilling"
         );
         assert!(
-            INDEX_HTML.contains(">Shift+D<"),
-            "help overlay should document Shift+D (set default)"
+            MOBILE_APP_JS.contains("function composeSignaturePrefill"),
+            "composeSignaturePrefill helper must exist"
         );
         assert!(
-            INDEX_HTML.contains(">Ctrl+Enter<"),
-            "help overlay should document Ctrl+Enter (save)"
+            MOBILE_APP_JS.contains("-- \\n"),
+            "signature prefill must use the RFC 3676 delimiter '-- ' \
+             (trailing space significant) before a newline"
         );
     }
 
     #[test]
-    fn load_emails_renders_cached_snapshot_before_network_refresh() {
-        // The Superhuman-style "instant switch" contract: when loadEmails is
-        // called for a mailbox/split/account with a cached entry, the cached
-        // list renders immediately (no awaiting the network). The fresh fetch
-        // races in the background and replaces the snapshot on arrival. Pins
-        // both halves: (1) the cache-hit branch exists, (2) no caller wipes
-        // the cache wholesale — that would force a cold reload on every
-        // switch and defeat the optimization.
-        assert!(
-            APP_JS.contains("if (splitListCache[context]) {"),
-            "loadEmails must render the cached snapshot before awaiting the network"
-        );
+    fn mobile_app_js_cancel_dirty_check_uses_prefill_baseline() {
+        // With a signature configured, clearComposeFields prefills the body
+        // with "\n\n-- \n<sig>", so a dirty check based on
+        // compose-body.value.trim() is ALWAYS truthy — plain open→Cancel
+        // would falsely raise the Discard bar with zero typing. The body
+        // dirty test must instead compare against the captured prefill
+        // baseline.
+        let start = MOBILE_APP_JS
+            .find("function clearComposeFields()")
+            .expect("clearComposeFields must exist");
+        let rest = &MOBILE_APP_JS[start..];
+        let end = rest.find("\n}").expect("clearComposeFields must close");
+        let clear_block = &rest[..end];
         assert!(
-            !APP_JS.contains("clearSplitListCache()"),
-            "no caller should wipe splitListCache wholesale — keys are already (account, mailbox, split, starred, search)-scoped"
+            clear_block.contains("state.composeBaseline"),
+            "clearComposeFields must capture the prefilled body as state.composeBaseline"
         );
-    }
 
-    #[test]
-    fn email_caches_are_account_scoped() {
-        // Cross-account isolation is enforced by prefixing every cache key
-        // with the active account id (`cacheKey(emailId)`), not by wiping
-        // caches on account switch. Wiping forced every revisit to refetch
-        // from the provider, which was unusably slow (Gmail in particular
-        // takes seconds per body). The scoped-key approach is both safer
-        // (no leak window between wipe and refill) and preserves state
-        // across switches — returning to an email finds its cached body.
+        // The dirty check moved into composeDirty() (kata wm57: shared with
+        // autosave) — cancelCompose now delegates to it. The baseline-compare
+        // invariant lives there.
+        let start = MOBILE_APP_JS
+            .find("function composeDirty()")
+            .expect("composeDirty must exist");
+        let rest = &MOBILE_APP_JS[start..];
+        let end = rest.find("\n}").expect("composeDirty must close");
+        let dirty_block = &rest[..end];
         assert!(
-            APP_JS.contains("function cacheKey(emailId)"),
-            "static/app.js must define cacheKey() so emailCache/scrollPositions are account-scoped"
+            dirty_block.contains("!== state.composeBaseline"),
+            "composeDirty's body-dirty test must compare against state.composeBaseline"
         );
         assert!(
-            APP_JS.contains("state.currentEmail = null"),
-            "selectAccount must null out state.currentEmail (no cross-account detail residue)"
+            !dirty_block.contains("compose-body').value.trim()"),
+            "composeDirty must not treat the signature prefill as dirty via value.trim()"
         );
         assert!(
-            !APP_JS.contains("for (const k in emailCache) delete emailCache[k]"),
-            "selectAccount must NOT wipe emailCache — cacheKey() scoping makes the wipe both unnecessary and a performance regression"
+            MOBILE_APP_JS.contains("if (composeDirty())"),
+            "cancelCompose must delegate its dirty decision to composeDirty()"
         );
+    }
+
+    // =========================================================================
+    // split-counts cache
+    // =========================================================================
+
+    async fn split_counts_body(state: Arc<AppState>) -> serde_json::Value {
+        let resp = split_counts(
+            State(state),
+            Query(SplitCountsParams {
+                mailbox_id: "inbox".into(),
+                account: Some("a".into()),
+                starred: None,
+            }),
+        )
+        .await
+        .expect("cached counts are served without a session")
+        .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn state_with_one_split(dir: &std::path::Path) -> AppState {
+        let path = dir.join("splits.json");
+        std::fs::write(
+            &path,
+            r#"{"splits": [{"id": "x", "name": "X", "filters": [{"type": "from", "pattern": "*@x.example"}]}]}"#,
+        )
+        .unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(path, None);
+        state
+    }
+
+    #[tokio::test]
+    async fn split_counts_serve_cache_with_age() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(state_with_one_split(temp_dir.path()));
+        state
+            .prefetch
+            .set_split_counts("a", "inbox".into(), HashMap::from([("x".into(), 3)]))
+            .await;
+
+        let body = split_counts_body(state).await;
+        assert_eq!(body["counts"]["x"], 3);
+        assert!(body["staleSeconds"].as_i64().unwrap() < 5);
+        assert_eq!(body["refreshing"], false);
+    }
+
+    #[tokio::test]
+    async fn invalidated_split_counts_answer_instantly_and_refresh() {
+        // The test state has no live session, so a blocking recompute
+        // would error; the stale answer must come straight from the cache.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(state_with_one_split(temp_dir.path()));
+        state
+            .prefetch
+            .set_split_counts("a", "inbox".into(), HashMap::from([("x".into(), 3)]))
+            .await;
+        state.prefetch.invalidate("a").await;
+
+        let body = split_counts_body(state).await;
+        assert_eq!(body["counts"]["x"], 3);
+        assert_eq!(body["refreshing"], true);
+    }
+
+    #[tokio::test]
+    async fn event_ics_rejects_unknown_account() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let err = event_ics(
+            State(state),
+            Path("m1".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account must be rejected");
+        assert!(matches!(err, Error::BadRequest(ref m) if m.contains("Unknown account")));
+    }
+
+    #[tokio::test]
+    async fn mark_mailbox_read_rejects_unknown_account() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let err = mark_mailbox_read(
+            State(state),
+            Path("inbox".into()),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account must be rejected");
+        assert!(matches!(err, Error::BadRequest(ref m) if m.contains("Unknown account")));
+    }
+
+    // =========================================================================
+    // body cache admin
+    // =========================================================================
+
+    #[tokio::test]
+    async fn clear_cache_drops_one_account_or_all() {
+        let mut state = test_state(&["a", "b"], "a");
+        state.email_cache = cache::EmailCache::in_memory();
+        let state = Arc::new(state);
+        let email = test_email_with_recipients(vec!["x@example.com"], vec![]);
+        for account in ["a", "b"] {
+            state
+                .prefetch
+                .set_body(account, "m1".into(), email.clone())
+                .await;
+            state.email_cache.put(account, cache::Kind::Full, &email);
+        }
+
+        let Json(body) = clear_cache(
+            State(state.clone()),
+            Query(AccountParam {
+                account: Some("a".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["clearedBodies"], 1);
+        assert_eq!(body["clearedStored"], 1);
+        assert!(body["freedBytes"].as_u64().unwrap() > 0);
+        assert!(state.prefetch.get_body("a", "m1").await.is_none());
+        assert!(state.prefetch.get_body("b", "m1").await.is_some());
         assert!(
-            !APP_JS.contains("for (const k in scrollPositions) delete scrollPositions[k]"),
-            "selectAccount must NOT wipe scrollPositions — cacheKey() scoping handles isolation"
+            state
+                .email_cache
+                .get("b", cache::Kind::Full, &email.id)
+                .is_some()
         );
+
+        let Json(body) = clear_cache(State(state.clone()), Query(AccountParam::default()))
+            .await
+            .unwrap();
+        assert_eq!(body["clearedBodies"], 1);
+        assert_eq!(body["clearedStored"], 1);
+        assert_eq!(state.prefetch.body_cache_stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn clear_cache_rejects_unknown_account() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let err = clear_cache(
+            State(state),
+            Query(AccountParam {
+                account: Some("typo".into()),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::BadRequest(_)));
     }
 
     #[test]
-    fn api_helper_excludes_settings_from_account_param() {
-        // The shared api client allowlists which paths receive ?account=.
-        // Settings paths (`/accounts/...`) must NOT be auto-tagged.
-        assert!(
-            API_JS.contains("ACCOUNT_SCOPED_API"),
-            "api.js must use an allowlist regex for ?account= injection"
-        );
-        assert!(
-            API_JS.contains(
-                "/(emails|mailboxes|identities|splits|upload|split-counts|calendar|drafts)"
+    fn metrics_render_prometheus_text() {
+        let text = render_metrics(&prefetch::BodyCacheStats {
+            entries: 2,
+            bytes: 4096,
+            capacity_bytes: 8192,
+            hits: 5,
+            misses: 1,
+            evictions: 3,
+            evicted_bytes: 900,
+        });
+        assert!(text.contains("# TYPE supervillain_body_cache_bytes gauge\n"));
+        assert!(text.contains("\nsupervillain_body_cache_bytes 4096\n"));
+        assert!(text.contains("\nsupervillain_body_cache_evictions_total 3\n"));
+        assert!(text.contains("\nsupervillain_body_cache_evicted_bytes_total 900\n"));
+    }
+
+    // =========================================================================
+    // splits import/export
+    // =========================================================================
+
+    #[tokio::test]
+    async fn import_toml_becomes_primary_config_with_comments() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let json_path = temp_dir.path().join("splits.json");
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(json_path.clone(), None);
+        let state = Arc::new(state);
+
+        let toml = "# mine\n[[splits]]\nid = \"x\"\nname = \"X\"\n";
+        import_splits(
+            State(state.clone()),
+            Query(SplitsFormatParams {
+                format: Some("toml".into()),
+            }),
+            toml.to_string(),
+        )
+        .await
+        .expect("valid TOML imports");
+
+        let written = std::fs::read_to_string(temp_dir.path().join("splits.toml")).unwrap();
+        assert_eq!(written, toml);
+        assert_eq!(splits::load_splits(&json_path, None).splits[0].id, "x");
+
+        let resp = export_splits(
+            State(state),
+            Query(SplitsFormatParams {
+                format: Some("toml".into()),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap(), toml);
+    }
+
+    #[tokio::test]
+    async fn export_converts_json_config_to_toml() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let json_path = temp_dir.path().join("splits.json");
+        std::fs::write(&json_path, r#"{"splits": [{"id": "x", "name": "X"}]}"#).unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(json_path, None);
+
+        let resp = export_splits(
+            State(Arc::new(state)),
+            Query(SplitsFormatParams {
+                format: Some("toml".into()),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(resp.headers()["content-type"], "application/toml");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = std::str::from_utf8(&body).unwrap();
+        assert!(text.contains("[[splits]]"), "{text}");
+        assert!(text.contains(r#"id = "x""#), "{text}");
+    }
+
+    #[tokio::test]
+    async fn import_rejects_duplicate_ids_and_bad_syntax() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(temp_dir.path().join("splits.json"), None);
+        let state = Arc::new(state);
+
+        for (format, body) in [
+            (
+                "json",
+                r#"{"splits": [{"id": "x", "name": "X"}, {"id": "x", "name": "Y"}]}"#,
             ),
-            "allowlist regex must enumerate account-scoped path prefixes"
-        );
+            ("toml", "[[splits]\n"),
+            ("yaml", "splits: []"),
+        ] {
+            let err = import_splits(
+                State(state.clone()),
+                Query(SplitsFormatParams {
+                    format: Some(format.into()),
+                }),
+                body.to_string(),
+            )
+            .await
+            .err()
+            .expect("import must be rejected");
+            assert!(matches!(err, Error::BadRequest(_)), "{format}: {err:?}");
+        }
+        assert!(!temp_dir.path().join("splits.json").exists());
+        assert!(!temp_dir.path().join("splits.toml").exists());
     }
 
-    #[test]
-    fn load_splits_goes_through_account_scoped_api_helper() {
-        // A raw fetch skips ?account= and renders every account's tabs.
-        assert!(
-            APP_JS.contains("const splits = await api('GET', '/splits')"),
-            "loadSplits must use the api() helper so ?account= is appended"
-        );
-        assert!(
-            !APP_JS.contains("fetch('/api/splits')"),
-            "loadSplits must not bypass api() with a raw fetch"
-        );
+    #[tokio::test]
+    async fn split_writes_publish_a_change_event() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(temp_dir.path().join("splits.json"), None);
+        let state = Arc::new(state);
+        let mut rx = state.splits.subscribe();
+
+        let new_split = SplitInbox {
+            id: "x".into(),
+            name: "X".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        create_split(State(state.clone()), Json(new_split))
+            .await
+            .expect("create succeeds");
+        assert_eq!(rx.try_recv().unwrap(), 1);
+        assert_eq!(state.splits.current().splits[0].id, "x");
+
+        delete_split(State(state.clone()), Path("x".into()))
+            .await
+            .expect("delete succeeds");
+        assert_eq!(rx.try_recv().unwrap(), 2);
+        assert!(state.splits.current().splits.is_empty());
     }
 
-    #[test]
-    fn load_splits_guards_against_stale_account_switch() {
-        // On rapid account switches, account A's in-flight response can
-        // land after B's and overwrite state.splits while B is active.
-        let start = APP_JS
-            .find("async function loadSplits()")
-            .expect("loadSplits must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("loadSplits must close");
-        let body = &rest[..end];
-        assert!(
-            body.matches("state.currentAccount?.id !== accountId")
-                .count()
-                >= 2,
-            "loadSplits must discard BOTH a stale success and a stale failure — \
-             a failed request from the previous account must not wipe the new \
-             account's already-loaded splits in the catch branch"
-        );
+    #[tokio::test]
+    async fn events_stream_emits_splits_event() {
+        use futures_util::StreamExt;
+
+        let state = Arc::new(test_state(&["a"], "a"));
+        let resp = server_events(State(state.clone())).await.into_response();
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let mut body = resp.into_body().into_data_stream();
+
+        state.splits.publish();
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+            .await
+            .expect("event within timeout")
+            .expect("stream open")
+            .unwrap();
+        let text = std::str::from_utf8(&chunk).unwrap();
+        assert!(text.contains("event: splits"), "{text}");
+        assert!(text.contains(r#"data: {"version":1}"#), "{text}");
     }
 
-    #[test]
-    fn init_does_not_call_load_splits_directly() {
-        // init() runs before account selection, so state.currentAccount is
-        // still null — a direct call here fetches the unscoped split list,
-        // which can race and overwrite the account-scoped load triggered by
-        // selectAccount() (called from loadAccounts()).
-        let start = APP_JS.find("function init() {").expect("init must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("init must close");
-        let body = &rest[..end];
-        assert!(
-            !body.contains("loadSplits()"),
-            "init must not call loadSplits() directly; selectAccount() loads it once an account is chosen"
-        );
+    fn recent_code(account: &str) -> otp::OtpCode {
+        otp::OtpCode {
+            code: "482913".into(),
+            sender: "no-reply@bank.example".into(),
+            account: account.into(),
+            email_id: "m1".into(),
+            received_at: chrono::Utc::now(),
+        }
     }
 
-    #[test]
-    fn select_account_reloads_splits() {
-        // Tab sets differ per account; switching must rebuild the row.
-        let start = APP_JS
-            .find("function selectAccount")
-            .expect("selectAccount must exist");
-        let rest = &APP_JS[start..];
-        // Slice to the function's own closing brace (first `}` at column
-        // 0) so a match can only come from selectAccount's body, not the
-        // loadSplits() declaration that follows it in the file.
-        let end = rest.find("\n}").expect("selectAccount must close");
-        let body = &rest[..end];
+    #[tokio::test]
+    async fn events_stream_emits_otp_code_and_sender_only() {
+        use futures_util::StreamExt;
+
+        let state = Arc::new(test_state(&["a"], "a"));
+        let resp = server_events(State(state.clone())).await.into_response();
+        let mut body = resp.into_body().into_data_stream();
+
+        state.otp.publish(recent_code("a"));
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+            .await
+            .expect("event within timeout")
+            .expect("stream open")
+            .unwrap();
+        let text = std::str::from_utf8(&chunk).unwrap();
+        assert!(text.contains("event: otp"), "{text}");
         assert!(
-            body.contains("loadSplits()"),
-            "selectAccount must call loadSplits()"
+            text.contains(r#"data: {"code":"482913","sender":"no-reply@bank.example"}"#),
+            "{text}"
         );
     }
 
-    #[test]
-    fn select_account_resets_stale_split_state() {
-        // Regression test: before loadSplits()'s async response lands, the
-        // previous account's tabs/counts must not linger — otherwise a fast
-        // click into the new account briefly renders the old account's
-        // split tabs and counts.
-        let start = APP_JS
-            .find("function selectAccount")
-            .expect("selectAccount must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("selectAccount must close");
-        let body = &rest[..end];
-        assert!(
-            body.contains("state.splits = []"),
-            "selectAccount must clear state.splits before the new account's loadSplits() resolves"
-        );
+    #[tokio::test]
+    async fn compose_session_changes_reach_the_event_stream() {
+        use futures_util::StreamExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.compose_sessions_path = temp_dir.path().join("compose-sessions.json");
+        let state = Arc::new(state);
+        let resp = server_events(State(state.clone())).await.into_response();
+        let mut body = resp.into_body().into_data_stream();
+
+        let created = create_compose_session(
+            State(state.clone()),
+            Json(compose_sessions::ComposeFields {
+                subject: "Started on desktop".into(),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let id = compose_sessions::load_sessions(&state.compose_sessions_path).sessions[0]
+            .id
+            .clone();
+
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+            .await
+            .expect("event within timeout")
+            .expect("stream open")
+            .unwrap();
+        let text = std::str::from_utf8(&chunk).unwrap();
+        assert!(text.contains("event: compose"), "{text}");
         assert!(
-            body.contains("state.splitCounts = {}"),
-            "selectAccount must clear state.splitCounts before the new account's loadSplitCounts() resolves"
+            text.contains(&format!(
+                r#"data: {{"deleted":false,"id":"{id}","version":1}}"#
+            )),
+            "{text}"
         );
     }
 
-    // =========================================================================
-    // Desktop sort control (kata 09ef) — buildEmailListUrl appends &sort=,
-    // splitCacheKey discriminates by it (mirrors starredOnly), and
-    // selectAccount resets it to the default like other per-session view state.
-    // =========================================================================
+    #[tokio::test]
+    async fn compose_session_update_rejects_stale_version_and_delete_is_404_after() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.compose_sessions_path = temp_dir.path().join("compose-sessions.json");
+        let state = Arc::new(state);
+        create_compose_session(State(state.clone()), Json(Default::default()))
+            .await
+            .unwrap();
+        let id = compose_sessions::load_sessions(&state.compose_sessions_path).sessions[0]
+            .id
+            .clone();
+
+        let update = |subject: &str, version| {
+            Json(ComposeSessionUpdate {
+                fields: compose_sessions::ComposeFields {
+                    subject: subject.into(),
+                    ..Default::default()
+                },
+                version,
+            })
+        };
+        update_compose_session(
+            State(state.clone()),
+            Path(id.clone()),
+            update("From phone", Some(1)),
+        )
+        .await
+        .unwrap();
+        let err = update_compose_session(
+            State(state.clone()),
+            Path(id.clone()),
+            update("From desktop", Some(1)),
+        )
+        .await
+        .err()
+        .expect("stale version must conflict");
+        assert!(matches!(err, Error::Conflict(_)), "got {err:?}");
+        let stored = compose_sessions::load_sessions(&state.compose_sessions_path);
+        assert_eq!(stored.find(&id).unwrap().fields.subject, "From phone");
 
-    #[test]
-    fn build_email_list_url_appends_sort_param() {
-        let start = APP_JS
-            .find("function buildEmailListUrl")
-            .expect("buildEmailListUrl must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("buildEmailListUrl must close");
-        let body = &rest[..end];
-        assert!(
-            body.contains("sort="),
-            "buildEmailListUrl must append &sort= so the sort control reaches the server"
-        );
-        assert!(
-            body.contains("state.sortOrder"),
-            "buildEmailListUrl must read the sort value from state.sortOrder"
-        );
+        delete_compose_session(State(state.clone()), Path(id.clone()))
+            .await
+            .unwrap();
+        let err = get_compose_session(State(state.clone()), Path(id.clone()))
+            .await
+            .err()
+            .expect("deleted session is gone");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+        let err = delete_compose_session(State(state), Path(id))
+            .await
+            .err()
+            .expect("second delete is 404");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
     }
 
-    #[test]
-    fn split_cache_key_includes_sort_order() {
-        // Same bug class as the server's prefetch InboxKey: without this,
-        // toggling sort would render a stale, wrong-order splitListCache
-        // entry before the network refresh corrects it, and the refresh
-        // would then overwrite the *other* order's cached entry.
-        let start = APP_JS
-            .find("function splitCacheKey")
-            .expect("splitCacheKey must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("splitCacheKey must close");
-        let body = &rest[..end];
+    #[tokio::test]
+    async fn latest_otp_is_404_until_a_code_arrives() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let err = latest_otp(State(state.clone()), Query(AccountParam::default()))
+            .await
+            .err()
+            .expect("no code yet");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+
+        state.otp.publish(recent_code("a"));
         assert!(
-            body.contains("state.sortOrder"),
-            "splitCacheKey must fold state.sortOrder into the cache key"
+            latest_otp(State(state.clone()), Query(AccountParam::default()))
+                .await
+                .is_ok()
         );
+        let err = latest_otp(
+            State(state),
+            Query(AccountParam {
+                account: Some("b".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("another account's code is not served");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
     }
 
     #[test]
-    fn render_sort_toggle_flags_gmail_paged_ascending_order() {
-        // Regression test (roborev 291): Gmail's "oldest first" is only
-        // oldest-first *within each fetched page* (see gmail.rs's
-        // apply_sort_order doc comment) — paginating forward still walks
-        // Gmail's underlying pages newest-block-first. Fastmail/Outlook
-        // sort globally, so without a per-provider affordance the desktop
-        // toggle reads identically for a guarantee that's actually weaker
-        // on Gmail. renderSortToggle must call out the Gmail case in both
-        // the visible label and a title attribute.
-        let start = APP_JS
-            .find("function renderSortToggle")
-            .expect("renderSortToggle must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("renderSortToggle must close");
-        let body = &rest[..end];
-        assert!(
-            body.contains("state.currentAccount?.provider === 'gmail'"),
-            "renderSortToggle must special-case the Gmail provider"
-        );
-        assert!(
-            body.contains("(per page)"),
-            "renderSortToggle must append a per-page hint to the label for Gmail ascending order"
-        );
-        assert!(
-            body.contains("els.sortToggle.title"),
-            "renderSortToggle must set a title attribute explaining the per-page limitation"
+    fn split_preview_estimate_scales_sample_to_mailbox() {
+        assert_eq!(
+            estimate_split_total(30, 1500, 1000),
+            30,
+            "sample covered it all"
         );
+        assert_eq!(estimate_split_total(30, 1500, 15000), 300);
+        assert_eq!(estimate_split_total(0, 1500, 15000), 0);
+        assert_eq!(estimate_split_total(0, 0, 15000), 0);
     }
 
-    #[test]
-    fn select_account_resets_sort_order_to_default() {
-        let start = APP_JS
-            .find("function selectAccount")
-            .expect("selectAccount must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("selectAccount must close");
-        let body = &rest[..end];
-        assert!(
-            body.contains("state.sortOrder"),
-            "selectAccount must reset state.sortOrder to the default on account switch"
-        );
+    #[tokio::test]
+    async fn split_preview_rejects_empty_filters_and_unknown_account() {
+        let state = Arc::new(test_state(&["a"], "a"));
+        let mut split = SplitInbox {
+            id: "x".into(),
+            name: "X".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        let err = preview_split(
+            State(state.clone()),
+            Query(SplitPreviewParams {
+                account: None,
+                mailbox_id: None,
+            }),
+            Json(split.clone()),
+        )
+        .await
+        .err()
+        .expect("no filters");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
+
+        split.filters.push(SplitFilter {
+            filter_type: FilterType::From,
+            pattern: "*@x.com".into(),
+            name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
+        });
+        split.account = Some("typo".into());
+        let err = preview_split(
+            State(state),
+            Query(SplitPreviewParams {
+                account: None,
+                mailbox_id: None,
+            }),
+            Json(split),
+        )
+        .await
+        .err()
+        .expect("split's own account tag is validated");
+        assert!(matches!(err, Error::BadRequest(_)), "{err:?}");
     }
 
-    #[test]
-    fn save_split_tags_current_account() {
-        assert!(
-            APP_JS.contains("account: state.currentAccount?.id"),
-            "saveSplit must scope new splits to the active account"
-        );
+    #[tokio::test]
+    async fn update_split_keeps_fields_the_ui_does_not_send() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let splits_path = temp_dir.path().join("splits.json");
+        std::fs::write(
+            &splits_path,
+            r#"{"splits": [{"id": "a", "name": "A", "color": "red"}]}"#,
+        )
+        .unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(splits_path.clone(), None);
+
+        let updated = SplitInbox {
+            id: "a".into(),
+            name: "Renamed".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+            .await
+            .expect("update succeeds");
+
+        let saved = splits::load_splits(&splits_path, None);
+        assert_eq!(saved.splits[0].name, "Renamed");
+        assert_eq!(saved.splits[0].extra["color"], "red");
     }
 
     // =========================================================================
-    // resolve_account_id / list_splits — reject unknown account ids (roborev 271)
+    // create_split / update_split validation tests (roborev 271)
     // =========================================================================
 
-    fn test_state(known: &[&str], default_account: &str) -> AppState {
-        let mut account_configs = std::collections::BTreeMap::new();
-        for id in known {
-            account_configs.insert(
-                id.to_string(),
-                accounts::AccountConfig::Fastmail {
-                    username: format!("{id}@example.com"),
-                    api_token: "tok".into(),
-                    signature: None,
-                },
-            );
-        }
-        AppState {
-            accounts: tokio::sync::RwLock::new(AccountRegistry {
-                sessions: HashMap::new(),
-                account_configs,
-                default_account: default_account.to_string(),
-            }),
-            account_errors: tokio::sync::RwLock::new(Vec::new()),
-            splits_config_path: std::path::PathBuf::from("/tmp/nonexistent-splits.json"),
-            timezone_config_path: std::path::PathBuf::from("/tmp/nonexistent-timezone.json"),
-            timezone_write_lock: tokio::sync::Mutex::new(()),
-            config_path: std::path::PathBuf::from("/tmp/nonexistent-config"),
-            tokens_dir: std::path::PathBuf::from("/tmp/nonexistent-tokens"),
-            token_store: std::sync::Arc::new(crate::platform::FsTokenStore::new(
-                std::path::PathBuf::from("/tmp/nonexistent-tokens"),
-            )),
-            authorizing: accounts::AuthorizingSlot::default(),
-            config_error_baseline: std::sync::RwLock::new(Vec::new()),
-            prefetch: std::sync::Arc::new(crate::prefetch::PrefetchCache::new()),
-            prefetch_cache_path: std::env::temp_dir().join("supervillain-test-prefetch-cache.json"),
-        }
-    }
-
     #[tokio::test]
-    async fn resolve_account_id_rejects_unknown_account() {
-        let state = test_state(&["known"], "known");
-        let err = resolve_account_id(&state, Some("typo"))
+    async fn create_split_rejects_unknown_account() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let splits_path = temp_dir.path().join("splits.json");
+
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(splits_path, None);
+
+        let new_split = SplitInbox {
+            id: "test-split".into(),
+            name: "Test".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: Some("typo".into()),
+            action: None,
+            extra: Default::default(),
+        };
+
+        let err = create_split(State(Arc::new(state)), Json(new_split))
             .await
-            .expect_err("an id absent from account_configs must be rejected");
+            .err()
+            .expect("create_split must reject a split tagged to an unknown account");
+
         assert!(
             matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
-            "expected a BadRequest naming the unknown account, got {err:?}"
+            "expected a BadRequest containing 'Unknown account', got {err:?}"
         );
     }
 
     #[tokio::test]
-    async fn resolve_account_id_accepts_known_account() {
-        let state = test_state(&["known"], "known");
-        let id = resolve_account_id(&state, Some("known"))
-            .await
-            .expect("a configured account must resolve");
-        assert_eq!(id, "known");
-    }
+    async fn create_split_rejects_invalid_regex_with_422() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(temp_dir.path().join("splits.json"), None);
 
-    #[tokio::test]
-    async fn resolve_account_id_default_none_still_works() {
-        // The None (default-account) path must keep working — default_account
-        // is always kept in sync with account_configs by the mutation
-        // handlers, so this can't regress into a false rejection.
-        let state = test_state(&["known"], "known");
-        let id = resolve_account_id(&state, None)
+        let new_split = SplitInbox {
+            id: "news".into(),
+            name: "News".into(),
+            icon: None,
+            filters: vec![SplitFilter {
+                filter_type: FilterType::Subject,
+                pattern: "digest|(weekly".into(),
+                name: None,
+                pattern_kind: Some(PatternKind::Regex),
+                glob_match: None,
+                extra: Default::default(),
+            }],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        let err = create_split(State(Arc::new(state)), Json(new_split))
             .await
-            .expect("omitting ?account= must fall back to the default account");
-        assert_eq!(id, "known");
+            .err()
+            .expect("invalid regex must be rejected");
+        assert!(
+            matches!(err, Error::Unprocessable(ref msg) if msg.contains("invalid regex")),
+            "{err:?}"
+        );
+        assert!(!temp_dir.path().join("splits.json").exists());
     }
 
     #[tokio::test]
-    async fn list_splits_rejects_unknown_account_param() {
-        let state = Arc::new(test_state(&["known"], "known"));
-        let params = ListSplitsParams {
+    async fn update_split_rejects_unknown_account() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let splits_path = temp_dir.path().join("splits.json");
+
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(splits_path.clone(), None);
+
+        // Seed a split with id "a" and no account tag
+        let existing_split = SplitInbox {
+            id: "a".into(),
+            name: "Original".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        let config = SplitsConfig {
+            splits: vec![existing_split],
+            version: splits::CONFIG_VERSION,
+            extra: Default::default(),
+        };
+        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
+
+        // Try to update it with account="typo"
+        let updated = SplitInbox {
+            id: "a".into(),
+            name: "Updated".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
             account: Some("typo".into()),
+            action: None,
+            extra: Default::default(),
         };
-        let err = list_splits(State(state), Query(params))
+
+        let err = update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
             .await
             .err()
-            .expect("an unknown ?account= must 400, not silently scope to untagged splits");
+            .expect("update_split must reject a split tagged to an unknown account");
+
         assert!(
             matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
-            "expected a BadRequest naming the unknown account, got {err:?}"
+            "expected a BadRequest containing 'Unknown account', got {err:?}"
         );
     }
 
     #[tokio::test]
-    async fn list_splits_without_account_param_is_unaffected() {
-        // No ?account= → full list (management/debugging view); this must
-        // stay reachable even though it never hits the new validation gate.
-        let state = Arc::new(test_state(&["known"], "known"));
-        let params = ListSplitsParams { account: None };
-        let resp = list_splits(State(state), Query(params))
-            .await
-            .expect("omitting ?account= must not be rejected")
-            .into_response();
-        assert_eq!(resp.status(), StatusCode::OK);
-    }
+    async fn update_split_rejects_changed_id() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let splits_path = temp_dir.path().join("splits.json");
 
-    // =========================================================================
-    // GetEmailParams deserialization (roborev 285) — mark_read opt-out lets
-    // mobile's prefetch warm the body cache without consuming unread state.
-    // =========================================================================
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(splits_path.clone(), None);
+
+        // Seed a split with id "a"
+        let existing_split = SplitInbox {
+            id: "a".into(),
+            name: "Original".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+        let config = SplitsConfig {
+            splits: vec![existing_split],
+            version: splits::CONFIG_VERSION,
+            extra: Default::default(),
+        };
+        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
+
+        // Try to update it with a different id "b"
+        let updated = SplitInbox {
+            id: "b".into(),
+            name: "Updated".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+
+        let err = update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+            .await
+            .err()
+            .expect("update_split must reject a body id that differs from the path id");
 
-    #[test]
-    fn get_email_params_mark_read_absent_defaults_to_read_semantics() {
-        let uri: axum::http::Uri = "/api/emails/e1".parse().unwrap();
-        let Query(params) = Query::<GetEmailParams>::try_from_uri(&uri)
-            .expect("no query string at all must still deserialize");
-        assert_eq!(params.mark_read, None);
         assert!(
-            params.mark_read.unwrap_or(true),
-            "absent mark_read must preserve the current (auto-mark-read) behavior"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("immutable")),
+            "expected a BadRequest containing 'immutable', got {err:?}"
         );
     }
 
-    #[test]
-    fn get_email_params_mark_read_false_parses() {
-        let uri: axum::http::Uri = "/api/emails/e1?mark_read=false".parse().unwrap();
-        let Query(params) =
-            Query::<GetEmailParams>::try_from_uri(&uri).expect("mark_read=false must deserialize");
-        assert_eq!(params.mark_read, Some(false));
-    }
+    #[tokio::test]
+    async fn update_split_without_account_untags() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let splits_path = temp_dir.path().join("splits.json");
 
-    #[test]
-    fn get_email_params_mark_read_false_with_account_parses() {
-        // Mirrors the URL mobile actually sends: mark_read=false alongside
-        // ?account=, joined with '&' since the path already has a '?'.
-        let uri: axum::http::Uri = "/api/emails/e1?mark_read=false&account=work"
-            .parse()
-            .unwrap();
-        let Query(params) = Query::<GetEmailParams>::try_from_uri(&uri)
-            .expect("mark_read=false&account=... must deserialize");
-        assert_eq!(params.mark_read, Some(false));
-        assert_eq!(params.account.as_deref(), Some("work"));
-    }
+        let mut state = test_state(&["known"], "known");
+        state.splits = splits::SplitsStore::new(splits_path.clone(), None);
 
-    #[test]
-    fn get_email_keeps_prefetch_off_the_interactive_lane() {
-        // provider::get_emails' reserved lane is "only for fetches a user is
-        // actively waiting on … never for bulk fan-outs" — but both bundles'
-        // prefetchAdjacentEmails hit this same route fire-and-forget (three
-        // full-body fetches per email open). mark_read=false is the prefetch
-        // signature, so the handler must derive the lane from it instead of
-        // hardcoding priority for every caller (roborev 315).
-        let src = include_str!("routes.rs");
-        let handler_src = src.split("mod tests").next().unwrap_or(src);
-        let start = handler_src
-            .find("async fn get_email(")
-            .expect("get_email must exist");
-        let rest = &handler_src[start..];
-        let end = rest.find("\n}").expect("get_email must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("let priority = params.mark_read.unwrap_or(true)"),
-            "get_email must derive the interactive-lane flag from mark_read"
-        );
-        assert!(
-            !block.contains("None, true)"),
-            "the fetch closure must pass the derived flag, not hardcode priority"
+        // Seed a split with id "a" and account="known"
+        let existing_split = SplitInbox {
+            id: "a".into(),
+            name: "Original".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: Some("known".into()),
+            action: None,
+            extra: Default::default(),
+        };
+        let config = SplitsConfig {
+            splits: vec![existing_split],
+            version: splits::CONFIG_VERSION,
+            extra: Default::default(),
+        };
+        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
+
+        // Update it with account=None (PUT replaces everything)
+        let updated = SplitInbox {
+            id: "a".into(),
+            name: "Updated".into(),
+            icon: None,
+            filters: vec![],
+            match_mode: Default::default(),
+            account: None,
+            action: None,
+            extra: Default::default(),
+        };
+
+        update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+            .await
+            .expect("update_split must succeed when account field is present and valid");
+
+        // Verify the stored split has account=None
+        let reloaded = splits::load_splits(&splits_path, None);
+        assert_eq!(reloaded.splits.len(), 1);
+        assert_eq!(reloaded.splits[0].id, "a");
+        assert_eq!(
+            reloaded.splits[0].account, None,
+            "account field must be None after PUT without account"
         );
     }
 
     // =========================================================================
-    // ListEmailsParams sort deserialization (kata 09ef) — accept both known
-    // values and absence, hard-reject anything else so a typo'd sort=
-    // param can never silently fall back to the default order.
+    // default-reminder injection
     // =========================================================================
 
-    #[test]
-    fn list_emails_params_sort_absent_deserializes_to_none() {
-        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox".parse().unwrap();
-        let Query(params) = Query::<ListEmailsParams>::try_from_uri(&uri)
-            .expect("no sort param must still deserialize");
-        assert_eq!(params.sort, None);
+    const ICS_NO_ALARM: &str = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:u\r\n\
+        DTSTART:20260215T100000Z\r\nEND:VEVENT\r\nEND:VCALENDAR";
+
+    #[tokio::test]
+    async fn with_default_reminder_unset_leaves_ics_untouched() {
+        let state = test_state(&["known"], "known");
         assert_eq!(
-            params.sort.unwrap_or_default(),
-            EmailSort::DateDesc,
-            "absent sort must resolve to today's default (newest-first) behavior"
+            with_default_reminder(&state, ICS_NO_ALARM).await,
+            ICS_NO_ALARM
         );
     }
 
-    #[test]
-    fn list_emails_params_sort_date_desc_parses() {
-        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=date_desc"
-            .parse()
-            .unwrap();
-        let Query(params) =
-            Query::<ListEmailsParams>::try_from_uri(&uri).expect("sort=date_desc must deserialize");
-        assert_eq!(params.sort, Some(EmailSort::DateDesc));
+    #[tokio::test]
+    async fn with_default_reminder_injects_configured_trigger() {
+        let state = test_state(&["known"], "known");
+        state.accounts.write().await.default_reminder = Some("10m".into());
+        let out = with_default_reminder(&state, ICS_NO_ALARM).await;
+        assert!(out.contains("TRIGGER:-PT10M"), "{out}");
     }
 
-    #[test]
-    fn list_emails_params_sort_date_asc_parses() {
-        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=date_asc"
-            .parse()
-            .unwrap();
-        let Query(params) =
-            Query::<ListEmailsParams>::try_from_uri(&uri).expect("sort=date_asc must deserialize");
-        assert_eq!(params.sort, Some(EmailSort::DateAsc));
+    #[tokio::test]
+    async fn with_default_reminder_ignores_invalid_setting() {
+        let state = test_state(&["known"], "known");
+        state.accounts.write().await.default_reminder = Some("soon".into());
+        assert_eq!(
+            with_default_reminder(&state, ICS_NO_ALARM).await,
+            ICS_NO_ALARM
+        );
+    }
+
+    // =========================================================================
+    // Calendar reply log
+    // =========================================================================
+
+    fn applied_reply(account: &str, uid: &str) -> AppliedReply {
+        AppliedReply {
+            account: account.into(),
+            email_id: "e1".into(),
+            uid: uid.into(),
+            summary: "Standup".into(),
+            attendee: "bob@example.com".into(),
+            status: "ACCEPTED".into(),
+            applied_at: chrono::Utc::now(),
+        }
     }
 
     #[test]
-    fn list_emails_params_sort_garbage_is_rejected() {
-        let uri: axum::http::Uri = "/api/emails?mailbox_id=inbox&sort=banana".parse().unwrap();
-        let result = Query::<ListEmailsParams>::try_from_uri(&uri);
-        assert!(
-            result.is_err(),
-            "an unrecognized sort value must be a 400, not a silent default"
+    fn record_applied_reply_caps_log() {
+        let state = test_state(&["known"], "known");
+        for i in 0..REPLY_LOG_CAP + 5 {
+            record_applied_reply(&state, applied_reply("known", &format!("uid-{i}")));
+        }
+        let log = state.calendar_reply_log.lock().unwrap();
+        assert_eq!(log.len(), REPLY_LOG_CAP);
+        assert_eq!(
+            log.front().unwrap().uid,
+            "uid-5",
+            "oldest entries drop first"
         );
     }
 
+    #[tokio::test]
+    async fn list_applied_replies_filters_by_account_newest_first() {
+        let state = Arc::new(test_state(&["a", "b"], "a"));
+        record_applied_reply(&state, applied_reply("a", "first"));
+        record_applied_reply(&state, applied_reply("b", "other"));
+        record_applied_reply(&state, applied_reply("a", "second"));
+
+        let resp = list_applied_replies(
+            State(state),
+            Query(AccountParam {
+                account: Some("a".into()),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let uids: Vec<&str> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["uid"].as_str().unwrap())
+            .collect();
+        assert_eq!(uids, vec!["second", "first"]);
+    }
+
     // =========================================================================
-    // list_is_cacheable sort gating (roborev 291)
+    // Recipient groups
     // =========================================================================
 
-    fn cacheable_shape_params(sort: Option<EmailSort>) -> ListEmailsParams {
-        ListEmailsParams {
-            mailbox_id: Some("inbox".into()),
-            limit: None,
-            offset: None,
-            split_id: None,
-            search: None,
-            account: None,
-            starred: None,
-            sort,
+    fn team_group() -> groups::RecipientGroup {
+        groups::RecipientGroup {
+            id: "team".into(),
+            name: "Team".into(),
+            members: vec!["a@x.com".into(), "b@x.com".into()],
         }
     }
 
-    #[test]
-    fn list_is_cacheable_true_for_default_shape_and_sort() {
-        let params = cacheable_shape_params(None);
-        assert!(
-            list_is_cacheable(&params, 0, EmailSort::DateDesc),
-            "default-inbox shape with default sort must remain cacheable"
+    #[tokio::test]
+    async fn create_group_persists_and_rejects_duplicate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+        let state = Arc::new(state);
+
+        create_group(State(state.clone()), Json(team_group()))
+            .await
+            .expect("first create must succeed");
+        assert_eq!(
+            groups::load_groups(&state.groups_config_path).groups,
+            vec![team_group()]
         );
-    }
 
-    #[test]
-    fn list_is_cacheable_false_for_date_asc_sort() {
-        // Regression test (roborev 291): a DateAsc request used to populate
-        // its own InboxKey slot, keyed apart from the warmer's DateDesc
-        // entry (kata 09ef). But the background warmer only ever re-warms
-        // the DateDesc slot and there's no TTL, so that DateAsc slot was
-        // never refreshed — a user sitting in "Oldest first" would never
-        // see new mail until some unrelated local mutation invalidated the
-        // whole account's cache. The fix: non-default sorts are no longer
-        // cacheable at all, so they always fetch live (no read, no write).
-        // DateDesc behavior is untouched — see the sibling
-        // `list_is_cacheable_true_for_default_shape_and_sort` test.
-        let params = cacheable_shape_params(Some(EmailSort::DateAsc));
+        let err = create_group(State(state), Json(team_group()))
+            .await
+            .err()
+            .expect("duplicate id must be rejected");
         assert!(
-            !list_is_cacheable(&params, 0, EmailSort::DateAsc),
-            "a DateAsc request must always bypass the prefetch cache"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("already exists")),
+            "expected a BadRequest containing 'already exists', got {err:?}"
         );
     }
 
-    #[test]
-    fn list_is_cacheable_still_false_for_non_default_shape() {
-        // Sanity check that extracting `list_is_cacheable` didn't change
-        // the pre-existing (non-sort) gating conditions.
-        let mut params = cacheable_shape_params(None);
-        params.split_id = Some("primary".into());
-        assert!(!list_is_cacheable(&params, 0, EmailSort::DateDesc));
+    #[tokio::test]
+    async fn delete_group_missing_is_not_found() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
 
-        let mut params = cacheable_shape_params(None);
-        params.starred = Some(true);
-        assert!(!list_is_cacheable(&params, 0, EmailSort::DateDesc));
+        let err = delete_group(State(Arc::new(state)), Path("nope".into()))
+            .await
+            .err()
+            .expect("deleting an unknown group must fail");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
 
-        let params = cacheable_shape_params(None);
+    #[tokio::test]
+    async fn send_with_unknown_group_rejects_before_session_lookup() {
+        // No sessions are registered in test_state, so reaching
+        // resolve_session would fail with "Unknown account" instead.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+
+        let body: SendEmailBody = serde_json::from_value(serde_json::json!({
+            "to": ["group:missing"],
+            "subject": "hi",
+            "body": "hello",
+        }))
+        .unwrap();
+        let err = send_email_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("unknown group must be rejected");
         assert!(
-            !list_is_cacheable(&params, 10, EmailSort::DateDesc),
-            "non-zero offset must not be cacheable"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown group 'missing'")),
+            "got {err:?}"
         );
     }
 
-    #[test]
-    fn mobile_app_js_prefetch_requests_mark_read_false() {
-        let start = MOBILE_APP_JS
-            .find("function prefetchAdjacentEmails")
-            .expect("prefetchAdjacentEmails must exist");
-        let rest = &MOBILE_APP_JS[start..];
-        let end = rest.find("\n}").expect("prefetchAdjacentEmails must close");
-        let block = &rest[..end];
+    #[tokio::test]
+    async fn invite_to_guarded_recipient_needs_a_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.send_guard_path = temp_dir.path().join("send-guard.json");
+        std::fs::write(
+            &state.send_guard_path,
+            r#"{"recipients": ["legal@"], "totpSecret": "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"}"#,
+        )
+        .unwrap();
+        let body: SendInviteBody = serde_json::from_value(serde_json::json!({
+            "to": ["legal@corp.example"],
+            "subject": "Sync",
+            "body": "Agenda attached.",
+            "summary": "Sync",
+            "start": "2026-06-01T10:00:00",
+            "end": "2026-06-01T11:00:00",
+            "tz": "UTC",
+            "from_address": "me@x.example",
+        }))
+        .unwrap();
+        let err = send_invite_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("guarded invite needs a code");
         assert!(
-            block.contains("mark_read=false"),
-            "prefetching adjacent emails must not silently mark them read \
-             (pass ?mark_read=false on the GET)"
+            matches!(err, Error::ConfirmationRequired { ref recipients, .. } if recipients == &["legal@corp.example"]),
+            "got {err:?}"
         );
     }
 
-    #[test]
-    fn mobile_app_js_cache_hit_detail_marks_read_on_server() {
-        // Opening a prefetched (mark_read=false) email from cache issues no
-        // GET, so nothing tells the server it's now read unless
-        // renderScreenDetail does so explicitly — otherwise the local
-        // isUnread flip masks a still-unread server record until the next
-        // refresh resurfaces it (roborev 286).
-        let start = MOBILE_APP_JS
-            .find("async function renderScreenDetail")
-            .expect("renderScreenDetail must exist");
-        let rest = &MOBILE_APP_JS[start..];
-        let end = rest.find("\n}").expect("renderScreenDetail must close");
-        let block = &rest[..end];
+    #[tokio::test]
+    async fn send_expands_the_accounts_address_book_groups() {
+        // The guard reports the expanded recipients, which shows the
+        // address book group resolved without needing a session.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+        state.contacts_path = temp_dir.path().join("contacts.json");
+        state.send_guard_path = temp_dir.path().join("send-guard.json");
+        std::fs::write(
+            &state.send_guard_path,
+            r#"{"recipients": ["press@"], "totpSecret": "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"}"#,
+        )
+        .unwrap();
+        let mut index = contacts::ContactsIndex::default();
+        index.accounts.insert(
+            "known".into(),
+            contacts::AddressBook {
+                groups: vec![groups::RecipientGroup {
+                    id: "media".into(),
+                    name: "Media".into(),
+                    members: vec!["press@news.example".into()],
+                }],
+                ..Default::default()
+            },
+        );
+        contacts::save_index(&index, &state.contacts_path).unwrap();
+
+        let body: SendEmailBody = serde_json::from_value(serde_json::json!({
+            "to": ["group:media"],
+            "subject": "hi",
+            "body": "hello",
+        }))
+        .unwrap();
+        let err = send_email_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("guarded member needs a code");
         assert!(
-            block.contains("/mark-read"),
-            "renderScreenDetail's cache-hit path must explicitly mark the \
-             email read on the server"
+            matches!(err, Error::ConfirmationRequired { ref recipients, .. } if recipients == &["press@news.example"]),
+            "got {err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_outside_work_hours_needs_confirmation() {
+        // No working days at all: every moment is out of hours.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.send_hours_path = temp_dir.path().join("send-hours.json");
+        std::fs::write(
+            &state.send_hours_path,
+            r#"{"workHours": {"start": "08:00", "end": "18:00", "days": []}}"#,
+        )
+        .unwrap();
+        let state = Arc::new(state);
+        let body = |confirm: bool| -> SendEmailBody {
+            serde_json::from_value(serde_json::json!({
+                "to": ["ann@example.com"],
+                "subject": "hi",
+                "body": "hello",
+                "confirm_send_time": confirm,
+            }))
+            .unwrap()
+        };
+
+        let err = send_email_handler(
+            State(state.clone()),
+            Query(AccountParam::default()),
+            Json(body(false)),
+        )
+        .await
+        .err()
+        .expect("out-of-hours send must be confirmed");
+        assert!(
+            matches!(err, Error::SendTimeWarning { ref reasons, suggested_send_at: None, .. } if reasons.len() == 1),
+            "got {err:?}"
         );
+
+        // Confirmed, it gets past the guard to the (missing) session.
+        let err = send_email_handler(
+            State(state),
+            Query(AccountParam::default()),
+            Json(body(true)),
+        )
+        .await
+        .err()
+        .unwrap();
         assert!(
-            block.contains("cacheHit"),
-            "the mark-read call must be gated to the cache-hit path only — \
-             the network-fetch path's GET already auto-marks read server-side"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
+            "got {err:?}"
         );
     }
 
-    #[test]
-    fn app_js_prefetch_requests_mark_read_false() {
-        // Desktop counterpart to mobile_app_js_prefetch_requests_mark_read_false
-        // (roborev 302, fix 2): the bare GET auto-marks read server-side, so
-        // background warm-up must never silently consume unread state for an
-        // email the user hasn't actually opened.
-        let start = APP_JS
-            .find("function prefetchAdjacentEmails")
-            .expect("prefetchAdjacentEmails must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("prefetchAdjacentEmails must close");
-        let block = &rest[..end];
+    #[tokio::test]
+    async fn logs_tail_rejects_an_unknown_level() {
+        let err = logs_tail(Query(LogTailParams {
+            level: Some("chatty".into()),
+            backlog: None,
+        }))
+        .await
+        .err()
+        .unwrap();
         assert!(
-            block.contains("mark_read=false"),
-            "prefetching adjacent emails must not silently mark them read \
-             (pass ?mark_read=false on the GET)"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("chatty")),
+            "got {err:?}"
+        );
+        assert!(
+            logs_tail(Query(LogTailParams {
+                level: Some("debug".into()),
+                backlog: Some(0),
+            }))
+            .await
+            .is_ok()
         );
     }
 
+    #[tokio::test]
+    async fn cancel_send_calls_back_a_pending_send_once() {
+        let mut state = test_state(&["known"], "known");
+        state.undo_send = undo_send::UndoSendQueue::new(std::time::Duration::from_secs(10));
+        let state = Arc::new(state);
+        let task = tokio::spawn(std::future::pending::<()>());
+        let token = state.undo_send.insert(
+            undo_send::PendingSend::new("known", Some("draft-1".into()), chrono::Utc::now()),
+            task.abort_handle(),
+        );
+
+        let resp = cancel_send(
+            State(state.clone()),
+            Path(token.clone()),
+            Query(AccountParam::default()),
+        )
+        .await
+        .unwrap();
+        let body = axum::body::to_bytes(resp.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["draftId"], "draft-1");
+        assert!(task.await.unwrap_err().is_cancelled());
+
+        let resp = send_status(
+            State(state.clone()),
+            Path(token.clone()),
+            Query(AccountParam::default()),
+        )
+        .await
+        .unwrap();
+        let body = axum::body::to_bytes(resp.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "cancelled");
+
+        let err = cancel_send(State(state), Path(token), Query(AccountParam::default()))
+            .await
+            .err()
+            .expect("a send can only be cancelled once");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
+
     #[test]
-    fn app_js_cache_hit_detail_marks_read_on_server() {
-        // Desktop counterpart to mobile_app_js_cache_hit_detail_marks_read_on_server
-        // (roborev 302, fix 2): opening a prefetched (mark_read=false) email
-        // from cache issues no GET, so nothing tells the server it's now read
-        // unless loadEmailDetail's cache-hit branch does so explicitly.
-        let start = APP_JS
-            .find("async function loadEmailDetail")
-            .expect("loadEmailDetail must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("loadEmailDetail must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("/mark-read"),
-            "loadEmailDetail's cache-hit path must explicitly mark the email \
-             read on the server"
+    fn summary_display_name_prefers_contact_over_header() {
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        email.from[0].name = Some("ACME Billing Dept".into());
+        let names = HashMap::from([("sender@example.com".to_string(), "Ann Lee".to_string())]);
+        assert_eq!(email_summary_json(&email, &names)["displayName"], "Ann Lee");
+        assert_eq!(
+            email_summary_json(&email, &HashMap::new())["displayName"],
+            "ACME Billing Dept"
         );
+        email.from[0].name = None;
+        assert!(email_summary_json(&email, &HashMap::new())["displayName"].is_null());
     }
 
     #[test]
-    fn app_js_load_email_detail_never_adjusts_split_counts() {
-        // roborev 303, fix 1: split-tab counts are presence counts —
-        // compute_split_counts counts every matching email regardless of
-        // read state, so only archive/trash/removal (membership changes)
-        // should ever touch them. Marking an email read here, on either the
-        // cache-hit or network path, must not call adjustSplitCounts —
-        // mirroring toggleUnread, which never does either. The previous
-        // wave's cache-hit branch wrongly called adjustSplitCounts(-1) (and
-        // +1 on revert) with no mailbox/account guard, so a late revert
-        // could corrupt another mailbox's counts.
-        let start = APP_JS
-            .find("async function loadEmailDetail")
-            .expect("loadEmailDetail must exist");
-        let rest = &APP_JS[start..];
-        let end = rest.find("\n}").expect("loadEmailDetail must close");
-        let block = &rest[..end];
-        assert!(
-            !block.contains("adjustSplitCounts"),
-            "loadEmailDetail must never adjust split-tab counts on mark-read"
+    fn summary_carries_flag_color_and_spam() {
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        let row = email_summary_json(&email, &HashMap::new());
+        assert!(row["flagColor"].is_null() && row["spam"].is_null());
+
+        email.keywords.insert("$flagged".into(), true);
+        email.keywords.insert("$MailFlagBit0".into(), true);
+        email.keywords.insert("$MailFlagBit1".into(), true);
+        email.spam = Some(SpamInfo {
+            score: Some(6.5),
+            ..Default::default()
+        });
+        let row = email_summary_json(&email, &HashMap::new());
+        assert_eq!(row["flagColor"], "green");
+        assert_eq!(row["spam"]["score"], 6.5);
+    }
+
+    #[test]
+    fn summary_lists_custom_keywords() {
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        email.keywords.insert("$seen".into(), true);
+        email.keywords.insert("client-acme".into(), true);
+        let row = email_summary_json(&email, &HashMap::new());
+        assert_eq!(row["keywords"], serde_json::json!(["client-acme"]));
+    }
+
+    #[test]
+    fn flag_color_body_accepts_a_color_or_null() {
+        let body: FlagColorBody = serde_json::from_str(r#"{"color": "purple"}"#).unwrap();
+        assert_eq!(body.color, Some(FlagColor::Purple));
+        let body: FlagColorBody = serde_json::from_str(r#"{"color": null}"#).unwrap();
+        assert_eq!(body.color, None);
+        assert!(serde_json::from_str::<FlagColorBody>(r#"{"color": "red"}"#).is_err());
+    }
+
+    #[test]
+    fn thread_message_carries_body_and_list_fields() {
+        let mut email = test_email_with_recipients(vec!["me@example.com"], vec![]);
+        email.text_body = Some("Sounds good".into());
+        email.in_reply_to = Some("<m1@example.com>".into());
+        let json = thread_message_json(&email, &HashMap::new());
+        assert_eq!(json["threadId"], "thread-id");
+        assert_eq!(json["textBody"], "Sounds good");
+        assert_eq!(json["inReplyTo"], "<m1@example.com>");
+        assert_eq!(json["isTruncated"], false);
+        assert!(json["displayName"].is_null());
+    }
+
+    #[tokio::test]
+    async fn list_drafts_rejects_out_of_range_limit() {
+        let state = Arc::new(test_state(&["known"], "known"));
+        for limit in [0, MAX_DRAFTS_LIST + 1] {
+            let err = list_drafts_handler(
+                State(state.clone()),
+                Query(ListDraftsParams {
+                    account: None,
+                    limit: Some(limit),
+                }),
+            )
+            .await
+            .err()
+            .expect("limit out of range");
+            assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn list_contacts_searches_only_the_accounts_book() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known", "other"], "known");
+        state.contacts_path = temp_dir.path().join("contacts.json");
+        let book = |name: &str, email: &str| contacts::AddressBook {
+            contacts: vec![contacts::Contact {
+                uid: email.into(),
+                name: name.into(),
+                emails: vec![email.into()],
+            }],
+            ..Default::default()
+        };
+        let mut index = contacts::ContactsIndex::default();
+        index
+            .accounts
+            .insert("known".into(), book("Ann Lee", "ann@example.com"));
+        index
+            .accounts
+            .insert("other".into(), book("Ann Other", "ann@other.example"));
+        contacts::save_index(&index, &state.contacts_path).unwrap();
+        let state = Arc::new(state);
+
+        let resp = list_contacts(
+            State(state.clone()),
+            Query(ContactsParams {
+                account: None,
+                q: "ann".into(),
+                limit: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"name": "Ann Lee", "email": "ann@example.com"}])
         );
     }
 
-    #[test]
-    fn app_js_network_open_marks_email_read_locally() {
-        // roborev 303, fix 2: get_email (src/routes.rs) fetches the email,
-        // then marks it read as a side effect — the JSON it returns still
-        // reflects the pre-mark isUnread state. Without a local flip,
-        // emailCache and the list row are left stale, so a later cache-hit
-        // reopen of loadEmailDetail sees isUnread=true and misfires the
-        // mark-read POST it issues from the cache-hit branch above. Mirrors
-        // mobile app.js's network path (renderScreenDetail).
-        let start = APP_JS
-            .find("async function loadEmailDetail")
-            .expect("loadEmailDetail must exist");
-        let rest = &APP_JS[start..];
-        let fetch_pos = rest
-            .find("const email = await api('GET', `/emails/${emailId}`);")
-            .expect("loadEmailDetail's network path must fetch the email");
-        let after_fetch = &rest[fetch_pos..];
-        let end = after_fetch
-            .find("} catch (err)")
-            .expect("loadEmailDetail's network path must have a catch block");
-        let block = &after_fetch[..end];
-        assert!(
-            block.contains("email.isUnread = false"),
-            "loadEmailDetail's network path must flip isUnread on the cached email object"
-        );
-        assert!(
-            block.contains("listItem.isUnread = false"),
-            "loadEmailDetail's network path must flip isUnread on the matching list row"
-        );
-        // roborev 304: the flip alone leaves the row's unread styling stale —
-        // returning to the list only toggles CSS classes — so the network
-        // path must also re-render the list (guarded on the email actually
-        // having been unread).
-        assert!(
-            block.contains("if (wasUnread) renderEmailList()"),
-            "loadEmailDetail's network path must re-render the list after the flip"
-        );
-        // roborev 305: the guard must consider the ROW's pre-flip state too —
-        // a stale-unread row whose email was read elsewhere comes back with
-        // isUnread: false, and the row still needs its restyle.
+    #[tokio::test]
+    async fn send_to_guarded_recipient_needs_code_before_session_lookup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+        state.send_guard_path = temp_dir.path().join("send-guard.json");
+        std::fs::write(
+            &state.send_guard_path,
+            r#"{"recipients": ["press@"], "totpSecret": "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"}"#,
+        )
+        .unwrap();
+
+        let body: SendEmailBody = serde_json::from_value(serde_json::json!({
+            "to": ["press@news.example"],
+            "subject": "hi",
+            "body": "hello",
+        }))
+        .unwrap();
+        let err = send_email_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("guarded recipient must be challenged");
         assert!(
-            block.contains("email.isUnread || Boolean(listItem?.isUnread)"),
-            "the re-render guard must capture both the email's and the row's pre-flip state"
+            matches!(err, Error::ConfirmationRequired { ref recipients, .. } if recipients == &["press@news.example"]),
+            "got {err:?}"
         );
     }
 
     #[test]
-    fn mobile_app_js_signature_prefilled_via_clear_compose_fields() {
-        // Mirrors app_js_signature_prefilled_via_clear_compose: mobile's
-        // startCompose/startReply/startForward all call clearComposeFields
-        // first, so that's the single place to prefill from.
-        let start = MOBILE_APP_JS
-            .find("function clearComposeFields()")
-            .expect("clearComposeFields must exist");
-        let rest = &MOBILE_APP_JS[start..];
-        let end = rest.find("\n}").expect("clearComposeFields must close");
-        let block = &rest[..end];
-        assert!(
-            block.contains("composeSignaturePrefill()"),
-            "clearComposeFields must prefill the body via composeSignaturePrefill()"
+    fn moved_response_names_the_target_mailbox() {
+        let papierkorb = Mailbox {
+            id: "mb-t".into(),
+            name: "Papierkorb".into(),
+            role: Some("trash".into()),
+            total_emails: 0,
+            unread_emails: 0,
+            parent_id: None,
+        };
+        assert_eq!(
+            moved_response(true, Some(papierkorb)),
+            serde_json::json!({ "success": true, "mailbox": { "id": "mb-t", "name": "Papierkorb" } })
         );
-        assert!(
-            block.contains("setSelectionRange(0, 0)"),
-            "clearComposeFields must place the cursor at position 0 after prefilling"
+        assert_eq!(
+            moved_response(true, None)["mailbox"],
+            serde_json::Value::Null
         );
+    }
+
+    #[tokio::test]
+    async fn send_with_invalid_recipient_rejects_before_session_lookup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+
+        let body: SendEmailBody = serde_json::from_value(serde_json::json!({
+            "to": ["ok@example.com"],
+            "cc": ["not an address"],
+            "subject": "hi",
+            "body": "hello",
+        }))
+        .unwrap();
+        let err = send_email_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("invalid address must be rejected");
         assert!(
-            MOBILE_APP_JS.contains("function composeSignaturePrefill"),
-            "composeSignaturePrefill helper must exist"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("Invalid recipient")),
+            "got {err:?}"
         );
+    }
+
+    #[tokio::test]
+    async fn individual_send_rejects_cc() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.groups_config_path = temp_dir.path().join("groups.json");
+        groups::save_groups(
+            &groups::GroupsConfig {
+                groups: vec![team_group()],
+            },
+            &state.groups_config_path,
+        )
+        .unwrap();
+
+        let body: SendEmailBody = serde_json::from_value(serde_json::json!({
+            "to": ["group:team"],
+            "cc": ["c@x.com"],
+            "subject": "hi",
+            "body": "hello",
+            "individual": true,
+        }))
+        .unwrap();
+        let err = send_email_handler(
+            State(Arc::new(state)),
+            Query(AccountParam::default()),
+            Json(body),
+        )
+        .await
+        .err()
+        .expect("individual send with cc must be rejected");
         assert!(
-            MOBILE_APP_JS.contains("-- \\n"),
-            "signature prefill must use the RFC 3676 delimiter '-- ' \
-             (trailing space significant) before a newline"
+            matches!(err, Error::BadRequest(ref msg) if msg.contains("cc or bcc")),
+            "got {err:?}"
         );
     }
 
+    // =========================================================================
+    // Dashboard
+    // =========================================================================
+
+    fn dashboard_email(from: &str, received_at: &str) -> Email {
+        let mut email = test_email_with_recipients(vec![], vec![]);
+        email.from[0].email = from.into();
+        email.received_at = received_at.parse().unwrap();
+        email
+    }
+
     #[test]
-    fn mobile_app_js_cancel_dirty_check_uses_prefill_baseline() {
-        // With a signature configured, clearComposeFields prefills the body
-        // with "\n\n-- \n<sig>", so a dirty check based on
-        // compose-body.value.trim() is ALWAYS truthy — plain open→Cancel
-        // would falsely raise the Discard bar with zero typing. The body
-        // dirty test must instead compare against the captured prefill
-        // baseline.
-        let start = MOBILE_APP_JS
-            .find("function clearComposeFields()")
-            .expect("clearComposeFields must exist");
-        let rest = &MOBILE_APP_JS[start..];
-        let end = rest.find("\n}").expect("clearComposeFields must close");
-        let clear_block = &rest[..end];
-        assert!(
-            clear_block.contains("state.composeBaseline"),
-            "clearComposeFields must capture the prefilled body as state.composeBaseline"
-        );
+    fn week_start_is_monday() {
+        let day = |s: &str| s.parse::<chrono::NaiveDate>().unwrap();
+        // 2026-10-16 is a Friday.
+        assert_eq!(week_start(day("2026-10-16")), day("2026-10-12"));
+        assert_eq!(week_start(day("2026-10-12")), day("2026-10-12"));
+        assert_eq!(week_start(day("2026-10-18")), day("2026-10-12"));
+    }
 
-        // The dirty check moved into composeDirty() (kata wm57: shared with
-        // autosave) — cancelCompose now delegates to it. The baseline-compare
-        // invariant lives there.
-        let start = MOBILE_APP_JS
-            .find("function composeDirty()")
-            .expect("composeDirty must exist");
-        let rest = &MOBILE_APP_JS[start..];
-        let end = rest.find("\n}").expect("composeDirty must close");
-        let dirty_block = &rest[..end];
-        assert!(
-            dirty_block.contains("!== state.composeBaseline"),
-            "composeDirty's body-dirty test must compare against state.composeBaseline"
-        );
-        assert!(
-            !dirty_block.contains("compose-body').value.trim()"),
-            "composeDirty must not treat the signature prefill as dirty via value.trim()"
-        );
-        assert!(
-            MOBILE_APP_JS.contains("if (composeDirty())"),
-            "cancelCompose must delegate its dirty decision to composeDirty()"
-        );
+    #[test]
+    fn per_day_counts_buckets_by_local_day() {
+        let start: chrono::NaiveDate = "2026-10-12".parse().unwrap();
+        // 23:30 UTC Monday is already Tuesday in Berlin.
+        let received = [
+            dashboard_email("a@x.com", "2026-10-12T08:00:00Z"),
+            dashboard_email("a@x.com", "2026-10-12T23:30:00Z"),
+        ];
+        let sent = [dashboard_email("me@x.com", "2026-10-18T12:00:00Z")];
+        let days = per_day_counts(&received, &sent, start, chrono_tz::Europe::Berlin);
+        assert_eq!(days.len(), 7);
+        assert_eq!(days[0]["date"], "2026-10-12");
+        assert_eq!(days[0]["received"], 1);
+        assert_eq!(days[1]["received"], 1);
+        assert_eq!(days[6]["sent"], 1);
+    }
+
+    #[test]
+    fn top_senders_ranks_case_folded_addresses() {
+        let emails = [
+            dashboard_email("Bob@x.com", "2026-10-12T08:00:00Z"),
+            dashboard_email("bob@x.com", "2026-10-12T09:00:00Z"),
+            dashboard_email("carol@x.com", "2026-10-12T10:00:00Z"),
+            dashboard_email("alice@x.com", "2026-10-12T11:00:00Z"),
+        ];
+        let top = top_senders(&emails, 2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0]["email"], "bob@x.com");
+        assert_eq!(top[0]["count"], 2);
+        // Ties break alphabetically.
+        assert_eq!(top[1]["email"], "alice@x.com");
+    }
+
+    #[tokio::test]
+    async fn dashboard_rejects_unknown_account() {
+        let state = test_state(&["known"], "known");
+        let err = dashboard(
+            State(Arc::new(state)),
+            Query(AccountParam {
+                account: Some("nope".into()),
+            }),
+        )
+        .await
+        .err()
+        .expect("unknown account must be rejected");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
     }
 
     // =========================================================================
-    // create_split / update_split validation tests (roborev 271)
+    // Daily digest
     // =========================================================================
 
     #[tokio::test]
-    async fn create_split_rejects_unknown_account() {
+    async fn put_digest_keeps_stored_last_sent() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let splits_path = temp_dir.path().join("splits.json");
+        let mut state = test_state(&["known"], "known");
+        state.digest_config_path = temp_dir.path().join("digest.json");
+        let sent_on: chrono::NaiveDate = "2026-10-16".parse().unwrap();
+        digest::save_digest(
+            &digest::DigestConfig {
+                last_sent: Some(sent_on),
+                ..Default::default()
+            },
+            &state.digest_config_path,
+        )
+        .unwrap();
+        let state = Arc::new(state);
+
+        put_digest(
+            State(state.clone()),
+            Json(digest::DigestConfig {
+                enabled: true,
+                splits: vec!["news".into()],
+                ..Default::default()
+            }),
+        )
+        .await
+        .expect("valid config must save");
+        let stored = digest::load_digest(&state.digest_config_path);
+        assert!(stored.enabled);
+        assert_eq!(stored.last_sent, Some(sent_on));
+    }
 
+    #[tokio::test]
+    async fn run_digest_without_splits_is_bad_request() {
+        let temp_dir = tempfile::tempdir().unwrap();
         let mut state = test_state(&["known"], "known");
-        state.splits_config_path = splits_path;
+        state.digest_config_path = temp_dir.path().join("digest.json");
 
-        let new_split = SplitInbox {
-            id: "test-split".into(),
-            name: "Test".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: Some("typo".into()),
-        };
+        let err = run_digest_handler(
+            State(Arc::new(state)),
+            Query(RunDigestParams { preview: true }),
+        )
+        .await
+        .err()
+        .expect("a digest with no splits must be rejected");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
+    }
 
-        let err = create_split(State(Arc::new(state)), Json(new_split))
+    // =========================================================================
+    // Receipts
+    // =========================================================================
+
+    fn receipt(account: &str, id: &str, date: &str, amount: f64) -> receipts::ReceiptRecord {
+        receipts::ReceiptRecord {
+            account: account.into(),
+            email_id: id.into(),
+            vendor: "Shop".into(),
+            amount: Some(amount),
+            currency: Some("USD".into()),
+            date: date.parse().unwrap(),
+            subject: "Your receipt".into(),
+            invoice_pdf: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_receipts_filters_month_and_account_and_totals() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.receipts_path = temp_dir.path().join("receipts.json");
+        let mut log = receipts::ReceiptLog::default();
+        log.merge(vec![
+            receipt("known", "a", "2026-10-03", 10.10),
+            receipt("known", "b", "2026-10-20", 5.25),
+            receipt("other", "c", "2026-10-05", 99.0),
+            receipt("known", "d", "2026-09-30", 1.0),
+        ]);
+        receipts::save_receipts(&log, &state.receipts_path).unwrap();
+
+        let resp = list_receipts(
+            State(Arc::new(state)),
+            Query(ReceiptsParams {
+                month: Some("2026-10".into()),
+                account: Some("known".into()),
+            }),
+        )
+        .await
+        .expect("valid month must list")
+        .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
             .await
-            .err()
-            .expect("create_split must reject a split tagged to an unknown account");
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<&str> = json["receipts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["emailId"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(json["totals"]["USD"], 15.35);
+    }
 
-        assert!(
-            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
-            "expected a BadRequest containing 'Unknown account', got {err:?}"
+    #[tokio::test]
+    async fn list_receipts_rejects_malformed_month() {
+        let state = test_state(&["known"], "known");
+        let err = list_receipts(
+            State(Arc::new(state)),
+            Query(ReceiptsParams {
+                month: Some("October".into()),
+                account: None,
+            }),
+        )
+        .await
+        .err()
+        .expect("a malformed month must be rejected");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
+    }
+
+    // =========================================================================
+    // Packages
+    // =========================================================================
+
+    #[tokio::test]
+    async fn list_recently_deleted_filters_account_and_window() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.recently_deleted_path = temp_dir.path().join("recently-deleted.json");
+        let trashed = |account: &str, id: &str, days_ago: i64| recently_deleted::TrashedRecord {
+            account: account.into(),
+            email_id: id.into(),
+            original_mailbox_ids: vec!["inbox".into()],
+            trashed_at: chrono::Utc::now() - chrono::Duration::days(days_ago),
+            subject: "Hi".into(),
+            from: None,
+        };
+        let journal = recently_deleted::TrashJournal {
+            trashed: vec![
+                trashed("known", "old", 10),
+                trashed("other", "theirs", 0),
+                trashed("known", "new", 1),
+            ],
+        };
+        recently_deleted::save_journal(&journal, &state.recently_deleted_path).unwrap();
+        let state = Arc::new(state);
+
+        let list = |days: Option<i64>| {
+            list_recently_deleted(
+                State(state.clone()),
+                Query(RecentlyDeletedParams {
+                    account: Some("known".into()),
+                    days,
+                }),
+            )
+        };
+        let resp = list(Some(7)).await.unwrap().into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<&str> = json["trashed"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["emailId"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["new"]);
+        assert_eq!(
+            json["trashed"][0]["originalMailboxIds"],
+            serde_json::json!(["inbox"])
         );
+
+        let err = list(Some(0)).await.err().expect("days=0 is out of range");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
+    }
+
+    #[tokio::test]
+    async fn restore_unknown_recently_deleted_is_404() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.recently_deleted_path = temp_dir.path().join("recently-deleted.json");
+        let err = restore_recently_deleted(
+            State(Arc::new(state)),
+            Path("missing".into()),
+            Query(AccountParam::default()),
+        )
+        .await
+        .err()
+        .expect("nothing journaled to restore");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
+
+    fn snoozed_record(account: &str, id: &str, wake_in_hours: i64) -> snooze::SnoozedRecord {
+        snooze::SnoozedRecord {
+            account: account.into(),
+            email_id: id.into(),
+            wake_at: chrono::Utc::now() + chrono::Duration::hours(wake_in_hours),
+            snoozed_at: chrono::Utc::now(),
+            snoozed_mailbox_id: "snoozed".into(),
+            subject: "Hi".into(),
+            from: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_snoozed_filters_account_next_first() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.snoozed_path = temp_dir.path().join("snoozed.json");
+        let journal = snooze::SnoozeJournal {
+            snoozed: vec![
+                snoozed_record("known", "later", 48),
+                snoozed_record("other", "theirs", 1),
+                snoozed_record("known", "sooner", 2),
+            ],
+        };
+        snooze::save_journal(&journal, &state.snoozed_path).unwrap();
+        let resp = list_snoozed(State(Arc::new(state)), Query(AccountParam::default()))
+            .await
+            .unwrap()
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<&str> = json["snoozed"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["emailId"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["sooner", "later"]);
+        assert_eq!(json["snoozed"][0]["snoozedMailboxId"], "snoozed");
+    }
+
+    #[tokio::test]
+    async fn snooze_into_the_past_is_bad_request() {
+        let err = snooze_email(
+            State(Arc::new(test_state(&["known"], "known"))),
+            Path("m1".into()),
+            Query(AccountParam::default()),
+            Json(SnoozeBody {
+                wake_at: chrono::Utc::now() - chrono::Duration::minutes(1),
+            }),
+        )
+        .await
+        .err()
+        .expect("a wake time in the past must be rejected");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
     }
 
     #[tokio::test]
-    async fn update_split_rejects_unknown_account() {
+    async fn unsnooze_unknown_is_404() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let splits_path = temp_dir.path().join("splits.json");
-
         let mut state = test_state(&["known"], "known");
-        state.splits_config_path = splits_path.clone();
+        state.snoozed_path = temp_dir.path().join("snoozed.json");
+        let err = unsnooze_email(
+            State(Arc::new(state)),
+            Path("missing".into()),
+            Query(AccountParam::default()),
+        )
+        .await
+        .err()
+        .expect("nothing snoozed to wake");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
 
-        // Seed a split with id "a" and no account tag
-        let existing_split = SplitInbox {
-            id: "a".into(),
-            name: "Original".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: None,
-        };
-        let config = SplitsConfig {
-            splits: vec![existing_split],
+    #[tokio::test]
+    async fn failed_wake_stays_in_the_journal() {
+        // No session is connected, so waking fails and must be retried.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.snoozed_path = temp_dir.path().join("snoozed.json");
+        let journal = snooze::SnoozeJournal {
+            snoozed: vec![
+                snoozed_record("known", "due", -1),
+                snoozed_record("known", "pending", 1),
+            ],
         };
-        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
+        snooze::save_journal(&journal, &state.snoozed_path).unwrap();
+        assert_eq!(wake_due_snoozes(&state).await, 0);
+        assert_eq!(snooze::load_journal(&state.snoozed_path), journal);
+    }
 
-        // Try to update it with account="typo"
-        let updated = SplitInbox {
-            id: "a".into(),
-            name: "Updated".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: Some("typo".into()),
+    #[tokio::test]
+    async fn muted_threads_list_newest_first_and_unmute() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.store = Arc::new(crate::storage::FileStore::new(temp_dir.path().into()));
+        let mut muted = mute::MutedThreads::default();
+        let now = chrono::Utc::now();
+        muted
+            .mute(
+                "T1",
+                Some("Old storm".into()),
+                now - chrono::Duration::days(1),
+            )
+            .unwrap();
+        muted.mute("T2", None, now).unwrap();
+        mute::save(&*state.store, "known", &muted).unwrap();
+        let state = Arc::new(state);
+        let account = || {
+            Query(AccountParam {
+                account: Some("known".into()),
+            })
         };
 
-        let err = update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+        let resp = list_muted_threads(State(state.clone()), account())
             .await
-            .err()
-            .expect("update_split must reject a split tagged to an unknown account");
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<&str> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["threadId"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["T2", "T1"]);
+        assert_eq!(json[1]["subject"], "Old storm");
 
-        assert!(
-            matches!(err, Error::BadRequest(ref msg) if msg.contains("Unknown account")),
-            "expected a BadRequest containing 'Unknown account', got {err:?}"
-        );
+        unmute_thread(State(state.clone()), Path("T1".into()), account())
+            .await
+            .unwrap();
+        assert!(!mute::load(&*state.store, "known").is_muted("T1"));
+        let again = unmute_thread(State(state.clone()), Path("T1".into()), account()).await;
+        assert!(matches!(again, Err(Error::NotFound(_))));
     }
 
     #[tokio::test]
-    async fn update_split_rejects_changed_id() {
+    async fn list_packages_filters_account_newest_first() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let splits_path = temp_dir.path().join("splits.json");
-
         let mut state = test_state(&["known"], "known");
-        state.splits_config_path = splits_path.clone();
-
-        // Seed a split with id "a"
-        let existing_split = SplitInbox {
-            id: "a".into(),
-            name: "Original".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: None,
-        };
-        let config = SplitsConfig {
-            splits: vec![existing_split],
+        state.packages_path = temp_dir.path().join("packages-tracked.json");
+        let tracked = |account: &str, number: &str, days_ago: i64| packages::TrackedPackage {
+            tracking: packages::TrackingNumber {
+                carrier: packages::Carrier::Ups,
+                number: number.into(),
+                url: String::new(),
+            },
+            account: account.into(),
+            email_id: "m1".into(),
+            subject: "Shipped".into(),
+            first_seen: chrono::Utc::now() - chrono::Duration::days(days_ago),
+            status: None,
         };
-        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
-
-        // Try to update it with a different id "b"
-        let updated = SplitInbox {
-            id: "b".into(),
-            name: "Updated".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: None,
+        let log = packages::TrackedLog {
+            packages: vec![
+                tracked("known", "old", 3),
+                tracked("other", "theirs", 0),
+                tracked("known", "new", 1),
+            ],
         };
+        packages::save_tracked(&log, &state.packages_path).unwrap();
 
-        let err = update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+        let resp = list_packages(
+            State(Arc::new(state)),
+            Query(AccountParam {
+                account: Some("known".into()),
+            }),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
             .await
-            .err()
-            .expect("update_split must reject a body id that differs from the path id");
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let numbers: Vec<&str> = json["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["number"].as_str().unwrap())
+            .collect();
+        assert_eq!(numbers, ["new", "old"]);
+        assert_eq!(json["polling"], false);
+    }
 
-        assert!(
-            matches!(err, Error::BadRequest(ref msg) if msg.contains("immutable")),
-            "expected a BadRequest containing 'immutable', got {err:?}"
+    // =========================================================================
+    // Protected senders
+    // =========================================================================
+
+    #[tokio::test]
+    async fn add_protected_sender_normalizes_and_rejects_duplicate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.protected_senders_path = temp_dir.path().join("protected-senders.json");
+        let state = Arc::new(state);
+
+        add_protected_sender(
+            State(state.clone()),
+            Json(ProtectedSenderBody {
+                sender: " Boss@Example.com ".into(),
+            }),
+        )
+        .await
+        .expect("first add must succeed");
+        assert_eq!(
+            protected::load_protected(&state.protected_senders_path).senders,
+            vec!["boss@example.com".to_string()]
         );
+
+        let err = add_protected_sender(
+            State(state),
+            Json(ProtectedSenderBody {
+                sender: "BOSS@example.com".into(),
+            }),
+        )
+        .await
+        .err()
+        .expect("duplicate must be rejected");
+        assert!(matches!(err, Error::Conflict(_)), "got {err:?}");
     }
 
     #[tokio::test]
-    async fn update_split_without_account_untags() {
+    async fn remove_protected_sender_missing_is_not_found() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let splits_path = temp_dir.path().join("splits.json");
-
         let mut state = test_state(&["known"], "known");
-        state.splits_config_path = splits_path.clone();
+        state.protected_senders_path = temp_dir.path().join("protected-senders.json");
 
-        // Seed a split with id "a" and account="known"
-        let existing_split = SplitInbox {
-            id: "a".into(),
-            name: "Original".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: Some("known".into()),
-        };
-        let config = SplitsConfig {
-            splits: vec![existing_split],
-        };
-        splits::save_splits(&config, &splits_path).expect("failed to save seed splits");
+        let err = remove_protected_sender(State(Arc::new(state)), Path("a@x.com".into()))
+            .await
+            .err()
+            .expect("removing an unknown sender must fail");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
 
-        // Update it with account=None (PUT replaces everything)
-        let updated = SplitInbox {
-            id: "a".into(),
-            name: "Updated".into(),
-            icon: None,
-            filters: vec![],
-            match_mode: Default::default(),
-            account: None,
-        };
+    #[tokio::test]
+    async fn image_senders_add_then_remove() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["known"], "known");
+        state.image_senders_path = temp_dir.path().join("image-senders.json");
+        let state = Arc::new(state);
 
-        update_split(State(Arc::new(state)), Path("a".into()), Json(updated))
+        add_image_sender(
+            State(state.clone()),
+            Json(ImageSenderBody {
+                sender: "*@Shop.example".into(),
+            }),
+        )
+        .await
+        .expect("add must succeed");
+        let config = image_proxy::load_image_senders(&state.image_senders_path);
+        assert!(image_proxy::allows(&config, "news@shop.example"));
+
+        remove_image_sender(State(state.clone()), Path("*@shop.example".into()))
             .await
-            .expect("update_split must succeed when account field is present and valid");
+            .expect("remove must succeed");
+        let err = remove_image_sender(State(state), Path("*@shop.example".into()))
+            .await
+            .err()
+            .expect("second remove must fail");
+        assert!(matches!(err, Error::NotFound(_)), "got {err:?}");
+    }
 
-        // Verify the stored split has account=None
-        let reloaded = splits::load_splits(&splits_path, None);
-        assert_eq!(reloaded.splits.len(), 1);
-        assert_eq!(reloaded.splits[0].id, "a");
-        assert_eq!(
-            reloaded.splits[0].account, None,
-            "account field must be None after PUT without account"
-        );
+    #[tokio::test]
+    async fn proxy_image_refuses_local_urls() {
+        let err = proxy_image(Query(ProxyImageParams {
+            url: "http://127.0.0.1:8000/api/accounts".into(),
+        }))
+        .await
+        .err()
+        .expect("loopback must be refused");
+        assert!(matches!(err, Error::BadRequest(_)), "got {err:?}");
     }
 
     // =========================================================================
@@ -3522,7 +11203,7 @@ mod tests {
     #[test]
     fn shared_api_js_loaded_by_both_bundles() {
         assert!(
-            INDEX_HTML.contains(r#"<script src="api.js"></script>"#),
+            INDEX_HTML.contains(r#"<script src="/api.js"></script>"#),
             "desktop index.html must load the shared api.js before app.js"
         );
         assert!(
@@ -3686,6 +11367,21 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn api_version_reports_build_and_update_status() {
+        let state = test_state(&["a"], "a");
+        let resp = version(State(Arc::new(state))).await.into_response();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["gitHash"], env!("SUPERVILLAIN_BUILD_ID"));
+        assert_eq!(body["buildDate"].as_str().unwrap().len(), 10);
+        assert_eq!(body["updateCheck"], false);
+        assert!(body["update"].is_null());
+    }
+
     async fn assert_png_icon(resp: axum::response::Response, label: &str) {
         assert_eq!(resp.status(), StatusCode::OK, "icon-{label} should be OK");
         let ct = resp
@@ -3744,6 +11440,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn shared_api_js_tells_read_only_apart_from_auth() {
+        assert!(API_JS.contains("class ApiReadOnlyError extends ApiError"));
+        assert!(API_JS.contains("body?.error === 'read_only'"));
+    }
+
     #[test]
     fn shared_api_js_handles_auth_errors() {
         // 401 and 403 must map to ApiAuthError, not plain ApiError
@@ -4908,7 +12610,11 @@ mod tests {
         );
         assert!(
             MOBILE_APP_JS.contains("function downloadAllAttachments("),
-            "Download All should mirror desktop's sequential-click helper"
+            "Download All should mirror desktop's zip download helper"
+        );
+        assert!(
+            MOBILE_APP_JS.contains("/attachments.zip"),
+            "Download All should fetch the server-side zip"
         );
     }
 
@@ -5115,6 +12821,14 @@ white   = '#fdf6e3'
         let json = r#"{"to":["a@b.com"],"subject":"Hi","body":"Hello"}"#;
         let body: SendEmailBody = serde_json::from_str(json).unwrap();
         assert!(body.attachments.is_empty());
+        assert!(body.forward_of.is_none());
+    }
+
+    #[test]
+    fn send_email_body_deserializes_forward_of() {
+        let json = r#"{"to":["a@b.com"],"subject":"Fwd: Hi","body":"FYI","forward_of":"M42"}"#;
+        let body: SendEmailBody = serde_json::from_str(json).unwrap();
+        assert_eq!(body.forward_of.as_deref(), Some("M42"));
     }
 
     #[test]
@@ -5728,6 +13442,43 @@ white   = '#fdf6e3'
         );
     }
 
+    #[test]
+    fn app_js_download_all_uses_the_zip() {
+        assert!(
+            APP_JS.contains("/attachments.zip"),
+            "Download All should fetch one server-side zip"
+        );
+    }
+
+    #[test]
+    fn thread_unread_count_counts_unseen_members() {
+        let mut read = test_email_with_recipients(vec![], vec![]);
+        read.keywords.insert("$seen".into(), true);
+        let unread = test_email_with_recipients(vec![], vec![]);
+        assert_eq!(
+            thread_unread_count(&[read.clone(), unread.clone(), unread]),
+            2
+        );
+        assert_eq!(thread_unread_count(&[read]), 0);
+    }
+
+    #[test]
+    fn app_js_marks_whole_thread_read_in_one_call() {
+        assert!(APP_JS.contains("`/threads/${encodeURIComponent(email.threadId)}/mark-read`"));
+        assert!(APP_JS.contains("g.unreadCount++"));
+    }
+
+    #[test]
+    fn app_js_snoozes_with_a_wake_time() {
+        assert!(APP_JS.contains("`/emails/${id}/snooze`, { wake_at: wakeAt.toISOString() }"));
+    }
+
+    #[test]
+    fn app_js_colors_starred_rows() {
+        assert!(APP_JS.contains("/flag-color`"));
+        assert!(APP_JS.contains("data-flag-color"));
+    }
+
     #[test]
     fn app_js_has_attachment_size_validation() {
         assert!(
@@ -6184,6 +13935,12 @@ white   = '#fdf6e3'
             has_calendar: false,
             attachments: vec![],
             in_reply_to: None,
+            body_parts: Vec::new(),
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
         }
     }
 
@@ -6210,6 +13967,11 @@ white   = '#fdf6e3'
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         }
     }
 
@@ -6566,6 +14328,40 @@ white   = '#fdf6e3'
         assert_eq!(json["calendarEvent"]["user_rsvp_status"], "ACCEPTED");
     }
 
+    #[test]
+    fn organizer_is_the_login_or_an_identity() {
+        let event = test_calendar_event(vec!["bob@example.com"]);
+        let alias = Identity {
+            id: "id1".into(),
+            email: "Org@Example.com".into(),
+            name: "Org".into(),
+        };
+        assert!(organized_by_me(&event, "org@example.com", &[]));
+        assert!(organized_by_me(&event, "me@example.com", &[alias]));
+        assert!(!organized_by_me(&event, "bob@example.com", &[]));
+        let mut no_organizer = event;
+        no_organizer.organizer_email = " ".into();
+        assert!(!organized_by_me(&no_organizer, " ", &[]));
+    }
+
+    #[test]
+    fn rsvp_response_carries_my_response() {
+        let mut event = test_calendar_event(vec!["bob@example.com"]);
+        let json = serde_json::json!({ "calendarEvent": &event });
+        assert!(json["calendarEvent"]["myResponse"].is_null());
+        let record = rsvp_log::RsvpRecord {
+            status: "TENTATIVE".into(),
+            sequence: 0,
+            responded_at: chrono::Utc::now(),
+        };
+        event.my_response = rsvp_log::my_response(None, Some(&record));
+        let json = serde_json::json!({ "calendarEvent": event });
+        let response = &json["calendarEvent"]["myResponse"];
+        assert_eq!(response["status"], "TENTATIVE");
+        assert_eq!(response["source"], "local");
+        assert!(response["respondedAt"].is_string());
+    }
+
     #[test]
     fn rsvp_response_includes_updated_attendee_status() {
         let mut event = test_calendar_event(vec!["bob@example.com", "carol@example.com"]);
@@ -6939,6 +14735,145 @@ white   = '#fdf6e3'
         assert!(body.attendees.is_empty());
     }
 
+    #[tokio::test]
+    async fn preferences_list_startup_views_of_configured_accounts() {
+        let state = test_state(&["a", "b"], "b");
+        {
+            let mut reg = state.accounts.write().await;
+            let view = |split: &str| accounts::StartupView {
+                mailbox: None,
+                split: Some(split.into()),
+            };
+            reg.startup.insert("b".into(), view("work"));
+            reg.startup.insert("gone".into(), view("old"));
+        }
+        let resp = get_preferences(State(Arc::new(state)))
+            .await
+            .into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["defaultAccount"], "b");
+        assert_eq!(
+            json["startup"],
+            serde_json::json!({ "b": { "mailbox": null, "split": "work" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn diagnostics_bundle_is_a_redacted_download() {
+        let state = test_state(&["work"], "work");
+        state.account_errors.write().await.push(AccountError {
+            account: "home".into(),
+            provider: "gmail".into(),
+            error: "refresh_token=abc expired for ann@home.example".into(),
+        });
+        let resp = diagnostics_bundle(State(Arc::new(state)))
+            .await
+            .into_response();
+        assert!(
+            resp.headers()["content-disposition"]
+                .to_str()
+                .unwrap()
+                .contains("supervillain-diagnostics-")
+        );
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let config = json["config"].as_str().unwrap();
+        assert!(config.contains("api-token = [redacted]"), "{config}");
+        assert!(!config.contains("tok\n") && !config.ends_with("tok"));
+        assert_eq!(json["version"]["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["accounts"][0]["name"], "home");
+        assert_eq!(json["accounts"][0]["connected"], false);
+        assert_eq!(
+            json["accounts"][0]["error"],
+            "refresh_token=[redacted] expired for a***@home.example"
+        );
+        assert!(json["tasks"].is_array());
+        assert!(json["recentErrors"].is_array());
+    }
+
+    #[tokio::test]
+    async fn sieve_from_split_files_into_the_chosen_mailbox() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut state = test_state(&["a"], "a");
+        state.splits = splits::SplitsStore::new(temp_dir.path().join("splits.json"), None);
+        let split: SplitInbox = serde_json::from_value(serde_json::json!({
+            "id": "shop", "name": "Shopping",
+            "filters": [{"type": "from", "pattern": "*@shop.example", "match": "exact"}]
+        }))
+        .unwrap();
+        state
+            .splits
+            .save(&SplitsConfig {
+                splits: vec![split],
+                version: splits::CONFIG_VERSION,
+                extra: Default::default(),
+            })
+            .unwrap();
+        state
+            .prefetch
+            .set_mailboxes(
+                "a",
+                vec![Mailbox {
+                    id: "M7".into(),
+                    name: "Deals".into(),
+                    role: None,
+                    total_emails: 0,
+                    unread_emails: 0,
+                    parent_id: None,
+                }],
+            )
+            .await;
+        let state = Arc::new(state);
+        let body = |mailbox_id: &str| {
+            Json(SieveFromSplitBody {
+                mailbox_id: mailbox_id.into(),
+            })
+        };
+        let params = || Query(AccountParam { account: None });
+
+        let resp = sieve_from_split(
+            State(state.clone()),
+            Path("shop".into()),
+            params(),
+            body("M7"),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(
+            json["rule"]
+                .as_str()
+                .unwrap()
+                .contains("fileinto :mailboxid \"M7\" \"Deals\";")
+        );
+        assert!(json["script"].as_str().unwrap().starts_with("require ["));
+
+        let missing = sieve_from_split(
+            State(state.clone()),
+            Path("shop".into()),
+            params(),
+            body("nope"),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(matches!(missing, Error::NotFound(_)));
+        let unknown = sieve_from_split(State(state), Path("gone".into()), params(), body("M7"))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(unknown, Error::NotFound(_)));
+    }
+
     #[test]
     fn timezone_routes_are_registered() {
         // Sentinel: the router string must mention each new endpoint so a
@@ -0,0 +1,139 @@
+//! Your own RSVP answers, remembered locally.
+//!
+//! An RSVP only reaches the calendar where the provider keeps one in
+//! sync (Fastmail's CalDAV copy, Outlook's event), and an invite that
+//! never got stored there reopens with its buttons reset. `rsvp` records
+//! each answer by event UID in the account's `rsvp` document in
+//! `storage`, and the email detail route reports the answer as
+//! `calendarEvent.myResponse`: the calendar's PARTSTAT when it has one,
+//! this log otherwise. An answer given to an older SEQUENCE doesn't count
+//! once the invite is revised.
+
+use crate::error::Error;
+use crate::storage::{self, DocumentStore, Namespace};
+use crate::types::{MyResponse, ResponseSource};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const SUBSYSTEM: &str = "rsvp";
+const RESPONSES_KEY: &str = "responses";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RsvpRecord {
+    /// `ACCEPTED`, `TENTATIVE` or `DECLINED`.
+    pub status: String,
+    /// SEQUENCE of the invite that was answered.
+    pub sequence: i32,
+    pub responded_at: DateTime<Utc>,
+}
+
+/// One account's answers, by event UID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RsvpLog {
+    #[serde(default)]
+    pub responses: BTreeMap<String, RsvpRecord>,
+}
+
+impl RsvpLog {
+    pub fn record(&mut self, uid: &str, record: RsvpRecord) {
+        self.responses.insert(uid.to_string(), record);
+    }
+
+    /// The answer to `uid`, unless it was given to an older revision than
+    /// `sequence`.
+    pub fn get(&self, uid: &str, sequence: i32) -> Option<&RsvpRecord> {
+        self.responses.get(uid).filter(|r| r.sequence >= sequence)
+    }
+}
+
+pub fn load(store: &dyn DocumentStore, account: &str) -> RsvpLog {
+    storage::get_json(
+        store,
+        &Namespace::account(SUBSYSTEM, account),
+        RESPONSES_KEY,
+    )
+    .unwrap_or_else(|e| {
+        tracing::warn!("Failed to read RSVP responses for {account}: {e}");
+        None
+    })
+    .unwrap_or_default()
+}
+
+pub fn save(store: &dyn DocumentStore, account: &str, log: &RsvpLog) -> Result<(), Error> {
+    storage::put_json(
+        store,
+        &Namespace::account(SUBSYSTEM, account),
+        RESPONSES_KEY,
+        log,
+    )
+}
+
+/// The user's answer: the calendar's PARTSTAT when it holds a real one,
+/// else what this app last sent. `NEEDS-ACTION` is no answer.
+pub fn my_response(calendar: Option<&str>, local: Option<&RsvpRecord>) -> Option<MyResponse> {
+    if let Some(status) = calendar.filter(|s| is_answer(s)) {
+        return Some(MyResponse {
+            status: status.to_string(),
+            source: ResponseSource::Calendar,
+            responded_at: None,
+        });
+    }
+    local.filter(|r| is_answer(&r.status)).map(|r| MyResponse {
+        status: r.status.clone(),
+        source: ResponseSource::Local,
+        responded_at: Some(r.responded_at),
+    })
+}
+
+fn is_answer(status: &str) -> bool {
+    matches!(status, "ACCEPTED" | "TENTATIVE" | "DECLINED")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(status: &str, sequence: i32) -> RsvpRecord {
+        RsvpRecord {
+            status: status.into(),
+            sequence,
+            responded_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn answers_to_older_revisions_are_stale() {
+        let mut log = RsvpLog::default();
+        log.record("uid-1", record("ACCEPTED", 2));
+        assert!(log.get("uid-1", 2).is_some());
+        assert!(log.get("uid-1", 1).is_some());
+        assert!(log.get("uid-1", 3).is_none());
+        assert!(log.get("uid-2", 0).is_none());
+    }
+
+    #[test]
+    fn calendar_answer_wins_over_the_local_one() {
+        let local = record("DECLINED", 0);
+        let from_calendar = my_response(Some("ACCEPTED"), Some(&local)).unwrap();
+        assert_eq!(from_calendar.status, "ACCEPTED");
+        assert_eq!(from_calendar.source, ResponseSource::Calendar);
+        let fallback = my_response(Some("NEEDS-ACTION"), Some(&local)).unwrap();
+        assert_eq!(fallback.status, "DECLINED");
+        assert_eq!(fallback.responded_at, Some(local.responded_at));
+        assert!(my_response(None, None).is_none());
+    }
+
+    #[test]
+    fn log_round_trips_through_the_store_per_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::storage::FileStore::new(dir.path().to_path_buf());
+        assert_eq!(load(&store, "work"), RsvpLog::default());
+        let mut log = RsvpLog::default();
+        log.record("uid-1", record("TENTATIVE", 0));
+        save(&store, "work", &log).unwrap();
+        assert_eq!(load(&store, "work"), log);
+        assert_eq!(load(&store, "home"), RsvpLog::default());
+    }
+}
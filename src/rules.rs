@@ -0,0 +1,618 @@
+//! Filing rules: split-style filters plus the actions to take on a match.
+//!
+//! `POST /api/rules/simulate` dry-runs a [`Rule`] against recent mail
+//! before anything acts on it: [`simulate`] groups the messages each
+//! action would change, leaving out ones it would be a no-op for (marking
+//! a read message read, moving a message into the mailbox it's already
+//! in). Actions that move mail skip protected senders, as every bulk path
+//! does; those messages are reported separately. Nothing is persisted.
+//!
+//! A split with an `action` is a rule that runs: [`spawn_filer`] looks at
+//! each connected account's inbox once a minute and applies the actions
+//! of the splits new messages match, planned by [`plan`] under the same
+//! no-op and protected-sender rules. "New" means received after the
+//! account's [`Watermark`], which is persisted in `storage`. Mail moved
+//! back into the inbox keeps its old `receivedAt` and is left alone, and
+//! mail that arrived while the server was down is filed once it is back
+//! (up to [`FILER_MAX_PAGES`] pages). Mail already in the inbox when the
+//! filer first runs for an account, or when actions come back after none
+//! were configured, is left alone too.
+
+use crate::error::Error;
+use crate::protected::{self, ProtectedSendersConfig};
+use crate::provider::{self, ProviderSession};
+use crate::splits::{self, CompiledSplit};
+use crate::storage::{self, Namespace};
+use crate::types::{AppState, Email, MatchMode, RuleAction, SplitFilter, SplitInbox};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Inbox messages the filer reads per page.
+const FILER_SCAN: usize = 100;
+
+/// Pages read per account and tick before the filer stops catching up.
+pub const FILER_MAX_PAGES: usize = 5;
+
+const WATERMARK_SUBSYSTEM: &str = "split-actions";
+const WATERMARK_KEY: &str = "watermark";
+
+/// The newest mail the filer has handled on one account: anything
+/// received later is new, and so is mail received at the same instant
+/// that isn't in `ids`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    pub received_at: DateTime<Utc>,
+    #[serde(default)]
+    pub ids: Vec<String>,
+}
+
+impl Watermark {
+    pub fn is_new(&self, email: &Email) -> bool {
+        email.received_at > self.received_at
+            || (email.received_at == self.received_at && !self.ids.contains(&email.id))
+    }
+
+    /// Move past `emails`.
+    pub fn advance(&mut self, emails: &[Email]) {
+        for email in emails {
+            if email.received_at > self.received_at {
+                self.received_at = email.received_at;
+                self.ids.clear();
+            }
+            if email.received_at == self.received_at && !self.ids.contains(&email.id) {
+                self.ids.push(email.id.clone());
+            }
+        }
+    }
+}
+
+fn load_watermark(state: &AppState, account: &str) -> Option<Watermark> {
+    storage::get_json(
+        &*state.store,
+        &Namespace::account(WATERMARK_SUBSYSTEM, account),
+        WATERMARK_KEY,
+    )
+    .unwrap_or_else(|e| {
+        tracing::warn!("Failed to read the split-action watermark for {account}: {e}");
+        None
+    })
+}
+
+fn save_watermark(state: &AppState, account: &str, mark: &Watermark) -> Result<(), Error> {
+    storage::put_json(
+        &*state.store,
+        &Namespace::account(WATERMARK_SUBSYSTEM, account),
+        WATERMARK_KEY,
+        mark,
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub filters: Vec<SplitFilter>,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    #[serde(default)]
+    pub actions: Vec<RuleAction>,
+}
+
+/// Reject rules that can't run as written: no filters, no actions, a
+/// filter that fails split validation, or more than one action that
+/// moves the message.
+pub fn validate(rule: &Rule) -> Result<(), String> {
+    if rule.filters.is_empty() {
+        return Err("rule has no filters".into());
+    }
+    if rule.actions.is_empty() {
+        return Err("rule has no actions".into());
+    }
+    for filter in &rule.filters {
+        splits::validate_filter(filter)?;
+    }
+    if rule.actions.iter().filter(|a| a.moves()).count() > 1 {
+        return Err("a rule can archive, trash or move a message, not several of them".into());
+    }
+    rule.actions.iter().try_for_each(RuleAction::validate)
+}
+
+/// The messages one action would change.
+#[derive(Debug)]
+pub struct ActionGroup<'a> {
+    pub action: RuleAction,
+    pub emails: Vec<&'a Email>,
+}
+
+#[derive(Debug)]
+pub struct Simulation<'a> {
+    /// One per rule action, in the rule's order.
+    pub groups: Vec<ActionGroup<'a>>,
+    /// Messages the filters match.
+    pub matched: usize,
+    /// Matches a moving action skipped because the sender is protected.
+    pub protected: Vec<&'a Email>,
+}
+
+pub fn simulate<'a>(
+    rule: &Rule,
+    emails: &'a [Email],
+    protected_senders: &ProtectedSendersConfig,
+) -> Simulation<'a> {
+    let matcher = CompiledSplit::from_filters(&rule.filters, &rule.match_mode);
+    let matched: Vec<&Email> = emails.iter().filter(|e| matcher.matches(e)).collect();
+    let is_protected = |e: &Email| {
+        e.from
+            .iter()
+            .any(|a| protected::is_protected(protected_senders, &a.email))
+    };
+    let mut protected_hits: Vec<&Email> = Vec::new();
+    let groups = rule
+        .actions
+        .iter()
+        .map(|action| {
+            let mut affected = Vec::new();
+            for &email in &matched {
+                if !changes(action, email) {
+                    continue;
+                }
+                if action.moves() && is_protected(email) {
+                    if !protected_hits.iter().any(|p| p.id == email.id) {
+                        protected_hits.push(email);
+                    }
+                    continue;
+                }
+                affected.push(email);
+            }
+            ActionGroup {
+                action: action.clone(),
+                emails: affected,
+            }
+        })
+        .collect();
+    Simulation {
+        groups,
+        matched: matched.len(),
+        protected: protected_hits,
+    }
+}
+
+/// The filer's work for `emails`: each message gets the actions of every
+/// split that matches it, in config order, grouped by action. Only the
+/// first moving action counts, since a message leaves the inbox once;
+/// protected senders get no moving action at all, and no-ops are dropped.
+/// Groups that leave the message where it is come first, so they run
+/// before a move that could change its id.
+pub fn plan<'a>(
+    splits: &[SplitInbox],
+    emails: &'a [Email],
+    protected_senders: &ProtectedSendersConfig,
+) -> Vec<ActionGroup<'a>> {
+    let rules: Vec<(&RuleAction, CompiledSplit)> = splits
+        .iter()
+        .filter_map(|s| s.action.as_ref().map(|a| (a, CompiledSplit::new(s))))
+        .collect();
+    let mut groups: Vec<ActionGroup<'a>> = Vec::new();
+    for email in emails {
+        let is_protected = email
+            .from
+            .iter()
+            .any(|a| protected::is_protected(protected_senders, &a.email));
+        let mut moved = false;
+        for (action, matcher) in &rules {
+            if !matcher.matches(email) || !changes(action, email) {
+                continue;
+            }
+            if action.moves() {
+                if moved || is_protected {
+                    continue;
+                }
+                moved = true;
+            }
+            match groups.iter_mut().find(|g| &g.action == *action) {
+                Some(group) if !group.emails.iter().any(|e| e.id == email.id) => {
+                    group.emails.push(email)
+                }
+                Some(_) => {}
+                None => groups.push(ActionGroup {
+                    action: (*action).clone(),
+                    emails: vec![email],
+                }),
+            }
+        }
+    }
+    groups.sort_by_key(|g| g.action.moves());
+    groups
+}
+
+/// Apply split actions to new inbox mail every `interval`; see the
+/// module docs.
+pub fn spawn_filer(state: Arc<AppState>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("split-actions", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Whether actions were configured on the last tick. Watermarks go
+        // when the actions do, so a rule added later starts from then
+        // rather than acting on everything since.
+        let mut active = true;
+        loop {
+            ticker.tick().await;
+            health.ticked("split-actions");
+            let config = state.splits.current();
+            let accounts: Vec<String> = {
+                let reg = state.accounts.read().await;
+                reg.sessions.keys().cloned().collect()
+            };
+            if !config.splits.iter().any(|s| s.action.is_some()) {
+                if std::mem::replace(&mut active, false) {
+                    for account in &accounts {
+                        let ns = Namespace::account(WATERMARK_SUBSYSTEM, account);
+                        if let Err(e) = state.store.delete(&ns, WATERMARK_KEY) {
+                            tracing::warn!("Failed to drop the watermark for {account}: {e}");
+                        }
+                    }
+                }
+                continue;
+            }
+            active = true;
+            if crate::read_only::enabled(&state).await {
+                continue;
+            }
+            for account in accounts {
+                let splits = (*config).clone().scoped_to(Some(&account)).splits;
+                match file_new_mail(&state, &account, &splits).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Split actions changed {n} message(s) on {account}"),
+                    Err(e) => {
+                        tracing::warn!("Split actions failed for {account}: {e}");
+                        health.failed("split-actions", format!("{account}: {e}"));
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// One filer pass over `account`'s inbox mail received since its
+/// watermark. Returns how many messages an action changed.
+async fn file_new_mail(
+    state: &AppState,
+    account: &str,
+    splits: &[SplitInbox],
+) -> Result<usize, Error> {
+    let session_lock = {
+        let reg = state.accounts.read().await;
+        reg.sessions
+            .get(account)
+            .cloned()
+            .ok_or(Error::NotConnected)?
+    };
+    let session = session_lock.read().await;
+    let inbox = provider::get_mailboxes(&session)
+        .await?
+        .into_iter()
+        .find(|m| m.role.as_deref() == Some("inbox"))
+        .ok_or_else(|| Error::NotFound("No inbox mailbox".into()))?;
+    let page = async |page: usize| -> Result<(usize, Vec<Email>), Error> {
+        let ids = provider::query_emails(
+            &session,
+            Some(&inbox.id),
+            FILER_SCAN,
+            page * FILER_SCAN,
+            None,
+            crate::types::EmailSort::default(),
+        )
+        .await?;
+        if ids.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+        let emails = provider::get_emails(&session, &ids, false, None, false).await?;
+        Ok((ids.len(), emails))
+    };
+    let Some(mut watermark) = load_watermark(state, account) else {
+        // First look: start from the newest message and act on nothing.
+        let (_, newest) = page(0).await?;
+        let mut mark = Watermark {
+            received_at: if newest.is_empty() {
+                Utc::now()
+            } else {
+                DateTime::<Utc>::MIN_UTC
+            },
+            ids: Vec::new(),
+        };
+        mark.advance(&newest);
+        save_watermark(state, account, &mark)?;
+        return Ok(0);
+    };
+    // Newest first, so reading stops at the first page that reaches
+    // mail the watermark already covers.
+    let mut emails = Vec::new();
+    for n in 0..FILER_MAX_PAGES {
+        let (listed, fetched) = page(n).await?;
+        let caught_up = listed < FILER_SCAN || fetched.iter().any(|e| !watermark.is_new(e));
+        emails.extend(fetched.into_iter().filter(|e| watermark.is_new(e)));
+        if caught_up {
+            break;
+        }
+        if n + 1 == FILER_MAX_PAGES {
+            tracing::warn!(
+                "Split actions on {account}: more than {} new messages, older ones skipped",
+                FILER_MAX_PAGES * FILER_SCAN
+            );
+        }
+    }
+    if emails.is_empty() {
+        return Ok(0);
+    }
+    let protected_senders = protected::load_protected(&state.protected_senders_path);
+    let groups = plan(splits, &emails, &protected_senders);
+    let mut changed = 0;
+    for group in &groups {
+        // On failure the watermark stays put and the next tick retries;
+        // what already happened plans as a no-op then.
+        changed += apply(state, account, &session, group).await?;
+    }
+    drop(session);
+    watermark.advance(&emails);
+    save_watermark(state, account, &watermark)?;
+    if changed > 0 {
+        state.prefetch.invalidate(account).await;
+    }
+    Ok(changed)
+}
+
+async fn apply(
+    state: &AppState,
+    account: &str,
+    session: &ProviderSession,
+    group: &ActionGroup<'_>,
+) -> Result<usize, Error> {
+    let ids: Vec<String> = group.emails.iter().map(|e| e.id.clone()).collect();
+    let mut changed = 0;
+    match &group.action {
+        RuleAction::Archive => changed = provider::archive_batch(session, &ids).await?,
+        RuleAction::MarkRead => changed = provider::mark_read_batch(session, &ids).await?,
+        RuleAction::Flag => {
+            for id in &ids {
+                changed += usize::from(provider::toggle_flag(session, id).await?);
+            }
+        }
+        RuleAction::Move { mailbox_id } => {
+            for id in &ids {
+                changed += usize::from(provider::move_to_mailbox(session, id, mailbox_id).await?);
+            }
+        }
+        RuleAction::Trash => {
+            let trash_id = provider::trash_mailbox_id(session);
+            let mut records = Vec::new();
+            for email in &group.emails {
+                if provider::trash(session, &email.id).await? {
+                    changed += 1;
+                    records.extend(crate::recently_deleted::TrashedRecord::before_trash(
+                        account,
+                        email,
+                        trash_id.as_deref(),
+                        chrono::Utc::now(),
+                    ));
+                }
+            }
+            if !records.is_empty() {
+                let _guard = state.recently_deleted_lock.lock().await;
+                let mut journal =
+                    crate::recently_deleted::load_journal(&state.recently_deleted_path);
+                for record in records {
+                    journal.record(record);
+                }
+                if let Err(e) =
+                    crate::recently_deleted::save_journal(&journal, &state.recently_deleted_path)
+                {
+                    tracing::warn!("Failed to save trash journal: {e}");
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Whether `action` would change `email` at all.
+fn changes(action: &RuleAction, email: &Email) -> bool {
+    match action {
+        RuleAction::MarkRead => email.is_unread(),
+        RuleAction::Flag => !email.is_flagged(),
+        RuleAction::Move { mailbox_id } => {
+            !email.mailbox_ids.get(mailbox_id).copied().unwrap_or(false)
+        }
+        RuleAction::Archive | RuleAction::Trash => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FilterType;
+
+    fn email(id: &str, from: &str, keywords: serde_json::Value) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "blob_id": "", "thread_id": "", "mailbox_ids": {"inbox": true},
+            "keywords": keywords, "received_at": "2026-01-01T00:00:00Z", "subject": "Deal",
+            "from": [{"name": null, "email": from}], "to": [], "cc": [], "preview": "",
+            "has_attachment": false, "size": 0, "text_body": null, "html_body": null,
+            "has_calendar": false, "attachments": [],
+        }))
+        .unwrap()
+    }
+
+    fn rule(pattern: &str, actions: Vec<RuleAction>) -> Rule {
+        Rule {
+            name: None,
+            filters: vec![SplitFilter {
+                filter_type: FilterType::From,
+                pattern: pattern.into(),
+                name: None,
+                pattern_kind: None,
+                glob_match: None,
+                extra: Default::default(),
+            }],
+            match_mode: MatchMode::Any,
+            actions,
+        }
+    }
+
+    #[test]
+    fn groups_by_action_and_skips_no_ops() {
+        let emails = vec![
+            email("1", "deals@shop.example", serde_json::json!({})),
+            email(
+                "2",
+                "deals@shop.example",
+                serde_json::json!({"$seen": true}),
+            ),
+            email("3", "ann@friends.example", serde_json::json!({})),
+        ];
+        let r = rule(
+            "*@shop.example",
+            vec![RuleAction::MarkRead, RuleAction::Archive],
+        );
+        let sim = simulate(&r, &emails, &ProtectedSendersConfig::default());
+        assert_eq!(sim.matched, 2);
+        let ids = |g: &ActionGroup| g.emails.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&sim.groups[0]), ["1"]);
+        assert_eq!(ids(&sim.groups[1]), ["1", "2"]);
+        assert!(sim.protected.is_empty());
+    }
+
+    #[test]
+    fn moving_actions_skip_protected_senders() {
+        let emails = vec![email("1", "boss@shop.example", serde_json::json!({}))];
+        let protected = ProtectedSendersConfig {
+            senders: vec!["boss@shop.example".into()],
+        };
+        let r = rule("*@shop.example", vec![RuleAction::Flag, RuleAction::Trash]);
+        let sim = simulate(&r, &emails, &protected);
+        assert_eq!(sim.groups[0].emails.len(), 1);
+        assert!(sim.groups[1].emails.is_empty());
+        assert_eq!(sim.protected.len(), 1);
+
+        let into_inbox = rule(
+            "*@shop.example",
+            vec![RuleAction::Move {
+                mailbox_id: "inbox".into(),
+            }],
+        );
+        let sim = simulate(&into_inbox, &emails, &ProtectedSendersConfig::default());
+        assert!(sim.groups[0].emails.is_empty());
+    }
+
+    fn split_with(id: &str, pattern: &str, action: RuleAction) -> SplitInbox {
+        let r = rule(pattern, vec![]);
+        SplitInbox {
+            id: id.into(),
+            name: id.into(),
+            icon: None,
+            filters: r.filters,
+            match_mode: MatchMode::Any,
+            account: None,
+            action: Some(action),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn plan_moves_each_message_once_and_runs_in_place_actions_first() {
+        let emails = vec![
+            email("1", "deals@shop.example", serde_json::json!({})),
+            email("2", "boss@shop.example", serde_json::json!({})),
+            email("3", "ann@friends.example", serde_json::json!({})),
+        ];
+        let splits = vec![
+            split_with("archive", "*@shop.example", RuleAction::Archive),
+            split_with(
+                "move",
+                "deals@*",
+                RuleAction::Move {
+                    mailbox_id: "deals".into(),
+                },
+            ),
+            split_with("read", "*@shop.example", RuleAction::MarkRead),
+            SplitInbox {
+                action: None,
+                ..split_with("view", "*", RuleAction::Trash)
+            },
+        ];
+        let protected = ProtectedSendersConfig {
+            senders: vec!["boss@shop.example".into()],
+        };
+        let groups = plan(&splits, &emails, &protected);
+        let summary: Vec<(RuleAction, Vec<&str>)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.action.clone(),
+                    g.emails.iter().map(|e| e.id.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (RuleAction::MarkRead, vec!["1", "2"]),
+                (RuleAction::Archive, vec!["1"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn watermark_admits_only_later_mail_and_unseen_ties() {
+        let at = |id: &str, when: &str| Email {
+            received_at: when.parse().unwrap(),
+            ..email(id, "a@x.example", serde_json::json!({}))
+        };
+        let mut mark = Watermark {
+            received_at: "2026-01-01T09:00:00Z".parse().unwrap(),
+            ids: vec!["a".into()],
+        };
+        assert!(!mark.is_new(&at("a", "2026-01-01T09:00:00Z")));
+        assert!(mark.is_new(&at("b", "2026-01-01T09:00:00Z")));
+        // Older mail moved back into the inbox is not new.
+        assert!(!mark.is_new(&at("old", "2025-12-31T09:00:00Z")));
+        assert!(mark.is_new(&at("c", "2026-01-01T09:05:00Z")));
+
+        mark.advance(&[
+            at("b", "2026-01-01T09:00:00Z"),
+            at("c", "2026-01-01T09:05:00Z"),
+            at("d", "2026-01-01T09:05:00Z"),
+        ]);
+        assert_eq!(
+            mark.received_at,
+            "2026-01-01T09:05:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(mark.ids, ["c", "d"]);
+        assert!(!mark.is_new(&at("b", "2026-01-01T09:00:00Z")));
+    }
+
+    #[test]
+    fn validate_rejects_incomplete_rules() {
+        assert!(validate(&rule("*@x.example", vec![RuleAction::Archive])).is_ok());
+        assert!(validate(&rule("*@x.example", vec![])).is_err());
+        assert!(validate(&rule("", vec![RuleAction::Archive])).is_err());
+        assert!(
+            validate(&rule(
+                "*@x.example",
+                vec![RuleAction::Archive, RuleAction::Trash]
+            ))
+            .is_err()
+        );
+        let parsed: Rule = serde_json::from_value(serde_json::json!({
+            "filters": [{"type": "from", "pattern": "*@x.example"}],
+            "actions": [{"type": "move", "mailbox_id": " "}, {"type": "mark_read"}]
+        }))
+        .unwrap();
+        assert!(validate(&parsed).unwrap_err().contains("mailbox_id"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Server-side cleaning of received HTML bodies before they're rendered.
+//!
+//! The reading pane's sandboxed iframe is the real boundary; [`render`]
+//! is a second layer that also keeps the message from phoning home. It
+//! drops scripts, event-handler attributes and anything ammonia doesn't
+//! allow. It keeps the inline styles and `<style>` blocks mail layouts
+//! depend on, minus the CSS that can run code or load resources
+//! (`expression()`, `behavior`, `-moz-binding`, `@import`). Remote images
+//! become a blank placeholder and remote CSS `url()`s become `none`, each
+//! counted so the client can offer to load them.
+//!
+//! [`render_proxied`] is the "load remote content" variant: remote images
+//! and CSS `url()`s point at `image_proxy` instead of being blocked, so
+//! they show without the reader's browser contacting the host.
+//!
+//! Inline `cid:` images are already rewritten to same-origin attachment
+//! URLs by the providers, and `data:` images carry their own bytes, so
+//! neither is remote.
+
+use crate::image_proxy;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+
+/// What a blocked `<img>` shows instead: a transparent 1×1 SVG, so the
+/// layout keeps its `width`/`height` without a request going out.
+pub const BLOCKED_IMAGE: &str =
+    "data:image/svg+xml,%3Csvg%20xmlns%3D%22http%3A//www.w3.org/2000/svg%22%2F%3E";
+
+/// A cleaned body and how many remote loads were cut from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sanitized {
+    pub html: String,
+    pub remote_blocked: usize,
+    /// Remote loads rewritten to the image proxy ([`render_proxied`]).
+    pub remote_proxied: usize,
+}
+
+/// What happens to a remote image or CSS `url()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Remote {
+    Block,
+    Proxy,
+}
+
+/// Layout tags ammonia leaves out but newsletters still lean on.
+const EXTRA_TAGS: [&str; 3] = ["center", "font", "style"];
+
+/// Presentational attributes kept on every element.
+const GENERIC_ATTRIBUTES: [&str; 16] = [
+    "align",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+    "class",
+    "color",
+    "dir",
+    "face",
+    "height",
+    "lang",
+    "size",
+    "style",
+    "title",
+    "valign",
+    "width",
+];
+
+/// Clean `html` for display; see the module docs for what goes.
+pub fn render(html: &str) -> Sanitized {
+    clean(html, Remote::Block)
+}
+
+/// [`render`], but with remote images and CSS `url()`s fetched through
+/// `/api/proxy-image` instead of blocked. `@import` and remote `srcset`
+/// candidates are still dropped.
+pub fn render_proxied(html: &str) -> Sanitized {
+    clean(html, Remote::Proxy)
+}
+
+#[derive(Default)]
+struct Counts {
+    blocked: AtomicUsize,
+    proxied: AtomicUsize,
+}
+
+fn clean(html: &str, remote: Remote) -> Sanitized {
+    let counts = Arc::new(Counts::default());
+    // `<style>` text is raw to the HTML parser, so its CSS is cleaned
+    // here; attribute CSS goes through the filter below.
+    let html = STYLE_BLOCK.replace_all(html, |c: &Captures| {
+        let css = clean_css(&c[2], remote, &counts);
+        format!("{}{css}</style>", &c[1])
+    });
+
+    let filter_counts = Arc::clone(&counts);
+    let html = ammonia::Builder::default()
+        .add_tags(EXTRA_TAGS)
+        .rm_clean_content_tags(["style"])
+        .add_generic_attributes(GENERIC_ATTRIBUTES)
+        .add_tag_attributes("img", ["background"])
+        .add_tag_attributes("td", ["background"])
+        .add_tag_attributes("table", ["background"])
+        .add_tag_attributes("body", ["background"])
+        // For inline images only; the filter drops `data:` anywhere else.
+        .add_url_schemes(["data"])
+        .attribute_filter(move |element, attribute, value| {
+            filter_attribute(element, attribute, value, remote, &filter_counts)
+        })
+        .clean(&html)
+        .to_string();
+    Sanitized {
+        html,
+        remote_blocked: counts.blocked.load(Ordering::Relaxed),
+        remote_proxied: counts.proxied.load(Ordering::Relaxed),
+    }
+}
+
+fn filter_attribute<'u>(
+    element: &str,
+    attribute: &str,
+    value: &'u str,
+    remote: Remote,
+    counts: &Counts,
+) -> Option<Cow<'u, str>> {
+    match attribute {
+        "style" => Some(Cow::Owned(clean_css(value, remote, counts))),
+        "src" | "background" if remote == Remote::Proxy && is_remote(value) => {
+            counts.proxied.fetch_add(1, Ordering::Relaxed);
+            Some(Cow::Owned(image_proxy::proxy_path(value)))
+        }
+        "src" if element == "img" && is_remote(value) => {
+            counts.blocked.fetch_add(1, Ordering::Relaxed);
+            Some(Cow::Borrowed(BLOCKED_IMAGE))
+        }
+        "src" if element == "img" => Some(Cow::Borrowed(value)),
+        _ if value
+            .trim_start()
+            .get(..5)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:")) =>
+        {
+            None
+        }
+        "srcset" | "background" if value.split(',').any(is_remote) => {
+            counts.blocked.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+        _ => Some(Cow::Borrowed(value)),
+    }
+}
+
+/// A URL the browser would fetch from another host.
+fn is_remote(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+static STYLE_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)(<style\b[^>]*>)(.*?)</style\s*>").unwrap());
+static CSS_COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)/\*.*?\*/").unwrap());
+/// CSS escapes (`\65 xpression`) exist to dodge exactly the checks below.
+static CSS_ESCAPE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\(?:[0-9a-fA-F]{1,6}\s?)?").unwrap());
+static CSS_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)@import\s+[^;]*;?"#).unwrap());
+static CSS_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)url\(\s*(['"]?)([^'")]*)(['"]?)\s*\)"#).unwrap());
+static CSS_DANGEROUS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)expression\s*\(|behavior\s*:|-moz-binding\s*:|javascript\s*:|vbscript\s*:")
+        .unwrap()
+});
+
+/// `css` without comments, escapes, `@import`s or anything in
+/// `CSS_DANGEROUS`. Every `url()` that isn't an inline `data:` image, a
+/// `cid:` or a same-origin path becomes `none`, except remote ones under
+/// [`Remote::Proxy`], which point at the image proxy. Imports and blocked
+/// or proxied URLs are added to `counts`.
+fn clean_css(css: &str, remote: Remote, counts: &Counts) -> String {
+    let css = CSS_COMMENT.replace_all(css, "");
+    let css = CSS_ESCAPE.replace_all(&css, "");
+    let css = CSS_IMPORT.replace_all(&css, |_: &Captures| {
+        counts.blocked.fetch_add(1, Ordering::Relaxed);
+        ""
+    });
+    let css = CSS_URL.replace_all(&css, |c: &Captures| {
+        let url = c[2].trim();
+        let lower = url.to_ascii_lowercase();
+        if lower.starts_with("data:image/") || lower.starts_with("cid:") {
+            return c[0].to_string();
+        }
+        if is_remote(url) {
+            if remote == Remote::Proxy {
+                counts.proxied.fetch_add(1, Ordering::Relaxed);
+                return format!("url('{}')", image_proxy::proxy_path(url));
+            }
+            counts.blocked.fetch_add(1, Ordering::Relaxed);
+        } else if lower.starts_with('/') && !lower.starts_with("//") {
+            return c[0].to_string();
+        }
+        "none".to_string()
+    });
+    CSS_DANGEROUS.replace_all(&css, "x-blocked-").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_scripts_and_event_handlers() {
+        let out = render(
+            r#"<p onclick="steal()">Hi<script>alert(1)</script></p><a href="javascript:alert(1)">x</a>"#,
+        );
+        assert!(!out.html.contains("script"), "{}", out.html);
+        assert!(!out.html.contains("onclick"), "{}", out.html);
+        assert!(!out.html.contains("javascript"), "{}", out.html);
+        assert!(out.html.contains("<p>Hi</p>"));
+        assert_eq!(out.remote_blocked, 0);
+    }
+
+    #[test]
+    fn remote_images_become_placeholders_and_are_counted() {
+        let out = render(
+            r#"<img src="https://t.example/pixel.gif" width="1" height="1"><img src="/api/emails/e1/attachments/b1/logo.png"><img src="data:image/png;base64,AAAA">"#,
+        );
+        assert_eq!(out.remote_blocked, 1);
+        assert!(!out.html.contains("t.example"), "{}", out.html);
+        assert!(out.html.contains(BLOCKED_IMAGE));
+        assert!(out.html.contains(r#"width="1""#));
+        assert!(out.html.contains("/api/emails/e1/attachments/b1/logo.png"));
+        assert!(out.html.contains("data:image/png;base64,AAAA"));
+    }
+
+    #[test]
+    fn keeps_layout_styles_but_not_dangerous_css() {
+        let out = render(concat!(
+            r#"<style>@import url(https://fonts.example/a.css); .hd { color: red; background: url('https://cdn.example/bg.png') no-repeat; } .x { width: e\78 pression(alert(1)) }</style>"#,
+            r##"<table bgcolor="#eee" style="-moz-binding: url(x.xml#a); padding: 4px; background-image: url(//cdn.example/t.png)"><tr><td class="hd">Hi</td></tr></table>"##,
+        ));
+        assert_eq!(out.remote_blocked, 3);
+        assert!(out.html.contains("color: red"), "{}", out.html);
+        assert!(out.html.contains("padding: 4px"), "{}", out.html);
+        assert!(out.html.contains(r##"bgcolor="#eee""##), "{}", out.html);
+        assert!(out.html.contains(r#"class="hd""#), "{}", out.html);
+        for gone in ["@import", "cdn.example", "expression", "-moz-binding"] {
+            assert!(!out.html.contains(gone), "{gone} left in {}", out.html);
+        }
+    }
+
+    #[test]
+    fn proxied_rendering_routes_remote_loads_through_the_proxy() {
+        let out = render_proxied(concat!(
+            r#"<style>@import url(https://fonts.example/a.css); .hd { background: url('https://cdn.example/bg.png') }</style>"#,
+            r#"<img src="https://t.example/pixel.gif"><img src="/api/emails/e1/attachments/b1/logo.png">"#,
+            r#"<table background="//cdn.example/t.png"><tr><td>x</td></tr></table>"#,
+        ));
+        assert_eq!(out.remote_proxied, 3, "{}", out.html);
+        assert_eq!(out.remote_blocked, 1, "{}", out.html);
+        assert!(
+            out.html
+                .contains("/api/proxy-image?url=https%3A%2F%2Ft%2Eexample%2Fpixel%2Egif"),
+            "{}",
+            out.html
+        );
+        assert!(out.html.contains("/api/emails/e1/attachments/b1/logo.png"));
+        assert!(!out.html.contains("fonts.example"), "{}", out.html);
+        assert!(!out.html.contains(r#"src="https://"#), "{}", out.html);
+    }
+}
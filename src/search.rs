@@ -1,80 +1,261 @@
-use crate::types::ParsedQuery;
+use crate::mailbox_roles::RoleAliases;
+use crate::types::{Comparison, Mailbox, MailboxTerm, ParsedQuery, PriorityLevel, ScoreFilter};
 use chrono::NaiveDate;
 
 // =============================================================================
 // Query parser
 // =============================================================================
 
+/// Parse a Gmail-style query. Terms are ANDed; `a OR b` needs either,
+/// `-term` and `NOT term` exclude, and parentheses group, so
+/// `-from:bob (subject:invoice OR subject:receipt)` works as in Gmail. OR
+/// binds tighter than the implicit AND, and `OR`/`NOT` are only operators
+/// in capitals. `spamscore:` and `priority:` are matched locally and only
+/// count outside OR and NOT.
 pub fn parse_query(raw: &str) -> ParsedQuery {
-    let mut query = ParsedQuery::default();
     let raw = raw.trim();
     if raw.is_empty() {
-        return query;
+        return ParsedQuery::default();
     }
+    let mut parser = Parser {
+        tokens: tokenize(raw),
+        pos: 0,
+    };
+    parser.and_expr()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Operator(String, String),
+    Word(String),
+    Open,
+    Close,
+    Or,
+    Not,
+}
 
-    let mut free_text_parts: Vec<String> = Vec::new();
+/// Split `raw` into operators, words and the boolean syntax. A `)` only
+/// closes a group when one is open, so a stray one stays part of a word.
+fn tokenize(raw: &str) -> Vec<Token> {
+    let bytes = raw.as_bytes();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
     let mut pos = 0;
 
     while pos < raw.len() {
         // Skip whitespace
-        while pos < raw.len() && raw.as_bytes()[pos] == b' ' {
+        while pos < raw.len() && bytes[pos] == b' ' {
             pos += 1;
         }
         if pos >= raw.len() {
             break;
         }
 
-        // Try to match an operator (keyword:value)
-        if let Some(colon_pos) = raw[pos..].find(':') {
-            let abs_colon = pos + colon_pos;
-            let keyword = &raw[pos..abs_colon];
-
-            // Only recognize known operators (no spaces in keyword)
-            if !keyword.contains(' ') && is_known_operator(keyword) {
-                let value_start = abs_colon + 1;
-                let (value, value_end) = extract_value(raw, value_start);
-
-                match keyword {
-                    "from" => query.from.push(value),
-                    "to" => query.to.push(value),
-                    "subject" => query.subject.push(value),
-                    "has" if value == "attachment" => query.has_attachment = true,
-                    "is" => match value.as_str() {
-                        "unread" => query.is_unread = Some(true),
-                        "read" => query.is_unread = Some(false),
-                        "starred" | "flagged" => query.is_flagged = Some(true),
-                        _ => {}
-                    },
-                    "before" => query.before = parse_date(&value),
-                    "after" => query.after = parse_date(&value),
-                    "newer_than" => query.after = parse_date_offset(&value),
-                    "older_than" => query.before = parse_date_offset(&value),
-                    _ => {}
-                }
+        match bytes[pos] {
+            b'(' => {
+                tokens.push(Token::Open);
+                depth += 1;
+                pos += 1;
+                continue;
+            }
+            b')' if depth > 0 => {
+                tokens.push(Token::Close);
+                depth -= 1;
+                pos += 1;
+                continue;
+            }
+            b'-' if bytes
+                .get(pos + 1)
+                .is_some_and(|b| !matches!(b, b' ' | b'-')) =>
+            {
+                tokens.push(Token::Not);
+                pos += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let in_group = depth > 0;
+        let word_end = raw[pos..]
+            .find(|c: char| c == ' ' || (in_group && c == ')'))
+            .map(|i| pos + i)
+            .unwrap_or(raw.len());
+        let word = &raw[pos..word_end];
 
+        // Try to match an operator (keyword:value)
+        if let Some(colon_pos) = word.find(':') {
+            let keyword = &word[..colon_pos];
+            if is_known_operator(keyword) {
+                let (value, value_end) = extract_value(raw, pos + colon_pos + 1, in_group);
+                tokens.push(Token::Operator(keyword.to_string(), value));
                 pos = value_end;
                 continue;
             }
         }
 
-        // Not an operator — collect as free text word
-        let word_end = raw[pos..].find(' ').map(|i| pos + i).unwrap_or(raw.len());
-        free_text_parts.push(raw[pos..word_end].to_string());
+        match word {
+            "OR" => tokens.push(Token::Or),
+            "NOT" => tokens.push(Token::Not),
+            "AND" => {}
+            // Not an operator — collect as free text word
+            _ => tokens.push(Token::Word(word.to_string())),
+        }
         pos = word_end;
     }
+    tokens
+}
 
-    query.text = free_text_parts.join(" ");
-    query
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Terms up to the end or the `)` closing the current group, ANDed.
+    fn and_expr(&mut self) -> ParsedQuery {
+        let mut query = ParsedQuery::default();
+        while !matches!(self.peek(), None | Some(Token::Close)) {
+            let term = self.or_expr();
+            merge(&mut query, term);
+        }
+        query
+    }
+
+    fn or_expr(&mut self) -> ParsedQuery {
+        let first = self.unary();
+        let mut alternatives = vec![first];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            // A trailing OR has nothing to join; drop it.
+            if matches!(self.peek(), None | Some(Token::Close)) {
+                break;
+            }
+            alternatives.push(self.unary());
+        }
+        if alternatives.len() == 1 {
+            return alternatives.pop().unwrap_or_default();
+        }
+        ParsedQuery {
+            any_of: vec![alternatives],
+            ..Default::default()
+        }
+    }
+
+    fn unary(&mut self) -> ParsedQuery {
+        let mut query = ParsedQuery::default();
+        match self.next() {
+            Some(Token::Not) => {
+                if matches!(self.peek(), None | Some(Token::Close)) {
+                    return query;
+                }
+                let inner = self.unary();
+                if !inner.is_empty() {
+                    query.exclude.push(inner);
+                }
+            }
+            Some(Token::Open) => {
+                query = self.and_expr();
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                }
+            }
+            // A leading OR is just the word.
+            Some(Token::Or) => query.text = "OR".into(),
+            Some(Token::Word(word)) => query.text = word,
+            Some(Token::Operator(keyword, value)) => apply_operator(&mut query, &keyword, value),
+            Some(Token::Close) | None => {}
+        }
+        query
+    }
+}
+
+fn apply_operator(query: &mut ParsedQuery, keyword: &str, value: String) {
+    match keyword {
+        "from" => query.from.push(value),
+        "to" => query.to.push(value),
+        "subject" => query.subject.push(value),
+        "has" if value == "attachment" => query.has_attachment = true,
+        "is" => match value.as_str() {
+            "unread" => query.is_unread = Some(true),
+            "read" => query.is_unread = Some(false),
+            "starred" | "flagged" => query.is_flagged = Some(true),
+            _ => {}
+        },
+        "before" => query.before = parse_date(&value),
+        "after" => query.after = parse_date(&value),
+        "newer_than" => query.after = parse_date_offset(&value),
+        "older_than" => query.before = parse_date_offset(&value),
+        "spamscore" => query.spam_score = parse_score_filter(&value),
+        "priority" => query.priority = parse_priority(&value),
+        "larger" | "size" => query.larger = parse_size(&value),
+        "smaller" => query.smaller = parse_size(&value),
+        "in" | "label" if !value.is_empty() => query.mailboxes.push(MailboxTerm {
+            name: value,
+            id: None,
+        }),
+        _ => {}
+    }
+}
+
+/// AND `other` into `query`. Single-valued operators keep the later
+/// value, as they always have.
+fn merge(query: &mut ParsedQuery, other: ParsedQuery) {
+    query.from.extend(other.from);
+    query.to.extend(other.to);
+    query.subject.extend(other.subject);
+    query.has_attachment |= other.has_attachment;
+    query.is_unread = other.is_unread.or(query.is_unread);
+    query.is_flagged = other.is_flagged.or(query.is_flagged);
+    query.before = other.before.or(query.before);
+    query.after = other.after.or(query.after);
+    query.spam_score = other.spam_score.or(query.spam_score);
+    query.priority = other.priority.or(query.priority);
+    query.larger = other.larger.or(query.larger);
+    query.smaller = other.smaller.or(query.smaller);
+    if !other.text.is_empty() {
+        if !query.text.is_empty() {
+            query.text.push(' ');
+        }
+        query.text.push_str(&other.text);
+    }
+    query.exclude.extend(other.exclude);
+    query.any_of.extend(other.any_of);
+    query.mailboxes.extend(other.mailboxes);
 }
 
 fn is_known_operator(keyword: &str) -> bool {
     matches!(
         keyword,
-        "from" | "to" | "subject" | "has" | "is" | "before" | "after" | "newer_than" | "older_than"
+        "from"
+            | "to"
+            | "subject"
+            | "has"
+            | "is"
+            | "before"
+            | "after"
+            | "newer_than"
+            | "older_than"
+            | "spamscore"
+            | "priority"
+            | "in"
+            | "label"
+            | "larger"
+            | "smaller"
+            | "size"
     )
 }
 
-fn extract_value(raw: &str, start: usize) -> (String, usize) {
+fn extract_value(raw: &str, start: usize, in_group: bool) -> (String, usize) {
     if start >= raw.len() {
         return (String::new(), start);
     }
@@ -91,9 +272,9 @@ fn extract_value(raw: &str, start: usize) -> (String, usize) {
         return (value, past_quote);
     }
 
-    // Unquoted value — up to next space
+    // Unquoted value — up to next space, or the `)` closing a group
     let end = raw[start..]
-        .find(' ')
+        .find(|c: char| c == ' ' || (in_group && c == ')'))
         .map(|i| start + i)
         .unwrap_or(raw.len());
     (raw[start..end].to_string(), end)
@@ -103,6 +284,48 @@ fn parse_date(s: &str) -> Option<NaiveDate> {
     NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
 }
 
+/// `>5`, `>=5`, `<2.5`, `<=0`, `=3` or a bare `3`.
+fn parse_score_filter(s: &str) -> Option<ScoreFilter> {
+    let (op, num) = if let Some(rest) = s.strip_prefix(">=") {
+        (Comparison::Ge, rest)
+    } else if let Some(rest) = s.strip_prefix("<=") {
+        (Comparison::Le, rest)
+    } else if let Some(rest) = s.strip_prefix('>') {
+        (Comparison::Gt, rest)
+    } else if let Some(rest) = s.strip_prefix('<') {
+        (Comparison::Lt, rest)
+    } else {
+        (Comparison::Eq, s.strip_prefix('=').unwrap_or(s))
+    };
+    let value: f64 = num.parse().ok()?;
+    value.is_finite().then_some(ScoreFilter { op, value })
+}
+
+fn parse_priority(s: &str) -> Option<PriorityLevel> {
+    match s.to_ascii_lowercase().as_str() {
+        "high" => Some(PriorityLevel::High),
+        "normal" => Some(PriorityLevel::Normal),
+        "low" => Some(PriorityLevel::Low),
+        _ => None,
+    }
+}
+
+/// `5M`, `200K`, `1.5MB`, `1G` or plain bytes; units are binary
+/// (`1K` = 1024), as in Gmail.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_uppercase();
+    let number = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match &s[number.len()..] {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+    let value: f64 = number.parse().ok()?;
+    (value.is_finite() && value >= 0.0).then(|| (value * multiplier as f64).round() as u64)
+}
+
 fn parse_date_offset(s: &str) -> Option<NaiveDate> {
     let s = s.trim();
     if s.len() < 2 {
@@ -131,6 +354,78 @@ fn parse_date_offset(s: &str) -> Option<NaiveDate> {
         .ok()
 }
 
+// =============================================================================
+// Mailbox resolution
+// =============================================================================
+
+/// Fill in the id of every `in:` / `label:` term, here and in nested
+/// groups. A name is tried as a role first (`in:inbox`, `in:spam`, and
+/// the localized names in `aliases`), then as a mailbox name, then as a
+/// `Parent/Child` path and finally as an id, all case-insensitively. A
+/// name that matches nothing is an error rather than a search that
+/// silently ignores it.
+pub fn resolve_mailboxes(
+    query: &mut ParsedQuery,
+    mailboxes: &[Mailbox],
+    aliases: &RoleAliases,
+) -> Result<(), String> {
+    let roles = crate::mailbox_roles::by_role(mailboxes, aliases);
+    resolve_terms(query, mailboxes, &roles, aliases)
+}
+
+fn resolve_terms(
+    query: &mut ParsedQuery,
+    mailboxes: &[Mailbox],
+    roles: &std::collections::HashMap<String, Mailbox>,
+    aliases: &RoleAliases,
+) -> Result<(), String> {
+    for term in &mut query.mailboxes {
+        let wanted = term.name.trim().to_lowercase();
+        let role = match wanted.as_str() {
+            "spam" => "junk",
+            other => aliases.get(other).map(String::as_str).unwrap_or(other),
+        };
+        let found = roles
+            .get(role)
+            .or_else(|| mailboxes.iter().find(|mb| mb.name.to_lowercase() == wanted))
+            .or_else(|| {
+                mailboxes
+                    .iter()
+                    .find(|mb| mailbox_path(mb, mailboxes).to_lowercase() == wanted)
+            })
+            .or_else(|| mailboxes.iter().find(|mb| mb.id == term.name))
+            .ok_or_else(|| format!("No mailbox named '{}'", term.name))?;
+        term.id = Some(found.id.clone());
+        term.name = found.name.clone();
+    }
+    for group in &mut query.exclude {
+        resolve_terms(group, mailboxes, roles, aliases)?;
+    }
+    for alternative in query.any_of.iter_mut().flatten() {
+        resolve_terms(alternative, mailboxes, roles, aliases)?;
+    }
+    Ok(())
+}
+
+/// `Parent/Child` for a nested mailbox; stops at a missing parent or a
+/// cycle.
+fn mailbox_path(mailbox: &Mailbox, mailboxes: &[Mailbox]) -> String {
+    let mut parts = vec![mailbox.name.as_str()];
+    let mut parent = mailbox.parent_id.as_deref();
+    while let Some(id) = parent {
+        let Some(mb) = mailboxes.iter().find(|mb| mb.id == id) else {
+            break;
+        };
+        if parts.len() > mailboxes.len() {
+            break;
+        }
+        parts.push(mb.name.as_str());
+        parent = mb.parent_id.as_deref();
+    }
+    parts.reverse();
+    parts.join("/")
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -248,6 +543,57 @@ mod tests {
         assert_eq!(q.text, "project meeting");
     }
 
+    #[test]
+    fn parse_spamscore_comparisons() {
+        let filter = |raw: &str| parse_query(raw).spam_score;
+        assert_eq!(
+            filter("spamscore:>5"),
+            Some(ScoreFilter {
+                op: Comparison::Gt,
+                value: 5.0
+            })
+        );
+        assert_eq!(
+            filter("spamscore:<=-1.5"),
+            Some(ScoreFilter {
+                op: Comparison::Le,
+                value: -1.5
+            })
+        );
+        assert_eq!(filter("spamscore:3").map(|f| f.op), Some(Comparison::Eq));
+        assert_eq!(filter("spamscore:high"), None);
+        assert_eq!(filter("spamscore:>5 invoice").unwrap().value, 5.0);
+        assert!(!parse_query("spamscore:>5").is_empty());
+    }
+
+    #[test]
+    fn parse_size_operators() {
+        let q = parse_query("larger:5M smaller:200K attachment");
+        assert_eq!(q.larger, Some(5 * 1024 * 1024));
+        assert_eq!(q.smaller, Some(200 * 1024));
+        assert_eq!(q.text, "attachment");
+        assert_eq!(parse_query("size:1.5mb").larger, Some(1_572_864));
+        assert_eq!(parse_query("larger:1000").larger, Some(1000));
+        assert_eq!(parse_query("larger:2G").larger, Some(2 << 30));
+        for bad in ["larger:5X", "larger:-1M", "larger:M", "smaller:"] {
+            assert!(parse_query(bad).is_empty(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parse_priority_levels() {
+        assert_eq!(
+            parse_query("priority:high").priority,
+            Some(PriorityLevel::High)
+        );
+        assert_eq!(
+            parse_query("priority:LOW report").priority,
+            Some(PriorityLevel::Low)
+        );
+        assert_eq!(parse_query("priority:urgent").priority, None);
+        assert!(!parse_query("priority:normal").is_empty());
+    }
+
     #[test]
     fn parse_free_text_only() {
         let q = parse_query("hello world");
@@ -329,4 +675,95 @@ mod tests {
         let q = parse_query("newer_than:1x");
         assert!(q.after.is_none());
     }
+
+    // --- Boolean operators ---
+
+    #[test]
+    fn parse_negated_operator_and_word() {
+        let q = parse_query("-from:bob NOT lunch report");
+        assert!(q.from.is_empty());
+        assert_eq!(q.text, "report");
+        assert_eq!(q.exclude.len(), 2);
+        assert_eq!(q.exclude[0].from, vec!["bob"]);
+        assert_eq!(q.exclude[1].text, "lunch");
+    }
+
+    #[test]
+    fn parse_or_binds_tighter_than_and() {
+        let q = parse_query("budget from:ann OR from:bob");
+        assert_eq!(q.text, "budget");
+        assert_eq!(q.any_of.len(), 1);
+        let alternatives: Vec<_> = q.any_of[0].iter().map(|a| a.from.clone()).collect();
+        assert_eq!(alternatives, vec![vec!["ann"], vec!["bob"]]);
+    }
+
+    #[test]
+    fn parse_parenthesized_groups() {
+        let q =
+            parse_query("-(subject:\"weekly sync\" is:unread) (from:a OR (to:b has:attachment))");
+        assert_eq!(q.exclude[0].subject, vec!["weekly sync"]);
+        assert_eq!(q.exclude[0].is_unread, Some(true));
+        let group = &q.any_of[0];
+        assert_eq!(group[0].from, vec!["a"]);
+        assert_eq!(group[1].to, vec!["b"]);
+        assert!(group[1].has_attachment);
+    }
+
+    #[test]
+    fn parse_boolean_syntax_degrades_to_text() {
+        // Lower-case `or`, a lone dash, a stray `)` and a dangling OR/NOT
+        // are not operators.
+        let q = parse_query("this or that - smile :) OR");
+        assert_eq!(q.text, "this or that - smile :)");
+        assert!(q.any_of.is_empty() && q.exclude.is_empty());
+        assert!(parse_query("NOT").is_empty());
+        // An unclosed group still parses.
+        let q = parse_query("(from:a OR from:b");
+        assert_eq!(q.any_of[0].len(), 2);
+    }
+
+    #[test]
+    fn parse_mailbox_operators() {
+        let q = parse_query("in:inbox label:\"Client Work\" -in:archive report");
+        let names: Vec<_> = q.mailboxes.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["inbox", "Client Work"]);
+        assert_eq!(q.exclude[0].mailboxes[0].name, "archive");
+        assert_eq!(q.text, "report");
+        assert!(q.has_mailbox_terms());
+        assert!(!parse_query("in: report").has_mailbox_terms());
+    }
+
+    fn mailbox(id: &str, name: &str, role: Option<&str>, parent: Option<&str>) -> Mailbox {
+        Mailbox {
+            id: id.into(),
+            name: name.into(),
+            role: role.map(String::from),
+            total_emails: 0,
+            unread_emails: 0,
+            parent_id: parent.map(String::from),
+        }
+    }
+
+    #[test]
+    fn mailbox_terms_resolve_by_role_name_and_path() {
+        let mailboxes = [
+            mailbox("m1", "Inbox", Some("inbox"), None),
+            mailbox("m2", "Archiv", None, None),
+            mailbox("m3", "Junk Mail", Some("junk"), None),
+            mailbox("m4", "Clients", None, None),
+            mailbox("m5", "Acme", None, Some("m4")),
+        ];
+        let aliases = crate::mailbox_roles::default_aliases();
+        let mut q = parse_query("in:INBOX in:archive in:spam (in:clients/acme OR label:m4)");
+        resolve_mailboxes(&mut q, &mailboxes, &aliases).unwrap();
+        let ids: Vec<_> = q.mailboxes.iter().map(|t| t.id.as_deref()).collect();
+        assert_eq!(ids, [Some("m1"), Some("m2"), Some("m3")]);
+        assert_eq!(q.mailboxes[1].name, "Archiv");
+        assert_eq!(q.any_of[0][0].mailboxes[0].id.as_deref(), Some("m5"));
+        assert_eq!(q.any_of[0][1].mailboxes[0].id.as_deref(), Some("m4"));
+
+        let mut q = parse_query("in:\"No Such Folder\"");
+        let err = resolve_mailboxes(&mut q, &mailboxes, &aliases).unwrap_err();
+        assert!(err.contains("No Such Folder"), "{err}");
+    }
 }
@@ -0,0 +1,279 @@
+//! Second-factor confirmation for sends to sensitive recipients.
+//!
+//! `send-guard.json` lists recipient patterns and a TOTP shared secret:
+//!
+//! ```json
+//! { "recipients": ["press@", "legal@", "@regulator.example"], "totpSecret": "JBSWY3DPEHPK3PXP" }
+//! ```
+//!
+//! A pattern ending in `@` matches that local part at any domain, one
+//! starting with `@` matches the domain and its subdomains, and anything
+//! else matches one address exactly. When a send's recipients include a
+//! match, `/api/emails/send` answers 428 with a challenge until the body
+//! carries a current code from an authenticator app holding the secret
+//! (RFC 6238: HMAC-SHA1, 6 digits, 30-second steps). The file is
+//! hand-edited because it holds the secret; no endpoint returns it. The
+//! guard is off while either list or secret is missing. A secret that
+//! isn't valid base32 fails closed: guarded sends are refused (422) until
+//! it's fixed.
+//!
+//! `/api/emails/send` and `/api/calendar/invite` both check it. Event
+//! cancellations and `mailto:` unsubscribes don't: the text is written by
+//! the server, and goes to the event's attendees or the list's own
+//! address.
+
+use crate::error::Error;
+use crate::types::EmailSubmission;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use std::path::Path;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+
+/// Steps either side of now still accepted, for clock drift and the time
+/// it takes to type the code.
+const DRIFT_STEPS: i64 = 1;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendGuardConfig {
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Base32 TOTP secret, as shown when enrolling an authenticator app.
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+}
+
+impl SendGuardConfig {
+    /// The decoded secret, `None` when the guard is off, or an error when
+    /// a secret is set but doesn't decode.
+    fn secret(&self) -> Result<Option<Vec<u8>>, Error> {
+        if self.recipients.is_empty() {
+            return Ok(None);
+        }
+        let Some(raw) = self.totp_secret.as_deref() else {
+            return Ok(None);
+        };
+        let raw = raw.replace([' ', '-'], "");
+        base32::decode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            &raw.trim_end_matches('=').to_uppercase(),
+        )
+        .filter(|s| !s.is_empty())
+        .map(Some)
+        .ok_or_else(|| {
+            tracing::warn!("send-guard.json: totpSecret is not valid base32");
+            Error::Unprocessable(
+                "the send guard's totpSecret is not valid base32; fix send-guard.json to send to these recipients"
+                    .into(),
+            )
+        })
+    }
+
+    /// Recipients of `sub` (to, cc and bcc) that match a guarded pattern.
+    pub fn sensitive_recipients(&self, sub: &EmailSubmission) -> Vec<String> {
+        sub.to
+            .iter()
+            .chain(&sub.cc)
+            .chain(sub.bcc.iter().flatten())
+            .filter(|addr| self.recipients.iter().any(|p| matches_pattern(p, addr)))
+            .cloned()
+            .collect()
+    }
+
+    /// Allow `sub` through, or fail with `Error::ConfirmationRequired` when
+    /// it goes to a guarded recipient and `code` isn't currently valid.
+    pub fn check(
+        &self,
+        sub: &EmailSubmission,
+        code: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        if self.recipients.is_empty() || self.totp_secret.is_none() {
+            return Ok(());
+        }
+        let recipients = self.sensitive_recipients(sub);
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        let Some(secret) = self.secret()? else {
+            return Ok(());
+        };
+        let message = match code {
+            Some(code) if verify(&secret, code, now) => return Ok(()),
+            Some(_) => "confirmation code is wrong or expired",
+            None => "sending to these recipients needs a confirmation code",
+        };
+        Err(Error::ConfirmationRequired {
+            message: message.into(),
+            recipients,
+        })
+    }
+}
+
+pub fn load_config(path: &Path) -> SendGuardConfig {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return SendGuardConfig::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring {}: {e}", path.display());
+        SendGuardConfig::default()
+    })
+}
+
+fn matches_pattern(pattern: &str, addr: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let addr = addr.to_lowercase();
+    let Some((local, domain)) = addr.rsplit_once('@') else {
+        return false;
+    };
+    if let Some(p) = pattern.strip_suffix('@') {
+        local == p
+    } else if let Some(p) = pattern.strip_prefix('@') {
+        domain == p || domain.ends_with(&format!(".{p}"))
+    } else {
+        addr == pattern
+    }
+}
+
+/// RFC 4226 HOTP value for `counter`.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret).expect("HMAC takes any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Whether `code` is the TOTP for `now`, give or take [`DRIFT_STEPS`].
+fn verify(secret: &[u8], code: &str, now: DateTime<Utc>) -> bool {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize {
+        return false;
+    }
+    let Ok(code) = code.parse::<u32>() else {
+        return false;
+    };
+    let step = now.timestamp().div_euclid(STEP_SECONDS);
+    (-DRIFT_STEPS..=DRIFT_STEPS)
+        .filter_map(|d| u64::try_from(step + d).ok())
+        .any(|counter| hotp(secret, counter) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // RFC 6238 appendix B test key for SHA-1, base32-encoded.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn config() -> SendGuardConfig {
+        SendGuardConfig {
+            recipients: vec!["press@".into(), "@regulator.example".into()],
+            totp_secret: Some(RFC_SECRET.into()),
+        }
+    }
+
+    fn to(addrs: &[&str]) -> EmailSubmission {
+        EmailSubmission {
+            to: addrs.iter().map(|s| s.to_string()).collect(),
+            cc: vec![],
+            subject: "Hi".into(),
+            text_body: String::new(),
+            bcc: None,
+            html_body: None,
+            in_reply_to: None,
+            references: None,
+            attachments: vec![],
+            calendar_ics: None,
+            forward_of: None,
+        }
+    }
+
+    #[test]
+    fn hotp_matches_rfc_6238_vectors() {
+        let secret = config().secret().unwrap().unwrap();
+        assert_eq!(secret, b"12345678901234567890");
+        // T = 59 s and 1111111109 s, truncated to 6 digits.
+        assert_eq!(hotp(&secret, 59 / 30), 287082);
+        assert_eq!(hotp(&secret, 1111111109 / 30), 81804);
+    }
+
+    #[test]
+    fn patterns_match_local_part_domain_or_address() {
+        let guard = config();
+        let sub = to(&[
+            "Press@news.example",
+            "a@sub.regulator.example",
+            "me@example.com",
+        ]);
+        assert_eq!(
+            guard.sensitive_recipients(&sub),
+            vec!["Press@news.example", "a@sub.regulator.example"]
+        );
+        assert!(!matches_pattern(
+            "@regulator.example",
+            "a@notregulator.example"
+        ));
+        assert!(matches_pattern("cfo@corp.example", "CFO@corp.example"));
+    }
+
+    #[test]
+    fn check_requires_a_current_code() {
+        let guard = config();
+        let now = Utc.timestamp_opt(1111111109, 0).unwrap();
+        let sub = to(&["press@news.example"]);
+        assert!(matches!(
+            guard.check(&sub, None, now),
+            Err(Error::ConfirmationRequired { ref recipients, .. }) if recipients == &["press@news.example"]
+        ));
+        assert!(guard.check(&sub, Some("081804"), now).is_ok());
+        assert!(
+            guard
+                .check(&sub, Some("081 804"), now + chrono::Duration::seconds(30))
+                .is_ok()
+        );
+        assert!(
+            guard
+                .check(&sub, Some("081804"), now + chrono::Duration::minutes(5))
+                .is_err()
+        );
+        assert!(guard.check(&sub, Some("000000"), now).is_err());
+        assert!(guard.check(&to(&["me@example.com"]), None, now).is_ok());
+    }
+
+    #[test]
+    fn guard_is_off_without_secret_or_recipients() {
+        let now = Utc::now();
+        let sub = to(&["press@news.example"]);
+        let no_secret = SendGuardConfig {
+            totp_secret: None,
+            ..config()
+        };
+        assert!(no_secret.check(&sub, None, now).is_ok());
+        assert!(SendGuardConfig::default().check(&sub, None, now).is_ok());
+    }
+
+    #[test]
+    fn malformed_secret_fails_closed() {
+        let now = Utc::now();
+        let broken = SendGuardConfig {
+            totp_secret: Some("not base32!".into()),
+            ..config()
+        };
+        assert!(matches!(
+            broken.check(&to(&["press@news.example"]), Some("123456"), now),
+            Err(Error::Unprocessable(_))
+        ));
+        assert!(broken.check(&to(&["me@example.com"]), None, now).is_ok());
+    }
+}
@@ -0,0 +1,318 @@
+//! Send-time guard: a second look before mail goes out at an odd hour.
+//!
+//! `send-hours.json` has two optional sections:
+//!
+//! ```json
+//! {
+//!   "workHours": { "start": "08:00", "end": "18:00", "days": ["mon", "tue", "wed", "thu", "fri"] },
+//!   "recipientNight": { "start": "22:00", "end": "07:00" }
+//! }
+//! ```
+//!
+//! `workHours` is read in the primary timezone (`timezone.json`);
+//! `days` defaults to Monday–Friday. `recipientNight` is read in each
+//! recipient's likely UTC offset, taken from the `Date` headers of mail
+//! they sent us. Either window may wrap past midnight. When a send falls
+//! outside work hours or into a recipient's night, `/api/emails/send`
+//! answers 428 with the reasons and the next time that clears every
+//! check, until the body sets `confirm_send_time`. Each check is off
+//! while its section is missing.
+
+use crate::error::Error;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Granularity of the suggested send time.
+const SUGGEST_STEP_MINUTES: i64 = 15;
+
+/// How far ahead to look for a suggestion before giving up.
+const SUGGEST_HORIZON_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendHoursConfig {
+    #[serde(default)]
+    pub work_hours: Option<WorkHours>,
+    #[serde(default)]
+    pub recipient_night: Option<Window>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Window {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkHours {
+    #[serde(flatten)]
+    pub window: Window,
+    #[serde(default = "weekdays")]
+    pub days: Vec<Weekday>,
+}
+
+fn weekdays() -> Vec<Weekday> {
+    vec![
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ]
+}
+
+impl Window {
+    /// `start` inclusive, `end` exclusive; `start > end` wraps midnight.
+    fn contains(&self, t: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+}
+
+/// A recipient and the UTC offset they most likely write from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipientZone {
+    pub address: String,
+    pub offset: FixedOffset,
+}
+
+impl SendHoursConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.work_hours.is_some() || self.recipient_night.is_some()
+    }
+
+    /// Why sending at `at` deserves a second look; empty when it doesn't.
+    pub fn reasons(&self, at: DateTime<Utc>, tz: Tz, recipients: &[RecipientZone]) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if let Some(work) = &self.work_hours {
+            let local = at.with_timezone(&tz);
+            if !work.days.contains(&local.weekday()) || !work.window.contains(local.time()) {
+                reasons.push(format!(
+                    "It's {} on {} ({tz}), outside your work hours of {}–{}",
+                    hhmm(local.time()),
+                    local.format("%A"),
+                    hhmm(work.window.start),
+                    hhmm(work.window.end),
+                ));
+            }
+        }
+        if let Some(night) = &self.recipient_night {
+            for r in recipients {
+                let theirs = at.with_timezone(&r.offset).time();
+                if night.contains(theirs) {
+                    reasons.push(format!(
+                        "It's {} for {} (UTC{})",
+                        hhmm(theirs),
+                        r.address,
+                        r.offset
+                    ));
+                }
+            }
+        }
+        reasons
+    }
+
+    /// The first quarter hour after `now` that clears every check, within
+    /// [`SUGGEST_HORIZON_DAYS`].
+    pub fn suggest(
+        &self,
+        now: DateTime<Utc>,
+        tz: Tz,
+        recipients: &[RecipientZone],
+    ) -> Option<DateTime<Utc>> {
+        let step = SUGGEST_STEP_MINUTES;
+        let minutes = now.minute() as i64;
+        let rounded = now
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(Duration::minutes(step - minutes % step))?;
+        (0..SUGGEST_HORIZON_DAYS * 24 * 60 / step)
+            .map(|i| rounded + Duration::minutes(i * step))
+            .find(|t| self.reasons(*t, tz, recipients).is_empty())
+    }
+
+    /// Allow a send at `now`, or fail with `Error::SendTimeWarning`.
+    pub fn check(
+        &self,
+        now: DateTime<Utc>,
+        tz: Tz,
+        recipients: &[RecipientZone],
+    ) -> Result<(), Error> {
+        let reasons = self.reasons(now, tz, recipients);
+        if reasons.is_empty() {
+            return Ok(());
+        }
+        Err(Error::SendTimeWarning {
+            message: "this is an unusual time to send".into(),
+            reasons,
+            suggested_send_at: self.suggest(now, tz, recipients),
+        })
+    }
+}
+
+/// The offset seen most often among `dates`, ties going to the earliest
+/// in the slice (pass newest first so a recent move wins a tie).
+pub fn likely_offset(dates: &[DateTime<FixedOffset>]) -> Option<FixedOffset> {
+    let mut counts: Vec<(FixedOffset, usize)> = Vec::new();
+    for d in dates {
+        let offset = *d.offset();
+        match counts.iter_mut().find(|(o, _)| *o == offset) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((offset, 1)),
+        }
+    }
+    // max_by_key keeps the last of equal maxima; reverse so the first wins.
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, n)| *n)
+        .map(|(o, _)| o)
+}
+
+fn hhmm(t: NaiveTime) -> String {
+    t.format("%H:%M").to_string()
+}
+
+pub fn load_config(path: &Path) -> SendHoursConfig {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return SendHoursConfig::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring {}: {e}", path.display());
+        SendHoursConfig::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config() -> SendHoursConfig {
+        serde_json::from_str(
+            r#"{
+                "workHours": { "start": "08:00", "end": "18:00" },
+                "recipientNight": { "start": "22:00", "end": "07:00" }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn zone(address: &str, hours: i32) -> RecipientZone {
+        RecipientZone {
+            address: address.into(),
+            offset: FixedOffset::east_opt(hours * 3600).unwrap(),
+        }
+    }
+
+    #[test]
+    fn inside_work_hours_with_awake_recipients_passes() {
+        // Wednesday 10:00 in London, 11:00 in Berlin.
+        let now = Utc.with_ymd_and_hms(2026, 1, 14, 10, 0, 0).unwrap();
+        let cfg = config();
+        assert!(
+            cfg.check(now, chrono_tz::Europe::London, &[zone("ann@example.de", 1)])
+                .is_ok()
+        );
+        assert!(
+            SendHoursConfig::default()
+                .reasons(now, Tz::UTC, &[])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn weekend_and_evening_fall_outside_work_hours() {
+        let cfg = config();
+        let saturday = Utc.with_ymd_and_hms(2026, 1, 17, 10, 0, 0).unwrap();
+        let reasons = cfg.reasons(saturday, Tz::UTC, &[]);
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].contains("Saturday"), "{reasons:?}");
+
+        let evening = Utc.with_ymd_and_hms(2026, 1, 14, 19, 30, 0).unwrap();
+        assert!(reasons_contain(
+            &cfg.reasons(evening, Tz::UTC, &[]),
+            "19:30"
+        ));
+
+        let weekend_shift: SendHoursConfig = serde_json::from_str(
+            r#"{"workHours": {"start": "08:00", "end": "18:00", "days": ["sat", "Sunday"]}}"#,
+        )
+        .unwrap();
+        assert!(weekend_shift.reasons(saturday, Tz::UTC, &[]).is_empty());
+    }
+
+    #[test]
+    fn recipient_night_wraps_midnight() {
+        let cfg = config();
+        // Wednesday 15:00 UTC is 00:00 Thursday in Tokyo.
+        let now = Utc.with_ymd_and_hms(2026, 1, 14, 15, 0, 0).unwrap();
+        let zones = [zone("kenji@example.jp", 9), zone("ann@example.de", 1)];
+        let reasons = cfg.reasons(now, Tz::UTC, &zones);
+        assert_eq!(reasons.len(), 1, "{reasons:?}");
+        assert!(reasons[0].contains("00:00 for kenji@example.jp (UTC+09:00)"));
+    }
+
+    #[test]
+    fn suggestion_is_first_quarter_hour_clearing_every_check() {
+        let cfg = config();
+        // 16:05 UTC is 01:05 in Tokyo; their 07:00 is 22:00 UTC, after our
+        // work day, so the first slot both sides accept is Thursday 08:00
+        // UTC (17:00 in Tokyo).
+        let now = Utc.with_ymd_and_hms(2026, 1, 14, 16, 5, 0).unwrap();
+        let err = cfg
+            .check(now, Tz::UTC, &[zone("kenji@example.jp", 9)])
+            .unwrap_err();
+        let Error::SendTimeWarning {
+            suggested_send_at, ..
+        } = err
+        else {
+            panic!("got {err:?}");
+        };
+        assert_eq!(
+            suggested_send_at,
+            Some(Utc.with_ymd_and_hms(2026, 1, 15, 8, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn no_suggestion_when_nothing_clears() {
+        let cfg: SendHoursConfig = serde_json::from_str(
+            r#"{"workHours": {"start": "08:00", "end": "18:00", "days": []}}"#,
+        )
+        .unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 1, 14, 10, 0, 0).unwrap();
+        assert_eq!(cfg.suggest(now, Tz::UTC, &[]), None);
+    }
+
+    #[test]
+    fn likely_offset_takes_the_most_common_then_the_newest() {
+        let d = |s: &str| DateTime::parse_from_rfc2822(s).unwrap();
+        let dates = [
+            d("Tue, 13 Jan 2026 09:00:00 -0500"),
+            d("Mon, 12 Jan 2026 09:00:00 +0100"),
+            d("Fri, 9 Jan 2026 09:00:00 +0100"),
+        ];
+        assert_eq!(likely_offset(&dates), FixedOffset::east_opt(3600));
+        assert_eq!(likely_offset(&dates[..2]), FixedOffset::west_opt(5 * 3600));
+        assert_eq!(likely_offset(&[]), None);
+    }
+
+    #[test]
+    fn load_config_missing_or_invalid_is_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!load_config(&dir.path().join("missing.json")).is_enabled());
+        let bad = dir.path().join("send-hours.json");
+        std::fs::write(&bad, "{not json").unwrap();
+        assert!(!load_config(&bad).is_enabled());
+    }
+
+    fn reasons_contain(reasons: &[String], needle: &str) -> bool {
+        reasons.iter().any(|r| r.contains(needle))
+    }
+}
@@ -0,0 +1,258 @@
+//! Server-side filing rules as Sieve (RFC 5228) scripts.
+//!
+//! Fastmail exposes an account's scripts over JMAP
+//! (`urn:ietf:params:jmap:sieve`, RFC 9661). `GET /api/sieve` returns the
+//! active script and `PUT /api/sieve` replaces it, creating and activating
+//! one when none is active; the server validates the script, and one it
+//! rejects comes back as a 422 with the server's reason.
+//!
+//! [`split_rule`] turns a split into a rule that files the same mail into a
+//! mailbox, so a split can become a filter that runs on the server whether
+//! or not the app is open. `POST /api/sieve/from-split/{split_id}` returns
+//! it without saving anything; the UI appends it to the script and PUTs.
+
+use crate::glob;
+use crate::types::{FilterType, GlobMatch, MatchMode, PatternKind, SplitFilter, SplitInbox};
+use serde::Serialize;
+
+/// Name given to the script `PUT /api/sieve` creates when the account has
+/// no active one.
+pub const SCRIPT_NAME: &str = "supervillain";
+
+/// The account's active script.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SieveScript {
+    /// `None` when no script is active yet.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub content: String,
+}
+
+/// A generated rule and the extensions it needs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SieveRule {
+    pub require: Vec<&'static str>,
+    pub rule: String,
+}
+
+impl SieveRule {
+    /// The rule as a complete script, `require` line included.
+    pub fn script(&self) -> String {
+        let require = self
+            .require
+            .iter()
+            .map(|ext| quote(ext))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("require [{require}];\n\n{}", self.rule)
+    }
+}
+
+/// A rule filing what `split` matches into the mailbox `mailbox_id`
+/// (`mailbox_name` is the fallback for servers without the `mailboxid`
+/// extension). Split patterns carry over as Sieve match types: globs as
+/// `:matches` (brace alternatives become a key list), substrings as
+/// `:contains`, regexes as `:regex`. Fails for what Sieve can't express:
+/// calendar filters and glob character classes.
+pub fn split_rule(
+    split: &SplitInbox,
+    mailbox_id: &str,
+    mailbox_name: &str,
+) -> Result<SieveRule, String> {
+    if split.filters.is_empty() {
+        return Err(format!("split '{}' has no filters", split.id));
+    }
+    let mut require = vec!["fileinto", "mailboxid"];
+    let mut tests = Vec::with_capacity(split.filters.len());
+    for filter in &split.filters {
+        let (test, extension) =
+            filter_test(filter).map_err(|e| format!("split '{}': {e}", split.id))?;
+        if let Some(ext) = extension
+            && !require.contains(&ext)
+        {
+            require.push(ext);
+        }
+        tests.push(test);
+    }
+    let condition = match tests.as_slice() {
+        [one] => one.clone(),
+        many => {
+            let op = match split.match_mode {
+                MatchMode::Any => "anyof",
+                MatchMode::All => "allof",
+            };
+            format!("{op} (\n    {}\n)", many.join(",\n    "))
+        }
+    };
+    let rule = format!(
+        "# Split: {}\nif {condition} {{\n    fileinto :mailboxid {} {};\n}}\n",
+        split.name.replace(['\r', '\n'], " "),
+        quote(mailbox_id),
+        quote(mailbox_name)
+    );
+    Ok(SieveRule { require, rule })
+}
+
+/// One filter as a Sieve test, plus the extension it needs beyond the
+/// base language.
+fn filter_test(filter: &SplitFilter) -> Result<(String, Option<&'static str>), String> {
+    let (match_type, keys, extension) = match filter.effective_kind() {
+        PatternKind::Glob => {
+            let mut keys = glob::wildcard_alternatives(&filter.pattern)
+                .ok_or("character classes have no Sieve equivalent; use a regex filter instead")?;
+            if filter.effective_glob_match() == GlobMatch::Contains {
+                for key in &mut keys {
+                    if !key.starts_with('*') {
+                        key.insert(0, '*');
+                    }
+                    if !key.ends_with('*') || key.ends_with("\\*") {
+                        key.push('*');
+                    }
+                }
+            }
+            (":matches", keys, None)
+        }
+        PatternKind::Substring => (":contains", vec![filter.pattern.clone()], None),
+        // Legacy subject filters fall back to a substring when the regex
+        // doesn't compile; do the same here.
+        PatternKind::Regex
+            if filter.pattern_kind.is_none() && regex::Regex::new(&filter.pattern).is_err() =>
+        {
+            (":contains", vec![filter.pattern.clone()], None)
+        }
+        PatternKind::Regex => (":regex", vec![filter.pattern.clone()], Some("regex")),
+    };
+    let keys = string_list(&keys);
+    let test = match filter.filter_type {
+        FilterType::From => format!("address :all {match_type} \"from\" {keys}"),
+        FilterType::To => format!("address :all {match_type} [\"to\", \"cc\"] {keys}"),
+        FilterType::Subject => format!("header {match_type} \"subject\" {keys}"),
+        FilterType::Keyword => {
+            if extension.is_some() {
+                return Err("regex keyword filters have no Sieve equivalent".into());
+            }
+            return Ok((format!("hasflag {match_type} {keys}"), Some("imap4flags")));
+        }
+        FilterType::Calendar | FilterType::Header => {
+            return Err("calendar filters have no Sieve equivalent".into());
+        }
+    };
+    Ok((test, extension))
+}
+
+fn string_list(items: &[String]) -> String {
+    match items {
+        [one] => quote(one),
+        many => format!(
+            "[{}]",
+            many.iter().map(|s| quote(s)).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// A Sieve quoted string: only `"` and `\` need escaping.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(filter_type: FilterType, pattern: &str) -> SplitFilter {
+        SplitFilter {
+            filter_type,
+            pattern: pattern.into(),
+            name: None,
+            pattern_kind: None,
+            glob_match: Some(GlobMatch::Exact),
+            extra: Default::default(),
+        }
+    }
+
+    fn split(filters: Vec<SplitFilter>, match_mode: MatchMode) -> SplitInbox {
+        SplitInbox {
+            id: "shop".into(),
+            name: "Shopping".into(),
+            icon: None,
+            filters,
+            match_mode,
+            account: None,
+            action: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn single_filter_becomes_one_test() {
+        let rule = split_rule(
+            &split(
+                vec![filter(FilterType::From, "*@{shop,store}.example")],
+                MatchMode::Any,
+            ),
+            "M42",
+            "Shopping",
+        )
+        .unwrap();
+        assert_eq!(
+            rule.script(),
+            "require [\"fileinto\", \"mailboxid\"];\n\n\
+             # Split: Shopping\n\
+             if address :all :matches \"from\" [\"*@shop.example\", \"*@store.example\"] {\n    \
+             fileinto :mailboxid \"M42\" \"Shopping\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn match_mode_and_pattern_kinds_map_to_sieve() {
+        let mut contains = filter(FilterType::To, "team");
+        contains.glob_match = Some(GlobMatch::Contains);
+        let mut substring = filter(FilterType::Subject, "50% \"off\"");
+        substring.pattern_kind = Some(PatternKind::Substring);
+        let regex = filter(FilterType::Subject, "^order #\\d+");
+        let rule = split_rule(
+            &split(vec![contains, substring, regex], MatchMode::All),
+            "M1",
+            "Orders",
+        )
+        .unwrap();
+        assert_eq!(rule.require, ["fileinto", "mailboxid", "regex"]);
+        assert!(rule.rule.contains(
+            "if allof (\n    address :all :matches [\"to\", \"cc\"] \"*team*\",\n    \
+             header :contains \"subject\" \"50% \\\"off\\\"\",\n    \
+             header :regex \"subject\" \"^order #\\\\d+\"\n) {"
+        ));
+    }
+
+    #[test]
+    fn keywords_need_imap4flags() {
+        let rule = split_rule(
+            &split(
+                vec![filter(FilterType::Keyword, "receipts")],
+                MatchMode::Any,
+            ),
+            "M1",
+            "Receipts",
+        )
+        .unwrap();
+        assert!(rule.require.contains(&"imap4flags"));
+        assert!(rule.rule.contains("if hasflag :matches \"receipts\" {"));
+    }
+
+    #[test]
+    fn inexpressible_filters_are_refused() {
+        let calendar = split(vec![filter(FilterType::Calendar, "")], MatchMode::Any);
+        assert!(split_rule(&calendar, "M1", "x").is_err());
+        let class = split(
+            vec![filter(FilterType::From, "[ab]*@x.example")],
+            MatchMode::Any,
+        );
+        assert!(
+            split_rule(&class, "M1", "x")
+                .unwrap_err()
+                .contains("character classes")
+        );
+        assert!(split_rule(&split(vec![], MatchMode::Any), "M1", "x").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Signature logos sent as inline images.
+//!
+//! A logo linked from a web server gets blocked by most recipients'
+//! clients, but an image carried in the message as a `cid:` part shows.
+//! `identities.json` names a local image per identity (`*` for any of the
+//! account's identities):
+//!
+//! ```json
+//! { "accounts": { "work": {
+//!     "signatureImages": { "me@work.example": "/home/me/logo.png" } } } }
+//! ```
+//!
+//! When an outgoing message still carries the account's signature (the
+//! compose prefill, `-- ` and the signature text), the send path uploads
+//! the image once per account and file version ([`SignatureBlobs`]) and
+//! [`embed`] adds it under the signature as an inline part. Only paths
+//! from the config are ever read, so nothing in a message body can pull
+//! a local file into outgoing mail. Fastmail only for now: Gmail and
+//! Outlook build their MIME without inline parts.
+
+use crate::error::Error;
+use crate::identities::AccountIdentityRules;
+use crate::types::{Attachment, EmailSubmission};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// `Content-ID` of the inline part; the HTML refers to it as `cid:`.
+pub const CONTENT_ID: &str = "signature-logo@supervillain";
+
+/// Larger files are refused: a logo that size would bloat every message.
+pub const MAX_IMAGE_BYTES: usize = 512 * 1024;
+
+/// The image configured for sending as `from`: its own entry, else `*`.
+pub fn image_for<'a>(rules: &'a AccountIdentityRules, from: &str) -> Option<&'a str> {
+    rules
+        .signature_images
+        .iter()
+        .find(|(identity, _)| identity.eq_ignore_ascii_case(from))
+        .or_else(|| rules.signature_images.get_key_value("*"))
+        .map(|(_, path)| path.as_str())
+        .filter(|path| !path.trim().is_empty())
+}
+
+/// The message still has the signature the compose prefilled.
+pub fn carries_signature(sub: &EmailSubmission, signature: &str) -> bool {
+    !signature.is_empty() && sub.text_body.contains(&format!("-- \n{signature}"))
+}
+
+/// A local image ready to upload: its bytes, sniffed type and version.
+pub struct LocalImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+    pub modified: SystemTime,
+    pub name: String,
+}
+
+/// Read `path`, which must be a raster image `sniff_mime_type` knows of
+/// at most [`MAX_IMAGE_BYTES`].
+pub fn read_image(path: &Path) -> Result<LocalImage, Error> {
+    let meta = std::fs::metadata(path)?;
+    if meta.len() > MAX_IMAGE_BYTES as u64 {
+        return Err(Error::BadRequest(format!(
+            "Signature image {} is over {} KiB",
+            path.display(),
+            MAX_IMAGE_BYTES / 1024
+        )));
+    }
+    let bytes = std::fs::read(path)?;
+    let mime_type = crate::provider_utils::sniff_mime_type(&bytes)
+        .filter(|m| m.starts_with("image/"))
+        .ok_or_else(|| {
+            Error::BadRequest(format!(
+                "Signature image {} is not a PNG, JPEG, GIF, WebP or BMP",
+                path.display()
+            ))
+        })?;
+    Ok(LocalImage {
+        bytes,
+        mime_type,
+        modified: meta.modified()?,
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "signature".into()),
+    })
+}
+
+/// Uploaded signature images by account and path, reused while the file
+/// is unchanged so each logo goes up once.
+#[derive(Default)]
+pub struct SignatureBlobs {
+    entries: Mutex<HashMap<(String, PathBuf), (SystemTime, Attachment)>>,
+}
+
+impl SignatureBlobs {
+    pub fn get(&self, account: &str, path: &Path, modified: SystemTime) -> Option<Attachment> {
+        self.lock()
+            .get(&(account.to_string(), path.to_path_buf()))
+            .filter(|(at, _)| *at == modified)
+            .map(|(_, blob)| blob.clone())
+    }
+
+    pub fn insert(&self, account: &str, path: &Path, modified: SystemTime, blob: Attachment) {
+        self.lock()
+            .insert((account.to_string(), path.to_path_buf()), (modified, blob));
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<(String, PathBuf), (SystemTime, Attachment)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Put `image` (an uploaded blob) under the signature: an HTML body is
+/// made from the text when there is none, the `<img>` goes before any
+/// quoted original, and the blob joins the attachments as the inline
+/// [`CONTENT_ID`] part.
+pub fn embed(sub: &mut EmailSubmission, image: Attachment) {
+    let img = format!("<br><img src=\"cid:{CONTENT_ID}\" alt=\"\">");
+    let html = sub
+        .html_body
+        .take()
+        .unwrap_or_else(|| format!("<div>{}</div>", text_to_html(&sub.text_body)));
+    let html = match html.find("<blockquote") {
+        Some(at) => format!("{}{img}{}", &html[..at], &html[at..]),
+        None => format!("{html}{img}"),
+    };
+    sub.html_body = Some(html);
+    sub.attachments.push(Attachment {
+        content_id: Some(CONTENT_ID.into()),
+        ..image
+    });
+}
+
+fn text_to_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(text: &str, html: Option<&str>) -> EmailSubmission {
+        EmailSubmission {
+            to: vec!["bob@example.com".into()],
+            cc: vec![],
+            subject: "Hi".into(),
+            text_body: text.into(),
+            bcc: None,
+            html_body: html.map(String::from),
+            in_reply_to: None,
+            references: None,
+            attachments: vec![],
+            calendar_ics: None,
+            forward_of: None,
+        }
+    }
+
+    fn blob() -> Attachment {
+        Attachment {
+            blob_id: "B1".into(),
+            name: "logo.png".into(),
+            mime_type: "image/png".into(),
+            size: 10,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
+        }
+    }
+
+    #[test]
+    fn identity_entry_wins_over_the_wildcard() {
+        let rules: AccountIdentityRules = serde_json::from_str(
+            r#"{"signatureImages": {"*": "/logos/any.png", "Me@Work.example": "/logos/me.png"}}"#,
+        )
+        .unwrap();
+        assert_eq!(image_for(&rules, "me@work.example"), Some("/logos/me.png"));
+        assert_eq!(
+            image_for(&rules, "other@work.example"),
+            Some("/logos/any.png")
+        );
+        assert_eq!(image_for(&AccountIdentityRules::default(), "me@x"), None);
+    }
+
+    #[test]
+    fn only_messages_keeping_the_signature_get_the_logo() {
+        let sub = submission("Thanks!\n\n-- \nAnn Lee\nAcme", None);
+        assert!(carries_signature(&sub, "Ann Lee\nAcme"));
+        assert!(!carries_signature(&submission("Thanks!", None), "Ann Lee"));
+        assert!(!carries_signature(&sub, ""));
+    }
+
+    #[test]
+    fn logo_goes_under_the_signature() {
+        let mut sub = submission("Hi <you>\n-- \nAnn", None);
+        embed(&mut sub, blob());
+        assert_eq!(
+            sub.html_body.as_deref(),
+            Some(
+                "<div>Hi &lt;you&gt;<br>-- <br>Ann</div>\
+                 <br><img src=\"cid:signature-logo@supervillain\" alt=\"\">"
+            )
+        );
+        assert_eq!(sub.attachments[0].content_id.as_deref(), Some(CONTENT_ID));
+
+        let mut reply = submission("", Some("<div>Ok</div><blockquote>old</blockquote>"));
+        embed(&mut reply, blob());
+        assert!(reply.html_body.unwrap().starts_with(
+            "<div>Ok</div><br><img src=\"cid:signature-logo@supervillain\" alt=\"\"><blockquote>"
+        ));
+    }
+
+    #[test]
+    fn only_small_images_are_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("logo.png");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\nrest").unwrap();
+        let image = read_image(&png).unwrap();
+        assert_eq!(
+            (image.mime_type, image.name.as_str()),
+            ("image/png", "logo.png")
+        );
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, b"not an image").unwrap();
+        assert!(read_image(&text).is_err());
+        let big = dir.path().join("big.png");
+        std::fs::write(&big, vec![0u8; MAX_IMAGE_BYTES + 1]).unwrap();
+        assert!(read_image(&big).is_err());
+    }
+}
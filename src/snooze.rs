@@ -0,0 +1,252 @@
+//! Snooze: hide a message until a wake time, then bring it back unread.
+//!
+//! `POST /api/emails/{id}/snooze` moves the message into a `Snoozed`
+//! mailbox (the account's `snoozed` role mailbox, else one by that name,
+//! created on first use) and appends a [`SnoozedRecord`] to
+//! `snoozed.json`. [`spawn_waker`] checks the journal every tick and moves
+//! each due message back to the Inbox, marked unread. The journal is the
+//! only schedule, so snoozes survive a restart and wake on the first tick
+//! after it when they fell due while the server was down.
+//!
+//! Outlook isn't supported: Graph gives a moved message a new id, so the
+//! recorded one couldn't be woken.
+
+use crate::error::Error;
+use crate::types::{Email, EmailAddress};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name of the mailbox snoozed mail waits in when the account has no
+/// `snoozed` role mailbox.
+pub const SNOOZED_MAILBOX_NAME: &str = "Snoozed";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnoozedRecord {
+    pub account: String,
+    pub email_id: String,
+    pub wake_at: DateTime<Utc>,
+    pub snoozed_at: DateTime<Utc>,
+    /// Mailbox (label on Gmail) the message waits in, removed on wake.
+    pub snoozed_mailbox_id: String,
+    pub subject: String,
+    #[serde(default)]
+    pub from: Option<EmailAddress>,
+}
+
+impl SnoozedRecord {
+    pub fn new(
+        account: &str,
+        email: &Email,
+        snoozed_mailbox_id: &str,
+        wake_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            account: account.to_string(),
+            email_id: email.id.clone(),
+            wake_at,
+            snoozed_at: now,
+            snoozed_mailbox_id: snoozed_mailbox_id.to_string(),
+            subject: email.subject.clone(),
+            from: email.from.first().cloned(),
+        }
+    }
+}
+
+/// On-disk shape of `snoozed.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SnoozeJournal {
+    #[serde(default)]
+    pub snoozed: Vec<SnoozedRecord>,
+}
+
+impl SnoozeJournal {
+    /// Add `record`, replacing an earlier snooze of the same message.
+    pub fn record(&mut self, record: SnoozedRecord) {
+        self.remove(&record.account, &record.email_id);
+        self.snoozed.push(record);
+    }
+
+    pub fn remove(&mut self, account: &str, email_id: &str) -> Option<SnoozedRecord> {
+        let pos = self
+            .snoozed
+            .iter()
+            .position(|r| r.account == account && r.email_id == email_id)?;
+        Some(self.snoozed.remove(pos))
+    }
+
+    pub fn find(&self, account: &str, email_id: &str) -> Option<&SnoozedRecord> {
+        self.snoozed
+            .iter()
+            .find(|r| r.account == account && r.email_id == email_id)
+    }
+
+    /// Every record whose wake time has passed, earliest first.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<SnoozedRecord> {
+        let mut out: Vec<SnoozedRecord> = self
+            .snoozed
+            .iter()
+            .filter(|r| r.wake_at <= now)
+            .cloned()
+            .collect();
+        out.sort_by_key(|r| r.wake_at);
+        out
+    }
+
+    /// `account`'s pending snoozes, next to wake first.
+    pub fn for_account(&self, account: &str) -> Vec<SnoozedRecord> {
+        let mut out: Vec<SnoozedRecord> = self
+            .snoozed
+            .iter()
+            .filter(|r| r.account == account)
+            .cloned()
+            .collect();
+        out.sort_by_key(|r| r.wake_at);
+        out
+    }
+}
+
+// =============================================================================
+// Journal load/save
+// =============================================================================
+
+pub fn load_journal(path: &Path) -> SnoozeJournal {
+    if path.exists() {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::warn!("Failed to read snooze journal: {e}");
+                return SnoozeJournal::default();
+            }
+        };
+        return serde_json::from_str(&content).unwrap_or_default();
+    }
+    SnoozeJournal::default()
+}
+
+pub fn save_journal(journal: &SnoozeJournal, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(journal)?;
+    crate::accounts::atomic_write_bytes(path, json.as_bytes(), /* secret */ false)?;
+    Ok(())
+}
+
+// =============================================================================
+// Background waker
+// =============================================================================
+
+pub fn spawn_waker(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("snooze", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("snooze");
+            if crate::read_only::enabled(&state).await {
+                continue;
+            }
+            let woken = crate::routes::wake_due_snoozes(&state).await;
+            if woken > 0 {
+                tracing::info!("Woke {woken} snoozed email(s)");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn email(id: &str) -> Email {
+        Email {
+            id: id.into(),
+            blob_id: String::new(),
+            thread_id: String::new(),
+            mailbox_ids: HashMap::new(),
+            keywords: HashMap::new(),
+            received_at: Utc::now(),
+            subject: "Follow up".into(),
+            from: vec![EmailAddress {
+                name: Some("Ann".into()),
+                email: "ann@example.com".into(),
+            }],
+            to: vec![],
+            cc: vec![],
+            preview: String::new(),
+            has_attachment: false,
+            size: 0,
+            text_body: None,
+            html_body: None,
+            has_calendar: false,
+            attachments: vec![],
+            in_reply_to: None,
+            body_parts: vec![],
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
+        }
+    }
+
+    fn record(account: &str, id: &str, wake_in_hours: i64) -> SnoozedRecord {
+        let now = Utc::now();
+        SnoozedRecord::new(
+            account,
+            &email(id),
+            "snoozed",
+            now + chrono::Duration::hours(wake_in_hours),
+            now,
+        )
+    }
+
+    #[test]
+    fn due_returns_past_wake_times_earliest_first() {
+        let mut journal = SnoozeJournal::default();
+        journal.record(record("a", "later", 5));
+        journal.record(record("a", "late", -1));
+        journal.record(record("b", "latest", -3));
+        let due: Vec<String> = journal
+            .due(Utc::now())
+            .into_iter()
+            .map(|r| r.email_id)
+            .collect();
+        assert_eq!(due, ["latest", "late"]);
+    }
+
+    #[test]
+    fn resnoozing_replaces_the_record() {
+        let mut journal = SnoozeJournal::default();
+        journal.record(record("a", "m1", 1));
+        journal.record(record("a", "m1", 24));
+        journal.record(record("b", "m1", 2));
+        assert_eq!(journal.snoozed.len(), 2);
+        let mine = journal.for_account("a");
+        assert_eq!(mine.len(), 1);
+        assert!(mine[0].wake_at > Utc::now() + chrono::Duration::hours(23));
+        assert_eq!(mine[0].from.as_ref().unwrap().email, "ann@example.com");
+        assert!(journal.remove("a", "m1").is_some());
+        assert!(journal.find("a", "m1").is_none());
+        assert!(journal.find("b", "m1").is_some());
+    }
+
+    #[test]
+    fn journal_roundtrips_and_missing_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snoozed.json");
+        assert_eq!(load_journal(&path), SnoozeJournal::default());
+        let mut journal = SnoozeJournal::default();
+        journal.record(record("a", "m1", 1));
+        save_journal(&journal, &path).unwrap();
+        assert_eq!(load_journal(&path), journal);
+    }
+}
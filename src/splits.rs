@@ -3,12 +3,18 @@
 //! `splits.json` is a single file, but each `SplitInbox` may carry an
 //! `account` tag (a config-section id, e.g. "aristoi"). Tagged splits
 //! exist only for that account; untagged splits apply to every account.
-//! Route handlers scope the loaded config with [`SplitsConfig::scoped_to`]
+//! Route handlers scope the loaded matchers with [`CompiledSplits::scoped_to`]
 //! before filtering or counting, so the synthetic "primary" split means
 //! "not matching any of *this account's* splits". A split tagged to a
 //! since-deleted account is never listed but stays in the file for
 //! hand-editing.
 //!
+//! The config may instead live in `splits.toml` beside `splits.json`;
+//! when that file exists it is the primary config (see
+//! [`resolve_config_path`]). TOML saves merge into the existing document
+//! so hand-written comments survive edits made from the UI, and keys this
+//! version doesn't know are kept on round-trip in either format.
+//!
 //! Filters run against parsed `Email` objects after fetch, so the same
 //! definition works identically on Fastmail, Outlook, and Gmail.
 //!
@@ -19,29 +25,106 @@
 //! would silently clobber the user's edits.
 
 use crate::error::Error;
-use crate::glob::glob_match;
+use crate::glob::{Glob, lowercase_if_needed};
 use crate::types::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
 
 // =============================================================================
 // Config load/save
 // =============================================================================
 
+/// Schema version written by this build.
+///
+/// - 0: no `version` key. Globs were always anchored.
+/// - 1: globs take `match = "exact" | "contains"`, and a filter without one
+///   matches anywhere (`contains`).
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Bring a parsed config up to [`CONFIG_VERSION`] without changing what it
+/// matches: version-0 glob filters are pinned to `match = "exact"` so they
+/// stay anchored. Returns whether anything changed; the file itself is
+/// rewritten by the next save.
+pub fn migrate(config: &mut SplitsConfig) -> bool {
+    if config.version >= CONFIG_VERSION {
+        return false;
+    }
+    for filter in config.splits.iter_mut().flat_map(|s| s.filters.iter_mut()) {
+        if filter.effective_kind() == PatternKind::Glob && filter.glob_match.is_none() {
+            filter.glob_match = Some(GlobMatch::Exact);
+        }
+    }
+    config.version = CONFIG_VERSION;
+    true
+}
+
+/// On-disk format of a splits config, chosen by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitsFormat {
+    Json,
+    Toml,
+}
+
+impl SplitsFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => SplitsFormat::Toml,
+            _ => SplitsFormat::Json,
+        }
+    }
+
+    /// Parse and [`migrate`] a config.
+    pub fn parse(self, content: &str) -> Result<SplitsConfig, String> {
+        let mut config: SplitsConfig = match self {
+            SplitsFormat::Json => {
+                serde_json::from_str(content).map_err(|e| format!("JSON parse failed: {e}"))?
+            }
+            SplitsFormat::Toml => {
+                toml_edit::de::from_str(content).map_err(|e| format!("TOML parse failed: {e}"))?
+            }
+        };
+        migrate(&mut config);
+        Ok(config)
+    }
+}
+
+/// The file load/save actually use. A `splits.toml` beside the configured
+/// `splits.json` takes over as the primary config as soon as it exists, so
+/// switching to TOML is just a matter of creating it (or exporting to it);
+/// the JSON file is then left alone.
+pub fn resolve_config_path(config_path: &Path) -> PathBuf {
+    if SplitsFormat::from_path(config_path) == SplitsFormat::Toml {
+        return config_path.to_path_buf();
+    }
+    let toml_path = config_path.with_extension("toml");
+    if toml_path.exists() {
+        toml_path
+    } else {
+        config_path.to_path_buf()
+    }
+}
+
 pub fn load_splits(config_path: &Path, env_override: Option<&str>) -> SplitsConfig {
     // Env var takes precedence
     if let Some(json_str) = env_override {
-        return serde_json::from_str(json_str).unwrap_or_default();
+        return SplitsFormat::Json.parse(json_str).unwrap_or_default();
     }
     // Try file
-    if config_path.exists() {
-        let content = match std::fs::read_to_string(config_path) {
+    let path = resolve_config_path(config_path);
+    if path.exists() {
+        let content = match std::fs::read_to_string(&path) {
             Ok(c) => c,
             Err(e) => {
                 tracing::warn!("Failed to read splits config: {e}");
                 return SplitsConfig::default();
             }
         };
-        return serde_json::from_str(&content).unwrap_or_default();
+        return SplitsFormat::from_path(&path)
+            .parse(&content)
+            .unwrap_or_default();
     }
     SplitsConfig::default()
 }
@@ -52,24 +135,396 @@ pub fn load_splits(config_path: &Path, env_override: Option<&str>) -> SplitsConf
 /// route-handler path keeps using `load_splits` so a transient read failure
 /// never 500s a live request.
 pub fn try_load_splits(config_path: &Path) -> Result<Option<SplitsConfig>, String> {
-    if !config_path.exists() {
+    let path = resolve_config_path(config_path);
+    if !path.exists() {
         return Ok(None);
     }
-    let content = std::fs::read_to_string(config_path).map_err(|e| format!("read failed: {e}"))?;
-    serde_json::from_str::<SplitsConfig>(&content)
-        .map(Some)
-        .map_err(|e| format!("JSON parse failed: {e}"))
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("read failed: {e}"))?;
+    SplitsFormat::from_path(&path).parse(&content).map(Some)
 }
 
 pub fn save_splits(config: &SplitsConfig, config_path: &Path) -> Result<(), Error> {
-    if let Some(parent) = config_path.parent() {
+    let path = resolve_config_path(config_path);
+    if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let json = serde_json::to_string_pretty(config)?;
-    std::fs::write(config_path, json)?;
+    let content = match SplitsFormat::from_path(&path) {
+        SplitsFormat::Json => serde_json::to_string_pretty(config)?,
+        SplitsFormat::Toml => {
+            let existing = std::fs::read_to_string(&path).ok();
+            to_toml(config, existing.as_deref())?
+        }
+    };
+    std::fs::write(&path, content)?;
     Ok(())
 }
 
+/// Render `config` as JSON or TOML — the export side of the conversion.
+pub fn render(config: &SplitsConfig, format: SplitsFormat) -> Result<String, Error> {
+    match format {
+        SplitsFormat::Json => Ok(serde_json::to_string_pretty(config)?),
+        SplitsFormat::Toml => to_toml(config, None),
+    }
+}
+
+// =============================================================================
+// TOML with comments preserved
+// =============================================================================
+
+/// Serialize `config` as TOML. With `existing` (the current file), the new
+/// values are merged into that document instead of replacing it, so
+/// comments, key order and formatting survive a save from the UI. Splits
+/// are matched by `id`, so a reorder moves each split together with its
+/// comments; a deleted split takes its comments with it.
+pub fn to_toml(config: &SplitsConfig, existing: Option<&str>) -> Result<String, Error> {
+    let rendered = toml_edit::ser::to_string_pretty(config)
+        .map_err(|e| Error::Internal(format!("TOML serialize failed: {e}")))?;
+    let Some(existing) = existing else {
+        return Ok(rendered);
+    };
+    // A file that no longer parses can't be merged into; overwrite it
+    // rather than refusing to save.
+    let Ok(mut doc) = existing.parse::<DocumentMut>() else {
+        return Ok(rendered);
+    };
+    let fresh = rendered
+        .parse::<DocumentMut>()
+        .map_err(|e| Error::Internal(format!("TOML reparse failed: {e}")))?;
+    let had_top_level_values = doc.iter().any(|(_, item)| item.is_value());
+    merge_table(doc.as_table_mut(), fresh.as_table().clone());
+    if !had_top_level_values {
+        hoist_file_header(&mut doc);
+    }
+    // Tables print in document-position order, not tree order; renumber
+    // so reordered and newly added splits land where the array says.
+    let mut next = 0;
+    renumber_tables(doc.as_table_mut(), &mut next);
+    Ok(doc.to_string())
+}
+
+fn merge_item(old: &mut Item, new: Item) {
+    match (old, new) {
+        (Item::Table(o), Item::Table(n)) => merge_table(o, n),
+        (Item::ArrayOfTables(o), Item::ArrayOfTables(n)) => merge_array_of_tables(o, n),
+        (Item::Value(o), Item::Value(n)) => merge_value(o, n),
+        (old, new) => *old = new,
+    }
+}
+
+/// Update `old` in place: keys missing from `new` go, existing keys are
+/// merged (keeping their decor), new keys are appended.
+fn merge_table(old: &mut Table, new: Table) {
+    let stale: Vec<String> = old
+        .iter()
+        .map(|(k, _)| k.to_string())
+        .filter(|k| !new.contains_key(k))
+        .collect();
+    for key in stale {
+        old.remove(&key);
+    }
+    for (key, item) in new {
+        match old.get_mut(&key) {
+            Some(existing) => merge_item(existing, item),
+            None => {
+                old.insert(&key, item);
+            }
+        }
+    }
+}
+
+/// A leading comment block belongs to the first table, so top-level keys
+/// added by a save (`version` after a migration) would print above it.
+/// Move everything up to the last blank line — the file's own header, not
+/// the first split's comment — onto the first top-level key instead.
+fn hoist_file_header(doc: &mut DocumentMut) {
+    let Some(first_key) = doc
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(k, _)| k.to_string())
+    else {
+        return;
+    };
+    let Some(Item::ArrayOfTables(splits)) = doc.get_mut("splits") else {
+        return;
+    };
+    let Some(first) = splits.get_mut(0) else {
+        return;
+    };
+    let prefix = first
+        .decor()
+        .prefix()
+        .and_then(|p| p.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let Some(split_at) = prefix.rfind("\n\n") else {
+        return;
+    };
+    let (header, rest) = prefix.split_at(split_at + 2);
+    first.decor_mut().set_prefix(format!("\n{rest}"));
+    let header = header.to_string();
+    if let Some(mut key) = doc.key_mut(&first_key) {
+        key.leaf_decor_mut().set_prefix(header);
+    }
+}
+
+fn table_id(table: &Table) -> Option<&str> {
+    table.get("id").and_then(|v| v.as_str())
+}
+
+/// Rebuild the array in `new`'s order, reusing the old table for each
+/// element that has a counterpart: same `id`, or same index for elements
+/// without one (filters).
+fn merge_array_of_tables(old: &mut ArrayOfTables, new: ArrayOfTables) {
+    let mut previous: Vec<Option<Table>> = old.iter().cloned().map(Some).collect();
+    let mut merged = ArrayOfTables::new();
+    for (i, table) in new.into_iter().enumerate() {
+        let slot = match table_id(&table) {
+            Some(id) => previous
+                .iter()
+                .position(|t| t.as_ref().and_then(table_id) == Some(id)),
+            None => previous
+                .get(i)
+                .and_then(|t| t.as_ref())
+                .is_some_and(|t| table_id(t).is_none())
+                .then_some(i),
+        };
+        match slot.and_then(|i| previous[i].take()) {
+            Some(mut kept) => {
+                merge_table(&mut kept, table);
+                merged.push(kept);
+            }
+            None => merged.push(table),
+        }
+    }
+    *old = merged;
+}
+
+/// Replace the value but keep its surrounding whitespace and trailing
+/// comment. Unchanged scalars are left untouched so quoting style survives.
+fn merge_value(old: &mut Value, new: Value) {
+    let unchanged = match (&*old, &new) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        _ => false,
+    };
+    if unchanged {
+        return;
+    }
+    let decor = old.decor().clone();
+    *old = new;
+    *old.decor_mut() = decor;
+}
+
+fn renumber_tables(table: &mut Table, next: &mut isize) {
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(t) => {
+                t.set_position(Some(*next));
+                *next += 1;
+                renumber_tables(t, next);
+            }
+            Item::ArrayOfTables(array) => {
+                for t in array.iter_mut() {
+                    t.set_position(Some(*next));
+                    *next += 1;
+                    renumber_tables(t, next);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// =============================================================================
+// Shared in-memory config + file watcher
+// =============================================================================
+
+/// What the watcher compares to notice an edit: which file is active
+/// (`splits.toml` can appear or vanish beside `splits.json`), its mtime and
+/// its length. `None` = no file.
+type FileStamp = Option<(PathBuf, SystemTime, u64)>;
+
+fn file_stamp(config_path: &Path) -> FileStamp {
+    let path = resolve_config_path(config_path);
+    let meta = std::fs::metadata(&path).ok()?;
+    Some((path, meta.modified().ok()?, meta.len()))
+}
+
+/// The splits config every request reads, held in memory and swapped
+/// atomically when it changes — by an app write ([`SplitsStore::save`]) or
+/// by a hand/tool edit the watcher picks up ([`spawn_watcher`]). Writers
+/// call [`SplitsStore::publish`] once dependent caches are invalidated so
+/// `/api/events` subscribers refetch against a consistent server.
+pub struct SplitsStore {
+    path: PathBuf,
+    /// `SUPERVILLAIN_SPLITS`: pins the config, disabling file reloads.
+    env_override: Option<String>,
+    current: std::sync::RwLock<Snapshot>,
+    stamp: std::sync::Mutex<FileStamp>,
+    version: AtomicU64,
+    changes: tokio::sync::broadcast::Sender<u64>,
+}
+
+/// A config and its compiled matchers, swapped together so a reader never
+/// pairs one version's splits with another's patterns.
+#[derive(Clone)]
+struct Snapshot {
+    config: Arc<SplitsConfig>,
+    compiled: Arc<CompiledSplits>,
+}
+
+impl Snapshot {
+    fn new(config: SplitsConfig) -> Self {
+        Self {
+            compiled: Arc::new(CompiledSplits::new(&config)),
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl SplitsStore {
+    pub fn new(path: PathBuf, env_override: Option<String>) -> Self {
+        let stamp = file_stamp(&path);
+        let config = load_splits(&path, env_override.as_deref());
+        let (changes, _) = tokio::sync::broadcast::channel(16);
+        Self {
+            path,
+            env_override,
+            current: std::sync::RwLock::new(Snapshot::new(config)),
+            stamp: std::sync::Mutex::new(stamp),
+            version: AtomicU64::new(0),
+            changes,
+        }
+    }
+
+    /// Configured path (`splits.json`); see [`resolve_config_path`] for
+    /// the file actually in use.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn current(&self) -> Arc<SplitsConfig> {
+        self.current
+            .read()
+            .expect("splits config lock poisoned")
+            .config
+            .clone()
+    }
+
+    /// Matchers for [`SplitsStore::current`], compiled when it was loaded.
+    pub fn compiled(&self) -> Arc<CompiledSplits> {
+        self.current
+            .read()
+            .expect("splits config lock poisoned")
+            .compiled
+            .clone()
+    }
+
+    /// Read the config from disk, bypassing the in-memory copy. Writers
+    /// start from this so an edit the watcher hasn't seen yet isn't
+    /// clobbered by a save from the UI.
+    pub fn load_fresh(&self) -> SplitsConfig {
+        load_splits(&self.path, self.env_override.as_deref())
+    }
+
+    /// Persist `config` and make it current. Doesn't notify subscribers;
+    /// call [`SplitsStore::publish`] after invalidating derived caches.
+    pub fn save(&self, config: &SplitsConfig) -> Result<(), Error> {
+        save_splits(config, &self.path)?;
+        self.swap(config.clone(), file_stamp(&self.path));
+        Ok(())
+    }
+
+    /// Write an uploaded TOML document verbatim as `splits.toml`, making it
+    /// the primary config. `config` is `raw` already parsed.
+    pub fn import_toml(&self, raw: &str, config: SplitsConfig) -> Result<(), Error> {
+        let toml_path = match SplitsFormat::from_path(&self.path) {
+            SplitsFormat::Toml => self.path.clone(),
+            SplitsFormat::Json => self.path.with_extension("toml"),
+        };
+        if let Some(parent) = toml_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&toml_path, raw)?;
+        self.swap(config, file_stamp(&self.path));
+        Ok(())
+    }
+
+    /// Re-read the file if it changed since the last load or save. A file
+    /// that no longer parses keeps the previous config (a half-saved edit
+    /// shouldn't wipe every tab) and is retried on the next change.
+    pub fn reload_if_changed(&self) -> bool {
+        if self.env_override.is_some() {
+            return false;
+        }
+        let stamp = file_stamp(&self.path);
+        if *self.stamp.lock().expect("splits stamp lock poisoned") == stamp {
+            return false;
+        }
+        match try_load_splits(&self.path) {
+            Ok(config) => {
+                self.swap(config.unwrap_or_default(), stamp);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable splits config: {e}");
+                *self.stamp.lock().expect("splits stamp lock poisoned") = stamp;
+                false
+            }
+        }
+    }
+
+    fn swap(&self, config: SplitsConfig, stamp: FileStamp) {
+        // Compile outside the lock; readers keep the old snapshot meanwhile.
+        let snapshot = Snapshot::new(config);
+        *self.current.write().expect("splits config lock poisoned") = snapshot;
+        *self.stamp.lock().expect("splits stamp lock poisoned") = stamp;
+    }
+
+    /// Bump the version and tell `/api/events` subscribers. Returns the new
+    /// version.
+    pub fn publish(&self) -> u64 {
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        // No receivers is the normal idle case, not an error.
+        let _ = self.changes.send(version);
+        version
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<u64> {
+        self.changes.subscribe()
+    }
+}
+
+/// Poll the splits file for edits made outside the app (an editor, a
+/// sync tool) and hot-swap them in. Polling a stat every `interval` is
+/// cheap and behaves the same on every platform and filesystem, including
+/// the network mounts and atomic-rename saves that trip up inotify-style
+/// watchers.
+pub fn spawn_watcher(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("splits-watcher", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("splits-watcher");
+            if state.splits.reload_if_changed() {
+                tracing::info!("Splits config changed on disk; reloaded");
+                crate::routes::splits_changed(&state).await;
+            }
+        }
+    })
+}
+
 // =============================================================================
 // Account scoping
 // =============================================================================
@@ -163,86 +618,313 @@ pub fn generate_splits_from_identities(
                     filter_type: FilterType::To,
                     pattern: format!("*@{domain}"),
                     name: None,
+                    pattern_kind: None,
+                    // Anchored, so `*@gmail.com` doesn't also claim
+                    // `gmail.com.au`.
+                    glob_match: Some(GlobMatch::Exact),
+                    extra: Default::default(),
                 }],
                 match_mode: MatchMode::Any,
                 account: Some(account.to_string()),
+                action: None,
+                extra: Default::default(),
             }
         })
         .collect();
 
-    SplitsConfig { splits }
+    SplitsConfig {
+        splits,
+        version: CONFIG_VERSION,
+        extra: Default::default(),
+    }
 }
 
 // =============================================================================
 // Filter matching
 // =============================================================================
 
-pub fn matches_filter(email: &Email, filter: &SplitFilter) -> bool {
-    match filter.filter_type {
-        FilterType::From => email
-            .from
-            .iter()
-            .any(|addr| glob_match(&filter.pattern, &addr.email)),
-        FilterType::To => {
-            let all_recipients = email.to.iter().chain(email.cc.iter());
-            all_recipients
-                .into_iter()
-                .any(|addr| glob_match(&filter.pattern, &addr.email))
+impl SplitFilter {
+    /// The kind the pattern is matched as, resolving `None` to the legacy
+    /// per-type default.
+    pub fn effective_kind(&self) -> PatternKind {
+        self.pattern_kind.unwrap_or(match self.filter_type {
+            FilterType::Subject => PatternKind::Regex,
+            _ => PatternKind::Glob,
+        })
+    }
+
+    /// How a glob pattern is anchored; only meaningful when
+    /// [`SplitFilter::effective_kind`] is `Glob`. Keywords are whole
+    /// words, so `receipt` doesn't also catch `receipts-2019`.
+    pub fn effective_glob_match(&self) -> GlobMatch {
+        self.glob_match.unwrap_or(match self.filter_type {
+            FilterType::Keyword => GlobMatch::Exact,
+            _ => GlobMatch::Contains,
+        })
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+}
+
+/// Reject filters that can't match as written. Run on every write path
+/// (create, update, import, preview); a hand-edited file that fails it
+/// still loads — see [`CompiledFilter::new`] for how it then behaves.
+pub fn validate_filter(filter: &SplitFilter) -> Result<(), String> {
+    if matches!(
+        filter.filter_type,
+        FilterType::Calendar | FilterType::Header
+    ) {
+        return Ok(());
+    }
+    if filter.pattern.is_empty() {
+        return Err("pattern must not be empty".into());
+    }
+    if filter.effective_kind() == PatternKind::Regex
+        && let Err(e) = compile_regex(&filter.pattern)
+    {
+        return Err(format!("invalid regex '{}': {e}", filter.pattern));
+    }
+    Ok(())
+}
+
+pub fn validate_split(split: &SplitInbox) -> Result<(), String> {
+    for filter in &split.filters {
+        validate_filter(filter).map_err(|e| format!("split '{}': {e}", split.id))?;
+    }
+    if let Some(action) = &split.action {
+        if split.filters.is_empty() {
+            return Err(format!(
+                "split '{}': an action needs at least one filter",
+                split.id
+            ));
+        }
+        action
+            .validate()
+            .map_err(|e| format!("split '{}': {e}", split.id))?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+enum PatternMatcher {
+    Glob(Glob),
+    Regex(regex::Regex),
+    /// Lowercased needle.
+    Substring(String),
+    /// An explicit `regex` that doesn't compile matches nothing — guessing
+    /// at intent is what hid typos before `pattern_kind` existed.
+    Never,
+}
+
+impl PatternMatcher {
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            PatternMatcher::Glob(g) => g.matches(text),
+            PatternMatcher::Regex(re) => re.is_match(text),
+            PatternMatcher::Substring(needle) => lowercase_if_needed(text).contains(needle),
+            PatternMatcher::Never => false,
         }
-        FilterType::Subject => {
-            let pattern_lower = filter.pattern.to_lowercase();
-            let subject_lower = email.subject.to_lowercase();
-            match regex::Regex::new(&format!("(?i){}", filter.pattern)) {
-                Ok(re) => re.is_match(&email.subject),
-                Err(_) => {
+    }
+}
+
+/// A filter with its pattern compiled, so matching a window of messages
+/// compiles each regex once instead of once per message.
+#[derive(Clone)]
+pub struct CompiledFilter {
+    filter_type: FilterType,
+    matcher: PatternMatcher,
+}
+
+impl CompiledFilter {
+    pub fn new(filter: &SplitFilter) -> Self {
+        let matcher = match filter.effective_kind() {
+            PatternKind::Glob => PatternMatcher::Glob(match filter.effective_glob_match() {
+                GlobMatch::Exact => Glob::new(&filter.pattern),
+                GlobMatch::Contains => Glob::unanchored(&filter.pattern),
+            }),
+            PatternKind::Substring => PatternMatcher::Substring(filter.pattern.to_lowercase()),
+            PatternKind::Regex => match compile_regex(&filter.pattern) {
+                Ok(re) => PatternMatcher::Regex(re),
+                // Legacy (untagged) subject filters keep their old
+                // substring fallback so existing configs behave as before.
+                Err(_) if filter.pattern_kind.is_none() => {
                     tracing::warn!(
                         "Invalid regex '{}', falling back to substring match",
                         filter.pattern
                     );
-                    subject_lower.contains(&pattern_lower)
+                    PatternMatcher::Substring(filter.pattern.to_lowercase())
                 }
-            }
+                Err(e) => {
+                    tracing::warn!("Invalid regex '{}' never matches: {e}", filter.pattern);
+                    PatternMatcher::Never
+                }
+            },
+        };
+        Self {
+            filter_type: filter.filter_type.clone(),
+            matcher,
+        }
+    }
+
+    pub fn matches(&self, email: &Email) -> bool {
+        match self.filter_type {
+            FilterType::From => email.from.iter().any(|a| self.matcher.is_match(&a.email)),
+            FilterType::To => email
+                .to
+                .iter()
+                .chain(email.cc.iter())
+                .any(|a| self.matcher.is_match(&a.email)),
+            FilterType::Subject => self.matcher.is_match(&email.subject),
+            FilterType::Keyword => email
+                .custom_keywords()
+                .into_iter()
+                .any(|k| self.matcher.is_match(k)),
+            FilterType::Calendar | FilterType::Header => email.has_calendar,
         }
-        FilterType::Calendar | FilterType::Header => email.has_calendar,
     }
 }
 
-pub fn matches_split(email: &Email, split: &SplitInbox) -> bool {
-    if split.filters.is_empty() {
-        return false;
+/// A split with every filter compiled; reuse it across the whole message
+/// window.
+#[derive(Clone)]
+pub struct CompiledSplit {
+    match_mode: MatchMode,
+    filters: Vec<CompiledFilter>,
+}
+
+impl CompiledSplit {
+    pub fn new(split: &SplitInbox) -> Self {
+        Self::from_filters(&split.filters, &split.match_mode)
+    }
+
+    /// Compile bare filters, for definitions that aren't a split (rules).
+    pub fn from_filters(filters: &[SplitFilter], match_mode: &MatchMode) -> Self {
+        Self {
+            match_mode: match_mode.clone(),
+            filters: filters.iter().map(CompiledFilter::new).collect(),
+        }
     }
-    match split.match_mode {
-        MatchMode::Any => split.filters.iter().any(|f| matches_filter(email, f)),
-        MatchMode::All => split.filters.iter().all(|f| matches_filter(email, f)),
+
+    pub fn matches(&self, email: &Email) -> bool {
+        if self.filters.is_empty() {
+            return false;
+        }
+        match self.match_mode {
+            MatchMode::Any => self.filters.iter().any(|f| f.matches(email)),
+            MatchMode::All => self.filters.iter().all(|f| f.matches(email)),
+        }
     }
 }
 
-pub fn matches_any_split(email: &Email, config: &SplitsConfig) -> bool {
-    config
-        .splits
-        .iter()
-        .any(|split| matches_split(email, split))
+/// One-off check of a single filter. Compiles the pattern on every call;
+/// loops over many messages should hold a [`CompiledFilter`] instead.
+pub fn matches_filter(email: &Email, filter: &SplitFilter) -> bool {
+    CompiledFilter::new(filter).matches(email)
 }
 
-pub fn filter_by_split(emails: Vec<Email>, split_id: &str, config: &SplitsConfig) -> Vec<Email> {
-    debug_assert!(!split_id.is_empty(), "split_id must not be empty");
+/// One-off check of a single split; see [`CompiledSplit`] for loops.
+pub fn matches_split(email: &Email, split: &SplitInbox) -> bool {
+    CompiledSplit::new(split).matches(email)
+}
 
-    if split_id == "primary" {
-        return emails
-            .into_iter()
-            .filter(|e| !matches_any_split(e, config))
-            .collect();
+/// Every split of a [`SplitsConfig`], compiled. [`SplitsStore`] builds this
+/// once per config load, so list and count requests never compile a
+/// pattern; scoping clones compiled regexes, which is a refcount bump.
+#[derive(Clone, Default)]
+pub struct CompiledSplits {
+    entries: Vec<CompiledEntry>,
+}
+
+#[derive(Clone)]
+struct CompiledEntry {
+    id: String,
+    account: Option<String>,
+    split: CompiledSplit,
+}
+
+impl CompiledSplits {
+    pub fn new(config: &SplitsConfig) -> Self {
+        Self {
+            entries: config
+                .splits
+                .iter()
+                .map(|s| CompiledEntry {
+                    id: s.id.clone(),
+                    account: s.account.clone(),
+                    split: CompiledSplit::new(s),
+                })
+                .collect(),
+        }
     }
 
-    let split = match config.splits.iter().find(|s| s.id == split_id) {
-        Some(s) => s,
-        None => return vec![],
-    };
+    /// Same rule as [`SplitsConfig::scoped_to`].
+    pub fn scoped_to(&self, account: Option<&str>) -> Self {
+        let Some(account) = account else {
+            return self.clone();
+        };
+        Self {
+            entries: self
+                .entries
+                .iter()
+                .filter(|e| e.account.as_deref().is_none_or(|a| a == account))
+                .cloned()
+                .collect(),
+        }
+    }
 
-    emails
-        .into_iter()
-        .filter(|e| matches_split(e, split))
-        .collect()
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, split_id: &str) -> bool {
+        self.entries.iter().any(|e| e.id == split_id)
+    }
+
+    pub fn matches_any(&self, email: &Email) -> bool {
+        self.entries.iter().any(|e| e.split.matches(email))
+    }
+
+    /// Messages in `split_id`; `"primary"` is everything no split claims.
+    pub fn filter(&self, emails: Vec<Email>, split_id: &str) -> Vec<Email> {
+        debug_assert!(!split_id.is_empty(), "split_id must not be empty");
+
+        if split_id == "primary" {
+            return emails
+                .into_iter()
+                .filter(|e| !self.matches_any(e))
+                .collect();
+        }
+
+        let split = match self.entries.iter().find(|e| e.id == split_id) {
+            Some(e) => &e.split,
+            None => return vec![],
+        };
+
+        emails.into_iter().filter(|e| split.matches(e)).collect()
+    }
+
+    /// Matches per split id over `emails`.
+    pub fn counts(&self, emails: &[Email]) -> std::collections::HashMap<String, u32> {
+        self.entries
+            .iter()
+            .map(|e| {
+                let count = emails.iter().filter(|m| e.split.matches(m)).count();
+                (e.id.clone(), count as u32)
+            })
+            .collect()
+    }
+}
+
+pub fn matches_any_split(email: &Email, config: &SplitsConfig) -> bool {
+    CompiledSplits::new(config).matches_any(email)
+}
+
+pub fn filter_by_split(emails: Vec<Email>, split_id: &str, config: &SplitsConfig) -> Vec<Email> {
+    CompiledSplits::new(config).filter(emails, split_id)
 }
 
 // =============================================================================
@@ -281,6 +963,12 @@ mod tests {
             has_calendar: false,
             attachments: vec![],
             in_reply_to: None,
+            body_parts: Vec::new(),
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
         }
     }
 
@@ -305,6 +993,9 @@ mod tests {
             filter_type: FilterType::From,
             pattern: pattern.into(),
             name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         }
     }
 
@@ -313,6 +1004,9 @@ mod tests {
             filter_type: FilterType::Subject,
             pattern: pattern.into(),
             name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         }
     }
 
@@ -321,6 +1015,9 @@ mod tests {
             filter_type: FilterType::To,
             pattern: pattern.into(),
             name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         }
     }
 
@@ -332,6 +1029,8 @@ mod tests {
             filters: vec![to_filter(pattern)],
             match_mode: MatchMode::Any,
             account: account.map(String::from),
+            action: None,
+            extra: Default::default(),
         }
     }
 
@@ -405,6 +1104,232 @@ mod tests {
         assert!(matches_filter(&email, &subject_filter("[bracket")));
     }
 
+    fn kind_filter(filter_type: FilterType, pattern: &str, kind: PatternKind) -> SplitFilter {
+        SplitFilter {
+            pattern_kind: Some(kind),
+            filter_type,
+            ..subject_filter(pattern)
+        }
+    }
+
+    #[test]
+    fn explicit_invalid_regex_never_matches() {
+        let email = make_email("sender@example.com", "Test [bracket] text");
+        let filter = kind_filter(FilterType::Subject, "[bracket", PatternKind::Regex);
+        assert!(!matches_filter(&email, &filter));
+    }
+
+    #[test]
+    fn substring_kind_matches_anywhere_case_insensitively() {
+        let email = make_email("alerts@mail.Example.com", "Weekly DIGEST");
+        let from = kind_filter(FilterType::From, "example.com", PatternKind::Substring);
+        assert!(matches_filter(&email, &from));
+        let subject = kind_filter(FilterType::Subject, "digest", PatternKind::Substring);
+        assert!(matches_filter(&email, &subject));
+        // Regex metacharacters are literal in substring mode.
+        let literal = kind_filter(FilterType::Subject, "week.y", PatternKind::Substring);
+        assert!(!matches_filter(&email, &literal));
+    }
+
+    #[test]
+    fn glob_kind_on_subject_is_anchored() {
+        let email = make_email("a@b.com", "Invoice 2024-03");
+        let exact = |pattern| SplitFilter {
+            glob_match: Some(GlobMatch::Exact),
+            ..kind_filter(FilterType::Subject, pattern, PatternKind::Glob)
+        };
+        assert!(matches_filter(&email, &exact("invoice *")));
+        assert!(!matches_filter(&email, &exact("2024*")));
+    }
+
+    #[test]
+    fn keyword_filter_matches_whole_custom_keywords() {
+        let mut email = make_email("billing@shop.example", "Invoice");
+        email.keywords.insert("receipt".into(), true);
+        email.keywords.insert("$seen".into(), true);
+        let keyword = |pattern: &str| SplitFilter {
+            pattern_kind: None,
+            ..kind_filter(FilterType::Keyword, pattern, PatternKind::Glob)
+        };
+        assert!(matches_filter(&email, &keyword("Receipt")));
+        assert!(matches_filter(&email, &keyword("rec*")));
+        assert!(!matches_filter(&email, &keyword("rec")));
+        assert!(!matches_filter(&email, &keyword("$seen")));
+    }
+
+    #[test]
+    fn glob_without_match_matches_anywhere() {
+        let email = make_email("billing@shop.example", "Invoice 2024-03");
+        assert!(matches_filter(&email, &from_filter("shop.example")));
+        let filter = kind_filter(FilterType::Subject, "2024-0[1-6]", PatternKind::Glob);
+        assert!(matches_filter(&email, &filter));
+        let exact = SplitFilter {
+            glob_match: Some(GlobMatch::Exact),
+            ..from_filter("shop.example")
+        };
+        assert!(!matches_filter(&email, &exact));
+    }
+
+    #[test]
+    fn match_is_ignored_for_regex_and_substring() {
+        let email = make_email("a@b.com", "Weekly digest");
+        for kind in [PatternKind::Regex, PatternKind::Substring] {
+            let filter = SplitFilter {
+                glob_match: Some(GlobMatch::Exact),
+                ..kind_filter(FilterType::Subject, "digest", kind)
+            };
+            assert!(matches_filter(&email, &filter));
+        }
+    }
+
+    #[test]
+    fn migrate_pins_old_globs_to_exact() {
+        let json = r#"{"splits": [{"id": "a", "name": "A", "filters": [
+            {"type": "from", "pattern": "*@corp.com"},
+            {"type": "to", "pattern": "x.com", "match": "contains"},
+            {"type": "subject", "pattern": "digest"},
+            {"type": "subject", "pattern": "news*", "pattern_kind": "glob"}
+        ]}]}"#;
+        let config = SplitsFormat::Json.parse(json).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        let matches: Vec<_> = config.splits[0]
+            .filters
+            .iter()
+            .map(|f| f.glob_match)
+            .collect();
+        assert_eq!(
+            matches,
+            [
+                Some(GlobMatch::Exact),
+                Some(GlobMatch::Contains),
+                None,
+                Some(GlobMatch::Exact)
+            ]
+        );
+        // Old anchored behavior is kept: an address merely containing the
+        // pattern doesn't match.
+        let email = make_email("me@corp.com.evil", "Hi");
+        assert!(!matches_split(&email, &config.splits[0]));
+    }
+
+    #[test]
+    fn migrate_leaves_current_configs_alone() {
+        let json = r#"{"version": 1, "splits": [{"id": "a", "name": "A", "filters": [
+            {"type": "from", "pattern": "corp.com"}
+        ]}]}"#;
+        let mut config = SplitsFormat::Json.parse(json).unwrap();
+        assert_eq!(config.splits[0].filters[0].glob_match, None);
+        assert!(!migrate(&mut config));
+        assert_eq!(SplitsConfig::default().version, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn regex_kind_on_from_matches_addresses() {
+        let email = make_email("bot+123@github.com", "x");
+        let filter = kind_filter(FilterType::From, r"^bot\+\d+@", PatternKind::Regex);
+        assert!(matches_filter(&email, &filter));
+    }
+
+    #[test]
+    fn validate_rejects_bad_regex_with_error_text() {
+        let err = validate_filter(&subject_filter("[bracket")).unwrap_err();
+        assert!(err.contains("invalid regex '[bracket'"), "{err}");
+        let err =
+            validate_filter(&kind_filter(FilterType::From, "(", PatternKind::Regex)).unwrap_err();
+        assert!(err.contains("invalid regex"), "{err}");
+    }
+
+    #[test]
+    fn validate_accepts_globs_substrings_and_calendar() {
+        assert!(validate_filter(&from_filter("*@[weird")).is_ok());
+        assert!(
+            validate_filter(&kind_filter(
+                FilterType::Subject,
+                "[x",
+                PatternKind::Substring
+            ))
+            .is_ok()
+        );
+        let calendar = SplitFilter {
+            filter_type: FilterType::Calendar,
+            ..subject_filter("")
+        };
+        assert!(validate_filter(&calendar).is_ok());
+        assert!(validate_filter(&from_filter("")).is_err());
+    }
+
+    #[test]
+    fn validate_split_names_the_offending_split() {
+        let mut split = tagged_split("news", "*@x.com", None);
+        split.filters.push(subject_filter("(unclosed"));
+        let err = validate_split(&split).unwrap_err();
+        assert!(err.starts_with("split 'news':"), "{err}");
+    }
+
+    #[test]
+    fn split_action_round_trips_and_is_validated() {
+        let split: SplitInbox = serde_json::from_value(serde_json::json!({
+            "id": "deals", "name": "Deals",
+            "filters": [{"type": "from", "pattern": "*@shop.example"}],
+            "action": {"type": "move", "mailbox_id": "m1"}
+        }))
+        .unwrap();
+        assert!(validate_split(&split).is_ok());
+        let json = serde_json::to_value(&split).unwrap();
+        assert_eq!(
+            json["action"],
+            serde_json::json!({"type": "move", "mailbox_id": "m1"})
+        );
+        assert!(json.get("extra").is_none() && split.extra.is_empty());
+        let config = SplitsConfig {
+            version: CONFIG_VERSION,
+            splits: vec![split.clone()],
+            extra: Default::default(),
+        };
+        let toml = to_toml(&config, None).unwrap();
+        let back: SplitsConfig = toml_edit::de::from_str(&toml).unwrap();
+        assert_eq!(back.splits[0].action, split.action);
+
+        let plain = tagged_split("news", "*@x.com", None);
+        assert!(
+            serde_json::to_value(&plain)
+                .unwrap()
+                .get("action")
+                .is_none()
+        );
+
+        let mut no_target = split.clone();
+        no_target.action = Some(RuleAction::Move {
+            mailbox_id: " ".into(),
+        });
+        assert!(
+            validate_split(&no_target)
+                .unwrap_err()
+                .contains("mailbox_id")
+        );
+        let mut no_filters = split;
+        no_filters.filters.clear();
+        assert!(validate_split(&no_filters).is_err());
+    }
+
+    #[test]
+    fn pattern_kind_serializes_lowercase_and_is_omitted_when_unset() {
+        let json = serde_json::to_value(kind_filter(
+            FilterType::Subject,
+            "x",
+            PatternKind::Substring,
+        ))
+        .unwrap();
+        assert_eq!(json["pattern_kind"], "substring");
+        let legacy = serde_json::to_value(subject_filter("x")).unwrap();
+        assert!(legacy.get("pattern_kind").is_none());
+        let parsed: SplitFilter =
+            serde_json::from_str(r#"{"type": "from", "pattern": "x", "pattern_kind": "regex"}"#)
+                .unwrap();
+        assert_eq!(parsed.effective_kind(), PatternKind::Regex);
+        assert!(parsed.extra.is_empty());
+    }
+
     #[test]
     fn subject_filter_no_match() {
         let email = make_email("sender@example.com", "Nothing relevant here");
@@ -450,6 +1375,9 @@ mod tests {
             filter_type: FilterType::Calendar,
             pattern: String::new(),
             name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         };
         assert!(matches_filter(&email, &filter));
     }
@@ -461,6 +1389,9 @@ mod tests {
             filter_type: FilterType::Calendar,
             pattern: String::new(),
             name: None,
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         };
         assert!(!matches_filter(&email, &filter));
     }
@@ -475,6 +1406,9 @@ mod tests {
             filter_type: FilterType::Header,
             pattern: "calendar".into(),
             name: Some("Content-Type".into()),
+            pattern_kind: None,
+            glob_match: None,
+            extra: Default::default(),
         };
         assert!(matches_filter(&email, &filter));
     }
@@ -494,6 +1428,8 @@ mod tests {
             ],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         assert!(matches_split(&email, &split));
     }
@@ -511,6 +1447,8 @@ mod tests {
             ],
             match_mode: MatchMode::All,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         assert!(!matches_split(&email, &split));
     }
@@ -531,7 +1469,11 @@ mod tests {
                 filters: vec![from_filter("*@calendar.google.com")],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let result = filter_by_split(emails, "cal", &config);
         assert_eq!(result.len(), 1);
@@ -552,7 +1494,11 @@ mod tests {
                 filters: vec![from_filter("*@calendar.google.com")],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let result = filter_by_split(emails, "primary", &config);
         assert_eq!(result.len(), 1);
@@ -569,6 +1515,8 @@ mod tests {
                 tagged_split("gmail", "*@gmail.com", Some("gmail")),
                 tagged_split("calendar", "*@cal.test", None),
             ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let ids: Vec<String> = config
             .scoped_to(Some("aristoi"))
@@ -586,6 +1534,8 @@ mod tests {
                 tagged_split("aristoi", "*@aristoi.ai", Some("aristoi")),
                 tagged_split("calendar", "*@cal.test", None),
             ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         assert_eq!(config.scoped_to(None).splits.len(), 2);
     }
@@ -598,6 +1548,8 @@ mod tests {
                 tagged_split("old", "*@old.test", Some("deleted-account")),
                 tagged_split("calendar", "*@cal.test", None),
             ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let scoped = config.scoped_to(Some("gmail"));
         assert_eq!(scoped.splits.len(), 1);
@@ -613,12 +1565,68 @@ mod tests {
         let emails = vec![make_email_with_to("alice@x.com", "matt@gmail.com", &[])];
         let config = SplitsConfig {
             splits: vec![tagged_split("gmail", "*@gmail.com", Some("gmail"))],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let scoped = config.scoped_to(Some("aristoi"));
         let primary = filter_by_split(emails, "primary", &scoped);
         assert_eq!(primary.len(), 1);
     }
 
+    // --- CompiledSplits ---
+
+    #[test]
+    fn compiled_counts_agree_with_one_off_matching() {
+        let config = SplitsConfig {
+            splits: vec![
+                tagged_split("corp", "*@corp.com", None),
+                SplitInbox {
+                    filters: vec![
+                        subject_filter("^invoice #\\d+"),
+                        kind_filter(FilterType::From, "billing", PatternKind::Substring),
+                    ],
+                    ..tagged_split("bills", "", None)
+                },
+            ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
+        };
+        let emails = vec![
+            make_email_with_to("a@x.com", "Me@Corp.com", &[]),
+            make_email("Billing@shop.com", "Receipt"),
+            make_email("a@x.com", "Invoice #42"),
+            make_email("a@x.com", "Hello"),
+        ];
+        let compiled = CompiledSplits::new(&config);
+        let counts = compiled.counts(&emails);
+        for split in &config.splits {
+            let naive = emails.iter().filter(|e| matches_split(e, split)).count() as u32;
+            assert_eq!(counts[&split.id], naive, "split {}", split.id);
+        }
+        assert_eq!(counts["corp"], 1);
+        assert_eq!(counts["bills"], 2);
+        assert_eq!(compiled.filter(emails, "primary").len(), 1);
+    }
+
+    #[test]
+    fn compiled_scoped_to_follows_config_scoping() {
+        let config = SplitsConfig {
+            splits: vec![
+                tagged_split("mine", "*@a.com", Some("aristoi")),
+                tagged_split("theirs", "*@b.com", Some("gmail")),
+                tagged_split("shared", "*@c.com", None),
+            ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
+        };
+        let compiled = CompiledSplits::new(&config);
+        let scoped = compiled.scoped_to(Some("aristoi"));
+        assert!(scoped.contains("mine") && scoped.contains("shared"));
+        assert!(!scoped.contains("theirs"));
+        assert!(compiled.scoped_to(None).contains("theirs"));
+        assert!(CompiledSplits::default().is_empty());
+    }
+
     // --- matches_any_split ---
 
     #[test]
@@ -632,7 +1640,11 @@ mod tests {
                 filters: vec![from_filter("*@calendar.google.com")],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         assert!(matches_any_split(&email, &config));
     }
@@ -744,7 +1756,11 @@ mod tests {
                 filters: vec![],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         save_splits(&config, &path).unwrap();
         let content = std::fs::read_to_string(&path).unwrap();
@@ -764,10 +1780,17 @@ mod tests {
                     filter_type: FilterType::Header,
                     pattern: "calendar".into(),
                     name: Some("Content-Type".into()),
+                    pattern_kind: None,
+                    glob_match: None,
+                    extra: Default::default(),
                 }],
                 match_mode: MatchMode::All,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         save_splits(&config, &path).unwrap();
         let loaded = load_splits(&path, None);
@@ -779,6 +1802,239 @@ mod tests {
         );
     }
 
+    // --- TOML config ---
+
+    const COMMENTED_TOML: &str = r#"# Hand-maintained splits.
+
+# Work mail
+[[splits]]
+id = "work"
+name = "Work" # shown in the tab row
+match_mode = "any"
+owner = "me" # not a field this version knows
+
+[[splits.filters]]
+type = "from"
+pattern = "*@corp.example"
+
+# Newsletters
+[[splits]]
+id = "news"
+name = "News"
+
+[[splits.filters]]
+type = "subject"
+pattern = "digest"
+"#;
+
+    #[test]
+    fn toml_sibling_takes_precedence_over_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("splits.json");
+        std::fs::write(&json_path, r#"{"splits": [{"id": "json", "name": "J"}]}"#).unwrap();
+        assert_eq!(load_splits(&json_path, None).splits[0].id, "json");
+
+        std::fs::write(dir.path().join("splits.toml"), COMMENTED_TOML).unwrap();
+        let config = load_splits(&json_path, None);
+        assert_eq!(config.splits.len(), 2);
+        assert_eq!(config.splits[0].id, "work");
+        assert_eq!(config.splits[0].filters[0].pattern, "*@corp.example");
+    }
+
+    #[test]
+    fn try_load_invalid_toml_returns_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("splits.toml");
+        std::fs::write(&path, "[[splits]\nid = ").unwrap();
+        let err = try_load_splits(&path).expect_err("should reject invalid TOML");
+        assert!(err.contains("TOML parse failed"), "{err}");
+    }
+
+    #[test]
+    fn toml_save_preserves_comments_and_unknown_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("splits.json");
+        let toml_path = dir.path().join("splits.toml");
+        std::fs::write(&toml_path, COMMENTED_TOML).unwrap();
+
+        let mut config = load_splits(&json_path, None);
+        assert_eq!(config.splits[0].extra["owner"], "me");
+        config.splits[0].name = "Day job".into();
+        save_splits(&config, &json_path).unwrap();
+
+        assert!(
+            !json_path.exists(),
+            "TOML is primary; JSON must not be written"
+        );
+        let written = std::fs::read_to_string(&toml_path).unwrap();
+        assert!(
+            written.starts_with("# Hand-maintained splits.\n\nversion = 1\n\n# Work mail\n"),
+            "{written}"
+        );
+        assert!(written.contains("match = \"exact\""), "{written}");
+        assert!(written.contains("# Work mail"), "{written}");
+        assert!(
+            written.contains(r#"name = "Day job" # shown in the tab row"#),
+            "{written}"
+        );
+        assert!(
+            written.contains(r#"owner = "me" # not a field this version knows"#),
+            "{written}"
+        );
+        assert!(written.contains("# Newsletters"), "{written}");
+        let reloaded = load_splits(&json_path, None);
+        assert_eq!(reloaded.splits[0].name, "Day job");
+        assert_eq!(reloaded.splits[1].filters[0].pattern, "digest");
+    }
+
+    #[test]
+    fn toml_reorder_and_delete_move_comments_with_their_split() {
+        let mut config = SplitsFormat::Toml.parse(COMMENTED_TOML).unwrap();
+        config.splits.reverse();
+        let reordered = to_toml(&config, Some(COMMENTED_TOML)).unwrap();
+        let news = reordered.find("# Newsletters").unwrap();
+        let work = reordered.find("# Work mail").unwrap();
+        assert!(news < work, "{reordered}");
+        let parsed = SplitsFormat::Toml.parse(&reordered).unwrap();
+        assert_eq!(parsed.splits[0].id, "news");
+        assert_eq!(parsed.splits[0].filters[0].pattern, "digest");
+        assert_eq!(parsed.splits[1].filters[0].pattern, "*@corp.example");
+
+        config.splits.retain(|s| s.id == "work");
+        let deleted = to_toml(&config, Some(COMMENTED_TOML)).unwrap();
+        assert!(!deleted.contains("# Newsletters"), "{deleted}");
+        assert!(!deleted.contains("digest"), "{deleted}");
+        assert!(deleted.contains("# Work mail"), "{deleted}");
+    }
+
+    #[test]
+    fn toml_save_appends_new_split() {
+        let mut config = SplitsFormat::Toml.parse(COMMENTED_TOML).unwrap();
+        config
+            .splits
+            .push(tagged_split("gh", "*@github.com", Some("acct")));
+        let written = to_toml(&config, Some(COMMENTED_TOML)).unwrap();
+        let parsed = SplitsFormat::Toml.parse(&written).unwrap();
+        assert_eq!(parsed.splits.len(), 3);
+        assert_eq!(parsed.splits[2].id, "gh");
+        assert_eq!(parsed.splits[2].account.as_deref(), Some("acct"));
+        assert!(written.contains("# Newsletters"), "{written}");
+    }
+
+    #[test]
+    fn json_and_toml_convert_both_ways() {
+        let json = r#"{
+            "splits": [{
+                "id": "cal",
+                "name": "Calendar",
+                "icon": "📅",
+                "filters": [{"type": "calendar", "pattern": ""}],
+                "match_mode": "all",
+                "account": "acct",
+                "color": "blue"
+            }],
+            "theme": "dark"
+        }"#;
+        let from_json = SplitsFormat::Json.parse(json).unwrap();
+        let toml = render(&from_json, SplitsFormat::Toml).unwrap();
+        assert!(toml.contains("[[splits.filters]]"), "{toml}");
+        let from_toml = SplitsFormat::Toml.parse(&toml).unwrap();
+        assert_eq!(
+            render(&from_toml, SplitsFormat::Json).unwrap(),
+            render(&from_json, SplitsFormat::Json).unwrap()
+        );
+        assert_eq!(from_toml.extra["theme"], "dark");
+        assert_eq!(from_toml.splits[0].extra["color"], "blue");
+    }
+
+    #[test]
+    fn json_save_preserves_unknown_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("splits.json");
+        std::fs::write(
+            &path,
+            r#"{"splits": [{"id": "a", "name": "A", "color": "red",
+                "filters": [{"type": "from", "pattern": "x", "note": "n"}]}],
+                "version": 2}"#,
+        )
+        .unwrap();
+        let config = load_splits(&path, None);
+        save_splits(&config, &path).unwrap();
+        let raw: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["version"], 2);
+        assert_eq!(raw["splits"][0]["color"], "red");
+        assert_eq!(raw["splits"][0]["filters"][0]["note"], "n");
+    }
+
+    // --- SplitsStore ---
+
+    #[test]
+    fn store_reloads_external_edits_only_when_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("splits.json");
+        std::fs::write(&path, r#"{"splits": [{"id": "a", "name": "A"}]}"#).unwrap();
+        let store = SplitsStore::new(path.clone(), None);
+        assert_eq!(store.current().splits[0].id, "a");
+        assert!(!store.reload_if_changed(), "nothing changed yet");
+
+        std::fs::write(&path, r#"{"splits": [{"id": "bb", "name": "B"}]}"#).unwrap();
+        assert!(store.reload_if_changed());
+        assert_eq!(store.current().splits[0].id, "bb");
+        assert!(store.compiled().contains("bb"), "matchers swap with config");
+        assert!(!store.compiled().contains("a"));
+        assert!(!store.reload_if_changed());
+
+        // A TOML file appearing beside the JSON is a change too.
+        std::fs::write(dir.path().join("splits.toml"), COMMENTED_TOML).unwrap();
+        assert!(store.reload_if_changed());
+        assert_eq!(store.current().splits[0].id, "work");
+    }
+
+    #[test]
+    fn store_keeps_previous_config_when_edit_does_not_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("splits.json");
+        std::fs::write(&path, r#"{"splits": [{"id": "a", "name": "A"}]}"#).unwrap();
+        let store = SplitsStore::new(path.clone(), None);
+
+        std::fs::write(&path, r#"{"splits": [{"id": "a", "#).unwrap();
+        assert!(!store.reload_if_changed());
+        assert_eq!(store.current().splits[0].id, "a");
+    }
+
+    #[test]
+    fn store_save_is_not_seen_as_external_edit() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SplitsStore::new(dir.path().join("splits.json"), None);
+        let mut config = store.load_fresh();
+        config.splits.push(tagged_split("x", "*@x.com", None));
+        store.save(&config).unwrap();
+        assert_eq!(store.current().splits[0].id, "x");
+        assert!(!store.reload_if_changed());
+    }
+
+    #[test]
+    fn store_env_override_disables_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("splits.json");
+        let env = r#"{"splits": [{"id": "env", "name": "Env"}]}"#;
+        let store = SplitsStore::new(path.clone(), Some(env.into()));
+        std::fs::write(&path, r#"{"splits": [{"id": "file", "name": "F"}]}"#).unwrap();
+        assert!(!store.reload_if_changed());
+        assert_eq!(store.current().splits[0].id, "env");
+    }
+
+    #[test]
+    fn store_publish_notifies_subscribers() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SplitsStore::new(dir.path().join("splits.json"), None);
+        let mut rx = store.subscribe();
+        assert_eq!(store.publish(), 1);
+        assert_eq!(rx.try_recv().unwrap(), 1);
+        assert_eq!(store.version(), 1);
+    }
+
     #[test]
     fn save_load_roundtrip() {
         let dir = tempfile::tempdir().unwrap();
@@ -792,6 +2048,8 @@ mod tests {
                     filters: vec![from_filter("*@example.com")],
                     match_mode: MatchMode::Any,
                     account: None,
+                    action: None,
+                    extra: Default::default(),
                 },
                 SplitInbox {
                     id: "b".into(),
@@ -800,8 +2058,12 @@ mod tests {
                     filters: vec![subject_filter("test")],
                     match_mode: MatchMode::All,
                     account: None,
+                    action: None,
+                    extra: Default::default(),
                 },
             ],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         save_splits(&config, &path).unwrap();
         let loaded = load_splits(&path, None);
@@ -959,7 +2221,11 @@ mod tests {
                 filters: vec![from_filter("*@example.com")],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         save_splits(&existing, &path).unwrap();
 
@@ -1013,6 +2279,8 @@ mod tests {
             filters: vec![to_filter("*@company.onmicrosoft.com")],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         assert!(matches_split(&email, &split));
     }
@@ -1029,6 +2297,8 @@ mod tests {
             filters: vec![to_filter("*@company.onmicrosoft.com")],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         assert!(!matches_split(&email, &split));
     }
@@ -1047,7 +2317,11 @@ mod tests {
                 filters: vec![to_filter("*@company.onmicrosoft.com")],
                 match_mode: MatchMode::Any,
                 account: None,
+                action: None,
+                extra: Default::default(),
             }],
+            version: CONFIG_VERSION,
+            extra: Default::default(),
         };
         let primary = filter_by_split(emails, "primary", &config);
         assert_eq!(primary.len(), 1);
@@ -0,0 +1,386 @@
+//! Namespaced document storage for persisted state.
+//!
+//! Each subsystem so far owns a file in the config dir (`splits.json`,
+//! `snoozed.json`, ...) with its own load/save pair. [`DocumentStore`] is
+//! the common shape for new ones: opaque documents addressed by a
+//! [`Namespace`] (a subsystem, optionally scoped to one account) and a
+//! key. Per-account state goes under [`Namespace::account`] so two
+//! accounts can't collide on a key and removing an account can drop its
+//! documents in one place.
+//!
+//! [`FileStore`] is the default: one file per document under
+//! `store/<subsystem>/` (or `store/<subsystem>/accounts/<account>/`),
+//! written atomically. [`SqliteStore`] keeps every document in one
+//! `store.sqlite` table and is selected with `SUPERVILLAIN_STORAGE=sqlite`.
+//! [`get_json`] and [`put_json`] cover the common serde case.
+
+use crate::error::Error;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Which [`DocumentStore`] backs `AppState::store`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    File,
+    Sqlite,
+}
+
+/// Open the configured store under `dir` (`store/` or `store.sqlite`). A
+/// database that can't be opened falls back to files with a warning
+/// rather than failing startup.
+pub fn open(backend: Backend, dir: &Path) -> std::sync::Arc<dyn DocumentStore> {
+    if backend == Backend::Sqlite {
+        let path = dir.join("store.sqlite");
+        match SqliteStore::open(&path) {
+            Ok(store) => return std::sync::Arc::new(store),
+            Err(e) => tracing::warn!("{}: {e}; using file storage", path.display()),
+        }
+    }
+    std::sync::Arc::new(FileStore::new(dir.join("store")))
+}
+
+/// Where a document lives: a subsystem, optionally scoped to one account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    pub subsystem: &'static str,
+    pub account: Option<String>,
+}
+
+impl Namespace {
+    /// State shared by every account.
+    pub fn global(subsystem: &'static str) -> Self {
+        Self {
+            subsystem,
+            account: None,
+        }
+    }
+
+    /// State belonging to `account`.
+    pub fn account(subsystem: &'static str, account: &str) -> Self {
+        Self {
+            subsystem,
+            account: Some(account.to_string()),
+        }
+    }
+}
+
+/// Persistence for namespaced documents. Keys and namespace parts must be
+/// plain names (no path separators, not starting with `.`) so every
+/// backend can store them as-is.
+pub trait DocumentStore: Send + Sync {
+    fn get(&self, ns: &Namespace, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    fn put(&self, ns: &Namespace, key: &str, bytes: &[u8]) -> Result<(), Error>;
+    /// Remove a document; a missing one is not an error.
+    fn delete(&self, ns: &Namespace, key: &str) -> Result<(), Error>;
+    /// Keys in `ns`, sorted.
+    fn list(&self, ns: &Namespace) -> Result<Vec<String>, Error>;
+}
+
+/// Deserialize the document at `key`; None when it doesn't exist.
+pub fn get_json<T: DeserializeOwned>(
+    store: &dyn DocumentStore,
+    ns: &Namespace,
+    key: &str,
+) -> Result<Option<T>, Error> {
+    match store.get(ns, key)? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn put_json<T: Serialize>(
+    store: &dyn DocumentStore,
+    ns: &Namespace,
+    key: &str,
+    value: &T,
+) -> Result<(), Error> {
+    store.put(ns, key, &serde_json::to_vec_pretty(value)?)
+}
+
+fn check_name(what: &str, name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', '\0'])
+        || name.len() > 200
+    {
+        return Err(Error::BadRequest(format!(
+            "invalid storage {what}: {name:?}"
+        )));
+    }
+    Ok(())
+}
+
+fn check(ns: &Namespace, key: &str) -> Result<(), Error> {
+    check_name("subsystem", ns.subsystem)?;
+    if let Some(account) = &ns.account {
+        check_name("account", account)?;
+    }
+    check_name("key", key)
+}
+
+// =============================================================================
+// FileStore — <root>/<subsystem>/[accounts/<account>/]<key>.json
+// =============================================================================
+
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn dir(&self, ns: &Namespace) -> PathBuf {
+        let dir = self.root.join(ns.subsystem);
+        match &ns.account {
+            Some(account) => dir.join("accounts").join(account),
+            None => dir,
+        }
+    }
+
+    fn path(&self, ns: &Namespace, key: &str) -> PathBuf {
+        self.dir(ns).join(format!("{key}.json"))
+    }
+}
+
+impl DocumentStore for FileStore {
+    fn get(&self, ns: &Namespace, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        check(ns, key)?;
+        match std::fs::read(self.path(ns, key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, ns: &Namespace, key: &str, bytes: &[u8]) -> Result<(), Error> {
+        check(ns, key)?;
+        crate::accounts::atomic_write_bytes(&self.path(ns, key), bytes, /* secret */ false)?;
+        Ok(())
+    }
+
+    fn delete(&self, ns: &Namespace, key: &str) -> Result<(), Error> {
+        check(ns, key)?;
+        match std::fs::remove_file(self.path(ns, key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self, ns: &Namespace) -> Result<Vec<String>, Error> {
+        check(ns, "list")?;
+        let entries = match std::fs::read_dir(self.dir(ns)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys: Vec<String> = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                // Skips atomic_write_bytes' dot-prefixed temp files too.
+                let key = name.strip_suffix(".json")?;
+                (!key.starts_with('.')).then(|| key.to_string())
+            })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+// =============================================================================
+// SqliteStore — one `documents` table
+// =============================================================================
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        subsystem TEXT NOT NULL,
+        account TEXT NOT NULL,
+        key TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        body BLOB NOT NULL,
+        PRIMARY KEY (subsystem, account, key)
+    );
+";
+
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) the database at `path`.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let store = Self::init(Connection::open(path).map_err(sqlite_error)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+        }
+        Ok(store)
+    }
+
+    /// An in-memory store, for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::init(Connection::open_in_memory().expect("in-memory sqlite")).expect("store schema")
+    }
+
+    fn init(conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch(SCHEMA).map_err(sqlite_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, Error> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        f(&conn).map_err(sqlite_error)
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::Internal(format!("storage: {e}"))
+}
+
+/// The `account` column: global documents use the empty string, which
+/// `check_name` rejects as an account name, so the two can't collide.
+fn account_column(ns: &Namespace) -> &str {
+    ns.account.as_deref().unwrap_or("")
+}
+
+impl DocumentStore for SqliteStore {
+    fn get(&self, ns: &Namespace, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        check(ns, key)?;
+        self.with(|conn| {
+            conn.query_row(
+                "SELECT body FROM documents WHERE subsystem = ?1 AND account = ?2 AND key = ?3",
+                params![ns.subsystem, account_column(ns), key],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+    }
+
+    fn put(&self, ns: &Namespace, key: &str, bytes: &[u8]) -> Result<(), Error> {
+        check(ns, key)?;
+        self.with(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO documents (subsystem, account, key, updated_at, body)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    ns.subsystem,
+                    account_column(ns),
+                    key,
+                    chrono::Utc::now().timestamp(),
+                    bytes
+                ],
+            )
+            .map(|_| ())
+        })
+    }
+
+    fn delete(&self, ns: &Namespace, key: &str) -> Result<(), Error> {
+        check(ns, key)?;
+        self.with(|conn| {
+            conn.execute(
+                "DELETE FROM documents WHERE subsystem = ?1 AND account = ?2 AND key = ?3",
+                params![ns.subsystem, account_column(ns), key],
+            )
+            .map(|_| ())
+        })
+    }
+
+    fn list(&self, ns: &Namespace) -> Result<Vec<String>, Error> {
+        check(ns, "list")?;
+        self.with(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT key FROM documents WHERE subsystem = ?1 AND account = ?2 ORDER BY key",
+            )?;
+            stmt.query_map(params![ns.subsystem, account_column(ns)], |row| row.get(0))?
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The behavior both backends must share.
+    fn exercise(store: &dyn DocumentStore) {
+        let global = Namespace::global("prefs");
+        let work = Namespace::account("prefs", "work");
+        let home = Namespace::account("prefs", "home");
+
+        assert_eq!(store.get(&global, "ui").unwrap(), None);
+        assert!(store.list(&work).unwrap().is_empty());
+
+        put_json(store, &global, "ui", &serde_json::json!({"dense": true})).unwrap();
+        put_json(store, &work, "ui", &serde_json::json!({"dense": false})).unwrap();
+        put_json(store, &work, "filters", &vec!["a"]).unwrap();
+
+        let ui: serde_json::Value = get_json(store, &global, "ui").unwrap().unwrap();
+        assert_eq!(ui["dense"], true);
+        let ui: serde_json::Value = get_json(store, &work, "ui").unwrap().unwrap();
+        assert_eq!(ui["dense"], false);
+        assert_eq!(store.list(&work).unwrap(), ["filters", "ui"]);
+        assert_eq!(store.list(&global).unwrap(), ["ui"]);
+        assert!(store.list(&home).unwrap().is_empty());
+
+        store.put(&work, "ui", b"{}").unwrap();
+        assert_eq!(store.get(&work, "ui").unwrap().unwrap(), b"{}");
+        store.delete(&work, "ui").unwrap();
+        store.delete(&work, "ui").unwrap();
+        assert_eq!(store.list(&work).unwrap(), ["filters"]);
+
+        for bad in ["", "../x", "a/b", ".hidden"] {
+            assert!(matches!(
+                store.put(&global, bad, b"x"),
+                Err(Error::BadRequest(_))
+            ));
+        }
+        assert!(store.get(&Namespace::account("prefs", ".."), "ui").is_err());
+    }
+
+    #[test]
+    fn file_store_namespaces_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&FileStore::new(dir.path().to_path_buf()));
+        assert!(dir.path().join("prefs/accounts/work/filters.json").exists());
+        assert!(dir.path().join("prefs/ui.json").exists());
+    }
+
+    #[test]
+    fn sqlite_store_namespaces_documents() {
+        exercise(&SqliteStore::in_memory());
+    }
+
+    #[test]
+    fn open_falls_back_to_files() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory where the database should be can't be opened.
+        std::fs::create_dir(dir.path().join("store.sqlite")).unwrap();
+        let store = open(Backend::Sqlite, dir.path());
+        store.put(&Namespace::global("prefs"), "ui", b"{}").unwrap();
+        assert!(dir.path().join("store/prefs/ui.json").exists());
+    }
+
+    #[test]
+    fn sqlite_store_persists_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.sqlite");
+        let ns = Namespace::account("prefs", "work");
+        SqliteStore::open(&path)
+            .unwrap()
+            .put(&ns, "ui", b"{}")
+            .unwrap();
+        let reopened = SqliteStore::open(&path).unwrap();
+        assert_eq!(reopened.get(&ns, "ui").unwrap().unwrap(), b"{}");
+    }
+}
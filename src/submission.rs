@@ -0,0 +1,255 @@
+//! Recipient checks run before any provider sees an outgoing message.
+//!
+//! [`prepare`] is applied by `provider::send_email`, so every send path
+//! (compose, invites, RSVPs, the digest) gets the same envelope: at least
+//! one recipient, every address a plain `local@domain`, no address twice
+//! across to/cc/bcc, and no more than [`MAX_RECIPIENTS`] in total. An
+//! individual send checks its whole list with [`prepare_individual`]
+//! first; each copy has one recipient, so the cap doesn't apply.
+
+use crate::error::Error;
+use crate::types::{Attachment, EmailSubmission};
+use std::collections::HashSet;
+
+/// Upper bound on to+cc+bcc after dedupe. Providers cap envelopes too,
+/// each differently; failing here gives one clear 400 instead of a
+/// provider-specific error.
+pub const MAX_RECIPIENTS: usize = 100;
+
+/// `sub` with recipients trimmed and deduplicated case-insensitively.
+/// The first occurrence wins, in to, cc, bcc order, so an address in both
+/// To and Bcc stays visible in To. An empty bcc list becomes `None`.
+pub fn prepare(sub: &EmailSubmission) -> Result<EmailSubmission, Error> {
+    prepare_capped(sub, Some(MAX_RECIPIENTS))
+}
+
+/// [`prepare`] for a list that goes out as one copy per `to` recipient,
+/// so any number of them is fine.
+pub fn prepare_individual(sub: &EmailSubmission) -> Result<EmailSubmission, Error> {
+    prepare_capped(sub, None)
+}
+
+fn prepare_capped(sub: &EmailSubmission, cap: Option<usize>) -> Result<EmailSubmission, Error> {
+    let mut seen = HashSet::new();
+    let mut keep = |list: &[String]| -> Result<Vec<String>, Error> {
+        let mut out = Vec::with_capacity(list.len());
+        for raw in list {
+            let addr = raw.trim();
+            if !is_valid_address(addr) {
+                return Err(Error::BadRequest(format!(
+                    "Invalid recipient address: {raw:?}"
+                )));
+            }
+            if seen.insert(addr.to_lowercase()) {
+                out.push(addr.to_string());
+            }
+        }
+        Ok(out)
+    };
+    let to = keep(&sub.to)?;
+    let cc = keep(&sub.cc)?;
+    let bcc = keep(sub.bcc.as_deref().unwrap_or_default())?;
+
+    let total = to.len() + cc.len() + bcc.len();
+    if total == 0 {
+        return Err(Error::BadRequest(
+            "At least one recipient is required".into(),
+        ));
+    }
+    if let Some(cap) = cap
+        && total > cap
+    {
+        return Err(Error::BadRequest(format!(
+            "Too many recipients: {total} (limit {cap})"
+        )));
+    }
+    Ok(EmailSubmission {
+        to,
+        cc,
+        bcc: if bcc.is_empty() { None } else { Some(bcc) },
+        ..sub.clone()
+    })
+}
+
+/// `sub` with `original`, the attachments of `sub.forward_of`, appended
+/// after its own. One the client already listed (same blob id) isn't
+/// attached twice. `forward_of` is cleared, so resolving again is a no-op.
+pub fn with_forwarded(sub: EmailSubmission, original: Vec<Attachment>) -> EmailSubmission {
+    let mut attachments = sub.attachments;
+    for att in original {
+        if !attachments.iter().any(|a| a.blob_id == att.blob_id) {
+            attachments.push(att);
+        }
+    }
+    EmailSubmission {
+        attachments,
+        forward_of: None,
+        ..sub
+    }
+}
+
+/// Whether `addr` is an RFC 5321 mailbox in its common form: a dot-atom
+/// local part of at most 64 bytes, `@`, and a hostname of dot-separated
+/// LDH labels (or an `[address literal]`). Quoted local parts and display
+/// names are rejected; the compose UI only produces bare addresses.
+pub fn is_valid_address(addr: &str) -> bool {
+    let Some((local, domain)) = addr.rsplit_once('@') else {
+        return false;
+    };
+    valid_local_part(local) && valid_domain(domain)
+}
+
+fn valid_local_part(local: &str) -> bool {
+    const SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+    !local.is_empty()
+        && local.len() <= 64
+        && local.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || SPECIALS.contains(c) || !c.is_ascii())
+        })
+}
+
+fn valid_domain(domain: &str) -> bool {
+    if let Some(literal) = domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+        return !literal.is_empty()
+            && literal
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':'));
+    }
+    !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || !c.is_ascii())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(to: &[&str], cc: &[&str], bcc: &[&str]) -> EmailSubmission {
+        let list = |xs: &[&str]| xs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        EmailSubmission {
+            to: list(to),
+            cc: list(cc),
+            subject: "Hi".into(),
+            text_body: "Hello".into(),
+            bcc: if bcc.is_empty() {
+                None
+            } else {
+                Some(list(bcc))
+            },
+            html_body: None,
+            in_reply_to: None,
+            references: None,
+            attachments: vec![],
+            calendar_ics: None,
+            forward_of: None,
+        }
+    }
+
+    #[test]
+    fn with_forwarded_appends_unlisted_attachments() {
+        let att = |blob_id: &str| Attachment {
+            blob_id: blob_id.into(),
+            name: format!("{blob_id}.pdf"),
+            mime_type: "application/pdf".into(),
+            size: 1,
+            sniffed_mime_type: None,
+            mime_mismatch: false,
+            content_id: None,
+        };
+        let sub = EmailSubmission {
+            attachments: vec![att("synth:new"), att("b1")],
+            forward_of: Some("e1".into()),
+            ..submission(&["a@example.com"], &[], &[])
+        };
+        let out = with_forwarded(sub, vec![att("b1"), att("b2")]);
+        let ids: Vec<_> = out.attachments.iter().map(|a| a.blob_id.as_str()).collect();
+        assert_eq!(ids, ["synth:new", "b1", "b2"]);
+        assert_eq!(out.forward_of, None);
+    }
+
+    #[test]
+    fn dedupes_across_fields_keeping_first() {
+        let sub = submission(
+            &["Ann@Example.com", "bob@example.com"],
+            &["ann@example.com", "cy@example.com"],
+            &[" BOB@example.com ", "dee@example.com"],
+        );
+        let out = prepare(&sub).unwrap();
+        assert_eq!(out.to, vec!["Ann@Example.com", "bob@example.com"]);
+        assert_eq!(out.cc, vec!["cy@example.com"]);
+        assert_eq!(out.bcc, Some(vec!["dee@example.com".to_string()]));
+        assert_eq!(out.subject, "Hi");
+    }
+
+    #[test]
+    fn bcc_emptied_by_dedupe_becomes_none() {
+        let out = prepare(&submission(&["a@x.com"], &[], &["A@x.com"])).unwrap();
+        assert_eq!(out.bcc, None);
+    }
+
+    #[test]
+    fn rejects_no_recipients() {
+        let err = prepare(&submission(&[], &[], &[])).unwrap_err();
+        assert!(
+            matches!(err, Error::BadRequest(ref m) if m.contains("At least one")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_address() {
+        for bad in [
+            "",
+            "bob",
+            "bob@",
+            "@x.com",
+            "a b@x.com",
+            "a..b@x.com",
+            "Bob <b@x.com>",
+            "b@-x.com",
+            "b@x..com",
+        ] {
+            let err = prepare(&submission(&["ok@x.com"], &[bad], &[])).unwrap_err();
+            assert!(matches!(err, Error::BadRequest(_)), "{bad:?} -> {err}");
+        }
+    }
+
+    #[test]
+    fn accepts_common_address_forms() {
+        for good in [
+            "a@x",
+            "first.last+tag@mail.example.co.uk",
+            "o'neil@x.com",
+            "u@[192.0.2.1]",
+            "ü@bücher.de",
+        ] {
+            assert!(is_valid_address(good), "{good}");
+        }
+    }
+
+    #[test]
+    fn caps_total_after_dedupe() {
+        let many: Vec<String> = (0..=MAX_RECIPIENTS)
+            .map(|i| format!("u{i}@x.com"))
+            .collect();
+        let refs: Vec<&str> = many.iter().map(String::as_str).collect();
+        assert!(prepare(&submission(&refs, &[], &[])).is_err());
+        // Duplicates don't count against the cap.
+        let dupes = vec!["a@x.com"; MAX_RECIPIENTS + 5];
+        assert!(prepare(&submission(&dupes, &[], &[])).is_ok());
+        // One copy each: a big group still goes out individually.
+        let individual = prepare_individual(&submission(&refs, &[], &[])).unwrap();
+        assert_eq!(individual.to.len(), MAX_RECIPIENTS + 1);
+    }
+}
@@ -0,0 +1,237 @@
+//! `{{variables}}` in outgoing mail, expanded when the message goes out.
+//!
+//! A signature (or anything typed into compose) can say
+//! `Hi {{recipient_first_name}},` or `Sent {{weekday}}`;
+//! `deliver_submission` runs [`expand_submission`]
+//! over the subject and both bodies right before handing the message to
+//! the provider, after any undo window, so drafts keep the variables.
+//! Individual sends expand once per recipient.
+//!
+//! | Variable | Value |
+//! |---|---|
+//! | `{{date}}` | Today in the configured primary timezone, e.g. `16 October 2026` |
+//! | `{{weekday}}` | Today's day name, e.g. `Friday` |
+//! | `{{recipient_first_name}}` | First word of the first To recipient's address book name; empty when unknown |
+//! | `{{my_name}}` | Name of the sending identity; empty when unknown |
+//!
+//! Anything else in braces is left alone, so quoted mail that happens to
+//! contain `{{...}}` goes out as written. A backslash keeps a known
+//! variable literal: `\{{date}}` sends `{{date}}`. Values put into the
+//! HTML body are escaped.
+
+use crate::types::EmailSubmission;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// The values one outgoing copy expands to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vars {
+    pub date: String,
+    pub weekday: String,
+    pub recipient_first_name: String,
+    pub my_name: String,
+}
+
+impl Vars {
+    /// `recipient` is a `to` address (`submission::prepare` only lets bare
+    /// addresses through); `contact_names` maps lowercased addresses to
+    /// address book names.
+    pub fn new(
+        now: DateTime<Tz>,
+        recipient: Option<&str>,
+        contact_names: &HashMap<String, String>,
+        my_name: &str,
+    ) -> Self {
+        Self {
+            date: now.format("%-d %B %Y").to_string(),
+            weekday: now.format("%A").to_string(),
+            recipient_first_name: recipient
+                .map(|r| recipient_first_name(r, contact_names))
+                .unwrap_or_default(),
+            my_name: my_name.trim().to_string(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "date" => Some(&self.date),
+            "weekday" => Some(&self.weekday),
+            "recipient_first_name" => Some(&self.recipient_first_name),
+            "my_name" => Some(&self.my_name),
+            _ => None,
+        }
+    }
+}
+
+/// Whether anything in the submission could need expanding.
+pub fn has_vars(sub: &EmailSubmission) -> bool {
+    sub.subject.contains(OPEN)
+        || sub.text_body.contains(OPEN)
+        || sub.html_body.as_deref().is_some_and(|h| h.contains(OPEN))
+}
+
+pub fn expand_submission(sub: &EmailSubmission, vars: &Vars) -> EmailSubmission {
+    EmailSubmission {
+        subject: expand(&sub.subject, vars, false),
+        text_body: expand(&sub.text_body, vars, false),
+        html_body: sub.html_body.as_deref().map(|h| expand(h, vars, true)),
+        ..sub.clone()
+    }
+}
+
+/// Replace known variables in `text`; `html` escapes the values.
+pub fn expand(text: &str, vars: &Vars, html: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        let escaped = rest[..start].ends_with('\\');
+        let before = if escaped {
+            &rest[..start - 1]
+        } else {
+            &rest[..start]
+        };
+        out.push_str(before);
+        let after_open = &rest[start + OPEN.len()..];
+        let known = after_open.find(CLOSE).and_then(|end| {
+            let name = after_open[..end].trim();
+            vars.get(name).map(|value| (value, end))
+        });
+        match known {
+            Some((value, end)) => {
+                let consumed = OPEN.len() + end + CLOSE.len();
+                if escaped {
+                    out.push_str(&rest[start..start + consumed]);
+                } else if html {
+                    out.push_str(&escape_html(value));
+                } else {
+                    out.push_str(value);
+                }
+                rest = &rest[start + consumed..];
+            }
+            None => {
+                // Not ours: keep it, backslash included, and move past
+                // the opening braces.
+                if escaped {
+                    out.push('\\');
+                }
+                out.push_str(OPEN);
+                rest = after_open;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// First word of the recipient's address book name (`Last, First` reads
+/// as First), else empty.
+fn recipient_first_name(recipient: &str, contact_names: &HashMap<String, String>) -> String {
+    let name = contact_names
+        .get(&recipient.trim().to_ascii_lowercase())
+        .map(String::as_str)
+        .unwrap_or_default();
+    let given = match name.split_once(',') {
+        Some((_, first)) => first,
+        None => name,
+    };
+    given
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn vars(recipient: Option<&str>) -> Vars {
+        let now = chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2026, 10, 16, 9, 30, 0)
+            .unwrap();
+        let mut names = HashMap::new();
+        names.insert("bob@x.example".to_string(), "Bob Stone".to_string());
+        Vars::new(now, recipient, &names, "Ann Lee")
+    }
+
+    #[test]
+    fn expands_known_variables() {
+        let v = vars(Some("bob@x.example"));
+        assert_eq!(
+            expand(
+                "Hi {{recipient_first_name}}, happy {{ weekday }}! {{date}} -- {{my_name}}",
+                &v,
+                false
+            ),
+            "Hi Bob, happy Friday! 16 October 2026 -- Ann Lee"
+        );
+    }
+
+    #[test]
+    fn unknown_and_escaped_variables_survive() {
+        let v = vars(None);
+        assert_eq!(
+            expand("{{unknown}} \\{{date}} {{date", &v, false),
+            "{{unknown}} {{date}} {{date"
+        );
+        assert_eq!(expand("a \\{{x}} b", &v, false), "a \\{{x}} b");
+        assert_eq!(expand("Hi {{recipient_first_name}}", &v, false), "Hi ");
+    }
+
+    #[test]
+    fn html_values_are_escaped() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2026, 1, 1, 0, 0, 0)
+            .unwrap();
+        let v = Vars::new(now, None, &HashMap::new(), "<b>Ann</b> & co");
+        assert_eq!(
+            expand("<p>{{my_name}}</p>", &v, true),
+            "<p>&lt;b&gt;Ann&lt;/b&gt; &amp; co</p>"
+        );
+    }
+
+    #[test]
+    fn first_name_from_the_address_book() {
+        let names = HashMap::from([
+            ("bob@x.example".to_string(), "Bob Stone".to_string()),
+            ("c@x.example".to_string(), "Diaz, Carla".to_string()),
+        ]);
+        assert_eq!(recipient_first_name("c@x.example", &names), "Carla");
+        assert_eq!(recipient_first_name(" Bob@X.example", &names), "Bob");
+        assert_eq!(recipient_first_name("nobody@x.example", &names), "");
+    }
+
+    #[test]
+    fn submission_expands_subject_and_bodies() {
+        let sub = EmailSubmission {
+            to: vec!["bob@x.example".into()],
+            cc: vec![],
+            subject: "{{weekday}} notes".into(),
+            text_body: "Hi {{recipient_first_name}}".into(),
+            bcc: None,
+            html_body: Some("<p>Hi {{recipient_first_name}}</p>".into()),
+            in_reply_to: None,
+            references: None,
+            attachments: vec![],
+            calendar_ics: None,
+            forward_of: None,
+        };
+        assert!(has_vars(&sub));
+        let out = expand_submission(&sub, &vars(Some("bob@x.example")));
+        assert_eq!(out.subject, "Friday notes");
+        assert_eq!(out.text_body, "Hi Bob");
+        assert_eq!(out.html_body.as_deref(), Some("<p>Hi Bob</p>"));
+    }
+}
@@ -0,0 +1,369 @@
+//! Whole-conversation export to PDF, for archiving contract and legal
+//! threads.
+//!
+//! `GET /api/threads/{id}/pdf` lays the thread's messages out oldest
+//! first: From/To/Cc/Date/Subject, then the body. A message with no text
+//! body has its HTML run through [`sanitize::render`] and flattened to
+//! text, so nothing remote is fetched and no markup reaches the file.
+//! Everything is set in Courier, one of the standard PDF fonts: nothing is
+//! embedded and wrapping at [`COLUMNS`] is exact. Characters outside
+//! WinAnsi (Latin-1 plus typographic quotes, dashes and the euro sign)
+//! print as `?`.
+
+use crate::compose;
+use crate::sanitize;
+use crate::types::Email;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+/// A4 in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const FONT_SIZE: f32 = 10.0;
+const LEADING: f32 = 12.0;
+/// Courier glyphs are 0.6 em wide: 80 columns fit between the margins.
+pub const COLUMNS: usize = 80;
+/// Lines per page; the page number sits in the bottom margin.
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2.0 * MARGIN) / LEADING) as usize;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    text: String,
+    bold: bool,
+}
+
+/// The conversation as a PDF. `emails` are in reading order; dates are
+/// shown in `tz`.
+pub fn render(emails: &[Email], tz: Tz, exported_at: DateTime<Utc>) -> Vec<u8> {
+    let lines = layout(emails, tz, exported_at);
+    let pages: Vec<&[Line]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(LINES_PER_PAGE).collect()
+    };
+    write_pdf(&thread_subject(emails), &pages)
+}
+
+/// Download name for the export: the subject's letters, digits, `-` and
+/// `_`, plus `.pdf`.
+pub fn filename(emails: &[Email]) -> String {
+    let cleaned: String = thread_subject(emails)
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let stem: String = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(60)
+        .collect();
+    match stem.trim() {
+        "" => "thread.pdf".into(),
+        stem => format!("{stem}.pdf"),
+    }
+}
+
+fn thread_subject(emails: &[Email]) -> String {
+    emails
+        .first()
+        .map(|e| e.subject.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("(no subject)")
+        .to_string()
+}
+
+fn layout(emails: &[Email], tz: Tz, exported_at: DateTime<Utc>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    push_wrapped(&mut lines, &thread_subject(emails), true);
+    let count = match emails.len() {
+        1 => "1 message".to_string(),
+        n => format!("{n} messages"),
+    };
+    push_wrapped(
+        &mut lines,
+        &format!(
+            "{count}, exported {}",
+            exported_at.with_timezone(&tz).format(DATE_FORMAT)
+        ),
+        false,
+    );
+    for email in emails {
+        lines.push(blank());
+        push_wrapped(&mut lines, &"=".repeat(COLUMNS), false);
+        push_header(&mut lines, "From", &compose::display_list(&email.from));
+        push_header(&mut lines, "To", &compose::display_list(&email.to));
+        if !email.cc.is_empty() {
+            push_header(&mut lines, "Cc", &compose::display_list(&email.cc));
+        }
+        push_header(
+            &mut lines,
+            "Date",
+            &email
+                .received_at
+                .with_timezone(&tz)
+                .format(DATE_FORMAT)
+                .to_string(),
+        );
+        push_header(&mut lines, "Subject", &email.subject);
+        if !email.attachments.is_empty() {
+            let names: Vec<&str> = email.attachments.iter().map(|a| a.name.as_str()).collect();
+            push_header(&mut lines, "Attachments", &names.join(", "));
+        }
+        lines.push(blank());
+        for paragraph in body_text(email).lines() {
+            push_wrapped(&mut lines, paragraph, false);
+        }
+    }
+    lines
+}
+
+const DATE_FORMAT: &str = "%a, %-d %b %Y %H:%M %Z";
+
+/// The text body, or the sanitized HTML body flattened to text.
+fn body_text(email: &Email) -> String {
+    if let Some(text) = email.text_body.as_deref().filter(|t| !t.trim().is_empty()) {
+        return text.replace("\r\n", "\n").trim_end().to_string();
+    }
+    match email.html_body.as_deref() {
+        Some(html) => compose::html_to_text(&sanitize::render(html).html),
+        None => email.preview.clone(),
+    }
+}
+
+fn blank() -> Line {
+    Line {
+        text: String::new(),
+        bold: false,
+    }
+}
+
+fn push_header(lines: &mut Vec<Line>, name: &str, value: &str) {
+    push_wrapped(lines, &format!("{name}: {value}"), true);
+}
+
+/// `text` as lines of at most [`COLUMNS`] characters, broken at spaces
+/// where possible and mid-word where not.
+fn push_wrapped(lines: &mut Vec<Line>, text: &str, bold: bool) {
+    let text = text.replace('\t', "    ");
+    let mut rest: &str = text.trim_end();
+    if rest.is_empty() {
+        lines.push(Line {
+            text: String::new(),
+            bold,
+        });
+        return;
+    }
+    while !rest.is_empty() {
+        let Some((limit, _)) = rest.char_indices().nth(COLUMNS) else {
+            lines.push(Line {
+                text: rest.to_string(),
+                bold,
+            });
+            break;
+        };
+        let cut = match rest[..limit].rfind(' ') {
+            Some(space) if space > 0 => space,
+            _ => limit,
+        };
+        lines.push(Line {
+            text: rest[..cut].trim_end().to_string(),
+            bold,
+        });
+        rest = rest[cut..].trim_start_matches(' ');
+    }
+}
+
+fn write_pdf(title: &str, pages: &[&[Line]]) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let regular_id = Ref::new(3);
+    let bold_id = Ref::new(4);
+    let info_id = Ref::new(5);
+    let first_page = 6;
+    let page_ids: Vec<Ref> = (0..pages.len())
+        .map(|i| Ref::new(first_page + 2 * i as i32))
+        .collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(pages.len() as i32);
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Courier"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Courier-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.document_info(info_id)
+        .title(TextStr(title))
+        .producer(TextStr("supervillain"));
+
+    for (i, (lines, &page_id)) in pages.iter().zip(&page_ids).enumerate() {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        let mut resources = page.resources();
+        resources
+            .fonts()
+            .pair(REGULAR, regular_id)
+            .pair(BOLD, bold_id);
+        resources.finish();
+        page.finish();
+
+        let mut content = Content::new();
+        let mut y = PAGE_HEIGHT - MARGIN - FONT_SIZE;
+        for line in lines.iter() {
+            if !line.text.is_empty() {
+                show(&mut content, &line.text, line.bold, MARGIN, y);
+            }
+            y -= LEADING;
+        }
+        let footer = format!("Page {} of {}", i + 1, pages.len());
+        let footer_x = PAGE_WIDTH - MARGIN - footer.len() as f32 * FONT_SIZE * 0.6;
+        show(&mut content, &footer, false, footer_x, MARGIN / 2.0);
+        pdf.stream(content_id, &content.finish());
+    }
+    pdf.finish()
+}
+
+fn show(content: &mut Content, text: &str, bold: bool, x: f32, y: f32) {
+    content.begin_text();
+    content.set_font(if bold { BOLD } else { REGULAR }, FONT_SIZE);
+    content.next_line(x, y);
+    content.show(Str(&win_ansi(text)));
+    content.end_text();
+}
+
+/// `text` in WinAnsiEncoding; anything it can't represent becomes `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '™' => 0x99,
+            _ => b'?',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(subject: &str, text: Option<&str>, html: Option<&str>) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": "e1", "blob_id": "", "thread_id": "t1", "mailbox_ids": {},
+            "keywords": {}, "received_at": "2026-03-02T15:04:00Z", "subject": subject,
+            "from": [{"name": "Ann Lee", "email": "ann@x.example"}],
+            "to": [{"name": null, "email": "bob@x.example"}], "cc": [], "preview": "",
+            "has_attachment": false, "size": 0, "text_body": text, "html_body": html,
+            "has_calendar": false, "attachments": [],
+        }))
+        .unwrap()
+    }
+
+    fn texts(lines: &[Line]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_splits_long_words() {
+        let mut lines = Vec::new();
+        let words = "word ".repeat(20);
+        push_wrapped(&mut lines, &words, false);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.text.chars().count() <= COLUMNS));
+        assert!(lines[0].text.ends_with("word"));
+
+        let mut lines = Vec::new();
+        push_wrapped(&mut lines, &"x".repeat(COLUMNS + 5), false);
+        assert_eq!(lines[0].text.len(), COLUMNS);
+        assert_eq!(lines[1].text, "xxxxx");
+    }
+
+    #[test]
+    fn layout_lists_headers_then_sanitized_body() {
+        let emails = vec![
+            email("Contract", Some("Please sign.\nThanks"), None),
+            email(
+                "Re: Contract",
+                None,
+                Some("<p>Signed</p><script>alert(1)</script><img src=\"https://t.example/p.gif\">"),
+            ),
+        ];
+        let lines = layout(
+            &emails,
+            chrono_tz::UTC,
+            "2026-03-03T10:00:00Z".parse().unwrap(),
+        );
+        let text = texts(&lines);
+        assert_eq!(text[0], "Contract");
+        assert_eq!(text[1], "2 messages, exported Tue, 3 Mar 2026 10:00 UTC");
+        assert!(text.contains(&"From: Ann Lee <ann@x.example>"));
+        assert!(text.contains(&"Date: Mon, 2 Mar 2026 15:04 UTC"));
+        assert!(text.contains(&"Please sign."));
+        assert!(text.contains(&"Signed"));
+        assert!(
+            !text
+                .iter()
+                .any(|l| l.contains("alert") || l.contains("t.example"))
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.bold && l.text == "Subject: Re: Contract")
+        );
+    }
+
+    #[test]
+    fn renders_a_pdf_with_one_page_per_chunk() {
+        let long = "line\n".repeat(LINES_PER_PAGE * 2);
+        let pdf = render(
+            &[email("Long", Some(&long), None)],
+            chrono_tz::UTC,
+            Utc::now(),
+        );
+        assert!(pdf.starts_with(b"%PDF-"));
+        let body = String::from_utf8_lossy(&pdf);
+        assert!(body.contains("/Count 3"));
+        assert!(body.contains("(Page 3 of 3)"));
+    }
+
+    #[test]
+    fn non_win_ansi_characters_become_question_marks() {
+        assert_eq!(win_ansi("café – “ok” 日本"), b"caf\xe9 \x96 \x93ok\x94 ??");
+    }
+
+    #[test]
+    fn filename_comes_from_the_subject() {
+        assert_eq!(
+            filename(&[email("Re: NDA / v2?", None, None)]),
+            "Re NDA v2.pdf"
+        );
+        assert_eq!(filename(&[email("***", None, None)]), "thread.pdf");
+        assert_eq!(filename(&[]), "no subject.pdf");
+    }
+}
@@ -1,13 +1,13 @@
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 // =============================================================================
 // Email types
 // =============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EmailAddress {
     pub name: Option<String>,
     pub email: String,
@@ -43,6 +43,104 @@ pub struct Email {
     /// its threading (kata wm57); Gmail/Outlook leave it None in v1.
     #[serde(default)]
     pub in_reply_to: Option<String>,
+    /// Message-ID header, in `<…>` form. Read on body fetches so
+    /// `compose` can thread replies; None when the provider didn't give it.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// References header ids, oldest first, in `<…>` form. JMAP and Gmail
+    /// only; Graph doesn't expose the header on a message fetch.
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// Reply-To addresses; empty when the header is absent.
+    #[serde(default)]
+    pub reply_to: Vec<EmailAddress>,
+    /// Text/HTML body parts as fetched, with their server-side size and
+    /// whether the value in `text_body`/`html_body` was cut at the fetch
+    /// limit. JMAP only; Gmail/Outlook return whole bodies and leave it empty.
+    #[serde(default)]
+    pub body_parts: Vec<BodyPart>,
+    /// Raw `List-Unsubscribe` value (`<mailto:…>, <https://…>`) when the
+    /// message carries one. Only populated on body fetches.
+    #[serde(default)]
+    pub list_unsubscribe: Option<String>,
+    /// Spam-filter headers the receiving server stamped, when any were
+    /// present. JMAP and Gmail only; Graph doesn't expose them.
+    #[serde(default)]
+    pub spam: Option<SpamInfo>,
+}
+
+/// One text/html body part of a fetched email (JMAP `textBody`/`htmlBody`
+/// entry joined with its `bodyValues` truncation flag).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyPart {
+    pub part_id: String,
+    /// Blob holding the full decoded part — what `/body/full` streams.
+    pub blob_id: Option<String>,
+    /// "text" or "html" — which of the email's bodies this part feeds.
+    pub kind: String,
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub charset: Option<String>,
+    pub size: i64,
+    pub is_truncated: bool,
+}
+
+/// Spam-scoring headers: SpamAssassin's `X-Spam-Score`/`X-Spam-Status`,
+/// Rspamd's `X-Rspamd-Score`, and Fastmail's `X-Spam-Known-Sender`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpamInfo {
+    /// `X-Spam-Score`, else the `score=` of `X-Spam-Status`, else
+    /// `X-Rspamd-Score`.
+    pub score: Option<f64>,
+    /// `X-Spam-Status` as sent (`No, score=1.2 required=5.0 tests=…`).
+    pub status: Option<String>,
+    /// `X-Spam-Known-Sender` as sent (`yes; in-addressbook`).
+    pub known_sender: Option<String>,
+}
+
+impl SpamInfo {
+    /// Build from `header`, which looks up one of the four headers above
+    /// by its wire-case name. None when the message carries none of them.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Option<Self> {
+        let text = |name: &str| {
+            header(name)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+        let status = text("X-Spam-Status");
+        let score = text("X-Spam-Score")
+            .as_deref()
+            .and_then(leading_number)
+            .or_else(|| status.as_deref().and_then(status_score))
+            .or_else(|| text("X-Rspamd-Score").as_deref().and_then(leading_number));
+        let info = Self {
+            score,
+            status,
+            known_sender: text("X-Spam-Known-Sender"),
+        };
+        (info != Self::default()).then_some(info)
+    }
+}
+
+/// The number a score header starts with (`"5.3 (+++++)"` → 5.3).
+fn leading_number(value: &str) -> Option<f64> {
+    value
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+}
+
+/// `score=` (or the older `hits=`) out of an `X-Spam-Status` value.
+fn status_score(status: &str) -> Option<f64> {
+    status
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .find_map(|tok| tok.strip_prefix("score=").or(tok.strip_prefix("hits=")))
+        .and_then(leading_number)
 }
 
 impl Email {
@@ -50,9 +148,134 @@ impl Email {
         !self.keywords.contains_key("$seen")
     }
 
+    /// True when any body part was cut at the fetch limit.
+    pub fn is_truncated(&self) -> bool {
+        self.body_parts.iter().any(|p| p.is_truncated)
+    }
+
+    /// Total size of the message's body parts in bytes. Falls back to the
+    /// length of the fetched bodies when no part metadata is available.
+    pub fn body_size(&self) -> i64 {
+        if self.body_parts.is_empty() {
+            let len = |b: &Option<String>| b.as_ref().map_or(0, |b| b.len() as i64);
+            return len(&self.text_body) + len(&self.html_body);
+        }
+        let mut seen = std::collections::HashSet::new();
+        self.body_parts
+            .iter()
+            .filter(|p| seen.insert(p.part_id.as_str()))
+            .map(|p| p.size)
+            .sum()
+    }
+
     pub fn is_flagged(&self) -> bool {
         self.keywords.contains_key("$flagged")
     }
+
+    /// Color of the flag, None when unflagged or a plain star.
+    pub fn flag_color(&self) -> Option<FlagColor> {
+        if !self.is_flagged() {
+            return None;
+        }
+        FlagColor::from_keywords(&self.keywords)
+    }
+
+    /// User keywords (Fastmail labels such as `receipt`), sorted; system
+    /// `$` keywords like `$seen` are left out.
+    pub fn custom_keywords(&self) -> Vec<&str> {
+        let mut keywords: Vec<&str> = self
+            .keywords
+            .iter()
+            .filter(|(k, set)| **set && is_custom_keyword(k))
+            .map(|(k, _)| k.as_str())
+            .collect();
+        keywords.sort_unstable();
+        keywords
+    }
+}
+
+/// Whether `keyword` is a user keyword rather than a system (`$`, or IMAP
+/// `\`) one.
+pub fn is_custom_keyword(keyword: &str) -> bool {
+    !keyword.starts_with('$') && !keyword.starts_with('\\')
+}
+
+/// Characters RFC 8621 keeps out of keywords, beyond spaces and controls.
+const KEYWORD_FORBIDDEN: &[char] = &['(', ')', '{', ']', '%', '*', '"', '\\'];
+
+/// `keyword` trimmed and lowercased (JMAP keywords are case-insensitive),
+/// or why it can't be a user keyword: 1-255 printable ASCII characters
+/// without `( ) { ] % * " \`, and not a `$` system keyword, which have
+/// their own endpoints.
+pub fn normalize_keyword(keyword: &str) -> Result<String, String> {
+    let keyword = keyword.trim();
+    if keyword.is_empty() || keyword.len() > 255 {
+        return Err("keyword must be 1-255 characters".into());
+    }
+    if !is_custom_keyword(keyword) {
+        return Err(format!("'{keyword}' is a system keyword"));
+    }
+    if let Some(c) = keyword
+        .chars()
+        .find(|c| !c.is_ascii_graphic() || KEYWORD_FORBIDDEN.contains(c))
+    {
+        return Err(format!("keyword '{keyword}' can't contain {c:?}"));
+    }
+    Ok(keyword.to_ascii_lowercase())
+}
+
+/// Flag colors as Apple Mail writes them and Fastmail shows them: a color
+/// index spread over three `$MailFlagBit` keywords next to `$flagged`.
+/// Index 0 (no bits) is the plain star, which Apple Mail draws red.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagColor {
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+/// The keywords holding a flag color's index, lowest bit first.
+pub const FLAG_COLOR_KEYWORDS: [&str; 3] = ["$MailFlagBit0", "$MailFlagBit1", "$MailFlagBit2"];
+
+impl FlagColor {
+    pub const ALL: [FlagColor; 6] = [
+        Self::Orange,
+        Self::Yellow,
+        Self::Green,
+        Self::Blue,
+        Self::Purple,
+        Self::Gray,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|c| *c == self)
+            .unwrap_or_default()
+            + 1
+    }
+
+    /// Decode the color bits in `keywords`, compared case-insensitively
+    /// since JMAP keywords are.
+    pub fn from_keywords(keywords: &HashMap<String, bool>) -> Option<Self> {
+        let index: usize = FLAG_COLOR_KEYWORDS
+            .iter()
+            .enumerate()
+            .filter(|(_, bit)| keywords.keys().any(|k| k.eq_ignore_ascii_case(bit)))
+            .fold(0, |acc, (i, _)| acc | 1 << i);
+        index.checked_sub(1).and_then(|i| Self::ALL.get(i).copied())
+    }
+
+    /// Each color keyword and whether `color` sets it; `None` clears all
+    /// three, leaving a plain star.
+    pub fn keyword_bits(color: Option<Self>) -> [(&'static str, bool); 3] {
+        let index = color.map_or(0, Self::index);
+        std::array::from_fn(|i| (FLAG_COLOR_KEYWORDS[i], index & (1 << i) != 0))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +292,11 @@ pub struct EmailSubmission {
     pub attachments: Vec<Attachment>,
     #[serde(skip)]
     pub calendar_ics: Option<String>,
+    /// Id of an email in the same account whose attachments go out with
+    /// this one, referenced where they are instead of uploaded again
+    /// (`provider::attach_forwarded`).
+    #[serde(default)]
+    pub forward_of: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +319,60 @@ pub struct Identity {
     pub name: String,
 }
 
+/// One header field as it appears in the message, in message order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Email ids changed since a provider state token — JMAP `Email/changes`,
+/// or its Gmail history equivalent. Serves `/api/changes`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmailChanges {
+    #[serde(alias = "oldState")]
+    pub old_state: String,
+    /// Pass back as `since` to continue from here.
+    #[serde(alias = "newState")]
+    pub new_state: String,
+    /// More changes are waiting past `new_state`; call again right away.
+    #[serde(alias = "hasMoreChanges", default)]
+    pub has_more_changes: bool,
+    #[serde(default)]
+    pub created: Vec<String>,
+    #[serde(default)]
+    pub updated: Vec<String>,
+    #[serde(default)]
+    pub destroyed: Vec<String>,
+}
+
+/// Where a windowed query starts: an absolute position, or `offset` rows
+/// from a known email (JMAP `anchor`/`anchorOffset`), which stays put when
+/// mail arrives above it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowStart {
+    Position(usize),
+    Anchor { id: String, offset: i64 },
+}
+
+/// One page of search results with the position bookkeeping a pager
+/// needs. Serves `/api/search`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPage {
+    pub ids: Vec<String>,
+    /// Zero-based index of the first id in the full result list.
+    pub position: usize,
+    /// Size of the full result list; `None` when the provider can't count
+    /// it without walking every page.
+    pub total: Option<usize>,
+    /// The server can report changes to this result list
+    /// (`Email/queryChanges`), so a client can update it in place.
+    pub can_calculate_changes: bool,
+    /// State of the result list, for `Email/queryChanges`.
+    pub query_state: Option<String>,
+}
+
 // =============================================================================
 // Attachment types
 // =============================================================================
@@ -101,6 +383,20 @@ pub struct Attachment {
     pub name: String,
     pub mime_type: String,
     pub size: i64,
+    /// Type recognized from the blob's magic bytes, once a download has
+    /// seen them (`provider_utils::sniff_mime_type`). Absent until then,
+    /// or when the bytes match nothing on the allowlist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sniffed_mime_type: Option<String>,
+    /// `sniffed_mime_type` disagrees with `mime_type`, e.g. a PDF sent as
+    /// `application/octet-stream`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mime_mismatch: bool,
+    /// Set on an outgoing attachment that the HTML body shows inline as
+    /// `cid:<content_id>` (a signature logo); sent in the
+    /// `multipart/related` part next to the HTML instead of as a download.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
 }
 
 /// Typed reference to attachment bytes, decoupled from the on-wire string
@@ -260,6 +556,60 @@ pub struct CalendarEvent {
     /// `get_email` sets it. Serialized as `isUpdate` (camelCase) for the client.
     #[serde(rename = "isUpdate", skip_deserializing)]
     pub is_update: bool,
+    /// Human-readable summary of what a revised invite (SEQUENCE > 0)
+    /// changes relative to the stored calendar copy, e.g. "Time moved from
+    /// 3pm to 4pm". Never set by `parse_ics`; only `get_email` fills it,
+    /// via `calendar::describe_changes`.
+    #[serde(skip_deserializing)]
+    pub changes: Vec<String>,
+    /// Warning when the sender's domain doesn't match the organizer's (the
+    /// replying attendee's, for a REPLY) — a common way to phish with a
+    /// forged invite. Never set by `parse_ics`; only `get_email` fills it,
+    /// via `calendar::organizer_mismatch`.
+    #[serde(rename = "organizerMismatch", skip_deserializing)]
+    pub organizer_mismatch: Option<String>,
+    /// The user's own answer to this invite, from the stored calendar copy
+    /// or, failing that, the account's `rsvp_log`. Never set by `parse_ics`;
+    /// only `get_email` and `rsvp` fill it, via `rsvp_log::my_response`.
+    #[serde(rename = "myResponse", skip_deserializing)]
+    pub my_response: Option<MyResponse>,
+    /// VALARM reminders carried by the event, in document order.
+    #[serde(default)]
+    pub alarms: Vec<Alarm>,
+    /// DTSTART was a `VALUE=DATE` (all-day event). `dtstart`/`dtend` then
+    /// hold UTC midnight of the floating date: writers must re-emit a DATE,
+    /// never that instant, or the day shifts for anyone west of UTC.
+    #[serde(rename = "allDay", default)]
+    pub all_day: bool,
+}
+
+/// An answer the user gave to an invite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MyResponse {
+    /// `ACCEPTED`, `TENTATIVE` or `DECLINED`.
+    pub status: String,
+    pub source: ResponseSource,
+    /// When it was sent from this app; unknown for calendar answers.
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseSource {
+    /// The PARTSTAT on the stored calendar event.
+    Calendar,
+    /// Recorded locally when the RSVP was sent.
+    Local,
+}
+
+/// One VALARM component. `trigger` is the raw TRIGGER value, e.g. `-PT15M`
+/// (relative to DTSTART) or an absolute `19980101T050000Z`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alarm {
+    pub action: String,
+    pub trigger: String,
+    pub description: Option<String>,
 }
 
 // =============================================================================
@@ -284,6 +634,32 @@ impl RsvpStatus {
             Self::Declined => "DECLINED",
         }
     }
+
+    /// Inverse of `as_ics_str` (case-insensitive). `None` for PARTSTAT
+    /// values that aren't a response, e.g. `NEEDS-ACTION` or `DELEGATED`.
+    pub fn from_ics_str(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "ACCEPTED" => Some(Self::Accepted),
+            "TENTATIVE" => Some(Self::Tentative),
+            "DECLINED" => Some(Self::Declined),
+            _ => None,
+        }
+    }
+}
+
+/// One attendee response applied to an event the user organizes, recorded
+/// when a `METHOD:REPLY` email updates the stored calendar copy. Served by
+/// `GET /api/calendar/replies`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedReply {
+    pub account: String,
+    pub email_id: String,
+    pub uid: String,
+    pub summary: String,
+    pub attendee: String,
+    pub status: String,
+    pub applied_at: DateTime<Utc>,
 }
 
 // =============================================================================
@@ -301,6 +677,92 @@ pub struct ParsedQuery {
     pub before: Option<NaiveDate>,
     pub after: Option<NaiveDate>,
     pub text: String,
+    /// `spamscore:` — matched locally against [`Email::spam`], never sent
+    /// to the provider.
+    #[serde(default)]
+    pub spam_score: Option<ScoreFilter>,
+    /// `priority:` — matched locally against the score `priority` assigns
+    /// at listing time.
+    #[serde(default)]
+    pub priority: Option<PriorityLevel>,
+    /// `-term` / `NOT term`: mail matching any of these is left out.
+    #[serde(default)]
+    pub exclude: Vec<ParsedQuery>,
+    /// `a OR b`: each group needs one of its alternatives to match.
+    #[serde(default)]
+    pub any_of: Vec<Vec<ParsedQuery>>,
+    /// `in:` / `label:` — mail must be in every one of these mailboxes.
+    #[serde(default)]
+    pub mailboxes: Vec<MailboxTerm>,
+    /// `larger:` / `size:` — more than this many bytes.
+    #[serde(default)]
+    pub larger: Option<u64>,
+    /// `smaller:` — fewer than this many bytes.
+    #[serde(default)]
+    pub smaller: Option<u64>,
+}
+
+/// A mailbox named in a query. The parser only fills in `name`;
+/// `search::resolve_mailboxes` looks it up before the query goes to a
+/// provider, and an unresolved term is left out of the provider filter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailboxTerm {
+    pub name: String,
+    #[serde(default)]
+    pub id: Option<String>,
+}
+
+/// Focused-inbox bucket of a message's [`Priority`] score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityLevel {
+    High,
+    Normal,
+    Low,
+}
+
+/// A list row's focused-inbox score (0–100) and its bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Priority {
+    pub score: u8,
+    pub level: PriorityLevel,
+    /// The sender's learned archive-vs-reply history decided the level,
+    /// not the heuristics.
+    pub learned: bool,
+}
+
+/// `spamscore:>5`, `spamscore:<=1.5`; a bare number means `=`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoreFilter {
+    pub op: Comparison,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl ScoreFilter {
+    /// Whether `spam` has a score passing this filter. Mail without a
+    /// score never matches.
+    pub fn matches(&self, spam: Option<&SpamInfo>) -> bool {
+        let Some(score) = spam.and_then(|s| s.score) else {
+            return false;
+        };
+        match self.op {
+            Comparison::Lt => score < self.value,
+            Comparison::Le => score <= self.value,
+            Comparison::Eq => score == self.value,
+            Comparison::Ge => score >= self.value,
+            Comparison::Gt => score > self.value,
+        }
+    }
 }
 
 impl ParsedQuery {
@@ -314,6 +776,20 @@ impl ParsedQuery {
             && self.before.is_none()
             && self.after.is_none()
             && self.text.is_empty()
+            && self.spam_score.is_none()
+            && self.priority.is_none()
+            && self.exclude.is_empty()
+            && self.any_of.is_empty()
+            && self.mailboxes.is_empty()
+            && self.larger.is_none()
+            && self.smaller.is_none()
+    }
+
+    /// Whether this query or any group inside it names a mailbox.
+    pub fn has_mailbox_terms(&self) -> bool {
+        !self.mailboxes.is_empty()
+            || self.exclude.iter().any(Self::has_mailbox_terms)
+            || self.any_of.iter().flatten().any(Self::has_mailbox_terms)
     }
 }
 
@@ -346,6 +822,8 @@ pub enum FilterType {
     Subject,
     Header,
     Calendar,
+    /// A user keyword (Fastmail label) on the message.
+    Keyword,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -356,12 +834,47 @@ pub enum MatchMode {
     All,
 }
 
+/// How a filter's `pattern` is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternKind {
+    /// Case-insensitive fnmatch-style glob (see `glob.rs`).
+    Glob,
+    /// Case-insensitive regex; must compile.
+    Regex,
+    /// Case-insensitive plain substring.
+    Substring,
+}
+
+/// How much of the text a glob pattern has to cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlobMatch {
+    /// The whole text, e.g. `example.com` matches only `example.com`.
+    Exact,
+    /// Any part of it, e.g. `example.com` matches `me@example.com`.
+    Contains,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SplitFilter {
     #[serde(rename = "type")]
     pub filter_type: FilterType,
     pub pattern: String,
     pub name: Option<String>,
+    /// `None` keeps the historical per-type behavior: globs for from/to,
+    /// a regex that falls back to substring for subject.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_kind: Option<PatternKind>,
+    /// Only consulted for globs. `None` means `contains`; configs written
+    /// before this existed are pinned to `exact` on load (see
+    /// `splits::migrate`).
+    #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
+    pub glob_match: Option<GlobMatch>,
+    /// Keys this version doesn't know, kept so a hand-edited config
+    /// round-trips through load/save unchanged.
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -378,12 +891,66 @@ pub struct SplitInbox {
     /// `None` = visible on every account.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub account: Option<String>,
+    /// Applied to new inbox mail the split matches by the background
+    /// filer (`rules::spawn_filer`). `None` keeps the split a plain view.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<RuleAction>,
+    /// Unknown keys, preserved on round-trip (see [`SplitFilter::extra`]).
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// What a rule does to a matching message; see `rules`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    Archive,
+    Trash,
+    MarkRead,
+    Flag,
+    Move { mailbox_id: String },
+}
+
+impl RuleAction {
+    /// Whether the action takes the message out of its mailbox, which
+    /// protected senders are exempt from.
+    pub fn moves(&self) -> bool {
+        matches!(self, Self::Archive | Self::Trash | Self::Move { .. })
+    }
+
+    /// Reject an action that can't run as written.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Move { mailbox_id } if mailbox_id.trim().is_empty() => {
+                Err("move needs a mailbox_id".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SplitsConfig {
+    /// Schema version; files without one are version 0 and get migrated
+    /// on load (see `splits::CONFIG_VERSION`).
+    #[serde(default)]
+    pub version: u32,
     #[serde(default)]
     pub splits: Vec<SplitInbox>,
+    /// Unknown top-level keys, preserved on round-trip.
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// An empty config is already current: there is nothing to migrate.
+impl Default for SplitsConfig {
+    fn default() -> Self {
+        Self {
+            version: crate::splits::CONFIG_VERSION,
+            splits: Vec::new(),
+            extra: BTreeMap::new(),
+        }
+    }
 }
 
 // =============================================================================
@@ -418,6 +985,21 @@ pub struct AccountRegistry {
     pub sessions: std::collections::HashMap<String, SessionLock>,
     pub account_configs: std::collections::BTreeMap<String, crate::accounts::AccountConfig>,
     pub default_account: String,
+    /// Global `default-reminder` from the config file. Not editable in-app
+    /// yet; carried here so `snapshot` writes it back instead of dropping it.
+    pub default_reminder: Option<String>,
+    /// Global `body-cache-mb`, carried for the same reason. Applied to the
+    /// prefetch cache once at startup.
+    pub body_cache_mb: Option<u64>,
+    /// Global `read-only`, carried for the same reason. Checked on every
+    /// request by `read_only::guard`.
+    pub read_only: bool,
+    /// Global `user-agent` / `http-header`, carried for the same reason.
+    /// Applied to `http_client` once at startup.
+    pub http: crate::http_client::HttpConfig,
+    /// Per-account `startup-mailbox` / `startup-split`, carried for the
+    /// same reason and served by `GET /api/preferences`.
+    pub startup: std::collections::BTreeMap<String, crate::accounts::StartupView>,
 }
 
 impl AccountRegistry {
@@ -430,7 +1012,12 @@ impl AccountRegistry {
             } else {
                 Some(self.default_account.clone())
             },
+            default_reminder: self.default_reminder.clone(),
+            body_cache_mb: self.body_cache_mb,
+            read_only: self.read_only,
+            http: self.http.clone(),
             accounts: self.account_configs.clone(),
+            startup: self.startup.clone(),
         }
     }
 }
@@ -441,13 +1028,98 @@ pub struct AppState {
     /// this lock; see `accounts::router` handlers for the pattern.
     pub accounts: tokio::sync::RwLock<AccountRegistry>,
     pub account_errors: tokio::sync::RwLock<Vec<AccountError>>,
-    pub splits_config_path: PathBuf,
+    /// In-memory splits config, hot-reloaded from `splits.json`/`.toml`.
+    pub splits: crate::splits::SplitsStore,
     pub timezone_config_path: PathBuf,
+    /// Named recipient groups (`groups.json`), expanded on send.
+    pub groups_config_path: PathBuf,
+    /// Reply identity rules (`identities.json`): per-account and
+    /// per-mailbox default From addresses.
+    pub identities_config_path: PathBuf,
+    /// Senders bulk archive paths must skip (`protected-senders.json`).
+    pub protected_senders_path: PathBuf,
+    /// Senders whose remote images load through the proxy by default
+    /// (`image-senders.json`); see `image_proxy`.
+    pub image_senders_path: PathBuf,
+    /// Daily digest settings (`digest.json`), read by the scheduler.
+    pub digest_config_path: PathBuf,
+    /// Auto-filed receipts (`receipts.json`), appended by the receipt scan.
+    pub receipts_path: PathBuf,
+    /// Package poller settings and carrier credentials (`packages.json`).
+    pub packages_config_path: PathBuf,
+    /// Tracking numbers seen in opened mail, with their last polled
+    /// status (`packages-tracked.json`).
+    pub packages_path: PathBuf,
+    /// Extra mailbox-name → role aliases (`mailbox-roles.json`), read when
+    /// a Fastmail account connects.
+    pub mailbox_roles_path: PathBuf,
+    /// Trash journal (`recently-deleted.json`): where each message trashed
+    /// through this server was, for restore.
+    pub recently_deleted_path: PathBuf,
+    /// Serializes trash-journal load→record→save across concurrent trashes.
+    pub recently_deleted_lock: tokio::sync::Mutex<()>,
+    /// Pending snoozes and their wake times (`snoozed.json`).
+    pub snoozed_path: PathBuf,
+    /// Serializes snooze-journal load→modify→save between the snooze
+    /// endpoints and the waker.
+    pub snoozed_lock: tokio::sync::Mutex<()>,
+    /// Serializes the per-sender archive/reply tallies' load→record→save;
+    /// see `priority`.
+    pub priority_lock: tokio::sync::Mutex<()>,
+    /// Serializes the newsletter read-rate document's load→note→save; see
+    /// `newsletters`.
+    pub newsletters_lock: tokio::sync::Mutex<()>,
+    /// Serializes the muted-threads document's load→change→save; see
+    /// `mute`.
+    pub muted_lock: tokio::sync::Mutex<()>,
+    /// Namespaced documents for subsystems that don't own a config file;
+    /// see `storage`.
+    pub store: std::sync::Arc<dyn crate::storage::DocumentStore>,
+    /// Confirm tokens for emptying Trash/Spam; see `purge`.
+    pub empty_confirmations: crate::purge::EmptyConfirmations,
+    /// Sends held for the undo window; see `undo_send`.
+    pub undo_send: crate::undo_send::UndoSendQueue,
+    /// Request timings for `/api/perf`; disabled unless `SUPERVILLAIN_PERF`.
+    pub perf: crate::perf::PerfStats,
+    /// Opt-in release check behind `/api/version`; see `update`.
+    pub update_check: crate::update::UpdateCheck,
+    /// Signature logos already uploaded; see `signature_image`.
+    pub signature_blobs: crate::signature_image::SignatureBlobs,
+    /// Guarded recipients and their TOTP secret (`send-guard.json`).
+    pub send_guard_path: PathBuf,
+    /// Command key remaps (`keybindings.json`); see `commands::Keymap`.
+    pub keybindings_path: PathBuf,
+    /// Work hours and recipient-night window for the send-time guard
+    /// (`send-hours.json`).
+    pub send_hours_path: PathBuf,
+    /// Synced CardDAV address books (`contacts.json`), per account.
+    pub contacts_path: PathBuf,
+    /// Serializes contacts-index load→replace→save across account syncs.
+    pub contacts_lock: tokio::sync::Mutex<()>,
+    /// In-progress compose windows (`compose-sessions.json`), shared
+    /// between devices.
+    pub compose_sessions_path: PathBuf,
+    /// Write lock and change events for `compose_sessions_path`.
+    pub compose_sessions: crate::compose_sessions::ComposeHub,
+    /// Downloaded attachment blobs on disk (`blob-cache/`).
+    pub blob_cache: crate::blob_cache::BlobCache,
+    /// Fetched emails on disk (`email-cache.sqlite`), synced through
+    /// `Email/changes` by the warmer.
+    pub email_cache: crate::cache::EmailCache,
+    /// Which accounts' caches are sealed, and the key; see `at_rest`.
+    pub at_rest: std::sync::Arc<crate::at_rest::AtRest>,
     /// Serializes timezone load→mutate→save so two concurrent settings
     /// writes can't lose-update each other. The value is unit because the
     /// authoritative state lives on disk; this lock just bracketizes the
     /// load-modify-store window.
     pub timezone_write_lock: tokio::sync::Mutex<()>,
+    /// Most recent attendee replies applied to the user's own events, newest
+    /// last, capped at `routes::REPLY_LOG_CAP`: the "what changed" audit
+    /// trail. Saved to `storage` on every change and loaded at startup; see
+    /// `calendar_replies`.
+    pub calendar_reply_log: std::sync::Mutex<std::collections::VecDeque<AppliedReply>>,
+    /// Latest one-time code seen in new mail, in memory only.
+    pub otp: crate::otp::OtpStore,
     pub config_path: PathBuf,
     pub tokens_dir: PathBuf,
     pub token_store: std::sync::Arc<dyn crate::platform::TokenStore>,
@@ -522,6 +1194,12 @@ mod tests {
             has_calendar: false,
             attachments: vec![],
             in_reply_to: None,
+            body_parts: Vec::new(),
+            list_unsubscribe: None,
+            message_id: None,
+            references: Vec::new(),
+            reply_to: Vec::new(),
+            spam: None,
         }
     }
 
@@ -538,6 +1216,26 @@ mod tests {
         assert!(!email.is_unread());
     }
 
+    #[test]
+    fn custom_keywords_skip_system_ones() {
+        let mut email = test_email();
+        for k in ["$seen", "$MailFlagBit0", "receipt", "acme"] {
+            email.keywords.insert(k.into(), true);
+        }
+        email.keywords.insert("stale".into(), false);
+        assert_eq!(email.custom_keywords(), ["acme", "receipt"]);
+    }
+
+    #[test]
+    fn normalize_keyword_lowercases_and_rejects_bad_ones() {
+        assert_eq!(normalize_keyword(" Client-Acme ").unwrap(), "client-acme");
+        assert!(normalize_keyword("").is_err());
+        assert!(normalize_keyword("$seen").is_err());
+        assert!(normalize_keyword("two words").is_err());
+        assert!(normalize_keyword("50%").is_err());
+        assert!(normalize_keyword("caf\u{e9}").is_err());
+    }
+
     #[test]
     fn email_is_flagged_when_flagged_keyword_present() {
         let mut email = test_email();
@@ -551,6 +1249,42 @@ mod tests {
         assert!(!email.is_flagged());
     }
 
+    fn body_part(part_id: &str, kind: &str, size: i64, is_truncated: bool) -> BodyPart {
+        BodyPart {
+            part_id: part_id.into(),
+            blob_id: Some(format!("blob-{part_id}")),
+            kind: kind.into(),
+            mime_type: format!("text/{}", if kind == "html" { "html" } else { "plain" }),
+            charset: None,
+            size,
+            is_truncated,
+        }
+    }
+
+    #[test]
+    fn email_body_size_sums_distinct_parts() {
+        let mut email = test_email();
+        // A text/plain part with no HTML alternative shows up in both
+        // textBody and htmlBody — count it once.
+        email.body_parts = vec![
+            body_part("1", "text", 100, false),
+            body_part("2", "html", 250, false),
+            body_part("1", "html", 100, false),
+        ];
+        assert_eq!(email.body_size(), 350);
+        assert!(!email.is_truncated());
+        email.body_parts[1].is_truncated = true;
+        assert!(email.is_truncated());
+    }
+
+    #[test]
+    fn email_body_size_falls_back_to_body_lengths() {
+        let mut email = test_email();
+        email.text_body = Some("hello".into());
+        email.html_body = Some("<p>hello</p>".into());
+        assert_eq!(email.body_size(), 17);
+    }
+
     #[test]
     fn email_serde_roundtrip() {
         let email = test_email();
@@ -603,6 +1337,7 @@ mod tests {
             references: Some(vec!["msg-100".into(), "msg-123".into()]),
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let json = serde_json::to_string(&sub).unwrap();
         let deserialized: EmailSubmission = serde_json::from_str(&json).unwrap();
@@ -623,6 +1358,7 @@ mod tests {
             references: None,
             attachments: vec![],
             calendar_ics: None,
+            forward_of: None,
         };
         let json = serde_json::to_string(&sub).unwrap();
         let deserialized: EmailSubmission = serde_json::from_str(&json).unwrap();
@@ -649,6 +1385,10 @@ mod tests {
             serde_json::to_string(&FilterType::Calendar).unwrap(),
             "\"calendar\""
         );
+        assert_eq!(
+            serde_json::to_string(&FilterType::Keyword).unwrap(),
+            "\"keyword\""
+        );
     }
 
     #[test]
@@ -714,6 +1454,8 @@ mod tests {
             filters: vec![],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         let json = serde_json::to_string(&split).unwrap();
         assert!(!json.contains("icon"));
@@ -728,6 +1470,8 @@ mod tests {
             filters: vec![],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         let json = serde_json::to_string(&split).unwrap();
         assert!(json.contains(r#""icon":"https://example.com/icon.svg""#));
@@ -742,6 +1486,8 @@ mod tests {
             filters: vec![],
             match_mode: MatchMode::Any,
             account: Some("aristoi".into()),
+            action: None,
+            extra: Default::default(),
         };
         let json = serde_json::to_string(&split).unwrap();
         assert!(json.contains(r#""account":"aristoi""#));
@@ -766,6 +1512,8 @@ mod tests {
             filters: vec![],
             match_mode: MatchMode::Any,
             account: None,
+            action: None,
+            extra: Default::default(),
         };
         let json = serde_json::to_string(&split).unwrap();
         assert!(!json.contains("account"));
@@ -790,6 +1538,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: Some("ACCEPTED".into()),
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = serde_json::to_string(&event).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
@@ -838,6 +1591,11 @@ mod tests {
             raw_ics: String::new(),
             user_rsvp_status: None,
             is_update: false,
+            changes: Vec::new(),
+            organizer_mismatch: None,
+            my_response: None,
+            alarms: Vec::new(),
+            all_day: false,
         };
         let json = serde_json::to_string(&event).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
@@ -895,15 +1653,23 @@ mod tests {
                             filter_type: FilterType::From,
                             pattern: "*@calendar.google.com".into(),
                             name: None,
+                            pattern_kind: None,
+                            glob_match: None,
+                            extra: Default::default(),
                         },
                         SplitFilter {
                             filter_type: FilterType::Subject,
                             pattern: "invite|invitation".into(),
                             name: None,
+                            pattern_kind: None,
+                            glob_match: None,
+                            extra: Default::default(),
                         },
                     ],
                     match_mode: MatchMode::All,
                     account: None,
+                    action: None,
+                    extra: Default::default(),
                 },
                 SplitInbox {
                     id: "newsletters".into(),
@@ -913,11 +1679,18 @@ mod tests {
                         filter_type: FilterType::From,
                         pattern: "noreply@*".into(),
                         name: None,
+                        pattern_kind: None,
+                        glob_match: None,
+                        extra: Default::default(),
                     }],
                     match_mode: MatchMode::Any,
                     account: None,
+                    action: None,
+                    extra: Default::default(),
                 },
             ],
+            version: crate::splits::CONFIG_VERSION,
+            extra: Default::default(),
         };
         let json = serde_json::to_string(&config).unwrap();
         let deserialized: SplitsConfig = serde_json::from_str(&json).unwrap();
@@ -1161,4 +1934,91 @@ mod tests {
         let result: Result<EmailSort, _> = serde_json::from_value(serde_json::json!("DateDesc"));
         assert!(result.is_err());
     }
+
+    fn spam_from(headers: &[(&str, &str)]) -> Option<SpamInfo> {
+        SpamInfo::from_headers(|name| headers.iter().find(|(n, _)| *n == name).map(|(_, v)| *v))
+    }
+
+    #[test]
+    fn spam_info_prefers_score_header_then_status_then_rspamd() {
+        let status = "Yes, score=7.2 required=5.0 tests=BAYES_99";
+        assert_eq!(
+            spam_from(&[("X-Spam-Score", "5.3 (+++++)"), ("X-Spam-Status", status)])
+                .unwrap()
+                .score,
+            Some(5.3)
+        );
+        let info = spam_from(&[("X-Spam-Status", status), ("X-Rspamd-Score", "1.0")]).unwrap();
+        assert_eq!(info.score, Some(7.2));
+        assert_eq!(info.status.as_deref(), Some(status));
+        assert_eq!(
+            spam_from(&[("X-Rspamd-Score", "-0.10")]).unwrap().score,
+            Some(-0.1)
+        );
+        assert_eq!(
+            spam_from(&[("X-Spam-Status", "No, hits=1.5 required=5.0")])
+                .unwrap()
+                .score,
+            Some(1.5)
+        );
+    }
+
+    #[test]
+    fn spam_info_keeps_known_sender_and_is_none_without_headers() {
+        let info = spam_from(&[("X-Spam-Known-Sender", " yes; in-addressbook ")]).unwrap();
+        assert_eq!(info.score, None);
+        assert_eq!(info.known_sender.as_deref(), Some("yes; in-addressbook"));
+        assert_eq!(spam_from(&[]), None);
+        assert_eq!(spam_from(&[("X-Spam-Score", "")]), None);
+    }
+
+    #[test]
+    fn flag_color_roundtrips_through_mail_flag_bits() {
+        for color in FlagColor::ALL {
+            let mut email = test_email();
+            email.keywords.insert("$flagged".into(), true);
+            for (keyword, set) in FlagColor::keyword_bits(Some(color)) {
+                if set {
+                    email.keywords.insert(keyword.to_lowercase(), true);
+                }
+            }
+            assert_eq!(email.flag_color(), Some(color));
+        }
+        assert_eq!(
+            FlagColor::keyword_bits(None).map(|(_, set)| set),
+            [false; 3]
+        );
+    }
+
+    #[test]
+    fn flag_color_needs_a_flag_and_a_bit() {
+        let mut email = test_email();
+        email.keywords.insert("$MailFlagBit2".into(), true);
+        assert_eq!(email.flag_color(), None);
+        email.keywords.insert("$flagged".into(), true);
+        assert_eq!(email.flag_color(), Some(FlagColor::Blue));
+        email.keywords.remove("$MailFlagBit2");
+        assert_eq!(email.flag_color(), None);
+        // Index 7 is unassigned.
+        for bit in FLAG_COLOR_KEYWORDS {
+            email.keywords.insert(bit.into(), true);
+        }
+        assert_eq!(email.flag_color(), None);
+    }
+
+    #[test]
+    fn score_filter_needs_a_score() {
+        let over_five = ScoreFilter {
+            op: Comparison::Gt,
+            value: 5.0,
+        };
+        let scored = |score| SpamInfo {
+            score: Some(score),
+            ..Default::default()
+        };
+        assert!(over_five.matches(Some(&scored(5.1))));
+        assert!(!over_five.matches(Some(&scored(5.0))));
+        assert!(!over_five.matches(Some(&SpamInfo::default())));
+        assert!(!over_five.matches(None));
+    }
 }
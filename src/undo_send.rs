@@ -0,0 +1,233 @@
+//! Undo send: hold each send for a few seconds so it can be called back.
+//!
+//! Off unless `SUPERVILLAIN_UNDO_SEND_SECONDS` sets a delay (`on` means
+//! [`DEFAULT_DELAY_SECS`]). With one, `POST /api/emails/send` validates
+//! the message as usual, keeps a copy in Drafts where the provider has
+//! them, and returns a token instead of sending. A task submits it when
+//! the delay runs out and then removes the draft.
+//! `POST /api/emails/send/{token}/cancel` calls it back, and the draft
+//! stays in Drafts to edit.
+//!
+//! Since the request has long returned by then, the task records how the
+//! send ended ([`SendStatus`]) and `GET /api/emails/send/{token}` reports
+//! it, so the clients can say "Sent" or show the failure. Outcomes are
+//! kept for [`OUTCOME_TTL`].
+//!
+//! Whoever [`UndoSendQueue::take`]s the entry first wins: the task takes
+//! it right before submitting, cancel takes it to abort, so a cancel
+//! either stops the send or finds it already gone, never half of each.
+//! Pending sends live in memory only. A restart drops them, which is why
+//! the draft is only removed after a successful send.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+/// Delay `SUPERVILLAIN_UNDO_SEND_SECONDS=on` stands for.
+pub const DEFAULT_DELAY_SECS: u64 = 10;
+
+/// How long a finished send's outcome stays readable.
+pub const OUTCOME_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Longest delay accepted; past this a send is better scheduled.
+pub const MAX_DELAY_SECS: u64 = 300;
+
+#[derive(Debug)]
+pub struct PendingSend {
+    pub account: String,
+    /// Copy kept in Drafts until the send goes out; None when the
+    /// provider has no drafts.
+    pub draft_id: Option<String>,
+    pub send_at: DateTime<Utc>,
+    task: Option<AbortHandle>,
+}
+
+impl PendingSend {
+    pub fn new(account: &str, draft_id: Option<String>, send_at: DateTime<Utc>) -> Self {
+        Self {
+            account: account.to_string(),
+            draft_id,
+            send_at,
+            task: None,
+        }
+    }
+}
+
+/// Where a held send stands, as `GET /api/emails/send/{token}` reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum SendStatus {
+    Pending,
+    Sent,
+    /// Nothing (or, for individual sends, not everything) went out; the
+    /// message is still in Drafts.
+    Failed {
+        error: String,
+    },
+    Cancelled,
+}
+
+#[derive(Debug)]
+struct Outcome {
+    account: String,
+    status: SendStatus,
+    at: Instant,
+}
+
+/// Sends waiting out the undo delay, by token. `Default` has no delay:
+/// every send goes out immediately.
+#[derive(Debug, Default)]
+pub struct UndoSendQueue {
+    delay: Duration,
+    pending: Mutex<HashMap<String, PendingSend>>,
+    outcomes: Mutex<HashMap<String, Outcome>>,
+}
+
+impl UndoSendQueue {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.delay.is_zero()
+    }
+
+    /// Queue `pending` under a fresh token, with `task` as the handle
+    /// [`cancel`](Self::cancel) aborts.
+    pub fn insert(&self, mut pending: PendingSend, task: AbortHandle) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        pending.task = Some(task);
+        self.lock().insert(token.clone(), pending);
+        token
+    }
+
+    /// Claim `token` for sending. None means it was cancelled (or never
+    /// existed), and the caller must not send.
+    pub fn take(&self, token: &str) -> Option<PendingSend> {
+        self.lock().remove(token)
+    }
+
+    /// Call back `token` for `account`. None when it already went out,
+    /// was cancelled, or belongs to another account.
+    pub fn cancel(&self, token: &str, account: &str) -> Option<PendingSend> {
+        let mut pending = self.lock();
+        if pending.get(token)?.account != account {
+            return None;
+        }
+        let send = pending.remove(token)?;
+        if let Some(task) = &send.task {
+            task.abort();
+        }
+        self.finish(token, account, SendStatus::Cancelled);
+        Some(send)
+    }
+
+    /// Record how the send under `token` ended.
+    pub fn finish(&self, token: &str, account: &str, status: SendStatus) {
+        let mut outcomes = self.outcomes.lock().unwrap_or_else(|e| e.into_inner());
+        outcomes.retain(|_, o| o.at.elapsed() < OUTCOME_TTL);
+        outcomes.insert(
+            token.to_string(),
+            Outcome {
+                account: account.to_string(),
+                status,
+                at: Instant::now(),
+            },
+        );
+    }
+
+    /// Where `token` stands for `account`. None when it is unknown,
+    /// belongs to another account, or finished over [`OUTCOME_TTL`] ago.
+    pub fn status(&self, token: &str, account: &str) -> Option<SendStatus> {
+        if let Some(send) = self.lock().get(token) {
+            return (send.account == account).then_some(SendStatus::Pending);
+        }
+        let outcomes = self.outcomes.lock().unwrap_or_else(|e| e.into_inner());
+        outcomes
+            .get(token)
+            .filter(|o| o.account == account && o.at.elapsed() < OUTCOME_TTL)
+            .map(|o| o.status.clone())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingSend>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_one(queue: &UndoSendQueue, account: &str) -> (String, tokio::task::JoinHandle<()>) {
+        let task = tokio::spawn(std::future::pending());
+        let token = queue.insert(
+            PendingSend::new(account, Some("d1".into()), Utc::now()),
+            task.abort_handle(),
+        );
+        (token, task)
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_and_wins_over_a_later_take() {
+        let queue = UndoSendQueue::new(Duration::from_secs(10));
+        let (token, task) = queue_one(&queue, "work");
+        let cancelled = queue.cancel(&token, "work").unwrap();
+        assert_eq!(cancelled.draft_id.as_deref(), Some("d1"));
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(queue.take(&token).is_none());
+        assert!(queue.cancel(&token, "work").is_none());
+        assert_eq!(queue.status(&token, "work"), Some(SendStatus::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn status_follows_the_send_and_is_scoped_to_the_account() {
+        let queue = UndoSendQueue::new(Duration::from_secs(10));
+        let (token, _task) = queue_one(&queue, "work");
+        assert_eq!(queue.status(&token, "work"), Some(SendStatus::Pending));
+        assert_eq!(queue.status(&token, "home"), None);
+        queue.take(&token).unwrap();
+        let failed = SendStatus::Failed {
+            error: "quota exceeded".into(),
+        };
+        queue.finish(&token, "work", failed.clone());
+        assert_eq!(queue.status(&token, "work"), Some(failed.clone()));
+        assert_eq!(queue.status(&token, "home"), None);
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap(),
+            serde_json::json!({"status": "failed", "error": "quota exceeded"})
+        );
+        assert_eq!(queue.status("unknown", "work"), None);
+    }
+
+    #[tokio::test]
+    async fn take_wins_over_a_later_cancel() {
+        let queue = UndoSendQueue::new(Duration::from_secs(10));
+        let (token, _task) = queue_one(&queue, "work");
+        assert!(queue.take(&token).is_some());
+        assert!(queue.cancel(&token, "work").is_none());
+    }
+
+    #[tokio::test]
+    async fn cancel_is_scoped_to_the_account() {
+        let queue = UndoSendQueue::new(Duration::from_secs(10));
+        let (token, _task) = queue_one(&queue, "work");
+        assert!(queue.cancel(&token, "home").is_none());
+        assert!(queue.cancel(&token, "work").is_some());
+    }
+
+    #[test]
+    fn default_sends_immediately() {
+        assert!(!UndoSendQueue::default().is_enabled());
+        assert!(UndoSendQueue::new(Duration::from_secs(1)).is_enabled());
+    }
+}
@@ -0,0 +1,184 @@
+//! Acting on `List-Unsubscribe` (RFC 2369) before archiving a sender.
+//!
+//! The header lists `<…>` URIs: web pages and `mailto:` addresses. When
+//! `List-Unsubscribe-Post: List-Unsubscribe=One-Click` is present too,
+//! RFC 8058 lets an https URL be POSTed without a browser, which is what
+//! [`choose`] prefers. Otherwise a `mailto:` gets a short message sent
+//! from the subscribed identity, and failing that the page is handed back
+//! for the user to open. The POST goes only to public hosts and doesn't
+//! follow redirects, so a sender can't aim it at the local network. It
+//! uses the image proxy's client, which also refuses a name that resolves
+//! to a private address (`image_proxy::PublicResolver`).
+
+use crate::error::Error;
+use percent_encoding::percent_decode_str;
+use std::time::Duration;
+
+/// The `List-Unsubscribe-Post` value, and the form body, of RFC 8058.
+pub const ONE_CLICK: &str = "List-Unsubscribe=One-Click";
+
+const ONE_CLICK_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// POST [`ONE_CLICK`] to the URL.
+    OneClick(url::Url),
+    /// Send a message to the list's address.
+    Mailto(Mailto),
+    /// Only a web page: the user has to open it.
+    Browser(String),
+}
+
+impl Action {
+    /// `unsubscribeMethod` in the route's response.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Action::OneClick(_) => "one-click",
+            Action::Mailto(_) => "mailto",
+            Action::Browser(_) => "browser",
+        }
+    }
+}
+
+/// A `mailto:` unsubscribe: where to write and what to say.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mailto {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// The `<…>` URIs of a `List-Unsubscribe` value, in order.
+pub fn uris(header: &str) -> Vec<String> {
+    header
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('>'))
+        .map(|(uri, _)| uri.split_whitespace().collect::<String>())
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+/// Pick how to unsubscribe: one-click when `List-Unsubscribe-Post` asks
+/// for it and there is an https URL on a public host, then the first
+/// `mailto:`, then the first web page. `None` when the header has none.
+pub fn choose(list_unsubscribe: &str, list_unsubscribe_post: Option<&str>) -> Option<Action> {
+    let uris = uris(list_unsubscribe);
+    let one_click = list_unsubscribe_post.is_some_and(|v| v.trim().eq_ignore_ascii_case(ONE_CLICK));
+    let web = || {
+        uris.iter()
+            .filter_map(|u| url::Url::parse(u).ok())
+            .filter(|u| matches!(u.scheme(), "http" | "https"))
+    };
+    if one_click
+        && let Some(url) =
+            web().find(|u| u.scheme() == "https" && crate::image_proxy::has_public_host(u))
+    {
+        return Some(Action::OneClick(url));
+    }
+    if let Some(mailto) = uris.iter().find_map(|u| parse_mailto(u)) {
+        return Some(Action::Mailto(mailto));
+    }
+    web().next().map(|u| Action::Browser(u.to_string()))
+}
+
+/// `mailto:addr?subject=…&body=…` (RFC 6068). Only the first address is
+/// used; subject and body default to "unsubscribe".
+pub fn parse_mailto(uri: &str) -> Option<Mailto> {
+    let rest = uri
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+        .map(|_| &uri[7..])?;
+    let (addresses, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().trim().to_string();
+    let to = decode(addresses.split(',').next()?);
+    if !to.contains('@') {
+        return None;
+    }
+    let (mut subject, mut body) = (None, None);
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key.to_ascii_lowercase().as_str() {
+            "subject" => subject = Some(decode(value)),
+            "body" => body = Some(decode(value)),
+            _ => {}
+        }
+    }
+    let or_default =
+        |v: Option<String>| v.filter(|v| !v.is_empty()).unwrap_or("unsubscribe".into());
+    Some(Mailto {
+        to,
+        subject: or_default(subject),
+        body: or_default(body),
+    })
+}
+
+/// POST the RFC 8058 form to `url`. A redirect counts as done: the
+/// request was received, and following it could leave the public web.
+pub async fn one_click(url: &url::Url) -> Result<(), Error> {
+    let resp = crate::image_proxy::public_client()
+        .post(url.clone())
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(ONE_CLICK)
+        .timeout(ONE_CLICK_TIMEOUT)
+        .send()
+        .await?;
+    let status = resp.status();
+    if status.is_success() || status.is_redirection() {
+        Ok(())
+    } else {
+        Err(Error::Network(format!(
+            "one-click unsubscribe: HTTP {status}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "<mailto:leave-123@lists.example?subject=unsubscribe%20me>, \
+                          <https://news.example/u?id=123>";
+
+    #[test]
+    fn one_click_is_preferred_when_offered() {
+        let action = choose(HEADER, Some(" List-Unsubscribe=One-Click ")).unwrap();
+        assert_eq!(action.method(), "one-click");
+        assert_eq!(
+            action,
+            Action::OneClick(url::Url::parse("https://news.example/u?id=123").unwrap())
+        );
+    }
+
+    #[test]
+    fn mailto_then_browser_without_one_click() {
+        let Some(Action::Mailto(mailto)) = choose(HEADER, None) else {
+            panic!("expected mailto");
+        };
+        assert_eq!(mailto.to, "leave-123@lists.example");
+        assert_eq!(mailto.subject, "unsubscribe me");
+        assert_eq!(mailto.body, "unsubscribe");
+        assert_eq!(
+            choose("<http://news.example/u>", Some(ONE_CLICK)),
+            Some(Action::Browser("http://news.example/u".into()))
+        );
+        assert_eq!(choose("no brackets here", None), None);
+    }
+
+    #[test]
+    fn one_click_never_targets_the_local_network() {
+        let header = "<https://127.0.0.1/u>, <https://router.local/u>";
+        assert_eq!(choose(header, Some(ONE_CLICK)).unwrap().method(), "browser");
+    }
+
+    #[test]
+    fn mailto_needs_an_address() {
+        assert!(parse_mailto("mailto:?subject=x").is_none());
+        assert!(parse_mailto("https://x.example").is_none());
+        let m = parse_mailto("MAILTO:a@x.example,b@x.example?Body=stop+now").unwrap();
+        assert_eq!(
+            (m.to.as_str(), m.body.as_str()),
+            ("a@x.example", "stop+now")
+        );
+    }
+}
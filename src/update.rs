@@ -0,0 +1,195 @@
+//! Version reporting and the opt-in update check.
+//!
+//! `GET /api/version` reports the crate version, the git commit and the
+//! build date baked in by `build.rs`. With `SUPERVILLAIN_UPDATE_CHECK` on,
+//! [`spawn_checker`] asks GitHub for the latest release once a day and the
+//! same payload carries `update`: the release found and whether it is newer
+//! than the running build. Nothing leaves the machine unless it's enabled.
+
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const RELEASES_URL: &str =
+    "https://api.github.com/repos/wiseyoungbuck/supervillain-rs/releases/latest";
+
+/// What `build.rs` embedded, plus the crate version.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// 12-character commit hash, `unknown` when built outside git.
+    pub git_hash: &'static str,
+    /// `YYYY-MM-DD`, UTC.
+    pub build_date: &'static str,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("SUPERVILLAIN_BUILD_ID"),
+            build_date: env!("SUPERVILLAIN_BUILD_DATE"),
+        }
+    }
+}
+
+/// The latest release as of the last successful check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateStatus {
+    pub latest: String,
+    pub url: String,
+    pub checked_at: DateTime<Utc>,
+    /// `latest` is newer than the running version.
+    pub available: bool,
+}
+
+/// Whether the check runs, and what it last found.
+#[derive(Default)]
+pub struct UpdateCheck {
+    enabled: bool,
+    status: Mutex<Option<UpdateStatus>>,
+}
+
+impl UpdateCheck {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            status: Mutex::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `None` until the first check succeeds (and always when disabled).
+    pub fn status(&self) -> Option<UpdateStatus> {
+        self.lock().clone()
+    }
+
+    pub fn record(&self, status: UpdateStatus) {
+        *self.lock() = Some(status);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<UpdateStatus>> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+/// `v1.2.3` / `1.2` as numbers; pre-release and build suffixes are
+/// dropped, so `1.3.0-rc1` compares as `1.3.0`.
+fn version_parts(v: &str) -> Option<[u64; 3]> {
+    let core = v.trim().trim_start_matches(['v', 'V']);
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = [0u64; 3];
+    for (i, part) in core.split('.').enumerate() {
+        *parts.get_mut(i)? = part.parse().ok()?;
+    }
+    Some(parts)
+}
+
+/// `latest` is a strictly newer release than `current`. A tag that isn't
+/// a version never counts as an update.
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (version_parts(latest), version_parts(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Fetch the latest release and compare it with this build.
+pub async fn check_once(client: &reqwest::Client) -> Result<UpdateStatus, Error> {
+    let resp = client
+        .get(RELEASES_URL)
+        .header("accept", "application/vnd.github+json")
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(Error::Network(format!("GitHub releases: HTTP {status}")));
+    }
+    let release: Release = resp.json().await?;
+    Ok(UpdateStatus {
+        available: is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")),
+        latest: release.tag_name,
+        url: release.html_url,
+        checked_at: Utc::now(),
+    })
+}
+
+/// Check for a new release every `interval`; a no-op loop unless the
+/// check is enabled, so it isn't even registered with the task health.
+pub fn spawn_checker(
+    state: Arc<crate::types::AppState>,
+    interval: Duration,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if !state.update_check.enabled() {
+        return None;
+    }
+    let health = crate::diagnostics::TaskHealth::global();
+    health.started("update-check", interval);
+    Some(tokio::spawn(async move {
+        let client = crate::http_client::client();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            health.ticked("update-check");
+            match check_once(&client).await {
+                Ok(status) => {
+                    if status.available {
+                        tracing::info!("Update available: {} ({})", status.latest, status.url);
+                    }
+                    state.update_check.record(status);
+                }
+                Err(e) => {
+                    tracing::warn!("Update check failed: {e}");
+                    health.failed("update-check", e);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_releases_are_detected() {
+        assert!(is_newer("v0.4.0", "0.3.0"));
+        assert!(is_newer("0.3.1", "0.3.0"));
+        assert!(is_newer("v1.0", "0.9.9"));
+        assert!(!is_newer("v0.3.0", "0.3.0"));
+        assert!(!is_newer("v0.2.9", "0.3.0"));
+        // Suffixes are ignored: an rc of the running version isn't newer.
+        assert!(!is_newer("v0.3.0-rc1", "0.3.0"));
+        assert!(!is_newer("nightly", "0.3.0"));
+        assert!(!is_newer("1.2.3.4", "0.3.0"));
+    }
+
+    #[test]
+    fn status_is_recorded_only_when_checked() {
+        let check = UpdateCheck::new(true);
+        assert!(check.enabled() && check.status().is_none());
+        let status = UpdateStatus {
+            latest: "v9.0.0".into(),
+            url: "https://example.invalid/r".into(),
+            checked_at: Utc::now(),
+            available: true,
+        };
+        check.record(status.clone());
+        assert_eq!(check.status(), Some(status));
+        assert!(!UpdateCheck::default().enabled());
+    }
+}
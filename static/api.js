@@ -7,13 +7,15 @@
 // Auto-append ?account= ONLY for account-scoped routes. Settings routes
 // (`/accounts/...`, `/theme`, `/timezone*`) are global and must never be
 // tagged.
-const ACCOUNT_SCOPED_API = /^\/(emails|mailboxes|identities|splits|upload|split-counts|calendar|drafts)/;
+const ACCOUNT_SCOPED_API = /^\/(emails|mailboxes|identities|splits|upload|split-counts|calendar|drafts|contacts|threads|muted-threads|flag-colors|suggestions|commands)/;
 
 // Error taxonomy: ApiAuthError means the account's provider session needs
-// re-authorization (401/403 from the server); everything else — network
-// failures and non-auth HTTP errors — is ApiError. Callers that redirect or
-// banner on auth problems must test `instanceof ApiAuthError` BEFORE
-// `instanceof ApiError` (the former extends the latter).
+// re-authorization (401/403 from the server); ApiReadOnlyError means the
+// server is in read-only mode and refused a write (409 with
+// `{"error": "read_only"}`); everything else — network failures and other
+// HTTP errors — is ApiError. Callers that redirect or banner on auth
+// problems must test `instanceof ApiAuthError` BEFORE `instanceof ApiError`
+// (both subclasses extend the latter).
 class ApiError extends Error {
     constructor(message, status = null) {
         super(message);
@@ -29,6 +31,13 @@ class ApiAuthError extends ApiError {
     }
 }
 
+class ApiReadOnlyError extends ApiError {
+    constructor(message = 'the server is in read-only mode') {
+        super(message, 409);
+        this.name = 'ApiReadOnlyError';
+    }
+}
+
 // makeApi(accountId) → async api(method, path, body, signal) bound to one
 // account. Pass a falsy accountId for an unscoped instance (global routes,
 // or before accounts are loaded). Make a new instance on account switch.
@@ -61,7 +70,13 @@ function makeApi(accountId) {
             throw new ApiAuthError(await resp.text(), resp.status);
         }
         if (!resp.ok) {
-            throw new ApiError(await resp.text(), resp.status);
+            const text = await resp.text();
+            if (resp.status === 409) {
+                let body = null;
+                try { body = JSON.parse(text); } catch { /* plain text */ }
+                if (body?.error === 'read_only') throw new ApiReadOnlyError(body.message);
+            }
+            throw new ApiError(text, resp.status);
         }
         if (resp.status === 204) return { data: null, headers: resp.headers };
         const text = await resp.text();
@@ -7,6 +7,7 @@ const state = {
     accounts: [],
     currentAccount: null,
     mailboxes: [],
+    sharedMailboxes: [],      // other accounts' mailboxes, each with its `account` key
     currentMailbox: null,
     emails: [],
     selectedIndex: 0,
@@ -38,6 +39,8 @@ const state = {
     expandedThreads: new Set(),
     pendingG: false,          // for gg command
     commandPaletteIndex: 0,
+    serverCommands: null,     // /api/commands registry for the palette (null until fetched)
+    keyRemap: new Map(),      // keybindings.json: pressed key → command id (null = moved away); see applyKeymap
     replyContext: null,       // for reply/forward
     draftId: null,            // server id of the persistent draft this compose is autosaving (kata wm57)
     composeBaseline: '',      // compose body value at clear/restore time; composeDirty compares
@@ -52,6 +55,11 @@ const state = {
     identities: [],           // send-as email addresses
     splits: [],               // split inbox definitions
     currentSplit: 'all',      // currently active split tab
+    // GET /api/preferences: per-account startup mailbox/split. The split
+    // waits in pendingStartupSplit until the inbox and splits are loaded.
+    preferences: null,
+    pendingStartupSplit: null,
+    splitsLoaded: false,
     pendingAttachments: [],   // files being uploaded for compose
     splitCounts: {},          // email counts per split tab
     starredOnly: false,       // sidebar "Starred" filter — restricts list to $flagged emails
@@ -110,6 +118,8 @@ const harvestedMessageIds = new Set();
 // this session (attempted, not necessarily successful — see
 // harvestSentContactsOnce: a failure isn't retried).
 const sentHarvestedAccounts = new Set();
+// accountIds whose synced address book (/api/contacts) has been folded in.
+const addressBookLoadedAccounts = new Set();
 // Lowercased addresses excluded from contact suggestions — accumulates across
 // every account whose identities have been loaded this session. Deliberately
 // NOT account-scoped, unlike contactIndex: it is exclusion-only, so
@@ -130,6 +140,10 @@ const SEARCH_OPERATORS = [
     { op: 'older_than:', hint: '7d, 2w, 3m, or MM-DD-YY', needsValue: true },
     { op: 'before:', hint: 'YYYY-MM-DD', needsValue: true },
     { op: 'after:', hint: 'YYYY-MM-DD', needsValue: true },
+    { op: 'larger:', hint: 'Size, e.g. 5M or 200K', needsValue: true },
+    { op: 'smaller:', hint: 'Size, e.g. 5M or 200K', needsValue: true },
+    { op: 'in:', hint: 'inbox, archive, or "Folder Name"', needsValue: true },
+    { op: 'label:', hint: 'Label or folder name', needsValue: true },
 ];
 
 // DOM elements
@@ -180,10 +194,15 @@ function init() {
     els.splitName = document.getElementById('split-name');
     els.splitFilterType = document.getElementById('split-filter-type');
     els.splitPattern = document.getElementById('split-pattern');
+    els.splitPatternKind = document.getElementById('split-pattern-kind');
+    els.splitGlobMatch = document.getElementById('split-glob-match');
+    els.splitAction = document.getElementById('split-action');
     els.splitCancel = document.getElementById('split-cancel');
     els.splitSave = document.getElementById('split-save');
     els.splitPatternField = document.getElementById('split-pattern-field');
     els.splitHint = document.getElementById('split-hint');
+    els.splitPreview = document.getElementById('split-preview');
+    els.splitPreviewButton = document.getElementById('split-preview-button');
     els.calendarEvent = document.getElementById('calendar-event');
     els.calTitle = document.getElementById('cal-title');
     els.calDatetime = document.getElementById('cal-datetime');
@@ -239,6 +258,7 @@ function init() {
     els.acctClientId = document.getElementById('acct-client-id');
     els.acctClientSecret = document.getElementById('acct-client-secret');
     els.acctSignature = document.getElementById('acct-signature');
+    els.acctProxy = document.getElementById('acct-proxy');
     els.acctAuthPill = document.getElementById('acct-auth-pill');
     els.acctAuthorizeBtn = document.getElementById('acct-authorize-btn');
     els.acctDefaultMarker = document.getElementById('acct-default-marker');
@@ -309,7 +329,13 @@ function init() {
     els.undoButton.addEventListener('click', performUndo);
     els.splitCancel.addEventListener('click', closeSplitModal);
     els.splitSave.addEventListener('click', saveSplit);
-    els.splitFilterType.addEventListener('change', updateSplitModalFields);
+    els.splitPreviewButton.addEventListener('click', previewSplit);
+    els.splitFilterType.addEventListener('change', () => {
+        els.splitPatternKind.value = defaultPatternKind(els.splitFilterType.value);
+        updateSplitModalFields();
+    });
+    els.splitPatternKind.addEventListener('change', updateSplitModalFields);
+    els.splitGlobMatch.addEventListener('change', updateSplitModalFields);
     els.rsvpAccept.addEventListener('click', () => rsvpToEvent('ACCEPTED'));
     els.rsvpMaybe.addEventListener('click', () => rsvpToEvent('TENTATIVE'));
     els.rsvpDecline.addEventListener('click', () => rsvpToEvent('DECLINED'));
@@ -495,10 +521,30 @@ function init() {
     });
 
     // Load data
+    pendingPermalink = parsePermalink(window.location);
     loadTheme();
     loadAccounts();
+    loadKeymap();
     loadTimezone();
     loadTzZones();
+    subscribeServerEvents();
+}
+
+// Server push (/api/events). A splits change — from another window or a
+// hand-edit of splits.json/.toml — re-renders the tab row without a reload.
+// EventSource reconnects on its own after a server restart.
+function subscribeServerEvents() {
+    if (typeof EventSource === 'undefined') return;
+    const events = new EventSource('/api/events');
+    events.addEventListener('splits', async () => {
+        if (!state.currentAccount) return;
+        await loadSplits();
+        // The tab being viewed may have just been deleted.
+        const current = state.currentSplit;
+        if (current && current !== 'all' && !state.splits.some(s => s.id === current)) {
+            selectSplit('all');
+        }
+    });
 }
 
 // Theme
@@ -712,18 +758,36 @@ async function dismissTimezoneChange() {
 // is read at call time because desktop switches accounts in-place.
 
 function api(method, path, body = null, signal = null) {
-    return makeApi(state.currentAccount?.id)(method, path, body, signal);
+    return makeApi(apiAccount(path))(method, path, body, signal);
 }
 
 // Like api(), but resolves to { data, headers } — for the one caller that
 // needs a response header (loadEmails' stale-snapshot detection).
 function apiWithMeta(method, path, body = null, signal = null) {
-    return makeApi(state.currentAccount?.id).withMeta(method, path, body, signal);
+    return makeApi(apiAccount(path)).withMeta(method, path, body, signal);
+}
+
+// While a shared mailbox is open, its messages belong to the account that
+// shares it (`work#<id>`), so calls go there — except the mailbox list,
+// which stays the signed-in account's.
+function apiAccount(path) {
+    const shared = state.currentMailbox?.account;
+    if (shared && !path.startsWith('/mailboxes')) return shared;
+    return state.currentAccount?.id;
 }
 
 async function loadAccounts() {
     try {
-        const data = await fetch('/api/accounts').then(r => r.json());
+        const [data, preferences, status] = await Promise.all([
+            fetch('/api/accounts').then(r => r.json()),
+            fetch('/api/preferences').then(r => (r.ok ? r.json() : null)).catch(() => null),
+            fetch('/api/status').then(r => (r.ok ? r.json() : null)).catch(() => null),
+        ]);
+        state.preferences = preferences;
+        // read-only = true in the config: writes answer 409 read_only
+        // (ApiReadOnlyError), so just say so.
+        state.readOnly = !!status?.readOnly;
+        document.getElementById('read-only-indicator').hidden = !state.readOnly;
         state.accounts = data.accounts;
         renderAccounts();
 
@@ -747,7 +811,9 @@ async function loadAccounts() {
         // Auto-select only a connected account — selecting a pending one
         // would fire mailbox fetches that can only fail.
         const connected = state.accounts.filter(a => a.authStatus !== 'pending');
-        const defaultAcc = connected.find(a => a.isDefault) || connected[0];
+        const linkedAcc = pendingPermalink?.account
+            && connected.find(a => a.id === pendingPermalink.account);
+        const defaultAcc = linkedAcc || connected.find(a => a.isDefault) || connected[0];
         if (defaultAcc) {
             selectAccount(defaultAcc);
         } else {
@@ -867,6 +933,7 @@ function selectAccount(account) {
     }
     state.currentAccount = account;
     state.mailboxes = [];
+    state.sharedMailboxes = [];
     state.emails = [];
     state.threadGroups = new Map();
     state.expandedThreads = new Set();
@@ -875,6 +942,8 @@ function selectAccount(account) {
     state.selectedIndex = 0;
     state.currentSplit = 'all';
     state.splits = [];
+    state.splitsLoaded = false;
+    state.pendingStartupSplit = startupView(account.id)?.split || null;
     state.splitCounts = {};
     // Sort order is session-only (kata 09ef), reset to the default on
     // every account switch — same treatment as currentSplit above.
@@ -904,7 +973,7 @@ function selectAccount(account) {
 // under account "outlook-aristotle", and re-selecting an account finds
 // its previous cache entries instead of a cold fetch.
 function cacheKey(emailId) {
-    return (state.currentAccount?.id ?? '') + ':' + emailId;
+    return (apiAccount('') ?? '') + ':' + emailId;
 }
 
 async function loadSplits() {
@@ -913,31 +982,67 @@ async function loadSplits() {
         const splits = await api('GET', '/splits');
         if (state.currentAccount?.id !== accountId) return; // stale response guard
         state.splits = splits;
+        state.splitsLoaded = true;
         renderSplitTabs();
         loadSplitCounts();
+        applyStartupSplit();
     } catch (err) {
         // Stale failure guard: a request from the previous account erroring
         // late must not wipe the new account's already-loaded splits.
         if (state.currentAccount?.id !== accountId) return;
         console.warn('Failed to load splits:', err);
         state.splits = [];
+        state.splitsLoaded = true;
+        state.pendingStartupSplit = null;
     }
 }
 
+function startupView(accountId) {
+    return state.preferences?.startup?.[accountId] || null;
+}
+
+// Opens the account's startup split once both the inbox and the split
+// list are in; whichever load finishes second applies it.
+function applyStartupSplit() {
+    const id = state.pendingStartupSplit;
+    if (!id || !state.splitsLoaded || state.currentMailbox?.role !== 'inbox') return;
+    state.pendingStartupSplit = null;
+    if (state.splits.some(s => s.id === id)) selectSplit(id);
+}
+
+// The account's startup mailbox (id, role or name) when it's configured
+// and exists, else the inbox. A startup split implies the inbox.
+function startupMailbox() {
+    const view = startupView(state.currentAccount?.id);
+    const inbox = state.mailboxes.find(m => m.role === 'inbox');
+    const wanted = !view?.split && view?.mailbox?.toLowerCase();
+    if (!wanted) return inbox;
+    return state.mailboxes.find(m => m.id === view.mailbox)
+        || state.mailboxes.find(m => m.role === wanted)
+        || state.mailboxes.find(m => m.name.toLowerCase() === wanted)
+        || inbox;
+}
+
 let splitCountsController = null;
+let splitCountsRepoll = null;
+// The server answers from its cache and recomputes in the background when
+// the counts are out of date (`refreshing`); re-poll once it has had time.
+const SPLIT_COUNTS_REPOLL_MS = 5000;
 
 async function loadSplitCounts() {
     if (state.currentMailbox?.role !== 'inbox' || state.splits.length === 0) return;
     if (splitCountsController) splitCountsController.abort();
+    clearTimeout(splitCountsRepoll);
     splitCountsController = new AbortController();
     const mailboxId = state.currentMailbox.id;
     try {
         let url = `/split-counts?mailbox_id=${mailboxId}`;
         if (state.starredOnly) url += '&starred=true';
-        const counts = await api('GET', url, null, splitCountsController.signal);
+        const data = await api('GET', url, null, splitCountsController.signal);
         if (state.currentMailbox?.id !== mailboxId) return; // stale response guard
-        state.splitCounts = counts;
+        state.splitCounts = data.counts;
         renderSplitTabs();
+        if (data.refreshing) splitCountsRepoll = setTimeout(loadSplitCounts, SPLIT_COUNTS_REPOLL_MS);
     } catch (err) {
         if (err.name !== 'AbortError') console.warn('Failed to load split counts:', err);
     } finally {
@@ -1021,7 +1126,7 @@ function renderSplitTabs() {
 }
 
 function splitCacheKey() {
-    return `${state.currentAccount?.id || ''}:${state.currentMailbox?.id || ''}:${state.currentSplit || 'all'}:${state.starredOnly ? 'S' : ''}:${state.sortOrder}:${getSearchQuery()}`;
+    return `${apiAccount('') || ''}:${state.currentMailbox?.id || ''}:${state.currentSplit || 'all'}:${state.starredOnly ? 'S' : ''}:${state.sortOrder}:${getSearchQuery()}`;
 }
 
 function invalidateSplitListCache() {
@@ -1056,22 +1161,74 @@ function selectSplitByIndex(index) {
 
 async function loadMailboxes() {
     try {
-        state.mailboxes = await api('GET', '/mailboxes');
+        const mailboxes = await api('GET', '/mailboxes');
+        state.mailboxes = mailboxes.filter(m => !m.account);
+        state.sharedMailboxes = mailboxes.filter(m => m.account);
         renderMailboxes();
 
-        // Select inbox by default
-        const inbox = state.mailboxes.find(m => m.role === 'inbox');
-        if (inbox) selectMailbox(inbox);
+        // The inbox, unless the account has a startup mailbox
+        const mailbox = startupMailbox();
+        if (mailbox) selectMailbox(mailbox);
+        applyStartupSplit();
+        applyPermalink();
 
         // Contact autocomplete (kata e64s): background-fill the index with
         // the Sent mailbox's first page, now that state.mailboxes can
         // resolve it. Fire-and-forget — it self-handles its own failure.
         harvestSentContactsOnce();
+        loadAddressBookOnce();
     } catch (err) {
         showStatus('Failed to load mailboxes: ' + err.message, 'error');
     }
 }
 
+// Permalinks: /m/{emailId}?account=… opens one message, /search?q=… runs a
+// search. The backend serves index.html for both; the link is read once at
+// boot and applied after the first mailbox load, then the URL is reset to /
+// so later navigation doesn't leave a stale link in the address bar.
+let pendingPermalink = null;
+
+function parsePermalink(loc) {
+    const params = new URLSearchParams(loc.search);
+    const account = params.get('account');
+    const msg = loc.pathname.match(/^\/m\/([^/]+)\/?$/);
+    if (msg) return { kind: 'message', emailId: decodeURIComponent(msg[1]), account };
+    if (/^\/search\/?$/.test(loc.pathname) && params.get('q')) {
+        return { kind: 'search', query: params.get('q'), account };
+    }
+    return null;
+}
+
+// Split a query into terms, keeping "quoted phrases" (and op:"quoted values")
+// together so each becomes one search chip.
+function splitSearchTerms(query) {
+    return (query.match(/(?:[^\s"]+|"[^"]*")+/g) || [])
+        .map(t => t.replace(/"/g, ''))
+        .filter(Boolean);
+}
+
+function applyPermalink() {
+    const link = pendingPermalink;
+    if (!link) return;
+    pendingPermalink = null;
+    history.replaceState(null, '', '/');
+    if (link.account && state.currentAccount?.id !== link.account) {
+        showStatus(`Account ${link.account} is not connected`, 'error');
+        return;
+    }
+    if (link.kind === 'message') {
+        loadEmailDetail(link.emailId);
+    } else if (link.kind === 'search') {
+        for (const term of splitSearchTerms(link.query)) {
+            els.searchInput.value = term;
+            commitCurrentInput();
+        }
+        els.searchInput.value = '';
+        updateActiveFilters();
+        loadEmails();
+    }
+}
+
 function buildEmailListUrl(mailboxId, { offset = 0 } = {}) {
     let url = `/emails?mailbox_id=${mailboxId}&limit=${CACHE_LIMIT}`;
     if (offset > 0) url += `&offset=${offset}`;
@@ -1280,7 +1437,7 @@ function harvestContacts(emails, accountId) {
             const existing = index.get(key);
             if (existing) {
                 existing.count += 1;
-                if (addr.name) existing.name = addr.name;
+                if (addr.name && !existing.fromAddressBook) existing.name = addr.name;
                 if (email.receivedAt && email.receivedAt > existing.lastSeen) {
                     existing.lastSeen = email.receivedAt;
                 }
@@ -1317,6 +1474,41 @@ async function harvestSentContactsOnce() {
     }
 }
 
+// One-shot per account session: folds the server's synced CardDAV address
+// book into the contact index. Address book names win over harvested header
+// names; counts stay as harvested, so frequent correspondents still rank
+// first. Empty for accounts without an address book.
+async function loadAddressBookOnce() {
+    const accountId = state.currentAccount?.id;
+    if (!accountId || addressBookLoadedAccounts.has(accountId)) return;
+    addressBookLoadedAccounts.add(accountId);
+
+    try {
+        const entries = await api('GET', '/contacts');
+        const index = contactIndexFor(accountId);
+        for (const entry of entries) {
+            const key = entry.email.toLowerCase();
+            const existing = index.get(key);
+            if (existing) {
+                if (entry.name) {
+                    existing.name = entry.name;
+                    existing.fromAddressBook = true;
+                }
+            } else {
+                index.set(key, {
+                    email: entry.email,
+                    name: entry.name,
+                    lastSeen: '',
+                    count: 0,
+                    fromAddressBook: Boolean(entry.name),
+                });
+            }
+        }
+    } catch (err) {
+        console.warn('Contact harvest: address book fetch failed:', err);
+    }
+}
+
 // Comma-segment boundaries around `pos` in a To/Cc field's raw value. Shared
 // by the matcher (reads the in-progress segment) and acceptContactAutocomplete
 // (replaces it) so both agree on the same span — critical for correctness on
@@ -1479,7 +1671,7 @@ async function loadEmailDetail(emailId) {
         // toggleUnread never does (roborev 303, fix 1).
         const email = state.currentEmail;
         const listItem = state.emails.find(e => e.id === emailId);
-        if (email.isUnread) {
+        if (email.isUnread && !state.readOnly) {
             email.isUnread = false;
             if (listItem) listItem.isUnread = false;
             renderEmailList();
@@ -1759,6 +1951,7 @@ async function doSendEmail() {
     const quotedText = state.replyContext?.quotedText;
     const quotedHtml = state.replyContext?.quotedHtml;
     const inReplyTo = state.replyContext?.inReplyTo || null;
+    const forwardOf = state.replyContext?.forwardOf || null;
 
     const fullTextBody = quotedText
         ? userText + '\n\n' + quotedText.split('\n').map(l => '> ' + l).join('\n')
@@ -1830,7 +2023,7 @@ async function doSendEmail() {
 
     if (invite) {
         try {
-            await api('POST', '/calendar/invite', {
+            await sendConfirmed({
                 to,
                 cc,
                 subject,
@@ -1846,7 +2039,7 @@ async function doSendEmail() {
                 // Roborev 186 #6: pass through attachments so the invite+files
                 // combo doesn't silently drop the user's uploads.
                 attachments: readyAttachments.length ? readyAttachments : undefined,
-            });
+            }, '/calendar/invite');
             showStatus('Invite sent!', 'success');
             // Delete the autosaved draft of the mail that just went out — by
             // the captured id, even from a stale completion — UNLESS a newer
@@ -1883,7 +2076,7 @@ async function doSendEmail() {
     }
 
     try {
-        await api('POST', '/emails/send', {
+        const result = await sendConfirmed({
             to,
             cc,
             subject,
@@ -1892,13 +2085,21 @@ async function doSendEmail() {
             in_reply_to: inReplyTo,
             from_address: fromAddress,
             attachments: readyAttachments.length ? readyAttachments : undefined,
+            forward_of: forwardOf || undefined,
+            draft_id: draftId || undefined,
         });
-        showStatus('Sent!', 'success');
         // Same shape as the invite path above: captured-id delete unless a
         // newer session recaptured the id (see the comment there),
         // clear/navigate only while this send still owns the compose.
+        // A held send (undo window) owns its draft: the server removes it
+        // once the message goes out and keeps it on cancel.
         const reopened = trackedDraftSession !== session && trackedDraftId === draftId;
-        if (!reopened) deleteDraftById(draftId);
+        if (result?.pending) {
+            pushSendUndo(result);
+        } else {
+            showStatus('Sent!', 'success');
+            if (!reopened) deleteDraftById(draftId);
+        }
         if (state.composeSession === session) {
             clearCompose();
             showView('list');
@@ -1910,10 +2111,39 @@ async function doSendEmail() {
     }
 }
 
+// POST /emails/send (or /calendar/invite), answering the server's 428
+// challenges: an authenticator code for the sensitive-recipient guard, or
+// a yes/no for the send-time guard. Cancelling either rethrows.
+async function sendConfirmed(payload, path = '/emails/send') {
+    for (;;) {
+        try {
+            return await api('POST', path, payload);
+        } catch (err) {
+            if (err.status !== 428) throw err;
+            let challenge = {};
+            try { challenge = JSON.parse(err.message); } catch { /* plain text */ }
+            if (challenge.challenge?.type === 'send-time') {
+                const reasons = (challenge.challenge.reasons || []).join('\n');
+                const at = challenge.challenge.suggestedSendAt;
+                const hint = at ? `\n\nA better time would be ${new Date(at).toLocaleString()}.` : '';
+                if (!confirm(`${reasons}${hint}\n\nSend now anyway?`)) {
+                    throw new ApiError('Send cancelled: unusual send time', 428);
+                }
+                payload = { ...payload, confirm_send_time: true };
+                continue;
+            }
+            const recipients = challenge.challenge?.recipients?.join(', ') || 'these recipients';
+            const code = prompt(`${challenge.error || 'Confirmation required'}\n\nAuthenticator code to send to ${recipients}:`);
+            if (!code) throw new ApiError('Send cancelled: confirmation code required', 428);
+            payload = { ...payload, totp_code: code.trim() };
+        }
+    }
+}
+
 // Rendering
 
-function renderMailboxes() {
-    els.mailboxList.innerHTML = state.mailboxes
+function renderMailboxItems(mailboxes) {
+    return mailboxes
         .filter(m => m.role || m.parentId === null)
         .sort((a, b) => {
             const order = ['inbox', 'drafts', 'sent', 'archive', 'trash', 'spam'];
@@ -1922,16 +2152,31 @@ function renderMailboxes() {
             return ai - bi;
         })
         .map(m => `
-            <div class="mailbox-item ${state.currentMailbox?.id === m.id ? 'active' : ''}"
-                 data-id="${m.id}">
+            <div class="mailbox-item ${state.currentMailbox?.id === m.id && state.currentMailbox?.account === m.account ? 'active' : ''}"
+                 data-id="${m.id}" data-account="${escapeAttr(m.account || '')}">
                 <span>${m.name}</span>
                 ${m.unreadEmails > 0 ? `<span class="unread-count">${m.unreadEmails}</span>` : ''}
             </div>
         `).join('');
+}
+
+function renderMailboxes() {
+    // Shared and delegated accounts follow the signed-in one, each under
+    // its owner's name.
+    const owners = [...new Set(state.sharedMailboxes.map(m => m.account))];
+    els.mailboxList.innerHTML = renderMailboxItems(state.mailboxes) + owners.map(account => {
+        const mailboxes = state.sharedMailboxes.filter(m => m.account === account);
+        return `
+            <div class="mailbox-group">${escapeHtml(mailboxes[0].accountName || account)}</div>
+            ${renderMailboxItems(mailboxes)}
+        `;
+    }).join('');
 
     els.mailboxList.querySelectorAll('.mailbox-item').forEach(el => {
         el.addEventListener('click', () => {
-            const mb = state.mailboxes.find(m => m.id === el.dataset.id);
+            const mb = el.dataset.account
+                ? state.sharedMailboxes.find(m => m.id === el.dataset.id && m.account === el.dataset.account)
+                : state.mailboxes.find(m => m.id === el.dataset.id);
             if (mb) selectMailbox(mb);
         });
     });
@@ -2064,7 +2309,7 @@ function visibleRows() {
 
     // One O(n) pass: per grouped thread, collect present members (in list
     // order), the newest member, and aggregate unread/starred flags.
-    const groups = new Map(); // tid -> { members:[email], newest, anyUnread, anyStarred }
+    const groups = new Map(); // tid -> { members:[email], newest, anyUnread, unreadCount, anyStarred }
     for (const email of emails) {
         const tid = email.threadId;
         if (!tid) continue;
@@ -2072,11 +2317,14 @@ function visibleRows() {
         if (!known || known.length < 2) continue; // never grouped
         let g = groups.get(tid);
         if (!g) {
-            g = { members: [], newest: email, anyUnread: false, anyStarred: false };
+            g = { members: [], newest: email, anyUnread: false, unreadCount: 0, anyStarred: false };
             groups.set(tid, g);
         }
         g.members.push(email);
-        if (email.isUnread) g.anyUnread = true;
+        if (email.isUnread) {
+            g.anyUnread = true;
+            g.unreadCount++;
+        }
         if (email.isFlagged) g.anyStarred = true;
         if (new Date(email.receivedAt) > new Date(g.newest.receivedAt)) g.newest = email;
     }
@@ -2096,7 +2344,7 @@ function visibleRows() {
         // when expanded).
         if (email !== g.newest) continue;
         const expanded = state.expandedThreads.has(tid);
-        rows.push({ kind: 'thread', emailId: g.newest.id, email: g.newest, threadId: tid, count: g.members.length, unread: g.anyUnread, starred: g.anyStarred, expanded });
+        rows.push({ kind: 'thread', emailId: g.newest.id, email: g.newest, threadId: tid, count: g.members.length, unread: g.anyUnread, unreadCount: g.unreadCount, starred: g.anyStarred, expanded });
         if (expanded) {
             for (const m of g.members) {
                 if (m === g.newest) continue;
@@ -2153,7 +2401,7 @@ function renderEmailList() {
     els.emailList.innerHTML = rows.map((row, idx) => {
         const email = row.email;
         const from = email.from[0];
-        const fromDisplay = from?.name || from?.email || 'Unknown';
+        const fromDisplay = email.displayName || from?.name || from?.email || 'Unknown';
         const date = formatDate(email.receivedAt);
         const badge = showBadge ? getRecipientBadge(email) : null;
         const isThread = row.kind === 'thread';
@@ -2172,17 +2420,18 @@ function renderEmailList() {
             }
         }
         const rowClass = `email-row${idx === state.selectedIndex ? ' selected' : ''}${row.unread ? ' unread' : ''}`
-            + `${isThread ? ' email-row-thread' : ''}${isMember ? ' email-row-member' : ''}`;
+            + `${isThread ? ' email-row-thread' : ''}${isMember ? ' email-row-member' : ''}`
+            + `${email.priority?.level === 'high' ? ' priority-high' : ''}`;
         // Collapsed/expanded thread header carries a clickable count badge; a
         // click on it toggles expansion instead of opening the message.
         const countBadge = isThread
-            ? `<span class="email-thread-count${row.expanded ? ' expanded' : ''}" data-thread="${escapeAttr(row.threadId)}" title="${row.expanded ? 'Collapse' : 'Expand'} conversation (${row.count})">${row.expanded ? '▾ ' : ''}${row.count}</span>`
+            ? `<span class="email-thread-count${row.expanded ? ' expanded' : ''}" data-thread="${escapeAttr(row.threadId)}" title="${row.expanded ? 'Collapse' : 'Expand'} conversation (${row.count}${row.unreadCount ? `, ${row.unreadCount} unread` : ''})">${row.expanded ? '▾ ' : ''}${row.count}</span>`
             : '';
 
         return divider + `
             <div class="${rowClass}"
                  data-id="${email.id}" data-index="${idx}"${isThread ? ` data-thread="${escapeAttr(row.threadId)}"` : ''}>
-                <span class="email-flag ${row.starred ? 'flagged' : ''}">${row.starred ? '★' : '☆'}</span>
+                <span class="email-flag ${row.starred ? 'flagged' : ''}"${row.starred && email.flagColor ? ` data-flag-color="${escapeAttr(email.flagColor)}"` : ''}>${row.starred ? '★' : '☆'}</span>
                 ${countBadge}
                 <span class="email-from">${escapeHtml(fromDisplay)}</span>
                 ${badge ? `<span class="email-recipient-badge">${escapeHtml(badge)}</span>` : ''}
@@ -2229,13 +2478,82 @@ function renderEmailDetail() {
         els.attachments.classList.add('hidden');
     }
 
+    renderEmailBody(e);
+}
+
+function renderEmailBody(e) {
     if (e.htmlBody) {
-        renderHtmlBodyIframe(els.emailBody, e.htmlBody);
+        // The server's sanitized copy (remote images and CSS blocked)
+        // unless the user asked for remote content on this message, which
+        // then loads through the server's image proxy.
+        const html = e.htmlBodySafe == null ? e.htmlBody
+            : e.loadRemote ? (e.htmlBodyProxied ?? e.htmlBodySafe) : e.htmlBodySafe;
+        renderHtmlBodyIframe(els.emailBody, html);
         els.emailBody.classList.add('html-content');
+        if (!e.loadRemote && e.remoteResourcesBlocked > 0) {
+            els.emailBody.prepend(remoteContentNotice(e));
+        }
     } else {
         els.emailBody.innerHTML = linkifyText(e.textBody || '(no content)');
         els.emailBody.classList.remove('html-content');
     }
+    if (e.isTruncated) els.emailBody.prepend(truncationNotice(e));
+}
+
+function remoteContentNotice(e) {
+    const n = e.remoteResourcesBlocked;
+    const notice = document.createElement('div');
+    notice.className = 'remote-notice';
+    notice.innerHTML = `${n} remote ${n === 1 ? 'resource' : 'resources'} blocked. <a href="#" data-load="once">Load remote content</a>`
+        + (e.from?.[0]?.email ? ` · <a href="#" data-load="always">Always load from sender</a>` : '');
+    notice.querySelectorAll('a').forEach(a => a.addEventListener('click', async (ev) => {
+        ev.preventDefault();
+        if (a.dataset.load === 'always') {
+            try {
+                await api('POST', '/image-senders', { sender: e.from[0].email });
+            } catch (err) {
+                // Already listed is fine; anything else is worth saying.
+                if (!/already/.test(err.message)) showStatus('Failed to save preference: ' + err.message, 'error');
+            }
+        }
+        e.loadRemote = true;
+        if (state.currentEmail === e) renderEmailBody(e);
+    }));
+    return notice;
+}
+
+// Body parts over the server's fetch cap arrive cut (isTruncated). The
+// notice swaps in the whole part, streamed from /body/full, on request.
+function truncationNotice(e) {
+    const notice = document.createElement('div');
+    notice.className = 'truncated-notice';
+    notice.innerHTML = `Message truncated (${formatFileSize(e.bodySize || 0)} total). <a href="#">Load full message</a>`;
+    notice.querySelector('a').addEventListener('click', async (ev) => {
+        ev.preventDefault();
+        notice.textContent = 'Loading full message…';
+        try {
+            const url = `/api/emails/${encodeURIComponent(e.id)}/body/full`
+                + `?account=${encodeURIComponent(state.currentAccount.id)}`;
+            const resp = await fetch(url);
+            if (!resp.ok) throw new Error(await resp.text());
+            const body = await resp.text();
+            if ((resp.headers.get('content-type') || '').startsWith('text/html')) {
+                // The full part is served as-is, with no sanitized copy.
+                e.htmlBody = body;
+                e.htmlBodySafe = null;
+                e.htmlBodyProxied = null;
+                e.remoteResourcesBlocked = 0;
+            } else {
+                e.textBody = body;
+            }
+            e.isTruncated = false;
+            if (state.currentEmail === e) renderEmailBody(e);
+        } catch (err) {
+            notice.remove();
+            showStatus('Failed to load full message: ' + err.message, 'error');
+        }
+    });
+    return notice;
 }
 
 function renderCommandPalette() {
@@ -2423,6 +2741,7 @@ function renderSettings() {
         // Signature isn't a secret either — the backend echoes it back, so
         // (unlike api-token/client-secret) it's safe to prefill for editing.
         els.acctSignature.value = existing.signature || '';
+        els.acctProxy.value = existing.proxy || '';
         els.acctDefaultMarker.textContent = existing.isDefault ? 'yes ★' : 'no';
         const pending = existing.authStatus === 'pending';
         els.acctAuthPill.className = 'auth-status-pill ' + (pending ? 'failed' : 'authorized');
@@ -2440,6 +2759,7 @@ function renderSettings() {
         els.acctClientSecret.value = '';
         els.acctClientSecret.placeholder = '';
         els.acctSignature.value = '';
+        els.acctProxy.value = '';
         els.acctDefaultMarker.textContent = 'no';
         els.acctAuthPill.className = 'auth-status-pill idle';
         els.acctAuthPill.textContent = 'IDLE';
@@ -2487,6 +2807,8 @@ async function saveAccount() {
     // textarea always holds the value to save, so an empty box means "clear
     // the signature", not "leave it unchanged".
     payload.signature = els.acctSignature.value;
+    // Same for proxy: blank falls back to the global setting.
+    payload.proxy = els.acctProxy.value.trim();
     const id = (els.acctName.value || state.selectedAccountId || '').trim();
     if (!id) {
         showFormError('Name is required');
@@ -3273,6 +3595,15 @@ function handleNormalModeKey(e) {
         }
     }
 
+    // Keys remapped in keybindings.json run their command; a built-in key
+    // whose command moved elsewhere does nothing. Everything else falls
+    // through to the defaults below.
+    if (!e.ctrlKey && !e.metaKey && !e.altKey && state.keyRemap.has(key)) {
+        const command = state.keyRemap.get(key);
+        if (command) runBoundCommand(command, e);
+        return;
+    }
+
     switch (key) {
         // Page scrolling in detail view
         case ' ':
@@ -3352,6 +3683,9 @@ function handleNormalModeKey(e) {
         case 'U':
             unsubscribeAndArchiveAll();
             break;
+        case 'M':
+            markMailboxRead();
+            break;
         case 's':
             toggleFlagSelected();
             break;
@@ -3387,7 +3721,7 @@ function handleNormalModeKey(e) {
 
         // Other
         case '?':
-            els.helpOverlay.classList.remove('hidden');
+            openHelp();
             break;
         case 'R':
             loadEmails();
@@ -3641,6 +3975,228 @@ function toggleFlagSelected() {
     if (id) toggleFlag(id);
 }
 
+// When a snooze answer means: "3h", "2d", "tomorrow" (9:00 local), or a
+// local "YYYY-MM-DD HH:MM". Null when unparseable.
+function parseSnoozeTime(answer, now = new Date()) {
+    const text = answer.trim().toLowerCase();
+    const rel = text.match(/^(\d+)\s*([hd])$/);
+    if (rel) {
+        const hours = Number(rel[1]) * (rel[2] === 'd' ? 24 : 1);
+        return new Date(now.getTime() + hours * 3600 * 1000);
+    }
+    if (text === 'tomorrow') {
+        const t = new Date(now);
+        t.setDate(t.getDate() + 1);
+        t.setHours(9, 0, 0, 0);
+        return t;
+    }
+    const abs = text.match(/^(\d{4})-(\d{2})-(\d{2})[ t](\d{1,2}):(\d{2})$/);
+    if (abs) {
+        const [, y, mo, d, h, mi] = abs.map(Number);
+        return new Date(y, mo - 1, d, h, mi);
+    }
+    return null;
+}
+
+// Snooze the selected message: it leaves the list now and comes back to
+// the Inbox, unread, at the chosen time.
+async function snoozeSelected() {
+    const id = getSelectedEmailId();
+    const email = state.emails.find(e => e.id === id);
+    if (!email) return;
+    const answer = prompt('Snooze until (3h, 2d, tomorrow, or YYYY-MM-DD HH:MM):', 'tomorrow');
+    if (answer === null) return;
+    const wakeAt = parseSnoozeTime(answer);
+    if (!wakeAt || wakeAt <= new Date()) {
+        showStatus(`Can't snooze until '${answer}'`, 'error');
+        return;
+    }
+    const index = state.emails.indexOf(email);
+    removeEmailFromList(id);
+    if (state.view === 'detail') goToNextEmail();
+    try {
+        await api('POST', `/emails/${id}/snooze`, { wake_at: wakeAt.toISOString() });
+        showStatus(`Snoozed until ${wakeAt.toLocaleString()}`, 'success');
+        loadSplitCounts();
+    } catch (err) {
+        state.emails.splice(index, 0, email);
+        invalidateSplitListCache();
+        renderEmailList();
+        adjustSplitCounts(+1);
+        showStatus(`Snooze failed: ${err.message}`, 'error');
+    }
+}
+
+// Star the selected message in one of the account's flag colors; an empty
+// answer leaves a plain star.
+async function flagColorSelected() {
+    const id = getSelectedEmailId();
+    const email = state.emails.find(e => e.id === id);
+    if (!email) return;
+    let colors;
+    try {
+        colors = (await api('GET', '/flag-colors')).colors;
+    } catch (err) {
+        showStatus('Failed to load flag colors', 'error');
+        return;
+    }
+    if (!colors.length) {
+        showStatus('This account has no flag colors', 'error');
+        return;
+    }
+    const answer = prompt(`Star color (${colors.join(', ')}), or empty for a plain star:`, email.flagColor || '');
+    if (answer === null) return;
+    const color = answer.trim().toLowerCase() || null;
+    if (color && !colors.includes(color)) {
+        showStatus(`Unknown color '${answer}'`, 'error');
+        return;
+    }
+    const before = { isFlagged: email.isFlagged, flagColor: email.flagColor };
+    email.isFlagged = true;
+    email.flagColor = color;
+    renderEmailList();
+    try {
+        await api('POST', `/emails/${id}/flag-color`, { color });
+    } catch (err) {
+        Object.assign(email, before);
+        renderEmailList();
+        showStatus('Failed to set flag color', 'error');
+    }
+}
+
+// Mark every message in the selected conversation read, including members
+// outside this mailbox; loaded members flip optimistically.
+async function markThreadRead() {
+    const id = getSelectedEmailId();
+    const email = state.emails.find(e => e.id === id);
+    if (!email) return;
+    if (!email.threadId) {
+        if (email.isUnread) toggleUnreadSelected();
+        return;
+    }
+    const flipped = state.emails.filter(e => e.threadId === email.threadId && e.isUnread);
+    flipped.forEach(e => { e.isUnread = false; });
+    renderEmailList();
+    try {
+        await api('POST', `/threads/${encodeURIComponent(email.threadId)}/mark-read`);
+        loadMailboxes();
+    } catch (err) {
+        flipped.forEach(e => { e.isUnread = true; });
+        renderEmailList();
+        showStatus(`Mark thread read failed: ${err.message}`, 'error');
+    }
+}
+
+// Mute the selected conversation: the server archives what it has in the
+// Inbox now and keeps archiving replies as they arrive.
+async function muteThread() {
+    const id = getSelectedEmailId();
+    const email = state.emails.find(e => e.id === id);
+    if (!email) return;
+    if (!email.threadId) {
+        showStatus('This message has no conversation to mute', 'error');
+        return;
+    }
+    try {
+        const result = await api('POST', `/threads/${encodeURIComponent(email.threadId)}/mute`);
+        showStatus(`Thread muted, ${result.archived} archived`, 'success');
+        loadEmails();
+        loadMailboxes();
+    } catch (err) {
+        showStatus(`Mute failed: ${err.message}`, 'error');
+    }
+}
+
+// The selected conversation as one PDF, laid out server-side, for
+// archiving. A plain navigation so the browser saves it as a download.
+function exportThreadPdf() {
+    const id = getSelectedEmailId();
+    const email = state.emails.find(e => e.id === id);
+    if (!email) return;
+    if (!email.threadId) {
+        showStatus('This message has no conversation to export', 'error');
+        return;
+    }
+    const account = state.currentAccount?.id;
+    const query = account ? `?account=${encodeURIComponent(account)}` : '';
+    window.location.href = `/api/threads/${encodeURIComponent(email.threadId)}/pdf${query}`;
+}
+
+// Zero the current mailbox. The server pages through every unread id, not
+// just the loaded ones; the loaded list flips optimistically.
+async function markMailboxRead() {
+    const mailbox = state.currentMailbox;
+    if (!mailbox) return;
+    const flipped = state.emails.filter(e => e.isUnread);
+    flipped.forEach(e => { e.isUnread = false; });
+    renderEmailList();
+    showStatus(`Marking ${mailbox.name} read...`, 'info');
+    try {
+        const result = await api('POST', `/mailboxes/${encodeURIComponent(mailbox.id)}/mark-all-read`);
+        showStatus(`Marked ${result.updated} read in ${mailbox.name}`, 'success');
+        loadMailboxes();
+    } catch (err) {
+        flipped.forEach(e => { e.isUnread = true; });
+        renderEmailList();
+        showStatus(`Mark all read failed: ${err.message}`, 'error');
+    }
+}
+
+// Two calls: the first only returns a count and a confirm token, the
+// second (after the user agrees) spends the token and purges.
+async function emptyMailbox() {
+    const mailbox = state.currentMailbox;
+    if (!mailbox) return;
+    if (mailbox.role !== 'trash' && mailbox.role !== 'junk') {
+        showStatus('Only Trash and Spam can be emptied', 'error');
+        return;
+    }
+    const path = `/mailboxes/${encodeURIComponent(mailbox.id)}/empty`;
+    try {
+        const pending = await api('POST', path);
+        if (!confirm(`Permanently delete ${pending.count} messages in ${mailbox.name}? This cannot be undone.`)) return;
+        const result = await api('POST', `${path}?confirm=${encodeURIComponent(pending.confirm)}`);
+        showStatus(`Deleted ${result.destroyed} from ${mailbox.name}`, 'success');
+        loadEmails();
+        loadMailboxes();
+    } catch (err) {
+        showStatus(`Empty ${mailbox.name} failed: ${err.message}`, 'error');
+    }
+}
+
+// Walk the server's rarely-opened newsletter senders one confirm at a
+// time; each yes goes through the same unsubscribe-and-archive route as
+// the U key, using the sender's latest message.
+async function reviewUnsubscribeSuggestions() {
+    let suggestions;
+    try {
+        suggestions = (await api('GET', '/suggestions/unsubscribe')).suggestions;
+    } catch (err) {
+        showStatus('Failed to load suggestions: ' + err.message, 'error');
+        return;
+    }
+    if (!suggestions.length) {
+        showStatus('No unsubscribe suggestions', 'info');
+        return;
+    }
+    let archived = 0;
+    for (const s of suggestions) {
+        const label = s.name ? `${s.name} <${s.sender}>` : s.sender;
+        if (!confirm(`${label}: opened ${s.opened} of ${s.received}. Unsubscribe and archive all?`)) continue;
+        try {
+            const result = await api('POST', `/emails/${encodeURIComponent(s.emailId)}/unsubscribe-and-archive-all`);
+            archived += result.archived ?? 0;
+        } catch (err) {
+            showStatus(`Failed for ${s.sender}: ${err.message}`, 'error');
+            return;
+        }
+    }
+    if (archived) {
+        showStatus(`Archived ${archived} emails`, 'success');
+        loadEmails();
+    }
+}
+
 async function unsubscribeAndArchiveAll() {
     const id = getSelectedEmailId();
     if (!id) return;
@@ -3666,11 +4222,19 @@ async function unsubscribeAndArchiveAll() {
     try {
         const result = await api('POST', `/emails/${id}/unsubscribe-and-archive-all`);
 
+        const archivedMsg = `Archived ${result.archivedCount} emails from ${result.sender}.`;
         if (result.unsubscribeUrl) {
+            // Only a web page, or the one-click POST failed: finish in the browser.
             window.open(result.unsubscribeUrl, '_blank');
-            showStatus(`Archived ${result.archivedCount} emails from ${result.sender}. Unsubscribe page opened.`, 'success');
+            showStatus(`${archivedMsg} Unsubscribe page opened.`, result.unsubscribeError ? 'warning' : 'success');
+        } else if (result.unsubscribeError) {
+            showStatus(`${archivedMsg} Unsubscribe failed: ${result.unsubscribeError}`, 'warning');
+        } else if (result.unsubscribeMethod === 'one-click') {
+            showStatus(`${archivedMsg} Unsubscribed.`, 'success');
+        } else if (result.unsubscribeMethod === 'mailto') {
+            showStatus(`${archivedMsg} Unsubscribe email sent.`, 'success');
         } else {
-            showStatus(`Archived ${result.archivedCount} emails from ${result.sender}. No unsubscribe link found.`, 'warning');
+            showStatus(`${archivedMsg} No unsubscribe link found.`, 'warning');
         }
         loadSplitCounts(); // resync with server truth
         maybeRefillEmails();
@@ -3759,6 +4323,7 @@ function startReply(replyAll) {
     };
 
     autoSelectFromAddress(email);
+    applyReplyIdentity(email, replyAll);
 
     const header = `On ${formatDate(email.receivedAt)}, ${from?.name || from?.email} wrote:`;
     renderComposeQuote(header, quotedHtml, quotedText);
@@ -3781,7 +4346,8 @@ function startForward() {
         ? htmlToPlainText(email.htmlBody)
         : (email.textBody || '');
 
-    state.replyContext = { quotedHtml, quotedText };
+    // The server attaches the original's attachments by reference.
+    state.replyContext = { quotedHtml, quotedText, forwardOf: email.id };
 
     const headerLines = `---------- Forwarded message ---------<br>From: ${escapeHtml(from?.name || '')} &lt;${escapeHtml(from?.email)}&gt;<br>Subject: ${escapeHtml(email.subject)}`;
     renderComposeQuote(headerLines, quotedHtml, quotedText);
@@ -4307,6 +4873,28 @@ function autoSelectFromAddress(email) {
     }
 }
 
+// Server-side reply identity (/compose-context): also matches Delivered-To,
+// plus-address/wildcard aliases, shared domains and the identities.json
+// defaults. Refines autoSelectFromAddress's instant guess unless the user
+// has already picked a From or moved on to another compose. A reply-all
+// also shows the server's warning when you were only Bcc'd.
+async function applyReplyIdentity(email, replyAll = false) {
+    const pickFrom = els.composeFrom && state.identities.length > 1;
+    if (!pickFrom && !replyAll) return;
+    const session = state.composeSession;
+    const guess = els.composeFrom?.value;
+    try {
+        const ctx = await api('GET', `/emails/${encodeURIComponent(email.id)}/compose-context`);
+        const addr = ctx.from?.email;
+        if (state.composeSession !== session) return;
+        if (replyAll && ctx.bccWarning) showStatus(ctx.bccWarning, 'warning');
+        if (!pickFrom || els.composeFrom.value !== guess) return;
+        if (addr && state.identities.some(i => i.email === addr)) els.composeFrom.value = addr;
+    } catch (err) {
+        console.warn('Failed to load compose context:', err);
+    }
+}
+
 // Command palette
 
 function openCommandPalette() {
@@ -4316,6 +4904,90 @@ function openCommandPalette() {
     renderCommandPalette();
     els.commandInput.focus();
     setMode('command');
+    loadServerCommands();
+}
+
+// The server registry (/api/commands) decides which commands apply to the
+// focused message — e.g. Unsubscribe only with List-Unsubscribe. Until it
+// answers (or if it fails) the palette shows the built-in list.
+async function loadServerCommands() {
+    const emailId = getSelectedEmailId();
+    const query = emailId ? `?email_id=${encodeURIComponent(emailId)}` : '';
+    try {
+        const data = await api('GET', `/commands${query}`);
+        state.serverCommands = data.commands;
+        applyKeymap(data.commands);
+        if (!els.commandPalette.classList.contains('hidden')) renderCommandPalette();
+    } catch (_) {
+        state.serverCommands = null;
+    }
+}
+
+// Key remaps from keybindings.json arrive with the registry; fetched once
+// at startup so remapped keys work before the palette is ever opened.
+async function loadKeymap() {
+    try {
+        applyKeymap((await api('GET', '/commands')).commands);
+    } catch (_) { /* built-in keys stay */ }
+}
+
+// Only the differences from the built-in keys: a command's new key maps to
+// its id, and its old key to null unless another command took it over.
+function applyKeymap(commands) {
+    const remap = new Map();
+    for (const c of commands) {
+        if (c.key !== c.defaultKey && c.defaultKey) remap.set(c.defaultKey, null);
+    }
+    for (const c of commands) {
+        if (c.key && c.key !== c.defaultKey) remap.set(c.key, c.id);
+    }
+    state.keyRemap = remap;
+}
+
+// A remapped key press: the same command the palette would run, with the
+// detail-view gate the built-in RSVP keys have.
+function runBoundCommand(command, e) {
+    if (command.startsWith('rsvp-') && !(state.view === 'detail' && state.currentEmail?.calendarEvent)) {
+        return;
+    }
+    if (command === 'refresh') showStatus('Refreshing...', 'info');
+    executeCommand(command);
+    e.preventDefault();
+}
+
+// The cheatsheet comes from /api/help (built from the same registry as the
+// palette); the sections in index.html stay up if it can't be fetched.
+async function openHelp() {
+    els.helpOverlay.classList.remove('hidden');
+    try {
+        const data = await api('GET', '/help');
+        renderHelp(data.sections);
+        document.getElementById('help-version').textContent = ` · v${data.version}`;
+    } catch (_) {}
+}
+
+function renderHelp(sections) {
+    const container = document.getElementById('help-sections');
+    container.replaceChildren(...sections.map(section => {
+        const div = document.createElement('div');
+        div.className = 'help-section';
+        const title = document.createElement('h3');
+        title.textContent = section.title;
+        div.appendChild(title);
+        for (const entry of section.entries) {
+            const line = document.createElement('div');
+            line.className = 'shortcut';
+            entry.keys.forEach((key, i) => {
+                if (i > 0) line.appendChild(document.createTextNode(' / '));
+                const kbd = document.createElement('kbd');
+                kbd.textContent = key;
+                line.appendChild(kbd);
+            });
+            line.appendChild(document.createTextNode(` ${entry.description}`));
+            div.appendChild(line);
+        }
+        return div;
+    }));
 }
 
 function closeCommandPalette() {
@@ -4324,23 +4996,11 @@ function closeCommandPalette() {
 }
 
 function getCommands() {
-    const commands = [
-        { name: 'Archive', desc: 'Archive email', shortcut: 'e', action: 'archive' },
-        { name: 'Trash', desc: 'Move to trash', shortcut: '#', action: 'trash' },
-        { name: 'Reply', desc: 'Reply to sender', shortcut: 'r', action: 'reply' },
-        { name: 'Reply All', desc: 'Reply to all', shortcut: 'a', action: 'reply-all' },
-        { name: 'Compose', desc: 'New email', shortcut: 'c', action: 'compose' },
-        { name: 'Forward', desc: 'Forward email', shortcut: 'f', action: 'forward' },
-        { name: 'Mark Unread', desc: 'Toggle unread', shortcut: 'u', action: 'toggle-unread' },
-        { name: 'Star', desc: 'Toggle star', shortcut: 's', action: 'toggle-flag' },
-        { name: 'Refresh', desc: 'Reload emails', shortcut: 'R', action: 'refresh' },
-        { name: 'Go to Inbox', desc: 'Switch to inbox', shortcut: '', action: 'inbox' },
-        { name: 'Go to Archive', desc: 'Switch to archive', shortcut: '', action: 'go-archive' },
-        { name: 'Go to Trash', desc: 'Switch to trash', shortcut: '', action: 'go-trash' },
-        { name: 'New Split', desc: 'Create split inbox', shortcut: '', action: 'new-split' },
-        { name: 'Add Account', desc: 'Connect a new mailbox', shortcut: '', action: 'add-account' },
-        { name: 'Help', desc: 'Show shortcuts', shortcut: '?', action: 'help' },
-    ];
+    const commands = state.serverCommands
+        ? state.serverCommands
+            .filter(c => c.applicable)
+            .map(c => ({ name: c.name, desc: c.description, shortcut: c.key || '', action: c.id }))
+        : builtinCommands();
 
     // Add delete commands for each existing split
     state.splits.forEach(split => {
@@ -4366,6 +5026,37 @@ function getCommands() {
     return commands;
 }
 
+// Fallback for when /api/commands hasn't answered (the server registry in
+// src/commands.rs is the source of truth).
+function builtinCommands() {
+    return [
+        { name: 'Archive', desc: 'Archive email', shortcut: 'e', action: 'archive' },
+        { name: 'Trash', desc: 'Move to trash', shortcut: '#', action: 'trash' },
+        { name: 'Reply', desc: 'Reply to sender', shortcut: 'r', action: 'reply' },
+        { name: 'Reply All', desc: 'Reply to all', shortcut: 'a', action: 'reply-all' },
+        { name: 'Compose', desc: 'New email', shortcut: 'c', action: 'compose' },
+        { name: 'Forward', desc: 'Forward email', shortcut: 'f', action: 'forward' },
+        { name: 'Mark Unread', desc: 'Toggle unread', shortcut: 'u', action: 'toggle-unread' },
+        { name: 'Star', desc: 'Toggle star', shortcut: 's', action: 'toggle-flag' },
+        { name: 'Snooze', desc: 'Hide until later, then return unread', shortcut: '', action: 'snooze' },
+        { name: 'Star Color', desc: 'Star in a color (Fastmail)', shortcut: '', action: 'flag-color' },
+        { name: 'Mark Thread Read', desc: 'Mark the whole conversation read', shortcut: '', action: 'mark-thread-read' },
+        { name: 'Mute Thread', desc: 'Archive the conversation and any later replies', shortcut: '', action: 'mute-thread' },
+        { name: 'Export Thread PDF', desc: 'Download the whole conversation as a PDF', shortcut: '', action: 'export-thread-pdf' },
+        { name: 'Mark All Read', desc: 'Mark every message in this mailbox read', shortcut: 'M', action: 'mark-all-read' },
+        { name: 'Empty Mailbox', desc: 'Permanently delete everything in Trash or Spam', shortcut: '', action: 'empty-mailbox' },
+        { name: 'Unsubscribe Suggestions', desc: 'Review newsletters you rarely open', shortcut: '', action: 'unsubscribe-suggestions' },
+        { name: 'Refresh', desc: 'Reload emails', shortcut: 'R', action: 'refresh' },
+        { name: 'Go to Inbox', desc: 'Switch to inbox', shortcut: '', action: 'inbox' },
+        { name: 'Go to Archive', desc: 'Switch to archive', shortcut: '', action: 'go-archive' },
+        { name: 'Go to Trash', desc: 'Switch to trash', shortcut: '', action: 'go-trash' },
+        { name: 'New Split', desc: 'Create split inbox', shortcut: '', action: 'new-split' },
+        { name: 'Add Account', desc: 'Connect a new mailbox', shortcut: '', action: 'add-account' },
+        { name: 'Download Diagnostics', desc: 'Save a redacted bundle to attach to a bug report', shortcut: '', action: 'download-diagnostics' },
+        { name: 'Help', desc: 'Show shortcuts', shortcut: '?', action: 'help' },
+    ];
+}
+
 function executeCommand(action) {
     switch (action) {
         case 'archive': actionSelected('archive'); break;
@@ -4376,7 +5067,21 @@ function executeCommand(action) {
         case 'forward': startForward(); break;
         case 'toggle-unread': toggleUnreadSelected(); break;
         case 'toggle-flag': toggleFlagSelected(); break;
+        case 'flag-color': flagColorSelected(); break;
+        case 'snooze': snoozeSelected(); break;
+        case 'mark-thread-read': markThreadRead(); break;
+        case 'mute-thread': muteThread(); break;
+        case 'export-thread-pdf': exportThreadPdf(); break;
+        case 'mark-all-read': markMailboxRead(); break;
         case 'refresh': loadEmails(); break;
+        case 'unsubscribe': unsubscribeAndArchiveAll(); break;
+        case 'empty-mailbox': emptyMailbox(); break;
+        case 'unsubscribe-suggestions': reviewUnsubscribeSuggestions(); break;
+        case 'rsvp-accept': rsvpToEvent('ACCEPTED'); break;
+        case 'rsvp-decline': rsvpToEvent('DECLINED'); break;
+        case 'rsvp-tentative': rsvpToEvent('TENTATIVE'); break;
+        case 'undo': performUndo(); break;
+        case 'search': openSearch(); break;
         case 'inbox': {
             const inbox = state.mailboxes.find(m => m.role === 'inbox');
             if (inbox) selectMailbox(inbox);
@@ -4393,7 +5098,7 @@ function executeCommand(action) {
             break;
         }
         case 'help':
-            els.helpOverlay.classList.remove('hidden');
+            openHelp();
             break;
         case 'new-split':
             openSplitModal();
@@ -4402,6 +5107,9 @@ function executeCommand(action) {
             openSettings();
             openWizard();
             break;
+        case 'download-diagnostics':
+            window.location.href = '/api/diagnostics';
+            break;
         default:
             // Handle dynamic delete-split commands
             if (action.startsWith('delete-split:')) {
@@ -4455,8 +5163,15 @@ function closeSearch() {
     setMode('normal');
 }
 
+// OR, NOT, -term and parentheses: the server parses these like Gmail, so
+// such input is sent as typed instead of becoming one quoted phrase.
+function hasBooleanSyntax(raw) {
+    return /(^|\s)(-\S|NOT\s|\()|\sOR\s/.test(raw);
+}
+
 function getSearchQuery() {
     return state.searchTokens.map(t => {
+        if (t.type === 'text' && hasBooleanSyntax(t.value)) return t.value;
         const sanitized = t.value.replace(/"/g, '');
         if (!sanitized) return '';
         if (t.type === 'text') {
@@ -4473,7 +5188,7 @@ function commitCurrentInput() {
 
     // Check if input matches operator:value pattern
     const colonIdx = raw.indexOf(':');
-    if (colonIdx > 0) {
+    if (colonIdx > 0 && !hasBooleanSyntax(raw)) {
         const prefix = raw.substring(0, colonIdx).toLowerCase();
         const value = raw.substring(colonIdx + 1);
         const rawLower = raw.toLowerCase();
@@ -4567,37 +5282,86 @@ function openSplitModal() {
     els.splitName.value = '';
     els.splitFilterType.value = 'from';
     els.splitPattern.value = '';
+    els.splitPatternKind.value = defaultPatternKind('from');
+    // Same as the server's default for a glob saved without `match`.
+    els.splitGlobMatch.value = 'contains';
+    fillSplitActions();
+    els.splitPreview.classList.add('hidden');
+    els.splitPreview.innerHTML = '';
     updateSplitModalFields();
     els.splitModal.classList.remove('hidden');
     els.splitName.focus();
     setMode('insert');
 }
 
+// Fixed actions plus "Move to" for each of the account's own mailboxes.
+function fillSplitActions() {
+    els.splitAction.querySelectorAll('option[data-move]').forEach(o => o.remove());
+    for (const mailbox of state.mailboxes.filter(m => !m.role)) {
+        const option = document.createElement('option');
+        option.value = `move:${mailbox.id}`;
+        option.dataset.move = '1';
+        option.textContent = `Move to ${mailbox.name}`;
+        els.splitAction.appendChild(option);
+    }
+    els.splitAction.value = '';
+}
+
+function splitActionFromModal() {
+    const value = els.splitAction.value;
+    if (!value) return undefined;
+    if (value.startsWith('move:')) return { type: 'move', mailbox_id: value.slice(5) };
+    return { type: value };
+}
+
 function closeSplitModal() {
     els.splitModal.classList.add('hidden');
     setMode('normal');
 }
 
+// Matches the server's default for filters saved without a pattern_kind.
+function defaultPatternKind(filterType) {
+    return filterType === 'subject' ? 'regex' : 'glob';
+}
+
 function updateSplitModalFields() {
     const filterType = els.splitFilterType.value;
+    const kind = els.splitPatternKind.value;
     const isCalendar = filterType === 'calendar';
+    const anywhere = els.splitGlobMatch.value === 'contains';
 
     // hide pattern field for calendar type (no pattern needed)
     els.splitPatternField.style.display = isCalendar ? 'none' : 'block';
+    // `match` only applies to globs
+    els.splitGlobMatch.style.display = kind === 'glob' ? '' : 'none';
 
     // update hint text
     if (isCalendar) {
         els.splitHint.textContent = 'Matches all emails with iCalendar (ICS) attachments.';
+    } else if (kind === 'regex') {
+        els.splitHint.textContent = filterType === 'subject'
+            ? 'Use regex pattern. e.g., newsletter|digest'
+            : 'Use regex pattern. e.g., ^noreply@';
+    } else if (kind === 'substring') {
+        els.splitHint.textContent = 'Matches anywhere in the text. e.g., github.com';
+    } else if (anywhere) {
+        els.splitHint.textContent = filterType === 'subject'
+            ? 'Matches anywhere; use * ? [a-z] {a,b}. e.g., invoice #[0-9]*'
+            : 'Matches anywhere; use * ? [a-z] {a,b}. e.g., {gmail,googlemail}.com';
     } else if (filterType === 'from') {
         els.splitHint.textContent = 'Use * as wildcard. e.g., *@calendar.google.com';
     } else if (filterType === 'to') {
         els.splitHint.textContent = 'Use * as wildcard. e.g., *@aristoi.ai';
+    } else if (filterType === 'keyword') {
+        els.splitHint.textContent = 'Use * as wildcard. e.g., client-*';
     } else {
-        els.splitHint.textContent = 'Use regex pattern. e.g., newsletter|digest';
+        els.splitHint.textContent = 'Use * as wildcard. e.g., Invoice *';
     }
 }
 
-async function saveSplit() {
+// The split described by the modal's fields, or null (with a status
+// message) when they're incomplete. Preview doesn't need a name yet.
+function splitFromModal({ requireName = true } = {}) {
     const name = els.splitName.value.trim();
     const filterType = els.splitFilterType.value;
     const pattern = els.splitPattern.value.trim();
@@ -4605,37 +5369,80 @@ async function saveSplit() {
 
     if (!state.currentAccount?.id) {
         showStatus('Select an account before creating a split', 'error');
-        return;
+        return null;
     }
 
-    if (!name) {
+    if (requireName && !name) {
         showStatus('Name is required', 'error');
-        return;
+        return null;
     }
 
     if (!isCalendar && !pattern) {
         showStatus('Pattern is required', 'error');
-        return;
+        return null;
     }
 
     // Generate ID from name (lowercase, no spaces)
     const id = name.toLowerCase().replace(/\s+/g, '-').replace(/[^a-z0-9-]/g, '');
 
     // Build filter - calendar type doesn't need a pattern
+    const patternKind = els.splitPatternKind.value;
     const filter = isCalendar
         ? { type: filterType, pattern: 'true' }  // dummy pattern, not used
-        : { type: filterType, pattern };
+        : { type: filterType, pattern, pattern_kind: patternKind };
+    if (patternKind === 'glob' && !isCalendar) {
+        filter.match = els.splitGlobMatch.value;
+    }
+
+    return {
+        id,
+        name,
+        filters: [filter],
+        match_mode: 'any',
+        // New splits belong to the account being viewed; hand-edit
+        // splits.json to make one global.
+        account: state.currentAccount?.id,
+        action: splitActionFromModal(),
+    };
+}
 
+// Dry-run the modal's filter against the inbox (POST /splits/preview) so
+// the pattern can be tuned before the split exists.
+async function previewSplit() {
+    const split = splitFromModal({ requireName: false });
+    if (!split) return;
+    // The server needs an id/name to parse a split; neither affects matching.
+    split.id ||= 'preview';
+    split.name ||= 'Preview';
+    els.splitPreview.classList.remove('hidden');
+    els.splitPreview.innerHTML = '<div class="split-preview-summary">Searching…</div>';
     try {
-        await api('POST', '/splits', {
-            id,
-            name,
-            filters: [filter],
-            match_mode: 'any',
-            // New splits belong to the account being viewed; hand-edit
-            // splits.json to make one global.
-            account: state.currentAccount?.id,
-        });
+        const result = await api('POST', '/splits/preview', split);
+        const approx = result.sampled < result.mailboxTotal ? '~' : '';
+        const summary = `${approx}${result.estimatedCount} matching `
+            + `(${result.matched} of the newest ${result.sampled})`;
+        const rows = result.emails.map(e => {
+            const from = e.displayName || e.from?.[0]?.name || e.from?.[0]?.email || '';
+            return `<div class="split-preview-row">`
+                + `<span class="split-preview-from">${escapeHtml(from)}</span>`
+                + `<span class="split-preview-subject">${escapeHtml(e.subject || '(no subject)')}</span>`
+                + `</div>`;
+        }).join('');
+        els.splitPreview.innerHTML =
+            `<div class="split-preview-summary">${escapeHtml(summary)}</div>${rows}`;
+    } catch (err) {
+        els.splitPreview.innerHTML =
+            `<div class="split-preview-summary">Preview failed: ${escapeHtml(err.message)}</div>`;
+    }
+}
+
+async function saveSplit() {
+    const split = splitFromModal();
+    if (!split) return;
+    const name = split.name;
+
+    try {
+        await api('POST', '/splits', split);
 
         showStatus(`Split "${name}" created`, 'success');
         closeSplitModal();
@@ -4685,11 +5492,71 @@ function pushUndo(action, emailId, emailData, insertIndex) {
     }, 5000);
 }
 
+// A send held for the undo window: Undo (z) calls it back while the
+// toast is up, and the message reopens from Drafts.
+function pushSendUndo(result) {
+    const seconds = Math.max(1, Math.round((new Date(result.sendAt) - Date.now()) / 1000));
+    state.undoStack.push({ action: 'sent', token: result.token, draftId: result.draftId, timestamp: Date.now() });
+    els.undoMessage.textContent = `Sending in ${seconds}s`;
+    els.undoToast.classList.remove('hidden');
+    setTimeout(() => {
+        els.undoToast.classList.add('hidden');
+        state.undoStack = state.undoStack.filter(i => i.token !== result.token);
+    }, seconds * 1000);
+    watchSend(result, makeApi(apiAccount('/emails/send')));
+}
+
+// Once the undo window closes, ask how the held send ended: the POST
+// answered long before, so this is where a failure surfaces. Bound to
+// the sending account, not whichever one is open by then.
+async function watchSend(result, sendApi) {
+    const path = `/emails/send/${encodeURIComponent(result.token)}`;
+    await new Promise(r => setTimeout(r, Math.max(0, new Date(result.sendAt) - Date.now()) + 1000));
+    for (let attempt = 0; attempt < 30; attempt++) {
+        let status;
+        try {
+            status = await sendApi('GET', path);
+        } catch (err) {
+            if (err.status === 404) return;
+            status = null;
+        }
+        if (status?.status === 'sent') {
+            showStatus('Sent!', 'success');
+            return;
+        }
+        if (status?.status === 'failed') {
+            showStatus(`Send failed: ${status.error} — the message is in Drafts`, 'error');
+            return;
+        }
+        if (status?.status === 'cancelled') return;
+        await new Promise(r => setTimeout(r, 2000));
+    }
+    showStatus('Could not confirm the send — check Sent and Drafts', 'error');
+}
+
+async function cancelSend(item) {
+    try {
+        const { draftId } = await api('POST', `/emails/send/${encodeURIComponent(item.token)}/cancel`);
+        if (draftId) {
+            showStatus('Send cancelled — back in Drafts', 'success');
+            openDraftInCompose(draftId);
+        } else {
+            showStatus('Send cancelled', 'success');
+        }
+    } catch (err) {
+        showStatus(err.status === 404 ? 'Too late — already sent' : 'Cancel failed: ' + err.message, 'error');
+    }
+}
+
 async function performUndo() {
     const item = state.undoStack.pop();
     if (!item) return;
 
     els.undoToast.classList.add('hidden');
+    if (item.action === 'sent') {
+        await cancelSend(item);
+        return;
+    }
     showStatus('Undone', 'success');
 
     // Optimistic: re-insert the email into the list immediately. insertIndex is
@@ -4932,15 +5799,20 @@ function linkifyText(text) {
 function renderAttachments(attachments, emailId) {
     els.attachments.classList.remove('hidden');
     const downloadAllBtn = attachments.length > 1
-        ? `<a class="attachments-download-all" onclick="downloadAllAttachments(event)">Download All</a>`
+        ? `<a class="attachments-download-all" data-zip-url="/api/emails/${emailId}/attachments.zip" onclick="downloadAllAttachments(event)">Download All</a>`
         : '';
     const header = `<div class="attachments-header"><span>📎 Attachments (${attachments.length})</span>${downloadAllBtn}</div>`;
     const items = attachments.map(att => {
-        const icon = getFileIcon(att.mime_type, att.name);
+        // Prefer the server's sniffed type (known once downloaded) over a
+        // sender's generic declaration.
+        const icon = getFileIcon(att.sniffed_mime_type || att.mime_type, att.name);
         const size = formatFileSize(att.size);
         const url = `/api/emails/${emailId}/attachments/${encodeURIComponent(att.blob_id)}/${encodeURIComponent(att.name)}`;
+        const title = att.mime_mismatch
+            ? ` title="Sent as ${escapeHtml(att.mime_type)}, actually ${escapeHtml(att.sniffed_mime_type)}"`
+            : '';
         return `
-            <a class="attachment-item" href="${url}" download="${escapeHtml(att.name)}">
+            <a class="attachment-item" href="${url}" download="${escapeHtml(att.name)}"${title}>
                 <span class="attachment-icon">${icon}</span>
                 <span class="attachment-name">${escapeHtml(att.name)}</span>
                 <span class="attachment-size">${size}</span>
@@ -4951,12 +5823,11 @@ function renderAttachments(attachments, emailId) {
     els.attachmentsList.innerHTML = header + items;
 }
 
+// One zip from the server, with repeated names suffixed, instead of a
+// click per link where same-named files overwrote each other.
 function downloadAllAttachments(e) {
     e.preventDefault();
-    const links = els.attachmentsList.querySelectorAll('.attachment-item');
-    links.forEach((a, i) => {
-        setTimeout(() => a.click(), i * 200);
-    });
+    window.location.href = e.currentTarget.dataset.zipUrl;
 }
 
 function formatFileSize(bytes) {
@@ -4990,7 +5861,7 @@ function renderCalendarCard(event) {
     card.classList.toggle('cancelled', cancelled);
 
     els.calTitle.textContent = event.summary || 'Calendar Event';
-    els.calDatetime.innerHTML = formatEventTimeMultiTz(event.dtstart, event.dtend);
+    els.calDatetime.innerHTML = formatEventTimeMultiTz(event.dtstart, event.dtend, event.allDay);
     els.calLocation.textContent = event.location || '';
     els.calLocation.style.display = event.location ? 'block' : 'none';
 
@@ -5051,6 +5922,11 @@ function renderCalendarCard(event) {
         els.rsvpAccept.classList.toggle('active', userStatus === 'ACCEPTED');
         els.rsvpMaybe.classList.toggle('active', userStatus === 'TENTATIVE');
         els.rsvpDecline.classList.toggle('active', userStatus === 'DECLINED');
+        const icsLink = document.getElementById('cal-download-ics');
+        if (icsLink && state.currentEmail) {
+            icsLink.href = `/api/emails/${encodeURIComponent(state.currentEmail.id)}/event.ics`
+                + `?account=${encodeURIComponent(state.currentAccount?.id || '')}`;
+        }
     }
 
     // Show "You responded" label
@@ -5059,11 +5935,43 @@ function renderCalendarCard(event) {
         if (userStatus && userStatus !== 'NEEDS-ACTION') {
             const label = { ACCEPTED: 'Accepted', TENTATIVE: 'Maybe', DECLINED: 'Declined' }[userStatus];
             statusLabel.textContent = `You responded ${label}`;
+            // Answers recorded locally know when they were sent.
+            const at = event.myResponse?.respondedAt;
+            statusLabel.title = at ? `Sent ${new Date(at).toLocaleString()}` : '';
             statusLabel.classList.remove('hidden');
         } else {
             statusLabel.classList.add('hidden');
         }
     }
+
+    // What a revised invite changed vs. the stored copy ("Time moved from
+    // 3pm to 4pm"). Server-computed; empty when there's nothing to diff.
+    let changesEl = els.calendarEvent.querySelector('.cal-changes');
+    const changes = cancelled ? [] : (event.changes || []);
+    if (changes.length > 0) {
+        if (!changesEl) {
+            changesEl = document.createElement('ul');
+            changesEl.className = 'cal-changes';
+            (updBanner || card.querySelector('.cal-header')).after(changesEl);
+        }
+        changesEl.innerHTML = changes.map(c => `<li>${escapeHtml(c)}</li>`).join('');
+    } else if (changesEl) {
+        changesEl.remove();
+    }
+
+    // Sender and organizer (or replying attendee) on different domains:
+    // a possibly forged invite. Server-computed; shown even when cancelled.
+    let spoofBanner = els.calendarEvent.querySelector('.cal-spoof');
+    if (event.organizerMismatch) {
+        if (!spoofBanner) {
+            spoofBanner = document.createElement('div');
+            spoofBanner.className = 'cal-spoof';
+            card.querySelector('.cal-header').after(spoofBanner);
+        }
+        spoofBanner.textContent = `⚠ ${event.organizerMismatch}`;
+    } else if (spoofBanner) {
+        spoofBanner.remove();
+    }
 }
 
 function getUserRsvpStatus(event) {
@@ -5118,7 +6026,24 @@ function sameDayInTz(a, b, timeZone) {
     return fmt.format(a) === fmt.format(b);
 }
 
-function formatEventTimeMultiTz(dtstart, dtend) {
+// All-day events carry floating dates (UTC midnight, exclusive end), so
+// they render as plain dates — shifting them into a display zone would
+// land them on the previous day west of UTC.
+function formatAllDayRange(dtstart, dtend) {
+    const opts = { timeZone: 'UTC', weekday: 'short', month: 'short', day: 'numeric' };
+    const start = new Date(dtstart);
+    let result = start.toLocaleDateString(undefined, opts);
+    if (dtend) {
+        const last = new Date(new Date(dtend).getTime() - 86400000);
+        if (last > start) result += ' – ' + last.toLocaleDateString(undefined, opts);
+    }
+    return result + ' (all day)';
+}
+
+function formatEventTimeMultiTz(dtstart, dtend, allDay) {
+    if (allDay) {
+        return `<div class="event-time primary">${escapeHtml(formatAllDayRange(dtstart, dtend))}</div>`;
+    }
     const zones = (state.timezone && state.timezone.display && state.timezone.display.length)
         ? state.timezone.display
         : [undefined];  // fall back to browser local
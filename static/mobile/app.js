@@ -276,6 +276,11 @@ function attachmentUrl(emailId, att) {
         + '?account=' + encodeURIComponent(state.currentAccount.id);
 }
 
+function attachmentsZipUrl(emailId) {
+    return '/api/emails/' + encodeURIComponent(emailId) + '/attachments.zip'
+        + '?account=' + encodeURIComponent(state.currentAccount.id);
+}
+
 // ============================================================================
 // Email body cache (LRU, max 50)
 // ============================================================================
@@ -407,7 +412,9 @@ async function loadMailboxes(acct, signal, preferredRole = 'inbox') {
     // tick as abort() can still resolve) — don't clobber the new account's
     // freshly-reset state with data that belongs to the one we left.
     if (state.currentAccount?.id !== acct) return;
-    state.mailboxes = mailboxes;
+    // Shared accounts' mailboxes (those with an `account` key) are
+    // desktop-only for now.
+    state.mailboxes = mailboxes.filter(m => !m.account);
     // preferredRole defaults to inbox (the account-switch path, unchanged);
     // restore passes the saved role so a resumed session lands back on the
     // mailbox it left, falling back to inbox if that role is gone.
@@ -568,18 +575,23 @@ async function loadSplits(acct) {
 }
 
 let splitCountsController = null;
+let splitCountsRepoll = null;
+// Mirrors desktop: re-poll while the server refreshes cached counts.
+const SPLIT_COUNTS_REPOLL_MS = 5000;
 
 async function loadSplitCounts() {
     if (state.currentMailbox?.role !== 'inbox' || state.splits.length === 0) return;
     splitCountsController?.abort();
+    clearTimeout(splitCountsRepoll);
     splitCountsController = new AbortController();
     const mailboxId = state.currentMailbox.id;
     try {
         const path = '/split-counts?mailbox_id=' + encodeURIComponent(mailboxId);
-        const counts = await state.api('GET', path, null, splitCountsController.signal);
+        const data = await state.api('GET', path, null, splitCountsController.signal);
         if (state.currentMailbox?.id !== mailboxId) return;
-        state.splitCounts = counts;
+        state.splitCounts = data.counts;
         renderSplitTabs();
+        if (data.refreshing) splitCountsRepoll = setTimeout(loadSplitCounts, SPLIT_COUNTS_REPOLL_MS);
     } catch (err) {
         // Graceful degradation: tabs render without badges, no toast spam
         // for a background count refresh (an aborted superseded request).
@@ -737,7 +749,7 @@ function renderEmailRows(emails, startGroup) {
     let lastGroup = startGroup;
     const html = emails.map(email => {
         const from = email.from[0];
-        const fromDisplay = from?.name || from?.email || 'Unknown';
+        const fromDisplay = email.displayName || from?.name || from?.email || 'Unknown';
         const date = formatDate(email.receivedAt);
         const group = getDateGroup(email.receivedAt);
         let divider = '';
@@ -1363,22 +1375,59 @@ function renderEmailDetail(email) {
         bodyEl.classList.remove('html-content');
         bodyEl.innerHTML = '<div style="padding:16px;color:var(--text-muted)">No content</div>';
     }
+    if (email.isTruncated) bodyEl.prepend(truncationNotice(email));
+}
+
+// Same contract as desktop: a cut body part (isTruncated) can be swapped
+// for the whole part, streamed from /body/full. Re-renders the detail view
+// only if the user is still looking at this email.
+function truncationNotice(email) {
+    const notice = document.createElement('div');
+    notice.className = 'truncated-notice';
+    notice.innerHTML = 'Message truncated (' + formatFileSize(email.bodySize || 0) + ' total). '
+        + '<button type="button">Load full message</button>';
+    notice.querySelector('button').addEventListener('click', async () => {
+        notice.textContent = 'Loading full message…';
+        try {
+            const url = '/api/emails/' + encodeURIComponent(email.id) + '/body/full'
+                + '?account=' + encodeURIComponent(state.currentAccount.id);
+            const resp = await fetch(url);
+            if (!resp.ok) throw new Error(await resp.text());
+            const body = await resp.text();
+            if ((resp.headers.get('content-type') || '').startsWith('text/html')) {
+                email.htmlBody = body;
+            } else {
+                email.textBody = body;
+            }
+            email.isTruncated = false;
+            if (state.screen === Screen.DETAIL && state.currentEmailId === email.id) {
+                renderEmailDetail(email);
+            }
+        } catch (err) {
+            notice.remove();
+            showError('Load full message', err);
+        }
+    });
+    return notice;
 }
 
 function renderAttachments(attachments, emailId) {
     // "Download All" only earns its place with 2+ attachments (kata 0g9v) —
     // mirrors desktop's downloadAllAttachments condition exactly.
     const downloadAll = attachments.length > 1
-        ? '<button type="button" class="att-download-all">Download All</button>'
+        ? '<button type="button" class="att-download-all" data-zip-url="' + escapeHtml(attachmentsZipUrl(emailId)) + '">Download All</button>'
         : '';
     const header = '<div class="att-header"><span>Attachments (' + attachments.length + ')</span>' + downloadAll + '</div>';
     const items = attachments.map(att => {
-        const icon = getFileIcon(att.mime_type, att.name);
+        // The server reports the sniffed type once a download has seen
+        // the bytes; it beats a sender's `application/octet-stream`.
+        const mime = att.sniffed_mime_type || att.mime_type;
+        const icon = getFileIcon(mime, att.name);
         const size = formatFileSize(att.size);
         const url = attachmentUrl(emailId, att);
         // Inline preview for images only; tapping it (or the row) opens the
         // full blob in a new tab via the same anchor — no separate viewer.
-        const preview = att.mime_type.startsWith('image/')
+        const preview = mime.startsWith('image/')
             ? '<img class="att-preview" loading="lazy" src="' + escapeHtml(url) + '" alt="">'
             : '';
         return '<a class="att-item" href="' + escapeHtml(url) + '" target="_blank" rel="noopener noreferrer">' +
@@ -1393,14 +1442,11 @@ function renderAttachments(attachments, emailId) {
     return header + items;
 }
 
-// Sequential anchor clicks with a small stagger — mirrors desktop's
-// downloadAllAttachments. Each anchor targets _blank, so this opens one new
-// tab per attachment (a popup blocker may cap how many actually go through).
-function downloadAllAttachments() {
-    const links = document.querySelectorAll('#detail-attachments .att-item');
-    links.forEach((a, i) => {
-        setTimeout(() => a.click(), i * 200);
-    });
+// One zip from the server — mirrors desktop's downloadAllAttachments.
+// Replaces a tab per attachment, which popup blockers capped and which
+// let same-named files overwrite each other.
+function downloadAllAttachments(button) {
+    window.location.href = button.dataset.zipUrl;
 }
 
 function formatDetailDate(isoString) {
@@ -1440,9 +1486,20 @@ function formatRecipients(email) {
 const RSVP_LABELS = { ACCEPTED: 'Accepted', TENTATIVE: 'Maybe', DECLINED: 'Declined' };
 
 // No timezone selector on mobile (unlike desktop's formatEventTimeMultiTz) —
-// always the device's local time.
-function formatEventTimeRange(dtstart, dtend) {
+// always the device's local time. All-day events are floating dates (UTC
+// midnight, exclusive end) and render date-only in UTC so they never slip a day.
+function formatEventTimeRange(dtstart, dtend, allDay) {
     if (!dtstart) return '';
+    if (allDay) {
+        const dayOpts = { timeZone: 'UTC', weekday: 'short', month: 'short', day: 'numeric' };
+        const start = new Date(dtstart);
+        let result = start.toLocaleDateString([], dayOpts);
+        if (dtend) {
+            const last = new Date(new Date(dtend).getTime() - 86400000);
+            if (last > start) result += ' – ' + last.toLocaleDateString([], dayOpts);
+        }
+        return result + ' (all day)';
+    }
     const opts = { weekday: 'short', month: 'short', day: 'numeric', hour: 'numeric', minute: '2-digit' };
     const start = new Date(dtstart);
     let result = start.toLocaleString([], opts);
@@ -1455,6 +1512,15 @@ function formatEventTimeRange(dtstart, dtend) {
     return result;
 }
 
+// Plain link to the open email's event.ics: the browser hands the file to
+// whatever calendar app the phone has, no CalDAV needed.
+function eventIcsLink() {
+    if (!state.currentEmailId) return '';
+    const url = '/api/emails/' + encodeURIComponent(state.currentEmailId)
+        + '/event.ics?account=' + encodeURIComponent(state.currentAccount.id);
+    return '<a class="cal-ics-link" href="' + escapeHtml(url) + '">Add to another calendar</a>';
+}
+
 // Redraws from scratch on every call (like renderAttachments, renderEmailList)
 // rather than mutating persistent DOM nodes the way desktop does. summary/
 // location/organizer are attacker-controlled ICS fields, so every one is
@@ -1471,6 +1537,9 @@ function renderCalendarCard(event) {
     const banner = cancelled
         ? '<div class="cal-cancelled">CANCELLED</div>'
         : (isUpdate ? '<div class="cal-updated">Updated — please respond again</div>' : '');
+    const changes = !cancelled && event.changes && event.changes.length
+        ? '<ul class="cal-changes">' + event.changes.map(c => '<li>' + escapeHtml(c) + '</li>').join('') + '</ul>'
+        : '';
     const location = event.location
         ? '<div class="cal-location">' + escapeHtml(event.location) + '</div>'
         : '';
@@ -1489,6 +1558,7 @@ function renderCalendarCard(event) {
             + '<button type="button" class="rsvp-btn decline' + (userStatus === 'DECLINED' ? ' active' : '') + '" data-status="DECLINED">Decline</button>'
             + '</div>'
         : '';
+    const icsLink = cancelled ? '' : eventIcsLink();
     const statusLabel = userStatus && userStatus !== 'NEEDS-ACTION' && RSVP_LABELS[userStatus]
         ? '<div class="rsvp-status-label">You responded ' + RSVP_LABELS[userStatus] + '</div>'
         : '';
@@ -1497,8 +1567,9 @@ function renderCalendarCard(event) {
         + '<div class="cal-header"><span class="cal-icon">📅</span>'
         + '<span class="cal-title">' + escapeHtml(event.summary || 'Calendar Event') + '</span></div>'
         + banner
-        + '<div class="cal-datetime">' + escapeHtml(formatEventTimeRange(event.dtstart, event.dtend)) + '</div>'
-        + location + organizer + attendeeCount + actions + statusLabel
+        + changes
+        + '<div class="cal-datetime">' + escapeHtml(formatEventTimeRange(event.dtstart, event.dtend, event.allDay)) + '</div>'
+        + location + organizer + attendeeCount + actions + statusLabel + icsLink
         + '</div>';
 }
 
@@ -1623,6 +1694,27 @@ function autoSelectFromAddress(email) {
     }
 }
 
+// Server-side refinement of the guess above (Delivered-To, aliases,
+// identities.json defaults), plus the Bcc warning on reply-all. Mirrors
+// desktop applyReplyIdentity.
+async function applyReplyIdentity(email, replyAll = false) {
+    const sel = composeEl('compose-from');
+    const pickFrom = sel && state.identities.length > 1;
+    if (!pickFrom && !replyAll) return;
+    const session = state.composeSession;
+    const guess = sel?.value;
+    try {
+        const ctx = await state.api('GET', '/emails/' + encodeURIComponent(email.id) + '/compose-context');
+        const addr = ctx.from?.email;
+        if (state.composeSession !== session) return;
+        if (replyAll && ctx.bccWarning) showToast(ctx.bccWarning);
+        if (!pickFrom || sel.value !== guess) return;
+        if (addr && state.identities.some(i => i.email === addr)) sel.value = addr;
+    } catch (err) {
+        console.warn('Failed to load compose context:', err);
+    }
+}
+
 function htmlToPlainText(html) {
     const doc = new DOMParser().parseFromString(html, 'text/html');
     return doc.body.innerText || '';
@@ -1888,6 +1980,7 @@ function startReply(replyAll) {
     state.replyContext = { inReplyTo: email.id, quotedHtml, quotedText };
 
     autoSelectFromAddress(email);
+    applyReplyIdentity(email, replyAll);
 
     const header = 'On ' + formatDate(email.receivedAt) + ', '
         + escapeHtml(from?.name || from?.email || '') + ' wrote:';
@@ -2104,7 +2197,7 @@ async function doSendComposedEmail() {
     const draftId = state.draftId;
 
     try {
-        await state.api('POST', '/emails/send', {
+        const result = await state.api('POST', '/emails/send', {
             to,
             cc,
             subject,
@@ -2113,6 +2206,7 @@ async function doSendComposedEmail() {
             in_reply_to: inReplyTo,
             from_address: fromAddress,
             attachments: readyAttachments.length ? readyAttachments : undefined,
+            draft_id: draftId || undefined,
         });
         // The user may have browser-backed out of compose while the send was
         // in flight — setScreen already popped the history entry on the way
@@ -2146,11 +2240,19 @@ async function doSendComposedEmail() {
         // where such a mid-send save is still in flight and the tracked id
         // hasn't rotated yet. In the normal still-in-compose case
         // trackedDraftSession === session, so the delete fires.
+        //
+        // A held send (server undo window) owns its draft instead: the
+        // server removes it once the message goes out and keeps it if the
+        // send fails, so it is left alone here and the outcome is watched.
         const reopened = trackedDraftSession !== session && trackedDraftId === draftId;
-        if (!reopened) deleteDraftById(draftId);
+        if (result?.pending) {
+            watchSend(result, state.api);
+        } else if (!reopened) {
+            deleteDraftById(draftId);
+        }
         if (state.composeSession === session) {
             if (state.screen === Screen.COMPOSE) {
-                showToast('Sent', 3000);
+                showToast(result?.pending ? 'Sending…' : 'Sent', 3000);
                 clearComposeFields();
                 history.back();
             } else {
@@ -2169,6 +2271,34 @@ async function doSendComposedEmail() {
     }
 }
 
+// A send the server holds for its undo window has no result when the POST
+// returns; poll its status once the window closes so a failure still
+// surfaces (mobile has no undo, only the outcome). sendApi is the
+// sending account's client, captured before any account switch.
+async function watchSend(result, sendApi) {
+    const path = '/emails/send/' + encodeURIComponent(result.token);
+    await new Promise(r => setTimeout(r, Math.max(0, new Date(result.sendAt) - Date.now()) + 1000));
+    for (let attempt = 0; attempt < 30; attempt++) {
+        let status = null;
+        try {
+            status = await sendApi('GET', path);
+        } catch (err) {
+            if (err.status === 404) return;
+        }
+        if (status?.status === 'sent') {
+            showToast('Sent', 3000);
+            return;
+        }
+        if (status?.status === 'failed') {
+            showToast('Send failed: ' + status.error + ' — the message is in Drafts');
+            return;
+        }
+        if (status?.status === 'cancelled') return;
+        await new Promise(r => setTimeout(r, 2000));
+    }
+    showToast('Could not confirm the send — check Sent and Drafts');
+}
+
 // Cancel: discard immediately when the draft is empty, else surface the
 // inline "Discard draft?" bar (no blocking confirm() — it would freeze the
 // automation harness). Dirty covers every field the user could have filled
@@ -3100,7 +3230,8 @@ document.getElementById('compose-attachments-list').addEventListener('click', ha
 // Detail attachments: delegated so re-renders (a fresh innerHTML per email)
 // never need their own rebind.
 document.getElementById('detail-attachments').addEventListener('click', (e) => {
-    if (e.target.closest('.att-download-all')) downloadAllAttachments();
+    const downloadAll = e.target.closest('.att-download-all');
+    if (downloadAll) downloadAllAttachments(downloadAll);
 });
 
 // Calendar RSVP buttons (kata nhxd, task A10): delegated for the same reason
@@ -3155,6 +3286,24 @@ if ('serviceWorker' in navigator) {
     }
 }
 
+// Server push (/api/events): refresh the split tabs when splits change
+// elsewhere. EventSource reconnects on its own, including after the
+// offline → online transition.
+function subscribeServerEvents() {
+    if (typeof EventSource === 'undefined') return;
+    const events = new EventSource('/api/events');
+    events.addEventListener('splits', async () => {
+        const acct = state.currentAccount?.id;
+        if (!acct) return;
+        await loadSplits(acct);
+        const current = state.currentSplit;
+        if (current && current !== 'all' && !state.splits.some(s => s.id === current)) {
+            selectSplit('all');
+        }
+    });
+}
+
 gestureController.init();
 setupInfiniteScroll();
+subscribeServerEvents();
 init();